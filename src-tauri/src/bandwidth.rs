@@ -0,0 +1,730 @@
+//! Upload/download rate limits and upload slots for chunk transfers.
+//!
+//! Limits are token buckets in bytes per second, one global bucket per
+//! direction plus one per peer. Uploads take tokens after a chunk is read
+//! and sleep off any debt before the response goes out; downloads only ask
+//! for tokens before a chunk request is sent, so the scheduler simply holds
+//! requests back until the bucket refills.
+//!
+//! Upload slots cap how many peers are served at once. A peer keeps its
+//! slot while it has chunk requests being served and for a short linger
+//! afterwards, so a downloader's next window of requests does not have to
+//! queue again. Other peers wait in FIFO order and get an error once they
+//! have waited too long, which makes the downloader fail over to another
+//! seeder. Queueing and throttling together stay inside the downloader's
+//! request timeout, so that error reaches it instead of a timeout.
+//!
+//! The manager is process-wide ([`global`]) so limits survive DHT restarts
+//! and are shared by the desktop app's commands and the daemon's HTTP API.
+
+use libp2p::PeerId;
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+/// Smallest bucket size. Must hold a full chunk, or a slow limit could
+/// never admit one.
+const MIN_BURST_BYTES: f64 = crate::merkle::CHUNK_SIZE as f64;
+/// How long a peer keeps its upload slot after its last chunk was served.
+pub const UPLOAD_SLOT_LINGER: Duration = Duration::from_secs(2);
+/// How long a peer may wait for an upload slot before it is turned away.
+pub const UPLOAD_QUEUE_TIMEOUT: Duration = upload_budget(crate::dht::FILE_REQUEST_TIMEOUT).0;
+/// How long after a chunk request arrives its answer must go out, counting
+/// both the slot queue and the upload throttle.
+pub const UPLOAD_ANSWER_BUDGET: Duration = upload_budget(crate::dht::FILE_REQUEST_TIMEOUT).1;
+/// Error a seeder returns for a chunk request it cannot serve within
+/// [`UPLOAD_ANSWER_BUDGET`]. Downloaders back off instead of counting it as
+/// a failure.
+pub const SEEDER_BUSY_ERROR: &str = "Seeder busy: all upload slots are in use";
+/// Throughput is averaged over this many one-second buckets.
+const RATE_WINDOW_SECS: u64 = 5;
+/// Idle per-peer buckets are pruned once the map grows past this.
+const PEER_BUCKET_PRUNE_THRESHOLD: usize = 256;
+
+/// Split a downloader's `request_timeout` into the slot queue timeout and
+/// the answer budget, leaving room for the response to cross the wire.
+const fn upload_budget(request_timeout: Duration) -> (Duration, Duration) {
+    let millis = request_timeout.as_millis() as u64;
+    (
+        Duration::from_millis(millis * 2 / 3),
+        Duration::from_millis(millis * 5 / 6),
+    )
+}
+
+/// Configured limits. Rates are bytes per second; 0 means unlimited.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct BandwidthLimits {
+    pub upload_bps: u64,
+    pub download_bps: u64,
+    pub per_peer_upload_bps: u64,
+    pub per_peer_download_bps: u64,
+    /// Peers served concurrently; 0 means unlimited.
+    pub upload_slots: usize,
+}
+
+/// Live throughput and limit state, as reported to the UI and daemon API.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BandwidthStats {
+    pub upload_bps: f64,
+    pub download_bps: f64,
+    pub total_uploaded: u64,
+    pub total_downloaded: u64,
+    pub active_upload_peers: usize,
+    pub queued_upload_peers: usize,
+    pub limits: BandwidthLimits,
+}
+
+#[derive(Debug, Clone)]
+struct TokenBucket {
+    rate: f64,
+    capacity: f64,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    fn new(rate_bps: u64, now: Instant) -> Self {
+        let rate = rate_bps as f64;
+        let capacity = rate.max(MIN_BURST_BYTES);
+        Self {
+            rate,
+            capacity,
+            tokens: capacity,
+            updated: now,
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.capacity);
+        self.updated = now;
+    }
+
+    /// Take `bytes` if they are available right now.
+    fn try_take(&mut self, bytes: u64, now: Instant) -> bool {
+        self.refill(now);
+        if self.tokens < bytes as f64 {
+            return false;
+        }
+        self.tokens -= bytes as f64;
+        true
+    }
+
+    /// Take `bytes` unconditionally, going into debt if needed, and return
+    /// how long the caller must wait for the debt to clear.
+    fn reserve(&mut self, bytes: u64, now: Instant) -> Duration {
+        self.refill(now);
+        self.tokens -= bytes as f64;
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / self.rate)
+        }
+    }
+
+    /// How long a [`Self::reserve`] of `bytes` would have to wait.
+    fn delay_for(&mut self, bytes: u64, now: Instant) -> Duration {
+        self.refill(now);
+        let missing = bytes as f64 - self.tokens;
+        if missing <= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(missing / self.rate)
+        }
+    }
+
+    fn is_full(&mut self, now: Instant) -> bool {
+        self.refill(now);
+        self.tokens >= self.capacity
+    }
+}
+
+/// Bytes per one-second bucket over the last [`RATE_WINDOW_SECS`].
+#[derive(Debug, Default)]
+struct RateMeter {
+    buckets: VecDeque<(u64, u64)>,
+    total: u64,
+}
+
+impl RateMeter {
+    fn record(&mut self, bytes: u64, second: u64) {
+        self.total += bytes;
+        match self.buckets.back_mut() {
+            Some((s, b)) if *s == second => *b += bytes,
+            _ => self.buckets.push_back((second, bytes)),
+        }
+        self.trim(second);
+    }
+
+    fn trim(&mut self, second: u64) {
+        while self
+            .buckets
+            .front()
+            .is_some_and(|(s, _)| *s + RATE_WINDOW_SECS <= second)
+        {
+            self.buckets.pop_front();
+        }
+    }
+
+    fn rate(&mut self, second: u64) -> f64 {
+        self.trim(second);
+        let bytes: u64 = self.buckets.iter().map(|(_, b)| b).sum();
+        bytes as f64 / RATE_WINDOW_SECS as f64
+    }
+}
+
+#[derive(Debug)]
+struct Direction {
+    global: Option<TokenBucket>,
+    per_peer_bps: u64,
+    peers: HashMap<PeerId, TokenBucket>,
+    meter: RateMeter,
+}
+
+impl Direction {
+    fn new(global_bps: u64, per_peer_bps: u64, now: Instant) -> Self {
+        Self {
+            global: (global_bps > 0).then(|| TokenBucket::new(global_bps, now)),
+            per_peer_bps,
+            peers: HashMap::new(),
+            meter: RateMeter::default(),
+        }
+    }
+
+    fn set_limits(&mut self, global_bps: u64, per_peer_bps: u64, now: Instant) {
+        let current = self.global.as_ref().map_or(0, |b| b.rate as u64);
+        if current != global_bps {
+            self.global = (global_bps > 0).then(|| TokenBucket::new(global_bps, now));
+        }
+        if self.per_peer_bps != per_peer_bps {
+            self.per_peer_bps = per_peer_bps;
+            self.peers.clear();
+        }
+    }
+
+    fn peer_bucket(&mut self, peer: PeerId, now: Instant) -> Option<&mut TokenBucket> {
+        if self.per_peer_bps == 0 {
+            return None;
+        }
+        if self.peers.len() > PEER_BUCKET_PRUNE_THRESHOLD {
+            self.peers.retain(|_, bucket| !bucket.is_full(now));
+        }
+        let rate = self.per_peer_bps;
+        Some(
+            self.peers
+                .entry(peer)
+                .or_insert_with(|| TokenBucket::new(rate, now)),
+        )
+    }
+
+    fn try_take(&mut self, peer: PeerId, bytes: u64, now: Instant) -> bool {
+        let global_ok = self.global.as_mut().is_none_or(|b| {
+            b.refill(now);
+            b.tokens >= bytes as f64
+        });
+        if !global_ok {
+            return false;
+        }
+        if let Some(bucket) = self.peer_bucket(peer, now) {
+            if !bucket.try_take(bytes, now) {
+                return false;
+            }
+        }
+        if let Some(bucket) = self.global.as_mut() {
+            bucket.tokens -= bytes as f64;
+        }
+        true
+    }
+
+    fn reserve(&mut self, peer: PeerId, bytes: u64, now: Instant) -> Duration {
+        let global = self
+            .global
+            .as_mut()
+            .map_or(Duration::ZERO, |b| b.reserve(bytes, now));
+        let per_peer = self
+            .peer_bucket(peer, now)
+            .map_or(Duration::ZERO, |b| b.reserve(bytes, now));
+        global.max(per_peer)
+    }
+
+    /// [`Self::reserve`] `bytes` unless that would mean waiting longer than
+    /// `max_delay`, in which case nothing is taken.
+    fn reserve_within(
+        &mut self,
+        peer: PeerId,
+        bytes: u64,
+        now: Instant,
+        max_delay: Duration,
+    ) -> Option<Duration> {
+        let global = self
+            .global
+            .as_mut()
+            .map_or(Duration::ZERO, |b| b.delay_for(bytes, now));
+        let per_peer = self
+            .peer_bucket(peer, now)
+            .map_or(Duration::ZERO, |b| b.delay_for(bytes, now));
+        if global.max(per_peer) > max_delay {
+            return None;
+        }
+        Some(self.reserve(peer, bytes, now))
+    }
+}
+
+#[derive(Debug)]
+struct SlotHolder {
+    active: usize,
+    idle_since: Option<Instant>,
+}
+
+#[derive(Debug, Default)]
+struct UploadSlots {
+    holders: HashMap<PeerId, SlotHolder>,
+    queue: VecDeque<PeerId>,
+}
+
+impl UploadSlots {
+    fn reclaim_idle(&mut self, now: Instant) {
+        self.holders.retain(|_, holder| {
+            holder
+                .idle_since
+                .is_none_or(|since| now.saturating_duration_since(since) < UPLOAD_SLOT_LINGER)
+        });
+    }
+
+    /// Grant `peer` a slot if it already holds one, or if one is free and
+    /// no peer queued before it is still waiting.
+    fn try_acquire(&mut self, peer: PeerId, max_slots: usize, now: Instant) -> bool {
+        self.reclaim_idle(now);
+        if let Some(holder) = self.holders.get_mut(&peer) {
+            holder.active += 1;
+            holder.idle_since = None;
+            self.queue.retain(|p| *p != peer);
+            return true;
+        }
+        let free = max_slots == 0 || self.holders.len() < max_slots;
+        let next_in_line = self.queue.front().is_none_or(|p| *p == peer);
+        if free && next_in_line {
+            self.holders.insert(
+                peer,
+                SlotHolder {
+                    active: 1,
+                    idle_since: None,
+                },
+            );
+            self.queue.retain(|p| *p != peer);
+            return true;
+        }
+        if !self.queue.contains(&peer) {
+            self.queue.push_back(peer);
+        }
+        false
+    }
+
+    fn release(&mut self, peer: PeerId, now: Instant) {
+        if let Some(holder) = self.holders.get_mut(&peer) {
+            holder.active = holder.active.saturating_sub(1);
+            if holder.active == 0 {
+                holder.idle_since = Some(now);
+            }
+        }
+    }
+}
+
+/// Shared rate limiter and upload-slot scheduler.
+pub struct BandwidthManager {
+    limits: Mutex<BandwidthLimits>,
+    upload: Mutex<Direction>,
+    download: Mutex<Direction>,
+    slots: Mutex<UploadSlots>,
+    slot_freed: tokio::sync::Notify,
+    started: Instant,
+}
+
+/// A granted upload slot; released when dropped.
+pub struct UploadSlot<'a> {
+    manager: &'a BandwidthManager,
+    peer: PeerId,
+}
+
+impl Drop for UploadSlot<'_> {
+    fn drop(&mut self) {
+        self.manager.slots.lock().release(self.peer, Instant::now());
+        self.manager.slot_freed.notify_waiters();
+    }
+}
+
+/// Takes `peer` out of the upload queue when its wait ends, including when
+/// the waiting future is dropped, so it does not block the peers behind it.
+struct QueuedPeer<'a> {
+    manager: &'a BandwidthManager,
+    peer: PeerId,
+}
+
+impl Drop for QueuedPeer<'_> {
+    fn drop(&mut self) {
+        let removed = {
+            let mut slots = self.manager.slots.lock();
+            let queued = slots.queue.len();
+            slots.queue.retain(|p| *p != self.peer);
+            slots.queue.len() != queued
+        };
+        if removed {
+            self.manager.slot_freed.notify_waiters();
+        }
+    }
+}
+
+static GLOBAL: Lazy<BandwidthManager> = Lazy::new(|| BandwidthManager::new(Default::default()));
+
+/// The process-wide manager used by the DHT file-request protocol.
+pub fn global() -> &'static BandwidthManager {
+    &GLOBAL
+}
+
+impl BandwidthManager {
+    pub fn new(limits: BandwidthLimits) -> Self {
+        let now = Instant::now();
+        Self {
+            limits: Mutex::new(limits),
+            upload: Mutex::new(Direction::new(
+                limits.upload_bps,
+                limits.per_peer_upload_bps,
+                now,
+            )),
+            download: Mutex::new(Direction::new(
+                limits.download_bps,
+                limits.per_peer_download_bps,
+                now,
+            )),
+            slots: Mutex::new(UploadSlots::default()),
+            slot_freed: tokio::sync::Notify::new(),
+            started: now,
+        }
+    }
+
+    pub fn limits(&self) -> BandwidthLimits {
+        *self.limits.lock()
+    }
+
+    pub fn set_limits(&self, limits: BandwidthLimits) {
+        let now = Instant::now();
+        *self.limits.lock() = limits;
+        self.upload
+            .lock()
+            .set_limits(limits.upload_bps, limits.per_peer_upload_bps, now);
+        self.download
+            .lock()
+            .set_limits(limits.download_bps, limits.per_peer_download_bps, now);
+        // More slots may be free now.
+        self.slot_freed.notify_waiters();
+    }
+
+    fn second(&self, now: Instant) -> u64 {
+        now.saturating_duration_since(self.started).as_secs()
+    }
+
+    /// Wait for an upload slot for `peer`. Fails after
+    /// [`UPLOAD_QUEUE_TIMEOUT`] so the peer can try another seeder.
+    pub async fn acquire_upload_slot(&self, peer: PeerId) -> Result<UploadSlot<'_>, String> {
+        self.acquire_upload_slot_within(peer, UPLOAD_QUEUE_TIMEOUT)
+            .await
+    }
+
+    async fn acquire_upload_slot_within(
+        &self,
+        peer: PeerId,
+        timeout: Duration,
+    ) -> Result<UploadSlot<'_>, String> {
+        let deadline = Instant::now() + timeout;
+        let _queued = QueuedPeer {
+            manager: self,
+            peer,
+        };
+        loop {
+            let freed = self.slot_freed.notified();
+            tokio::pin!(freed);
+            // Register before checking so a release between the check and
+            // the wait still wakes us.
+            freed.as_mut().enable();
+            let now = Instant::now();
+            let max_slots = self.limits().upload_slots;
+            if self.slots.lock().try_acquire(peer, max_slots, now) {
+                return Ok(UploadSlot {
+                    manager: self,
+                    peer,
+                });
+            }
+            if now >= deadline {
+                return Err(SEEDER_BUSY_ERROR.to_string());
+            }
+            // Idle holders are reclaimed lazily, so re-check at least once
+            // per linger period even without a notification.
+            let wait = deadline
+                .saturating_duration_since(now)
+                .min(UPLOAD_SLOT_LINGER);
+            let _ = tokio::time::timeout(wait, freed).await;
+        }
+    }
+
+    /// Account for `bytes` sent to `peer` and wait until the upload limits
+    /// allow them to go out. Fails with [`SEEDER_BUSY_ERROR`], without
+    /// counting the bytes, if that wait would run past `answer_by`.
+    pub async fn throttle_upload(
+        &self,
+        peer: PeerId,
+        bytes: u64,
+        answer_by: Instant,
+    ) -> Result<(), String> {
+        let delay = {
+            let now = Instant::now();
+            let mut upload = self.upload.lock();
+            let delay = upload
+                .reserve_within(peer, bytes, now, answer_by.saturating_duration_since(now))
+                .ok_or_else(|| SEEDER_BUSY_ERROR.to_string())?;
+            upload.meter.record(bytes, self.second(now));
+            delay
+        };
+        if !delay.is_zero() {
+            tokio::time::sleep(delay).await;
+        }
+        Ok(())
+    }
+
+    /// Ask to download `bytes` from `peer` now. `false` means the request
+    /// should wait for a later scheduler tick.
+    pub fn try_reserve_download(&self, peer: PeerId, bytes: u64) -> bool {
+        self.download.lock().try_take(peer, bytes, Instant::now())
+    }
+
+    /// Count `bytes` received for the throughput stats.
+    pub fn record_download(&self, bytes: u64) {
+        let now = Instant::now();
+        self.download.lock().meter.record(bytes, self.second(now));
+    }
+
+    pub fn stats(&self) -> BandwidthStats {
+        let now = Instant::now();
+        let second = self.second(now);
+        let (upload_bps, total_uploaded) = {
+            let mut upload = self.upload.lock();
+            (upload.meter.rate(second), upload.meter.total)
+        };
+        let (download_bps, total_downloaded) = {
+            let mut download = self.download.lock();
+            (download.meter.rate(second), download.meter.total)
+        };
+        let (active_upload_peers, queued_upload_peers) = {
+            let mut slots = self.slots.lock();
+            slots.reclaim_idle(now);
+            (slots.holders.len(), slots.queue.len())
+        };
+        BandwidthStats {
+            upload_bps,
+            download_bps,
+            total_uploaded,
+            total_downloaded,
+            active_upload_peers,
+            queued_upload_peers,
+            limits: self.limits(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn token_bucket_holds_at_least_one_chunk_and_refills() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(1024, start);
+        assert_eq!(bucket.capacity, MIN_BURST_BYTES);
+        assert!(bucket.try_take(MIN_BURST_BYTES as u64, start));
+        assert!(!bucket.try_take(1024, start));
+        assert!(bucket.try_take(1024, start + Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn token_bucket_reserve_reports_debt_as_delay() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(1_000_000, start);
+        assert_eq!(bucket.reserve(1_000_000, start), Duration::ZERO);
+        let delay = bucket.reserve(500_000, start);
+        assert!((delay.as_secs_f64() - 0.5).abs() < 1e-6, "{:?}", delay);
+    }
+
+    #[test]
+    fn per_peer_limit_does_not_starve_other_peers() {
+        let now = Instant::now();
+        let mut direction = Direction::new(0, 1, now);
+        let (a, b) = (PeerId::random(), PeerId::random());
+        let chunk = MIN_BURST_BYTES as u64;
+        assert!(direction.try_take(a, chunk, now));
+        assert!(!direction.try_take(a, chunk, now));
+        assert!(direction.try_take(b, chunk, now));
+    }
+
+    #[test]
+    fn global_limit_is_not_charged_when_peer_limit_refuses() {
+        let now = Instant::now();
+        let chunk = MIN_BURST_BYTES as u64;
+        let mut direction = Direction::new(2 * chunk, 1, now);
+        let a = PeerId::random();
+        assert!(direction.try_take(a, chunk, now));
+        assert!(!direction.try_take(a, chunk, now));
+        assert_eq!(direction.global.as_ref().unwrap().tokens, chunk as f64);
+    }
+
+    #[test]
+    fn unlimited_direction_admits_everything() {
+        let now = Instant::now();
+        let mut direction = Direction::new(0, 0, now);
+        let peer = PeerId::random();
+        for _ in 0..100 {
+            assert!(direction.try_take(peer, 1 << 30, now));
+            assert_eq!(direction.reserve(peer, 1 << 30, now), Duration::ZERO);
+        }
+    }
+
+    #[test]
+    fn rate_meter_averages_over_window() {
+        let mut meter = RateMeter::default();
+        meter.record(5_000, 10);
+        meter.record(5_000, 11);
+        assert_eq!(meter.rate(11), 2_000.0);
+        assert_eq!(meter.rate(10 + RATE_WINDOW_SECS), 1_000.0);
+        assert_eq!(meter.rate(11 + RATE_WINDOW_SECS), 0.0);
+        assert_eq!(meter.total, 10_000);
+    }
+
+    #[test]
+    fn upload_slots_queue_peers_in_order_and_linger() {
+        let now = Instant::now();
+        let mut slots = UploadSlots::default();
+        let (a, b, c) = (PeerId::random(), PeerId::random(), PeerId::random());
+        assert!(slots.try_acquire(a, 1, now));
+        assert!(slots.try_acquire(a, 1, now), "holder may stack requests");
+        assert!(!slots.try_acquire(b, 1, now));
+        assert!(!slots.try_acquire(c, 1, now));
+        assert_eq!(slots.queue, [b, c]);
+
+        slots.release(a, now);
+        slots.release(a, now);
+        // Still lingering.
+        assert!(!slots.try_acquire(c, 1, now));
+        let later = now + UPLOAD_SLOT_LINGER;
+        // c is behind b in the queue.
+        assert!(!slots.try_acquire(c, 1, later));
+        assert!(slots.try_acquire(b, 1, later));
+        assert_eq!(slots.queue, [c]);
+    }
+
+    #[tokio::test]
+    async fn acquire_upload_slot_waits_for_release_and_times_out() {
+        let manager = BandwidthManager::new(BandwidthLimits {
+            upload_slots: 1,
+            ..Default::default()
+        });
+        let (a, b) = (PeerId::random(), PeerId::random());
+        let slot = manager.acquire_upload_slot(a).await.unwrap();
+        let err = manager
+            .acquire_upload_slot_within(b, Duration::from_millis(20))
+            .await
+            .err()
+            .unwrap();
+        assert!(err.contains("upload slots"));
+        assert_eq!(manager.stats().queued_upload_peers, 0);
+
+        drop(slot);
+        // a lingers, so b has to wait out the linger period.
+        let started = Instant::now();
+        let _slot = manager
+            .acquire_upload_slot_within(b, UPLOAD_SLOT_LINGER * 2)
+            .await
+            .unwrap();
+        assert!(started.elapsed() >= UPLOAD_SLOT_LINGER / 2);
+        assert_eq!(manager.stats().active_upload_peers, 1);
+    }
+
+    #[tokio::test]
+    async fn cancelled_upload_slot_waiter_leaves_the_queue() {
+        let manager = BandwidthManager::new(BandwidthLimits {
+            upload_slots: 1,
+            ..Default::default()
+        });
+        let (a, b, c) = (PeerId::random(), PeerId::random(), PeerId::random());
+        let slot = manager.acquire_upload_slot(a).await.unwrap();
+        // Drop b's wait from outside, well before its own queue timeout.
+        let cancelled =
+            tokio::time::timeout(Duration::from_millis(20), manager.acquire_upload_slot(b)).await;
+        assert!(cancelled.is_err());
+        assert_eq!(manager.stats().queued_upload_peers, 0);
+
+        drop(slot);
+        let _slot = manager
+            .acquire_upload_slot_within(c, UPLOAD_SLOT_LINGER * 2)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn busy_seeder_answers_before_the_request_times_out() {
+        assert!(UPLOAD_QUEUE_TIMEOUT < UPLOAD_ANSWER_BUDGET);
+        assert!(UPLOAD_ANSWER_BUDGET < crate::dht::FILE_REQUEST_TIMEOUT);
+
+        // The same split, scaled down so the test runs quickly.
+        let request_timeout = Duration::from_millis(300);
+        let (queue_timeout, answer_budget) = upload_budget(request_timeout);
+        let manager = BandwidthManager::new(BandwidthLimits {
+            upload_bps: 1,
+            upload_slots: 1,
+            ..Default::default()
+        });
+        let chunk = crate::merkle::CHUNK_SIZE as u64;
+        let (a, b) = (PeerId::random(), PeerId::random());
+        let _slot = manager.acquire_upload_slot(a).await.unwrap();
+
+        // No free slot: b is told the seeder is busy.
+        let received = Instant::now();
+        let err = manager
+            .acquire_upload_slot_within(b, queue_timeout)
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(err, SEEDER_BUSY_ERROR);
+        assert!(received.elapsed() < request_timeout);
+
+        // A slot, but the upload limit is spent: a is told so right away.
+        let received = Instant::now();
+        manager
+            .throttle_upload(a, chunk, received + answer_budget)
+            .await
+            .unwrap();
+        let err = manager
+            .throttle_upload(a, chunk, received + answer_budget)
+            .await
+            .unwrap_err();
+        assert_eq!(err, SEEDER_BUSY_ERROR);
+        assert!(received.elapsed() < request_timeout);
+        assert_eq!(manager.stats().total_uploaded, chunk);
+    }
+
+    #[test]
+    fn stats_report_limits_and_totals() {
+        let limits = BandwidthLimits {
+            upload_bps: 1_000_000,
+            ..Default::default()
+        };
+        let manager = BandwidthManager::new(Default::default());
+        manager.set_limits(limits);
+        manager.record_download(4096);
+        let stats = manager.stats();
+        assert_eq!(stats.limits, limits);
+        assert_eq!(stats.total_downloaded, 4096);
+        assert!(stats.download_bps > 0.0);
+    }
+}
@@ -1,22 +1,22 @@
 use base64::Engine;
 use clap::{Parser, Subcommand};
-use rand::RngCore;
 use rlp::RlpStream;
-use secp256k1::{Message, PublicKey, Secp256k1, SecretKey};
+use secp256k1::{Message, Secp256k1, SecretKey};
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use sha2::{Digest, Sha256};
-use std::io::Read;
 use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
 use tiny_keccak::{Hasher, Keccak};
 
+use chiral_network::chain_index::{self, IndexQuery, IndexedTx};
+use chiral_network::daemon_auth::{self, TokenScope, TokenStore};
 use chiral_network::dht;
 use chiral_network::drive_storage;
 use chiral_network::drive_storage::DriveItem;
 use chiral_network::geth;
 use chiral_network::hosting;
+use chiral_network::keystore::{self, Keystore};
 use chiral_network::rating_storage::{
     self, compute_reputation_for_wallet, RatingState, LOOKBACK_SECS,
 };
@@ -92,6 +92,10 @@ enum Commands {
         #[command(subcommand)]
         cmd: GethCommand,
     },
+    Events {
+        #[command(subcommand)]
+        cmd: EventsCommand,
+    },
 }
 
 #[derive(Subcommand, Debug)]
@@ -105,6 +109,31 @@ enum DaemonCommand {
         #[arg(long, default_value_t = 9419)]
         port: u16,
     },
+    /// Manage API tokens for the daemon's /api/headless routes
+    Token {
+        #[command(subcommand)]
+        cmd: DaemonTokenCommand,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum DaemonTokenCommand {
+    /// Print the token this CLI sends to the daemon
+    Show,
+    /// List token names and scopes
+    List,
+    /// Mint a token (scope: read, control or spend)
+    Create {
+        #[arg(long)]
+        name: String,
+        #[arg(long, default_value = "read")]
+        scope: TokenScope,
+    },
+    /// Revoke a token by name
+    Revoke {
+        #[arg(long)]
+        name: String,
+    },
 }
 
 #[derive(Subcommand, Debug)]
@@ -140,19 +169,68 @@ enum DiagnosticsCommand {
 
 #[derive(Subcommand, Debug)]
 enum WalletCommand {
-    Create,
+    /// Create a wallet from a new recovery phrase (account 0)
+    Create {
+        #[arg(long)]
+        passphrase_file: Option<PathBuf>,
+        #[arg(long, default_value_t = 12)]
+        words: usize,
+    },
+    /// Import a private key, or account `--index` of a recovery phrase
     Import {
+        #[arg(long, required_unless_present = "mnemonic_file")]
+        private_key: Option<String>,
+        /// File holding the recovery phrase (or set CHIRAL_WALLET_MNEMONIC)
+        #[arg(long, conflicts_with = "private_key")]
+        mnemonic_file: Option<PathBuf>,
+        #[arg(long, default_value_t = 0)]
+        index: u32,
+        #[arg(long)]
+        passphrase_file: Option<PathBuf>,
+    },
+    /// Derive accounts m/44'/60'/0'/0/N from a recovery phrase
+    Derive {
+        #[arg(long, default_value_t = 0)]
+        index: u32,
+        #[arg(long, default_value_t = 1)]
+        count: u32,
+        #[arg(long)]
+        mnemonic_file: Option<PathBuf>,
+        /// Make the derived account the active wallet
+        #[arg(long, default_value_t = false)]
+        activate: bool,
         #[arg(long)]
-        private_key: String,
+        passphrase_file: Option<PathBuf>,
     },
     Login {
         #[arg(long)]
         private_key: Option<String>,
+        #[arg(long)]
+        passphrase_file: Option<PathBuf>,
     },
     Show,
     Export {
         #[arg(long, default_value_t = false)]
         reveal_private_key: bool,
+        #[arg(long)]
+        passphrase_file: Option<PathBuf>,
+    },
+    /// Re-encrypt a plaintext wallet.json as a v3 keystore
+    Encrypt {
+        #[arg(long)]
+        passphrase_file: Option<PathBuf>,
+    },
+    /// Unlock the running daemon's keystore so it can sign
+    Unlock {
+        #[arg(long)]
+        passphrase_file: Option<PathBuf>,
+        #[arg(long, default_value_t = 9419)]
+        port: u16,
+    },
+    /// Drop the running daemon's decrypted key
+    Lock {
+        #[arg(long, default_value_t = 9419)]
+        port: u16,
     },
 }
 
@@ -175,10 +253,19 @@ enum AccountCommand {
     History {
         #[arg(long)]
         address: Option<String>,
+        /// Blocks to scan when the local chain index is not caught up
         #[arg(long, default_value_t = 20_000)]
         max_blocks: u64,
         #[arg(long, default_value_t = 50)]
         limit: usize,
+        #[arg(long, default_value_t = 0)]
+        offset: usize,
+        /// Only transactions at or after this unix time
+        #[arg(long)]
+        since: Option<u64>,
+        /// Only transactions at or before this unix time
+        #[arg(long)]
+        until: Option<u64>,
     },
     Meta,
 }
@@ -237,9 +324,15 @@ enum DhtCommand {
 
 #[derive(Subcommand, Debug)]
 enum DownloadCommand {
+    /// Look up a file by `--hash`, or find files by keyword with `--query`
+    /// (`ext:pdf`, `mime:image` and `tag:<tag>` narrow the match)
     Search {
-        #[arg(long)]
-        hash: String,
+        #[arg(long, required_unless_present = "query")]
+        hash: Option<String>,
+        #[arg(long, conflicts_with = "hash")]
+        query: Option<String>,
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
         #[arg(long, default_value_t = 9419)]
         port: u16,
     },
@@ -430,8 +523,6 @@ enum DropCommand {
         price_wei: String,
         #[arg(long)]
         sender_wallet: String,
-        #[arg(long)]
-        file_hash: Option<String>,
         #[arg(long, default_value_t = 9419)]
         port: u16,
     },
@@ -439,11 +530,10 @@ enum DropCommand {
         #[arg(long, default_value_t = 9419)]
         port: u16,
     },
+    /// Accept an offer; the daemon downloads it into its download directory
     Accept {
         #[arg(long)]
         transfer_id: String,
-        #[arg(long)]
-        download_dir: Option<String>,
         #[arg(long, default_value_t = 9419)]
         port: u16,
     },
@@ -453,6 +543,13 @@ enum DropCommand {
         #[arg(long, default_value_t = 9419)]
         port: u16,
     },
+    /// Stop an accepted transfer and delete what was downloaded
+    Cancel {
+        #[arg(long)]
+        transfer_id: String,
+        #[arg(long, default_value_t = 9419)]
+        port: u16,
+    },
     History,
 }
 
@@ -576,6 +673,17 @@ enum MiningCommand {
     Blocks {
         #[arg(long, default_value_t = 500)]
         max: u64,
+        #[arg(long, default_value_t = 0)]
+        offset: usize,
+        /// Only blocks at or after this unix time
+        #[arg(long)]
+        since: Option<u64>,
+        /// Only blocks at or before this unix time
+        #[arg(long)]
+        until: Option<u64>,
+        /// Coinbase to list (defaults to the daemon's miner address)
+        #[arg(long)]
+        address: Option<String>,
         #[arg(long, default_value_t = 9419)]
         port: u16,
     },
@@ -605,6 +713,21 @@ enum GethCommand {
     },
 }
 
+#[derive(Subcommand, Debug)]
+enum EventsCommand {
+    /// Print daemon events as they happen
+    Tail {
+        /// Comma-separated event types; `download-*` matches a prefix
+        #[arg(long)]
+        types: Option<String>,
+        /// Print each event as a JSON line
+        #[arg(long, default_value_t = false)]
+        json: bool,
+        #[arg(long, default_value_t = 9419)]
+        port: u16,
+    },
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
 struct NotificationsConfig {
@@ -703,7 +826,14 @@ impl Default for HeadlessConfig {
 #[serde(rename_all = "camelCase")]
 struct WalletProfile {
     address: String,
+    /// Legacy plaintext key; empty once the profile carries a keystore.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     private_key: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    keystore: Option<Keystore>,
+    /// BIP-44 path when the key was derived from a recovery phrase.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    derivation_path: Option<String>,
     created_at: u64,
 }
 
@@ -822,6 +952,8 @@ struct HostRegistryEntry {
     peer_id: String,
     wallet_address: String,
     updated_at: u64,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    signature: String,
 }
 
 fn host_registry_from_dht_value(
@@ -845,6 +977,8 @@ fn host_registry_after_publish(
         peer_id,
         wallet_address,
         updated_at,
+        // Signed by the DHT layer when the registry is published.
+        signature: String::new(),
     });
     registry
 }
@@ -943,8 +1077,12 @@ fn load_wallet_store() -> Result<WalletStore, String> {
     read_json_file(&wallet_store_path())
 }
 
+/// The store holds a plaintext key when no passphrase was given, so it is
+/// written like a keystore: atomically and readable by the owner only.
 fn save_wallet_store(store: &WalletStore) -> Result<(), String> {
-    write_json_file(&wallet_store_path(), store)
+    let raw = serde_json::to_string_pretty(store)
+        .map_err(|e| format!("Failed to serialize JSON: {}", e))?;
+    keystore::write_private_file(&wallet_store_path(), raw.as_bytes())
 }
 
 fn load_download_history() -> Result<Vec<DownloadRecord>, String> {
@@ -1113,8 +1251,8 @@ async fn check_gateway_health(port: u16) -> bool {
 }
 
 async fn check_headless_api(port: u16) -> bool {
-    let url = format!("http://127.0.0.1:{}/api/headless/runtime", port);
-    let client = reqwest::Client::builder()
+    let url = format!("{}/api/headless/runtime", headless_base_url(port));
+    let client = daemon_http_client_builder()
         .timeout(std::time::Duration::from_millis(500))
         .build();
     let Ok(client) = client else {
@@ -1153,6 +1291,35 @@ fn gateway_base_url(port: u16) -> String {
     format!("http://127.0.0.1:{}", port)
 }
 
+/// Base URL for `/api/headless/*`. Defaults to the gateway port; set
+/// `CHIRAL_DAEMON_ADMIN_URL` when the daemon runs with `--admin-bind`.
+fn headless_base_url(port: u16) -> String {
+    match std::env::var("CHIRAL_DAEMON_ADMIN_URL") {
+        Ok(url) if !url.trim().is_empty() => url.trim().trim_end_matches('/').to_string(),
+        _ => gateway_base_url(port),
+    }
+}
+
+/// HTTP client that sends the daemon API token (from `CHIRAL_DAEMON_TOKEN`
+/// or the data dir's token file) with every request.
+fn daemon_http_client_builder() -> reqwest::ClientBuilder {
+    let mut headers = reqwest::header::HeaderMap::new();
+    if let Some(token) = daemon_auth::client_token(&default_data_dir()) {
+        if let Ok(mut value) = reqwest::header::HeaderValue::from_str(&format!("Bearer {}", token))
+        {
+            value.set_sensitive(true);
+            headers.insert(reqwest::header::AUTHORIZATION, value);
+        }
+    }
+    reqwest::Client::builder().default_headers(headers)
+}
+
+fn daemon_http_client() -> Result<reqwest::Client, String> {
+    daemon_http_client_builder()
+        .build()
+        .map_err(|e| format!("Failed to build HTTP client: {}", e))
+}
+
 fn print_json(value: &Value) -> Result<(), String> {
     let rendered =
         serde_json::to_string_pretty(value).map_err(|e| format!("Failed to render JSON: {}", e))?;
@@ -1179,9 +1346,9 @@ async fn daemon_post_json<T: Serialize>(
     path: &str,
     payload: &T,
 ) -> Result<Value, String> {
-    let client = reqwest::Client::new();
+    let client = daemon_http_client()?;
     let resp = client
-        .post(format!("{}{}", gateway_base_url(port), path))
+        .post(format!("{}{}", headless_base_url(port), path))
         .json(payload)
         .send()
         .await
@@ -1194,9 +1361,9 @@ async fn daemon_post_empty(port: u16, path: &str) -> Result<Value, String> {
 }
 
 async fn daemon_get_json(port: u16, path: &str) -> Result<Value, String> {
-    let client = reqwest::Client::new();
+    let client = daemon_http_client()?;
     let resp = client
-        .get(format!("{}{}", gateway_base_url(port), path))
+        .get(format!("{}{}", headless_base_url(port), path))
         .send()
         .await
         .map_err(|e| format!("Request failed: {}", e))?;
@@ -1208,9 +1375,9 @@ async fn daemon_get_json_with_query(
     path: &str,
     params: &[(&str, String)],
 ) -> Result<Value, String> {
-    let client = reqwest::Client::new();
+    let client = daemon_http_client()?;
     let resp = client
-        .get(format!("{}{}", gateway_base_url(port), path))
+        .get(format!("{}{}", headless_base_url(port), path))
         .query(params)
         .send()
         .await
@@ -1599,10 +1766,13 @@ async fn send_transaction(
     })
 }
 
+/// Served from the chain index the desktop app or daemon keeps on disk when
+/// it was built from this chain, covers its head and its tip is still
+/// canonical; otherwise the last `max_blocks` are scanned.
 async fn get_transaction_history(
     address: &str,
     max_blocks: u64,
-    limit: usize,
+    query: &IndexQuery,
 ) -> Result<Vec<TransactionHistoryItem>, String> {
     let client = reqwest::Client::builder()
         .timeout(std::time::Duration::from_secs(30))
@@ -1630,7 +1800,20 @@ async fn get_transaction_history(
     let latest_block_hex = block_json["result"].as_str().unwrap_or("0x0");
     let latest_block = parse_hex_u64(latest_block_hex, "eth_blockNumber result")?;
 
+    if let Some(index) =
+        chain_index::load_serving(chain_index::index_path(), &geth::rpc_endpoint(), latest_block)
+            .await?
+    {
+        let page = index.transactions(address, query);
+        return Ok(page
+            .items
+            .into_iter()
+            .map(|tx| indexed_history_item(address, tx))
+            .collect());
+    }
+
     const BATCH_SIZE: u64 = 100;
+    let wanted = query.offset + query.page_size();
     let first_block_to_scan = latest_block.saturating_sub(max_blocks.saturating_sub(1));
     let mut cursor = latest_block;
     let mut transactions = Vec::new();
@@ -1671,6 +1854,9 @@ async fn get_transaction_history(
                 continue;
             };
             let (block_num, block_timestamp) = parse_history_block_fields(result)?;
+            if !query.matches(block_timestamp) {
+                continue;
+            }
 
             for tx in txs {
                 let from = tx
@@ -1726,7 +1912,7 @@ async fn get_transaction_history(
                     direction: direction.to_string(),
                 });
 
-                if transactions.len() >= limit {
+                if transactions.len() >= wanted {
                     break 'outer;
                 }
             }
@@ -1739,7 +1925,28 @@ async fn get_transaction_history(
     }
 
     transactions.sort_by(|a, b| b.block_number.cmp(&a.block_number));
-    Ok(transactions)
+    Ok(query.page(transactions, |tx| tx.timestamp).items)
+}
+
+fn indexed_history_item(address: &str, tx: IndexedTx) -> TransactionHistoryItem {
+    let value_wei = tx.value_wei.parse::<u128>().unwrap_or(0);
+    let direction = if tx.from.eq_ignore_ascii_case(address) {
+        "send"
+    } else {
+        "receive"
+    };
+    TransactionHistoryItem {
+        hash: tx.hash,
+        from: tx.from,
+        to: tx.to,
+        value: format!("{:.6}", value_wei as f64 / 1e18),
+        value_wei: tx.value_wei,
+        block_number: tx.block_number,
+        timestamp: tx.timestamp,
+        status: "confirmed".to_string(),
+        gas_used: tx.gas,
+        direction: direction.to_string(),
+    }
 }
 
 fn normalize_private_key(input: &str) -> Result<String, String> {
@@ -1755,40 +1962,99 @@ fn normalize_private_key(input: &str) -> Result<String, String> {
     Ok(format!("0x{}", key))
 }
 
-fn address_from_private_key(private_key: &str) -> Result<String, String> {
-    let key_hex = private_key.trim_start_matches("0x");
-    let sk_bytes = hex::decode(key_hex).map_err(|e| format!("Invalid private key: {}", e))?;
-    let secret_key =
-        SecretKey::from_slice(&sk_bytes).map_err(|e| format!("Invalid private key: {}", e))?;
-    let secp = Secp256k1::new();
-    let public_key = PublicKey::from_secret_key(&secp, &secret_key);
-    let uncompressed = public_key.serialize_uncompressed();
-    let hash = keccak256(&uncompressed[1..]);
-    Ok(format!("0x{}", hex::encode(&hash[12..])))
-}
-
-fn generate_wallet() -> Result<WalletProfile, String> {
-    let mut rng = rand::thread_rng();
-    let private_key = loop {
-        let mut bytes = [0u8; 32];
-        rng.fill_bytes(&mut bytes);
-        if SecretKey::from_slice(&bytes).is_ok() {
-            break format!("0x{}", hex::encode(bytes));
-        }
+const MNEMONIC_ENV: &str = "CHIRAL_WALLET_MNEMONIC";
+/// BIP-39 extension passphrase; empty (the common case) when unset.
+const MNEMONIC_PASSPHRASE_ENV: &str = "CHIRAL_WALLET_MNEMONIC_PASSPHRASE";
+
+/// Recovery phrase from `--mnemonic-file` or `CHIRAL_WALLET_MNEMONIC`.
+/// Never taken as an argument so it stays out of shell history.
+fn require_mnemonic(mnemonic_file: Option<&Path>) -> Result<String, String> {
+    let raw = match mnemonic_file {
+        Some(path) => std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?,
+        None => std::env::var(MNEMONIC_ENV).map_err(|_| {
+            format!(
+                "A recovery phrase is required: pass --mnemonic-file or set {}",
+                MNEMONIC_ENV
+            )
+        })?,
     };
-    let address = address_from_private_key(&private_key)?;
+    chiral_network::wallet::validate_mnemonic(&raw)
+}
+
+fn mnemonic_passphrase() -> String {
+    std::env::var(MNEMONIC_PASSPHRASE_ENV).unwrap_or_default()
+}
+
+fn wallet_from_account(
+    account: chiral_network::wallet::DerivedAccount,
+) -> Result<WalletProfile, String> {
     Ok(WalletProfile {
-        address,
-        private_key,
+        address: account.address,
+        private_key: account.private_key,
+        keystore: None,
+        derivation_path: Some(account.path),
         created_at: now_secs()?,
     })
 }
 
+/// Passphrase from `--passphrase-file` or `CHIRAL_WALLET_PASSPHRASE`.
+fn require_passphrase(passphrase_file: Option<&Path>) -> Result<String, String> {
+    keystore::read_passphrase(passphrase_file)?.ok_or_else(|| {
+        format!(
+            "A wallet passphrase is required: pass --passphrase-file or set {}",
+            keystore::PASSPHRASE_ENV
+        )
+    })
+}
+
+/// Replace the plaintext key in `wallet` with an encrypted keystore.
+fn seal_wallet(wallet: &mut WalletProfile, passphrase: &str) -> Result<(), String> {
+    if wallet.keystore.is_some() {
+        return Ok(());
+    }
+    let ks = keystore::encrypt_private_key(&wallet.private_key, passphrase)?;
+    wallet.keystore = Some(ks);
+    wallet.private_key.clear();
+    Ok(())
+}
+
+fn save_active_wallet(wallet: &WalletProfile) -> Result<(), String> {
+    let mut store = load_wallet_store()?;
+    store.active = Some(wallet.clone());
+    save_wallet_store(&store)
+}
+
 fn require_wallet() -> Result<WalletProfile, String> {
     let store = load_wallet_store()?;
-    store
-        .active
-        .ok_or("No active wallet. Use `chiral wallet create` or `chiral wallet import`".to_string())
+    let mut wallet = store.active.ok_or(
+        "No active wallet. Use `chiral wallet create` or `chiral wallet import`".to_string(),
+    )?;
+    // Opportunistically migrate a plaintext store whenever a passphrase is
+    // already in the environment.
+    if wallet.keystore.is_none() {
+        if let Ok(Some(passphrase)) = keystore::read_passphrase(None) {
+            seal_wallet(&mut wallet, &passphrase)?;
+            save_active_wallet(&wallet)?;
+            eprintln!(
+                "Encrypted plaintext wallet {} in {}",
+                wallet.address,
+                wallet_store_path().display()
+            );
+        }
+    }
+    Ok(wallet)
+}
+
+/// The active wallet's private key, decrypting the keystore if needed.
+fn unlock_wallet_key(
+    wallet: &WalletProfile,
+    passphrase_file: Option<&Path>,
+) -> Result<String, String> {
+    match &wallet.keystore {
+        Some(ks) => keystore::decrypt_private_key(ks, &require_passphrase(passphrase_file)?),
+        None => Ok(wallet.private_key.clone()),
+    }
 }
 
 /// Fixed download cost: 0.001 CHI per MB
@@ -2191,6 +2457,7 @@ mod tests {
             peer_id: peer_id.to_string(),
             wallet_address: wallet_address.to_string(),
             updated_at,
+            signature: String::new(),
         }
     }
 
@@ -2264,20 +2531,7 @@ mod tests {
 }
 
 fn compute_file_hash(path: &Path) -> Result<String, String> {
-    let mut file = std::fs::File::open(path)
-        .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
-    let mut hasher = Sha256::new();
-    let mut buf = vec![0u8; 256 * 1024];
-    loop {
-        let n = file
-            .read(&mut buf)
-            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
-        if n == 0 {
-            break;
-        }
-        hasher.update(&buf[..n]);
-    }
-    Ok(hex::encode(hasher.finalize()))
+    chiral_network::merkle::MerkleTree::from_file(path).map(|tree| tree.root())
 }
 
 fn encode_html_escape(input: &str) -> String {
@@ -2574,7 +2828,7 @@ fn drive_publish_credentials(
     }
 
     let private_key = normalize_private_key(&private_key)?;
-    let derived_wallet = address_from_private_key(&private_key)?;
+    let derived_wallet = chiral_network::wallet::address_from_private_key(&private_key)?;
     let wallet_address = if wallet_address.is_empty() {
         derived_wallet
     } else {
@@ -2954,6 +3208,46 @@ async fn handle_daemon(cmd: DaemonCommand) -> Result<(), String> {
             );
             Ok(())
         }
+        DaemonCommand::Token { cmd } => handle_daemon_token(cmd),
+    }
+}
+
+fn handle_daemon_token(cmd: DaemonTokenCommand) -> Result<(), String> {
+    let path = daemon_auth::token_file(&default_data_dir());
+    match cmd {
+        DaemonTokenCommand::Show => {
+            let token = daemon_auth::client_token(&default_data_dir()).ok_or_else(|| {
+                format!(
+                    "No daemon token found at {} (start the daemon once to create it)",
+                    path.display()
+                )
+            })?;
+            println!("{}", token);
+            Ok(())
+        }
+        DaemonTokenCommand::List => {
+            let store = TokenStore::load_or_init(&path)?;
+            for token in &store.tokens {
+                println!("{} {}", token.name, token.scope.as_str());
+            }
+            Ok(())
+        }
+        DaemonTokenCommand::Create { name, scope } => {
+            let mut store = TokenStore::load_or_init(&path)?;
+            let token = store.create(&name, scope)?;
+            store.save(&path)?;
+            println!("{}", token);
+            Ok(())
+        }
+        DaemonTokenCommand::Revoke { name } => {
+            let mut store = TokenStore::load_or_init(&path)?;
+            if !store.revoke(&name) {
+                return Err(format!("No token named '{}'", name));
+            }
+            store.save(&path)?;
+            println!("revoked {}", name);
+            Ok(())
+        }
     }
 }
 
@@ -3034,6 +3328,18 @@ async fn handle_reputation(cmd: ReputationCommand) -> Result<(), String> {
             println!("completed={}", snapshot.completed_count);
             println!("failed={}", snapshot.failed_count);
             println!("earned_wei={}", snapshot.total_earned_wei);
+            let b = &snapshot.breakdown;
+            println!("time_weight={:.2}", b.time_weight);
+            println!("amount_weight={:.2}", b.amount_weight);
+            println!("counterparty_weight={:.2}", b.counterparty_weight);
+            println!("wallet_age_weight={:.2}", b.wallet_age_weight);
+            println!("counterparties={}", b.counterparties);
+            println!("capped_counterparties={}", b.capped_counterparties);
+            println!("unverified_payments={}", b.unverified_payments);
+            println!("reused_payments={}", b.reused_payments);
+            println!("capped_unpaid_events={}", b.capped_unpaid_events);
+            println!("elo_gained={:.1}", b.elo_gained);
+            println!("elo_lost={:.1}", b.elo_lost);
             println!("recent_events={}", recent_events);
             Ok(())
         }
@@ -3096,56 +3402,109 @@ fn derive_wallet_from_optional(
     }
 
     let wallet = require_wallet()?;
-    let addr = address.unwrap_or(wallet.address);
     let pk = match private_key {
         Some(v) => normalize_private_key(&v)?,
-        None => wallet.private_key,
+        None => unlock_wallet_key(&wallet, None)?,
     };
+    let addr = address.unwrap_or(wallet.address);
     Ok((addr, pk))
 }
 
 async fn handle_wallet(cmd: WalletCommand) -> Result<(), String> {
     match cmd {
-        WalletCommand::Create => {
-            let wallet = generate_wallet()?;
-            let mut store = load_wallet_store()?;
-            store.active = Some(wallet.clone());
-            save_wallet_store(&store)?;
-
+        WalletCommand::Create {
+            passphrase_file,
+            words,
+        } => {
+            let passphrase = require_passphrase(passphrase_file.as_deref())?;
+            let mnemonic = chiral_network::wallet::generate_mnemonic(words)?;
+            let account =
+                chiral_network::wallet::derive_account(&mnemonic, &mnemonic_passphrase(), 0)?;
+            let mut wallet = wallet_from_account(account)?;
+            seal_wallet(&mut wallet, &passphrase)?;
+            save_active_wallet(&wallet)?;
+
+            // The key stays behind `wallet export --reveal-private-key`.
             println!("address={}", wallet.address);
-            println!("private_key={}", wallet.private_key);
-            println!("wallet_store={}", wallet_store_path().display());
+            println!("mnemonic={}", mnemonic);
             Ok(())
         }
-        WalletCommand::Import { private_key } => {
+        WalletCommand::Import {
+            private_key: None,
+            mnemonic_file,
+            index,
+            passphrase_file,
+        } => {
+            let passphrase = require_passphrase(passphrase_file.as_deref())?;
+            let mnemonic = require_mnemonic(mnemonic_file.as_deref())?;
+            let account =
+                chiral_network::wallet::derive_account(&mnemonic, &mnemonic_passphrase(), index)?;
+            let mut wallet = wallet_from_account(account)?;
+            seal_wallet(&mut wallet, &passphrase)?;
+            save_active_wallet(&wallet)?;
+            println!(
+                "Imported wallet {} ({})",
+                wallet.address,
+                wallet.derivation_path.as_deref().unwrap_or_default()
+            );
+            Ok(())
+        }
+        WalletCommand::Import {
+            private_key: Some(private_key),
+            passphrase_file,
+            ..
+        }
+        | WalletCommand::Login {
+            private_key: Some(private_key),
+            passphrase_file,
+        } => {
+            let passphrase = require_passphrase(passphrase_file.as_deref())?;
             let normalized = normalize_private_key(&private_key)?;
-            let address = address_from_private_key(&normalized)?;
-            let wallet = WalletProfile {
+            let address = chiral_network::wallet::address_from_private_key(&normalized)?;
+            let mut wallet = WalletProfile {
                 address,
                 private_key: normalized,
+                keystore: None,
+                derivation_path: None,
                 created_at: now_secs()?,
             };
-            let mut store = load_wallet_store()?;
-            store.active = Some(wallet.clone());
-            save_wallet_store(&store)?;
+            seal_wallet(&mut wallet, &passphrase)?;
+            save_active_wallet(&wallet)?;
             println!("Imported wallet {}", wallet.address);
             Ok(())
         }
-        WalletCommand::Login { private_key } => {
-            if let Some(pk) = private_key {
-                let normalized = normalize_private_key(&pk)?;
-                let address = address_from_private_key(&normalized)?;
-                let wallet = WalletProfile {
-                    address,
-                    private_key: normalized,
-                    created_at: now_secs()?,
-                };
-                let mut store = load_wallet_store()?;
-                store.active = Some(wallet.clone());
-                save_wallet_store(&store)?;
-                println!("Imported wallet {}", wallet.address);
-                return Ok(());
+        WalletCommand::Derive {
+            index,
+            count,
+            mnemonic_file,
+            activate,
+            passphrase_file,
+        } => {
+            if activate && count != 1 {
+                return Err("--activate takes a single account; drop --count".to_string());
             }
+            let end = index
+                .checked_add(count)
+                .filter(|end| *end <= 0x8000_0000)
+                .ok_or("Account index out of range".to_string())?;
+            let mnemonic = require_mnemonic(mnemonic_file.as_deref())?;
+            let seed = chiral_network::wallet::mnemonic_to_seed(&mnemonic, &mnemonic_passphrase())?;
+            for i in index..end {
+                let account = chiral_network::wallet::derive_account_from_seed(&seed, i)?;
+                println!("{}\t{}\t{}", account.index, account.path, account.address);
+                if activate {
+                    let passphrase = require_passphrase(passphrase_file.as_deref())?;
+                    let mut wallet = wallet_from_account(account)?;
+                    seal_wallet(&mut wallet, &passphrase)?;
+                    save_active_wallet(&wallet)?;
+                    println!("Active wallet: {}", wallet.address);
+                }
+            }
+            Ok(())
+        }
+        WalletCommand::Login {
+            private_key: None, ..
+        } => {
             let wallet = require_wallet()?;
             println!("Active wallet: {}", wallet.address);
             Ok(())
@@ -3153,16 +3512,64 @@ async fn handle_wallet(cmd: WalletCommand) -> Result<(), String> {
         WalletCommand::Show => {
             let wallet = require_wallet()?;
             println!("address={}", wallet.address);
+            println!("encrypted={}", wallet.keystore.is_some());
+            if let Some(path) = &wallet.derivation_path {
+                println!("derivation_path={}", path);
+            }
             Ok(())
         }
-        WalletCommand::Export { reveal_private_key } => {
+        WalletCommand::Export {
+            reveal_private_key,
+            passphrase_file,
+        } => {
             let wallet = require_wallet()?;
             println!("address={}", wallet.address);
             if reveal_private_key {
-                println!("private_key={}", wallet.private_key);
+                let private_key = unlock_wallet_key(&wallet, passphrase_file.as_deref())?;
+                println!("private_key={}", private_key);
             }
             Ok(())
         }
+        WalletCommand::Encrypt { passphrase_file } => {
+            let passphrase = require_passphrase(passphrase_file.as_deref())?;
+            let store = load_wallet_store()?;
+            let mut wallet = store
+                .active
+                .ok_or("No active wallet to encrypt".to_string())?;
+            if wallet.keystore.is_some() {
+                println!("Wallet {} is already encrypted", wallet.address);
+                return Ok(());
+            }
+            seal_wallet(&mut wallet, &passphrase)?;
+            save_active_wallet(&wallet)?;
+            println!("Encrypted wallet {}", wallet.address);
+            Ok(())
+        }
+        WalletCommand::Unlock {
+            passphrase_file,
+            port,
+        } => {
+            let passphrase = require_passphrase(passphrase_file.as_deref())?;
+            let value = daemon_post_json(
+                port,
+                "/api/headless/wallet/unlock",
+                &serde_json::json!({ "passphrase": passphrase }),
+            )
+            .await?;
+            println!(
+                "Unlocked wallet {}",
+                value.get("address").and_then(Value::as_str).unwrap_or("")
+            );
+            Ok(())
+        }
+        WalletCommand::Lock { port } => {
+            let value = daemon_post_empty(port, "/api/headless/wallet/lock").await?;
+            println!(
+                "Locked wallet {}",
+                value.get("address").and_then(Value::as_str).unwrap_or("")
+            );
+            Ok(())
+        }
     }
 }
 
@@ -3197,12 +3604,21 @@ async fn handle_account(cmd: AccountCommand) -> Result<(), String> {
             address,
             max_blocks,
             limit,
+            offset,
+            since,
+            until,
         } => {
             let addr = match address {
                 Some(v) => v,
                 None => require_wallet()?.address,
             };
-            let txs = get_transaction_history(&addr, max_blocks, limit).await?;
+            let query = IndexQuery {
+                offset,
+                limit: Some(limit),
+                since,
+                until,
+            };
+            let txs = get_transaction_history(&addr, max_blocks, &query).await?;
             for tx in txs {
                 println!(
                     "{} {} {}->{} value={}CHI block={} ts={}",
@@ -3297,7 +3713,32 @@ async fn handle_dht(cmd: DhtCommand) -> Result<(), String> {
 
 async fn handle_download(cmd: DownloadCommand) -> Result<(), String> {
     match cmd {
-        DownloadCommand::Search { hash, port } => {
+        DownloadCommand::Search {
+            query: Some(query),
+            limit,
+            port,
+            ..
+        } => {
+            let payload = serde_json::json!({ "query": query, "limit": limit });
+            let result =
+                daemon_post_json(port, "/api/headless/file/keyword-search", &payload).await?;
+            let results = result["results"].as_array().cloned().unwrap_or_default();
+            println!("results={}", results.len());
+            for r in results {
+                println!(
+                    "  hash={} file_name={} file_size={} seeders={} reputation={} score={}",
+                    r["hash"].as_str().unwrap_or_default(),
+                    r["fileName"].as_str().unwrap_or_default(),
+                    r["fileSize"].as_u64().unwrap_or(0),
+                    r["seederCount"].as_u64().unwrap_or(0),
+                    r["reputation"].as_f64().unwrap_or(0.0),
+                    r["score"].as_f64().unwrap_or(0.0),
+                );
+            }
+            Ok(())
+        }
+        DownloadCommand::Search { hash, port, .. } => {
+            let hash = hash.ok_or("--hash or --query is required")?;
             let key = format!("chiral_file_{}", hash);
             let maybe_json = dht_get_value(port, &key).await?;
             if let Some(raw) = maybe_json {
@@ -3634,8 +4075,6 @@ async fn handle_drop(cmd: DropCommand) -> Result<(), String> {
                     "fileName": name,
                     "filePath": file_path,
                     "priceWei": "0",
-                    "senderWallet": "",
-                    "fileHash": "",
                 }),
             )
             .await?;
@@ -3659,7 +4098,6 @@ async fn handle_drop(cmd: DropCommand) -> Result<(), String> {
             file_path,
             price_wei,
             sender_wallet,
-            file_hash,
             port,
         } => {
             let name = Path::new(&file_path)
@@ -3669,11 +4107,6 @@ async fn handle_drop(cmd: DropCommand) -> Result<(), String> {
                 .to_string();
             let now = now_secs()?;
             let transfer_id = format!("drop-paid-{}", now);
-            let hash = if let Some(h) = file_hash {
-                h
-            } else {
-                compute_file_hash(Path::new(&file_path))?
-            };
 
             let value = daemon_post_json(
                 port,
@@ -3685,7 +4118,6 @@ async fn handle_drop(cmd: DropCommand) -> Result<(), String> {
                     "filePath": file_path,
                     "priceWei": price_wei,
                     "senderWallet": sender_wallet,
-                    "fileHash": hash,
                 }),
             )
             .await?;
@@ -3708,18 +4140,20 @@ async fn handle_drop(cmd: DropCommand) -> Result<(), String> {
             let value = daemon_get_json(port, "/api/headless/drop/inbox").await?;
             print_json(&value)
         }
-        DropCommand::Accept {
-            transfer_id,
-            download_dir,
-            port,
-        } => {
+        DropCommand::Accept { transfer_id, port } => {
             let value = daemon_post_json(
                 port,
                 "/api/headless/drop/accept",
-                &serde_json::json!({
-                    "transferId": transfer_id,
-                    "downloadDir": download_dir,
-                }),
+                &serde_json::json!({ "transferId": transfer_id }),
+            )
+            .await?;
+            print_json(&value)
+        }
+        DropCommand::Cancel { transfer_id, port } => {
+            let value = daemon_post_json(
+                port,
+                "/api/headless/drop/cancel",
+                &serde_json::json!({ "transferId": transfer_id }),
             )
             .await?;
             print_json(&value)
@@ -4072,18 +4506,86 @@ async fn handle_mining(cmd: MiningCommand) -> Result<(), String> {
             let value = daemon_get_json(port, "/api/headless/mining/status").await?;
             print_json(&value)
         }
-        MiningCommand::Blocks { max, port } => {
-            let value = daemon_get_json_with_query(
-                port,
-                "/api/headless/mining/blocks",
-                &[("max", max.to_string())],
-            )
-            .await?;
+        MiningCommand::Blocks {
+            max,
+            offset,
+            since,
+            until,
+            address,
+            port,
+        } => {
+            let mut query = vec![("max", max.to_string()), ("offset", offset.to_string())];
+            if let Some(since) = since {
+                query.push(("since", since.to_string()));
+            }
+            if let Some(until) = until {
+                query.push(("until", until.to_string()));
+            }
+            if let Some(address) = address {
+                query.push(("address", address));
+            }
+            let value =
+                daemon_get_json_with_query(port, "/api/headless/mining/blocks", &query).await?;
             print_json(&value)
         }
     }
 }
 
+async fn handle_events(cmd: EventsCommand) -> Result<(), String> {
+    match cmd {
+        EventsCommand::Tail { types, json, port } => {
+            let client = daemon_http_client()?;
+            let mut request = client
+                .get(format!("{}/api/headless/events", headless_base_url(port)))
+                .header(reqwest::header::ACCEPT, "text/event-stream");
+            if let Some(types) = types {
+                request = request.query(&[("types", types)]);
+            }
+            let mut resp = request
+                .send()
+                .await
+                .map_err(|e| format!("Request failed: {}", e))?;
+            if !resp.status().is_success() {
+                let status = resp.status();
+                let body = resp.text().await.unwrap_or_default();
+                return Err(format!("HTTP {}: {}", status, body));
+            }
+
+            // Each SSE frame carries the whole event as JSON on one `data:`
+            // line; keep-alive comments and `event:` lines are skipped.
+            let mut pending = String::new();
+            while let Some(chunk) = resp
+                .chunk()
+                .await
+                .map_err(|e| format!("Event stream failed: {}", e))?
+            {
+                pending.push_str(&String::from_utf8_lossy(&chunk));
+                while let Some(newline) = pending.find('\n') {
+                    let line: String = pending.drain(..=newline).collect();
+                    let Some(data) = line.trim_end().strip_prefix("data:") else {
+                        continue;
+                    };
+                    let data = data.trim_start();
+                    if json {
+                        println!("{}", data);
+                        continue;
+                    }
+                    match serde_json::from_str::<Value>(data) {
+                        Ok(event) => println!(
+                            "{} {} {}",
+                            event["timestamp"],
+                            event["event"].as_str().unwrap_or("?"),
+                            event["payload"]
+                        ),
+                        Err(_) => println!("{}", data),
+                    }
+                }
+            }
+            Err("Daemon closed the event stream".to_string())
+        }
+    }
+}
+
 async fn handle_geth(cmd: GethCommand) -> Result<(), String> {
     match cmd {
         GethCommand::Start {
@@ -4223,8 +4725,8 @@ mod tests {
     #[test]
     fn drive_publish_credentials_derive_wallet_from_private_key() {
         let private_key = "4c0883a69102937d6231471b5dbb6204fe512961708279cea2c89f1f7a0f2c4f";
-        let expected_wallet =
-            address_from_private_key(private_key).expect("test private key should derive wallet");
+        let expected_wallet = chiral_network::wallet::address_from_private_key(private_key)
+            .expect("test private key should derive wallet");
 
         let credentials =
             drive_publish_credentials("0", None, Some(format!("  0x{}  ", private_key)))
@@ -4278,6 +4780,7 @@ async fn main() {
         Commands::Market { cmd } => handle_market(cmd).await,
         Commands::Mining { cmd } => handle_mining(cmd).await,
         Commands::Geth { cmd } => handle_geth(cmd).await,
+        Commands::Events { cmd } => handle_events(cmd).await,
     };
 
     if let Err(err) = result {
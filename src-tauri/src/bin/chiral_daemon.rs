@@ -1,7 +1,13 @@
 use axum::{
-    extract::{Query, State},
+    extract::{
+        ws::{Message as WsMessage, WebSocket, WebSocketUpgrade},
+        Query, State,
+    },
     http::StatusCode,
-    response::{IntoResponse, Response},
+    response::{
+        sse::{Event as SseEvent, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
     routing::{get, post},
     Json, Router,
 };
@@ -16,13 +22,21 @@ use std::sync::Arc;
 use tokio::sync::Mutex;
 use tower_http::cors::{Any, CorsLayer};
 
+use chiral_network::bandwidth::{self, BandwidthLimits};
+use chiral_network::cdn_server::CdnState;
+use chiral_network::daemon_auth;
 use chiral_network::dht;
 use chiral_network::drive_api::DriveState;
-use chiral_network::event_sink::EventSink;
-use chiral_network::file_transfer::{FileTransferService, PendingTransfer};
+use chiral_network::event_sink::{self, DaemonEvent, EventFilter, EventSink};
+use chiral_network::file_transfer::{FileTransferService, PendingTransfer, TransferStatus};
 use chiral_network::geth::{validate_mining_threads, GethDownloader, GethProcess};
 use chiral_network::hosting_server::{self, HostingServerState};
+use chiral_network::kad_store::{self, StoreMode};
+use chiral_network::keystore::{self, Keystore, WalletKeyFile};
+use chiral_network::metrics;
+use chiral_network::nat;
 use chiral_network::rating_storage::RatingState;
+use chiral_network::search_index;
 
 #[derive(Parser, Debug)]
 #[command(name = "chiral_daemon")]
@@ -56,27 +70,87 @@ struct DaemonArgs {
     #[arg(long, env = "CHIRAL_MINING_THREADS", default_value_t = 1)]
     mining_threads: u32,
 
-    /// Pin the libp2p TCP port. Default: OS-assigned (0). Set this on k3s/Docker
+    /// Pin the libp2p port (TCP and QUIC/UDP). Default: OS-assigned (0). Set this on k3s/Docker
     /// nodes to match the externally exposed NodePort, otherwise the random port
     /// the daemon picks won't be reachable and stale multiaddrs end up in the DHT.
     #[arg(long, env = "CHIRAL_P2P_PORT")]
     p2p_port: Option<u16>,
 
-    /// Path to a wallet-key file. The file's contents must be a hex
-    /// secp256k1 private key (with or without leading `0x`). When set,
+    /// Kademlia record store: `memory` (default) or `disk`. `disk` keeps
+    /// the records and provider entries this node holds for the network
+    /// across restarts, under `<data dir>/dht_store`.
+    #[arg(long, env = "CHIRAL_DHT_STORE")]
+    dht_store: Option<StoreMode>,
+
+    /// Path to a wallet-key file: a v3 JSON keystore, or (legacy) a hex
+    /// secp256k1 private key with or without leading `0x`. When set,
     /// the daemon loads it at startup and populates `state.wallet` so
     /// the CDN module can sign FileInfo / SeederInfo records (FM-A07,
     /// FM-A08, FM-A09). Without it, the CDN runs with empty signatures
     /// and clients reject every record it publishes — which broke
     /// every existing CDN upload after the FM-Agent enforcement
-    /// landed.
+    /// landed. A keystore without a passphrase leaves the wallet
+    /// locked until `POST /api/headless/wallet/unlock`; a plaintext
+    /// file is rewritten as a keystore once a passphrase is available.
     #[arg(long, env = "CHIRAL_WALLET_KEY_FILE")]
     wallet_key_file: Option<PathBuf>,
+
+    /// File whose first line is the wallet passphrase. Falls back to
+    /// `CHIRAL_WALLET_PASSPHRASE`.
+    #[arg(long, env = "CHIRAL_WALLET_PASSPHRASE_FILE")]
+    wallet_passphrase_file: Option<PathBuf>,
+
+    /// Total upload rate for seeding, in KiB/s (0 = unlimited)
+    #[arg(long, env = "CHIRAL_UPLOAD_LIMIT_KIB", default_value_t = 0)]
+    upload_limit_kib: u64,
+
+    /// Total download rate, in KiB/s (0 = unlimited)
+    #[arg(long, env = "CHIRAL_DOWNLOAD_LIMIT_KIB", default_value_t = 0)]
+    download_limit_kib: u64,
+
+    /// Upload rate to any single peer, in KiB/s (0 = unlimited)
+    #[arg(long, env = "CHIRAL_PEER_UPLOAD_LIMIT_KIB", default_value_t = 0)]
+    peer_upload_limit_kib: u64,
+
+    /// Download rate from any single peer, in KiB/s (0 = unlimited)
+    #[arg(long, env = "CHIRAL_PEER_DOWNLOAD_LIMIT_KIB", default_value_t = 0)]
+    peer_download_limit_kib: u64,
+
+    /// Peers served at once; others queue for a slot (0 = unlimited)
+    #[arg(long, env = "CHIRAL_UPLOAD_SLOTS", default_value_t = 0)]
+    upload_slots: usize,
+
+    /// Address for the gateway port (sites, Drive share links, CDN)
+    #[arg(long, env = "CHIRAL_DAEMON_BIND", default_value = "0.0.0.0")]
+    bind: std::net::IpAddr,
+
+    /// Serve `/api/headless/*` on this address (e.g. 127.0.0.1:9420)
+    /// instead of the gateway port. Requests still need a token.
+    #[arg(long, env = "CHIRAL_DAEMON_ADMIN_BIND")]
+    admin_bind: Option<SocketAddr>,
+
+    /// Also serve `/api/headless/*` on this Unix socket. The socket is
+    /// created owner-only and requests over it need no token.
+    #[arg(long, env = "CHIRAL_DAEMON_ADMIN_SOCKET")]
+    admin_socket: Option<PathBuf>,
+}
+
+impl DaemonArgs {
+    fn bandwidth_limits(&self) -> BandwidthLimits {
+        BandwidthLimits {
+            upload_bps: self.upload_limit_kib.saturating_mul(1024),
+            download_bps: self.download_limit_kib.saturating_mul(1024),
+            per_peer_upload_bps: self.peer_upload_limit_kib.saturating_mul(1024),
+            per_peer_download_bps: self.peer_download_limit_kib.saturating_mul(1024),
+            upload_slots: self.upload_slots,
+        }
+    }
 }
 
 #[derive(Clone, serde::Serialize)]
 struct WalletInfo {
     address: String,
+    /// Empty while the wallet is locked (or address-only).
     private_key: String,
 }
 
@@ -86,6 +160,8 @@ struct HostRegistryEntry {
     peer_id: String,
     wallet_address: String,
     updated_at: u64,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    signature: String,
 }
 
 fn host_registry_from_dht_value(
@@ -117,6 +193,8 @@ fn host_registry_after_publish(
         peer_id,
         wallet_address,
         updated_at,
+        // Signed by the DHT layer when the registry is published.
+        signature: String::new(),
     });
     registry
 }
@@ -129,6 +207,12 @@ struct HeadlessRuntimeState {
     download_credentials: dht::DownloadCredentialsMap,
     geth: Arc<Mutex<GethProcess>>,
     wallet: Arc<Mutex<Option<WalletInfo>>>,
+    /// Encrypted copy of the wallet key; what `wallet/unlock` decrypts.
+    keystore: Arc<Mutex<Option<Keystore>>>,
+    /// Set once the CDN is built so unlock/lock can swap its signing key.
+    cdn: Arc<Mutex<Option<Arc<CdnState>>>>,
+    /// Fans DHT and transfer events out to `/api/headless/events`.
+    events: EventSink,
 }
 
 impl HeadlessRuntimeState {
@@ -140,6 +224,9 @@ impl HeadlessRuntimeState {
             download_credentials: Arc::new(Mutex::new(HashMap::new())),
             geth: Arc::new(Mutex::new(GethProcess::new())),
             wallet: Arc::new(Mutex::new(None)),
+            keystore: Arc::new(Mutex::new(None)),
+            cdn: Arc::new(Mutex::new(None)),
+            events: EventSink::broadcast(),
         }
     }
 
@@ -372,9 +459,6 @@ struct SendFileRequest {
     price_wei: String,
     #[serde(default)]
     sender_wallet: String,
-    #[serde(default)]
-    file_hash: String,
-    file_size: Option<u64>,
 }
 
 #[derive(Deserialize)]
@@ -398,6 +482,20 @@ struct RegisterSharedFileRequest {
     /// (downloaders will reject; use for free-only or proxy seeding).
     #[serde(default)]
     private_key: String,
+    /// Optional search metadata; see `search_index`.
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct KeywordSearchRequest {
+    #[serde(default)]
+    query: String,
+    #[serde(default)]
+    limit: Option<usize>,
 }
 
 fn register_shared_file_protocol(protocol: Option<&str>) -> String {
@@ -448,7 +546,6 @@ struct UnregisterSharedFileRequest {
 #[serde(rename_all = "camelCase")]
 struct AcceptTransferRequest {
     transfer_id: String,
-    download_dir: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -477,6 +574,10 @@ struct LogsQuery {
 #[derive(Deserialize)]
 struct BlocksQuery {
     max: Option<u64>,
+    address: Option<String>,
+    offset: Option<usize>,
+    since: Option<u64>,
+    until: Option<u64>,
 }
 
 /// GET /api/health — liveness probe (always returns 200 if server is up)
@@ -526,10 +627,99 @@ async fn runtime_status(State(state): State<Arc<HeadlessRuntimeState>>) -> Respo
         "dhtRunning": dht_running,
         "peerId": peer_id,
         "gethStatus": geth_status,
+        "bandwidth": bandwidth::global().stats(),
     }))
     .into_response()
 }
 
+/// GET /api/headless/bandwidth — current limits and throughput
+async fn bandwidth_status() -> Response {
+    Json(bandwidth::global().stats()).into_response()
+}
+
+/// PUT /api/headless/bandwidth — replace the limits (bytes/s, 0 = unlimited)
+async fn bandwidth_set_limits(Json(limits): Json<BandwidthLimits>) -> Response {
+    bandwidth::global().set_limits(limits);
+    println!("[BANDWIDTH] Limits: {:?}", limits);
+    Json(bandwidth::global().stats()).into_response()
+}
+
+/// GET /metrics — Prometheus scrape. Geth and CDN usage are polled here;
+/// everything else is recorded as it happens.
+async fn metrics_scrape(State(state): State<Arc<HeadlessRuntimeState>>) -> Response {
+    {
+        let geth = state.geth.lock().await;
+        match geth.get_status().await {
+            Ok(status) => metrics::global().set_geth_status(&status),
+            Err(e) => eprintln!("[METRICS] Geth status unavailable: {}", e),
+        }
+        match geth.get_mining_status().await {
+            Ok(status) => metrics::global().set_mining_status(&status),
+            Err(e) => eprintln!("[METRICS] Mining status unavailable: {}", e),
+        }
+    }
+    let cdn = state.cdn.lock().await.clone();
+    if let Some(cdn) = cdn {
+        cdn.record_usage_metrics().await;
+    }
+    metrics::metrics_response()
+}
+
+#[derive(Deserialize)]
+struct EventsQuery {
+    /// Comma-separated event types; `download-*` matches a prefix.
+    types: Option<String>,
+}
+
+/// GET /api/headless/events?types=download-*,file-transfer-request — live
+/// DHT and transfer events. Sent as Server-Sent Events, or as one JSON text
+/// frame per event when the request is a WebSocket upgrade.
+async fn events_stream(
+    State(state): State<Arc<HeadlessRuntimeState>>,
+    Query(query): Query<EventsQuery>,
+    ws: Option<WebSocketUpgrade>,
+) -> Response {
+    let Some(rx) = state.events.subscribe() else {
+        return json_error(StatusCode::SERVICE_UNAVAILABLE, "Event stream unavailable");
+    };
+    let filter = EventFilter::parse(query.types.as_deref());
+    if let Some(ws) = ws {
+        return ws.on_upgrade(move |socket| forward_events_ws(socket, rx, filter));
+    }
+    let stream = futures::stream::unfold((rx, filter), |(mut rx, filter)| async move {
+        let event = event_sink::next_matching(&mut rx, &filter).await?;
+        let sse = SseEvent::default()
+            .event(event.event.clone())
+            .data(serde_json::to_string(&event).unwrap_or_default());
+        Some((Ok::<_, std::convert::Infallible>(sse), (rx, filter)))
+    });
+    Sse::new(stream)
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+
+async fn forward_events_ws(
+    mut socket: WebSocket,
+    mut rx: tokio::sync::broadcast::Receiver<DaemonEvent>,
+    filter: EventFilter,
+) {
+    loop {
+        tokio::select! {
+            event = event_sink::next_matching(&mut rx, &filter) => {
+                let Some(event) = event else { break };
+                let text = serde_json::to_string(&event).unwrap_or_default();
+                if socket.send(WsMessage::Text(text)).await.is_err() {
+                    break;
+                }
+            }
+            incoming = socket.recv() => match incoming {
+                Some(Ok(WsMessage::Close(_))) | Some(Err(_)) | None => break,
+                _ => {}
+            },
+        }
+    }
+}
+
 async fn dht_start(State(state): State<Arc<HeadlessRuntimeState>>) -> Response {
     let mut guard = state.dht.lock().await;
     if guard.is_some() {
@@ -542,7 +732,7 @@ async fn dht_start(State(state): State<Arc<HeadlessRuntimeState>>) -> Response {
         Arc::clone(&state.download_credentials),
     ));
 
-    match svc.start_headless().await {
+    match svc.start_headless(state.events.clone()).await {
         Ok(message) => {
             *guard = Some(svc);
             drop(guard);
@@ -668,7 +858,10 @@ async fn dht_ping(
         return json_error(StatusCode::BAD_REQUEST, "DHT not running");
     };
 
-    match svc.ping_peer_headless(req.peer_id).await {
+    match svc
+        .ping_peer_headless(req.peer_id, state.events.clone())
+        .await
+    {
         Ok(message) => Json(json!({ "status": "ok", "message": message })).into_response(),
         Err(err) => json_error(StatusCode::BAD_REQUEST, err),
     }
@@ -724,39 +917,76 @@ async fn dht_request_file(
     }
 }
 
+/// Offer a file on disk to a peer over ChiralDrop. Only the offer is sent;
+/// the peer pulls the content in chunks after accepting it. Paid offers are
+/// signed by the daemon's wallet and pay out to it.
 async fn dht_send_file(
     State(state): State<Arc<HeadlessRuntimeState>>,
     Json(req): Json<SendFileRequest>,
 ) -> Response {
-    let data = match tokio::fs::read(&req.file_path).await {
-        Ok(v) => v,
-        Err(e) => {
+    let price_wei = if req.price_wei.trim().is_empty() {
+        0
+    } else {
+        match req.price_wei.trim().parse::<u128>() {
+            Ok(v) => v,
+            Err(_) => return json_error(StatusCode::BAD_REQUEST, "priceWei must be a wei amount"),
+        }
+    };
+
+    let (wallet_address, private_key) = {
+        let wallet = state.wallet.lock().await;
+        wallet
+            .as_ref()
+            .map(|w| (w.address.clone(), w.private_key.clone()))
+            .unwrap_or_default()
+    };
+    if price_wei > 0 {
+        if private_key.is_empty() {
+            return json_error(
+                StatusCode::CONFLICT,
+                "Wallet must be unlocked to offer a paid file",
+            );
+        }
+        if !req.sender_wallet.is_empty() && !req.sender_wallet.eq_ignore_ascii_case(&wallet_address)
+        {
             return json_error(
                 StatusCode::BAD_REQUEST,
-                format!("Failed to read file {}: {}", req.file_path, e),
-            )
+                "senderWallet does not match the daemon wallet",
+            );
         }
-    };
+    }
 
     let Some(svc) = state.dht_service().await else {
         return json_error(StatusCode::BAD_REQUEST, "DHT not running");
     };
 
-    let file_size = req.file_size.unwrap_or(data.len() as u64);
     match svc
-        .send_file(
-            req.peer_id,
+        .offer_file(
+            req.peer_id.clone(),
             req.transfer_id,
+            PathBuf::from(&req.file_path),
             req.file_name,
-            data,
-            req.price_wei,
-            req.sender_wallet,
-            req.file_hash,
-            file_size,
+            price_wei,
+            wallet_address,
+            private_key,
         )
         .await
     {
-        Ok(()) => Json(json!({ "status": "ok" })).into_response(),
+        Ok(offer) => {
+            let response = json!({
+                "status": "offered",
+                "transferId": offer.transfer_id,
+                "fileHash": offer.file_hash,
+                "fileSize": offer.file_size,
+            });
+            state
+                .file_transfer
+                .lock()
+                .await
+                .record_outgoing_offer(state.events.clone(), req.peer_id, offer)
+                .await;
+            Json(response).into_response()
+        }
         Err(err) => json_error(StatusCode::BAD_REQUEST, err),
     }
 }
@@ -1070,6 +1300,8 @@ async fn dht_register_shared_file(
         price_wei: req.price_wei.clone(),
         wallet_address: req.wallet_address.clone(),
         private_key: req.private_key.clone(),
+        description: None,
+        tags: Vec::new(),
     };
     let blob = match signed_file_metadata_json_for_register(&metadata_req, &protocol) {
         Ok(blob) => blob,
@@ -1092,6 +1324,19 @@ async fn dht_register_shared_file(
         )))
         .into_response();
     }
+    chiral_network::publish_search_keywords(
+        &svc,
+        search_index::KeywordEntry::new(
+            &file_hash,
+            &file_name,
+            file_size,
+            &req.tags,
+            req.description.as_deref(),
+        ),
+        &req.wallet_address,
+        Some(&req.private_key),
+    )
+    .await;
 
     Json(json!({ "status": "ok", "dhtPublished": true })).into_response()
 }
@@ -1187,31 +1432,153 @@ async fn drop_outgoing(State(state): State<Arc<HeadlessRuntimeState>>) -> Respon
     Json(pending).into_response()
 }
 
+/// Accept a ChiralDrop offer and start pulling it from the sender into the
+/// daemon's download directory. Paid offers are paid from the daemon wallet.
 async fn drop_accept(
     State(state): State<Arc<HeadlessRuntimeState>>,
     Json(req): Json<AcceptTransferRequest>,
 ) -> Response {
-    let svc = state.file_transfer.lock().await;
-    match svc
-        .accept_transfer(EventSink::noop(), req.transfer_id, req.download_dir)
+    let Some(svc) = state.dht_service().await else {
+        return json_error(StatusCode::BAD_REQUEST, "DHT not running");
+    };
+    let file_transfer = Arc::clone(&state.file_transfer);
+    let transfer = match file_transfer
+        .lock()
+        .await
+        .accept_transfer(&req.transfer_id)
         .await
     {
-        Ok(path) => Json(json!({ "status": "accepted", "path": path })).into_response(),
-        Err(err) => json_error(StatusCode::BAD_REQUEST, err),
+        Ok(t) => t,
+        Err(err) => return json_error(StatusCode::BAD_REQUEST, err),
+    };
+    let request_id = format!("chiraldrop-{}", req.transfer_id);
+
+    if transfer.is_paid() {
+        let wallet = state.wallet.lock().await.clone();
+        match wallet.filter(|w| !w.private_key.is_empty()) {
+            Some(w) => {
+                state.download_credentials.lock().await.insert(
+                    request_id.clone(),
+                    dht::DownloadCredentials {
+                        wallet_address: w.address,
+                        private_key: w.private_key,
+                        folder_hash: None,
+                        folder_payment_tx: None,
+                        pay_with_vouchers: false,
+                    },
+                );
+            }
+            None => {
+                file_transfer
+                    .lock()
+                    .await
+                    .reset_transfer(&req.transfer_id)
+                    .await;
+                return json_error(
+                    StatusCode::CONFLICT,
+                    "Wallet must be unlocked to accept a paid transfer",
+                );
+            }
+        }
+    }
+
+    if let Err(err) = svc
+        .request_file(
+            transfer.peer_id.clone(),
+            transfer.file_hash.clone(),
+            request_id.clone(),
+            Vec::new(),
+            None,
+        )
+        .await
+    {
+        state.download_credentials.lock().await.remove(&request_id);
+        file_transfer
+            .lock()
+            .await
+            .reset_transfer(&req.transfer_id)
+            .await;
+        return json_error(StatusCode::BAD_GATEWAY, err);
+    }
+
+    if let Err(err) = file_transfer
+        .lock()
+        .await
+        .start_transfer_download(&req.transfer_id, request_id.clone())
+        .await
+    {
+        return json_error(StatusCode::BAD_REQUEST, err);
+    }
+    Json(json!({
+        "status": "downloading",
+        "requestId": request_id,
+        "fileHash": transfer.file_hash,
+        "fileSize": transfer.file_size,
+    }))
+    .into_response()
+}
+
+/// Stop an accepted ChiralDrop transfer and delete its partial download.
+async fn drop_cancel(
+    State(state): State<Arc<HeadlessRuntimeState>>,
+    Json(req): Json<AcceptTransferRequest>,
+) -> Response {
+    let file_transfer = Arc::clone(&state.file_transfer);
+    let transfer = match file_transfer
+        .lock()
+        .await
+        .cancel_transfer(&req.transfer_id)
+        .await
+    {
+        Ok(transfer) => transfer,
+        Err(err) => return json_error(StatusCode::BAD_REQUEST, err),
+    };
+    if let Some(svc) = state.dht_service().await {
+        notify_drop_sender(&svc, &transfer, TransferStatus::Cancelled).await;
+        if let Some(request_id) = transfer.download_request_id {
+            if let Err(err) = svc.cancel_download(request_id).await {
+                return json_error(StatusCode::BAD_REQUEST, err);
+            }
+        }
     }
+    Json(json!({ "status": "cancelled" })).into_response()
 }
 
 async fn drop_decline(
     State(state): State<Arc<HeadlessRuntimeState>>,
     Json(req): Json<KeyRequest>,
 ) -> Response {
-    let svc = state.file_transfer.lock().await;
-    match svc.decline_transfer(req.key).await {
-        Ok(()) => Json(json!({ "status": "declined" })).into_response(),
+    let declined = state
+        .file_transfer
+        .lock()
+        .await
+        .decline_transfer(req.key)
+        .await;
+    match declined {
+        Ok(transfer) => {
+            if let Some(svc) = state.dht_service().await {
+                notify_drop_sender(&svc, &transfer, TransferStatus::Declined).await;
+            }
+            Json(json!({ "status": "declined" })).into_response()
+        }
         Err(err) => json_error(StatusCode::BAD_REQUEST, err),
     }
 }
 
+/// Best-effort notice to the sender of `transfer` that we closed it.
+async fn notify_drop_sender(
+    svc: &dht::DhtService,
+    transfer: &PendingTransfer,
+    status: TransferStatus,
+) {
+    if let Err(err) = svc.close_file_offer(transfer, status).await {
+        eprintln!(
+            "Failed to tell {} that ChiralDrop {} closed: {}",
+            transfer.peer_id, transfer.transfer_id, err
+        );
+    }
+}
+
 async fn geth_install() -> Response {
     let downloader = GethDownloader::new();
     match downloader.download_geth(|_progress| {}).await {
@@ -1289,9 +1656,15 @@ async fn mining_blocks(
     State(state): State<Arc<HeadlessRuntimeState>>,
     Query(q): Query<BlocksQuery>,
 ) -> Response {
+    let query = chiral_network::chain_index::IndexQuery {
+        offset: q.offset.unwrap_or(0),
+        limit: Some(q.max.unwrap_or(500) as usize),
+        since: q.since,
+        until: q.until,
+    };
     let geth = state.geth.lock().await;
-    match geth.get_mined_blocks(q.max.unwrap_or(500)).await {
-        Ok(blocks) => Json(blocks).into_response(),
+    match geth.get_mined_blocks(q.address.as_deref(), &query).await {
+        Ok(blocks) => Json(blocks.items).into_response(),
         Err(err) => json_error(StatusCode::BAD_REQUEST, err),
     }
 }
@@ -1314,92 +1687,196 @@ async fn set_miner_address(
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct ImportWalletRequest {
+    #[serde(default)]
     private_key: String,
+    mnemonic: Option<String>,
+    mnemonic_passphrase: Option<String>,
+    index: Option<u32>,
+    passphrase: Option<String>,
 }
 
-/// Derive `0x`-prefixed lowercase Ethereum address from a 32-byte
-/// secp256k1 private key.
-fn address_from_private_key(priv_bytes: &[u8; 32]) -> Result<String, String> {
-    let secp = secp256k1::Secp256k1::new();
-    let secret_key = secp256k1::SecretKey::from_slice(priv_bytes)
-        .map_err(|e| format!("invalid private key: {}", e))?;
-    let public_key = secp256k1::PublicKey::from_secret_key(&secp, &secret_key);
-    let pub_bytes = public_key.serialize_uncompressed();
-    use tiny_keccak::{Hasher, Keccak};
-    let mut keccak = Keccak::v256();
-    keccak.update(&pub_bytes[1..]);
-    let mut hash = [0u8; 32];
-    keccak.finalize(&mut hash);
-    Ok(format!("0x{}", hex::encode(&hash[12..])))
-}
-
-/// Read a wallet-key file: trim whitespace, strip optional `0x`,
-/// require 32-byte hex. Returns `WalletInfo` populated with both
-/// address (derived) and the canonical `0x`-prefixed private key.
-fn load_wallet_from_file(path: &PathBuf) -> Result<WalletInfo, String> {
-    let raw =
-        std::fs::read_to_string(path).map_err(|e| format!("read {}: {}", path.display(), e))?;
-    let cleaned = raw.trim().trim_start_matches("0x");
-    let bytes = hex::decode(cleaned).map_err(|e| format!("wallet-key file is not hex: {}", e))?;
-    if bytes.len() != 32 {
-        return Err(format!(
-            "wallet-key file decoded to {} bytes, expected 32",
-            bytes.len()
-        ));
-    }
-    let mut arr = [0u8; 32];
-    arr.copy_from_slice(&bytes);
-    let address = address_from_private_key(&arr)?;
-    Ok(WalletInfo {
-        address,
-        private_key: format!("0x{}", hex::encode(arr)),
-    })
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct CreateWalletRequest {
+    passphrase: Option<String>,
+    /// Recovery phrase length; 12 (the desktop app's default) if absent.
+    word_count: Option<usize>,
+    /// BIP-39 extension passphrase, distinct from the keystore passphrase.
+    mnemonic_passphrase: Option<String>,
 }
 
-/// POST /api/headless/wallet/create — generate a new wallet (random private key)
-async fn wallet_create(State(state): State<Arc<HeadlessRuntimeState>>) -> Response {
-    // Generate a random 32-byte private key
-    let mut rng_bytes = [0u8; 32];
-    use std::io::Read;
-    match std::fs::File::open("/dev/urandom")
-        .and_then(|mut f| f.read_exact(&mut rng_bytes).map(|_| ()))
-    {
-        Ok(()) => {}
-        Err(e) => {
-            return json_error(
-                StatusCode::INTERNAL_SERVER_ERROR,
-                format!("RNG failed: {}", e),
-            )
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DeriveWalletRequest {
+    mnemonic: String,
+    mnemonic_passphrase: Option<String>,
+    #[serde(default)]
+    start: u32,
+    count: Option<u32>,
+}
+
+/// Upper bound on accounts listed by one `wallet/derive` call.
+const MAX_DERIVE_COUNT: u32 = 100;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct WalletPassphraseRequest {
+    passphrase: String,
+}
+
+/// Resolve a wallet-key file into the wallet the daemon starts with.
+///
+/// - keystore + passphrase: decrypted, unlocked
+/// - keystore alone: locked (address only) until `wallet/unlock`
+/// - plaintext + passphrase: rewritten as a keystore at `path`, unlocked
+/// - plaintext alone: loaded as before, with a warning
+///
+/// Runs scrypt, so call it off the async runtime.
+fn load_wallet_key_file(
+    path: &Path,
+    passphrase: Option<&str>,
+) -> Result<(WalletInfo, Option<Keystore>), String> {
+    match (keystore::read_wallet_key_file(path)?, passphrase) {
+        (WalletKeyFile::Encrypted(ks), Some(passphrase)) => {
+            let private_key = keystore::decrypt_private_key(&ks, passphrase)?;
+            let address = chiral_network::wallet::address_from_private_key(&private_key)?;
+            Ok((
+                WalletInfo {
+                    address,
+                    private_key,
+                },
+                Some(*ks),
+            ))
+        }
+        (WalletKeyFile::Encrypted(ks), None) => {
+            let address = ks
+                .address()
+                .ok_or("keystore has no address field; a passphrase is needed to load it")?;
+            Ok((
+                WalletInfo {
+                    address,
+                    private_key: String::new(),
+                },
+                Some(*ks),
+            ))
+        }
+        (WalletKeyFile::Plaintext(private_key), Some(passphrase)) => {
+            let ks = keystore::migrate_plaintext_key_file(path, passphrase)?;
+            println!(
+                "[WALLET] Encrypted plaintext key file {} as a v3 keystore",
+                path.display()
+            );
+            let address = chiral_network::wallet::address_from_private_key(&private_key)?;
+            Ok((
+                WalletInfo {
+                    address,
+                    private_key,
+                },
+                Some(ks),
+            ))
+        }
+        (WalletKeyFile::Plaintext(private_key), None) => {
+            eprintln!(
+                "[WALLET] {} holds the private key in clear; set {} (or --wallet-passphrase-file) to encrypt it",
+                path.display(),
+                keystore::PASSPHRASE_ENV
+            );
+            let address = chiral_network::wallet::address_from_private_key(&private_key)?;
+            Ok((
+                WalletInfo {
+                    address,
+                    private_key,
+                },
+                None,
+            ))
         }
     }
-    let private_key_hex = hex::encode(rng_bytes);
+}
 
-    // Derive address from private key using secp256k1
-    let secp = secp256k1::Secp256k1::new();
-    let secret_key = match secp256k1::SecretKey::from_slice(&rng_bytes) {
-        Ok(k) => k,
-        Err(e) => {
-            return json_error(
-                StatusCode::INTERNAL_SERVER_ERROR,
-                format!("Invalid key: {}", e),
-            )
+fn wallet_status_payload(wallet: &WalletInfo, encrypted: bool) -> serde_json::Value {
+    let mut payload = json!({
+        "address": wallet.address,
+        "locked": wallet.private_key.is_empty(),
+        "encrypted": encrypted,
+    });
+    add_plaintext_key(&mut payload, wallet, encrypted);
+    payload
+}
+
+/// Echo the private key only for a wallet kept in clear; a key sealed in a
+/// keystore never leaves the daemon.
+fn add_plaintext_key(payload: &mut serde_json::Value, wallet: &WalletInfo, sealed: bool) {
+    if !sealed {
+        payload["privateKey"] = json!(wallet.private_key);
+    }
+}
+
+/// Make `wallet` the daemon's wallet. With a passphrase, an encrypted
+/// keystore is kept so the wallet can later be locked and unlocked;
+/// without one, any keystore of a previous wallet is dropped so it cannot
+/// unlock to the wrong key.
+async fn install_wallet(
+    state: &HeadlessRuntimeState,
+    wallet: WalletInfo,
+    passphrase: Option<String>,
+) -> Result<Option<Keystore>, Response> {
+    let ks = match passphrase.filter(|p| !p.is_empty()) {
+        Some(passphrase) => {
+            let private_key = wallet.private_key.clone();
+            let encrypted = tokio::task::spawn_blocking(move || {
+                keystore::encrypt_private_key(&private_key, &passphrase)
+            })
+            .await
+            .map_err(|e| {
+                json_error(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("Keystore task failed: {}", e),
+                )
+            })?
+            .map_err(|e| json_error(StatusCode::BAD_REQUEST, e))?;
+            Some(encrypted)
         }
+        None => None,
+    };
+    *state.keystore.lock().await = ks.clone();
+    if let Some(cdn) = state.cdn.lock().await.as_ref() {
+        if cdn.wallet_address.eq_ignore_ascii_case(&wallet.address) {
+            cdn.set_signing_key(wallet.private_key.clone());
+        }
+    }
+    *state.wallet.lock().await = Some(wallet);
+    Ok(ks)
+}
+
+/// POST /api/headless/wallet/create — generate a new wallet from a fresh
+/// recovery phrase (account 0). An optional `passphrase` returns (and
+/// keeps) an encrypted keystore in place of the plaintext key.
+async fn wallet_create(
+    State(state): State<Arc<HeadlessRuntimeState>>,
+    req: Option<Json<CreateWalletRequest>>,
+) -> Response {
+    let req = req.map(|Json(req)| req).unwrap_or_default();
+    let mnemonic = match chiral_network::wallet::generate_mnemonic(req.word_count.unwrap_or(12)) {
+        Ok(mnemonic) => mnemonic,
+        Err(e) => return json_error(StatusCode::BAD_REQUEST, e),
+    };
+    let account = match chiral_network::wallet::derive_account(
+        &mnemonic,
+        req.mnemonic_passphrase.as_deref().unwrap_or(""),
+        0,
+    ) {
+        Ok(account) => account,
+        Err(e) => return json_error(StatusCode::INTERNAL_SERVER_ERROR, e),
     };
-    let public_key = secp256k1::PublicKey::from_secret_key(&secp, &secret_key);
-    let pub_bytes = public_key.serialize_uncompressed();
-    // Address = last 20 bytes of keccak256(pub_key_bytes[1..])
-    use tiny_keccak::{Hasher, Keccak};
-    let mut keccak = Keccak::v256();
-    keccak.update(&pub_bytes[1..]);
-    let mut hash = [0u8; 32];
-    keccak.finalize(&mut hash);
-    let address = format!("0x{}", hex::encode(&hash[12..]));
 
     let wallet = WalletInfo {
-        address: address.clone(),
-        private_key: format!("0x{}", private_key_hex),
+        address: account.address,
+        private_key: account.private_key,
+    };
+    let ks = match install_wallet(&state, wallet.clone(), req.passphrase).await {
+        Ok(ks) => ks,
+        Err(resp) => return resp,
     };
-    *state.wallet.lock().await = Some(wallet.clone());
 
     let (wallet_advertised, wallet_advertise_error) =
         match auto_publish_wallet_advertisement(state.as_ref(), &wallet.address).await {
@@ -1413,21 +1890,36 @@ async fn wallet_create(State(state): State<Arc<HeadlessRuntimeState>>) -> Respon
             }
         };
 
-    Json(json!({
+    let mut payload = json!({
         "address": wallet.address,
-        "privateKey": wallet.private_key,
+        "mnemonic": mnemonic,
+        "derivationPath": account.path,
+        "keystore": ks,
         "walletAdvertised": wallet_advertised,
         "walletAdvertiseError": wallet_advertise_error,
-    }))
-    .into_response()
+    });
+    add_plaintext_key(&mut payload, &wallet, ks.is_some());
+    Json(payload).into_response()
 }
 
-/// POST /api/headless/wallet/import — import wallet from private key
+/// POST /api/headless/wallet/import — import wallet from private key, or from
+/// a recovery phrase (`mnemonic`, account `index`, default 0)
 async fn wallet_import(
     State(state): State<Arc<HeadlessRuntimeState>>,
     Json(req): Json<ImportWalletRequest>,
 ) -> Response {
-    let pk_hex = req.private_key.trim().trim_start_matches("0x");
+    let private_key = match req.mnemonic.as_deref() {
+        Some(mnemonic) => match chiral_network::wallet::derive_account(
+            mnemonic,
+            req.mnemonic_passphrase.as_deref().unwrap_or(""),
+            req.index.unwrap_or(0),
+        ) {
+            Ok(account) => account.private_key,
+            Err(e) => return json_error(StatusCode::BAD_REQUEST, e),
+        },
+        None => req.private_key,
+    };
+    let pk_hex = private_key.trim().trim_start_matches("0x");
     let pk_bytes = match hex::decode(pk_hex) {
         Ok(b) if b.len() == 32 => b,
         _ => {
@@ -1461,7 +1953,10 @@ async fn wallet_import(
         address: address.clone(),
         private_key: format!("0x{}", pk_hex),
     };
-    *state.wallet.lock().await = Some(wallet.clone());
+    let ks = match install_wallet(&state, wallet.clone(), req.passphrase).await {
+        Ok(ks) => ks,
+        Err(resp) => return resp,
+    };
 
     let (wallet_advertised, wallet_advertise_error) =
         match auto_publish_wallet_advertisement(state.as_ref(), &wallet.address).await {
@@ -1475,28 +1970,131 @@ async fn wallet_import(
             }
         };
 
-    Json(json!({
+    let mut payload = json!({
         "address": wallet.address,
-        "privateKey": wallet.private_key,
+        "keystore": ks,
         "walletAdvertised": wallet_advertised,
         "walletAdvertiseError": wallet_advertise_error,
-    }))
-    .into_response()
+    });
+    add_plaintext_key(&mut payload, &wallet, ks.is_some());
+    Json(payload).into_response()
+}
+
+/// POST /api/headless/wallet/derive — list the addresses of accounts
+/// `start..start+count` of a recovery phrase without loading any of them
+async fn wallet_derive(Json(req): Json<DeriveWalletRequest>) -> Response {
+    let count = req.count.unwrap_or(1);
+    if count == 0 || count > MAX_DERIVE_COUNT {
+        return json_error(
+            StatusCode::BAD_REQUEST,
+            format!("count must be between 1 and {}", MAX_DERIVE_COUNT),
+        );
+    }
+    let Some(end) = req
+        .start
+        .checked_add(count)
+        .filter(|end| *end <= 0x8000_0000)
+    else {
+        return json_error(StatusCode::BAD_REQUEST, "account index out of range");
+    };
+    let seed = match chiral_network::wallet::mnemonic_to_seed(
+        &req.mnemonic,
+        req.mnemonic_passphrase.as_deref().unwrap_or(""),
+    ) {
+        Ok(seed) => seed,
+        Err(e) => return json_error(StatusCode::BAD_REQUEST, e),
+    };
+    let mut accounts = Vec::with_capacity(count as usize);
+    for index in req.start..end {
+        match chiral_network::wallet::derive_account_from_seed(&seed, index) {
+            Ok(account) => accounts.push(json!({
+                "index": account.index,
+                "path": account.path,
+                "address": account.address,
+            })),
+            Err(e) => return json_error(StatusCode::INTERNAL_SERVER_ERROR, e),
+        }
+    }
+    Json(json!({ "accounts": accounts })).into_response()
 }
 
 /// GET /api/headless/wallet — get current wallet info
 async fn wallet_show(State(state): State<Arc<HeadlessRuntimeState>>) -> Response {
+    let encrypted = state.keystore.lock().await.is_some();
     let guard = state.wallet.lock().await;
     match &*guard {
-        Some(w) => Json(json!({
-            "address": w.address,
-            "privateKey": w.private_key,
-        }))
-        .into_response(),
+        Some(w) => Json(wallet_status_payload(w, encrypted)).into_response(),
         None => json_error(StatusCode::NOT_FOUND, "No wallet loaded"),
     }
 }
 
+/// POST /api/headless/wallet/unlock — decrypt the keystore with `passphrase`
+/// and start signing with it
+async fn wallet_unlock(
+    State(state): State<Arc<HeadlessRuntimeState>>,
+    Json(req): Json<WalletPassphraseRequest>,
+) -> Response {
+    let Some(ks) = state.keystore.lock().await.clone() else {
+        return json_error(StatusCode::CONFLICT, "No encrypted keystore loaded");
+    };
+    let decrypted =
+        tokio::task::spawn_blocking(move || keystore::decrypt_private_key(&ks, &req.passphrase))
+            .await;
+    let private_key = match decrypted {
+        Ok(Ok(private_key)) => private_key,
+        Ok(Err(e)) => return json_error(StatusCode::UNAUTHORIZED, e),
+        Err(e) => {
+            return json_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Keystore task failed: {}", e),
+            )
+        }
+    };
+    let address = match chiral_network::wallet::address_from_private_key(&private_key) {
+        Ok(address) => address,
+        Err(e) => return json_error(StatusCode::INTERNAL_SERVER_ERROR, e),
+    };
+
+    let wallet = WalletInfo {
+        address,
+        private_key,
+    };
+    *state.wallet.lock().await = Some(wallet.clone());
+    if let Some(cdn) = state.cdn.lock().await.clone() {
+        if cdn.wallet_address.eq_ignore_ascii_case(&wallet.address) {
+            let was_locked = cdn.signing_key().is_empty();
+            cdn.set_signing_key(wallet.private_key.clone());
+            // Records skipped while locked get published now.
+            if was_locked {
+                tokio::spawn(chiral_network::cdn_server::reseed_on_startup(cdn));
+            }
+        }
+    }
+    println!("[WALLET] Unlocked {}", wallet.address);
+    Json(wallet_status_payload(&wallet, true)).into_response()
+}
+
+/// POST /api/headless/wallet/lock — forget the decrypted key; the keystore
+/// stays loaded for a later unlock
+async fn wallet_lock(State(state): State<Arc<HeadlessRuntimeState>>) -> Response {
+    if state.keystore.lock().await.is_none() {
+        return json_error(
+            StatusCode::CONFLICT,
+            "Wallet has no encrypted keystore; locking would discard the key. Re-import it with a passphrase first.",
+        );
+    }
+    let mut guard = state.wallet.lock().await;
+    let Some(wallet) = guard.as_mut() else {
+        return json_error(StatusCode::NOT_FOUND, "No wallet loaded");
+    };
+    wallet.private_key.clear();
+    if let Some(cdn) = state.cdn.lock().await.as_ref() {
+        cdn.set_signing_key(String::new());
+    }
+    println!("[WALLET] Locked {}", wallet.address);
+    Json(wallet_status_payload(wallet, true)).into_response()
+}
+
 // ---- Wallet balance/transaction endpoints ----
 
 async fn wallet_balance(
@@ -1601,9 +2199,17 @@ async fn wallet_history(
     if address.is_empty() {
         return json_error(StatusCode::BAD_REQUEST, "address required");
     }
+    let query = chiral_network::chain_index::IndexQuery {
+        offset: body["offset"].as_u64().unwrap_or(0) as usize,
+        limit: body["limit"].as_u64().map(|limit| limit as usize),
+        since: body["since"].as_u64(),
+        until: body["until"].as_u64(),
+    };
     let endpoint = chiral_network::geth::effective_rpc_endpoint();
     let metadata = chiral_network::wallet::load_tx_metadata();
-    match chiral_network::wallet::get_transaction_history(&endpoint, &address, &metadata).await {
+    match chiral_network::wallet::get_transaction_history(&endpoint, &address, &metadata, &query)
+        .await
+    {
         Ok(result) => Json(json!(result)).into_response(),
         Err(e) => json_error(StatusCode::INTERNAL_SERVER_ERROR, &e),
     }
@@ -1932,6 +2538,38 @@ async fn file_search(
     .into_response()
 }
 
+// ---- Keyword search endpoint ----
+
+async fn file_keyword_search(
+    State(state): State<Arc<HeadlessRuntimeState>>,
+    Json(req): Json<KeywordSearchRequest>,
+) -> Response {
+    if search_index::query_terms(&req.query).is_empty() {
+        return json_error(
+            StatusCode::BAD_REQUEST,
+            "query needs at least one word of 2+ characters",
+        );
+    }
+    let Some(dht) = state.dht_service().await else {
+        return json_error(StatusCode::SERVICE_UNAVAILABLE, "DHT not running");
+    };
+    let reputation = match search_index::local_reputation().await {
+        Ok(reputation) => reputation,
+        Err(e) => return json_error(StatusCode::INTERNAL_SERVER_ERROR, e),
+    };
+    match search_index::search_keywords(
+        &dht,
+        &req.query,
+        search_index::clamp_limit(req.limit),
+        &reputation,
+    )
+    .await
+    {
+        Ok(results) => Json(json!({ "query": req.query, "results": results })).into_response(),
+        Err(e) => json_error(StatusCode::BAD_REQUEST, e),
+    }
+}
+
 // ---- Folder bundle search endpoint ----
 
 async fn folder_search(
@@ -2099,15 +2737,34 @@ async fn bootstrap_health(State(_state): State<Arc<HeadlessRuntimeState>>) -> Re
         Err(e) => json_error(StatusCode::INTERNAL_SERVER_ERROR, e),
     }
 }
-fn headless_routes(state: Arc<HeadlessRuntimeState>) -> Router {
+/// Routes served on every listener without a token: liveness/readiness
+/// probes and the file lookups other nodes' frontends use for CDN search.
+fn public_routes(state: Arc<HeadlessRuntimeState>) -> Router {
     Router::new()
         // Health/readiness probes
         .route("/api/health", get(health_check))
         .route("/api/ready", get(readiness_check))
+        // File search
+        .route("/api/headless/file/search", post(file_search))
+        .route(
+            "/api/headless/file/keyword-search",
+            post(file_keyword_search),
+        )
+        .route("/api/headless/folder/search", post(folder_search))
+        .with_state(state)
+}
+
+/// The `/api/headless/*` control API. Callers on TCP listeners wrap this
+/// in [`daemon_auth::require_token`]; see [`authenticated_headless_routes`].
+fn headless_routes(state: Arc<HeadlessRuntimeState>) -> Router {
+    Router::new()
         // Wallet management
         .route("/api/headless/wallet", get(wallet_show))
         .route("/api/headless/wallet/create", post(wallet_create))
         .route("/api/headless/wallet/import", post(wallet_import))
+        .route("/api/headless/wallet/derive", post(wallet_derive))
+        .route("/api/headless/wallet/unlock", post(wallet_unlock))
+        .route("/api/headless/wallet/lock", post(wallet_lock))
         // Wallet transactions
         .route("/api/headless/wallet/balance", post(wallet_balance))
         .route("/api/headless/wallet/send", post(wallet_send))
@@ -2117,6 +2774,11 @@ fn headless_routes(state: Arc<HeadlessRuntimeState>) -> Router {
         .route("/api/headless/wallet/chain-id", get(wallet_chain_id))
         // Runtime
         .route("/api/headless/runtime", get(runtime_status))
+        .route(
+            "/api/headless/bandwidth",
+            get(bandwidth_status).put(bandwidth_set_limits),
+        )
+        .route("/api/headless/events", get(events_stream))
         // DHT
         .route("/api/headless/dht/start", post(dht_start))
         .route("/api/headless/dht/stop", post(dht_stop))
@@ -2141,14 +2803,12 @@ fn headless_routes(state: Arc<HeadlessRuntimeState>) -> Router {
             "/api/headless/dht/unregister-shared-file",
             post(dht_unregister_shared_file),
         )
-        // File search
-        .route("/api/headless/file/search", post(file_search))
-        .route("/api/headless/folder/search", post(folder_search))
         // ChiralDrop
         .route("/api/headless/drop/inbox", get(drop_inbox))
         .route("/api/headless/drop/outgoing", get(drop_outgoing))
         .route("/api/headless/drop/accept", post(drop_accept))
         .route("/api/headless/drop/decline", post(drop_decline))
+        .route("/api/headless/drop/cancel", post(drop_cancel))
         // Geth
         .route("/api/headless/geth/install", post(geth_install))
         .route("/api/headless/geth/start", post(geth_start))
@@ -2172,9 +2832,105 @@ fn headless_routes(state: Arc<HeadlessRuntimeState>) -> Router {
         // state so the CDN module can own its own registry + price config.
         // Diagnostics
         .route("/api/headless/bootstrap-health", get(bootstrap_health))
+        .route(daemon_auth::METRICS_ROUTE, get(metrics_scrape))
         .with_state(state)
 }
 
+fn authenticated_headless_routes(
+    state: Arc<HeadlessRuntimeState>,
+    auth: Arc<daemon_auth::DaemonAuth>,
+) -> Router {
+    headless_routes(state).route_layer(axum::middleware::from_fn_with_state(
+        auth,
+        daemon_auth::require_token,
+    ))
+}
+
+fn cors_layer() -> CorsLayer {
+    CorsLayer::new()
+        .allow_origin(Any)
+        .allow_methods(Any)
+        .allow_headers(Any)
+        .expose_headers(Any)
+}
+
+/// Serve `app` on a Unix socket until `shutdown` fires. `axum::serve` only
+/// accepts TCP listeners, so connections are driven with hyper directly.
+#[cfg(unix)]
+async fn serve_unix_socket(
+    listener: tokio::net::UnixListener,
+    app: Router,
+    mut shutdown: tokio::sync::watch::Receiver<bool>,
+) {
+    use tower::ServiceExt;
+    loop {
+        let stream = tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    eprintln!("[AUTH] Admin socket accept failed: {}", e);
+                    continue;
+                }
+            },
+            _ = shutdown.changed() => break,
+        };
+        let app = app.clone();
+        tokio::spawn(async move {
+            let service =
+                hyper::service::service_fn(move |req: hyper::Request<hyper::body::Incoming>| {
+                    app.clone().oneshot(req.map(axum::body::Body::new))
+                });
+            if let Err(e) = hyper::server::conn::http1::Builder::new()
+                .serve_connection(hyper_util::rt::TokioIo::new(stream), service)
+                .await
+            {
+                eprintln!("[AUTH] Admin socket connection error: {}", e);
+            }
+        });
+    }
+}
+
+/// Bind the admin socket with owner-only permissions, replacing a stale
+/// socket left by a previous run.
+///
+/// The socket is bound inside a private 0700 directory and only moved to
+/// `path` once it is 0600, so nobody else can connect while the umask
+/// permissions are still in effect.
+#[cfg(unix)]
+fn bind_admin_socket(path: &Path) -> Result<tokio::net::UnixListener, String> {
+    use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    std::fs::create_dir_all(parent)
+        .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+    if path.exists() {
+        std::fs::remove_file(path)
+            .map_err(|e| format!("Failed to remove stale socket {}: {}", path.display(), e))?;
+    }
+
+    let staging = parent.join(format!(".chiral-admin-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&staging);
+    std::fs::DirBuilder::new()
+        .mode(0o700)
+        .create(&staging)
+        .map_err(|e| format!("Failed to create {}: {}", staging.display(), e))?;
+    let staged = staging.join("sock");
+    let bound = tokio::net::UnixListener::bind(&staged)
+        .map_err(|e| format!("Failed to bind admin socket {}: {}", path.display(), e))
+        .and_then(|listener| {
+            std::fs::set_permissions(&staged, std::fs::Permissions::from_mode(0o600)).map_err(
+                |e| format!("Failed to restrict admin socket {}: {}", path.display(), e),
+            )?;
+            std::fs::rename(&staged, path)
+                .map_err(|e| format!("Failed to move admin socket to {}: {}", path.display(), e))?;
+            Ok(listener)
+        });
+    let _ = std::fs::remove_dir_all(&staging);
+    bound
+}
+
 #[tokio::main]
 async fn main() {
     let args = DaemonArgs::parse();
@@ -2188,7 +2944,24 @@ async fn main() {
     if let Some(port) = args.p2p_port {
         std::env::set_var("CHIRAL_P2P_PORT", port.to_string());
     }
+    if let Some(mode) = args.dht_store {
+        let value = match mode {
+            StoreMode::Memory => "memory",
+            StoreMode::Disk => "disk",
+        };
+        std::env::set_var(kad_store::DHT_STORE_ENV, value);
+    }
+    // When AutoNAT finds us behind NAT, the router is asked to forward this
+    // gateway port too. An explicit CHIRAL_GATEWAY_PORT (0 disables) wins.
+    if std::env::var_os(nat::GATEWAY_PORT_ENV).is_none() {
+        std::env::set_var(nat::GATEWAY_PORT_ENV, args.port.to_string());
+    }
     chiral_network::version::log_policy_key_status();
+    let limits = args.bandwidth_limits();
+    if limits != BandwidthLimits::default() {
+        println!("[BANDWIDTH] Limits: {:?}", limits);
+    }
+    bandwidth::global().set_limits(limits);
     let pid_file = args.pid_file.unwrap_or_else(default_pid_file);
 
     if let Err(e) = write_pid_file(&pid_file) {
@@ -2213,14 +2986,32 @@ async fn main() {
     // before CdnState::new runs so the CDN module can pull the
     // private_key for FileInfo / SeederInfo signing.
     if let Some(ref key_path) = args.wallet_key_file {
-        match load_wallet_from_file(key_path) {
-            Ok(wallet) => {
+        let passphrase = match keystore::read_passphrase(args.wallet_passphrase_file.as_deref()) {
+            Ok(passphrase) => passphrase,
+            Err(e) => {
+                eprintln!("[WALLET] {}", e);
+                None
+            }
+        };
+        let path = key_path.clone();
+        let loaded =
+            tokio::task::spawn_blocking(move || load_wallet_key_file(&path, passphrase.as_deref()))
+                .await
+                .unwrap_or_else(|e| Err(format!("wallet load task failed: {}", e)));
+        match loaded {
+            Ok((wallet, ks)) => {
                 println!(
-                    "[WALLET] Loaded key from {}: address={}",
+                    "[WALLET] Loaded key from {}: address={}{}",
                     key_path.display(),
-                    wallet.address
+                    wallet.address,
+                    if wallet.private_key.is_empty() {
+                        " (locked; POST /api/headless/wallet/unlock to sign)"
+                    } else {
+                        ""
+                    }
                 );
                 *runtime_state.wallet.lock().await = Some(wallet);
+                *runtime_state.keystore.lock().await = ks;
             }
             Err(e) => {
                 eprintln!(
@@ -2232,7 +3023,7 @@ async fn main() {
         }
     }
 
-    let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel();
+    let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
 
     let base_router = hosting_server::create_gateway_router(
         Arc::clone(&hosting_state),
@@ -2276,19 +3067,37 @@ async fn main() {
             .await,
         )
     };
+    *runtime_state.cdn.lock().await = Some(Arc::clone(&cdn_state));
 
-    let app = base_router
-        .merge(headless_routes(Arc::clone(&runtime_state)))
-        .merge(chiral_network::cdn_server::router(Arc::clone(&cdn_state)))
-        .layer(
-            CorsLayer::new()
-                .allow_origin(Any)
-                .allow_methods(Any)
-                .allow_headers(Any)
-                .expose_headers(Any),
-        );
+    let auth = match daemon_auth::DaemonAuth::open(daemon_auth::token_file(&default_data_dir())) {
+        Ok(auth) => Arc::new(auth.with_fixed_token(std::env::var(daemon_auth::TOKEN_ENV).ok())),
+        Err(e) => {
+            remove_pid_file(&pid_file);
+            eprintln!("[AUTH] Failed to load daemon API tokens: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let control_routes =
+        authenticated_headless_routes(Arc::clone(&runtime_state), Arc::clone(&auth));
+
+    // With --admin-bind the control API moves to its own listener and the
+    // gateway port only carries public routes.
+    let mut app = base_router
+        .merge(public_routes(Arc::clone(&runtime_state)))
+        .merge(chiral_network::cdn_server::router(Arc::clone(&cdn_state)));
+    let admin_app = if args.admin_bind.is_some() {
+        Some(
+            public_routes(Arc::clone(&runtime_state))
+                .merge(control_routes)
+                .layer(cors_layer()),
+        )
+    } else {
+        app = app.merge(control_routes);
+        None
+    };
+    let app = app.layer(cors_layer());
 
-    let addr = SocketAddr::from(([0, 0, 0, 0], args.port));
+    let addr = SocketAddr::new(args.bind, args.port);
     let listener = match tokio::net::TcpListener::bind(addr).await {
         Ok(v) => v,
         Err(e) => {
@@ -2297,6 +3106,31 @@ async fn main() {
             std::process::exit(1);
         }
     };
+    let admin_listener = match args.admin_bind {
+        Some(admin_addr) => match tokio::net::TcpListener::bind(admin_addr).await {
+            Ok(v) => Some(v),
+            Err(e) => {
+                remove_pid_file(&pid_file);
+                eprintln!("Failed to bind admin API on {}: {}", admin_addr, e);
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
+    #[cfg(unix)]
+    let admin_socket = match args.admin_socket.as_deref().map(bind_admin_socket) {
+        Some(Ok(listener)) => Some(listener),
+        Some(Err(e)) => {
+            remove_pid_file(&pid_file);
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        None => None,
+    };
+    #[cfg(not(unix))]
+    if args.admin_socket.is_some() {
+        eprintln!("[AUTH] --admin-socket is only supported on Unix; ignoring");
+    }
 
     let bound = match listener.local_addr() {
         Ok(v) => v,
@@ -2308,6 +3142,14 @@ async fn main() {
     };
 
     println!("chiral-daemon running on http://{}", bound);
+    if let Some(admin) = admin_listener.as_ref().and_then(|l| l.local_addr().ok()) {
+        println!("Admin API on http://{}", admin);
+    }
+    #[cfg(unix)]
+    if let (Some(path), Some(_)) = (args.admin_socket.as_ref(), admin_socket.as_ref()) {
+        println!("Admin API on unix socket {}", path.display());
+    }
+    println!("API tokens: {}", auth.path().display());
     println!("PID file: {}", pid_file.display());
     {
         // Loud network log so an operator restarting a CDN server can see
@@ -2343,7 +3185,7 @@ async fn main() {
                         let dd = Arc::clone(&rt.download_directory);
                         let dc = Arc::clone(&rt.download_credentials);
                         let svc = Arc::new(dht::DhtService::new(ft, dd, dc));
-                        match svc.start_headless().await {
+                        match svc.start_headless(rt.events.clone()).await {
                             Ok(_) => {
                                 *guard = Some(svc.clone());
                                 println!("[AUTO] DHT started successfully");
@@ -2427,17 +3269,50 @@ async fn main() {
         let cdn_state = Arc::clone(&cdn_state);
         tokio::spawn(chiral_network::cdn_server::expiration_loop(cdn_state));
     }
+    // Local chain index behind wallet/history and mining/blocks.
+    tokio::spawn(chiral_network::chain_index::run(
+        chiral_network::geth::effective_rpc_endpoint,
+    ));
     // Kademlia handles provider-record republishing on its configured
     // interval, so the manual CDN republish loop from the legacy blob
     // schema is no longer needed.
 
+    if let Some(admin_listener) = admin_listener {
+        let admin_app = admin_app.expect("admin router is built whenever --admin-bind is set");
+        let mut rx = shutdown_rx.clone();
+        tokio::spawn(async move {
+            let server = axum::serve(
+                admin_listener,
+                admin_app.into_make_service_with_connect_info::<SocketAddr>(),
+            )
+            .with_graceful_shutdown(async move {
+                rx.changed().await.ok();
+            });
+            if let Err(e) = server.await {
+                eprintln!("Admin API server error: {}", e);
+            }
+        });
+    }
+    #[cfg(unix)]
+    if let Some(admin_socket) = admin_socket {
+        // The socket's file permissions are the access check here.
+        let socket_app = public_routes(Arc::clone(&runtime_state))
+            .merge(headless_routes(Arc::clone(&runtime_state)));
+        tokio::spawn(serve_unix_socket(
+            admin_socket,
+            socket_app,
+            shutdown_rx.clone(),
+        ));
+    }
+
+    let mut rx = shutdown_rx.clone();
     tokio::spawn(async move {
         let server = axum::serve(
             listener,
             app.into_make_service_with_connect_info::<SocketAddr>(),
         )
-        .with_graceful_shutdown(async {
-            shutdown_rx.await.ok();
+        .with_graceful_shutdown(async move {
+            rx.changed().await.ok();
             println!("Headless daemon received shutdown signal");
         });
 
@@ -2485,8 +3360,12 @@ async fn main() {
         let _ = geth.stop();
     }
 
-    let _ = shutdown_tx.send(());
+    let _ = shutdown_tx.send(true);
     tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+    #[cfg(unix)]
+    if let Some(path) = args.admin_socket.as_ref() {
+        let _ = std::fs::remove_file(path);
+    }
     remove_pid_file(&pid_file);
     println!("Daemon stopped.");
 }
@@ -2569,6 +3448,7 @@ mod tests {
             peer_id: peer_id.to_string(),
             wallet_address: wallet_address.to_string(),
             updated_at,
+            signature: String::new(),
         }
     }
 
@@ -2591,6 +3471,8 @@ mod tests {
             price_wei: "1000".to_string(),
             wallet_address: wallet_address_from_private_key(private_key),
             private_key: private_key.to_string(),
+            description: None,
+            tags: Vec::new(),
         }
     }
 
@@ -2834,4 +3716,425 @@ mod tests {
 
         assert_eq!(response.status(), StatusCode::BAD_REQUEST);
     }
+    const TEST_WALLET_KEY: &str =
+        "0x4c0883a69102937d6231471b5dbb6204fe512961708279cea2c89f1f7a0f2c4f";
+
+    fn test_keystore(passphrase: &str) -> Keystore {
+        keystore::encrypt_private_key_with(
+            TEST_WALLET_KEY,
+            passphrase,
+            keystore::KdfParams::Scrypt {
+                dklen: 32,
+                n: 1 << 10,
+                r: 8,
+                p: 1,
+                salt: hex::encode([9u8; 32]),
+            },
+        )
+        .unwrap()
+    }
+
+    fn test_wallet_address() -> String {
+        chiral_network::wallet::address_from_private_key(TEST_WALLET_KEY).unwrap()
+    }
+
+    #[test]
+    fn wallet_key_file_keystore_without_passphrase_starts_locked() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("wallet.key");
+        keystore::write_keystore_file(&path, &test_keystore("pw")).unwrap();
+
+        let (wallet, ks) = load_wallet_key_file(&path, None).unwrap();
+        assert_eq!(wallet.address, test_wallet_address());
+        assert!(wallet.private_key.is_empty());
+        assert!(ks.is_some());
+
+        let (wallet, _) = load_wallet_key_file(&path, Some("pw")).unwrap();
+        assert_eq!(wallet.private_key, TEST_WALLET_KEY);
+        assert!(load_wallet_key_file(&path, Some("nope")).is_err());
+    }
+
+    #[test]
+    fn wallet_key_file_plaintext_without_passphrase_still_loads() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("wallet.key");
+        std::fs::write(&path, &TEST_WALLET_KEY[2..]).unwrap();
+
+        let (wallet, ks) = load_wallet_key_file(&path, None).unwrap();
+        assert_eq!(wallet.address, test_wallet_address());
+        assert_eq!(wallet.private_key, TEST_WALLET_KEY);
+        assert!(ks.is_none());
+    }
+
+    #[test]
+    fn wallet_status_payload_hides_a_sealed_key() {
+        let wallet = WalletInfo {
+            address: test_wallet_address(),
+            private_key: TEST_WALLET_KEY.to_string(),
+        };
+        let sealed = wallet_status_payload(&wallet, true);
+        assert!(sealed.get("privateKey").is_none());
+        assert_eq!(sealed["locked"], false);
+        assert_eq!(
+            wallet_status_payload(&wallet, false)["privateKey"],
+            TEST_WALLET_KEY
+        );
+    }
+
+    #[tokio::test]
+    async fn wallet_unlock_and_lock_require_a_keystore() {
+        let state = Arc::new(HeadlessRuntimeState::new());
+        *state.wallet.lock().await = Some(WalletInfo {
+            address: test_wallet_address(),
+            private_key: TEST_WALLET_KEY.to_string(),
+        });
+
+        let response = wallet_unlock(
+            State(state.clone()),
+            Json(WalletPassphraseRequest {
+                passphrase: "pw".to_string(),
+            }),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+
+        let response = wallet_lock(State(state.clone())).await;
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        // The only copy of the key must survive a refused lock.
+        assert_eq!(
+            state.wallet.lock().await.as_ref().unwrap().private_key,
+            TEST_WALLET_KEY
+        );
+    }
+
+    #[tokio::test]
+    async fn wallet_unlock_checks_passphrase_and_lock_clears_key() {
+        let state = Arc::new(HeadlessRuntimeState::new());
+        *state.keystore.lock().await = Some(test_keystore("pw"));
+        *state.wallet.lock().await = Some(WalletInfo {
+            address: test_wallet_address(),
+            private_key: String::new(),
+        });
+
+        let response = wallet_unlock(
+            State(state.clone()),
+            Json(WalletPassphraseRequest {
+                passphrase: "wrong".to_string(),
+            }),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert!(state
+            .wallet
+            .lock()
+            .await
+            .as_ref()
+            .unwrap()
+            .private_key
+            .is_empty());
+
+        let response = wallet_unlock(
+            State(state.clone()),
+            Json(WalletPassphraseRequest {
+                passphrase: "pw".to_string(),
+            }),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            state.wallet.lock().await.as_ref().unwrap().private_key,
+            TEST_WALLET_KEY
+        );
+
+        let response = wallet_lock(State(state.clone())).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let wallet = state.wallet.lock().await.clone().unwrap();
+        assert_eq!(wallet.address, test_wallet_address());
+        assert!(wallet.private_key.is_empty());
+        assert!(state.keystore.lock().await.is_some());
+    }
+
+    #[tokio::test]
+    async fn wallet_derive_lists_accounts_of_a_recovery_phrase() {
+        let response = wallet_derive(Json(DeriveWalletRequest {
+            mnemonic: "test test test test test test test test test test test junk".to_string(),
+            mnemonic_passphrase: None,
+            start: 1,
+            count: Some(2),
+        }))
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let value: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let accounts = value["accounts"].as_array().unwrap();
+        assert_eq!(accounts.len(), 2);
+        assert_eq!(accounts[0]["path"], "m/44'/60'/0'/0/1");
+        assert_eq!(
+            accounts[0]["address"],
+            "0x70997970c51812dc3a010c7d01b50e0d17dc79c8"
+        );
+        assert_eq!(accounts[1]["index"], 2);
+        assert!(accounts[0].get("privateKey").is_none());
+    }
+
+    #[tokio::test]
+    async fn wallet_derive_rejects_bad_phrase_and_counts() {
+        for (mnemonic, start, count) in [
+            ("abandon abandon abandon", 0, Some(1)),
+            (
+                "test test test test test test test test test test test junk",
+                0,
+                Some(0),
+            ),
+            (
+                "test test test test test test test test test test test junk",
+                0,
+                Some(MAX_DERIVE_COUNT + 1),
+            ),
+            (
+                "test test test test test test test test test test test junk",
+                0x7fff_ffff,
+                Some(2),
+            ),
+        ] {
+            let response = wallet_derive(Json(DeriveWalletRequest {
+                mnemonic: mnemonic.to_string(),
+                mnemonic_passphrase: None,
+                start,
+                count,
+            }))
+            .await;
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        }
+    }
+
+    #[test]
+    fn bandwidth_args_convert_kib_to_bytes() {
+        let args = DaemonArgs::try_parse_from([
+            "chiral_daemon",
+            "--upload-limit-kib",
+            "512",
+            "--peer-download-limit-kib",
+            "64",
+            "--upload-slots",
+            "4",
+        ])
+        .unwrap();
+        assert_eq!(
+            args.bandwidth_limits(),
+            BandwidthLimits {
+                upload_bps: 512 * 1024,
+                download_bps: 0,
+                per_peer_upload_bps: 0,
+                per_peer_download_bps: 64 * 1024,
+                upload_slots: 4,
+            }
+        );
+    }
+
+    #[test]
+    fn admin_bind_args_parse() {
+        let args = DaemonArgs::try_parse_from([
+            "chiral_daemon",
+            "--bind",
+            "127.0.0.1",
+            "--admin-bind",
+            "127.0.0.1:9420",
+            "--admin-socket",
+            "/tmp/chiral-admin.sock",
+        ])
+        .unwrap();
+        assert_eq!(args.bind, std::net::IpAddr::from([127, 0, 0, 1]));
+        assert_eq!(
+            args.admin_bind,
+            Some(SocketAddr::from(([127, 0, 0, 1], 9420)))
+        );
+        assert_eq!(
+            args.admin_socket.as_deref(),
+            Some(Path::new("/tmp/chiral-admin.sock"))
+        );
+
+        let defaults = DaemonArgs::try_parse_from(["chiral_daemon"]).unwrap();
+        assert_eq!(defaults.bind, std::net::IpAddr::from([0, 0, 0, 0]));
+        assert!(defaults.admin_bind.is_none());
+    }
+
+    #[test]
+    fn dht_store_arg_parses() {
+        let args = DaemonArgs::try_parse_from(["chiral_daemon", "--dht-store", "disk"]).unwrap();
+        assert_eq!(args.dht_store, Some(StoreMode::Disk));
+        assert!(DaemonArgs::try_parse_from(["chiral_daemon", "--dht-store", "tape"]).is_err());
+    }
+
+    #[tokio::test]
+    async fn headless_routes_require_a_token_but_probes_do_not() {
+        use tower::ServiceExt;
+        let dir = tempfile::tempdir().unwrap();
+        let path = daemon_auth::token_file(dir.path());
+        let auth = Arc::new(daemon_auth::DaemonAuth::open(path.clone()).unwrap());
+        let token = daemon_auth::TokenStore::load(&path)
+            .unwrap()
+            .strongest()
+            .unwrap()
+            .token
+            .clone();
+        let state = Arc::new(HeadlessRuntimeState::new());
+        let app =
+            public_routes(Arc::clone(&state)).merge(authenticated_headless_routes(state, auth));
+
+        let get = |uri: &str, token: Option<&str>| {
+            let mut req = axum::http::Request::builder().uri(uri);
+            if let Some(token) = token {
+                req = req.header("authorization", format!("Bearer {}", token));
+            }
+            req.body(axum::body::Body::empty()).unwrap()
+        };
+        let status = |req: axum::http::Request<axum::body::Body>| {
+            let app = app.clone();
+            async move { app.oneshot(req).await.unwrap().status() }
+        };
+        assert_eq!(status(get("/api/health", None)).await, StatusCode::OK);
+        assert_eq!(
+            status(get("/api/headless/runtime", None)).await,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            status(get("/api/headless/runtime", Some(&token))).await,
+            StatusCode::OK
+        );
+    }
+
+    #[tokio::test]
+    async fn metrics_scrape_needs_a_token_and_reports_geth() {
+        use tower::ServiceExt;
+        let dir = tempfile::tempdir().unwrap();
+        let path = daemon_auth::token_file(dir.path());
+        let auth = Arc::new(daemon_auth::DaemonAuth::open(path.clone()).unwrap());
+        let token = daemon_auth::TokenStore::load(&path)
+            .unwrap()
+            .strongest()
+            .unwrap()
+            .token
+            .clone();
+        let app = authenticated_headless_routes(Arc::new(HeadlessRuntimeState::new()), auth);
+        let get = |token: Option<&str>| {
+            let mut req = axum::http::Request::builder().uri("/metrics");
+            if let Some(token) = token {
+                req = req.header("authorization", format!("Bearer {}", token));
+            }
+            req.body(axum::body::Body::empty()).unwrap()
+        };
+
+        let response = app.clone().oneshot(get(None)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let response = app.oneshot(get(Some(&token))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()["content-type"],
+            metrics::METRICS_CONTENT_TYPE
+        );
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let text = String::from_utf8_lossy(&body);
+        assert!(text.contains("\nchiral_geth_running 0\n"), "{}", text);
+        assert!(text.ends_with("# EOF\n"), "{}", text);
+    }
+
+    #[tokio::test]
+    async fn keyword_search_is_public_and_validates_the_query() {
+        use tower::ServiceExt;
+
+        let app = public_routes(Arc::new(HeadlessRuntimeState::new()));
+        let post = |body: serde_json::Value| {
+            axum::http::Request::builder()
+                .method("POST")
+                .uri("/api/headless/file/keyword-search")
+                .header("content-type", "application/json")
+                .body(axum::body::Body::from(body.to_string()))
+                .unwrap()
+        };
+        let status = |req: axum::http::Request<axum::body::Body>| {
+            let app = app.clone();
+            async move { app.oneshot(req).await.unwrap().status() }
+        };
+        assert_eq!(
+            status(post(json!({ "query": "a" }))).await,
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            status(post(json!({ "query": "annual report" }))).await,
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+    }
+
+    #[tokio::test]
+    async fn events_stream_sends_filtered_sse() {
+        use futures::StreamExt;
+        use tower::ServiceExt;
+
+        let state = Arc::new(HeadlessRuntimeState::new());
+        let response = headless_routes(Arc::clone(&state))
+            .oneshot(
+                axum::http::Request::builder()
+                    .uri("/api/headless/events?types=download-*")
+                    .body(axum::body::Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["content-type"], "text/event-stream");
+
+        state
+            .events
+            .emit("peer-discovered", json!({ "peerId": "p1" }));
+        state
+            .events
+            .emit("download-progress", json!({ "requestId": "r1" }));
+        let mut body = response.into_body().into_data_stream();
+        let chunk = body.next().await.unwrap().unwrap();
+        let text = String::from_utf8_lossy(&chunk);
+        assert!(text.starts_with("event: download-progress\n"), "{}", text);
+        assert!(text.contains(r#""requestId":"r1""#), "{}", text);
+        assert!(!text.contains("peer-discovered"));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn admin_socket_is_private_and_serves_requests() {
+        use std::os::unix::fs::PermissionsExt;
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("admin.sock");
+        std::fs::write(&path, b"stale").unwrap();
+        let listener = bind_admin_socket(&path).unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o077, 0);
+        let entries: Vec<_> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        assert_eq!(entries, ["admin.sock"], "staging directory is cleaned up");
+
+        let (tx, rx) = tokio::sync::watch::channel(false);
+        let app = public_routes(Arc::new(HeadlessRuntimeState::new()));
+        let server = tokio::spawn(serve_unix_socket(listener, app, rx));
+
+        let mut stream = tokio::net::UnixStream::connect(&path).await.unwrap();
+        stream
+            .write_all(b"GET /api/health HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+
+        tx.send(true).unwrap();
+        server.await.unwrap();
+    }
 }
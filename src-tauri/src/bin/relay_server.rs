@@ -1,33 +1,46 @@
 //! Standalone v2-compatible relay server for the Chiral Network bootstrap node.
 //!
 //! Uses the same libp2p 0.53 + relay 0.17 as v2 peers, ensuring protocol compatibility.
-//! Also runs an HTTP gateway for hosting static sites uploaded by peers.
+//! Also runs an HTTP gateway for hosting static sites uploaded by peers, and
+//! fetches relay-shared content from owners over `/chiral/http/1.0.0` when
+//! their HTTP origin isn't reachable.
 //!
 //! Usage:
 //!   relay_server [--port PORT] [--secret SECRET] [--http-port HTTP_PORT]
+//!                [--dht-store memory|disk] [--metrics-bind ADDR:PORT]
 //!
 //! The secret is used to derive a deterministic keypair for a stable PeerId.
+//! `--metrics-bind` serves Prometheus `/metrics` on a separate listener,
+//! e.g. `127.0.0.1:9464`; it is off by default.
 
+use libp2p::kad::store::RecordStore as _;
 use libp2p::{
-    kad, noise, ping, relay, identify,
+    autonat, kad, noise, ping, relay, identify, request_response,
     swarm::{NetworkBehaviour, SwarmEvent},
-    tcp, yamux, Multiaddr, PeerId, StreamProtocol,
+    tcp, yamux, Multiaddr, PeerId, StreamProtocol, Swarm,
 };
 use futures::StreamExt;
 use std::error::Error;
+use std::net::SocketAddr;
 use std::sync::Arc;
 use sha2::{Sha256, Digest};
 
+use chiral_network::dht_record;
 use chiral_network::hosting_server::{self, HostingServerState};
+use chiral_network::http_p2p::{self, HttpP2pClient, HttpP2pPending};
+use chiral_network::kad_store::{self, PersistentStore, StoreMode};
+use chiral_network::metrics;
 use chiral_network::rating_storage::RatingState;
 use chiral_network::relay_share_proxy::RelayShareRegistry;
 
 #[derive(NetworkBehaviour)]
 struct RelayServerBehaviour {
     relay_server: relay::Behaviour,
-    kad: kad::Behaviour<kad::store::MemoryStore>,
+    kad: kad::Behaviour<PersistentStore>,
     ping: ping::Behaviour,
     identify: identify::Behaviour,
+    autonat: autonat::Behaviour,
+    http_p2p: http_p2p::HttpP2pBehaviour,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -35,6 +48,10 @@ struct RelayServerArgs {
     port: u16,
     http_port: u16,
     secret: String,
+    /// Kademlia record store; `None` defers to `CHIRAL_DHT_STORE`.
+    dht_store: Option<StoreMode>,
+    /// Listener for Prometheus `/metrics`; `None` leaves it off.
+    metrics_bind: Option<SocketAddr>,
 }
 
 impl Default for RelayServerArgs {
@@ -43,6 +60,8 @@ impl Default for RelayServerArgs {
             port: 4001,
             http_port: 8080,
             secret: String::from("chiral-relay-server-default"),
+            dht_store: None,
+            metrics_bind: None,
         }
     }
 }
@@ -63,6 +82,8 @@ mod tests {
         assert_eq!(parsed.port, 4001);
         assert_eq!(parsed.http_port, 8080);
         assert_eq!(parsed.secret, "chiral-relay-server-default");
+        assert_eq!(parsed.dht_store, None);
+        assert_eq!(parsed.metrics_bind, None);
     }
 
     #[test]
@@ -83,6 +104,34 @@ mod tests {
         assert_eq!(parsed.secret, "custom-secret");
     }
 
+    #[test]
+    fn relay_server_args_select_dht_store() {
+        let parsed = parse_relay_server_args(&args(&["relay_server", "--dht-store", "disk"]))
+            .expect("valid store should parse");
+        assert_eq!(parsed.dht_store, Some(StoreMode::Disk));
+
+        let err = parse_relay_server_args(&args(&["relay_server", "--dht-store", "tape"]))
+            .expect_err("unknown store should be rejected");
+        assert!(err.contains("--dht-store"));
+        assert!(err.contains("tape"));
+    }
+
+    #[test]
+    fn relay_server_args_accept_metrics_bind() {
+        let parsed =
+            parse_relay_server_args(&args(&["relay_server", "--metrics-bind", "127.0.0.1:9464"]))
+                .expect("valid metrics address should parse");
+        assert_eq!(
+            parsed.metrics_bind,
+            Some(SocketAddr::from(([127, 0, 0, 1], 9464)))
+        );
+
+        let err = parse_relay_server_args(&args(&["relay_server", "--metrics-bind", "9464"]))
+            .expect_err("a bare port should be rejected");
+        assert!(err.contains("--metrics-bind"));
+        assert!(err.contains("9464"));
+    }
+
     #[test]
     fn relay_server_args_reject_invalid_port() {
         let err = parse_relay_server_args(&args(&["relay_server", "--port", "not-a-port"]))
@@ -140,6 +189,34 @@ fn parse_relay_server_args(args: &[String]) -> Result<RelayServerArgs, String> {
                     return Err("--secret requires a value".to_string());
                 }
             }
+            "--dht-store" => {
+                if i + 1 < args.len() {
+                    let mode = args[i + 1].parse::<StoreMode>().map_err(|_| {
+                        format!(
+                            "--dht-store must be 'memory' or 'disk', got '{}'",
+                            args[i + 1]
+                        )
+                    })?;
+                    parsed.dht_store = Some(mode);
+                    i += 2;
+                } else {
+                    return Err("--dht-store requires a value".to_string());
+                }
+            }
+            "--metrics-bind" => {
+                if i + 1 < args.len() {
+                    let addr = args[i + 1].parse::<SocketAddr>().map_err(|_| {
+                        format!(
+                            "--metrics-bind requires an ADDR:PORT socket address, got '{}'",
+                            args[i + 1]
+                        )
+                    })?;
+                    parsed.metrics_bind = Some(addr);
+                    i += 2;
+                } else {
+                    return Err("--metrics-bind requires a value".to_string());
+                }
+            }
             _ => {
                 return Err(format!("Unknown argument: {}", args[i]));
             }
@@ -186,7 +263,16 @@ async fn main() -> Result<(), Box<dyn Error>> {
         port,
         http_port,
         secret,
+        dht_store,
+        metrics_bind,
     } = parsed_args;
+    let dht_store = match dht_store.map(Ok).unwrap_or_else(kad_store::store_mode_from_env) {
+        Ok(mode) => mode,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
 
     // Generate deterministic keypair from secret
     let local_key = match keypair_from_secret(&secret) {
@@ -202,6 +288,7 @@ async fn main() -> Result<(), Box<dyn Error>> {
     println!("PeerId: {}", local_peer_id);
     println!("P2P Port: {}", port);
     println!("HTTP Port: {}", http_port);
+    println!("DHT store: {:?}", dht_store);
 
     // -----------------------------------------------------------------------
     // Start HTTP gateway for hosting
@@ -221,7 +308,11 @@ async fn main() -> Result<(), Box<dyn Error>> {
     let relay_share_data_dir = dirs::data_dir()
         .unwrap_or_else(|| std::path::PathBuf::from("."))
         .join("chiral-network");
-    let relay_share_state = Arc::new(RelayShareRegistry::new(relay_share_data_dir));
+    // Share proxy requests that need the owner's libp2p connection are
+    // queued here and sent by the swarm loop below.
+    let (p2p_http, mut p2p_http_calls) = HttpP2pClient::new(256);
+    let relay_share_state =
+        Arc::new(RelayShareRegistry::new(relay_share_data_dir).with_p2p_http(p2p_http));
     match relay_share_state.load_from_disk().await {
         Ok(()) => println!("Relay share registry loaded from disk"),
         Err(err) => eprintln!("[RELAY-SHARE] {}", err),
@@ -246,14 +337,29 @@ async fn main() -> Result<(), Box<dyn Error>> {
     // Keep shutdown sender alive — drop it on process exit
     let _http_shutdown = http_shutdown_tx;
 
+    if let Some(addr) = metrics_bind {
+        match metrics::start_metrics_server(addr).await {
+            Ok(addr) => println!("Metrics listening on http://{}/metrics", addr),
+            Err(e) => eprintln!("WARNING: {}", e),
+        }
+    }
+
     // -----------------------------------------------------------------------
     // Configure libp2p
     // -----------------------------------------------------------------------
 
     // Configure Kademlia
-    let kad_store = kad::store::MemoryStore::new(local_peer_id);
+    // With `--dht-store disk` a restarted bootstrap node keeps serving the
+    // records and provider entries it held instead of starting empty.
+    let kad_store = kad_store::build_store(
+        dht_store,
+        local_peer_id,
+        &chiral_network::network::data_dir().join(kad_store::DHT_STORE_DIR),
+    );
     let mut kad_config = kad::Config::default();
     kad_config.set_protocol_names(vec![StreamProtocol::new("/chiral/kad/1.0.0")]);
+    // Store only records whose signed envelope and contents validate.
+    kad_config.set_record_filtering(kad::StoreInserts::FilterBoth);
     let mut kad = kad::Behaviour::with_config(local_peer_id, kad_store, kad_config);
     kad.set_mode(Some(kad::Mode::Server));
 
@@ -273,6 +379,10 @@ async fn main() -> Result<(), Box<dyn Error>> {
         ping::Config::new().with_interval(std::time::Duration::from_secs(15)),
     );
 
+    // AutoNAT server: clients register bootstrap nodes as probe servers and
+    // ask us to dial them back to learn whether they're behind NAT.
+    let autonat = autonat::Behaviour::new(local_peer_id, autonat::Config::default());
+
     // Configure relay server with limits sized for file transfers.
     // Default max_circuit_bytes is 128 KiB — far too small for chunked file
     // transfers (each chunk is 256 KB). Increase to allow full downloads.
@@ -283,6 +393,10 @@ async fn main() -> Result<(), Box<dyn Error>> {
     relay_config.max_circuits_per_peer = 16;
     let relay_server = relay::Behaviour::new(local_peer_id, relay_config);
 
+    let http_p2p = http_p2p::behaviour(request_response::ProtocolSupport::Outbound);
+    let mut http_p2p_pending = HttpP2pPending::default();
+
+    let mut transport_metrics = metrics::Registry::default();
     let mut swarm = libp2p::SwarmBuilder::with_existing_identity(local_key)
         .with_tokio()
         .with_tcp(
@@ -290,16 +404,21 @@ async fn main() -> Result<(), Box<dyn Error>> {
             noise::Config::new,
             yamux::Config::default,
         )?
+        .with_quic()
+        .with_bandwidth_metrics(&mut transport_metrics)
         .with_behaviour(|_key| {
             RelayServerBehaviour {
                 relay_server,
                 kad,
                 ping,
                 identify,
+                autonat,
+                http_p2p,
             }
         })?
         .with_swarm_config(|c| c.with_idle_connection_timeout(std::time::Duration::from_secs(3600)))
         .build();
+    metrics::global().set_transport_registry(transport_metrics);
 
     // Listen on specified port (all interfaces)
     let listen_addr: Multiaddr = format!("/ip4/0.0.0.0/tcp/{}", port).parse()?;
@@ -311,6 +430,16 @@ async fn main() -> Result<(), Box<dyn Error>> {
     swarm.listen_on(listen_addr_v6)?;
     println!("Listening on /ip6/::/tcp/{}", port);
 
+    // QUIC on the same port number (UDP) for faster handshakes and better
+    // hole-punching; clients prefer it when both are advertised.
+    let quic_addr: Multiaddr = format!("/ip4/0.0.0.0/udp/{}/quic-v1", port).parse()?;
+    swarm.listen_on(quic_addr)?;
+    println!("Listening on /ip4/0.0.0.0/udp/{}/quic-v1", port);
+
+    let quic_addr_v6: Multiaddr = format!("/ip6/::/udp/{}/quic-v1", port).parse()?;
+    swarm.listen_on(quic_addr_v6)?;
+    println!("Listening on /ip6/::/udp/{}/quic-v1", port);
+
     // CRITICAL: Add external addresses explicitly so relay RESERVE_OK responses
     // include the server's public addresses. Without this, the first RESERVE_OK
     // would contain EMPTY addresses (because Identify hasn't discovered external
@@ -373,19 +502,67 @@ async fn main() -> Result<(), Box<dyn Error>> {
     // Print the multiaddr that peers should use
     println!("Peers should connect to:");
     println!("  /ip4/<YOUR_PUBLIC_IP>/tcp/{}/p2p/{}", port, local_peer_id);
+    println!(
+        "  /ip4/<YOUR_PUBLIC_IP>/udp/{}/quic-v1/p2p/{}",
+        port, local_peer_id
+    );
     println!();
 
     loop {
-        match swarm.select_next_some().await {
+        let event = tokio::select! {
+            event = swarm.select_next_some() => event,
+            Some(call) = p2p_http_calls.recv() => {
+                http_p2p_pending.send(&mut swarm.behaviour_mut().http_p2p, call);
+                continue;
+            }
+        };
+        match event {
             SwarmEvent::Behaviour(event) => {
                 match event {
                     RelayServerBehaviourEvent::RelayServer(event) => {
+                        match &event {
+                            relay::Event::CircuitReqAccepted { .. } => {
+                                metrics::global().relay_circuit_opened();
+                            }
+                            relay::Event::CircuitClosed { .. } => {
+                                metrics::global().relay_circuit_closed();
+                            }
+                            _ => {}
+                        }
                         println!("[RELAY] {:?}", event);
                     }
                     RelayServerBehaviourEvent::Kad(event) => {
-                        match &event {
+                        match event {
                             kad::Event::RoutingUpdated { peer, .. } => {
                                 println!("[KAD] Routing updated for peer: {}", peer);
+                                sample_swarm_metrics(&mut swarm);
+                            }
+                            kad::Event::OutboundQueryProgressed { result, stats, step, .. }
+                                if step.last =>
+                            {
+                                metrics::global().observe_kad_query(&result, stats.duration());
+                            }
+                            kad::Event::InboundRequest {
+                                request:
+                                    kad::InboundRequest::PutRecord {
+                                        source,
+                                        record: Some(record),
+                                        ..
+                                    },
+                            } => {
+                                let store = swarm.behaviour_mut().kad.store_mut();
+                                let now = dht_record::now_ms();
+                                if let Err(e) = dht_record::store_inbound(store, record, now) {
+                                    println!("[KAD] Rejected record from {}: {}", source, e);
+                                }
+                            }
+                            kad::Event::InboundRequest {
+                                request:
+                                    kad::InboundRequest::AddProvider {
+                                        record: Some(record),
+                                    },
+                            } => {
+                                let _ = swarm.behaviour_mut().kad.store_mut().add_provider(record);
                             }
                             kad::Event::InboundRequest { request } => {
                                 println!("[KAD] Inbound request: {:?}", request);
@@ -463,6 +640,13 @@ async fn main() -> Result<(), Box<dyn Error>> {
                     }
                     RelayServerBehaviourEvent::Identify(_) => {}
                     RelayServerBehaviourEvent::Ping(_) => {}
+                    RelayServerBehaviourEvent::Autonat(autonat::Event::InboundProbe(event)) => {
+                        println!("[AUTONAT] {:?}", event);
+                    }
+                    RelayServerBehaviourEvent::Autonat(_) => {}
+                    RelayServerBehaviourEvent::HttpP2p(event) => {
+                        http_p2p_pending.on_event(event);
+                    }
                 }
             }
             SwarmEvent::NewListenAddr { address, .. } => {
@@ -471,10 +655,12 @@ async fn main() -> Result<(), Box<dyn Error>> {
             SwarmEvent::ConnectionEstablished { peer_id, endpoint, num_established, .. } => {
                 println!("[CONN] Connection established with {} (total: {}, addr: {})",
                     peer_id, num_established, endpoint.get_remote_address());
+                sample_swarm_metrics(&mut swarm);
             }
             SwarmEvent::ConnectionClosed { peer_id, num_established, cause, .. } => {
                 println!("[CONN] Connection closed with {} (remaining: {}, cause: {:?})",
                     peer_id, num_established, cause);
+                sample_swarm_metrics(&mut swarm);
             }
             SwarmEvent::IncomingConnection { local_addr, send_back_addr, .. } => {
                 println!("[CONN] Incoming connection: local={}, remote={}", local_addr, send_back_addr);
@@ -494,6 +680,17 @@ async fn main() -> Result<(), Box<dyn Error>> {
     }
 }
 
+/// Refresh the connected-peer and routing-table gauges.
+fn sample_swarm_metrics(swarm: &mut Swarm<RelayServerBehaviour>) {
+    let routing_table_peers = swarm
+        .behaviour_mut()
+        .kad
+        .kbuckets()
+        .map(|bucket| bucket.num_entries())
+        .sum();
+    metrics::global().set_swarm_gauges(swarm.connected_peers().count(), routing_table_peers);
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
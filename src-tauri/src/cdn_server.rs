@@ -29,7 +29,6 @@ use axum::{
 };
 use serde::{Deserialize, Serialize};
 use serde_json::json;
-use sha2::{Digest, Sha256};
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
@@ -37,6 +36,7 @@ use std::time::{SystemTime, UNIX_EPOCH};
 use tokio::sync::Mutex as AsyncMutex;
 
 use crate::dht::DhtService;
+use crate::http_range;
 use crate::network;
 
 /// One-line description of a transaction's `from` / `to` / `value`
@@ -145,7 +145,8 @@ pub struct CdnState {
     /// unsigned. Operators set it via the `CHIRAL_CDN_PRIVATE_KEY`
     /// env var at process start. Empty string means "unsigned mode" —
     /// CDN serves only free files until the operator wires a key.
-    pub wallet_private_key: String,
+    /// Swapped at runtime when the daemon's wallet is unlocked or locked.
+    wallet_private_key: std::sync::RwLock<String>,
     pub price_wei_per_mb_month: u128,
     pub dht: Arc<AsyncMutex<Option<Arc<DhtService>>>>,
 }
@@ -176,12 +177,26 @@ impl CdnState {
             sites_registry_path,
             sites_registry: AsyncMutex::new(sites_registry),
             wallet_address,
-            wallet_private_key,
+            wallet_private_key: std::sync::RwLock::new(wallet_private_key),
             price_wei_per_mb_month,
             dht,
         }
     }
 
+    /// Current signing key, or an empty string while the wallet is locked.
+    pub fn signing_key(&self) -> String {
+        self.wallet_private_key
+            .read()
+            .map(|key| key.clone())
+            .unwrap_or_default()
+    }
+
+    pub fn set_signing_key(&self, private_key: String) {
+        if let Ok(mut key) = self.wallet_private_key.write() {
+            *key = private_key;
+        }
+    }
+
     /// Mirror of `with_registry` for the sites registry. Acquires the
     /// per-sites lock, lets the closure mutate the in-memory vec, and
     /// re-persists to disk before releasing.
@@ -215,6 +230,33 @@ impl CdnState {
     async fn snapshot(&self) -> Vec<CdnEntry> {
         self.registry.lock().await.clone()
     }
+
+    /// Refresh the storage and active-entry gauges on `/metrics` from the
+    /// files and sites that haven't expired yet.
+    pub async fn record_usage_metrics(&self) {
+        let Ok(now) = now_secs() else {
+            return;
+        };
+        let (file_bytes, files) = self
+            .registry
+            .lock()
+            .await
+            .iter()
+            .filter(|e| e.expires_at > now)
+            .fold((0u64, 0usize), |(bytes, count), e| {
+                (bytes.saturating_add(e.file_size), count + 1)
+            });
+        let (site_bytes, sites) = self
+            .sites_registry
+            .lock()
+            .await
+            .iter()
+            .filter(|e| e.expires_at > now)
+            .fold((0u64, 0usize), |(bytes, count), e| {
+                (bytes.saturating_add(e.total_size_bytes), count + 1)
+            });
+        crate::metrics::global().set_cdn_usage(file_bytes.saturating_add(site_bytes), files, sites);
+    }
 }
 
 async fn load_registry(path: &PathBuf) -> Vec<CdnEntry> {
@@ -590,6 +632,7 @@ async fn upload(
         Err(e) => return err(StatusCode::INTERNAL_SERVER_ERROR, &format!("Verify task panicked: {e}")),
     };
     if !mined {
+        crate::metrics::global().payment_verification("cdn_upload", false);
         return err(
             StatusCode::PAYMENT_REQUIRED,
             &format!("Payment not confirmed in time. Tx: {payment_tx}"),
@@ -601,8 +644,9 @@ async fn upload(
     // the cause — e.g. payment sent to a stale CDN wallet address from
     // a cached session).
     match crate::wallet::verify_tx_details(&payment_tx, &owner_wallet, &s.wallet_address, min_accepted_wei).await {
-        Ok(true) => {}
+        Ok(true) => crate::metrics::global().payment_verification("cdn_upload", true),
         Ok(false) => {
+            crate::metrics::global().payment_verification("cdn_upload", false);
             let observed = describe_tx(&payment_tx).await;
             return err(
                 StatusCode::PAYMENT_REQUIRED,
@@ -616,11 +660,7 @@ async fn upload(
     }
 
     // Hash + write to disk.
-    let file_hash = {
-        let mut hasher = Sha256::new();
-        hasher.update(&file_data);
-        hex::encode(hasher.finalize())
-    };
+    let file_hash = crate::merkle::MerkleTree::from_bytes(&file_data).root();
     let _ = tokio::fs::create_dir_all(&s.storage_dir).await;
     let file_path = s.storage_dir.join(&file_hash);
     if let Err(e) = tokio::fs::write(&file_path, &file_data).await {
@@ -645,7 +685,7 @@ async fn upload(
             file_size,
             download_price_wei,
             &s.wallet_address,
-            &s.wallet_private_key,
+            &s.signing_key(),
             now,
         )
         .await;
@@ -782,7 +822,7 @@ async fn update_price(
                 entry.file_size,
                 new_price_wei,
                 &s.wallet_address,
-                &s.wallet_private_key,
+                &s.signing_key(),
                 entry.uploaded_at,
             )
             .await;
@@ -861,7 +901,7 @@ pub async fn reseed_on_startup(state: Arc<CdnState>) {
             entry.file_size,
             download_price_wei,
             &state.wallet_address,
-            &state.wallet_private_key,
+            &state.signing_key(),
             entry.uploaded_at,
         )
         .await;
@@ -898,6 +938,7 @@ pub async fn expiration_loop(state: Arc<CdnState>) {
                     unregister_in_dht(d, &entry.file_hash).await;
                 }
             }
+            crate::metrics::global().cdn_expired("file", expired.len());
             println!("[CDN] Expiration cleanup removed {} files", expired.len());
         }
 
@@ -915,11 +956,13 @@ pub async fn expiration_loop(state: Arc<CdnState>) {
             for site in &expired_sites {
                 let _ = tokio::fs::remove_dir_all(state.sites_dir.join(&site.site_id)).await;
             }
+            crate::metrics::global().cdn_expired("site", expired_sites.len());
             println!(
                 "[CDN] Expiration cleanup removed {} sites",
                 expired_sites.len()
             );
         }
+        state.record_usage_metrics().await;
     }
 }
 
@@ -1272,6 +1315,7 @@ async fn upload_site(
         }
     };
     if !mined {
+        crate::metrics::global().payment_verification("cdn_site_upload", false);
         let _ = tokio::fs::remove_dir_all(&staging_dir).await;
         return err(
             StatusCode::PAYMENT_REQUIRED,
@@ -1288,8 +1332,9 @@ async fn upload_site(
     )
     .await
     {
-        Ok(true) => {}
+        Ok(true) => crate::metrics::global().payment_verification("cdn_site_upload", true),
         Ok(false) => {
+            crate::metrics::global().payment_verification("cdn_site_upload", false);
             let _ = tokio::fs::remove_dir_all(&staging_dir).await;
             return err(
                 StatusCode::PAYMENT_REQUIRED,
@@ -1461,23 +1506,30 @@ async fn serve_site_redirect(AxumPath(site_id): AxumPath<String>) -> Response {
 async fn serve_site_root(
     State(s): State<Arc<CdnState>>,
     AxumPath(site_id): AxumPath<String>,
+    headers: HeaderMap,
 ) -> Response {
-    serve_site_path_inner(&s, &site_id, "index.html").await
+    serve_site_path_inner(&s, &site_id, "index.html", &headers).await
 }
 
 async fn serve_site_file(
     State(s): State<Arc<CdnState>>,
     AxumPath((site_id, file_path)): AxumPath<(String, String)>,
+    headers: HeaderMap,
 ) -> Response {
     let path = if file_path.is_empty() || file_path == "/" {
         "index.html"
     } else {
         &file_path
     };
-    serve_site_path_inner(&s, &site_id, path).await
+    serve_site_path_inner(&s, &site_id, path, &headers).await
 }
 
-async fn serve_site_path_inner(s: &CdnState, site_id: &str, requested_path: &str) -> Response {
+async fn serve_site_path_inner(
+    s: &CdnState,
+    site_id: &str,
+    requested_path: &str,
+    headers: &HeaderMap,
+) -> Response {
     if validate_site_id(site_id).is_err() {
         return err(StatusCode::BAD_REQUEST, "Invalid site id");
     }
@@ -1511,22 +1563,17 @@ async fn serve_site_path_inner(s: &CdnState, site_id: &str, requested_path: &str
     if !canonical.starts_with(&canonical_root) {
         return err(StatusCode::FORBIDDEN, "Path traversal not allowed");
     }
-    let data = match tokio::fs::read(&canonical).await {
-        Ok(d) => d,
-        Err(_) => return err(StatusCode::NOT_FOUND, "File not found"),
-    };
     let ext = canonical.extension().and_then(|e| e.to_str()).unwrap_or("");
-    let content_type = crate::hosting::mime_from_extension(ext);
-    (
-        StatusCode::OK,
-        [
-            ("Content-Type", content_type.to_string()),
-            ("Content-Length", data.len().to_string()),
-            ("Cache-Control", "public, max-age=3600".to_string()),
-        ],
-        data,
-    )
-        .into_response()
+    let file = http_range::FileResponse {
+        content_type: crate::hosting::mime_from_extension(ext).to_string(),
+        path: canonical,
+        content_hash: None,
+        cache_control: "public, max-age=3600",
+        content_disposition: None,
+    };
+    http_range::serve_file(headers, file)
+        .await
+        .unwrap_or_else(|_| err(StatusCode::NOT_FOUND, "File not found"))
 }
 
 // ============================================================================
@@ -0,0 +1,1434 @@
+//! Local index of the chain for wallet history and mined blocks.
+//!
+//! A background follower (`run`) walks the chain from genesis in batches of
+//! `SYNC_BATCH` blocks and then keeps up with the head, recording every
+//! transaction under its sender and recipient and every block under its
+//! miner. History and mined-block queries are then answered from memory
+//! instead of rescanning recent blocks over RPC on every call.
+//!
+//! The hashes of the newest `REORG_DEPTH` indexed blocks are kept. When the
+//! indexed tip drops off the chain, the index rolls back to the newest block
+//! that is still canonical and re-indexes from there. A deeper reorg, or a
+//! different chain behind the endpoint, starts the index over; the index
+//! records its genesis hash so a different chain is caught even when the
+//! heights line up.
+//!
+//! The index is saved to `<data_dir>/chain-index.json`, so the CLI can read
+//! what the desktop app or daemon has built. Blocks indexed after that
+//! snapshot are appended to `chain-index.journal` and replayed on load; the
+//! journal is folded into a new snapshot once it outgrows half the index.
+
+use crate::rpc_client;
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+/// Blocks fetched per JSON-RPC batch while syncing.
+pub const SYNC_BATCH: u64 = 100;
+/// Indexed blocks whose hashes are kept for reorg detection.
+pub const REORG_DEPTH: u64 = 256;
+/// Queries fall back to a live scan when the index is further behind.
+pub const MAX_SERVE_LAG: u64 = 8;
+pub const DEFAULT_PAGE_SIZE: usize = 50;
+pub const MAX_PAGE_SIZE: usize = 500;
+
+const INDEX_FILE: &str = "chain-index.json";
+const JOURNAL_EXTENSION: &str = "journal";
+/// Journal entries allowed beyond half the indexed blocks before the
+/// journal is folded into a new snapshot.
+const COMPACT_SLACK_ENTRIES: u64 = 1024;
+/// Pause between head polls once the index has caught up.
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+/// Save at least this often while catching up.
+const PERSIST_INTERVAL: Duration = Duration::from_secs(30);
+
+/// A transaction as recorded under its sender and recipient.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IndexedTx {
+    pub hash: String,
+    pub from: String,
+    /// Empty for contract creations
+    pub to: String,
+    /// u128 as string for JSON safety
+    pub value_wei: String,
+    pub gas: u64,
+    pub block_number: u64,
+    pub timestamp: u64,
+}
+
+/// A block as recorded under its miner.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IndexedBlock {
+    pub number: u64,
+    pub timestamp: u64,
+    pub difficulty: u64,
+}
+
+/// The parts of an `eth_getBlockByNumber` result the index keeps.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockSummary {
+    pub number: u64,
+    pub hash: String,
+    pub parent_hash: String,
+    pub timestamp: u64,
+    pub miner: String,
+    pub difficulty: u64,
+    /// Empty when the block was fetched without full transactions
+    pub transactions: Vec<IndexedTx>,
+}
+
+fn hex_field_str<'a>(value: &'a serde_json::Value, field: &str) -> Result<&'a str, String> {
+    value
+        .get(field)
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| format!("eth_getBlockByNumber missing string `{field}` field"))
+}
+
+fn hex_field_u64(value: &serde_json::Value, field: &str) -> Result<u64, String> {
+    rpc_client::hex_to_u64(hex_field_str(value, field)?)
+        .map_err(|e| format!("eth_getBlockByNumber {field}: {e}"))
+}
+
+impl BlockSummary {
+    pub fn from_rpc(block: &serde_json::Value) -> Result<Self, String> {
+        let number = hex_field_u64(block, "number")?;
+        let timestamp = hex_field_u64(block, "timestamp")?;
+        let mut transactions = Vec::new();
+        let txs = block.get("transactions").and_then(|t| t.as_array());
+        // Header-only fetches list transaction hashes as plain strings.
+        for tx in txs.into_iter().flatten().filter(|tx| tx.is_object()) {
+            let value_wei = rpc_client::hex_to_u128(hex_field_str(tx, "value")?)
+                .map_err(|e| format!("eth_getBlockByNumber transaction value: {e}"))?;
+            transactions.push(IndexedTx {
+                hash: hex_field_str(tx, "hash")?.to_lowercase(),
+                from: hex_field_str(tx, "from")?.to_lowercase(),
+                to: tx
+                    .get("to")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("")
+                    .to_lowercase(),
+                value_wei: value_wei.to_string(),
+                gas: hex_field_u64(tx, "gas")?,
+                block_number: number,
+                timestamp,
+            });
+        }
+        Ok(BlockSummary {
+            number,
+            hash: hex_field_str(block, "hash")?.to_lowercase(),
+            parent_hash: hex_field_str(block, "parentHash")?.to_lowercase(),
+            timestamp,
+            miner: hex_field_str(block, "miner")?.to_lowercase(),
+            difficulty: hex_field_u64(block, "difficulty")?,
+            transactions,
+        })
+    }
+}
+
+/// Pagination and date filter for index queries. Times are unix seconds
+/// and both bounds are inclusive.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IndexQuery {
+    #[serde(default)]
+    pub offset: usize,
+    pub limit: Option<usize>,
+    pub since: Option<u64>,
+    pub until: Option<u64>,
+}
+
+impl IndexQuery {
+    pub fn page_size(&self) -> usize {
+        self.limit
+            .unwrap_or(DEFAULT_PAGE_SIZE)
+            .clamp(1, MAX_PAGE_SIZE)
+    }
+
+    pub fn matches(&self, timestamp: u64) -> bool {
+        self.since.is_none_or(|since| timestamp >= since)
+            && self.until.is_none_or(|until| timestamp <= until)
+    }
+
+    /// One page of `newest_first`, after the date filter.
+    pub fn page<T>(
+        &self,
+        newest_first: impl IntoIterator<Item = T>,
+        timestamp: impl Fn(&T) -> u64,
+    ) -> Page<T> {
+        let mut total = 0;
+        let mut items = Vec::new();
+        for item in newest_first {
+            if !self.matches(timestamp(&item)) {
+                continue;
+            }
+            if total >= self.offset && items.len() < self.page_size() {
+                items.push(item);
+            }
+            total += 1;
+        }
+        Page { items, total }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// Matches across all pages
+    pub total: usize,
+}
+
+impl<T> Page<T> {
+    pub fn map<U>(self, f: impl FnMut(T) -> U) -> Page<U> {
+        Page {
+            items: self.items.into_iter().map(f).collect(),
+            total: self.total,
+        }
+    }
+}
+
+/// One change to the index since the snapshot.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+enum JournalOp {
+    Apply(BlockSummary),
+    Rollback(Option<u64>),
+}
+
+/// One journal line.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct JournalEntry {
+    /// One more than the entry before it
+    seq: u64,
+    op: JournalOp,
+}
+
+/// Disk work taken from the index under its lock and done outside it.
+enum PendingWrite {
+    /// Append these entries to the journal.
+    Append {
+        journal: PathBuf,
+        entries: Vec<JournalEntry>,
+    },
+    /// Replace the snapshot with this copy of the index and empty the
+    /// journal.
+    Compact {
+        path: PathBuf,
+        journal: PathBuf,
+        index: Box<ChainIndex>,
+    },
+}
+
+impl PendingWrite {
+    fn run(&self) -> Result<(), String> {
+        match self {
+            PendingWrite::Append { journal, entries } => {
+                let mut lines = String::new();
+                for entry in entries {
+                    lines.push_str(&serde_json::to_string(entry).map_err(|e| e.to_string())?);
+                    lines.push('\n');
+                }
+                if let Some(dir) = journal.parent() {
+                    std::fs::create_dir_all(dir)
+                        .map_err(|e| format!("create {}: {}", dir.display(), e))?;
+                }
+                std::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(journal)
+                    .and_then(|mut file| file.write_all(lines.as_bytes()))
+                    .map_err(|e| format!("append to {}: {}", journal.display(), e))
+            }
+            PendingWrite::Compact {
+                path,
+                journal,
+                index,
+            } => {
+                crate::json_file::save_json_atomic(path, index)?;
+                // Entries left behind by a crash before this point are at
+                // or below the snapshot's `journal_seq` and skipped on load.
+                std::fs::write(journal, "")
+                    .map_err(|e| format!("truncate {}: {}", journal.display(), e))
+            }
+        }
+    }
+}
+
+fn journal_path(path: &Path) -> PathBuf {
+    path.with_extension(JOURNAL_EXTENSION)
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChainIndex {
+    /// Hash of block 0 of the indexed chain
+    #[serde(default)]
+    genesis_hash: Option<String>,
+    /// Highest block indexed
+    head: Option<u64>,
+    /// Hashes of the newest `REORG_DEPTH` indexed blocks
+    recent_hashes: BTreeMap<u64, String>,
+    /// Lowercase address -> transactions, oldest first
+    transactions: HashMap<String, Vec<IndexedTx>>,
+    /// Lowercase coinbase -> mined blocks, oldest first
+    mined: HashMap<String, Vec<IndexedBlock>>,
+    /// Last journal entry reflected in this state
+    #[serde(default)]
+    journal_seq: u64,
+    /// Chain head at the last sync; unknown until this process has synced
+    #[serde(skip)]
+    chain_head: Option<u64>,
+    /// Journal entries not yet written
+    #[serde(skip)]
+    unsaved: Vec<JournalEntry>,
+    /// Entries in the journal file since the snapshot
+    #[serde(skip)]
+    journaled: u64,
+    /// Set when the next save must write a fresh snapshot
+    #[serde(skip)]
+    needs_compact: bool,
+    #[serde(skip)]
+    path: Option<PathBuf>,
+}
+
+impl ChainIndex {
+    pub fn load(path: PathBuf) -> Self {
+        let mut index = match std::fs::read_to_string(&path) {
+            Ok(json) => serde_json::from_str::<ChainIndex>(&json).unwrap_or_else(|e| {
+                eprintln!(
+                    "[ChainIndex] Ignoring malformed index {}: {}; re-indexing",
+                    path.display(),
+                    e
+                );
+                ChainIndex::default()
+            }),
+            Err(_) => ChainIndex::default(),
+        };
+        index.replay(&journal_path(&path));
+        index.path = Some(path);
+        index
+    }
+
+    /// `load`, starting over when the saved index was built from a chain
+    /// other than the one whose genesis block is `genesis_hash`.
+    pub fn load_for_chain(path: PathBuf, genesis_hash: &str) -> Self {
+        let index = Self::load(path);
+        if index.head.is_none() || index.genesis_hash.as_deref() == Some(genesis_hash) {
+            return index;
+        }
+        eprintln!(
+            "[ChainIndex] Ignoring index of another chain (genesis {}); re-indexing",
+            index.genesis_hash.as_deref().unwrap_or("unknown")
+        );
+        ChainIndex {
+            needs_compact: true,
+            path: index.path,
+            ..ChainIndex::default()
+        }
+    }
+
+    /// Apply the journal entries written after the snapshot. A torn or
+    /// out-of-sequence entry ends the replay and schedules a compaction,
+    /// since anything appended after it would be lost too.
+    fn replay(&mut self, journal: &Path) {
+        let Ok(file) = std::fs::File::open(journal) else {
+            return;
+        };
+        for line in BufReader::new(file).lines() {
+            let entry = line
+                .ok()
+                .and_then(|line| serde_json::from_str::<JournalEntry>(&line).ok());
+            match entry {
+                // Already in the snapshot; compacting drops it.
+                Some(entry) if entry.seq <= self.journal_seq => self.needs_compact = true,
+                Some(entry) if entry.seq == self.journal_seq + 1 => {
+                    self.apply_op(entry.op);
+                    self.journal_seq = entry.seq;
+                    self.journaled += 1;
+                }
+                _ => {
+                    eprintln!(
+                        "[ChainIndex] Stopped replaying {} at an unreadable entry",
+                        journal.display()
+                    );
+                    self.needs_compact = true;
+                    break;
+                }
+            }
+        }
+    }
+
+    fn apply_op(&mut self, op: JournalOp) {
+        match op {
+            JournalOp::Apply(block) => {
+                self.index_block(block);
+            }
+            JournalOp::Rollback(keep_through) => {
+                self.drop_above(keep_through);
+            }
+        }
+    }
+
+    /// Queue `op` for the journal. Indexes that are never saved keep none.
+    fn record(&mut self, op: JournalOp) {
+        if self.path.is_none() {
+            return;
+        }
+        self.journal_seq += 1;
+        self.unsaved.push(JournalEntry {
+            seq: self.journal_seq,
+            op,
+        });
+    }
+
+    /// Save changes since the last save.
+    pub fn persist(&mut self) -> Result<(), String> {
+        let Some(write) = self.take_write() else {
+            return Ok(());
+        };
+        let written = write.run();
+        if written.is_err() {
+            self.needs_compact = true;
+        }
+        written
+    }
+
+    /// The disk work that saves changes since the last save, for doing
+    /// outside the lock: new entries for the journal, or a copy of the
+    /// index once the journal has outgrown half of it. Counts as saved; a
+    /// failed write must set `needs_compact`, as a failed append may have
+    /// left a torn line.
+    fn take_write(&mut self) -> Option<PendingWrite> {
+        let path = self.path.clone()?;
+        let journal = journal_path(&path);
+        let journaled = self.journaled + self.unsaved.len() as u64;
+        if self.needs_compact || journaled > self.next_block() / 2 + COMPACT_SLACK_ENTRIES {
+            self.needs_compact = false;
+            self.unsaved.clear();
+            self.journaled = 0;
+            let copy = ChainIndex {
+                genesis_hash: self.genesis_hash.clone(),
+                head: self.head,
+                recent_hashes: self.recent_hashes.clone(),
+                transactions: self.transactions.clone(),
+                mined: self.mined.clone(),
+                journal_seq: self.journal_seq,
+                ..ChainIndex::default()
+            };
+            return Some(PendingWrite::Compact {
+                path,
+                journal,
+                index: Box::new(copy),
+            });
+        }
+        if self.unsaved.is_empty() {
+            return None;
+        }
+        self.journaled = journaled;
+        Some(PendingWrite::Append {
+            journal,
+            entries: std::mem::take(&mut self.unsaved),
+        })
+    }
+
+    pub fn head(&self) -> Option<u64> {
+        self.head
+    }
+
+    pub fn genesis_hash(&self) -> Option<&str> {
+        self.genesis_hash.as_deref()
+    }
+
+    pub fn chain_head(&self) -> Option<u64> {
+        self.chain_head
+    }
+
+    pub fn set_chain_head(&mut self, chain_head: u64) {
+        self.chain_head = Some(chain_head);
+    }
+
+    pub fn next_block(&self) -> u64 {
+        self.head.map_or(0, |head| head + 1)
+    }
+
+    pub fn hash_at(&self, number: u64) -> Option<&str> {
+        self.recent_hashes.get(&number).map(String::as_str)
+    }
+
+    /// Whether the index is close enough to `chain_head` to answer queries.
+    pub fn covers(&self, chain_head: u64) -> bool {
+        self.head
+            .is_some_and(|head| head.saturating_add(MAX_SERVE_LAG) >= chain_head)
+    }
+
+    /// Whether this process has synced the index up to the chain head.
+    pub fn caught_up(&self) -> bool {
+        self.chain_head
+            .is_some_and(|chain_head| self.covers(chain_head))
+    }
+
+    /// Index the block after the current head. Returns false, leaving the
+    /// index unchanged, when `block` is not that block or does not build on
+    /// the indexed tip.
+    pub fn apply(&mut self, block: BlockSummary) -> bool {
+        let op = JournalOp::Apply(block.clone());
+        if !self.index_block(block) {
+            return false;
+        }
+        self.record(op);
+        true
+    }
+
+    fn index_block(&mut self, block: BlockSummary) -> bool {
+        if block.number != self.next_block() {
+            return false;
+        }
+        if let Some(head) = self.head {
+            if self
+                .hash_at(head)
+                .is_some_and(|hash| hash != block.parent_hash)
+            {
+                return false;
+            }
+        }
+        for tx in block.transactions {
+            if !tx.to.is_empty() && tx.to != tx.from {
+                self.transactions
+                    .entry(tx.to.clone())
+                    .or_default()
+                    .push(tx.clone());
+            }
+            self.transactions
+                .entry(tx.from.clone())
+                .or_default()
+                .push(tx);
+        }
+        self.mined
+            .entry(block.miner)
+            .or_default()
+            .push(IndexedBlock {
+                number: block.number,
+                timestamp: block.timestamp,
+                difficulty: block.difficulty,
+            });
+        if block.number == 0 {
+            self.genesis_hash = Some(block.hash.clone());
+        }
+        self.recent_hashes.insert(block.number, block.hash);
+        if let Some(oldest) = (block.number + 1).checked_sub(REORG_DEPTH) {
+            self.recent_hashes = self.recent_hashes.split_off(&oldest);
+        }
+        self.head = Some(block.number);
+        true
+    }
+
+    /// Drop every block above `keep_through`; `None` drops everything.
+    pub fn rollback(&mut self, keep_through: Option<u64>) {
+        if self.drop_above(keep_through) {
+            self.record(JournalOp::Rollback(keep_through));
+        }
+    }
+
+    fn drop_above(&mut self, keep_through: Option<u64>) -> bool {
+        let Some(keep) = keep_through else {
+            self.genesis_hash = None;
+            self.head = None;
+            self.recent_hashes.clear();
+            self.transactions.clear();
+            self.mined.clear();
+            return true;
+        };
+        if self.head.is_none_or(|head| head <= keep) {
+            return false;
+        }
+        for txs in self.transactions.values_mut() {
+            txs.retain(|tx| tx.block_number <= keep);
+        }
+        self.transactions.retain(|_, txs| !txs.is_empty());
+        for blocks in self.mined.values_mut() {
+            blocks.retain(|block| block.number <= keep);
+        }
+        self.mined.retain(|_, blocks| !blocks.is_empty());
+        self.recent_hashes.retain(|number, _| *number <= keep);
+        self.head = Some(keep);
+        true
+    }
+
+    /// Newest indexed block whose hash is still `canonical` at its height.
+    pub fn fork_point(&self, canonical: &HashMap<u64, String>) -> Option<u64> {
+        self.recent_hashes
+            .iter()
+            .rev()
+            .find(|(number, hash)| canonical.get(number) == Some(*hash))
+            .map(|(number, _)| *number)
+    }
+
+    /// Transactions sent or received by `address`, newest first.
+    pub fn transactions(&self, address: &str, query: &IndexQuery) -> Page<IndexedTx> {
+        let txs = self.transactions.get(&address.trim().to_lowercase());
+        query.page(txs.into_iter().flatten().rev().cloned(), |tx| tx.timestamp)
+    }
+
+    /// Blocks mined by `coinbase`, newest first.
+    pub fn mined_blocks(&self, coinbase: &str, query: &IndexQuery) -> Page<IndexedBlock> {
+        let blocks = self.mined.get(&coinbase.trim().to_lowercase());
+        query.page(blocks.into_iter().flatten().rev().cloned(), |block| {
+            block.timestamp
+        })
+    }
+}
+
+pub fn index_path() -> PathBuf {
+    crate::network::data_dir().join(INDEX_FILE)
+}
+
+static INDEX: Lazy<Mutex<ChainIndex>> = Lazy::new(|| Mutex::new(ChainIndex::load(index_path())));
+
+static FOLLOWING: AtomicBool = AtomicBool::new(false);
+
+/// The process-wide index, kept current by `run`.
+pub fn global() -> &'static Mutex<ChainIndex> {
+    &INDEX
+}
+
+fn block_params(number: u64, full: bool) -> serde_json::Value {
+    serde_json::json!([format!("0x{number:x}"), full])
+}
+
+fn batch_result(
+    results: &[Result<serde_json::Value, String>],
+    idx: usize,
+) -> Result<&serde_json::Value, String> {
+    match results.get(idx) {
+        Some(Ok(value)) => Ok(value),
+        Some(Err(e)) => Err(e.clone()),
+        None => Err("batch response missing a result".to_string()),
+    }
+}
+
+fn block_hash(block: &serde_json::Value) -> Option<String> {
+    block
+        .get("hash")
+        .and_then(|h| h.as_str())
+        .map(str::to_lowercase)
+}
+
+/// Roll `index` back to the newest block that is still on the chain.
+async fn rewind(index: &Mutex<ChainIndex>, endpoint: &str) -> Result<(), String> {
+    let heights: Vec<u64> = index.lock().recent_hashes.keys().copied().collect();
+    let mut batch = rpc_client::batch();
+    for number in &heights {
+        batch.add("eth_getBlockByNumber", block_params(*number, false));
+    }
+    let results = batch.execute(endpoint).await?;
+    let mut canonical = HashMap::new();
+    for (idx, number) in heights.iter().enumerate() {
+        if let Some(hash) = block_hash(batch_result(&results, idx)?) {
+            canonical.insert(*number, hash);
+        }
+    }
+
+    let mut index = index.lock();
+    let old_head = index.head;
+    let fork = index.fork_point(&canonical);
+    index.rollback(fork);
+    match fork {
+        Some(fork) => println!(
+            "[ChainIndex] Reorg: rolled back from block {} to {}",
+            old_head.unwrap_or(0),
+            fork
+        ),
+        None => println!(
+            "[ChainIndex] Indexed blocks are no longer on the chain; re-indexing from genesis"
+        ),
+    }
+    Ok(())
+}
+
+/// Bring `index` one batch closer to the chain head. Returns whether more
+/// blocks are waiting.
+pub async fn sync_once(index: &Mutex<ChainIndex>, endpoint: &str) -> Result<bool, String> {
+    let tip = {
+        let index = index.lock();
+        index.head.map(|head| {
+            (
+                head,
+                index.hash_at(head).map(str::to_string),
+                index.genesis_hash.clone(),
+            )
+        })
+    };
+    let mut batch = rpc_client::batch();
+    batch.add("eth_blockNumber", serde_json::json!([]));
+    if let Some((head, _, _)) = &tip {
+        batch.add("eth_getBlockByNumber", block_params(*head, false));
+        batch.add("eth_getBlockByNumber", block_params(0, false));
+    }
+    let results = batch.execute(endpoint).await?;
+    let chain_head = batch_result(&results, 0)?
+        .as_str()
+        .ok_or_else(|| "eth_blockNumber returned a non-string value".to_string())
+        .and_then(rpc_client::hex_to_u64)?;
+    if let Some((_, indexed_hash, genesis_hash)) = tip {
+        if block_hash(batch_result(&results, 2)?) != genesis_hash {
+            index.lock().rollback(None);
+            println!("[ChainIndex] The endpoint serves another chain; re-indexing from genesis");
+            return Ok(true);
+        }
+        let hash = block_hash(batch_result(&results, 1)?);
+        if indexed_hash.is_some() && hash != indexed_hash {
+            rewind(index, endpoint).await?;
+            return Ok(true);
+        }
+    }
+
+    let next = {
+        let mut index = index.lock();
+        index.set_chain_head(chain_head);
+        index.next_block()
+    };
+    if next > chain_head {
+        return Ok(false);
+    }
+    let last = chain_head.min(next + SYNC_BATCH - 1);
+    let mut batch = rpc_client::batch();
+    for number in next..=last {
+        batch.add("eth_getBlockByNumber", block_params(number, true));
+    }
+    let results = batch.execute(endpoint).await?;
+    let mut blocks = Vec::new();
+    for idx in 0..results.len() {
+        let block = batch_result(&results, idx)?;
+        // The chain got shorter mid-sync; the next tip check rewinds.
+        if block.is_null() {
+            break;
+        }
+        blocks.push(BlockSummary::from_rpc(block)?);
+    }
+
+    let mut index = index.lock();
+    for block in blocks {
+        // A parent mismatch means the indexed tip was reorged away since
+        // the tip check; the next round rewinds.
+        if !index.apply(block) {
+            return Ok(true);
+        }
+    }
+    Ok(index.next_block() <= chain_head)
+}
+
+/// The index saved at `path`, for answering queries without following the
+/// chain. `None` unless it was built from the chain behind `endpoint`,
+/// covers `chain_head`, and its tip is still on that chain.
+pub async fn load_serving(
+    path: PathBuf,
+    endpoint: &str,
+    chain_head: u64,
+) -> Result<Option<ChainIndex>, String> {
+    let genesis =
+        rpc_client::call(endpoint, "eth_getBlockByNumber", block_params(0, false)).await?;
+    let Some(genesis_hash) = block_hash(&genesis) else {
+        return Ok(None);
+    };
+    let index = ChainIndex::load_for_chain(path, &genesis_hash);
+    let Some(head) = index.head.filter(|_| index.covers(chain_head)) else {
+        return Ok(None);
+    };
+    let tip = rpc_client::call(endpoint, "eth_getBlockByNumber", block_params(head, false)).await?;
+    let canonical = block_hash(&tip).is_some_and(|hash| index.hash_at(head) == Some(hash.as_str()));
+    Ok(canonical.then_some(index))
+}
+
+/// Save `index` from a blocking task. Only taking the changes happens
+/// under its lock; serialising and writing them does not, so lookups are
+/// not held up by the save.
+async fn persist_in_background(index: &Mutex<ChainIndex>) -> Result<(), String> {
+    let Some(write) = index.lock().take_write() else {
+        return Ok(());
+    };
+    let written = tokio::task::spawn_blocking(move || write.run())
+        .await
+        .map_err(|e| format!("index write task failed: {e}"))
+        .and_then(|result| result);
+    if written.is_err() {
+        index.lock().needs_compact = true;
+    }
+    written
+}
+
+/// Follow the chain behind `endpoint` forever, keeping the global index
+/// current. Only the first call in a process does anything.
+pub async fn run(endpoint: fn() -> String) {
+    if FOLLOWING.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    let index = global();
+    println!(
+        "[ChainIndex] Following the chain from block {}",
+        index.lock().next_block()
+    );
+    let mut last_error: Option<String> = None;
+    let mut last_persist = Instant::now();
+    let mut was_caught_up = false;
+    loop {
+        let catching_up = match sync_once(index, &endpoint()).await {
+            Ok(more) => {
+                if last_error.take().is_some() {
+                    println!("[ChainIndex] Sync recovered");
+                }
+                more
+            }
+            Err(e) => {
+                if last_error.as_deref() != Some(e.as_str()) {
+                    eprintln!("[ChainIndex] Sync failed: {e}");
+                    last_error = Some(e);
+                }
+                false
+            }
+        };
+        if !catching_up || last_persist.elapsed() >= PERSIST_INTERVAL {
+            if let Err(e) = persist_in_background(index).await {
+                eprintln!("[ChainIndex] Failed to save index: {e}");
+            }
+            last_persist = Instant::now();
+            let index = index.lock();
+            if index.caught_up() && !was_caught_up {
+                println!(
+                    "[ChainIndex] Caught up at block {}",
+                    index.head.unwrap_or(0)
+                );
+            }
+            was_caught_up = index.caught_up();
+        }
+        if !catching_up {
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hash(number: u64, fork: u8) -> String {
+        format!("0x{fork:02x}{number:062x}")
+    }
+
+    fn tx(number: u64, from: &str, to: &str, value: u128) -> IndexedTx {
+        IndexedTx {
+            hash: format!("0xtx{number}{from}{to}"),
+            from: from.to_string(),
+            to: to.to_string(),
+            value_wei: value.to_string(),
+            gas: 21_000,
+            block_number: number,
+            timestamp: 1_000 + number * 10,
+        }
+    }
+
+    fn block(number: u64, fork: u8, miner: &str, txs: Vec<IndexedTx>) -> BlockSummary {
+        let parent_fork = if number == 0 { 0 } else { fork };
+        BlockSummary {
+            number,
+            hash: hash(number, fork),
+            parent_hash: number
+                .checked_sub(1)
+                .map_or_else(String::new, |parent| hash(parent, parent_fork)),
+            timestamp: 1_000 + number * 10,
+            miner: miner.to_string(),
+            difficulty: 100 + number,
+            transactions: txs,
+        }
+    }
+
+    fn chain(len: u64) -> ChainIndex {
+        let mut index = ChainIndex::default();
+        for number in 0..len {
+            let txs = if number % 2 == 0 {
+                vec![tx(number, "0xaa", "0xbb", number as u128)]
+            } else {
+                vec![tx(number, "0xbb", "0xcc", number as u128)]
+            };
+            let miner = if number % 3 == 0 { "0xm1" } else { "0xm2" };
+            assert!(index.apply(block(number, 0, miner, txs)));
+        }
+        index
+    }
+
+    #[test]
+    fn apply_indexes_both_parties_and_the_miner() {
+        let index = chain(4);
+        assert_eq!(index.head(), Some(3));
+        assert_eq!(index.next_block(), 4);
+
+        let all = IndexQuery::default();
+        let bb: Vec<u64> = index
+            .transactions("0xBB", &all)
+            .items
+            .iter()
+            .map(|tx| tx.block_number)
+            .collect();
+        assert_eq!(bb, vec![3, 2, 1, 0]);
+        assert_eq!(index.transactions("0xaa", &all).total, 2);
+        assert_eq!(index.transactions("0xcc", &all).total, 2);
+        assert_eq!(index.transactions("0xdd", &all).total, 0);
+
+        let m1: Vec<u64> = index
+            .mined_blocks("0xm1", &all)
+            .items
+            .iter()
+            .map(|b| b.number)
+            .collect();
+        assert_eq!(m1, vec![3, 0]);
+        assert_eq!(index.mined_blocks("0xm2", &all).total, 2);
+    }
+
+    #[test]
+    fn apply_records_self_transfers_once() {
+        let mut index = ChainIndex::default();
+        assert!(index.apply(block(0, 0, "0xm", vec![tx(0, "0xaa", "0xaa", 1)])));
+        assert_eq!(index.transactions("0xaa", &IndexQuery::default()).total, 1);
+    }
+
+    #[test]
+    fn apply_rejects_gaps_and_foreign_parents() {
+        let mut index = chain(3);
+        assert!(!index.apply(block(4, 0, "0xm", vec![])));
+        assert!(!index.apply(block(2, 0, "0xm", vec![])));
+        assert!(
+            !index.apply(block(3, 1, "0xm", vec![])),
+            "block 3 of another fork builds on a different block 2"
+        );
+        assert_eq!(index.head(), Some(2));
+        assert!(index.apply(block(3, 0, "0xm", vec![])));
+    }
+
+    #[test]
+    fn apply_keeps_only_recent_hashes() {
+        let index = chain(REORG_DEPTH + 10);
+        assert_eq!(index.recent_hashes.len() as u64, REORG_DEPTH);
+        assert!(index.hash_at(9).is_none());
+        assert!(index.hash_at(10).is_some());
+        assert!(index.hash_at(REORG_DEPTH + 9).is_some());
+    }
+
+    #[test]
+    fn rollback_drops_blocks_above_the_fork() {
+        let mut index = chain(6);
+        index.rollback(Some(2));
+        assert_eq!(index.head(), Some(2));
+        assert!(index.hash_at(3).is_none());
+        let all = IndexQuery::default();
+        assert_eq!(index.transactions("0xbb", &all).total, 3);
+        assert_eq!(index.transactions("0xcc", &all).total, 1);
+        let m1: Vec<u64> = index
+            .mined_blocks("0xm1", &all)
+            .items
+            .iter()
+            .map(|b| b.number)
+            .collect();
+        assert_eq!(m1, vec![0]);
+
+        // The other fork continues from the kept block.
+        let mut fork = block(3, 1, "0xm3", vec![tx(3, "0xdd", "0xaa", 7)]);
+        fork.parent_hash = hash(2, 0);
+        assert!(index.apply(fork));
+        assert_eq!(index.transactions("0xaa", &all).items[0].block_number, 3);
+        assert_eq!(index.mined_blocks("0xm3", &all).total, 1);
+    }
+
+    #[test]
+    fn rollback_to_nothing_starts_over() {
+        let mut index = chain(4);
+        index.set_chain_head(10);
+        index.rollback(None);
+        assert_eq!(index.head(), None);
+        assert_eq!(index.next_block(), 0);
+        assert_eq!(index.chain_head(), Some(10));
+        assert_eq!(index.transactions("0xbb", &IndexQuery::default()).total, 0);
+        assert!(index.apply(block(0, 1, "0xm", vec![])));
+    }
+
+    #[test]
+    fn fork_point_is_the_newest_matching_hash() {
+        let index = chain(6);
+        let mut canonical: HashMap<u64, String> =
+            (0..6).map(|number| (number, hash(number, 0))).collect();
+        assert_eq!(index.fork_point(&canonical), Some(5));
+
+        canonical.insert(4, hash(4, 1));
+        canonical.remove(&5);
+        assert_eq!(index.fork_point(&canonical), Some(3));
+
+        let other_chain: HashMap<u64, String> =
+            (0..6).map(|number| (number, hash(number, 2))).collect();
+        assert_eq!(index.fork_point(&other_chain), None);
+    }
+
+    #[test]
+    fn queries_paginate_newest_first() {
+        let index = chain(10);
+        let query = IndexQuery {
+            offset: 2,
+            limit: Some(3),
+            ..IndexQuery::default()
+        };
+        let page = index.transactions("0xbb", &query);
+        assert_eq!(page.total, 10);
+        let numbers: Vec<u64> = page.items.iter().map(|tx| tx.block_number).collect();
+        assert_eq!(numbers, vec![7, 6, 5]);
+
+        let past_end = IndexQuery {
+            offset: 20,
+            ..IndexQuery::default()
+        };
+        let page = index.transactions("0xbb", &past_end);
+        assert!(page.items.is_empty());
+        assert_eq!(page.total, 10);
+    }
+
+    #[test]
+    fn queries_filter_by_date() {
+        let index = chain(10);
+        // Block n has timestamp 1000 + 10n.
+        let query = IndexQuery {
+            since: Some(1_030),
+            until: Some(1_060),
+            ..IndexQuery::default()
+        };
+        let page = index.transactions("0xbb", &query);
+        let numbers: Vec<u64> = page.items.iter().map(|tx| tx.block_number).collect();
+        assert_eq!(numbers, vec![6, 5, 4, 3]);
+        assert_eq!(page.total, 4);
+
+        let mined = index.mined_blocks("0xm1", &query);
+        let numbers: Vec<u64> = mined.items.iter().map(|b| b.number).collect();
+        assert_eq!(numbers, vec![6, 3]);
+    }
+
+    #[test]
+    fn page_size_is_clamped() {
+        assert_eq!(IndexQuery::default().page_size(), DEFAULT_PAGE_SIZE);
+        let huge = IndexQuery {
+            limit: Some(10_000),
+            ..IndexQuery::default()
+        };
+        assert_eq!(huge.page_size(), MAX_PAGE_SIZE);
+        let zero = IndexQuery {
+            limit: Some(0),
+            ..IndexQuery::default()
+        };
+        assert_eq!(zero.page_size(), 1);
+    }
+
+    #[test]
+    fn covers_allows_a_small_lag() {
+        let mut index = chain(10);
+        assert!(!index.caught_up(), "chain head unknown until synced");
+        index.set_chain_head(9 + MAX_SERVE_LAG);
+        assert!(index.caught_up());
+        index.set_chain_head(10 + MAX_SERVE_LAG);
+        assert!(!index.caught_up());
+        assert!(!ChainIndex::default().covers(0));
+    }
+
+    #[test]
+    fn block_summary_parses_full_and_header_only_blocks() {
+        let full = serde_json::json!({
+            "number": "0x10",
+            "hash": "0xABC",
+            "parentHash": "0xDEF",
+            "timestamp": "0x64",
+            "miner": "0xMINER",
+            "difficulty": "0x20000",
+            "transactions": [
+                {
+                    "hash": "0xT1",
+                    "from": "0xFROM",
+                    "to": "0xTO",
+                    "value": "0xde0b6b3a7640000",
+                    "gas": "0x5208"
+                },
+                {
+                    "hash": "0xT2",
+                    "from": "0xFROM",
+                    "to": null,
+                    "value": "0x0",
+                    "gas": "0x100000"
+                }
+            ]
+        });
+        let block = BlockSummary::from_rpc(&full).unwrap();
+        assert_eq!(block.number, 16);
+        assert_eq!(block.hash, "0xabc");
+        assert_eq!(block.parent_hash, "0xdef");
+        assert_eq!(block.timestamp, 100);
+        assert_eq!(block.miner, "0xminer");
+        assert_eq!(block.difficulty, 0x20000);
+        assert_eq!(block.transactions.len(), 2);
+        assert_eq!(block.transactions[0].to, "0xto");
+        assert_eq!(block.transactions[0].value_wei, "1000000000000000000");
+        assert_eq!(block.transactions[0].gas, 21_000);
+        assert_eq!(block.transactions[0].block_number, 16);
+        assert_eq!(block.transactions[1].to, "", "contract creation");
+
+        let mut header = full.clone();
+        header["transactions"] = serde_json::json!(["0xt1", "0xt2"]);
+        assert!(BlockSummary::from_rpc(&header)
+            .unwrap()
+            .transactions
+            .is_empty());
+
+        let mut broken = full;
+        broken["transactions"][0]["value"] = serde_json::json!("0xzz");
+        assert!(BlockSummary::from_rpc(&broken).is_err());
+    }
+
+    type MockChain = std::sync::Arc<Mutex<Vec<BlockSummary>>>;
+
+    fn rpc_block(block: &BlockSummary, full: bool) -> serde_json::Value {
+        let txs: Vec<serde_json::Value> = block
+            .transactions
+            .iter()
+            .map(|tx| {
+                if full {
+                    serde_json::json!({
+                        "hash": tx.hash,
+                        "from": tx.from,
+                        "to": tx.to,
+                        "value": format!("0x{:x}", tx.value_wei.parse::<u128>().unwrap()),
+                        "gas": format!("0x{:x}", tx.gas),
+                    })
+                } else {
+                    serde_json::json!(tx.hash)
+                }
+            })
+            .collect();
+        serde_json::json!({
+            "number": format!("0x{:x}", block.number),
+            "hash": block.hash,
+            "parentHash": block.parent_hash,
+            "timestamp": format!("0x{:x}", block.timestamp),
+            "miner": block.miner,
+            "difficulty": format!("0x{:x}", block.difficulty),
+            "transactions": txs,
+        })
+    }
+
+    fn rpc_reply(chain: &[BlockSummary], request: &serde_json::Value) -> serde_json::Value {
+        let result = match request["method"].as_str() {
+            Some("eth_blockNumber") => serde_json::json!(format!("0x{:x}", chain.len() - 1)),
+            Some("eth_getBlockByNumber") => {
+                let number =
+                    rpc_client::hex_to_u64(request["params"][0].as_str().unwrap()).unwrap();
+                let full = request["params"][1].as_bool().unwrap();
+                chain
+                    .get(number as usize)
+                    .map_or(serde_json::Value::Null, |block| rpc_block(block, full))
+            }
+            other => panic!("unexpected RPC method {other:?}"),
+        };
+        serde_json::json!({"jsonrpc": "2.0", "id": request["id"], "result": result})
+    }
+
+    async fn serve_chain(chain: MockChain) -> String {
+        let app = axum::Router::new().route(
+            "/",
+            axum::routing::post(
+                |axum::extract::State(chain): axum::extract::State<MockChain>,
+                 axum::Json(body): axum::Json<serde_json::Value>| async move {
+                    let chain = chain.lock();
+                    let reply = match body.as_array() {
+                        Some(batch) => batch.iter().map(|r| rpc_reply(&chain, r)).collect(),
+                        None => rpc_reply(&chain, &body),
+                    };
+                    axum::Json(reply)
+                },
+            ),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app.with_state(chain)).await.unwrap();
+        });
+        format!("http://{addr}")
+    }
+
+    fn mock_chain(len: u64, fork: u8) -> Vec<BlockSummary> {
+        (0..len)
+            .map(|number| {
+                let txs = vec![tx(number, "0xaa", "0xbb", number as u128)];
+                block(number, fork, "0xm1", txs)
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn sync_once_catches_up_in_batches_and_follows_reorgs() {
+        let chain: MockChain = std::sync::Arc::new(Mutex::new(mock_chain(SYNC_BATCH + 20, 0)));
+        let endpoint = serve_chain(chain.clone()).await;
+        let index = Mutex::new(ChainIndex::default());
+
+        assert!(sync_once(&index, &endpoint).await.unwrap());
+        assert_eq!(index.lock().head(), Some(SYNC_BATCH - 1));
+        assert!(!index.lock().caught_up());
+        assert!(!sync_once(&index, &endpoint).await.unwrap());
+        assert_eq!(index.lock().head(), Some(SYNC_BATCH + 19));
+        assert!(index.lock().caught_up());
+        assert!(!sync_once(&index, &endpoint).await.unwrap());
+
+        // The last five blocks are replaced by a longer fork.
+        {
+            let mut chain = chain.lock();
+            chain.truncate(SYNC_BATCH as usize + 15);
+            for number in SYNC_BATCH + 15..SYNC_BATCH + 25 {
+                let mut fork = block(number, 1, "0xm2", vec![tx(number, "0xcc", "0xbb", 1)]);
+                if number == SYNC_BATCH + 15 {
+                    fork.parent_hash = hash(number - 1, 0);
+                }
+                chain.push(fork);
+            }
+        }
+        assert!(sync_once(&index, &endpoint).await.unwrap(), "rewinds first");
+        assert_eq!(index.lock().head(), Some(SYNC_BATCH + 14));
+        assert!(!sync_once(&index, &endpoint).await.unwrap());
+
+        let index = index.lock();
+        assert_eq!(index.head(), Some(SYNC_BATCH + 24));
+        assert_eq!(
+            index.hash_at(SYNC_BATCH + 24),
+            Some(hash(SYNC_BATCH + 24, 1).as_str())
+        );
+        let all = IndexQuery::default();
+        assert_eq!(
+            index.transactions("0xaa", &all).total as u64,
+            SYNC_BATCH + 15
+        );
+        assert_eq!(index.transactions("0xcc", &all).total, 10);
+        assert_eq!(index.mined_blocks("0xm2", &all).total, 10);
+        assert_eq!(
+            index.mined_blocks("0xm1", &all).total as u64,
+            SYNC_BATCH + 15
+        );
+    }
+
+    #[tokio::test]
+    async fn sync_once_starts_over_on_a_different_chain() {
+        let chain: MockChain = std::sync::Arc::new(Mutex::new(mock_chain(10, 0)));
+        let endpoint = serve_chain(chain.clone()).await;
+        let index = Mutex::new(ChainIndex::default());
+        assert!(!sync_once(&index, &endpoint).await.unwrap());
+        assert_eq!(index.lock().head(), Some(9));
+
+        *chain.lock() = mock_chain(4, 2);
+        assert!(sync_once(&index, &endpoint).await.unwrap());
+        assert_eq!(index.lock().head(), None);
+        assert!(!sync_once(&index, &endpoint).await.unwrap());
+        let index = index.lock();
+        assert_eq!(index.head(), Some(3));
+        assert_eq!(index.hash_at(3), Some(hash(3, 2).as_str()));
+        assert_eq!(index.transactions("0xbb", &IndexQuery::default()).total, 4);
+    }
+
+    #[test]
+    fn persist_round_trips_and_skips_clean_saves() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(INDEX_FILE);
+        let mut index = ChainIndex::load(path.clone());
+        assert_eq!(index.head(), None);
+        index.persist().unwrap();
+        assert!(!path.exists(), "nothing to save yet");
+
+        for number in 0..3 {
+            assert!(index.apply(block(number, 0, "0xm", vec![tx(number, "0xaa", "0xbb", 5)])));
+        }
+        index.set_chain_head(3);
+        index.persist().unwrap();
+
+        let loaded = ChainIndex::load(path.clone());
+        assert_eq!(loaded.head(), Some(2));
+        assert_eq!(loaded.chain_head(), None, "chain head is not persisted");
+        assert_eq!(loaded.hash_at(2), index.hash_at(2));
+        assert_eq!(
+            loaded.transactions("0xbb", &IndexQuery::default()).items,
+            index.transactions("0xbb", &IndexQuery::default()).items
+        );
+
+        index.needs_compact = true;
+        index.persist().unwrap();
+        assert_eq!(ChainIndex::load(path.clone()).head(), Some(2));
+        std::fs::write(&path, "{not json").unwrap();
+        assert_eq!(ChainIndex::load(path).head(), None);
+    }
+
+    #[test]
+    fn saves_append_new_blocks_without_rewriting_the_snapshot() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(INDEX_FILE);
+        let journal = journal_path(&path);
+        let mut index = ChainIndex::load(path.clone());
+        for number in 0..4 {
+            assert!(index.apply(block(number, 0, "0xm", vec![tx(number, "0xaa", "0xbb", 5)])));
+        }
+        index.needs_compact = true;
+        index.persist().unwrap();
+        assert_eq!(std::fs::read_to_string(&journal).unwrap(), "");
+        let snapshot = std::fs::read(&path).unwrap();
+
+        assert!(index.apply(block(4, 0, "0xm", vec![])));
+        index.persist().unwrap();
+        index.rollback(Some(3));
+        let mut fork = block(4, 1, "0xm", vec![tx(4, "0xcc", "0xaa", 9)]);
+        fork.parent_hash = hash(3, 0);
+        assert!(index.apply(fork));
+        index.persist().unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), snapshot);
+        let stale = std::fs::read_to_string(&journal).unwrap();
+        assert_eq!(stale.lines().count(), 3);
+
+        let all = IndexQuery::default();
+        let loaded = ChainIndex::load(path.clone());
+        assert_eq!(loaded.head(), Some(4));
+        assert_eq!(loaded.hash_at(4), Some(hash(4, 1).as_str()));
+        assert_eq!(loaded.transactions("0xaa", &all).total, 5);
+        assert!(!loaded.needs_compact);
+
+        // A crash after the snapshot is replaced but before the journal is
+        // emptied leaves entries the snapshot already holds.
+        index.needs_compact = true;
+        index.persist().unwrap();
+        std::fs::write(&journal, stale).unwrap();
+        let loaded = ChainIndex::load(path.clone());
+        assert_eq!(loaded.hash_at(4), Some(hash(4, 1).as_str()));
+        assert_eq!(loaded.transactions("0xaa", &all).total, 5);
+        assert!(loaded.needs_compact, "stale entries are compacted away");
+
+        // A torn line ends the replay there.
+        std::fs::write(&journal, "").unwrap();
+        assert!(index.apply(block(5, 1, "0xm", vec![])));
+        index.persist().unwrap();
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(&journal)
+            .and_then(|mut file| file.write_all(b"{\"seq\":"))
+            .unwrap();
+        let loaded = ChainIndex::load(path);
+        assert_eq!(loaded.head(), Some(5));
+        assert!(loaded.needs_compact);
+    }
+
+    #[test]
+    fn journal_is_compacted_once_it_outgrows_half_the_index() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(INDEX_FILE);
+        let journal = journal_path(&path);
+        let mut index = ChainIndex::load(path.clone());
+        let mut compactions = Vec::new();
+        for number in 0..3 * COMPACT_SLACK_ENTRIES {
+            assert!(index.apply(block(number, 0, "0xm", vec![])));
+            index.persist().unwrap();
+            if std::fs::read_to_string(&journal).unwrap().is_empty() {
+                compactions.push(number);
+            }
+        }
+        assert_eq!(compactions, vec![2 * COMPACT_SLACK_ENTRIES]);
+        let loaded = ChainIndex::load(path);
+        assert_eq!(loaded.head(), Some(3 * COMPACT_SLACK_ENTRIES - 1));
+        assert_eq!(loaded.journaled, COMPACT_SLACK_ENTRIES - 1);
+    }
+
+    #[test]
+    fn load_for_chain_ignores_another_chains_index() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(INDEX_FILE);
+        let mut index = ChainIndex::load(path.clone());
+        for number in 0..3 {
+            assert!(index.apply(block(number, 0, "0xm", vec![])));
+        }
+        index.persist().unwrap();
+        assert_eq!(index.genesis_hash(), Some(hash(0, 0).as_str()));
+
+        let same = ChainIndex::load_for_chain(path.clone(), &hash(0, 0));
+        assert_eq!(same.head(), Some(2));
+        let other = ChainIndex::load_for_chain(path.clone(), &hash(0, 1));
+        assert_eq!(other.head(), None);
+        assert_eq!(other.genesis_hash(), None);
+        assert!(other.needs_compact, "the next save drops the foreign index");
+    }
+
+    #[tokio::test]
+    async fn load_serving_checks_the_saved_index_against_the_chain() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(INDEX_FILE);
+        let chain: MockChain = std::sync::Arc::new(Mutex::new(mock_chain(10, 0)));
+        let endpoint = serve_chain(chain.clone()).await;
+        let index = Mutex::new(ChainIndex::load(path.clone()));
+        assert!(!sync_once(&index, &endpoint).await.unwrap());
+        index.lock().persist().unwrap();
+
+        let served = load_serving(path.clone(), &endpoint, 9).await.unwrap();
+        assert_eq!(served.and_then(|index| index.head()), Some(9));
+        assert!(
+            load_serving(path.clone(), &endpoint, 10 + MAX_SERVE_LAG)
+                .await
+                .unwrap()
+                .is_none(),
+            "too far behind"
+        );
+
+        // The indexed tip was reorged away.
+        {
+            let mut chain = chain.lock();
+            let mut fork = block(9, 1, "0xm2", vec![]);
+            fork.parent_hash = hash(8, 0);
+            chain[9] = fork;
+        }
+        assert!(load_serving(path.clone(), &endpoint, 9)
+            .await
+            .unwrap()
+            .is_none());
+
+        // Another chain whose tip happens to match.
+        {
+            let mut chain = chain.lock();
+            *chain = mock_chain(10, 2);
+            chain[9].hash = hash(9, 0);
+        }
+        assert!(load_serving(path, &endpoint, 9).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn background_persist_writes_outside_the_lock_and_retries_failures() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(INDEX_FILE);
+        let index = Mutex::new(ChainIndex::load(path.clone()));
+        assert!(index.lock().apply(block(0, 0, "0xm", vec![])));
+
+        persist_in_background(&index).await.unwrap();
+        assert!(index.lock().unsaved.is_empty());
+        assert_eq!(ChainIndex::load(path.clone()).head(), Some(0));
+
+        // A directory in the way makes the append fail.
+        let journal = journal_path(&path);
+        std::fs::remove_file(&journal).unwrap();
+        std::fs::create_dir(&journal).unwrap();
+        assert!(index.lock().apply(block(1, 0, "0xm", vec![])));
+        assert!(persist_in_background(&index).await.is_err());
+        assert!(
+            index.lock().needs_compact,
+            "a failed write is retried as a fresh snapshot"
+        );
+
+        std::fs::remove_dir(&journal).unwrap();
+        persist_in_background(&index).await.unwrap();
+        assert_eq!(ChainIndex::load(path).head(), Some(1));
+    }
+}
@@ -0,0 +1,599 @@
+//! Bearer-token authentication for the daemon's control API.
+//!
+//! Routes under `/api/headless/` can move funds, stop services, or write
+//! to the DHT, so they require `Authorization: Bearer <token>`. The file
+//! search lookups in [`PUBLIC_ROUTES`] stay open, and the public gateway
+//! routes (sites, Drive share links, CDN, ratings) keep their own checks.
+//! The Prometheus scrape at `/metrics` needs a `read` token.
+//!
+//! Tokens live in `<data_dir>/headless/daemon-tokens.json`, readable by
+//! the owner only. The daemon creates the file with one `admin` token on
+//! first start; the `chiral` CLI reads the same file, so local use needs no
+//! setup. More tokens can be minted with narrower scopes:
+//!
+//! - `read`: `GET` status endpoints that reveal no secrets
+//! - `control`: also start/stop services, publish to the DHT, send offers
+//! - `spend`: everything, including routes that move funds or reveal or
+//!   replace wallet keys
+//!
+//! The daemon re-reads the file when it changes, so tokens created or
+//! revoked by the CLI take effect without a restart. Setting
+//! `CHIRAL_DAEMON_TOKEN` for the daemon additionally accepts that value
+//! as a spend token, which suits containers provisioned with a secret.
+
+use axum::{
+    extract::{Request, State},
+    http::{header, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use parking_lot::Mutex;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+/// Overrides the token the CLI reads from the token file.
+pub const TOKEN_ENV: &str = "CHIRAL_DAEMON_TOKEN";
+const TOKEN_PREFIX: &str = "chd_";
+
+/// What a token may do. Scopes are ordered: each includes the ones below.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenScope {
+    Read,
+    Control,
+    Spend,
+}
+
+impl std::str::FromStr for TokenScope {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "read" => Ok(Self::Read),
+            "control" => Ok(Self::Control),
+            "spend" => Ok(Self::Spend),
+            other => Err(format!(
+                "Unknown token scope '{}' (expected read, control or spend)",
+                other
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DaemonToken {
+    pub name: String,
+    pub scope: TokenScope,
+    pub token: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenStore {
+    pub tokens: Vec<DaemonToken>,
+}
+
+pub fn token_file(data_dir: &Path) -> PathBuf {
+    data_dir.join("headless").join("daemon-tokens.json")
+}
+
+fn generate_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::rngs::OsRng.fill_bytes(&mut bytes);
+    format!("{}{}", TOKEN_PREFIX, hex::encode(bytes))
+}
+
+/// Compare digests so the time taken does not depend on how much of a
+/// guessed token is right.
+fn tokens_equal(a: &str, b: &str) -> bool {
+    let (a, b) = (Sha256::digest(a.as_bytes()), Sha256::digest(b.as_bytes()));
+    a.iter()
+        .zip(b.iter())
+        .fold(0u8, |acc, (x, y)| acc | (x ^ y))
+        == 0
+}
+
+impl TokenStore {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let raw =
+            std::fs::read_to_string(path).map_err(|e| format!("read {}: {}", path.display(), e))?;
+        serde_json::from_str(&raw).map_err(|e| format!("parse {}: {}", path.display(), e))
+    }
+
+    /// Load the token file, creating it with a single `admin` token if it
+    /// does not exist yet.
+    pub fn load_or_init(path: &Path) -> Result<Self, String> {
+        if path.exists() {
+            return Self::load(path);
+        }
+        let mut store = Self::default();
+        store.create("admin", TokenScope::Spend)?;
+        store.save(path)?;
+        Ok(store)
+    }
+
+    /// Write the file atomically, readable by the owner only.
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let json =
+            serde_json::to_string_pretty(self).map_err(|e| format!("serialize tokens: {}", e))?;
+        crate::keystore::write_private_file(path, json.as_bytes())
+    }
+
+    /// Mint a token. Names must be unique so tokens can be revoked by name.
+    pub fn create(&mut self, name: &str, scope: TokenScope) -> Result<String, String> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err("Token name is required".to_string());
+        }
+        if self.tokens.iter().any(|t| t.name == name) {
+            return Err(format!("A token named '{}' already exists", name));
+        }
+        let token = generate_token();
+        self.tokens.push(DaemonToken {
+            name: name.to_string(),
+            scope,
+            token: token.clone(),
+        });
+        Ok(token)
+    }
+
+    /// Remove a token by name. Returns false if there was none.
+    pub fn revoke(&mut self, name: &str) -> bool {
+        let before = self.tokens.len();
+        self.tokens.retain(|t| t.name != name.trim());
+        self.tokens.len() != before
+    }
+
+    /// The scope of a presented token, if it is one of ours.
+    pub fn scope_of(&self, presented: &str) -> Option<TokenScope> {
+        // Check every token rather than stopping at the first match.
+        self.tokens
+            .iter()
+            .filter(|t| tokens_equal(&t.token, presented))
+            .map(|t| t.scope)
+            .max()
+    }
+
+    /// The widest-scoped token, which is what the CLI uses.
+    pub fn strongest(&self) -> Option<&DaemonToken> {
+        self.tokens.iter().max_by_key(|t| t.scope)
+    }
+}
+
+/// The token the CLI should send: `CHIRAL_DAEMON_TOKEN` if set, otherwise
+/// the widest-scoped token in the data dir's token file.
+pub fn client_token(data_dir: &Path) -> Option<String> {
+    if let Ok(token) = std::env::var(TOKEN_ENV) {
+        if !token.trim().is_empty() {
+            return Some(token.trim().to_string());
+        }
+    }
+    TokenStore::load(&token_file(data_dir))
+        .ok()?
+        .strongest()
+        .map(|t| t.token.clone())
+}
+
+/// Routes under `/api/headless/` that move funds, reveal or replace
+/// wallet keys, or redirect mining rewards.
+const SPEND_ROUTES: &[(&str, &str)] = &[
+    ("GET", "/api/headless/wallet"),
+    ("POST", "/api/headless/wallet/create"),
+    ("POST", "/api/headless/wallet/import"),
+    ("POST", "/api/headless/wallet/derive"),
+    ("POST", "/api/headless/wallet/unlock"),
+    ("POST", "/api/headless/wallet/send"),
+    ("POST", "/api/headless/dht/request-file"),
+    ("POST", "/api/headless/drop/accept"),
+    ("POST", "/api/headless/mining/miner-address"),
+];
+
+/// `POST` routes under `/api/headless/` that only look things up. They take
+/// a request body but change nothing, so a read token may call them.
+const READ_ROUTES: &[(&str, &str)] = &[
+    ("POST", "/api/headless/wallet/balance"),
+    ("POST", "/api/headless/wallet/history"),
+    ("POST", "/api/headless/wallet/receipt"),
+    ("POST", "/api/headless/dht/get"),
+    ("POST", "/api/headless/dht/ping"),
+    ("POST", "/api/headless/dht/echo"),
+];
+
+/// Lookups under `/api/headless/` that other nodes' frontends call when
+/// they use this daemon as a CDN search server.
+pub const PUBLIC_ROUTES: &[&str] = &[
+    "/api/headless/file/search",
+    "/api/headless/file/keyword-search",
+    "/api/headless/folder/search",
+];
+
+/// Prometheus scrape endpoint; served with the control API and readable
+/// with any token.
+pub const METRICS_ROUTE: &str = "/metrics";
+
+/// The scope a request needs, or `None` for routes outside the control
+/// API (and for the public lookups and CORS preflights).
+pub fn required_scope(method: &Method, path: &str) -> Option<TokenScope> {
+    if method == Method::OPTIONS {
+        return None;
+    }
+    if path == METRICS_ROUTE {
+        return Some(TokenScope::Read);
+    }
+    if !path.starts_with("/api/headless/") {
+        return None;
+    }
+    let path = path.trim_end_matches('/');
+    if PUBLIC_ROUTES.contains(&path) {
+        return None;
+    }
+    if SPEND_ROUTES
+        .iter()
+        .any(|(m, p)| method.as_str() == *m && path == *p)
+    {
+        return Some(TokenScope::Spend);
+    }
+    let read_route = READ_ROUTES
+        .iter()
+        .any(|(m, p)| method.as_str() == *m && path == *p);
+    if read_route || method == Method::GET || method == Method::HEAD {
+        Some(TokenScope::Read)
+    } else {
+        Some(TokenScope::Control)
+    }
+}
+
+/// Token store shared by the daemon's listeners, reloaded from disk when
+/// the file's modification time changes.
+pub struct DaemonAuth {
+    path: PathBuf,
+    cached: Mutex<(Option<SystemTime>, TokenStore)>,
+    /// Spend-scoped token from the daemon's environment, for deployments
+    /// that provision the token up front instead of reading the file.
+    fixed: Option<String>,
+}
+
+impl DaemonAuth {
+    /// Load (or create) the token file at `path`.
+    pub fn open(path: PathBuf) -> Result<Self, String> {
+        let store = TokenStore::load_or_init(&path)?;
+        let modified = std::fs::metadata(&path).and_then(|m| m.modified()).ok();
+        Ok(Self {
+            path,
+            cached: Mutex::new((modified, store)),
+            fixed: None,
+        })
+    }
+
+    /// Also accept `token` with spend scope. Empty values are ignored.
+    pub fn with_fixed_token(mut self, token: Option<String>) -> Self {
+        self.fixed = token
+            .map(|t| t.trim().to_string())
+            .filter(|t| !t.is_empty());
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The scope granted to `presented`, reloading the file if it changed.
+    /// A file that has become unreadable keeps the last good tokens.
+    pub fn scope_of(&self, presented: &str) -> Option<TokenScope> {
+        if self
+            .fixed
+            .as_deref()
+            .is_some_and(|fixed| tokens_equal(fixed, presented))
+        {
+            return Some(TokenScope::Spend);
+        }
+        let modified = std::fs::metadata(&self.path)
+            .and_then(|m| m.modified())
+            .ok();
+        let mut cached = self.cached.lock();
+        if modified != cached.0 {
+            match TokenStore::load(&self.path) {
+                Ok(store) => *cached = (modified, store),
+                Err(e) => println!("[AUTH] Keeping previous daemon tokens: {}", e),
+            }
+        }
+        cached.1.scope_of(presented)
+    }
+}
+
+fn auth_error(status: StatusCode, message: &str) -> Response {
+    let mut response = (status, Json(serde_json::json!({ "error": message }))).into_response();
+    if status == StatusCode::UNAUTHORIZED {
+        response.headers_mut().insert(
+            header::WWW_AUTHENTICATE,
+            header::HeaderValue::from_static("Bearer"),
+        );
+    }
+    response
+}
+
+/// Middleware guarding the control API with a bearer token.
+pub async fn require_token(
+    State(auth): State<Arc<DaemonAuth>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let Some(needed) = required_scope(req.method(), req.uri().path()) else {
+        return next.run(req).await;
+    };
+    let presented = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(str::trim)
+        .filter(|t| !t.is_empty());
+    let Some(presented) = presented else {
+        return auth_error(StatusCode::UNAUTHORIZED, "Missing daemon API token");
+    };
+    match auth.scope_of(presented) {
+        None => auth_error(StatusCode::UNAUTHORIZED, "Invalid daemon API token"),
+        Some(scope) if scope < needed => auth_error(
+            StatusCode::FORBIDDEN,
+            &format!(
+                "Token scope '{}' cannot use this endpoint (needs '{}')",
+                scope.as_str(),
+                needed.as_str()
+            ),
+        ),
+        Some(_) => next.run(req).await,
+    }
+}
+
+impl TokenScope {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Read => "read",
+            Self::Control => "control",
+            Self::Spend => "spend",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, routing::get, Router};
+    use tower::ServiceExt;
+
+    #[test]
+    fn scopes_are_ordered() {
+        assert!(TokenScope::Read < TokenScope::Control);
+        assert!(TokenScope::Control < TokenScope::Spend);
+        assert_eq!("Spend".parse::<TokenScope>().unwrap(), TokenScope::Spend);
+        assert!("admin".parse::<TokenScope>().is_err());
+    }
+
+    #[test]
+    fn required_scope_classifies_routes() {
+        let get = Method::GET;
+        let post = Method::POST;
+        assert_eq!(required_scope(&get, "/api/health"), None);
+        assert_eq!(required_scope(&get, "/sites/abc/"), None);
+        assert_eq!(required_scope(&post, "/api/drive/upload"), None);
+        assert_eq!(required_scope(&post, "/api/headless/file/search"), None);
+        assert_eq!(
+            required_scope(&post, "/api/headless/file/keyword-search"),
+            None
+        );
+        assert_eq!(
+            required_scope(&Method::OPTIONS, "/api/headless/wallet/send"),
+            None
+        );
+        assert_eq!(
+            required_scope(&get, "/api/headless/runtime"),
+            Some(TokenScope::Read)
+        );
+        assert_eq!(
+            required_scope(&post, "/api/headless/dht/put"),
+            Some(TokenScope::Control)
+        );
+        for lookup in [
+            "/api/headless/wallet/balance",
+            "/api/headless/wallet/history",
+            "/api/headless/wallet/receipt",
+            "/api/headless/dht/get",
+            "/api/headless/dht/ping",
+            "/api/headless/dht/echo/",
+        ] {
+            assert_eq!(
+                required_scope(&post, lookup),
+                Some(TokenScope::Read),
+                "{lookup}"
+            );
+        }
+        // Read routes are matched by method too.
+        assert_eq!(
+            required_scope(&Method::DELETE, "/api/headless/dht/get"),
+            Some(TokenScope::Control)
+        );
+        assert_eq!(
+            required_scope(&post, "/api/headless/wallet/send"),
+            Some(TokenScope::Spend)
+        );
+        // Showing the wallet reveals its private key.
+        assert_eq!(
+            required_scope(&get, "/api/headless/wallet/"),
+            Some(TokenScope::Spend)
+        );
+        assert_eq!(
+            required_scope(&Method::PUT, "/api/headless/bandwidth"),
+            Some(TokenScope::Control)
+        );
+        assert_eq!(required_scope(&get, METRICS_ROUTE), Some(TokenScope::Read));
+    }
+
+    #[test]
+    fn token_store_create_revoke_and_lookup() {
+        let mut store = TokenStore::default();
+        let admin = store.create("admin", TokenScope::Spend).unwrap();
+        let monitor = store.create("monitor", TokenScope::Read).unwrap();
+        assert!(admin.starts_with(TOKEN_PREFIX));
+        assert_ne!(admin, monitor);
+        assert!(store.create("admin", TokenScope::Read).is_err());
+        assert!(store.create("  ", TokenScope::Read).is_err());
+
+        assert_eq!(store.scope_of(&admin), Some(TokenScope::Spend));
+        assert_eq!(store.scope_of(&monitor), Some(TokenScope::Read));
+        assert_eq!(store.scope_of("chd_nope"), None);
+        assert_eq!(store.strongest().unwrap().name, "admin");
+
+        assert!(store.revoke("monitor"));
+        assert!(!store.revoke("monitor"));
+        assert_eq!(store.scope_of(&monitor), None);
+    }
+
+    #[test]
+    fn load_or_init_creates_a_private_admin_token() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = token_file(dir.path());
+        let store = TokenStore::load_or_init(&path).unwrap();
+        assert_eq!(store.tokens.len(), 1);
+        assert_eq!(store.tokens[0].scope, TokenScope::Spend);
+        assert_eq!(TokenStore::load_or_init(&path).unwrap(), store);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o077, 0);
+        }
+    }
+
+    #[test]
+    fn fixed_token_has_spend_scope() {
+        let dir = tempfile::tempdir().unwrap();
+        let auth = DaemonAuth::open(token_file(dir.path()))
+            .unwrap()
+            .with_fixed_token(Some(" cluster-secret ".to_string()));
+        assert_eq!(auth.scope_of("cluster-secret"), Some(TokenScope::Spend));
+        assert_eq!(auth.scope_of("cluster"), None);
+
+        let auth = DaemonAuth::open(token_file(dir.path()))
+            .unwrap()
+            .with_fixed_token(Some("  ".to_string()));
+        assert_eq!(auth.scope_of(""), None);
+    }
+
+    #[test]
+    fn daemon_auth_picks_up_file_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = token_file(dir.path());
+        let auth = DaemonAuth::open(path.clone()).unwrap();
+        let mut store = TokenStore::load(&path).unwrap();
+        let reader = store.create("reader", TokenScope::Read).unwrap();
+        assert_eq!(auth.scope_of(&reader), None);
+
+        // Make sure the modification time moves even on coarse clocks.
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        store.save(&path).unwrap();
+        let later = SystemTime::now() + std::time::Duration::from_secs(5);
+        std::fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+        assert_eq!(auth.scope_of(&reader), Some(TokenScope::Read));
+    }
+
+    async fn status_for(
+        auth: Arc<DaemonAuth>,
+        method: Method,
+        path: &str,
+        token: Option<&str>,
+    ) -> StatusCode {
+        let app = Router::new()
+            .route("/api/headless/runtime", get(|| async { "ok" }))
+            .route(
+                "/api/headless/wallet/send",
+                axum::routing::post(|| async { "sent" }),
+            )
+            .route(
+                "/api/headless/dht/put",
+                axum::routing::post(|| async { "put" }),
+            )
+            .route("/api/health", get(|| async { "ok" }))
+            .route_layer(axum::middleware::from_fn_with_state(auth, require_token));
+        let mut request = Request::builder().method(method).uri(path);
+        if let Some(token) = token {
+            request = request.header(header::AUTHORIZATION, format!("Bearer {}", token));
+        }
+        app.oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+            .status()
+    }
+
+    #[tokio::test]
+    async fn middleware_enforces_token_and_scope() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = token_file(dir.path());
+        let mut store = TokenStore::load_or_init(&path).unwrap();
+        let admin = store.strongest().unwrap().token.clone();
+        let reader = store.create("reader", TokenScope::Read).unwrap();
+        let operator = store.create("operator", TokenScope::Control).unwrap();
+        store.save(&path).unwrap();
+        let auth = Arc::new(DaemonAuth::open(path).unwrap());
+
+        let s = |m: Method, p: &'static str, t: Option<String>| {
+            let auth = Arc::clone(&auth);
+            async move { status_for(auth, m, p, t.as_deref()).await }
+        };
+        assert_eq!(s(Method::GET, "/api/health", None).await, StatusCode::OK);
+        assert_eq!(
+            s(Method::GET, "/api/headless/runtime", None).await,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            s(Method::GET, "/api/headless/runtime", Some("chd_bad".into())).await,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            s(Method::GET, "/api/headless/runtime", Some(reader.clone())).await,
+            StatusCode::OK
+        );
+        assert_eq!(
+            s(Method::POST, "/api/headless/dht/put", Some(reader.clone())).await,
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            s(
+                Method::POST,
+                "/api/headless/dht/put",
+                Some(operator.clone())
+            )
+            .await,
+            StatusCode::OK
+        );
+        assert_eq!(
+            s(Method::POST, "/api/headless/wallet/send", Some(operator)).await,
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            s(Method::POST, "/api/headless/wallet/send", Some(admin)).await,
+            StatusCode::OK
+        );
+    }
+
+    #[test]
+    fn client_token_reads_the_strongest_token() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = TokenStore::default();
+        store.create("reader", TokenScope::Read).unwrap();
+        let admin = store.create("admin", TokenScope::Spend).unwrap();
+        store.save(&token_file(dir.path())).unwrap();
+        if std::env::var(TOKEN_ENV).is_err() {
+            assert_eq!(client_token(dir.path()), Some(admin));
+            assert_eq!(client_token(&dir.path().join("missing")), None);
+        }
+    }
+}
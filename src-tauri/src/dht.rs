@@ -1,12 +1,13 @@
+use crate::dht_record;
 use crate::event_sink::EventSink;
 use futures::StreamExt;
+use libp2p::kad::store::RecordStore as _;
 use libp2p::{
-    dcutr, identify, kad, mdns, noise, ping, relay, request_response,
+    autonat, dcutr, identify, kad, mdns, noise, ping, relay, request_response,
     swarm::{NetworkBehaviour, SwarmEvent},
     tcp, yamux, Multiaddr, PeerId, StreamProtocol, Swarm,
 };
 use serde::{Deserialize, Serialize};
-use sha2::{Digest, Sha256};
 use std::collections::{HashMap, HashSet};
 use std::error::Error;
 use std::io::{Read as _, Seek as _, SeekFrom};
@@ -192,6 +193,40 @@ fn remove_peer_id_from_multiaddr(addr: &Multiaddr) -> Multiaddr {
         .collect()
 }
 
+/// Dial preference for a peer address: direct QUIC first (faster handshake,
+/// better hole-punching with dcutr), then direct TCP, then relay circuits.
+fn transport_dial_rank(addr: &Multiaddr) -> u8 {
+    use libp2p::multiaddr::Protocol;
+    if addr.iter().any(|p| matches!(p, Protocol::P2pCircuit)) {
+        2
+    } else if addr.iter().any(|p| matches!(p, Protocol::QuicV1)) {
+        0
+    } else {
+        1
+    }
+}
+
+/// Stable-sort addresses so QUIC is listed before TCP and relays come last.
+fn prefer_quic_addrs(addrs: &mut [Multiaddr]) {
+    addrs.sort_by_key(transport_dial_rank);
+}
+
+/// Split dial addresses into the direct QUIC ones, dialed first, and the
+/// rest, dialed only if none of those connect. The swarm dials up to eight
+/// addresses of one dial at once, so ordering alone would let TCP win.
+fn split_quic_dial(mut addrs: Vec<Multiaddr>) -> (Vec<Multiaddr>, Vec<Multiaddr>) {
+    prefer_quic_addrs(&mut addrs);
+    let quic = addrs
+        .iter()
+        .take_while(|addr| transport_dial_rank(addr) == 0)
+        .count();
+    if quic == 0 {
+        return (addrs, Vec::new());
+    }
+    let rest = addrs.split_off(quic);
+    (addrs, rest)
+}
+
 // Ping protocol messages
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct PingRequest(pub String);
@@ -206,24 +241,50 @@ pub struct EchoRequest(pub Vec<u8>);
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EchoResponse(pub Vec<u8>);
 
-// File transfer protocol messages (for direct file push / ChiralDrop)
+// File transfer protocol messages (ChiralDrop offers)
+//
+// A ChiralDrop send carries only the offer. Once the receiver accepts, it
+// pulls the content from the sender over the chunked file-request protocol,
+// so neither side ever holds the whole file in a single message. When the
+// receiver declines, cancels or finishes, it sends the offer back with
+// `closed` set so the sender stops serving the file.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FileTransferRequest {
     pub transfer_id: String,
     pub file_name: String,
-    pub file_data: Vec<u8>,
+    /// File size in bytes.
+    pub file_size: u64,
+    /// Merkle content hash the receiver requests from the sender.
+    pub file_hash: String,
     /// Price in wei (as string for CBOR safety). "0" or empty = free.
     #[serde(default)]
     pub price_wei: String,
     /// Sender's wallet address for receiving payment.
     #[serde(default)]
     pub sender_wallet: String,
-    /// SHA-256 hash of the file (needed for paid downloads via chunked protocol).
-    #[serde(default)]
-    pub file_hash: String,
-    /// Actual file size in bytes (for paid transfers where file_data is empty).
-    #[serde(default)]
-    pub file_size: u64,
+    /// Set on the notice a receiver sends when it closes the offer.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub closed: Option<crate::file_transfer::TransferStatus>,
+}
+
+impl FileTransferRequest {
+    /// Notice telling the sender of `transfer_id` that the receiver closed
+    /// it with `status`.
+    pub fn close_notice(
+        transfer_id: String,
+        file_hash: String,
+        status: crate::file_transfer::TransferStatus,
+    ) -> Self {
+        Self {
+            transfer_id,
+            file_name: String::new(),
+            file_size: 0,
+            file_hash,
+            price_wei: String::new(),
+            sender_wallet: String::new(),
+            closed: Some(status),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -233,12 +294,40 @@ pub struct FileTransferResponse {
     pub error: Option<String>,
 }
 
+/// Request-response protocol carrying file info, payments and chunks.
+const FILE_REQUEST_PROTOCOL: &str = "/chiral/file-request/4.0.0";
+/// How long a downloader waits for a seeder to answer a file request.
+pub const FILE_REQUEST_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(12);
 /// Chunk size for file transfers: 256 KB
-const CHUNK_SIZE: usize = 256 * 1024;
+const CHUNK_SIZE: usize = crate::merkle::CHUNK_SIZE;
 /// Maximum retries per chunk before aborting
 const MAX_CHUNK_RETRIES: u8 = 3;
 /// Retry delay schedule for chunk failures (attempt 1..=3), plus jitter.
 const CHUNK_RETRY_BASE_MS: [u64; 3] = [150, 400, 900];
+/// Backoff before re-asking a seeder that turned a chunk away as busy.
+const CHUNK_BUSY_RETRY_MS: u64 = 2_000;
+/// How long a voucher top-up refused for credit waits on the settlement
+/// before it is tried again.
+const VOUCHER_CREDIT_RETRY_SECS: u64 = 30;
+/// Maximum chunk requests in flight for one download, across all sources.
+const CHUNK_DOWNLOAD_WINDOW: usize = 16;
+/// Maximum chunk requests in flight against a single source.
+const CHUNK_MAX_IN_FLIGHT_PER_PEER: usize = 4;
+/// Upper bound on extra providers asked to join a free download.
+const CHUNK_MAX_EXTRA_SOURCES: usize = 10;
+/// A chunk request is never treated as a straggler before this long.
+const CHUNK_STRAGGLER_MIN_MS: u64 = 4_000;
+/// Multiple of a source's expected per-chunk time after which its
+/// outstanding request is reassigned to a faster source.
+const CHUNK_STRAGGLER_FACTOR: f64 = 3.0;
+/// Weight of the newest sample in the per-source throughput average.
+const CHUNK_THROUGHPUT_EWMA_ALPHA: f64 = 0.3;
+/// How often active downloads re-fill their window and check for stragglers.
+const CHUNK_SCHEDULER_TICK_MS: u64 = 250;
+/// How often to look for own DHT records whose signed envelopes need re-sealing.
+const DHT_RECORD_REFRESH_CHECK_SECS: u64 = 10 * 60;
+/// How often to stop serving ChiralDrop offers that outlived the outbox.
+const DROP_OFFER_EXPIRY_CHECK_SECS: u64 = 10 * 60;
 /// How often to refresh the host registry from DHT for auto peer discovery.
 const AUTO_HOST_REGISTRY_REFRESH_SECS: u64 = 20;
 /// Minimum delay between automatic dial attempts to the same discovered peer.
@@ -260,6 +349,8 @@ struct HostRegistryMergeEntry {
     wallet_address: String,
     #[serde(default)]
     updated_at: u64,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    signature: String,
 }
 
 // Chunked file request protocol messages
@@ -287,11 +378,26 @@ pub enum ChunkRequest {
         payment_tx: String,
         payer_address: String,
     },
+    /// Pay for the next chunks with a signed cumulative voucher instead of
+    /// an up-front transaction (see `crate::micropayments`)
+    PaymentVoucher {
+        request_id: String,
+        file_hash: String,
+        voucher: crate::micropayments::PaymentVoucher,
+    },
+    /// Tell the seeder an on-chain transfer settled earlier vouchers
+    VoucherSettlement {
+        tx_hash: String,
+        payer_address: String,
+        payee_address: String,
+        /// Voucher debt settled in wei, platform fee included
+        amount_wei: String,
+    },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ChunkResponse {
-    /// File metadata with per-chunk SHA-256 hashes
+    /// File metadata with the Merkle root every chunk is proven against
     FileInfo {
         request_id: String,
         file_hash: String,
@@ -299,7 +405,9 @@ pub enum ChunkResponse {
         file_size: u64,
         chunk_size: u32,
         total_chunks: u32,
-        chunk_hashes: Vec<String>,
+        /// Merkle commitment over the file's chunks (see `crate::merkle`).
+        /// Equals `file_hash` for content published with Merkle hashes.
+        merkle_root: String,
         /// Price in wei as string (u128 as string for CBOR safety)
         price_wei: String,
         /// Seeder's wallet address for payment
@@ -308,6 +416,10 @@ pub enum ChunkResponse {
         /// being downloaded as a member of a paid folder bundle.
         #[serde(default)]
         folder_hash: Option<String>,
+        /// Chunks per payment voucher when the seeder accepts pay-as-you-go
+        /// vouchers for this file; 0 means up-front payment only.
+        #[serde(default)]
+        voucher_interval: u32,
         /// ECDSA signature by `wallet_address` over the canonical
         /// payload of every other field (see `file_info_sign_payload`).
         /// Empty string means "unsigned" — readers reject unsigned
@@ -323,7 +435,8 @@ pub enum ChunkResponse {
         file_hash: String,
         chunk_index: u32,
         chunk_data: Option<Vec<u8>>,
-        chunk_hash: String,
+        /// Sibling hashes from this chunk's leaf up to `merkle_root`
+        proof: Vec<String>,
         error: Option<String>,
     },
     /// Acknowledgement of payment verification
@@ -333,6 +446,21 @@ pub enum ChunkResponse {
         accepted: bool,
         error: Option<String>,
     },
+    /// Answer to a payment voucher: how many chunks the download is now
+    /// paid for
+    VoucherAck {
+        request_id: String,
+        file_hash: String,
+        accepted: bool,
+        chunks_covered: u32,
+        error: Option<String>,
+    },
+    /// Whether the seeder verified and credited a voucher settlement
+    SettlementAck {
+        tx_hash: String,
+        accepted: bool,
+        error: Option<String>,
+    },
 }
 
 fn parse_protocol_price_wei(price_wei: &str, field: &str) -> Result<u128, String> {
@@ -355,26 +483,27 @@ fn parse_protocol_price_wei(price_wei: &str, field: &str) -> Result<u128, String
 /// `SiteDirectoryEntry::sign_payload`: every variable-length field is
 /// preceded by its `u32` LE byte length so an attacker can't shift
 /// content across field boundaries by embedding NUL or other separator
-/// bytes. `chunk_hashes` is encoded as a count followed by each
-/// length-prefixed element.
+/// bytes.
 pub fn file_info_sign_payload(
     file_hash: &str,
     file_name: &str,
     file_size: u64,
     chunk_size: u32,
     total_chunks: u32,
-    chunk_hashes: &[String],
+    merkle_root: &str,
     price_wei: &str,
     wallet_address: &str,
     folder_hash: Option<&str>,
+    voucher_interval: u32,
 ) -> Vec<u8> {
-    let mut out = Vec::with_capacity(256 + chunk_hashes.len() * 36);
-    out.extend_from_slice(b"file-info-v1");
+    let mut out = Vec::with_capacity(320);
+    out.extend_from_slice(b"file-info-v2");
     for part in [
         file_hash.as_bytes(),
         file_name.as_bytes(),
         price_wei.as_bytes(),
         wallet_address.as_bytes(),
+        merkle_root.as_bytes(),
     ] {
         out.extend_from_slice(&(part.len() as u32).to_le_bytes());
         out.extend_from_slice(part);
@@ -382,29 +511,27 @@ pub fn file_info_sign_payload(
     out.extend_from_slice(&file_size.to_le_bytes());
     out.extend_from_slice(&chunk_size.to_le_bytes());
     out.extend_from_slice(&total_chunks.to_le_bytes());
-    out.extend_from_slice(&(chunk_hashes.len() as u32).to_le_bytes());
-    for h in chunk_hashes {
-        out.extend_from_slice(&(h.len() as u32).to_le_bytes());
-        out.extend_from_slice(h.as_bytes());
-    }
     if let Some(folder_hash) = folder_hash.filter(|h| !h.trim().is_empty()) {
         out.extend_from_slice(&(folder_hash.len() as u32).to_le_bytes());
         out.extend_from_slice(folder_hash.as_bytes());
     }
+    // Left out when zero so FileInfo without vouchers signs as before.
+    if voucher_interval > 0 {
+        out.extend_from_slice(b"vouchers");
+        out.extend_from_slice(&voucher_interval.to_le_bytes());
+    }
     out
 }
 
 enum SwarmCommand {
     SendPing(PeerId),
-    SendFile {
+    SendFileOffer {
         peer_id: PeerId,
-        transfer_id: String,
-        file_name: String,
-        file_data: Vec<u8>,
-        price_wei: String,
-        sender_wallet: String,
-        file_hash: String,
-        file_size: u64,
+        offer: FileTransferRequest,
+    },
+    CancelDownload {
+        request_id: String,
+        response_tx: tokio::sync::oneshot::Sender<bool>,
     },
     RequestFileInfo {
         peer_id: PeerId,
@@ -417,9 +544,10 @@ enum SwarmCommand {
         /// Synchronous ack so caller can try another seeder if this one is unreachable immediately.
         response_tx: tokio::sync::oneshot::Sender<Result<(), String>>,
     },
+    /// `value` is an already-sealed `dht_record::SignedRecord`.
     PutDhtValue {
         key: String,
-        value: String,
+        value: Vec<u8>,
         response_tx: tokio::sync::oneshot::Sender<Result<(), String>>,
     },
     GetDhtValue {
@@ -432,6 +560,15 @@ enum SwarmCommand {
     RemoveDhtRecord {
         key: String,
     },
+    /// Records in the local store that this node published, for re-sealing
+    /// before their envelopes expire.
+    ListOwnDhtRecords {
+        response_tx: tokio::sync::oneshot::Sender<Vec<(String, Vec<u8>)>>,
+    },
+    /// Result of a UPnP / NAT-PMP mapping round from `spawn_port_mapping`.
+    PortMappingsUpdated {
+        mappings: Vec<crate::nat::PortMapping>,
+    },
     HealthCheck {
         response_tx: tokio::sync::oneshot::Sender<DhtHealthInfo>,
     },
@@ -474,6 +611,17 @@ enum SwarmCommand {
         channel: request_response::ResponseChannel<ChunkResponse>,
         response: ChunkResponse,
     },
+    /// Answer a relay's HTTP-over-libp2p request once the local server has
+    /// responded (see `crate::http_p2p`).
+    SendHttpP2pResponse {
+        channel: request_response::ResponseChannel<crate::http_p2p::HttpP2pResponse>,
+        response: crate::http_p2p::HttpP2pResponse,
+    },
+    /// Tell a seeder about an on-chain settlement of its vouchers.
+    NotifyVoucherSettlement {
+        peer_id: PeerId,
+        settlement: crate::micropayments::IssuedSettlement,
+    },
 }
 
 #[derive(Clone, Debug)]
@@ -494,6 +642,12 @@ pub struct DhtHealthInfo {
     pub bootstrap_nodes: Vec<BootstrapNodeStatus>,
     pub shared_files: usize,
     pub protocols: Vec<String>,
+    /// AutoNAT verdict: "unknown", "public" or "private".
+    pub nat_status: crate::nat::Reachability,
+    /// Address a remote peer confirmed it could dial, when public.
+    pub public_address: Option<String>,
+    /// UPnP / NAT-PMP mappings currently held on the router.
+    pub port_mappings: Vec<crate::nat::PortMapping>,
 }
 
 #[derive(Clone, Serialize, Debug)]
@@ -517,13 +671,15 @@ pub struct PeerInfo {
 pub struct NetworkStats {
     pub connected_peers: usize,
     pub total_peers: usize,
+    pub bandwidth: crate::bandwidth::BandwidthStats,
 }
 
 #[derive(NetworkBehaviour)]
 struct DhtBehaviour {
     relay_client: relay::client::Behaviour,
     dcutr: dcutr::Behaviour,
-    kad: kad::Behaviour<kad::store::MemoryStore>,
+    autonat: autonat::Behaviour,
+    kad: kad::Behaviour<crate::kad_store::PersistentStore>,
     mdns: mdns::tokio::Behaviour,
     ping: ping::Behaviour,
     identify: identify::Behaviour,
@@ -531,6 +687,7 @@ struct DhtBehaviour {
     file_transfer: cbor_codec::Behaviour<FileTransferRequest, FileTransferResponse>,
     file_request: cbor_codec::Behaviour<ChunkRequest, ChunkResponse>,
     echo_protocol: request_response::cbor::Behaviour<EchoRequest, EchoResponse>,
+    http_p2p: crate::http_p2p::HttpP2pBehaviour,
 }
 
 /// Map of file hash -> file path for files we're seeding
@@ -547,8 +704,25 @@ pub struct FolderAccessPolicy {
 struct AuthorizedChunkAccess {
     peer_id: PeerId,
     scope: PaymentScope,
+    /// Set for downloads paid with vouchers: only this many distinct
+    /// chunks may be served until the next voucher arrives.
+    voucher_allowance: Option<VoucherAllowance>,
 }
 
+#[derive(Clone, Debug, Default)]
+struct VoucherAllowance {
+    /// Chunks paid for by the newest accepted voucher
+    covered: u32,
+    /// Distinct chunks served so far; serving one again is free
+    served: HashSet<u32>,
+}
+
+/// Chunk error a seeder returns when the newest voucher is used up.
+const VOUCHER_EXHAUSTED_ERROR: &str = "Payment voucher exhausted; send a new voucher";
+/// Marks `outbound_request_map` entries that carry a settlement notice
+/// rather than a download request.
+const SETTLEMENT_NOTICE_PREFIX: &str = "settlement:";
+
 type SeederAuthorizedChunksMap = Arc<Mutex<HashMap<String, AuthorizedChunkAccess>>>;
 
 #[derive(Clone, Debug)]
@@ -556,8 +730,9 @@ pub struct SharedFileInfo {
     pub file_path: String,
     pub file_name: String,
     pub file_size: u64,
-    /// Cached chunk hashes, precomputed at registration when possible
-    pub chunk_hashes: Option<Vec<String>>,
+    /// Cached Merkle tree over the file's chunks, built in the background
+    /// at registration or on first request
+    pub merkle_tree: Option<Arc<crate::merkle::MerkleTree>>,
     /// Price in wei (0 = free)
     pub price_wei: u128,
     /// Seeder's wallet address for receiving payment
@@ -575,6 +750,95 @@ pub struct SharedFileInfo {
     /// file-hash requests are rejected unless they carry one of these
     /// folder hashes.
     pub folder_access: HashMap<String, FolderAccessPolicy>,
+    /// ChiralDrop offers this entry was shared for, by transfer id. When
+    /// non-empty the file is served only to the offered peers, and it stops
+    /// being shared once the last offer closes or expires.
+    pub drop_offers: HashMap<String, DropOffer>,
+}
+
+/// A file offered to one peer over ChiralDrop.
+#[derive(Clone, Debug)]
+pub struct DropOffer {
+    pub peer_id: PeerId,
+    pub offered_at: Instant,
+}
+
+fn drop_offer_allows(file_info: &SharedFileInfo, peer_id: PeerId) -> bool {
+    file_info.drop_offers.is_empty()
+        || file_info
+            .drop_offers
+            .values()
+            .any(|offer| offer.peer_id == peer_id)
+}
+
+/// Close the ChiralDrop offer `transfer_id` made to `peer_id`. Returns the
+/// shared entry if that was its last offer, since the file is then no
+/// longer served.
+fn close_drop_offer(
+    shared: &mut HashMap<String, SharedFileInfo>,
+    transfer_id: &str,
+    peer_id: PeerId,
+) -> Option<SharedFileInfo> {
+    let file_hash = shared
+        .iter()
+        .find(|(_, info)| {
+            info.drop_offers
+                .get(transfer_id)
+                .is_some_and(|offer| offer.peer_id == peer_id)
+        })
+        .map(|(file_hash, _)| file_hash.clone())?;
+    let info = shared.get_mut(&file_hash)?;
+    info.drop_offers.remove(transfer_id);
+    if info.drop_offers.is_empty() {
+        shared.remove(&file_hash)
+    } else {
+        None
+    }
+}
+
+/// Drop ChiralDrop offers older than the outbox retention, returning the
+/// entries that are no longer shared at all.
+fn expire_drop_offers(
+    shared: &mut HashMap<String, SharedFileInfo>,
+    now: Instant,
+) -> Vec<SharedFileInfo> {
+    let mut expired = Vec::new();
+    shared.retain(|_, info| {
+        if info.drop_offers.is_empty() {
+            return true;
+        }
+        info.drop_offers.retain(|_, offer| {
+            now.duration_since(offer.offered_at) < crate::file_transfer::OUTBOX_RETENTION
+        });
+        if info.drop_offers.is_empty() {
+            expired.push(info.clone());
+            false
+        } else {
+            true
+        }
+    });
+    expired
+}
+
+/// Expire old ChiralDrop offers and delete the outbox copies of the files
+/// they leave unshared. Returns how many files stopped being shared.
+async fn sweep_drop_offers(shared_files: &SharedFilesMap, now: Instant) -> usize {
+    let unshared = expire_drop_offers(&mut *shared_files.lock().await, now);
+    let count = unshared.len();
+    discard_unshared_offers(unshared);
+    count
+}
+
+/// Delete the outbox copies of files that are no longer offered.
+fn discard_unshared_offers(unshared: Vec<SharedFileInfo>) {
+    if unshared.is_empty() {
+        return;
+    }
+    tokio::task::spawn_blocking(move || {
+        for info in unshared {
+            crate::file_transfer::discard_staged_file(Path::new(&info.file_path));
+        }
+    });
 }
 
 #[derive(Clone, Debug)]
@@ -590,8 +854,13 @@ fn normalize_folder_hash(folder_hash: &str) -> String {
 
 fn resolve_file_access(
     file_info: &SharedFileInfo,
+    peer_id: PeerId,
     requested_folder_hash: Option<&str>,
 ) -> Result<ResolvedFileAccess, String> {
+    // ChiralDrop offers are private to the peers they were sent to.
+    if !drop_offer_allows(file_info, peer_id) {
+        return Err("File not found".to_string());
+    }
     if let Some(folder_hash) = requested_folder_hash
         .map(str::trim)
         .filter(|h| !h.is_empty())
@@ -641,10 +910,14 @@ async fn remember_authorized_chunk_access(
     peer_id: PeerId,
     scope: PaymentScope,
 ) {
-    authorized_chunks
-        .lock()
-        .await
-        .insert(request_id, AuthorizedChunkAccess { peer_id, scope });
+    authorized_chunks.lock().await.insert(
+        request_id,
+        AuthorizedChunkAccess {
+            peer_id,
+            scope,
+            voucher_allowance: None,
+        },
+    );
 }
 
 fn authorized_scope_matches_file(
@@ -664,6 +937,7 @@ fn authorized_scope_matches_file(
         PaymentScope::Folder { folder_hash } => file_info
             .folder_access
             .contains_key(&normalize_folder_hash(folder_hash)),
+        PaymentScope::Settlement { .. } => false,
     }
 }
 
@@ -673,7 +947,10 @@ fn chunk_request_access_error(
     file_hash: &str,
     file_info: &SharedFileInfo,
 ) -> Option<String> {
-    if let Ok(access) = resolve_file_access(file_info, None) {
+    if !drop_offer_allows(file_info, peer_id) {
+        return Some("File not found".to_string());
+    }
+    if let Ok(access) = resolve_file_access(file_info, peer_id, None) {
         if access.price_wei == 0 {
             return None;
         }
@@ -694,6 +971,169 @@ fn chunk_request_access_error(
     }
 }
 
+/// Count `chunk_index` against the download's voucher, if it is paid
+/// with vouchers. Returns whether this call newly counted the chunk, or the
+/// error to send when the voucher is used up.
+fn charge_voucher_allowance(
+    authorized: Option<&mut AuthorizedChunkAccess>,
+    chunk_index: u32,
+) -> Result<bool, String> {
+    let Some(allowance) = authorized.and_then(|a| a.voucher_allowance.as_mut()) else {
+        return Ok(false);
+    };
+    if allowance.served.contains(&chunk_index) {
+        return Ok(false);
+    }
+    if allowance.served.len() >= allowance.covered as usize {
+        return Err(VOUCHER_EXHAUSTED_ERROR.to_string());
+    }
+    allowance.served.insert(chunk_index);
+    Ok(true)
+}
+
+/// Undo a [`charge_voucher_allowance`] for a chunk that was never sent.
+fn refund_voucher_allowance(authorized: Option<&mut AuthorizedChunkAccess>, chunk_index: u32) {
+    if let Some(allowance) = authorized.and_then(|a| a.voucher_allowance.as_mut()) {
+        allowance.served.remove(&chunk_index);
+    }
+}
+
+/// [`refund_voucher_allowance`] for the download behind `request_id`.
+async fn refund_voucher_chunk(
+    authorized_chunks: &SeederAuthorizedChunksMap,
+    request_id: &str,
+    chunk_index: u32,
+) {
+    refund_voucher_allowance(
+        authorized_chunks.lock().await.get_mut(request_id),
+        chunk_index,
+    );
+}
+
+/// Check a voucher against the file it claims to pay for and record it.
+/// Returns how many chunks of the download are now paid for.
+fn accept_payment_voucher(
+    book: &mut crate::micropayments::ReceivedVoucherBook,
+    voucher: crate::micropayments::PaymentVoucher,
+    peer_id: PeerId,
+    request_id: &str,
+    file_hash: &str,
+    file_info: &SharedFileInfo,
+    now: u64,
+) -> Result<u32, String> {
+    check_voucher_terms(&voucher, peer_id, request_id, file_hash, file_info)?;
+    book.accept(voucher, now)
+}
+
+/// Check that a voucher's terms are this file's price, wallet and size.
+fn check_voucher_terms(
+    voucher: &crate::micropayments::PaymentVoucher,
+    peer_id: PeerId,
+    request_id: &str,
+    file_hash: &str,
+    file_info: &SharedFileInfo,
+) -> Result<(), String> {
+    let access = resolve_file_access(file_info, peer_id, None)?;
+    if access.price_wei == 0 {
+        return Err("Vouchers are only accepted for paid files".to_string());
+    }
+    let terms = &voucher.terms;
+    let total_chunks = file_info
+        .merkle_tree
+        .as_ref()
+        .map(|tree| tree.chunk_count())
+        .unwrap_or_else(|| crate::merkle::chunk_count(file_info.file_size));
+    if terms.session_id != request_id
+        || !terms.file_hash.eq_ignore_ascii_case(file_hash)
+        || !terms.payee.eq_ignore_ascii_case(&access.wallet_address)
+        || terms.price()? != access.price_wei
+        || terms.total_chunks != total_chunks
+    {
+        return Err("Voucher does not match this file's price, wallet or size".to_string());
+    }
+    Ok(())
+}
+
+/// Check and record a voucher from `peer`. The first voucher of a download
+/// is only taken if the payer's balance covers the file and what it
+/// already owes this seeder.
+async fn receive_payment_voucher(
+    shared_files: &SharedFilesMap,
+    voucher: crate::micropayments::PaymentVoucher,
+    peer_id: PeerId,
+    request_id: &str,
+    file_hash: &str,
+) -> Result<u32, String> {
+    {
+        let shared = shared_files.lock().await;
+        let info = shared
+            .get(file_hash)
+            .ok_or_else(|| "File not found".to_string())?;
+        check_voucher_terms(&voucher, peer_id, request_id, file_hash, info)?;
+    }
+    voucher.verify()?;
+    let opening_debt = {
+        let book = crate::micropayments::received().lock();
+        (!book.has_session(&voucher.terms))
+            .then(|| book.outstanding(&voucher.terms.payer, &voucher.terms.payee))
+    };
+    if let Some(outstanding) = opening_debt {
+        let balance =
+            crate::wallet::get_balance(&crate::geth::rpc_endpoint(), &voucher.terms.payer)
+                .await
+                .map_err(|e| format!("Could not check the payer's balance: {}", e))?;
+        let balance_wei = balance
+            .balance_wei
+            .parse::<u128>()
+            .map_err(|e| format!("Invalid payer balance: {}", e))?;
+        crate::micropayments::check_payer_funds(balance_wei, outstanding, &voucher.terms)?;
+    }
+    let now = crate::drive_storage::now_secs().unwrap_or(0);
+    let covered = {
+        let shared = shared_files.lock().await;
+        let info = shared
+            .get(file_hash)
+            .ok_or_else(|| "File not found".to_string())?;
+        let mut book = crate::micropayments::received().lock();
+        accept_payment_voucher(
+            &mut book, voucher, peer_id, request_id, file_hash, info, now,
+        )?
+    };
+    crate::micropayments::persist_received().await?;
+    Ok(covered)
+}
+
+/// Let `peer_id` fetch the first `covered` chunks of `file_hash` on
+/// `request_id`. Access already paid up front is left as it is.
+async fn grant_voucher_allowance(
+    authorized_chunks: &SeederAuthorizedChunksMap,
+    request_id: String,
+    peer_id: PeerId,
+    file_hash: String,
+    covered: u32,
+) {
+    let mut authorized_chunks = authorized_chunks.lock().await;
+    let existing = authorized_chunks
+        .remove(&request_id)
+        .filter(|a| a.peer_id == peer_id);
+    let access = match existing {
+        // Already paid up front: a voucher must not narrow that access.
+        Some(a) if a.voucher_allowance.is_none() => a,
+        existing => AuthorizedChunkAccess {
+            peer_id,
+            scope: PaymentScope::DirectFile { file_hash },
+            voucher_allowance: Some(VoucherAllowance {
+                covered,
+                served: existing
+                    .and_then(|a| a.voucher_allowance)
+                    .map(|a| a.served)
+                    .unwrap_or_default(),
+            }),
+        },
+    };
+    authorized_chunks.insert(request_id, access);
+}
+
 fn file_info_error_response(
     request_id: String,
     file_hash: String,
@@ -707,10 +1147,11 @@ fn file_info_error_response(
         file_size: 0,
         chunk_size: CHUNK_SIZE as u32,
         total_chunks: 0,
-        chunk_hashes: vec![],
+        merkle_root: String::new(),
         price_wei: "0".to_string(),
         wallet_address: String::new(),
         folder_hash,
+        voucher_interval: 0,
         signature: String::new(),
         error: Some(error.into()),
     }
@@ -720,8 +1161,7 @@ fn signed_file_info_response(
     request_id: String,
     file_hash: String,
     file_name: String,
-    file_size: u64,
-    chunk_hashes: Vec<String>,
+    tree: &crate::merkle::MerkleTree,
     access: ResolvedFileAccess,
     private_key: &str,
 ) -> ChunkResponse {
@@ -735,17 +1175,27 @@ fn signed_file_info_response(
         );
     }
 
-    let total_chunks = chunk_hashes.len() as u32;
+    let file_size = tree.file_size();
+    let total_chunks = tree.chunk_count();
+    let merkle_root = tree.root();
+    // Folder bundles are paid once for every member, so only direct paid
+    // files are offered pay-as-you-go.
+    let voucher_interval = if access.price_wei > 0 && access.folder_hash.is_none() {
+        crate::micropayments::VOUCHER_CHUNK_INTERVAL
+    } else {
+        0
+    };
     let payload = file_info_sign_payload(
         &file_hash,
         &file_name,
         file_size,
         CHUNK_SIZE as u32,
         total_chunks,
-        &chunk_hashes,
+        &merkle_root,
         &price_str,
         &access.wallet_address,
         access.folder_hash.as_deref(),
+        voucher_interval,
     );
     match crate::wallet::sign_message(private_key, &payload) {
         Ok(signature) if !signature.is_empty() => ChunkResponse::FileInfo {
@@ -755,10 +1205,11 @@ fn signed_file_info_response(
             file_size,
             chunk_size: CHUNK_SIZE as u32,
             total_chunks,
-            chunk_hashes,
+            merkle_root,
             price_wei: price_str,
             wallet_address: access.wallet_address,
             folder_hash: access.folder_hash,
+            voucher_interval,
             signature,
             error: None,
         },
@@ -787,11 +1238,47 @@ pub struct DownloadCredentials {
     pub private_key: String,
     pub folder_hash: Option<String>,
     pub folder_payment_tx: Option<String>,
+    /// Pay with signed vouchers settled later, when the seeder offers them
+    pub pay_with_vouchers: bool,
 }
 
 /// Map of request_id -> download credentials for payment during chunked transfer
 pub type DownloadCredentialsMap = Arc<Mutex<HashMap<String, DownloadCredentials>>>;
 
+/// Per-seeder bookkeeping for a multi-source chunked download
+#[derive(Clone, Debug, Default)]
+struct ChunkSource {
+    /// Smoothed throughput in bytes/sec; `None` until the first chunk lands
+    throughput_bps: Option<f64>,
+    /// Consecutive failed chunk requests against this source
+    failures: u8,
+}
+
+/// A chunk request currently outstanding against one source
+#[derive(Clone, Debug)]
+struct InFlightChunk {
+    peer_id: PeerId,
+    sent_at: std::time::Instant,
+}
+
+/// Buyer-side state of a download paid with vouchers
+struct VoucherProgress {
+    terms: crate::micropayments::VoucherTerms,
+    private_key: String,
+    /// Seeder that receives the vouchers
+    payee_peer: PeerId,
+    /// Chunks each top-up adds, as offered in the FileInfo
+    interval: u32,
+    /// Chunks paid for by the newest voucher the seeder accepted
+    acked: u32,
+    /// Voucher awaiting the seeder's answer
+    pending: Option<request_response::OutboundRequestId>,
+    /// When the seeder refused a voucher for credit and we started settling
+    awaiting_settlement: Option<Instant>,
+    /// Distinct chunks requested so far; each is paid for once
+    charged: HashSet<u32>,
+}
+
 /// Tracks an in-progress chunked download on the downloader side
 struct ActiveChunkedDownload {
     request_id: String,
@@ -799,22 +1286,51 @@ struct ActiveChunkedDownload {
     file_name: String,
     file_size: u64,
     total_chunks: u32,
-    chunk_hashes: Vec<String>,
+    /// Signed Merkle root each chunk's inclusion proof is checked against
+    merkle_root: String,
     received_chunks: Vec<bool>,
     output_path: PathBuf,
     bytes_written: u64,
+    /// Seeder that answered FileInfo first (and was paid, for paid files)
     peer_id: PeerId,
+    /// Seeders held in reserve; promoted only when every source has failed
     backup_peers: Vec<PeerId>,
+    /// Seeders currently serving chunks. Paid downloads only ever use
+    /// `peer_id`, since other seeders have not been paid for this request.
+    sources: HashMap<PeerId, ChunkSource>,
+    /// Whether other providers may join as sources (free downloads only)
+    allow_parallel_sources: bool,
+    /// chunk_index -> outstanding request
+    in_flight: HashMap<u32, InFlightChunk>,
+    /// Outbound request -> (chunk_index, peer), used to match responses and
+    /// failures to the chunk they were for
+    outbound_chunks: HashMap<request_response::OutboundRequestId, (u32, PeerId)>,
+    /// chunk_index -> earliest time a failed chunk may be requested again
+    retry_after: HashMap<u32, std::time::Instant>,
+    /// Every chunk below this index has been received and written
+    first_missing_chunk: u32,
     retry_counts: Vec<u8>,
-    current_chunk_index: u32,
     start_time: std::time::Instant,
     /// Whether payment has been confirmed by seeder
     payment_confirmed: bool,
+    /// Set when the download is paid with vouchers instead of up front
+    vouchers: Option<VoucherProgress>,
 }
 
 /// Map of request_id -> active chunked download state
 type ActiveDownloadsMap = HashMap<String, ActiveChunkedDownload>;
 
+/// Progress recovered from disk for a download interrupted by a restart,
+/// waiting for a seeder to answer the re-sent FileInfo request.
+struct ResumedDownload {
+    record: crate::pending_downloads::PendingDownload,
+    received_chunks: Vec<bool>,
+    verified_bytes: u64,
+}
+
+/// Map of request_id -> recovered progress for a resumed download
+type ResumedDownloadsMap = Arc<Mutex<HashMap<String, ResumedDownload>>>;
+
 #[derive(Clone)]
 struct DhtBootstrapGate {
     ready: Arc<AtomicBool>,
@@ -872,41 +1388,53 @@ impl DhtBootstrapGate {
     }
 }
 
-/// Compute SHA-256 hashes for each chunk of a file
-fn compute_chunk_hashes(file_path: &str) -> Result<Vec<String>, String> {
-    let mut file =
-        std::fs::File::open(file_path).map_err(|e| format!("Failed to open file: {}", e))?;
-    let metadata = file
-        .metadata()
-        .map_err(|e| format!("Failed to get file metadata: {}", e))?;
-    let file_size = metadata.len();
-    let total_chunks = ((file_size as usize + CHUNK_SIZE - 1) / CHUNK_SIZE) as u32;
-    let mut hashes = Vec::with_capacity(total_chunks as usize);
+/// Chunk bytes, their inclusion proof, and the tree if it had to be built.
+type ProvedChunk = (Vec<u8>, Vec<String>, Option<Arc<crate::merkle::MerkleTree>>);
 
-    let mut buf = vec![0u8; CHUNK_SIZE];
-    for _ in 0..total_chunks {
-        let bytes_read = file
-            .read(&mut buf)
-            .map_err(|e| format!("Failed to read chunk: {}", e))?;
-        let mut hasher = Sha256::new();
-        hasher.update(&buf[..bytes_read]);
-        hashes.push(hex::encode(hasher.finalize()));
+/// Build the Merkle tree for a shared file and check it against the hash
+/// the file was published under, so a file that changed on disk is never
+/// served under its old hash.
+fn compute_merkle_tree(
+    file_path: &str,
+    file_hash: &str,
+) -> Result<crate::merkle::MerkleTree, String> {
+    let tree = crate::merkle::MerkleTree::from_file(Path::new(file_path))?;
+    if !tree.matches_hash(file_hash) {
+        return Err(format!(
+            "File on disk no longer matches its published hash {}",
+            file_hash
+        ));
     }
+    Ok(tree)
+}
 
-    Ok(hashes)
+/// A throwaway wallet (address, private key) for signing the FileInfo of a
+/// free ChiralDrop offer when the sender has no wallet unlocked.
+fn ephemeral_signing_identity() -> Result<(String, String), String> {
+    use rand::RngCore;
+    let mut secret = [0u8; 32];
+    rand::rngs::OsRng.fill_bytes(&mut secret);
+    let private_key = format!("0x{}", hex::encode(secret));
+    let address = crate::wallet::address_from_private_key(&private_key)?;
+    Ok((address, private_key))
 }
 
 pub struct DhtService {
     peers: Arc<Mutex<Vec<PeerInfo>>>,
     is_running: Arc<Mutex<bool>>,
     local_peer_id: Arc<Mutex<Option<String>>>,
+    /// Node identity, used to sign every record this node puts in the DHT.
+    identity: Arc<Mutex<Option<libp2p::identity::Keypair>>>,
     command_sender: Arc<Mutex<Option<mpsc::UnboundedSender<SwarmCommand>>>>,
     file_transfer_service: Option<Arc<Mutex<crate::file_transfer::FileTransferService>>>,
     shared_files: SharedFilesMap,
     download_directory: DownloadDirectoryRef,
     active_downloads: Arc<Mutex<ActiveDownloadsMap>>,
+    resumed_downloads: ResumedDownloadsMap,
     download_credentials: DownloadCredentialsMap,
     bootstrap_gate: DhtBootstrapGate,
+    /// Shares and sites served to relays over libp2p.
+    http_p2p_server: crate::http_p2p::HttpP2pServer,
 }
 
 impl DhtService {
@@ -919,16 +1447,26 @@ impl DhtService {
             peers: Arc::new(Mutex::new(Vec::new())),
             is_running: Arc::new(Mutex::new(false)),
             local_peer_id: Arc::new(Mutex::new(None)),
+            identity: Arc::new(Mutex::new(None)),
             command_sender: Arc::new(Mutex::new(None)),
             file_transfer_service: Some(file_transfer_service),
             shared_files: Arc::new(Mutex::new(std::collections::HashMap::new())),
             download_directory,
             active_downloads: Arc::new(Mutex::new(HashMap::new())),
+            resumed_downloads: Arc::new(Mutex::new(HashMap::new())),
             download_credentials,
             bootstrap_gate: DhtBootstrapGate::new(),
+            http_p2p_server: crate::http_p2p::HttpP2pServer::new(),
         }
     }
 
+    /// Serve relay HTTP requests from `server`'s published shares and
+    /// sites instead of an empty set.
+    pub fn with_http_p2p_server(mut self, server: crate::http_p2p::HttpP2pServer) -> Self {
+        self.http_p2p_server = server;
+        self
+    }
+
     /// Register a file for sharing (seeding)
     pub async fn register_shared_file(
         &self,
@@ -988,49 +1526,54 @@ impl DhtService {
                 file_path: file_path.clone(),
                 file_name,
                 file_size,
-                // Keep seeding registration instant; chunk hashes are computed lazily
-                // on first request (and we also warm them in the background below).
-                chunk_hashes: None,
+                // Keep seeding registration instant; the Merkle tree is built lazily
+                // on first request (and we also warm it in the background below).
+                merkle_tree: None,
                 price_wei,
                 wallet_address,
                 private_key,
                 folder_access,
+                drop_offers: HashMap::new(),
             },
         );
         println!("  Total shared files now: {}", shared.len());
-        println!("  Seeding registration is instant; chunk hashes warming in background");
+        println!("  Seeding registration is instant; Merkle tree warming in background");
         println!("================================");
 
-        // Warm chunk hashes in the background so first download request is faster,
+        // Warm the Merkle tree in the background so first download request is faster,
         // without blocking publish/seed operations.
         let shared_files = self.shared_files.clone();
         let file_hash_for_task = file_hash;
         tokio::spawn(async move {
             let path_for_hashing = file_path;
-            match tokio::task::spawn_blocking(move || compute_chunk_hashes(&path_for_hashing)).await
+            let hash_for_check = file_hash_for_task.clone();
+            match tokio::task::spawn_blocking(move || {
+                compute_merkle_tree(&path_for_hashing, &hash_for_check)
+            })
+            .await
             {
-                Ok(Ok(hashes)) => {
-                    let hash_count = hashes.len();
+                Ok(Ok(tree)) => {
+                    let chunk_count = tree.chunk_count();
                     let mut shared = shared_files.lock().await;
                     if let Some(info) = shared.get_mut(&file_hash_for_task) {
-                        if info.chunk_hashes.is_none() {
-                            info.chunk_hashes = Some(hashes);
+                        if info.merkle_tree.is_none() {
+                            info.merkle_tree = Some(Arc::new(tree));
                             println!(
-                                "  Background chunk hash warmup complete for {} ({} chunks)",
-                                file_hash_for_task, hash_count
+                                "  Background Merkle tree warmup complete for {} ({} chunks)",
+                                file_hash_for_task, chunk_count
                             );
                         }
                     }
                 }
                 Ok(Err(e)) => {
                     println!(
-                        "  Background chunk hash warmup failed for {}: {}",
+                        "  Background Merkle tree warmup failed for {}: {}",
                         file_hash_for_task, e
                     );
                 }
                 Err(e) => {
                     println!(
-                        "  Background chunk hash warmup task failed for {}: {}",
+                        "  Background Merkle tree warmup task failed for {}: {}",
                         file_hash_for_task, e
                     );
                 }
@@ -1093,8 +1636,10 @@ impl DhtService {
         self.start_with_sink(EventSink::tauri(app)).await
     }
 
-    pub async fn start_headless(&self) -> Result<String, String> {
-        self.start_with_sink(EventSink::noop()).await
+    /// Start without a GUI; events go to `events` (the daemon's broadcast
+    /// sink, or a no-op).
+    pub async fn start_headless(&self, events: EventSink) -> Result<String, String> {
+        self.start_with_sink(events).await
     }
 
     async fn start_with_sink(&self, events: EventSink) -> Result<String, String> {
@@ -1105,12 +1650,13 @@ impl DhtService {
         self.bootstrap_gate.reset();
 
         // Create libp2p swarm
-        let (swarm, peer_id) = create_swarm().await.map_err(|e| e.to_string())?;
+        let (swarm, peer_id, identity) = create_swarm().await.map_err(|e| e.to_string())?;
 
         // Store peer ID
         let mut local_id = self.local_peer_id.lock().await;
         *local_id = Some(peer_id.clone());
         drop(local_id);
+        *self.identity.lock().await = Some(identity.clone());
 
         *running = true;
 
@@ -1120,6 +1666,8 @@ impl DhtService {
         // the swarm task itself.
         let (cmd_tx, cmd_rx) = mpsc::unbounded_channel();
         let cmd_tx_for_loop = cmd_tx.clone();
+        let cmd_tx_for_resume = cmd_tx.clone();
+        let cmd_tx_for_refresh = cmd_tx.clone();
         let mut cmd_sender = self.command_sender.lock().await;
         *cmd_sender = Some(cmd_tx);
         drop(cmd_sender);
@@ -1131,8 +1679,10 @@ impl DhtService {
         let shared_files_clone = self.shared_files.clone();
         let download_dir_clone = self.download_directory.clone();
         let active_downloads_clone = self.active_downloads.clone();
+        let resumed_downloads_clone = self.resumed_downloads.clone();
         let download_credentials_clone = self.download_credentials.clone();
         let bootstrap_gate = self.bootstrap_gate.clone();
+        let http_p2p_server = self.http_p2p_server.clone();
         let events_clone = events.clone();
 
         tokio::spawn(async move {
@@ -1147,12 +1697,22 @@ impl DhtService {
                 shared_files_clone,
                 download_dir_clone,
                 active_downloads_clone,
+                resumed_downloads_clone,
                 download_credentials_clone,
                 bootstrap_gate,
+                http_p2p_server,
             )
             .await;
         });
 
+        spawn_pending_download_resume(
+            cmd_tx_for_resume,
+            self.resumed_downloads.clone(),
+            events,
+            self.bootstrap_gate.clone(),
+        );
+        spawn_dht_record_refresh(cmd_tx_for_refresh, identity, self.is_running.clone());
+
         Ok(format!("DHT started with peer ID: {}", peer_id))
     }
 
@@ -1188,6 +1748,7 @@ impl DhtService {
         NetworkStats {
             connected_peers: peers.len(),
             total_peers: peers.len(),
+            bandwidth: crate::bandwidth::global().stats(),
         }
     }
 
@@ -1213,6 +1774,9 @@ impl DhtService {
                     .collect(),
                 shared_files: 0,
                 protocols: vec![],
+                nat_status: crate::nat::Reachability::Unknown,
+                public_address: None,
+                port_mappings: vec![],
             };
         }
 
@@ -1245,6 +1809,9 @@ impl DhtService {
             bootstrap_nodes: vec![],
             shared_files: shared.len(),
             protocols: vec![],
+            nat_status: crate::nat::Reachability::Unknown,
+            public_address: None,
+            port_mappings: vec![],
         }
     }
 
@@ -1257,8 +1824,12 @@ impl DhtService {
             .await
     }
 
-    pub async fn ping_peer_headless(&self, peer_id: String) -> Result<String, String> {
-        self.ping_peer_with_sink(peer_id, EventSink::noop()).await
+    pub async fn ping_peer_headless(
+        &self,
+        peer_id: String,
+        events: EventSink,
+    ) -> Result<String, String> {
+        self.ping_peer_with_sink(peer_id, events).await
     }
 
     async fn ping_peer_with_sink(
@@ -1278,29 +1849,19 @@ impl DhtService {
         }
     }
 
-    pub async fn send_file(
+    /// Send a ChiralDrop offer to a peer. The content must already be
+    /// registered as a shared file so the receiver can pull it in chunks.
+    pub async fn send_file_offer(
         &self,
         peer_id: String,
-        transfer_id: String,
-        file_name: String,
-        file_data: Vec<u8>,
-        price_wei: String,
-        sender_wallet: String,
-        file_hash: String,
-        file_size: u64,
+        offer: FileTransferRequest,
     ) -> Result<(), String> {
         let sender = self.command_sender.lock().await;
         if let Some(tx) = sender.as_ref() {
             let peer_id_parsed = PeerId::from_str(&peer_id).map_err(|e| e.to_string())?;
-            tx.send(SwarmCommand::SendFile {
+            tx.send(SwarmCommand::SendFileOffer {
                 peer_id: peer_id_parsed,
-                transfer_id,
-                file_name,
-                file_data,
-                price_wei,
-                sender_wallet,
-                file_hash,
-                file_size,
+                offer,
             })
             .map_err(|e| e.to_string())?;
             Ok(())
@@ -1309,10 +1870,166 @@ impl DhtService {
         }
     }
 
-    /// Store a value in the DHT
-    pub async fn put_dht_value(&self, key: String, value: String) -> Result<(), String> {
+    /// Register a file on disk for chunked serving and offer it to a peer.
+    ///
+    /// The file is hashed from disk in chunks and never loaded whole. It is
+    /// served only to the offered peer, and nothing is announced in the DHT;
+    /// a file that is already shared publicly stays public. The entry goes
+    /// away when the receiver closes the offer or it expires. Free offers
+    /// without a wallet are signed with a one-off key, since the signature
+    /// only binds the FileInfo to the payee.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn offer_file(
+        &self,
+        peer_id: String,
+        transfer_id: String,
+        file_path: PathBuf,
+        file_name: String,
+        price_wei: u128,
+        wallet_address: String,
+        private_key: String,
+    ) -> Result<FileTransferRequest, String> {
+        let receiver = PeerId::from_str(&peer_id).map_err(|e| e.to_string())?;
+        let (wallet_address, private_key) = if wallet_address.is_empty() || private_key.is_empty() {
+            if price_wei > 0 {
+                return Err("Wallet must be unlocked to offer a paid file".to_string());
+            }
+            ephemeral_signing_identity()?
+        } else {
+            (wallet_address, private_key)
+        };
+
+        let path_for_hash = file_path.clone();
+        let tree = tokio::task::spawn_blocking(move || {
+            crate::merkle::MerkleTree::from_file(&path_for_hash)
+        })
+        .await
+        .map_err(|e| format!("Hashing task failed: {}", e))??;
+        let file_hash = tree.root();
+        let file_size = tree.file_size();
+
+        let drop_offer = DropOffer {
+            peer_id: receiver,
+            offered_at: Instant::now(),
+        };
+        {
+            let mut shared = self.shared_files.lock().await;
+            discard_unshared_offers(expire_drop_offers(&mut shared, Instant::now()));
+            match shared.get_mut(&file_hash) {
+                Some(existing) if existing.price_wei != price_wei => {
+                    return Err(format!(
+                        "{} is already shared at a different price; offer it at that price or stop sharing it first",
+                        file_name
+                    ));
+                }
+                Some(existing) => {
+                    if !existing.drop_offers.is_empty() {
+                        existing.drop_offers.insert(transfer_id.clone(), drop_offer);
+                    }
+                }
+                None => {
+                    println!(
+                        "📤 Serving ChiralDrop offer {} ({} bytes) from {:?}",
+                        file_hash, file_size, file_path
+                    );
+                    // The tree was just built, so there is no background warmup.
+                    shared.insert(
+                        file_hash.clone(),
+                        SharedFileInfo {
+                            file_path: file_path.to_string_lossy().to_string(),
+                            file_name: file_name.clone(),
+                            file_size,
+                            merkle_tree: Some(Arc::new(tree)),
+                            price_wei,
+                            wallet_address: wallet_address.clone(),
+                            private_key,
+                            folder_access: HashMap::new(),
+                            drop_offers: HashMap::from([(transfer_id.clone(), drop_offer)]),
+                        },
+                    );
+                }
+            }
+        }
+
+        let offer = FileTransferRequest {
+            transfer_id,
+            file_name,
+            file_size,
+            file_hash,
+            price_wei: price_wei.to_string(),
+            sender_wallet: if price_wei > 0 {
+                wallet_address
+            } else {
+                String::new()
+            },
+            closed: None,
+        };
+        if let Err(e) = self.send_file_offer(peer_id, offer.clone()).await {
+            let mut shared = self.shared_files.lock().await;
+            if let Some(unshared) = close_drop_offer(&mut shared, &offer.transfer_id, receiver) {
+                discard_unshared_offers(vec![unshared]);
+            }
+            return Err(e);
+        }
+        Ok(offer)
+    }
+
+    /// Tell the sender of a ChiralDrop offer that we closed it, so it stops
+    /// serving the file.
+    pub async fn close_file_offer(
+        &self,
+        transfer: &crate::file_transfer::PendingTransfer,
+        status: crate::file_transfer::TransferStatus,
+    ) -> Result<(), String> {
+        let notice = FileTransferRequest::close_notice(
+            transfer.transfer_id.clone(),
+            transfer.file_hash.clone(),
+            status,
+        );
+        self.send_file_offer(transfer.peer_id.clone(), notice).await
+    }
+
+    /// Cancel a download started with `request_file`, deleting its partial
+    /// output. Returns whether anything was still in progress.
+    pub async fn cancel_download(&self, request_id: String) -> Result<bool, String> {
         let sender = self.command_sender.lock().await;
         if let Some(tx) = sender.as_ref() {
+            let (response_tx, response_rx) = tokio::sync::oneshot::channel();
+            tx.send(SwarmCommand::CancelDownload {
+                request_id,
+                response_tx,
+            })
+            .map_err(|e| e.to_string())?;
+            response_rx.await.map_err(|e| e.to_string())
+        } else {
+            Err("DHT not running".to_string())
+        }
+    }
+
+    /// Settle `payer`'s outstanding payment vouchers on chain and tell the
+    /// seeders. Without `force`, only debts that are large or old enough
+    /// are settled.
+    pub async fn settle_payment_vouchers(
+        &self,
+        payer: &str,
+        private_key: &str,
+        force: bool,
+    ) -> Result<Vec<crate::micropayments::IssuedSettlement>, String> {
+        let settled = crate::micropayments::settle(payer, private_key, force).await?;
+        if let Some(tx) = self.command_sender.lock().await.as_ref() {
+            notify_voucher_settlements(tx, payer);
+        }
+        Ok(settled)
+    }
+
+    /// Store a value in the DHT, sealed in a signed record envelope. Values
+    /// the record-type validators would reject are refused here rather than
+    /// being dropped silently by every peer.
+    pub async fn put_dht_value(&self, key: String, value: String) -> Result<(), String> {
+        let identity = self.identity.lock().await.clone();
+        let sender = self.command_sender.lock().await;
+        if let (Some(tx), Some(identity)) = (sender.as_ref(), identity) {
+            let value = seal_dht_value(&key, value, &identity)?;
             let (response_tx, response_rx) = tokio::sync::oneshot::channel();
             tx.send(SwarmCommand::PutDhtValue {
                 key,
@@ -1524,10 +2241,33 @@ async fn verify_payment_proof(
                 error: None,
             };
         }
-        PaymentClaimStatus::ClaimedDirectFile | PaymentClaimStatus::ClaimedOtherScope => {
+        PaymentClaimStatus::ClaimedDirectFile
+            if direct_payment_resumable(payment_tx, &scope, payer_address, &peer_id).await =>
+        {
+            // The same buyer on the same node is resuming an interrupted
+            // download of the file it already paid for.
             println!(
-                "❌ Payment tx {} already redeemed for a different download scope",
-                payment_tx
+                "✅ Reusing verified payment tx {} to resume {} for {}",
+                payment_tx, file_hash, peer_id
+            );
+            remember_authorized_chunk_access(
+                &authorized_chunks,
+                request_id.clone(),
+                peer_id,
+                scope.clone(),
+            )
+            .await;
+            return ChunkResponse::PaymentAck {
+                request_id,
+                file_hash: file_hash.to_string(),
+                accepted: true,
+                error: None,
+            };
+        }
+        PaymentClaimStatus::ClaimedDirectFile | PaymentClaimStatus::ClaimedOtherScope => {
+            println!(
+                "❌ Payment tx {} already redeemed for a different download scope",
+                payment_tx
             );
             return ChunkResponse::PaymentAck {
                 request_id,
@@ -1692,7 +2432,8 @@ async fn verify_payment_on_chain(
 // Spent-tx guard (Stage 3).
 //
 // Each successful payment tx is persisted under the scope it unlocked. Direct
-// file payments can only unlock one file delivery. Folder payments can unlock
+// file payments only unlock that file, and only for the payer and peer that
+// redeemed them (so an interrupted download can resume). Folder payments can unlock
 // every manifest member for that same folder, payer, and peer, but cannot be
 // replayed by another downloader that learns the public tx hash.
 //
@@ -1709,8 +2450,16 @@ static SPENT_TX_STORE: tokio::sync::OnceCell<Mutex<HashSet<String>>> =
 
 #[derive(Clone, Debug)]
 enum PaymentScope {
-    DirectFile { file_hash: String },
-    Folder { folder_hash: String },
+    DirectFile {
+        file_hash: String,
+    },
+    Folder {
+        folder_hash: String,
+    },
+    /// A transfer settling `payer`'s vouchers; it unlocks no chunks
+    Settlement {
+        payer: String,
+    },
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -1775,7 +2524,13 @@ fn payment_scope_key(
     let tx = tx_hash.to_lowercase();
     match scope {
         PaymentScope::DirectFile { file_hash } => {
-            format!("file:{}:{}", tx, file_hash.to_lowercase())
+            format!(
+                "file:{}:{}:{}:{}",
+                tx,
+                file_hash.to_lowercase(),
+                payer_address.trim().to_lowercase(),
+                peer_id
+            )
         }
         PaymentScope::Folder { folder_hash } => {
             format!(
@@ -1786,6 +2541,14 @@ fn payment_scope_key(
                 peer_id
             )
         }
+        PaymentScope::Settlement { payer } => {
+            format!(
+                "settlement:{}:{}:{}",
+                tx,
+                payer.trim().to_lowercase(),
+                peer_id
+            )
+        }
     }
 }
 
@@ -1795,6 +2558,7 @@ fn ledger_entry_uses_tx(entry: &str, tx_hash: &str) -> bool {
         || entry.starts_with(&format!("{}:", tx))
         || entry.starts_with(&format!("file:{}:", tx))
         || entry.starts_with(&format!("folder:{}:", tx))
+        || entry.starts_with(&format!("settlement:{}:", tx))
 }
 
 fn ledger_entry_matches_scope(
@@ -1808,10 +2572,12 @@ fn ledger_entry_matches_scope(
     match scope {
         PaymentScope::DirectFile { file_hash } => {
             let file_hash = file_hash.to_lowercase();
+            // Legacy entries predate the payer/peer suffix.
             entry == format!("file:{}:{}", tx, file_hash)
                 || entry == format!("{}:{}", tx, file_hash)
+                || entry.starts_with(&format!("file:{}:{}:", tx, file_hash))
         }
-        PaymentScope::Folder { .. } => {
+        PaymentScope::Folder { .. } | PaymentScope::Settlement { .. } => {
             entry == payment_scope_key(tx_hash, scope, payer_address, peer_id)
         }
     }
@@ -1831,6 +2597,9 @@ fn classify_payment_claim(
         return match scope {
             PaymentScope::DirectFile { .. } => PaymentClaimStatus::ClaimedDirectFile,
             PaymentScope::Folder { .. } => PaymentClaimStatus::ClaimedSameFolder,
+            // The voucher book credits each settlement once, so a repeat
+            // never gets this far.
+            PaymentScope::Settlement { .. } => PaymentClaimStatus::ClaimedOtherScope,
         };
     }
     if spent
@@ -1854,6 +2623,31 @@ async fn payment_claim_status(
     classify_payment_claim(&guard, tx_hash, scope, payer_address, peer_id)
 }
 
+/// True when this exact payer and peer already redeemed `tx_hash` for the
+/// same direct file. Ledger entries written before payer/peer were recorded
+/// never match, so those payments stay single-use.
+fn direct_payment_claimed_by(
+    spent: &HashSet<String>,
+    tx_hash: &str,
+    scope: &PaymentScope,
+    payer_address: &str,
+    peer_id: &PeerId,
+) -> bool {
+    matches!(scope, PaymentScope::DirectFile { .. })
+        && spent.contains(&payment_scope_key(tx_hash, scope, payer_address, peer_id))
+}
+
+async fn direct_payment_resumable(
+    tx_hash: &str,
+    scope: &PaymentScope,
+    payer_address: &str,
+    peer_id: &PeerId,
+) -> bool {
+    let store = spent_tx_store().await;
+    let guard = store.lock().await;
+    direct_payment_claimed_by(&guard, tx_hash, scope, payer_address, peer_id)
+}
+
 /// Records a verified payment claim. `Available` means this call inserted a
 /// new claim. A same-folder repeat is accepted because one folder payment
 /// unlocks all manifest members.
@@ -1922,13 +2716,37 @@ fn configured_p2p_port_from_env() -> Result<u16, String> {
     }
 }
 
-async fn create_swarm() -> Result<(Swarm<DhtBehaviour>, String), Box<dyn Error>> {
+/// TCP and QUIC listen addresses for both address families on `port`.
+fn p2p_listen_addrs(port: u16) -> Vec<Multiaddr> {
+    use libp2p::multiaddr::Protocol;
+    let mut addrs = Vec::new();
+    for ip in [
+        std::net::IpAddr::from(std::net::Ipv4Addr::UNSPECIFIED),
+        std::net::IpAddr::from(std::net::Ipv6Addr::UNSPECIFIED),
+    ] {
+        let base = Multiaddr::from(ip);
+        addrs.push(base.clone().with(Protocol::Tcp(port)));
+        addrs.push(base.with(Protocol::Udp(port)).with(Protocol::QuicV1));
+    }
+    addrs
+}
+
+async fn create_swarm(
+) -> Result<(Swarm<DhtBehaviour>, String, libp2p::identity::Keypair), Box<dyn Error>> {
     let local_key = load_or_generate_keypair();
     let local_peer_id = PeerId::from(local_key.public());
 
     println!("Local peer ID: {}", local_peer_id);
 
-    let kad_store = kad::store::MemoryStore::new(local_peer_id);
+    // CHIRAL_DHT_STORE=disk keeps records and provider entries across
+    // restarts (bootstrap/daemon nodes); the default stays in memory.
+    let store_mode = crate::kad_store::store_mode_from_env()
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+    let kad_store = crate::kad_store::build_store(
+        store_mode,
+        local_peer_id,
+        &crate::network::data_dir().join(crate::kad_store::DHT_STORE_DIR),
+    );
     // Use custom Kademlia protocol name to match v1 bootstrap nodes
     let mut kad_config = kad::Config::default();
     kad_config.set_protocol_names(vec![StreamProtocol::new("/chiral/kad/1.0.0")]);
@@ -1952,9 +2770,18 @@ async fn create_swarm() -> Result<(Swarm<DhtBehaviour>, String), Box<dyn Error>>
     kad_config.set_replication_interval(Some(Duration::from_secs(3 * 60)));
     kad_config.set_publication_interval(Some(Duration::from_secs(3 * 60)));
     kad_config.set_record_ttl(Some(Duration::from_secs(22 * 60)));
+    // Inbound records and provider announcements are handed to us instead
+    // of being stored blindly, so `dht_record` can drop forged or stale
+    // values before they reach the store.
+    kad_config.set_record_filtering(kad::StoreInserts::FilterBoth);
     let mut kad = kad::Behaviour::with_config(local_peer_id, kad_store, kad_config);
     kad.set_mode(Some(kad::Mode::Server));
 
+    // AutoNAT asks peers to dial us back to learn whether we're reachable.
+    // Connected peers are used as probe servers; bootstrap nodes are public,
+    // so they're registered up front.
+    let mut autonat = autonat::Behaviour::new(local_peer_id, autonat::Config::default());
+
     // Add bootstrap nodes to Kademlia routing table
     for addr_str in get_bootstrap_nodes() {
         if let Ok(addr) = addr_str.parse::<Multiaddr>() {
@@ -1962,7 +2789,8 @@ async fn create_swarm() -> Result<(Swarm<DhtBehaviour>, String), Box<dyn Error>>
             if let Some(peer_id) = extract_peer_id_from_multiaddr(&addr) {
                 // Remove the /p2p/<peer_id> suffix to get the transport address
                 let transport_addr = remove_peer_id_from_multiaddr(&addr);
-                kad.add_address(&peer_id, transport_addr);
+                kad.add_address(&peer_id, transport_addr.clone());
+                autonat.add_server(peer_id, Some(transport_addr));
                 println!("Added bootstrap node to Kademlia: {}", peer_id);
             }
         }
@@ -1990,7 +2818,7 @@ async fn create_swarm() -> Result<(Swarm<DhtBehaviour>, String), Box<dyn Error>>
 
     let file_transfer = cbor_codec::Behaviour::new(
         [(
-            StreamProtocol::new("/chiral/file-transfer/1.0.0"),
+            StreamProtocol::new("/chiral/file-transfer/3.0.0"),
             request_response::ProtocolSupport::Full,
         )],
         request_response::Config::default()
@@ -1999,11 +2827,10 @@ async fn create_swarm() -> Result<(Swarm<DhtBehaviour>, String), Box<dyn Error>>
 
     let file_request = cbor_codec::Behaviour::new(
         [(
-            StreamProtocol::new("/chiral/file-request/3.0.0"),
+            StreamProtocol::new(FILE_REQUEST_PROTOCOL),
             request_response::ProtocolSupport::Full,
         )],
-        request_response::Config::default()
-            .with_request_timeout(std::time::Duration::from_secs(12)),
+        request_response::Config::default().with_request_timeout(FILE_REQUEST_TIMEOUT),
     );
 
     let echo_protocol = request_response::cbor::Behaviour::new(
@@ -2014,6 +2841,13 @@ async fn create_swarm() -> Result<(Swarm<DhtBehaviour>, String), Box<dyn Error>>
         request_response::Config::default().with_request_timeout(std::time::Duration::from_secs(5)),
     );
 
+    // Relays fetch published shares and sites from us over this protocol;
+    // we never originate requests on it.
+    let http_p2p = crate::http_p2p::behaviour(request_response::ProtocolSupport::Inbound);
+
+    // Per-transport byte counters for `/metrics`; swapped in below so a
+    // restarted swarm replaces the previous one's series.
+    let mut transport_metrics = crate::metrics::Registry::default();
     let mut swarm = libp2p::SwarmBuilder::with_existing_identity(local_key.clone())
         .with_tokio()
         .with_tcp(
@@ -2021,12 +2855,15 @@ async fn create_swarm() -> Result<(Swarm<DhtBehaviour>, String), Box<dyn Error>>
             noise::Config::new,
             yamux::Config::default,
         )?
+        .with_quic()
         .with_relay_client(noise::Config::new, yamux::Config::default)?
+        .with_bandwidth_metrics(&mut transport_metrics)
         .with_behaviour(|_key, relay_client| {
             let dcutr = dcutr::Behaviour::new(local_peer_id);
             DhtBehaviour {
                 relay_client,
                 dcutr,
+                autonat,
                 kad,
                 mdns,
                 ping,
@@ -2035,20 +2872,24 @@ async fn create_swarm() -> Result<(Swarm<DhtBehaviour>, String), Box<dyn Error>>
                 file_transfer,
                 file_request,
                 echo_protocol,
+                http_p2p,
             }
         })?
         .with_swarm_config(|c| c.with_idle_connection_timeout(std::time::Duration::from_secs(3600)))
         .build();
+    crate::metrics::global().set_transport_registry(transport_metrics);
 
-    // Listen on all interfaces (dual-stack: IPv4 + IPv6).
-    // CHIRAL_P2P_PORT pins the libp2p TCP port — set this on containerized nodes
+    // Listen on all interfaces (dual-stack: IPv4 + IPv6) over TCP and QUIC.
+    // CHIRAL_P2P_PORT pins the libp2p port — set this on containerized nodes
     // (k3s/Docker) so the bound port matches the externally exposed NodePort.
     // Without it, the OS picks a random ephemeral port that won't be reachable
     // from outside the pod and will leave stale unreachable multiaddrs in the DHT.
+    // QUIC binds the same number on UDP, so expose both protocols.
     let p2p_port = configured_p2p_port_from_env()
         .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
-    swarm.listen_on(format!("/ip4/0.0.0.0/tcp/{}", p2p_port).parse()?)?;
-    swarm.listen_on(format!("/ip6/::/tcp/{}", p2p_port).parse()?)?;
+    for listen_addr in p2p_listen_addrs(p2p_port) {
+        swarm.listen_on(listen_addr)?;
+    }
 
     // Request relay reservations ONLY on nodes that run a relay server.
     // Using get_relay_nodes() (not get_bootstrap_nodes()) to avoid sending RESERVE to
@@ -2085,7 +2926,7 @@ async fn create_swarm() -> Result<(Swarm<DhtBehaviour>, String), Box<dyn Error>>
         }
     }
 
-    Ok((swarm, local_peer_id.to_string()))
+    Ok((swarm, local_peer_id.to_string(), local_key))
 }
 
 /// Path to the failed-peers persistence file.
@@ -2347,13 +3188,9 @@ fn chunk_retry_delay(attempt: u8, chunk_index: u32) -> std::time::Duration {
     std::time::Duration::from_millis(base_ms + jitter_ms)
 }
 
-async fn wait_for_chunk_retry_delay(attempt: u8, chunk_index: u32) {
-    tokio::time::sleep(chunk_retry_delay(attempt, chunk_index)).await;
-}
-
 fn take_next_backup_peer(download: &mut ActiveChunkedDownload) -> Option<PeerId> {
     while let Some(candidate) = download.backup_peers.pop() {
-        if candidate != download.peer_id {
+        if candidate != download.peer_id && !download.sources.contains_key(&candidate) {
             return Some(candidate);
         }
     }
@@ -2376,328 +3213,1194 @@ fn remove_active_download_for_cleanup(
 }
 
 fn add_backup_peer(download: &mut ActiveChunkedDownload, peer: PeerId) -> bool {
-    if download.peer_id == peer || download.backup_peers.iter().any(|p| *p == peer) {
+    if download.peer_id == peer
+        || download.sources.contains_key(&peer)
+        || download.backup_peers.contains(&peer)
+    {
         return false;
     }
     download.backup_peers.push(peer);
     true
 }
 
-fn looks_like_file_metadata(value: &serde_json::Value) -> bool {
-    value
-        .as_object()
-        .map(|obj| {
-            obj.get("hash").and_then(|v| v.as_str()).is_some()
-                && obj.get("fileName").and_then(|v| v.as_str()).is_some()
-                && obj.get("fileSize").and_then(|v| v.as_u64()).is_some()
-        })
-        .unwrap_or(false)
+/// Start pulling chunks from `peer` alongside the existing sources.
+/// Returns false when the peer is already serving this download.
+fn add_chunk_source(download: &mut ActiveChunkedDownload, peer: PeerId) -> bool {
+    if download.sources.contains_key(&peer) {
+        return false;
+    }
+    download.backup_peers.retain(|p| *p != peer);
+    download.sources.insert(peer, ChunkSource::default());
+    true
 }
 
-fn merge_seeder_record(existing: &mut serde_json::Value, candidate: &serde_json::Value) {
-    let Some(existing_obj) = existing.as_object_mut() else {
-        *existing = candidate.clone();
-        return;
-    };
-    let Some(candidate_obj) = candidate.as_object() else {
-        return;
-    };
+fn chunk_requests_in_flight(download: &ActiveChunkedDownload, peer: &PeerId) -> usize {
+    download
+        .in_flight
+        .values()
+        .filter(|chunk| chunk.peer_id == *peer)
+        .count()
+}
 
-    let existing_addr_count = existing_obj
-        .get("multiaddrs")
-        .and_then(|v| v.as_array())
-        .map(|a| a.len())
-        .unwrap_or(0);
-    let candidate_addr_count = candidate_obj
-        .get("multiaddrs")
-        .and_then(|v| v.as_array())
-        .map(|a| a.len())
-        .unwrap_or(0);
+/// Pick the source for the next chunk request: the one with the highest
+/// expected throughput per outstanding request, among sources with spare
+/// capacity. Unmeasured sources are assumed to be as fast as the best
+/// measured one so every new source gets probed. `exclude` skips the
+/// source a straggling request is stuck on.
+fn pick_chunk_source(download: &ActiveChunkedDownload, exclude: Option<PeerId>) -> Option<PeerId> {
+    let fallback_bps = download
+        .sources
+        .values()
+        .filter_map(|source| source.throughput_bps)
+        .fold(1.0, f64::max);
+    download
+        .sources
+        .iter()
+        .filter(|(peer, _)| Some(**peer) != exclude)
+        .filter_map(|(peer, source)| {
+            let in_flight = chunk_requests_in_flight(download, peer);
+            if in_flight >= CHUNK_MAX_IN_FLIGHT_PER_PEER {
+                return None;
+            }
+            let rate = source.throughput_bps.unwrap_or(fallback_bps);
+            Some((*peer, rate / (in_flight as f64 + 1.0)))
+        })
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(peer, _)| peer)
+}
 
-    let mut merged_addr_set = std::collections::HashSet::new();
-    let mut merged_addrs = Vec::new();
-
-    let existing_addrs = existing_obj
-        .get("multiaddrs")
-        .and_then(|v| v.as_array())
-        .cloned()
-        .unwrap_or_default();
-    let candidate_addrs = candidate_obj
-        .get("multiaddrs")
-        .and_then(|v| v.as_array())
-        .cloned()
-        .unwrap_or_default();
-
-    for addr in existing_addrs.iter().chain(candidate_addrs.iter()) {
-        let Some(addr_str) = addr.as_str() else {
+/// Assign missing chunks, lowest index first, to the best available
+/// sources until the window is full. The returned requests are recorded
+/// as in flight; the caller is responsible for actually sending them.
+fn schedule_chunk_requests(
+    download: &mut ActiveChunkedDownload,
+    now: std::time::Instant,
+) -> Vec<(u32, PeerId)> {
+    let mut assignments = Vec::new();
+    if !download.payment_confirmed {
+        return assignments;
+    }
+    for chunk_index in download.first_missing_chunk..download.total_chunks {
+        if download.in_flight.len() >= CHUNK_DOWNLOAD_WINDOW {
+            break;
+        }
+        let received = download
+            .received_chunks
+            .get(chunk_index as usize)
+            .copied()
+            .unwrap_or(true);
+        if received
+            || download.in_flight.contains_key(&chunk_index)
+            || download
+                .retry_after
+                .get(&chunk_index)
+                .is_some_and(|at| *at > now)
+        {
             continue;
+        }
+        // Paid with vouchers: a new chunk needs room under the newest
+        // accepted voucher.
+        if download.vouchers.as_ref().is_some_and(|v| {
+            !v.charged.contains(&chunk_index) && v.charged.len() >= v.acked as usize
+        }) {
+            break;
+        }
+        let Some(peer) = pick_chunk_source(download, None) else {
+            break;
         };
-        let trimmed = addr_str.trim();
-        if trimmed.is_empty() {
-            continue;
+        // Over the download limit: leave the rest for a later tick.
+        let len = crate::merkle::chunk_len(download.file_size, chunk_index).unwrap_or(CHUNK_SIZE);
+        if !crate::bandwidth::global().try_reserve_download(peer, len as u64) {
+            break;
         }
-        if merged_addr_set.insert(trimmed.to_string()) {
-            merged_addrs.push(serde_json::Value::String(trimmed.to_string()));
+        if let Some(vouchers) = download.vouchers.as_mut() {
+            vouchers.charged.insert(chunk_index);
         }
-    }
-    existing_obj.insert(
-        "multiaddrs".to_string(),
-        serde_json::Value::Array(merged_addrs),
-    );
-
-    let existing_price = existing_obj
-        .get("priceWei")
-        .and_then(|v| v.as_str())
-        .unwrap_or("");
-    let candidate_price = candidate_obj
-        .get("priceWei")
-        .and_then(|v| v.as_str())
-        .unwrap_or("");
-    if !candidate_price.is_empty()
-        && (existing_price.is_empty() || candidate_addr_count > existing_addr_count)
-    {
-        existing_obj.insert(
-            "priceWei".to_string(),
-            serde_json::Value::String(candidate_price.to_string()),
+        download.retry_after.remove(&chunk_index);
+        download.in_flight.insert(
+            chunk_index,
+            InFlightChunk {
+                peer_id: peer,
+                sent_at: now,
+            },
         );
+        assignments.push((chunk_index, peer));
     }
+    assignments
+}
 
-    let existing_wallet = existing_obj
-        .get("walletAddress")
-        .and_then(|v| v.as_str())
-        .unwrap_or("");
-    let candidate_wallet = candidate_obj
-        .get("walletAddress")
-        .and_then(|v| v.as_str())
-        .unwrap_or("");
-    if !candidate_wallet.is_empty()
-        && (existing_wallet.is_empty() || candidate_addr_count > existing_addr_count)
+/// How long a request to `source` may stay outstanding before it counts
+/// as a straggler.
+fn chunk_straggler_timeout(source: Option<&ChunkSource>) -> std::time::Duration {
+    let min = std::time::Duration::from_millis(CHUNK_STRAGGLER_MIN_MS);
+    match source
+        .and_then(|source| source.throughput_bps)
+        .filter(|bps| *bps > 0.0)
     {
-        existing_obj.insert(
-            "walletAddress".to_string(),
-            serde_json::Value::String(candidate_wallet.to_string()),
-        );
+        Some(bps) => min.max(std::time::Duration::from_secs_f64(
+            CHUNK_SIZE as f64 / bps * CHUNK_STRAGGLER_FACTOR,
+        )),
+        None => min,
     }
 }
 
-fn merge_file_metadata_records(records: &[String]) -> Option<String> {
-    let mut parsed_records: Vec<serde_json::Value> = Vec::new();
-    let mut target_hash: Option<String> = None;
+fn record_chunk_throughput(source: &mut ChunkSource, bytes: usize, elapsed: std::time::Duration) {
+    let sample = bytes as f64 / elapsed.as_secs_f64().max(0.001);
+    source.throughput_bps = Some(match source.throughput_bps {
+        Some(previous) => previous + CHUNK_THROUGHPUT_EWMA_ALPHA * (sample - previous),
+        None => sample,
+    });
+}
 
-    for raw in records {
-        let Ok(value) = serde_json::from_str::<serde_json::Value>(raw) else {
+/// Move requests that have been outstanding for too long onto another
+/// source with spare capacity. The slow source's throughput estimate is
+/// lowered so it stops winning new chunks; if its response still lands
+/// first it is accepted, and whichever copy arrives second is dropped.
+/// The second request counts against `bandwidth` like any other; a chunk
+/// the new source has no room for stays with the slow one until a later
+/// tick.
+fn reassign_straggler_chunks(
+    download: &mut ActiveChunkedDownload,
+    bandwidth: &crate::bandwidth::BandwidthManager,
+    now: std::time::Instant,
+) -> Vec<(u32, PeerId)> {
+    if download.sources.len() < 2 {
+        return Vec::new();
+    }
+    let mut stragglers: Vec<(u32, PeerId, std::time::Duration)> =
+        download
+            .in_flight
+            .iter()
+            .filter_map(|(chunk_index, chunk)| {
+                let elapsed = now.saturating_duration_since(chunk.sent_at);
+                (elapsed >= chunk_straggler_timeout(download.sources.get(&chunk.peer_id)))
+                    .then_some((*chunk_index, chunk.peer_id, elapsed))
+            })
+            .collect();
+    stragglers.sort_by_key(|(chunk_index, _, _)| *chunk_index);
+
+    let mut reassigned = Vec::new();
+    for (chunk_index, slow_peer, elapsed) in stragglers {
+        let Some(peer) = pick_chunk_source(download, Some(slow_peer)) else {
             continue;
         };
-        if !looks_like_file_metadata(&value) {
+        let len = crate::merkle::chunk_len(download.file_size, chunk_index).unwrap_or(CHUNK_SIZE);
+        if !bandwidth.try_reserve_download(peer, len as u64) {
             continue;
         }
-        let hash = value
-            .get("hash")
-            .and_then(|v| v.as_str())
-            .map(|v| v.trim())
-            .filter(|v| !v.is_empty())?
-            .to_string();
+        // Only once the chunk moves, or the lowered estimate would stop
+        // it counting as a straggler on the next tick.
+        if let Some(source) = download.sources.get_mut(&slow_peer) {
+            record_chunk_throughput(source, CHUNK_SIZE, elapsed);
+        }
+        download.in_flight.insert(
+            chunk_index,
+            InFlightChunk {
+                peer_id: peer,
+                sent_at: now,
+            },
+        );
+        reassigned.push((chunk_index, peer));
+    }
+    reassigned
+}
 
-        if let Some(existing_hash) = target_hash.as_ref() {
-            if existing_hash != &hash {
-                continue;
+/// Record a verified, written chunk and update the sender's throughput.
+fn mark_chunk_received(
+    download: &mut ActiveChunkedDownload,
+    chunk_index: u32,
+    peer: PeerId,
+    bytes: usize,
+    now: std::time::Instant,
+) {
+    if let Some(chunk) = download.in_flight.remove(&chunk_index) {
+        if chunk.peer_id == peer {
+            if let Some(source) = download.sources.get_mut(&peer) {
+                record_chunk_throughput(
+                    source,
+                    bytes,
+                    now.saturating_duration_since(chunk.sent_at),
+                );
             }
-        } else {
-            target_hash = Some(hash);
         }
-
-        parsed_records.push(value);
     }
-
-    if parsed_records.is_empty() {
-        return None;
+    if let Some(source) = download.sources.get_mut(&peer) {
+        source.failures = 0;
     }
+    let idx = chunk_index as usize;
+    if let Some(received) = download.received_chunks.get_mut(idx) {
+        *received = true;
+    }
+    if let Some(count) = download.retry_counts.get_mut(idx) {
+        *count = 0;
+    }
+    download.retry_after.remove(&chunk_index);
+    download.bytes_written += bytes as u64;
+    while download
+        .received_chunks
+        .get(download.first_missing_chunk as usize)
+        .copied()
+        .unwrap_or(false)
+    {
+        download.first_missing_chunk += 1;
+    }
+}
 
-    let mut base = parsed_records
-        .iter()
-        .max_by_key(|record| {
-            record
-                .get("createdAt")
-                .and_then(|v| v.as_u64())
-                .unwrap_or(0)
-        })
-        .cloned()
-        .unwrap_or_else(|| parsed_records[0].clone());
-
-    let mut seeders_by_peer: std::collections::HashMap<String, serde_json::Value> =
-        std::collections::HashMap::new();
-
-    for record in &parsed_records {
-        if let Some(seeders) = record.get("seeders").and_then(|v| v.as_array()) {
-            for seeder in seeders {
-                let Some(peer_id) = seeder
-                    .get("peerId")
-                    .and_then(|v| v.as_str())
-                    .map(|v| v.trim())
-                    .filter(|v| !v.is_empty())
-                else {
-                    continue;
-                };
-
-                let candidate = seeder.clone();
-                seeders_by_peer
-                    .entry(peer_id.to_string())
-                    .and_modify(|existing| merge_seeder_record(existing, &candidate))
-                    .or_insert(candidate);
-            }
-        }
+fn chunked_download_complete(download: &ActiveChunkedDownload) -> bool {
+    download.first_missing_chunk >= download.total_chunks
+}
 
-        // Legacy single-seeder metadata support.
-        if let Some(peer_id) = record
-            .get("peerId")
-            .and_then(|v| v.as_str())
-            .map(|v| v.trim())
-            .filter(|v| !v.is_empty())
-        {
-            let legacy = serde_json::json!({
-                "peerId": peer_id,
-                "priceWei": record.get("priceWei").and_then(|v| v.as_str()).unwrap_or(""),
-                "walletAddress": record.get("walletAddress").and_then(|v| v.as_str()).unwrap_or(""),
-                "multiaddrs": [],
-            });
-            seeders_by_peer
-                .entry(peer_id.to_string())
-                .and_modify(|existing| merge_seeder_record(existing, &legacy))
-                .or_insert(legacy);
-        }
-    }
+/// Check a chunk response's inclusion proof against the download's Merkle
+/// root. Returns the chunk bytes and leaf hash, or the reason the response
+/// must be retried.
+fn verify_chunk_payload(
+    download: &ActiveChunkedDownload,
+    chunk_index: u32,
+    chunk_data: Option<Vec<u8>>,
+    proof: &[String],
+    error: Option<String>,
+) -> Result<(Vec<u8>, [u8; 32]), String> {
+    if let Some(err) = error {
+        return Err(err);
+    }
+    let data = chunk_data.ok_or_else(|| "chunk has no data and no error".to_string())?;
+    let leaf = crate::merkle::verify_chunk(
+        &download.merkle_root,
+        download.file_size,
+        chunk_index,
+        &data,
+        proof,
+    )?;
+    Ok((data, leaf))
+}
 
-    let mut merged_seeders: Vec<serde_json::Value> = seeders_by_peer.into_values().collect();
-    merged_seeders.sort_by(|a, b| {
-        let a_peer = a.get("peerId").and_then(|v| v.as_str()).unwrap_or("");
-        let b_peer = b.get("peerId").and_then(|v| v.as_str()).unwrap_or("");
-        a_peer.cmp(b_peer)
-    });
+/// Whether another seeder's FileInfo describes exactly the manifest this
+/// download is already verifying chunks against.
+fn file_info_matches_download(
+    download: &ActiveChunkedDownload,
+    file_size: u64,
+    total_chunks: u32,
+    merkle_root: &str,
+) -> bool {
+    download.file_size == file_size
+        && download.total_chunks == total_chunks
+        && download.merkle_root.eq_ignore_ascii_case(merkle_root)
+}
 
-    if let Some(base_obj) = base.as_object_mut() {
-        base_obj.insert(
-            "seeders".to_string(),
-            serde_json::Value::Array(merged_seeders.clone()),
-        );
+/// A FileInfo's chunk count must follow from its size; the Merkle tree
+/// shape (and so every proof) depends on it.
+fn file_info_manifest_consistent(file_size: u64, total_chunks: u32, merkle_root: &str) -> bool {
+    total_chunks == crate::merkle::chunk_count(file_size) && !merkle_root.is_empty()
+}
 
-        if let Some(primary) = merged_seeders.iter().find_map(|s| {
-            s.get("peerId")
-                .and_then(|v| v.as_str())
-                .map(|v| v.trim())
-                .filter(|v| !v.is_empty())
-                .map(|v| v.to_string())
-        }) {
-            base_obj.insert(
-                "peerId".to_string(),
-                serde_json::Value::String(primary.clone()),
-            );
+/// Outcome of a failed chunk request
+#[derive(Debug, PartialEq, Eq)]
+enum ChunkFailureOutcome {
+    /// The chunk goes back into the window once its backoff expires
+    Retry { attempt: u8 },
+    /// `from` exhausted its retries and was dropped; `to` carries on
+    /// (another active source, or a promoted backup seeder)
+    Failover { from: PeerId, to: PeerId },
+    /// No source is left to fetch from
+    Exhausted,
+}
 
-            if let Some(primary_seeder) = merged_seeders.iter().find(|s| {
-                s.get("peerId").and_then(|v| v.as_str()).map(|v| v.trim()) == Some(primary.as_str())
-            }) {
-                if let Some(price) = primary_seeder.get("priceWei").and_then(|v| v.as_str()) {
-                    base_obj.insert(
-                        "priceWei".to_string(),
-                        serde_json::Value::String(price.to_string()),
-                    );
-                }
-                if let Some(wallet) = primary_seeder.get("walletAddress").and_then(|v| v.as_str()) {
-                    base_obj.insert(
-                        "walletAddress".to_string(),
-                        serde_json::Value::String(wallet.to_string()),
-                    );
-                }
-            }
-        } else {
-            base_obj.insert(
-                "peerId".to_string(),
-                serde_json::Value::String(String::new()),
-            );
-        }
+/// Book-keep a chunk `peer` turned away because all its upload slots were
+/// taken. The chunk goes back into the window after a backoff; neither the
+/// chunk's retry count nor the source's failure count moves, so a busy
+/// seeder is never dropped for being busy.
+fn record_chunk_busy(
+    download: &mut ActiveChunkedDownload,
+    chunk_index: u32,
+    peer: PeerId,
+    now: std::time::Instant,
+) {
+    if download
+        .in_flight
+        .get(&chunk_index)
+        .is_some_and(|chunk| chunk.peer_id == peer)
+    {
+        download.in_flight.remove(&chunk_index);
     }
-
-    serde_json::to_string(&base).ok()
+    let jitter_ms = (chunk_index as u64 * 31) % 250;
+    download.retry_after.insert(
+        chunk_index,
+        now + std::time::Duration::from_millis(CHUNK_BUSY_RETRY_MS + jitter_ms),
+    );
 }
 
-fn merge_host_registry_records(records: &[String]) -> Option<String> {
-    let mut merged_by_peer: HashMap<String, HostRegistryMergeEntry> = HashMap::new();
-    let mut saw_registry_record = false;
+/// Book-keep a failed chunk request from `peer`. The chunk is retried
+/// with backoff until the source has failed `MAX_CHUNK_RETRIES` times in
+/// a row; then the source is dropped and its outstanding chunks are
+/// redistributed over the remaining sources.
+fn record_chunk_failure(
+    download: &mut ActiveChunkedDownload,
+    chunk_index: u32,
+    peer: PeerId,
+    now: std::time::Instant,
+) -> ChunkFailureOutcome {
+    if download
+        .in_flight
+        .get(&chunk_index)
+        .is_some_and(|chunk| chunk.peer_id == peer)
+    {
+        download.in_flight.remove(&chunk_index);
+    }
+    let idx = chunk_index as usize;
+    let attempt = match download.retry_counts.get_mut(idx) {
+        Some(count) => {
+            *count = count.saturating_add(1);
+            *count
+        }
+        None => MAX_CHUNK_RETRIES,
+    };
+    let peer_failures = download
+        .sources
+        .get_mut(&peer)
+        .map(|source| {
+            source.failures = source.failures.saturating_add(1);
+            source.failures
+        })
+        .unwrap_or(0);
+    if peer_failures <= MAX_CHUNK_RETRIES {
+        download
+            .retry_after
+            .insert(chunk_index, now + chunk_retry_delay(attempt, chunk_index));
+        return ChunkFailureOutcome::Retry { attempt };
+    }
 
-    for raw in records {
-        let Ok(entries) = serde_json::from_str::<Vec<HostRegistryMergeEntry>>(raw) else {
-            continue;
-        };
-        saw_registry_record = true;
+    download.sources.remove(&peer);
+    download.in_flight.retain(|_, chunk| chunk.peer_id != peer);
+    if let Some(count) = download.retry_counts.get_mut(idx) {
+        *count = 0;
+    }
+    download.retry_after.remove(&chunk_index);
 
-        for mut entry in entries {
-            let peer_id = entry.peer_id.trim().to_string();
-            if peer_id.is_empty() || PeerId::from_str(&peer_id).is_err() {
-                continue;
+    if let Some(to) =
+        pick_chunk_source(download, None).or_else(|| download.sources.keys().next().copied())
+    {
+        return ChunkFailureOutcome::Failover { from: peer, to };
+    }
+    match take_next_backup_peer(download) {
+        Some(next_peer) => {
+            download.peer_id = next_peer;
+            download.sources.insert(next_peer, ChunkSource::default());
+            ChunkFailureOutcome::Failover {
+                from: peer,
+                to: next_peer,
             }
-            entry.peer_id = peer_id.clone();
-
-            merged_by_peer
-                .entry(peer_id)
-                .and_modify(|existing| {
-                    if entry.updated_at > existing.updated_at {
-                        *existing = entry.clone();
-                    } else if existing.wallet_address.is_empty() && !entry.wallet_address.is_empty()
-                    {
-                        existing.wallet_address = entry.wallet_address.clone();
-                    }
-                })
-                .or_insert(entry);
         }
+        None => ChunkFailureOutcome::Exhausted,
     }
+}
 
-    if !saw_registry_record || merged_by_peer.is_empty() {
-        return None;
+/// Remember how each source served a finished download, so later seeder
+/// rankings favour peers that performed well.
+fn record_source_sessions(download: &ActiveChunkedDownload) {
+    let now = crate::drive_storage::now_secs().unwrap_or(0);
+    {
+        let mut book = crate::seeder_selection::performance().lock();
+        for (peer, source) in &download.sources {
+            book.record_session(&peer.to_string(), source.throughput_bps, true, now);
+        }
     }
+    crate::seeder_selection::persist_in_background();
+}
 
-    let mut merged_entries: Vec<HostRegistryMergeEntry> = merged_by_peer.into_values().collect();
-    merged_entries.sort_by(|a, b| {
-        b.updated_at
-            .cmp(&a.updated_at)
-            .then_with(|| a.peer_id.cmp(&b.peer_id))
+/// Remember that `peer` was dropped from a download after repeated failures.
+fn record_failed_source(peer: &PeerId) {
+    let now = crate::drive_storage::now_secs().unwrap_or(0);
+    crate::seeder_selection::performance()
+        .lock()
+        .record_session(&peer.to_string(), None, false, now);
+    crate::seeder_selection::persist_in_background();
+}
+
+/// Order backup seeders so the one that performed best in earlier
+/// sessions is promoted first (`take_next_backup_peer` pops from the end).
+fn rank_backup_peers(
+    download: &mut ActiveChunkedDownload,
+    history: impl Fn(&PeerId) -> Option<crate::seeder_selection::PeerPerformance>,
+) {
+    let perfs: HashMap<PeerId, Option<crate::seeder_selection::PeerPerformance>> = download
+        .backup_peers
+        .iter()
+        .map(|peer| (*peer, history(peer)))
+        .collect();
+    let best_throughput = perfs
+        .values()
+        .filter_map(|perf| perf.as_ref().and_then(|p| p.throughput_bps))
+        .reduce(f64::max);
+    download.backup_peers.sort_by(|a, b| {
+        let score = |peer: &PeerId| {
+            crate::seeder_selection::performance_score(
+                perfs.get(peer).and_then(|p| p.as_ref()),
+                best_throughput,
+            )
+        };
+        score(a).total_cmp(&score(b))
     });
+}
 
-    serde_json::to_string(&merged_entries).ok()
+/// Send the chunk requests chosen by the scheduler and remember which
+/// outbound request carries which chunk.
+fn send_chunk_requests(
+    swarm: &mut Swarm<DhtBehaviour>,
+    outbound_request_map: &mut HashMap<request_response::OutboundRequestId, String>,
+    download: &mut ActiveChunkedDownload,
+    assignments: Vec<(u32, PeerId)>,
+) {
+    for (chunk_index, peer) in assignments {
+        let request = ChunkRequest::Chunk {
+            request_id: download.request_id.clone(),
+            file_hash: download.file_hash.clone(),
+            chunk_index,
+        };
+        let req_id = swarm
+            .behaviour_mut()
+            .file_request
+            .send_request(&peer, request);
+        outbound_request_map.insert(req_id, download.request_id.clone());
+        download.outbound_chunks.insert(req_id, (chunk_index, peer));
+    }
 }
 
-fn select_best_get_record_value(results: Vec<String>) -> Option<String> {
-    if results.is_empty() {
+/// How many chunks the next voucher should cover, if one is due: when no
+/// voucher is awaiting an answer or a settlement, and at most half an
+/// interval of the accepted coverage is left unused.
+fn voucher_top_up_due(download: &ActiveChunkedDownload) -> Option<u32> {
+    let vouchers = download.vouchers.as_ref()?;
+    if vouchers.pending.is_some() || vouchers.acked >= download.total_chunks {
         return None;
     }
-
-    // For file metadata keys, merge all observed copies to avoid stale seeder
-    // addresses after restart/republish races.
-    if let Some(merged) = merge_file_metadata_records(&results) {
-        return Some(merged);
+    if vouchers
+        .awaiting_settlement
+        .is_some_and(|since| since.elapsed() < Duration::from_secs(VOUCHER_CREDIT_RETRY_SECS))
+    {
+        return None;
     }
-
-    // For host registry values, merge per-peer entries so concurrent publishers
-    // do not clobber each other when records are fetched from multiple peers.
-    if let Some(merged) = merge_host_registry_records(&results) {
-        return Some(merged);
+    let unused = (vouchers.acked as usize).saturating_sub(vouchers.charged.len());
+    if unused > (vouchers.interval / 2) as usize {
+        return None;
     }
+    let charged = vouchers.charged.len().min(u32::MAX as usize) as u32;
+    Some(
+        vouchers
+            .acked
+            .max(charged)
+            .saturating_add(vouchers.interval)
+            .min(download.total_chunks),
+    )
+}
 
-    // Generic fallback for non-file records.
-    results.into_iter().max_by_key(|record| record.len())
+/// Sign and send the next voucher when one is due. The voucher is saved in
+/// the issued book before it leaves, so the debt survives a restart.
+fn send_voucher_top_up(
+    swarm: &mut Swarm<DhtBehaviour>,
+    outbound_request_map: &mut HashMap<request_response::OutboundRequestId, String>,
+    download: &mut ActiveChunkedDownload,
+) -> Result<(), String> {
+    let Some(chunks) = voucher_top_up_due(download) else {
+        return Ok(());
+    };
+    let Some(vouchers) = download.vouchers.as_mut() else {
+        return Ok(());
+    };
+    let now = crate::drive_storage::now_secs().unwrap_or(0);
+    let voucher = {
+        let mut book = crate::micropayments::issued().lock();
+        let voucher = book.issue(
+            &vouchers.terms,
+            &vouchers.payee_peer.to_string(),
+            chunks,
+            &vouchers.private_key,
+            now,
+        )?;
+        book.persist()?;
+        voucher
+    };
+    println!(
+        "🧾 Voucher for {} of {} chunks ({} wei) on request {}",
+        voucher.chunks_covered, download.total_chunks, voucher.cumulative_wei, download.request_id
+    );
+    let request = ChunkRequest::PaymentVoucher {
+        request_id: download.request_id.clone(),
+        file_hash: download.file_hash.clone(),
+        voucher,
+    };
+    let req_id = swarm
+        .behaviour_mut()
+        .file_request
+        .send_request(&vouchers.payee_peer, request);
+    outbound_request_map.insert(req_id, download.request_id.clone());
+    vouchers.pending = Some(req_id);
+    Ok(())
 }
 
-fn build_relay_circuit_dial_addrs(target_peer_id: &PeerId) -> Vec<Multiaddr> {
-    let mut dial_addrs: Vec<Multiaddr> = Vec::new();
-    let mut seen_addrs: HashSet<String> = HashSet::new();
-    let mut relay_peer_ids_tried: HashSet<PeerId> = HashSet::new();
+/// Settle `payer`'s due vouchers (every non-zero debt with `force`) in the
+/// background, then tell the seeders.
+fn spawn_voucher_settlement(
+    cmd_tx: &mpsc::UnboundedSender<SwarmCommand>,
+    payer: String,
+    private_key: String,
+    force: bool,
+) {
+    let cmd_tx = cmd_tx.clone();
+    tokio::spawn(async move {
+        if let Err(e) = crate::micropayments::settle(&payer, &private_key, force).await {
+            println!("❌ Voucher settlement failed: {}", e);
+        }
+        notify_voucher_settlements(&cmd_tx, &payer);
+    });
+}
 
-    for relay_addr_str in get_relay_nodes() {
-        if let Ok(relay_addr) = relay_addr_str.parse::<Multiaddr>() {
-            if let Some(relay_pid) = extract_peer_id_from_multiaddr(&relay_addr) {
-                if !relay_peer_ids_tried.insert(relay_pid) {
-                    continue;
-                }
-            }
-            let circuit_addr = relay_addr
+/// Send every settlement the seeders have not acknowledged yet.
+fn notify_voucher_settlements(cmd_tx: &mpsc::UnboundedSender<SwarmCommand>, payer: &str) {
+    let unnotified = crate::micropayments::issued().lock().unnotified(payer);
+    for settlement in unnotified {
+        let Ok(peer_id) = PeerId::from_str(&settlement.payee_peer) else {
+            continue;
+        };
+        let _ = cmd_tx.send(SwarmCommand::NotifyVoucherSettlement {
+            peer_id,
+            settlement,
+        });
+    }
+}
+
+/// Check a settlement notice on chain and credit it to the payer's
+/// vouchers. Like `verify_payment_proof`, this runs off the reactor and
+/// claims the tx in the spent ledger, so it can't also unlock a download.
+/// The tx must name `local_peer` in its input: other nodes of the same
+/// wallet keep their own voucher books and are settled separately.
+async fn verify_voucher_settlement(
+    tx_hash: &str,
+    payer_address: &str,
+    payee_address: &str,
+    amount_wei: &str,
+    peer_id: PeerId,
+    local_peer: PeerId,
+) -> ChunkResponse {
+    let reject = |error: String| ChunkResponse::SettlementAck {
+        tx_hash: tx_hash.to_string(),
+        accepted: false,
+        error: Some(error),
+    };
+    let amount = match parse_protocol_price_wei(amount_wei, "VoucherSettlement.amount_wei") {
+        Ok(amount) if amount > 0 => amount,
+        Ok(_) => return reject("Settlement amount must be positive".to_string()),
+        Err(e) => return reject(e),
+    };
+    {
+        let book = crate::micropayments::received().lock();
+        if book.has_settlement(tx_hash) {
+            return ChunkResponse::SettlementAck {
+                tx_hash: tx_hash.to_string(),
+                accepted: true,
+                error: None,
+            };
+        }
+        if !book.has_vouchers_from(payer_address, payee_address) {
+            return reject("No vouchers from this payer to settle".to_string());
+        }
+    }
+    let scope = PaymentScope::Settlement {
+        payer: payer_address.to_string(),
+    };
+    if payment_claim_status(tx_hash, &scope, payer_address, &peer_id).await
+        != PaymentClaimStatus::Available
+    {
+        return reject("Settlement tx already redeemed".to_string());
+    }
+    match crate::wallet::wait_for_tx_mined(tx_hash).await {
+        Ok(true) => {}
+        Ok(false) => {
+            return reject("Settlement not yet confirmed on-chain (retryable)".to_string());
+        }
+        Err(e) => return reject(format!("Settlement verification error: {}", e)),
+    }
+    let (seller_wei, _) = crate::speed_tiers::split_payment(amount);
+    match verify_payment_on_chain(tx_hash, payer_address, payee_address, seller_wei).await {
+        Ok(true) => {}
+        Ok(false) => {
+            return reject(
+                "Settlement verification failed: insufficient amount or wrong recipient"
+                    .to_string(),
+            );
+        }
+        Err(e) => return reject(format!("Settlement verification error: {}", e)),
+    }
+    match crate::wallet::tx_input(tx_hash).await {
+        Ok(input) => {
+            if let Err(e) =
+                crate::micropayments::check_settlement_memo(&input, &local_peer.to_string())
+            {
+                return reject(e);
+            }
+        }
+        Err(e) => return reject(format!("Settlement verification error: {}", e)),
+    }
+    match claim_payment_tx(tx_hash, &scope, payer_address, &peer_id).await {
+        Ok(PaymentClaimStatus::Available) => {}
+        Ok(_) => return reject("Settlement tx already redeemed".to_string()),
+        Err(e) => return reject(format!("Settlement could not be recorded: {}", e)),
+    }
+    crate::micropayments::received().lock().apply_settlement(
+        crate::micropayments::ReceivedSettlement {
+            tx_hash: tx_hash.to_string(),
+            payer: payer_address.to_lowercase(),
+            payee: payee_address.to_lowercase(),
+            amount_wei: amount.to_string(),
+            settled_at: crate::drive_storage::now_secs().unwrap_or(0),
+        },
+    );
+    if let Err(e) = crate::micropayments::persist_received().await {
+        println!("⚠️ Failed to save voucher settlement {}: {}", tx_hash, e);
+    }
+    println!(
+        "✅ Voucher settlement {} credited: {} wei from {}",
+        tx_hash, amount, payer_address
+    );
+    ChunkResponse::SettlementAck {
+        tx_hash: tx_hash.to_string(),
+        accepted: true,
+        error: None,
+    }
+}
+
+/// Reassign stragglers, then top the request window back up.
+fn pump_chunk_download(
+    swarm: &mut Swarm<DhtBehaviour>,
+    outbound_request_map: &mut HashMap<request_response::OutboundRequestId, String>,
+    download: &mut ActiveChunkedDownload,
+    now: std::time::Instant,
+) {
+    if let Err(e) = send_voucher_top_up(swarm, outbound_request_map, download) {
+        println!(
+            "❌ Could not sign payment voucher for {}: {}",
+            download.request_id, e
+        );
+    }
+    let mut assignments = reassign_straggler_chunks(download, crate::bandwidth::global(), now);
+    if !assignments.is_empty() {
+        println!(
+            "🐢 Reassigned {} straggling chunk(s) for request {}",
+            assignments.len(),
+            download.request_id
+        );
+    }
+    assignments.extend(schedule_chunk_requests(download, now));
+    send_chunk_requests(swarm, outbound_request_map, download, assignments);
+}
+
+fn emit_chunk_source_failover(
+    events: &EventSink,
+    download: &ActiveChunkedDownload,
+    from: PeerId,
+    to: PeerId,
+    chunk_index: u32,
+    reason: &str,
+) {
+    println!(
+        "🔁 Dropping source {} for request {} at chunk {} ({}); continuing with {}",
+        from, download.request_id, chunk_index, reason, to
+    );
+    events.emit(
+        "download-peer-failover",
+        serde_json::json!({
+            "requestId": download.request_id,
+            "fileHash": download.file_hash,
+            "fromPeer": from.to_string(),
+            "toPeer": to.to_string(),
+            "chunkIndex": chunk_index,
+            "activeSources": download.sources.len(),
+        }),
+    );
+}
+
+/// Ask the other providers of a free file to join `request_id` as chunk
+/// sources. Their FileInfo responses are validated like the first one
+/// before any chunk is requested from them.
+fn discover_additional_chunk_sources(
+    cmd_tx: &mpsc::UnboundedSender<SwarmCommand>,
+    request_id: String,
+    file_hash: String,
+    known_peers: Vec<PeerId>,
+) {
+    let cmd_tx = cmd_tx.clone();
+    tokio::spawn(async move {
+        let (response_tx, response_rx) = tokio::sync::oneshot::channel();
+        if cmd_tx
+            .send(SwarmCommand::GetProviders {
+                file_hash: file_hash.clone(),
+                response_tx,
+            })
+            .is_err()
+        {
+            return;
+        }
+        let providers = match response_rx.await {
+            Ok(Ok(providers)) => providers,
+            _ => return,
+        };
+        let extra: Vec<PeerId> = providers
+            .iter()
+            .filter_map(|p| PeerId::from_str(p).ok())
+            .filter(|p| !known_peers.contains(p))
+            .take(CHUNK_MAX_EXTRA_SOURCES)
+            .collect();
+        if extra.is_empty() {
+            return;
+        }
+        println!(
+            "🧭 Asking {} more provider(s) to serve request {}",
+            extra.len(),
+            request_id
+        );
+        for peer_id in extra {
+            let (response_tx, _response_rx) = tokio::sync::oneshot::channel();
+            let _ = cmd_tx.send(SwarmCommand::RequestFileInfo {
+                peer_id,
+                request_id: request_id.clone(),
+                file_hash: file_hash.clone(),
+                folder_hash: None,
+                multiaddrs: Vec::new(),
+                response_tx,
+            });
+        }
+    });
+}
+
+/// Tell the sender of the ChiralDrop transfer pulled by `request_id`, if it
+/// is one, that every chunk arrived so it can stop serving the file.
+async fn notify_drop_completed(
+    swarm: &mut Swarm<DhtBehaviour>,
+    file_transfer_service: &Option<Arc<Mutex<crate::file_transfer::FileTransferService>>>,
+    request_id: &str,
+) {
+    let Some(fts) = file_transfer_service else {
+        return;
+    };
+    let Some(transfer) = fts
+        .lock()
+        .await
+        .complete_transfer_download(request_id)
+        .await
+    else {
+        return;
+    };
+    let Ok(sender) = PeerId::from_str(&transfer.peer_id) else {
+        return;
+    };
+    let notice = FileTransferRequest::close_notice(
+        transfer.transfer_id,
+        transfer.file_hash,
+        crate::file_transfer::TransferStatus::Completed,
+    );
+    swarm
+        .behaviour_mut()
+        .file_transfer
+        .send_request(&sender, notice);
+}
+
+/// Delete a failed download's partial file together with its resume state.
+fn discard_partial_download(download: &ActiveChunkedDownload) {
+    let _ = std::fs::remove_file(&download.output_path);
+    crate::pending_downloads::remove_pending_download(&download.request_id);
+}
+
+/// Hash the finished output file off the reactor and report the outcome.
+/// Either way the download is over, so its resume state is dropped.
+fn spawn_full_file_verification(
+    events: &EventSink,
+    request_id: String,
+    expected_file_hash: String,
+    file_name: String,
+    file_size: u64,
+    output_path: PathBuf,
+) {
+    let events = events.clone();
+    tokio::spawn(async move {
+        match tokio::task::spawn_blocking(move || {
+            crate::merkle::MerkleTree::from_file(&output_path).map(|tree| (tree, output_path))
+        })
+        .await
+        {
+            Ok(Ok((tree, output_path))) => {
+                // Accepts both Merkle content hashes and legacy whole-file
+                // SHA-256 hashes.
+                if tree.matches_hash(&expected_file_hash) {
+                    println!("✅ File verified and saved: {:?}", output_path);
+                    events.emit(
+                        "file-download-complete",
+                        serde_json::json!({
+                            "requestId": request_id,
+                            "fileHash": expected_file_hash,
+                            "fileName": file_name,
+                            "filePath": output_path.to_string_lossy(),
+                            "fileSize": file_size,
+                            "status": "completed"
+                        }),
+                    );
+                } else {
+                    println!(
+                        "❌ Full file hash mismatch! Expected: {}, Got: {}",
+                        expected_file_hash,
+                        tree.root()
+                    );
+                    let _ = std::fs::remove_file(&output_path);
+                    events.emit("file-download-failed", serde_json::json!({
+                        "requestId": request_id,
+                        "fileHash": expected_file_hash,
+                        "error": "File integrity verification failed — hash mismatch after all chunks received"
+                    }));
+                }
+            }
+            Ok(Err(e)) => {
+                println!("❌ Failed to verify file: {}", e);
+                events.emit(
+                    "file-download-failed",
+                    serde_json::json!({
+                        "requestId": request_id,
+                        "fileHash": expected_file_hash,
+                        "error": format!("Failed to verify file: {}", e)
+                    }),
+                );
+            }
+            Err(e) => {
+                println!("❌ Verification task panicked: {}", e);
+                events.emit(
+                    "file-download-failed",
+                    serde_json::json!({
+                        "requestId": request_id,
+                        "fileHash": expected_file_hash,
+                        "error": format!("Verification task failed: {}", e)
+                    }),
+                );
+            }
+        }
+        crate::pending_downloads::remove_pending_download(&request_id);
+    });
+}
+
+/// How long a resumed download waits for the DHT to bootstrap before
+/// contacting its seeder anyway.
+const RESUME_BOOTSTRAP_WAIT_SECS: u64 = 30;
+
+/// Pick up downloads that were still running when the node last stopped.
+///
+/// Each persisted record is re-verified (seeder signature, then every chunk
+/// already on disk) before anything goes on the wire. The verified bitmap is
+/// parked in `resumed_downloads` and the original seeder is asked for the
+/// FileInfo again under the same request id; the FileInfo handler then
+/// continues from the bitmap, re-presenting the stored payment for paid
+/// files instead of paying twice. Free files also ask other providers.
+fn spawn_pending_download_resume(
+    cmd_tx: mpsc::UnboundedSender<SwarmCommand>,
+    resumed_downloads: ResumedDownloadsMap,
+    events: EventSink,
+    bootstrap_gate: DhtBootstrapGate,
+) {
+    tokio::spawn(async move {
+        let recovered = match tokio::task::spawn_blocking(|| {
+            crate::pending_downloads::load_pending_downloads()
+                .into_iter()
+                .filter_map(|record| {
+                    if !record.signature_valid()
+                        || record.chunk_size as usize != CHUNK_SIZE
+                        || record.total_chunks != crate::merkle::chunk_count(record.file_size)
+                    {
+                        println!(
+                            "❌ Discarding resume state for {}: manifest no longer verifies",
+                            record.request_id
+                        );
+                        crate::pending_downloads::remove_pending_download(&record.request_id);
+                        return None;
+                    }
+                    let leaves = crate::pending_downloads::load_verified_leaves(
+                        &record.request_id,
+                        record.total_chunks,
+                    );
+                    let (received_chunks, verified_bytes) =
+                        crate::pending_downloads::verify_existing_chunks(
+                            &record.output_path,
+                            &leaves,
+                            record.chunk_size,
+                            record.file_size,
+                        );
+                    Some(ResumedDownload {
+                        record,
+                        received_chunks,
+                        verified_bytes,
+                    })
+                })
+                .collect::<Vec<_>>()
+        })
+        .await
+        {
+            Ok(recovered) => recovered,
+            Err(e) => {
+                println!("❌ Failed to load interrupted downloads: {}", e);
+                return;
+            }
+        };
+        if recovered.is_empty() {
+            return;
+        }
+
+        let mut to_request = Vec::new();
+        for resumed in recovered {
+            let record = &resumed.record;
+            let display_name = record
+                .output_path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_else(|| record.file_name.clone());
+            events.emit(
+                "download-resumed",
+                serde_json::json!({
+                    "requestId": record.request_id,
+                    "fileHash": record.file_hash,
+                    "fileName": display_name,
+                    "filePath": record.output_path.to_string_lossy(),
+                    "bytesWritten": resumed.verified_bytes,
+                    "totalBytes": record.file_size,
+                    "peerId": record.peer_id,
+                }),
+            );
+            if resumed.received_chunks.iter().all(|r| *r) {
+                // Every chunk made it to disk before the restart.
+                println!(
+                    "🔁 Interrupted download {} was already complete, verifying",
+                    record.request_id
+                );
+                spawn_full_file_verification(
+                    &events,
+                    record.request_id.clone(),
+                    record.file_hash.clone(),
+                    display_name,
+                    record.file_size,
+                    record.output_path.clone(),
+                );
+                continue;
+            }
+            println!(
+                "🔁 Resuming {} ({}/{} bytes already verified)",
+                display_name, resumed.verified_bytes, record.file_size
+            );
+            to_request.push((
+                record.request_id.clone(),
+                record.file_hash.clone(),
+                record.folder_hash.clone(),
+                record.peer_id.clone(),
+                record.price_wei == "0" && record.folder_hash.is_none(),
+            ));
+            resumed_downloads
+                .lock()
+                .await
+                .insert(record.request_id.clone(), resumed);
+        }
+        if to_request.is_empty() {
+            return;
+        }
+
+        bootstrap_gate
+            .wait_ready(Duration::from_secs(RESUME_BOOTSTRAP_WAIT_SECS))
+            .await;
+        for (request_id, file_hash, folder_hash, peer_id, free) in to_request {
+            let known_peer = PeerId::from_str(&peer_id).ok();
+            let mut requested = false;
+            if let Some(peer_id) = known_peer {
+                let (response_tx, response_rx) = tokio::sync::oneshot::channel();
+                if cmd_tx
+                    .send(SwarmCommand::RequestFileInfo {
+                        peer_id,
+                        request_id: request_id.clone(),
+                        file_hash: file_hash.clone(),
+                        folder_hash,
+                        multiaddrs: Vec::new(),
+                        response_tx,
+                    })
+                    .is_err()
+                {
+                    return;
+                }
+                requested = matches!(response_rx.await, Ok(Ok(())));
+            }
+            if free {
+                // Any provider with the same signed manifest can finish a
+                // free download.
+                discover_additional_chunk_sources(
+                    &cmd_tx,
+                    request_id,
+                    file_hash,
+                    known_peer.into_iter().collect(),
+                );
+            } else if !requested {
+                // Paid downloads can only resume at the seeder that holds
+                // the payment; keep the state for the next start.
+                resumed_downloads.lock().await.remove(&request_id);
+                events.emit(
+                    "file-download-failed",
+                    serde_json::json!({
+                        "requestId": request_id,
+                        "fileHash": file_hash,
+                        "error": "Seeder holding this payment is unreachable; the download will resume on the next start",
+                    }),
+                );
+            }
+        }
+    });
+}
+
+fn merge_host_registry_records(records: &[String]) -> Option<String> {
+    let mut merged_by_peer: HashMap<String, HostRegistryMergeEntry> = HashMap::new();
+    let mut saw_registry_record = false;
+
+    for raw in records {
+        let Ok(entries) = serde_json::from_str::<Vec<HostRegistryMergeEntry>>(raw) else {
+            continue;
+        };
+        saw_registry_record = true;
+
+        for mut entry in entries {
+            let peer_id = entry.peer_id.trim().to_string();
+            if peer_id.is_empty() || PeerId::from_str(&peer_id).is_err() {
+                continue;
+            }
+            entry.peer_id = peer_id.clone();
+
+            merged_by_peer
+                .entry(peer_id)
+                .and_modify(|existing| {
+                    if entry.updated_at > existing.updated_at {
+                        *existing = entry.clone();
+                    } else if existing.wallet_address.is_empty() && !entry.wallet_address.is_empty()
+                    {
+                        existing.wallet_address = entry.wallet_address.clone();
+                    }
+                })
+                .or_insert(entry);
+        }
+    }
+
+    if !saw_registry_record || merged_by_peer.is_empty() {
+        return None;
+    }
+
+    let mut merged_entries: Vec<HostRegistryMergeEntry> = merged_by_peer.into_values().collect();
+    merged_entries.sort_by(|a, b| {
+        b.updated_at
+            .cmp(&a.updated_at)
+            .then_with(|| a.peer_id.cmp(&b.peer_id))
+    });
+
+    serde_json::to_string(&merged_entries).ok()
+}
+
+/// Pick the value to return for a GET from the validated copies seen.
+/// Host registries are a union of individually signed per-host entries, so
+/// every copy contributes; for every other kind the newest record wins.
+fn select_best_get_record_value(records: Vec<dht_record::SignedRecord>) -> Option<String> {
+    if records
+        .first()
+        .is_some_and(|r| r.kind == dht_record::RecordKind::HostRegistry)
+    {
+        let values: Vec<String> = records.into_iter().map(|r| r.value).collect();
+        return merge_host_registry_records(&values);
+    }
+    records
+        .into_iter()
+        .max_by_key(|record| record.seq)
+        .map(|record| record.value)
+}
+
+/// Sign this node's host-registry entries, seal `value` for `key`, and run
+/// the result through the same validation remote peers will apply.
+fn seal_dht_value(
+    key: &str,
+    value: String,
+    identity: &libp2p::identity::Keypair,
+) -> Result<Vec<u8>, String> {
+    let value = if key == HOST_REGISTRY_KEY {
+        dht_record::sign_host_registry(&value, identity)?
+    } else {
+        value
+    };
+    let now = dht_record::now_ms();
+    let bytes = dht_record::SignedRecord::seal(key, value, identity, now)?.to_bytes();
+    dht_record::SignedRecord::open(key, &bytes, now)
+        .map_err(|e| format!("Refusing to publish {}: {}", key, e))?;
+    Ok(bytes)
+}
+
+/// Re-seal records this node published before their envelopes expire.
+/// Kademlia republishes the stored bytes unchanged, so without this every
+/// long-lived record would be rejected network-wide after its lifetime.
+fn spawn_dht_record_refresh(
+    cmd_tx: mpsc::UnboundedSender<SwarmCommand>,
+    identity: libp2p::identity::Keypair,
+    is_running: Arc<Mutex<bool>>,
+) {
+    tokio::spawn(async move {
+        let mut interval =
+            tokio::time::interval(Duration::from_secs(DHT_RECORD_REFRESH_CHECK_SECS));
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            if !*is_running.lock().await {
+                break;
+            }
+            let (response_tx, response_rx) = tokio::sync::oneshot::channel();
+            if cmd_tx
+                .send(SwarmCommand::ListOwnDhtRecords { response_tx })
+                .is_err()
+            {
+                break;
+            }
+            let Ok(records) = response_rx.await else {
+                break;
+            };
+            let now = dht_record::now_ms();
+            for (key, bytes) in records {
+                // Our own copy may already be past expiry (e.g. after a
+                // suspend), so parse without validating the lifetime.
+                let Ok(record) = serde_json::from_slice::<dht_record::SignedRecord>(&bytes) else {
+                    continue;
+                };
+                if !record.needs_refresh(now) {
+                    continue;
+                }
+                match seal_dht_value(&key, record.value, &identity) {
+                    Ok(value) => {
+                        println!("Refreshing signed DHT record: {}", key);
+                        let (response_tx, _) = tokio::sync::oneshot::channel();
+                        let _ = cmd_tx.send(SwarmCommand::PutDhtValue {
+                            key,
+                            value,
+                            response_tx,
+                        });
+                    }
+                    Err(e) => println!("⚠️ Not refreshing DHT record {}: {}", key, e),
+                }
+            }
+        }
+    });
+}
+
+fn build_relay_circuit_dial_addrs(target_peer_id: &PeerId) -> Vec<Multiaddr> {
+    let mut dial_addrs: Vec<Multiaddr> = Vec::new();
+    let mut seen_addrs: HashSet<String> = HashSet::new();
+    let mut relay_peer_ids_tried: HashSet<PeerId> = HashSet::new();
+
+    for relay_addr_str in get_relay_nodes() {
+        if let Ok(relay_addr) = relay_addr_str.parse::<Multiaddr>() {
+            if let Some(relay_pid) = extract_peer_id_from_multiaddr(&relay_addr) {
+                if !relay_peer_ids_tried.insert(relay_pid) {
+                    continue;
+                }
+            }
+            let circuit_addr = relay_addr
                 .with(libp2p::multiaddr::Protocol::P2pCircuit)
                 .with(libp2p::multiaddr::Protocol::P2p(target_peer_id.clone()));
             let key = circuit_addr.to_string();
@@ -2818,6 +4521,30 @@ fn auto_dial_discovered_hosts_from_registry(
     }
 }
 
+/// Keep the router's port mappings alive while `keep_running` is set,
+/// reporting each round back to the event loop. Leases are renewed at half
+/// their length; a round that maps nothing is retried on the same cadence.
+fn spawn_port_mapping(
+    cmd_tx: mpsc::UnboundedSender<SwarmCommand>,
+    keep_running: Arc<AtomicBool>,
+    requests: Vec<crate::nat::MappingRequest>,
+) {
+    tokio::spawn(async move {
+        let renew = Duration::from_secs(u64::from(crate::nat::MAPPING_LEASE_SECS) / 2);
+        while keep_running.load(Ordering::SeqCst) {
+            let mappings = crate::nat::map_ports(&requests).await;
+            if !keep_running.load(Ordering::SeqCst)
+                || cmd_tx
+                    .send(SwarmCommand::PortMappingsUpdated { mappings })
+                    .is_err()
+            {
+                break;
+            }
+            tokio::time::sleep(renew).await;
+        }
+    });
+}
+
 async fn event_loop(
     mut swarm: Swarm<DhtBehaviour>,
     peers: Arc<Mutex<Vec<PeerInfo>>>,
@@ -2829,8 +4556,10 @@ async fn event_loop(
     shared_files: SharedFilesMap,
     download_directory: DownloadDirectoryRef,
     active_downloads: Arc<Mutex<ActiveDownloadsMap>>,
+    resumed_downloads: ResumedDownloadsMap,
     download_credentials: DownloadCredentialsMap,
     bootstrap_gate: DhtBootstrapGate,
+    http_p2p_server: crate::http_p2p::HttpP2pServer,
 ) {
     // Track pending get queries
     let mut pending_get_queries: HashMap<
@@ -2840,7 +4569,8 @@ async fn event_loop(
     // Accumulate all FoundRecord results for a GET query before resolving.
     // Without this, the first FoundRecord (often from local stale MemoryStore)
     // would resolve the query, ignoring more up-to-date remote records.
-    let mut pending_get_results: HashMap<kad::QueryId, Vec<String>> = HashMap::new();
+    let mut pending_get_results: HashMap<kad::QueryId, Vec<dht_record::SignedRecord>> =
+        HashMap::new();
     // Track pending GetProviders queries and accumulate the union of peer IDs
     // found across all FoundProviders events for each query.
     let mut pending_get_providers_queries: HashMap<
@@ -2860,6 +4590,9 @@ async fn event_loop(
     // Solution: dial explicitly, queue the request, send it on ConnectionEstablished.
     let mut pending_file_requests: HashMap<libp2p::PeerId, Vec<PendingFileInfoRequest>> =
         HashMap::new();
+    // TCP and relay addresses held back while a seeder's QUIC addresses are
+    // dialed; dialed if those fail.
+    let mut quic_dial_fallbacks: HashMap<libp2p::PeerId, Vec<Multiaddr>> = HashMap::new();
     // Ping requests deferred until a connection exists to the target peer.
     // send_request() may race dialing and fail with DialFailure when only relay
     // circuit paths are viable. We explicitly dial relay addresses first, then
@@ -2906,6 +4639,27 @@ async fn event_loop(
     let mut kad_peer_sync_interval = tokio::time::interval(tokio::time::Duration::from_secs(30));
     kad_peer_sync_interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
 
+    // Re-fill chunk request windows once retry backoffs expire, and move
+    // straggling chunk requests onto faster sources.
+    let mut chunk_scheduler_interval =
+        tokio::time::interval(tokio::time::Duration::from_millis(CHUNK_SCHEDULER_TICK_MS));
+    chunk_scheduler_interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    // Stop serving ChiralDrop offers once they outlive the outbox, whether
+    // or not new offers are made.
+    let mut drop_offer_expiry_interval = tokio::time::interval(tokio::time::Duration::from_secs(
+        DROP_OFFER_EXPIRY_CHECK_SECS,
+    ));
+    drop_offer_expiry_interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    // AutoNAT verdict and router mappings; the gateway port is forwarded
+    // alongside libp2p so the relay's drive share proxy can reach it.
+    let mut nat_state = crate::nat::NatState::default();
+    let gateway_port = crate::nat::gateway_port_from_env().unwrap_or_else(|e| {
+        println!("⚠️ {}; mapping the default gateway port", e);
+        crate::nat::DEFAULT_GATEWAY_PORT
+    });
+
     loop {
         let running = *is_running.lock().await;
         if !running {
@@ -2938,6 +4692,19 @@ async fn event_loop(
                     println!("✅ Kademlia bootstrap triggered via timer fallback");
                 }
             }
+            _ = chunk_scheduler_interval.tick() => {
+                let mut downloads = active_downloads.lock().await;
+                let now = Instant::now();
+                for dl in downloads.values_mut() {
+                    pump_chunk_download(&mut swarm, &mut outbound_request_map, dl, now);
+                }
+            }
+            _ = drop_offer_expiry_interval.tick() => {
+                let unshared = sweep_drop_offers(&shared_files, Instant::now()).await;
+                if unshared > 0 {
+                    println!("ChiralDrop: stopped serving {} expired offers", unshared);
+                }
+            }
             _ = auto_host_registry_refresh.tick(), if kad_bootstrapped => {
                 let query_id = swarm
                     .behaviour_mut()
@@ -2953,6 +4720,7 @@ async fn event_loop(
             _ = kad_peer_sync_interval.tick(), if kad_bootstrapped => {
                 // Active discovery: random walk to find new peers on the network
                 let _ = swarm.behaviour_mut().kad.bootstrap();
+                sample_swarm_metrics(&mut swarm);
 
                 let Some(now) = current_kademlia_peer_sync_timestamp_secs(
                     "syncing Kademlia routing table into visible peer list",
@@ -3018,6 +4786,26 @@ async fn event_loop(
             }
             event = swarm.select_next_some() => {
                 match event {
+                    SwarmEvent::Behaviour(DhtBehaviourEvent::Autonat(autonat::Event::StatusChanged { old, new })) => {
+                        println!("[NAT] Reachability changed: {:?} -> {:?}", old, new);
+                        if let Some(keep_running) = nat_state.update_status(&new) {
+                            let listeners: Vec<Multiaddr> = swarm.listeners().cloned().collect();
+                            let requests = crate::nat::mapping_requests(&listeners, gateway_port);
+                            println!("[NAT] Behind NAT; requesting {} port mapping(s)", requests.len());
+                            spawn_port_mapping(cmd_tx.clone(), keep_running, requests);
+                        }
+                    }
+                    SwarmEvent::Behaviour(DhtBehaviourEvent::Autonat(_)) => {}
+                    SwarmEvent::Behaviour(DhtBehaviourEvent::Ping(ping::Event { peer, result: Ok(rtt), .. })) => {
+                        crate::seeder_selection::performance().lock().record_latency(
+                            &peer.to_string(),
+                            rtt,
+                            crate::drive_storage::now_secs().unwrap_or(0),
+                        );
+                    }
+                    SwarmEvent::Behaviour(DhtBehaviourEvent::HttpP2p(event)) => {
+                        handle_http_p2p_event(event, &http_p2p_server, &cmd_tx);
+                    }
                     SwarmEvent::Behaviour(event) => {
                         handle_behaviour_event(
                             event,
@@ -3033,6 +4821,7 @@ async fn event_loop(
                             &shared_files,
                             &download_directory,
                             &active_downloads,
+                            &resumed_downloads,
                             &mut outbound_request_map,
                             &mut pending_file_attempts,
                             &mut pending_file_hashes,
@@ -3076,6 +4865,7 @@ async fn event_loop(
                     }
                     SwarmEvent::ConnectionEstablished { peer_id, endpoint, .. } => {
                         println!("Connection established with {:?}", peer_id);
+                        sample_swarm_metrics(&mut swarm);
 
                         // Peer is reachable — remove from failed set
                         if failed_peers.remove(&peer_id) {
@@ -3085,6 +4875,7 @@ async fn event_loop(
                             );
                         }
                         auto_peer_dial_attempts.remove(&peer_id);
+                        quic_dial_fallbacks.remove(&peer_id);
 
                         // Send any file requests that were waiting for this connection
                         if let Some(pending) = pending_file_requests.remove(&peer_id) {
@@ -3154,6 +4945,7 @@ async fn event_loop(
                         }
                     }
                     SwarmEvent::ConnectionClosed { peer_id, num_established, .. } => {
+                        sample_swarm_metrics(&mut swarm);
                         // Only remove when all connections to this peer are gone
                         if num_established == 0 {
                             let peer_id_str = peer_id.to_string();
@@ -3215,6 +5007,24 @@ async fn event_loop(
                                 continue;
                             }
 
+                            // QUIC didn't connect; try TCP and relays before
+                            // giving up on the deferred requests.
+                            if let Some(fallback) = quic_dial_fallbacks.remove(&peer) {
+                                println!(
+                                    "📡 QUIC dial to {} failed, trying {} TCP/relay address(es)",
+                                    peer,
+                                    fallback.len()
+                                );
+                                let opts = libp2p::swarm::dial_opts::DialOpts::peer_id(peer)
+                                    .addresses(fallback)
+                                    .condition(libp2p::swarm::dial_opts::PeerCondition::DisconnectedAndNotDialing)
+                                    .build();
+                                match swarm.dial(opts) {
+                                    Ok(_) => continue,
+                                    Err(e) => println!("Fallback dial setup failed: {:?}", e),
+                                }
+                            }
+
                             let peer_id_str = peer.to_string();
                             let bootstrap_peer_ids = get_bootstrap_peer_ids();
                             let is_bootstrap_peer = bootstrap_peer_ids.contains(&peer_id_str);
@@ -3348,25 +5158,56 @@ async fn event_loop(
                             }
                         }
                     }
-                    SwarmCommand::SendFile { peer_id, transfer_id, file_name, file_data, price_wei, sender_wallet, file_hash, file_size } => {
-                        println!("Sending file '{}' to peer {} (price: {} wei, size: {} bytes)", file_name, peer_id, price_wei, file_size);
-                        let request = FileTransferRequest {
-                            transfer_id: transfer_id.clone(),
-                            file_name: file_name.clone(),
-                            file_size,
-                            file_data,
-                            price_wei,
-                            sender_wallet,
-                            file_hash,
-                        };
-                        let request_id = swarm.behaviour_mut().file_transfer.send_request(&peer_id, request);
-                        println!("File transfer request sent with ID: {:?}", request_id);
+                    SwarmCommand::SendFileOffer { peer_id, offer } if offer.closed.is_some() => {
+                        println!("Telling peer {} that ChiralDrop {} is {:?}", peer_id, offer.transfer_id, offer.closed);
+                        swarm.behaviour_mut().file_transfer.send_request(&peer_id, offer);
+                    }
+                    SwarmCommand::SendFileOffer { peer_id, offer } => {
+                        println!("Offering '{}' to peer {} (price: {} wei, size: {} bytes)", offer.file_name, peer_id, offer.price_wei, offer.file_size);
+                        let transfer_id = offer.transfer_id.clone();
+                        let file_name = offer.file_name.clone();
+                        let request_id = swarm.behaviour_mut().file_transfer.send_request(&peer_id, offer);
+                        println!("File offer sent with ID: {:?}", request_id);
                         let _ = events.emit("file-transfer-started", serde_json::json!({
                             "transferId": transfer_id,
                             "peerId": peer_id.to_string(),
                             "fileName": file_name
                         }));
                     }
+                    SwarmCommand::CancelDownload { request_id, response_tx } => {
+                        // Forgetting the outstanding FileInfo attempts makes a late
+                        // FileInfo answer for this request a no-op, so a cancelled
+                        // download can neither restart nor pay.
+                        let was_requesting = pending_file_attempts.remove(&request_id).is_some();
+                        pending_file_hashes.remove(&request_id);
+                        for queued in pending_file_requests.values_mut() {
+                            queued.retain(|r| r.request_id != request_id);
+                        }
+                        pending_file_requests.retain(|_, queued| !queued.is_empty());
+                        let was_resuming = resumed_downloads.lock().await.remove(&request_id).is_some();
+                        download_credentials.lock().await.remove(&request_id);
+                        let removed = active_downloads.lock().await.remove(&request_id);
+                        let cancelled = was_requesting || was_resuming || removed.is_some();
+                        let file_hash = match removed {
+                            Some(dl) => {
+                                outbound_request_map.retain(|id, _| !dl.outbound_chunks.contains_key(id));
+                                discard_partial_download(&dl);
+                                Some(dl.file_hash)
+                            }
+                            None => {
+                                crate::pending_downloads::remove_pending_download(&request_id);
+                                None
+                            }
+                        };
+                        if cancelled {
+                            println!("🛑 Download {} cancelled", request_id);
+                            events.emit("file-download-cancelled", serde_json::json!({
+                                "requestId": request_id,
+                                "fileHash": file_hash
+                            }));
+                        }
+                        let _ = response_tx.send(cancelled);
+                    }
                     SwarmCommand::PutDhtValue { key, value, response_tx } => {
                         println!("Storing DHT value for key: {}", key);
                         let record_key = kad::RecordKey::new(&key);
@@ -3376,7 +5217,7 @@ async fn event_loop(
                         // republish semantics get confused.
                         let record = kad::Record {
                             key: record_key,
-                            value: value.into_bytes(),
+                            value,
                             publisher: Some(*swarm.local_peer_id()),
                             expires: None,
                         };
@@ -3402,6 +5243,23 @@ async fn event_loop(
                         swarm.behaviour_mut().kad.remove_record(&record_key);
                         println!("Removed DHT record from local store: {}", key);
                     }
+                    SwarmCommand::ListOwnDhtRecords { response_tx } => {
+                        let local = *swarm.local_peer_id();
+                        let own = swarm
+                            .behaviour_mut()
+                            .kad
+                            .store_mut()
+                            .records()
+                            .filter(|r| r.publisher == Some(local))
+                            .map(|r| {
+                                (
+                                    String::from_utf8_lossy(r.key.as_ref()).to_string(),
+                                    r.value.clone(),
+                                )
+                            })
+                            .collect();
+                        let _ = response_tx.send(own);
+                    }
                     SwarmCommand::StartProviding { file_hash, response_tx } => {
                         let record_key = kad::RecordKey::new(&file_hash.as_bytes());
                         match swarm.behaviour_mut().kad.start_providing(record_key) {
@@ -3449,6 +5307,31 @@ async fn event_loop(
                             println!("Failed to send deferred chunk response: {:?}", e);
                         }
                     }
+                    SwarmCommand::NotifyVoucherSettlement { peer_id, settlement } => {
+                        let notice_key =
+                            format!("{}{}", SETTLEMENT_NOTICE_PREFIX, settlement.tx_hash);
+                        let request = ChunkRequest::VoucherSettlement {
+                            tx_hash: settlement.tx_hash,
+                            payer_address: settlement.payer,
+                            payee_address: settlement.payee,
+                            amount_wei: settlement.amount_wei,
+                        };
+                        let req_id = swarm
+                            .behaviour_mut()
+                            .file_request
+                            .send_request(&peer_id, request);
+                        outbound_request_map.insert(req_id, notice_key);
+                    }
+                    SwarmCommand::SendHttpP2pResponse { channel, response } => {
+                        if swarm
+                            .behaviour_mut()
+                            .http_p2p
+                            .send_response(channel, response)
+                            .is_err()
+                        {
+                            println!("[HTTP-P2P] Relay closed the stream before the response was sent");
+                        }
+                    }
                     SwarmCommand::HealthCheck { response_tx } => {
                         let listeners: Vec<String> = swarm.listeners().map(|a| a.to_string()).collect();
                         let connected: Vec<PeerId> = swarm.connected_peers().cloned().collect();
@@ -3473,9 +5356,10 @@ async fn event_loop(
                         let protocols = vec![
                             "/chiral/id/1.0.0".to_string(),
                             "/chiral/ping/1.0.0".to_string(),
-                            "/chiral/file-transfer/1.0.0".to_string(),
-                            "/chiral/file-request/3.0.0".to_string(),
+                            "/chiral/file-transfer/3.0.0".to_string(),
+                            "/chiral/file-request/4.0.0".to_string(),
                             "/chiral/kad/1.0.0".to_string(),
+                            "/libp2p/autonat/1.0.0".to_string(),
                         ];
 
                         let _ = response_tx.send(DhtHealthInfo {
@@ -3487,6 +5371,9 @@ async fn event_loop(
                             bootstrap_nodes: bootstrap_status,
                             shared_files: 0, // filled in by get_health()
                             protocols,
+                            nat_status: nat_state.reachability,
+                            public_address: nat_state.public_address.as_ref().map(|a| a.to_string()),
+                            port_mappings: nat_state.mappings.clone(),
                         });
                     }
                     SwarmCommand::CheckPeerConnected { peer_id, response_tx } => {
@@ -3498,7 +5385,16 @@ async fn event_loop(
                         pending_echo.insert(req_id, response_tx);
                     }
                     SwarmCommand::GetListeningAddresses { response_tx } => {
-                        let addrs: Vec<String> = swarm.listeners()
+                        // Published as SeederInfo.multiaddrs — keep only what peers
+                        // can reach, and list QUIC first so readers that dial in
+                        // order try it before TCP.
+                        let listeners: Vec<Multiaddr> = swarm.listeners().cloned().collect();
+                        let external: Vec<Multiaddr> = swarm.external_addresses().cloned().collect();
+                        let mut publishable =
+                            crate::nat::publishable_addrs(nat_state.reachability, &listeners, &external);
+                        prefer_quic_addrs(&mut publishable);
+                        let addrs: Vec<String> = publishable
+                            .into_iter()
                             .map(|a| a.to_string())
                             .collect();
                         let _ = response_tx.send(addrs);
@@ -3605,19 +5501,26 @@ async fn event_loop(
                             }
 
                             if !dial_addrs.is_empty() {
+                                let (first_wave, fallback) = split_quic_dial(dial_addrs);
                                 println!(
-                                    "📡 Trying {} dial address(es) for peer {}",
-                                    dial_addrs.len(),
-                                    peer_id
+                                    "📡 Trying {} dial address(es) for peer {} ({} held back until QUIC fails)",
+                                    first_wave.len(),
+                                    peer_id,
+                                    fallback.len()
                                 );
                                 let opts = libp2p::swarm::dial_opts::DialOpts::peer_id(peer_id.clone())
-                                    .addresses(dial_addrs)
+                                    .addresses(first_wave)
                                     .condition(libp2p::swarm::dial_opts::PeerCondition::DisconnectedAndNotDialing)
                                     .build();
                                 match swarm.dial(opts) {
                                     Ok(_) => {
                                         println!("📡 Dial initiated for peer {}", peer_id);
                                         started_attempt = true;
+                                        if fallback.is_empty() {
+                                            quic_dial_fallbacks.remove(&peer_id);
+                                        } else {
+                                            quic_dial_fallbacks.insert(peer_id, fallback);
+                                        }
                                     }
                                     Err(e) => {
                                         println!("Dial setup failed: {:?}", e);
@@ -3659,10 +5562,86 @@ async fn event_loop(
                             )));
                         }
                     }
+                    SwarmCommand::PortMappingsUpdated { mappings } => {
+                        for stale in nat_state.mappings.iter().filter(|m| !mappings.contains(m)) {
+                            if let Some(addr) = stale.external_multiaddr() {
+                                swarm.remove_external_address(&addr);
+                            }
+                        }
+                        for mapping in &mappings {
+                            if !nat_state.mappings.contains(mapping) {
+                                println!(
+                                    "[NAT] Mapped {:?} port {} -> {}:{} via {:?}",
+                                    mapping.protocol,
+                                    mapping.internal_port,
+                                    mapping.external_ip,
+                                    mapping.external_port,
+                                    mapping.method
+                                );
+                            }
+                            // Router-granted mappings are reachable by construction,
+                            // so they're confirmed directly rather than probed.
+                            if let Some(addr) = mapping.external_multiaddr() {
+                                swarm.add_external_address(addr);
+                            }
+                        }
+                        nat_state.mappings = mappings;
+                    }
                 }
             }
         }
     }
+    nat_state.stop_mapping();
+    crate::metrics::global().set_swarm_gauges(0, 0);
+}
+
+/// Refresh the connected-peer and routing-table gauges.
+fn sample_swarm_metrics(swarm: &mut Swarm<DhtBehaviour>) {
+    let routing_table_peers = swarm
+        .behaviour_mut()
+        .kad
+        .kbuckets()
+        .map(|bucket| bucket.num_entries())
+        .sum();
+    crate::metrics::global().set_swarm_gauges(swarm.connected_peers().count(), routing_table_peers);
+}
+
+/// Serve a relay's HTTP request off the swarm task; the local server's
+/// reply is handed back through `SendHttpP2pResponse`.
+fn handle_http_p2p_event(
+    event: request_response::Event<
+        crate::http_p2p::HttpP2pRequest,
+        crate::http_p2p::HttpP2pResponse,
+    >,
+    server: &crate::http_p2p::HttpP2pServer,
+    cmd_tx: &mpsc::UnboundedSender<SwarmCommand>,
+) {
+    match event {
+        request_response::Event::Message {
+            peer,
+            message:
+                request_response::Message::Request {
+                    request, channel, ..
+                },
+        } => {
+            let server = server.clone();
+            let cmd_tx = cmd_tx.clone();
+            tokio::spawn(async move {
+                let response = server.handle(peer, request).await;
+                if let crate::http_p2p::HttpP2pResponse::Error { message } = &response {
+                    println!("[HTTP-P2P] Request from {} failed: {}", peer, message);
+                }
+                let _ = cmd_tx.send(SwarmCommand::SendHttpP2pResponse { channel, response });
+            });
+        }
+        request_response::Event::InboundFailure { peer, error, .. } => {
+            println!(
+                "[HTTP-P2P] Inbound request from {} failed: {:?}",
+                peer, error
+            );
+        }
+        _ => {}
+    }
 }
 
 async fn handle_behaviour_event(
@@ -3675,7 +5654,7 @@ async fn handle_behaviour_event(
         kad::QueryId,
         tokio::sync::oneshot::Sender<Result<Option<String>, String>>,
     >,
-    pending_get_results: &mut HashMap<kad::QueryId, Vec<String>>,
+    pending_get_results: &mut HashMap<kad::QueryId, Vec<dht_record::SignedRecord>>,
     pending_get_providers_queries: &mut HashMap<
         kad::QueryId,
         tokio::sync::oneshot::Sender<Result<Vec<String>, String>>,
@@ -3685,6 +5664,7 @@ async fn handle_behaviour_event(
     shared_files: &SharedFilesMap,
     download_directory: &DownloadDirectoryRef,
     active_downloads: &Arc<Mutex<ActiveDownloadsMap>>,
+    resumed_downloads: &ResumedDownloadsMap,
     outbound_request_map: &mut HashMap<request_response::OutboundRequestId, String>,
     pending_file_attempts: &mut HashMap<String, usize>,
     pending_file_hashes: &mut HashMap<String, String>,
@@ -4102,7 +6082,15 @@ async fn handle_behaviour_event(
                 _ => {}
             }
         }
-        DhtBehaviourEvent::Kad(kad::Event::OutboundQueryProgressed { id, result, .. }) => {
+        DhtBehaviourEvent::Kad(kad::Event::OutboundQueryProgressed {
+            id,
+            result,
+            stats,
+            step,
+        }) => {
+            if step.last {
+                crate::metrics::global().observe_kad_query(&result, stats.duration());
+            }
             match result {
                 kad::QueryResult::GetRecord(Ok(kad::GetRecordOk::FoundRecord(record))) => {
                     let key_bytes = record.record.key.as_ref();
@@ -4116,24 +6104,33 @@ async fn handle_behaviour_event(
                         "DHT get: found {} record for query {:?}, key: {}",
                         source, id, key_str
                     );
-                    let value = String::from_utf8(record.record.value.clone())
-                        .unwrap_or_else(|_| String::new());
-                    println!("DHT record value length: {} bytes", value.len());
-                    // Fire the response on FIRST hit and drop the waiter.
-                    // Kademlia keeps the query alive until convergence
-                    // (often 15-30s) — waiting for that turns every search
-                    // by hash into a multi-second roundtrip even when the
-                    // record is in the local store. The records we read
-                    // here (chiral_file_*, chiral_seeder_*, chiral_folder_*,
-                    // chiral_drive_share_*) are all ECDSA-signed by the
-                    // publisher, so taking the first replica is safe — a
-                    // forgery is rejected by the caller's verify step.
-                    pending_get_results
-                        .entry(id)
-                        .or_default()
-                        .push(value.clone());
-                    if let Some(tx) = pending_get_queries.remove(&id) {
-                        let _ = tx.send(Ok(Some(value)));
+                    println!(
+                        "DHT record value length: {} bytes",
+                        record.record.value.len()
+                    );
+                    // Fire the response on FIRST valid hit and drop the
+                    // waiter. Kademlia keeps the query alive until
+                    // convergence (often 15-30s) — waiting for that turns
+                    // every search by hash into a multi-second roundtrip
+                    // even when the record is in the local store. Every
+                    // record must pass its kind's validator first, so
+                    // taking the first replica is safe.
+                    match dht_record::SignedRecord::open(
+                        &key_str,
+                        &record.record.value,
+                        dht_record::now_ms(),
+                    ) {
+                        Ok(signed) => {
+                            let value = signed.value.clone();
+                            pending_get_results.entry(id).or_default().push(signed);
+                            if let Some(tx) = pending_get_queries.remove(&id) {
+                                let _ = tx.send(Ok(Some(value)));
+                            }
+                        }
+                        Err(e) => println!(
+                            "⚠️ Dropping invalid {} DHT record {}: {}",
+                            source, key_str, e
+                        ),
                     }
                 }
                 kad::QueryResult::GetRecord(Ok(
@@ -4312,6 +6309,24 @@ async fn handle_behaviour_event(
                 _ => {}
             }
         }
+        DhtBehaviourEvent::Kad(kad::Event::InboundRequest { request }) => match request {
+            kad::InboundRequest::PutRecord {
+                source,
+                record: Some(record),
+                ..
+            } => {
+                let store = swarm.behaviour_mut().kad.store_mut();
+                if let Err(e) = dht_record::store_inbound(store, record, dht_record::now_ms()) {
+                    println!("⚠️ Rejected DHT record from {}: {}", source, e);
+                }
+            }
+            kad::InboundRequest::AddProvider {
+                record: Some(record),
+            } => {
+                let _ = swarm.behaviour_mut().kad.store_mut().add_provider(record);
+            }
+            _ => {}
+        },
         DhtBehaviourEvent::Identify(identify::Event::Received { peer_id, info, .. }) => {
             // Drop peers whose Identify says they're below the
             // currently-effective `min_required`. The peer's
@@ -4386,14 +6401,69 @@ async fn handle_behaviour_event(
                         request_response::Message::Request {
                             request, channel, ..
                         } => {
-                            let price_wei = match parse_protocol_price_wei(
+                            if let Some(status) = request.closed.clone() {
+                                // The receiver is done with an offer we sent;
+                                // only it can close it.
+                                let unshared = close_drop_offer(
+                                    &mut *shared_files.lock().await,
+                                    &request.transfer_id,
+                                    peer,
+                                );
+                                println!(
+                                    "ChiralDrop {} closed by {} ({:?}){}",
+                                    request.transfer_id,
+                                    peer,
+                                    status,
+                                    if unshared.is_some() {
+                                        "; no longer serving it"
+                                    } else {
+                                        ""
+                                    }
+                                );
+                                discard_unshared_offers(unshared.into_iter().collect());
+                                if let Some(fts) = file_transfer_service {
+                                    fts.lock()
+                                        .await
+                                        .close_outgoing_offer(&request.transfer_id, status.clone())
+                                        .await;
+                                }
+                                events.emit(
+                                    "file-transfer-complete",
+                                    serde_json::json!({
+                                        "transferId": request.transfer_id,
+                                        "status": status
+                                    }),
+                                );
+                                let response = FileTransferResponse {
+                                    transfer_id: request.transfer_id,
+                                    accepted: true,
+                                    error: None,
+                                };
+                                if let Err(e) = swarm
+                                    .behaviour_mut()
+                                    .file_transfer
+                                    .send_response(channel, response)
+                                {
+                                    println!("Failed to send file transfer response: {:?}", e);
+                                }
+                                return;
+                            }
+                            let validated = parse_protocol_price_wei(
                                 &request.price_wei,
                                 "FileTransferRequest.price_wei",
-                            ) {
+                            )
+                            .and_then(|price_wei| {
+                                if request.file_hash.trim().is_empty() {
+                                    Err("FileTransferRequest.file_hash is required".to_string())
+                                } else {
+                                    Ok(price_wei)
+                                }
+                            });
+                            let price_wei = match validated {
                                 Ok(price_wei) => price_wei,
                                 Err(err) => {
                                     println!(
-                                        "Rejecting malformed file transfer price from {}: {}",
+                                        "Rejecting malformed file offer from {}: {}",
                                         peer, err
                                     );
                                     let response = FileTransferResponse {
@@ -4411,47 +6481,26 @@ async fn handle_behaviour_event(
                                     return;
                                 }
                             };
-                            let is_paid = price_wei > 0;
 
                             println!(
-                                "Received file transfer from {}: {} (paid: {})",
-                                peer, request.file_name, is_paid
+                                "Received file offer from {}: {} ({} bytes, paid: {})",
+                                peer,
+                                request.file_name,
+                                request.file_size,
+                                price_wei > 0
                             );
 
-                            if is_paid {
-                                // Paid transfer: file_data is empty, emit event with pricing
-                                // so the frontend can prompt the user to accept and pay
-                                let actual_size = if request.file_size > 0 {
-                                    request.file_size
-                                } else {
-                                    request.file_data.len() as u64
-                                };
-                                let _ = events.emit(
-                                    "chiraldrop-paid-request",
-                                    serde_json::json!({
-                                        "transferId": request.transfer_id,
-                                        "fromPeerId": peer.to_string(),
-                                        "fileName": request.file_name,
-                                        "fileHash": request.file_hash,
-                                        "fileSize": actual_size,
-                                        "priceWei": request.price_wei,
-                                        "senderWallet": request.sender_wallet
-                                    }),
-                                );
-                            } else {
-                                // Free transfer: store file data for acceptance
-                                if let Some(fts) = file_transfer_service {
-                                    let fts_lock = fts.lock().await;
-                                    let _ = fts_lock
-                                        .receive_file_request(
-                                            events.clone(),
-                                            peer.to_string(),
-                                            request.file_name.clone(),
-                                            request.file_data.clone(),
-                                            request.transfer_id.clone(),
-                                        )
-                                        .await;
-                                }
+                            // Only the offer is kept; the content is pulled in
+                            // chunks if the user accepts.
+                            if let Some(fts) = file_transfer_service {
+                                fts.lock()
+                                    .await
+                                    .receive_offer(
+                                        events.clone(),
+                                        peer.to_string(),
+                                        request.clone(),
+                                    )
+                                    .await;
                             }
 
                             // Protocol requires a response
@@ -4473,15 +6522,31 @@ async fn handle_behaviour_event(
                                 "Received file transfer response from {}: accepted={}",
                                 peer, response.accepted
                             );
+                            // Acks for close notices we sent about offers we
+                            // received need no handling.
+                            if let Some(fts) = file_transfer_service {
+                                if fts.lock().await.is_incoming(&response.transfer_id).await {
+                                    return;
+                                }
+                            }
                             if response.accepted {
+                                // The receiver has the offer; the content moves
+                                // once they accept and start pulling chunks.
                                 let _ = events.emit(
                                     "file-transfer-complete",
                                     serde_json::json!({
                                         "transferId": response.transfer_id,
-                                        "status": "completed"
+                                        "status": "offered"
                                     }),
                                 );
                             } else {
+                                // Refused offers are not served.
+                                let unshared = close_drop_offer(
+                                    &mut *shared_files.lock().await,
+                                    &response.transfer_id,
+                                    peer,
+                                );
+                                discard_unshared_offers(unshared.into_iter().collect());
                                 let _ = events.emit(
                                     "file-transfer-complete",
                                     serde_json::json!({
@@ -4542,7 +6607,7 @@ async fn handle_behaviour_event(
                                     );
                                     let shared = shared_files.lock().await;
 
-                                    // Cold path: chunk hashes haven't been
+                                    // Cold path: the Merkle tree hasn't been
                                     // warmed yet (the background warmup
                                     // spawned at register_shared_file is
                                     // still running, or the seeder just
@@ -4559,6 +6624,7 @@ async fn handle_behaviour_event(
                                     if let Some(file_info) = shared.get(&file_hash) {
                                         let access = match resolve_file_access(
                                             file_info,
+                                            peer,
                                             folder_hash.as_deref(),
                                         ) {
                                             Ok(access) => access,
@@ -4583,10 +6649,9 @@ async fn handle_behaviour_event(
                                                 return;
                                             }
                                         };
-                                        if file_info.chunk_hashes.is_none() {
+                                        if file_info.merkle_tree.is_none() {
                                             let path = file_info.file_path.clone();
                                             let file_name = file_info.file_name.clone();
-                                            let file_size = file_info.file_size;
                                             let private_key = file_info.private_key.clone();
                                             let shared_clone = shared_files.clone();
                                             let cmd_tx_clone = cmd_tx.clone();
@@ -4598,9 +6663,13 @@ async fn handle_behaviour_event(
                                             drop(shared);
                                             tokio::spawn(async move {
                                                 let path_for_hash = path.clone();
+                                                let hash_for_check = file_hash_owned.clone();
                                                 let hash_result =
                                                     match tokio::task::spawn_blocking(move || {
-                                                        compute_chunk_hashes(&path_for_hash)
+                                                        compute_merkle_tree(
+                                                            &path_for_hash,
+                                                            &hash_for_check,
+                                                        )
                                                     })
                                                     .await
                                                     {
@@ -4611,19 +6680,20 @@ async fn handle_behaviour_event(
                                                         )),
                                                     };
                                                 let response = match hash_result {
-                                                    Ok(hashes) => {
+                                                    Ok(tree) => {
                                                         // Cache the result so the next request
                                                         // hits the warm path. Lose-the-race is
                                                         // fine — another worker may have
                                                         // populated it concurrently; either
                                                         // set wins.
+                                                        let tree = Arc::new(tree);
                                                         let mut shared = shared_clone.lock().await;
                                                         if let Some(info) =
                                                             shared.get_mut(&file_hash_owned)
                                                         {
-                                                            if info.chunk_hashes.is_none() {
-                                                                info.chunk_hashes =
-                                                                    Some(hashes.clone());
+                                                            if info.merkle_tree.is_none() {
+                                                                info.merkle_tree =
+                                                                    Some(Arc::clone(&tree));
                                                             }
                                                         }
                                                         drop(shared);
@@ -4639,8 +6709,7 @@ async fn handle_behaviour_event(
                                                             request_id.clone(),
                                                             file_hash_owned,
                                                             file_name,
-                                                            file_size,
-                                                            hashes,
+                                                            &tree,
                                                             access,
                                                             &private_key,
                                                         );
@@ -4665,7 +6734,7 @@ async fn handle_behaviour_event(
                                                     }
                                                     Err(msg) => {
                                                         println!(
-                                                            "Failed to compute chunk hashes for {}: {}",
+                                                            "Failed to build Merkle tree for {}: {}",
                                                             file_hash_owned, msg
                                                         );
                                                         file_info_error_response(
@@ -4687,22 +6756,25 @@ async fn handle_behaviour_event(
                                         }
                                     }
 
-                                    // Warm path: chunk hashes are already
+                                    // Warm path: the Merkle tree is already
                                     // cached, so build + send the response
                                     // synchronously while still on the
                                     // swarm task. No I/O here — just clones
                                     // + a (cheap) signature.
                                     let response = if let Some(file_info) = shared.get(&file_hash) {
-                                        match resolve_file_access(file_info, folder_hash.as_deref())
-                                        {
+                                        match resolve_file_access(
+                                            file_info,
+                                            peer,
+                                            folder_hash.as_deref(),
+                                        ) {
                                             Ok(access) => {
-                                                let chunk_hashes =
-                                                    file_info.chunk_hashes.as_ref().unwrap();
+                                                let tree = Arc::clone(
+                                                    file_info.merkle_tree.as_ref().unwrap(),
+                                                );
                                                 let private_key = file_info.private_key.clone();
                                                 let file_name_owned = file_info.file_name.clone();
-                                                let file_size_owned = file_info.file_size;
                                                 println!("Serving FileInfo for {} ({} bytes, {} chunks, price={} wei) to peer {}",
-                                                         file_name_owned, file_size_owned, chunk_hashes.len(), access.price_wei, peer);
+                                                         file_name_owned, tree.file_size(), tree.chunk_count(), access.price_wei, peer);
 
                                                 let free_scope = if access.price_wei == 0 {
                                                     Some(payment_scope_for_access(
@@ -4715,8 +6787,7 @@ async fn handle_behaviour_event(
                                                     request_id.clone(),
                                                     file_hash,
                                                     file_name_owned,
-                                                    file_size_owned,
-                                                    chunk_hashes.clone(),
+                                                    &tree,
                                                     access,
                                                     &private_key,
                                                 );
@@ -4776,41 +6847,58 @@ async fn handle_behaviour_event(
                                     // I/Os in a row, blocking every other
                                     // peer's events the whole time.
                                     let shared = shared_files.lock().await;
-                                    let file_path = match shared.get(&file_hash) {
+                                    let (file_path, cached_tree, voucher_charged) = match shared
+                                        .get(&file_hash)
+                                    {
                                         Some(info) => {
-                                            let authorized = {
-                                                let authorized_chunks =
+                                            let access = {
+                                                let mut authorized_chunks =
                                                     seeder_authorized_chunks.lock().await;
-                                                authorized_chunks.get(&request_id).cloned()
+                                                let authorized =
+                                                    authorized_chunks.get_mut(&request_id);
+                                                match chunk_request_access_error(
+                                                    authorized.as_deref(),
+                                                    peer,
+                                                    &file_hash,
+                                                    info,
+                                                ) {
+                                                    Some(err) => Err(err),
+                                                    None => charge_voucher_allowance(
+                                                        authorized,
+                                                        chunk_index,
+                                                    ),
+                                                }
                                             };
-                                            if let Some(err) = chunk_request_access_error(
-                                                authorized.as_ref(),
-                                                peer,
-                                                &file_hash,
-                                                info,
-                                            ) {
-                                                drop(shared);
-                                                let response = ChunkResponse::Chunk {
-                                                    request_id,
-                                                    file_hash,
-                                                    chunk_index,
-                                                    chunk_data: None,
-                                                    chunk_hash: String::new(),
-                                                    error: Some(err),
-                                                };
-                                                if let Err(e) = swarm
-                                                    .behaviour_mut()
-                                                    .file_request
-                                                    .send_response(channel, response)
-                                                {
-                                                    println!(
-                                                        "Failed to send chunk response: {:?}",
-                                                        e
-                                                    );
+                                            let voucher_charged = match access {
+                                                Ok(charged) => charged,
+                                                Err(err) => {
+                                                    drop(shared);
+                                                    let response = ChunkResponse::Chunk {
+                                                        request_id,
+                                                        file_hash,
+                                                        chunk_index,
+                                                        chunk_data: None,
+                                                        proof: Vec::new(),
+                                                        error: Some(err),
+                                                    };
+                                                    if let Err(e) = swarm
+                                                        .behaviour_mut()
+                                                        .file_request
+                                                        .send_response(channel, response)
+                                                    {
+                                                        println!(
+                                                            "Failed to send chunk response: {:?}",
+                                                            e
+                                                        );
+                                                    }
+                                                    return;
                                                 }
-                                                return;
-                                            }
-                                            info.file_path.clone()
+                                            };
+                                            (
+                                                info.file_path.clone(),
+                                                info.merkle_tree.clone(),
+                                                voucher_charged,
+                                            )
                                         }
                                         None => {
                                             drop(shared);
@@ -4819,7 +6907,7 @@ async fn handle_behaviour_event(
                                                 file_hash,
                                                 chunk_index,
                                                 chunk_data: None,
-                                                chunk_hash: String::new(),
+                                                proof: Vec::new(),
                                                 error: Some("File not found".to_string()),
                                             };
                                             if let Err(e) = swarm
@@ -4835,12 +6923,70 @@ async fn handle_behaviour_event(
                                     drop(shared);
 
                                     let cmd_tx_clone = cmd_tx.clone();
+                                    let shared_clone = shared_files.clone();
+                                    let authorized_chunks = Arc::clone(seeder_authorized_chunks);
                                     let request_id = request_id.clone();
                                     let file_hash_owned = file_hash.clone();
+                                    // Busy replies have to beat the downloader's
+                                    // request timeout to be seen as busy.
+                                    let answer_by = std::time::Instant::now()
+                                        + crate::bandwidth::UPLOAD_ANSWER_BUDGET;
                                     tokio::spawn(async move {
+                                        let bandwidth = crate::bandwidth::global();
+                                        // Held until the response is handed to the swarm.
+                                        let _slot = match bandwidth.acquire_upload_slot(peer).await
+                                        {
+                                            Ok(slot) => slot,
+                                            Err(err) => {
+                                                // A busy reply must not use up the voucher.
+                                                if voucher_charged {
+                                                    refund_voucher_chunk(
+                                                        &authorized_chunks,
+                                                        &request_id,
+                                                        chunk_index,
+                                                    )
+                                                    .await;
+                                                }
+                                                let _ = cmd_tx_clone.send(
+                                                    SwarmCommand::SendChunkResponse {
+                                                        channel,
+                                                        response: ChunkResponse::Chunk {
+                                                            request_id,
+                                                            file_hash: file_hash_owned,
+                                                            chunk_index,
+                                                            chunk_data: None,
+                                                            proof: Vec::new(),
+                                                            error: Some(err),
+                                                        },
+                                                    },
+                                                );
+                                                return;
+                                            }
+                                        };
                                         let path_for_read = file_path.clone();
+                                        let hash_for_check = file_hash_owned.clone();
                                         let read_result = tokio::task::spawn_blocking(
-                                            move || -> Result<(Vec<u8>, String), String> {
+                                            move || -> Result<ProvedChunk, String> {
+                                                // The tree is normally cached by the FileInfo
+                                                // request; rebuild it if this seeder restarted
+                                                // since then.
+                                                let (tree, built) = match cached_tree {
+                                                    Some(tree) => (tree, None),
+                                                    None => {
+                                                        let tree = Arc::new(compute_merkle_tree(
+                                                            &path_for_read,
+                                                            &hash_for_check,
+                                                        )?);
+                                                        (Arc::clone(&tree), Some(tree))
+                                                    }
+                                                };
+                                                let proof =
+                                                    tree.proof(chunk_index).ok_or_else(|| {
+                                                        format!(
+                                                            "Chunk index {} out of range",
+                                                            chunk_index
+                                                        )
+                                                    })?;
                                                 let mut file = std::fs::File::open(&path_for_read)
                                                     .map_err(|e| {
                                                         format!("Failed to open file: {}", e)
@@ -4849,34 +6995,76 @@ async fn handle_behaviour_event(
                                                 file.seek(SeekFrom::Start(offset)).map_err(
                                                     |e| format!("Failed to seek: {}", e),
                                                 )?;
-                                                let mut buf = vec![0u8; CHUNK_SIZE];
-                                                let bytes_read =
-                                                    file.read(&mut buf).map_err(|e| {
+                                                let mut buf = Vec::with_capacity(CHUNK_SIZE);
+                                                file.take(CHUNK_SIZE as u64)
+                                                    .read_to_end(&mut buf)
+                                                    .map_err(|e| {
                                                         format!("Failed to read chunk: {}", e)
                                                     })?;
-                                                buf.truncate(bytes_read);
-                                                let mut hasher = Sha256::new();
-                                                hasher.update(&buf);
-                                                let chunk_hash = hex::encode(hasher.finalize());
-                                                Ok((buf, chunk_hash))
+                                                Ok((buf, proof, built))
                                             },
                                         )
                                         .await;
                                         let response = match read_result {
-                                            Ok(Ok((buf, chunk_hash))) => ChunkResponse::Chunk {
-                                                request_id,
-                                                file_hash: file_hash_owned,
-                                                chunk_index,
-                                                chunk_data: Some(buf),
-                                                chunk_hash,
-                                                error: None,
-                                            },
+                                            Ok(Ok((buf, proof, built))) => {
+                                                if let Some(tree) = built {
+                                                    let mut shared = shared_clone.lock().await;
+                                                    if let Some(info) =
+                                                        shared.get_mut(&file_hash_owned)
+                                                    {
+                                                        if info.merkle_tree.is_none() {
+                                                            info.merkle_tree = Some(tree);
+                                                        }
+                                                    }
+                                                }
+                                                match bandwidth
+                                                    .throttle_upload(
+                                                        peer,
+                                                        buf.len() as u64,
+                                                        answer_by,
+                                                    )
+                                                    .await
+                                                {
+                                                    Ok(()) => {
+                                                        crate::metrics::global().chunk_served(
+                                                            buf.len() as u64,
+                                                            FILE_REQUEST_PROTOCOL,
+                                                        );
+                                                        ChunkResponse::Chunk {
+                                                            request_id,
+                                                            file_hash: file_hash_owned,
+                                                            chunk_index,
+                                                            chunk_data: Some(buf),
+                                                            proof,
+                                                            error: None,
+                                                        }
+                                                    }
+                                                    Err(err) => {
+                                                        if voucher_charged {
+                                                            refund_voucher_chunk(
+                                                                &authorized_chunks,
+                                                                &request_id,
+                                                                chunk_index,
+                                                            )
+                                                            .await;
+                                                        }
+                                                        ChunkResponse::Chunk {
+                                                            request_id,
+                                                            file_hash: file_hash_owned,
+                                                            chunk_index,
+                                                            chunk_data: None,
+                                                            proof: Vec::new(),
+                                                            error: Some(err),
+                                                        }
+                                                    }
+                                                }
+                                            }
                                             Ok(Err(msg)) => ChunkResponse::Chunk {
                                                 request_id,
                                                 file_hash: file_hash_owned,
                                                 chunk_index,
                                                 chunk_data: None,
-                                                chunk_hash: String::new(),
+                                                proof: Vec::new(),
                                                 error: Some(msg),
                                             },
                                             Err(je) => ChunkResponse::Chunk {
@@ -4884,7 +7072,7 @@ async fn handle_behaviour_event(
                                                 file_hash: file_hash_owned,
                                                 chunk_index,
                                                 chunk_data: None,
-                                                chunk_hash: String::new(),
+                                                proof: Vec::new(),
                                                 error: Some(format!(
                                                     "Chunk read task panicked: {}",
                                                     je
@@ -4926,6 +7114,7 @@ async fn handle_behaviour_event(
                                         Some(info) => {
                                             let access = match resolve_file_access(
                                                 info,
+                                                peer,
                                                 folder_hash.as_deref(),
                                             ) {
                                                 Ok(access) => access,
@@ -5031,6 +7220,108 @@ async fn handle_behaviour_event(
                                             peer_id_for_auth,
                                         )
                                         .await;
+                                        if let ChunkResponse::PaymentAck { accepted, .. } =
+                                            &response
+                                        {
+                                            crate::metrics::global()
+                                                .payment_verification("chunk_download", *accepted);
+                                        }
+                                        let _ =
+                                            cmd_tx_clone.send(SwarmCommand::SendChunkResponse {
+                                                channel,
+                                                response,
+                                            });
+                                    });
+                                }
+                                ChunkRequest::PaymentVoucher {
+                                    request_id,
+                                    file_hash,
+                                    voucher,
+                                } => {
+                                    // A voucher that opens a download needs a
+                                    // balance lookup, so like payment proofs
+                                    // vouchers are answered off the reactor.
+                                    let cmd_tx_clone = cmd_tx.clone();
+                                    let shared_files = Arc::clone(shared_files);
+                                    let authorized_chunks = Arc::clone(seeder_authorized_chunks);
+                                    tokio::spawn(async move {
+                                        let result = receive_payment_voucher(
+                                            &shared_files,
+                                            voucher,
+                                            peer,
+                                            &request_id,
+                                            &file_hash,
+                                        )
+                                        .await;
+                                        crate::metrics::global().payment_verification(
+                                            "payment_voucher",
+                                            result.is_ok(),
+                                        );
+                                        let response = match result {
+                                            Ok(covered) => {
+                                                grant_voucher_allowance(
+                                                    &authorized_chunks,
+                                                    request_id.clone(),
+                                                    peer,
+                                                    file_hash.clone(),
+                                                    covered,
+                                                )
+                                                .await;
+                                                ChunkResponse::VoucherAck {
+                                                    request_id,
+                                                    file_hash,
+                                                    accepted: true,
+                                                    chunks_covered: covered,
+                                                    error: None,
+                                                }
+                                            }
+                                            Err(e) => {
+                                                println!(
+                                                    "❌ Payment voucher rejected for {}: {}",
+                                                    file_hash, e
+                                                );
+                                                ChunkResponse::VoucherAck {
+                                                    request_id,
+                                                    file_hash,
+                                                    accepted: false,
+                                                    chunks_covered: 0,
+                                                    error: Some(e),
+                                                }
+                                            }
+                                        };
+                                        let _ =
+                                            cmd_tx_clone.send(SwarmCommand::SendChunkResponse {
+                                                channel,
+                                                response,
+                                            });
+                                    });
+                                }
+                                ChunkRequest::VoucherSettlement {
+                                    tx_hash,
+                                    payer_address,
+                                    payee_address,
+                                    amount_wei,
+                                } => {
+                                    let cmd_tx_clone = cmd_tx.clone();
+                                    let local_peer = *swarm.local_peer_id();
+                                    tokio::spawn(async move {
+                                        let response = verify_voucher_settlement(
+                                            &tx_hash,
+                                            &payer_address,
+                                            &payee_address,
+                                            &amount_wei,
+                                            peer,
+                                            local_peer,
+                                        )
+                                        .await;
+                                        if let ChunkResponse::SettlementAck { accepted, .. } =
+                                            &response
+                                        {
+                                            crate::metrics::global().payment_verification(
+                                                "voucher_settlement",
+                                                *accepted,
+                                            );
+                                        }
                                         let _ =
                                             cmd_tx_clone.send(SwarmCommand::SendChunkResponse {
                                                 channel,
@@ -5057,19 +7348,111 @@ async fn handle_behaviour_event(
                                     file_size,
                                     chunk_size: _,
                                     total_chunks,
-                                    chunk_hashes,
+                                    merkle_root,
                                     price_wei,
                                     wallet_address,
                                     folder_hash,
+                                    voucher_interval,
                                     signature,
                                     error,
                                 } => {
+                                    // Verify the seeder's FileInfo signature. Without
+                                    // this, a hostile seeder can substitute its own
+                                    // wallet_address into the response and divert the
+                                    // buyer's payment (FM-A09). An invalid signature
+                                    // is treated like a seeder-side error so the
+                                    // downloader fails over to other seeders.
+                                    let payload = file_info_sign_payload(
+                                        &file_hash,
+                                        &file_name,
+                                        file_size,
+                                        CHUNK_SIZE as u32,
+                                        total_chunks,
+                                        &merkle_root,
+                                        &price_wei,
+                                        &wallet_address,
+                                        folder_hash.as_deref(),
+                                        voucher_interval,
+                                    );
+                                    let sig_ok = !signature.is_empty()
+                                        && !wallet_address.is_empty()
+                                        && crate::wallet::verify_signature(
+                                            &payload,
+                                            &signature,
+                                            &wallet_address,
+                                        );
+                                    // The chunk count must follow from the size, otherwise
+                                    // proofs would be checked against the wrong tree shape.
+                                    let manifest_ok = file_info_manifest_consistent(
+                                        file_size,
+                                        total_chunks,
+                                        &merkle_root,
+                                    );
+
                                     // Another seeder may have already started this download request.
-                                    // Use duplicate FileInfo responses as backup seeders for failover.
+                                    // For free downloads, a duplicate FileInfo that describes the
+                                    // same manifest joins as a parallel chunk source; anything else
+                                    // is kept as a backup seeder for failover.
                                     {
                                         let mut downloads = active_downloads.lock().await;
                                         if let Some(existing) = downloads.get_mut(&request_id) {
+                                            if decrement_file_request_attempt(
+                                                pending_file_attempts,
+                                                &request_id,
+                                            ) == 0
+                                            {
+                                                pending_file_hashes.remove(&request_id);
+                                            }
+                                            let joins_swarm = existing.allow_parallel_sources
+                                                && error.is_none()
+                                                && folder_hash.is_none()
+                                                && sig_ok
+                                                && manifest_ok
+                                                && parse_protocol_price_wei(
+                                                    &price_wei,
+                                                    "ChunkResponse::FileInfo.price_wei",
+                                                ) == Ok(0)
+                                                && file_info_matches_download(
+                                                    existing,
+                                                    file_size,
+                                                    total_chunks,
+                                                    &merkle_root,
+                                                );
+                                            if joins_swarm {
+                                                if add_chunk_source(existing, peer) {
+                                                    // Start from what this peer managed in
+                                                    // earlier sessions, if anything.
+                                                    let history =
+                                                        crate::seeder_selection::performance()
+                                                            .lock()
+                                                            .get(&peer.to_string());
+                                                    if let Some(source) =
+                                                        existing.sources.get_mut(&peer)
+                                                    {
+                                                        source.throughput_bps =
+                                                            history.and_then(|p| p.throughput_bps);
+                                                    }
+                                                    println!(
+                                                        "🧭 Seeder {} joined request {} ({} sources)",
+                                                        peer,
+                                                        request_id,
+                                                        existing.sources.len()
+                                                    );
+                                                    pump_chunk_download(
+                                                        swarm,
+                                                        outbound_request_map,
+                                                        existing,
+                                                        Instant::now(),
+                                                    );
+                                                }
+                                                return;
+                                            }
                                             if add_backup_peer(existing, peer) {
+                                                rank_backup_peers(existing, |p| {
+                                                    crate::seeder_selection::performance()
+                                                        .lock()
+                                                        .get(&p.to_string())
+                                                });
                                                 println!(
                                                     "🧭 Added backup seeder {} for request {} ({} backups total)",
                                                     peer,
@@ -5085,6 +7468,17 @@ async fn handle_behaviour_event(
                                         }
                                     }
 
+                                    // Nobody is waiting on this FileInfo any more (the
+                                    // download was cancelled or already finished), so it
+                                    // must not start a fresh download or payment.
+                                    if !pending_file_attempts.contains_key(&request_id) {
+                                        println!(
+                                            "ℹ️ Ignoring FileInfo for request {} with no outstanding attempt",
+                                            request_id
+                                        );
+                                        return;
+                                    }
+
                                     if let Some(err) = error {
                                         println!("❌ FileInfo error: {}", err);
                                         let remaining = decrement_file_request_attempt(
@@ -5110,35 +7504,13 @@ async fn handle_behaviour_event(
                                         return;
                                     }
 
-                                    // Verify the seeder's FileInfo signature. Without
-                                    // this, a hostile seeder can substitute its own
-                                    // wallet_address into the response and divert the
-                                    // buyer's payment (FM-A09). An invalid signature
-                                    // is treated like a seeder-side error so the
-                                    // downloader fails over to other seeders.
-                                    let payload = file_info_sign_payload(
-                                        &file_hash,
-                                        &file_name,
-                                        file_size,
-                                        CHUNK_SIZE as u32,
-                                        total_chunks,
-                                        &chunk_hashes,
-                                        &price_wei,
-                                        &wallet_address,
-                                        folder_hash.as_deref(),
-                                    );
-                                    let sig_ok = !signature.is_empty()
-                                        && !wallet_address.is_empty()
-                                        && crate::wallet::verify_signature(
-                                            &payload,
-                                            &signature,
-                                            &wallet_address,
-                                        );
-                                    if !sig_ok {
+                                    if !sig_ok || !manifest_ok {
                                         let reason = if signature.is_empty() {
                                             "unsigned FileInfo"
-                                        } else {
+                                        } else if !sig_ok {
                                             "FileInfo signature did not verify against wallet_address"
+                                        } else {
+                                            "FileInfo chunk count does not match its file size"
                                         };
                                         println!(
                                             "❌ {} from peer {} for {}",
@@ -5206,6 +7578,12 @@ async fn handle_behaviour_event(
                                     pending_file_hashes.remove(&request_id);
 
                                     println!("📋 Received FileInfo: {} ({} bytes, {} chunks, price={} wei)", file_name, file_size, total_chunks, price);
+                                    if !merkle_root.eq_ignore_ascii_case(&file_hash) {
+                                        println!(
+                                            "ℹ️ {} is a legacy content hash; chunks are checked against the seeder-signed root {}",
+                                            file_hash, merkle_root
+                                        );
+                                    }
 
                                     // Determine output path
                                     let custom_dir = download_directory.lock().await.clone();
@@ -5229,10 +7607,47 @@ async fn handle_behaviour_event(
                                     } else {
                                         file_name.clone()
                                     };
-                                    let output_path = downloads_dir.join(&output_name);
-
-                                    // Create empty output file
-                                    if let Err(e) = std::fs::File::create(&output_path) {
+                                    // A download interrupted by a restart continues in its
+                                    // original file if the seeder still describes the same
+                                    // bytes; the chunks verified on startup are kept.
+                                    let resumed =
+                                        resumed_downloads.lock().await.remove(&request_id).filter(
+                                            |r| {
+                                                r.record.same_content(
+                                                    &file_hash,
+                                                    file_size,
+                                                    &merkle_root,
+                                                    folder_hash.as_deref(),
+                                                )
+                                            },
+                                        );
+                                    let output_path = match &resumed {
+                                        Some(r) => r.record.output_path.clone(),
+                                        None => downloads_dir.join(&output_name),
+                                    };
+                                    let reused_payment = resumed
+                                        .as_ref()
+                                        .filter(|r| {
+                                            price > 0
+                                                && r.record.payment_reusable(
+                                                    &peer.to_string(),
+                                                    &price_wei,
+                                                    &wallet_address,
+                                                )
+                                        })
+                                        .and_then(|r| r.record.payment.clone());
+
+                                    // Create empty output file (or reopen the partial one)
+                                    let opened = if resumed.is_some() {
+                                        std::fs::OpenOptions::new()
+                                            .write(true)
+                                            .create(true)
+                                            .truncate(false)
+                                            .open(&output_path)
+                                    } else {
+                                        std::fs::File::create(&output_path)
+                                    };
+                                    if let Err(e) = opened {
                                         println!("❌ Failed to create output file: {}", e);
                                         let _ = events.emit("file-download-failed", serde_json::json!({
                                             "requestId": request_id,
@@ -5242,6 +7657,47 @@ async fn handle_behaviour_event(
                                         return;
                                     }
 
+                                    if resumed.is_none() {
+                                        crate::pending_downloads::clear_verified_chunks(
+                                            &request_id,
+                                        );
+                                    }
+                                    crate::pending_downloads::save_pending_download(
+                                        &crate::pending_downloads::PendingDownload {
+                                            request_id: request_id.clone(),
+                                            file_hash: file_hash.clone(),
+                                            file_name: file_name.clone(),
+                                            file_size,
+                                            chunk_size: CHUNK_SIZE as u32,
+                                            total_chunks,
+                                            merkle_root: merkle_root.clone(),
+                                            price_wei: price_wei.clone(),
+                                            wallet_address: wallet_address.clone(),
+                                            folder_hash: folder_hash.clone(),
+                                            signature: signature.clone(),
+                                            voucher_interval,
+                                            output_path: output_path.clone(),
+                                            peer_id: peer.to_string(),
+                                            payment: reused_payment.clone(),
+                                            updated_at: crate::drive_storage::now_secs()
+                                                .unwrap_or(0),
+                                        },
+                                    );
+
+                                    let (received_chunks, bytes_written) = match resumed {
+                                        Some(r)
+                                            if r.received_chunks.len() == total_chunks as usize =>
+                                        {
+                                            (r.received_chunks, r.verified_bytes)
+                                        }
+                                        _ => (vec![false; total_chunks as usize], 0),
+                                    };
+                                    let first_missing_chunk = received_chunks
+                                        .iter()
+                                        .position(|received| !received)
+                                        .unwrap_or(received_chunks.len())
+                                        as u32;
+
                                     // Create active download state
                                     let download = ActiveChunkedDownload {
                                         request_id: request_id.clone(),
@@ -5249,16 +7705,22 @@ async fn handle_behaviour_event(
                                         file_name: output_name,
                                         file_size,
                                         total_chunks,
-                                        chunk_hashes,
-                                        received_chunks: vec![false; total_chunks as usize],
+                                        merkle_root,
+                                        received_chunks,
                                         output_path: output_path.clone(),
-                                        bytes_written: 0,
+                                        bytes_written,
                                         peer_id: peer,
                                         backup_peers: vec![],
+                                        sources: HashMap::from([(peer, ChunkSource::default())]),
+                                        allow_parallel_sources: price == 0 && folder_hash.is_none(),
+                                        in_flight: HashMap::new(),
+                                        outbound_chunks: HashMap::new(),
+                                        retry_after: HashMap::new(),
+                                        first_missing_chunk,
                                         retry_counts: vec![0u8; total_chunks as usize],
-                                        current_chunk_index: 0,
                                         start_time: std::time::Instant::now(),
                                         payment_confirmed: price == 0,
+                                        vouchers: None,
                                     };
 
                                     {
@@ -5288,7 +7750,26 @@ async fn handle_behaviour_event(
                                             creds_map.get(&request_id).cloned()
                                         };
 
-                                        if let Some(creds) = creds {
+                                        if let Some(payment) = reused_payment {
+                                            // Already paid before the restart; the seeder
+                                            // re-authorizes the same payer and peer.
+                                            println!(
+                                                "💳 Resuming {} with earlier payment tx {}",
+                                                file_hash, payment.payment_tx
+                                            );
+                                            let request = ChunkRequest::PaymentProof {
+                                                request_id: request_id.clone(),
+                                                file_hash: file_hash.clone(),
+                                                folder_hash: folder_hash.clone(),
+                                                payment_tx: payment.payment_tx,
+                                                payer_address: payment.payer_address,
+                                            };
+                                            let req_id = swarm
+                                                .behaviour_mut()
+                                                .file_request
+                                                .send_request(&peer, request);
+                                            outbound_request_map.insert(req_id, request_id);
+                                        } else if let Some(creds) = creds {
                                             if let Some(folder_hash_for_payment) =
                                                 folder_hash.clone()
                                             {
@@ -5314,8 +7795,7 @@ async fn handle_behaviour_event(
                                                         active_downloads.lock().await;
                                                     if let Some(dl) = downloads.remove(&request_id)
                                                     {
-                                                        let _ =
-                                                            std::fs::remove_file(&dl.output_path);
+                                                        discard_partial_download(&dl);
                                                     }
                                                     let _ = events.emit("file-download-failed", serde_json::json!({
                                                         "requestId": request_id,
@@ -5326,6 +7806,11 @@ async fn handle_behaviour_event(
                                                 }
 
                                                 let payment_tx = payment_tx.unwrap();
+                                                crate::pending_downloads::record_pending_download_payment(
+                                                    &request_id,
+                                                    &payment_tx,
+                                                    &creds.wallet_address,
+                                                );
                                                 let _ = events.emit(
                                                     "chiraldrop-payment-sent",
                                                     serde_json::json!({
@@ -5351,6 +7836,56 @@ async fn handle_behaviour_event(
                                                     .file_request
                                                     .send_request(&peer, request);
                                                 outbound_request_map.insert(req_id, request_id);
+                                            } else if creds.pay_with_vouchers
+                                                && voucher_interval > 0
+                                            {
+                                                // Pay as chunks arrive; the signed vouchers
+                                                // are settled on chain in batches later.
+                                                let mut downloads = active_downloads.lock().await;
+                                                let Some(dl) = downloads.get_mut(&request_id)
+                                                else {
+                                                    return;
+                                                };
+                                                let charged = dl
+                                                    .received_chunks
+                                                    .iter()
+                                                    .enumerate()
+                                                    .filter(|(_, received)| **received)
+                                                    .map(|(index, _)| index as u32)
+                                                    .collect();
+                                                dl.vouchers = Some(VoucherProgress {
+                                                    terms: crate::micropayments::VoucherTerms {
+                                                        session_id: request_id.clone(),
+                                                        payer: creds.wallet_address.clone(),
+                                                        payee: wallet_address.clone(),
+                                                        file_hash: file_hash.clone(),
+                                                        price_wei: price.to_string(),
+                                                        total_chunks,
+                                                    },
+                                                    private_key: creds.private_key.clone(),
+                                                    payee_peer: peer,
+                                                    interval: voucher_interval,
+                                                    acked: 0,
+                                                    pending: None,
+                                                    awaiting_settlement: None,
+                                                    charged,
+                                                });
+                                                if let Err(e) = send_voucher_top_up(
+                                                    swarm,
+                                                    outbound_request_map,
+                                                    dl,
+                                                ) {
+                                                    println!("❌ Payment voucher failed: {}", e);
+                                                    if let Some(dl) = downloads.remove(&request_id)
+                                                    {
+                                                        discard_partial_download(&dl);
+                                                    }
+                                                    events.emit("file-download-failed", serde_json::json!({
+                                                        "requestId": request_id,
+                                                        "fileHash": file_hash,
+                                                        "error": format!("Payment voucher failed: {}", e)
+                                                    }));
+                                                }
                                             } else {
                                                 // Split: 99.5% to seeder, 0.5% platform fee
                                                 let (seller_wei, fee_wei) =
@@ -5376,6 +7911,13 @@ async fn handle_behaviour_event(
                                                             "💰 Payment sent: tx={}",
                                                             payment.tx_hash
                                                         );
+                                                        // Persist the tx before anything else so a
+                                                        // restart resumes instead of paying again.
+                                                        crate::pending_downloads::record_pending_download_payment(
+                                                            &request_id,
+                                                            &payment.tx_hash,
+                                                            &creds.wallet_address,
+                                                        );
                                                         // Send platform fee (best-effort)
                                                         if fee_wei > 0 {
                                                             let _ = crate::wallet::send_payment(
@@ -5425,9 +7967,7 @@ async fn handle_behaviour_event(
                                                         if let Some(dl) =
                                                             downloads.remove(&request_id)
                                                         {
-                                                            let _ = std::fs::remove_file(
-                                                                &dl.output_path,
-                                                            );
+                                                            discard_partial_download(&dl);
                                                         }
                                                         let _ = events.emit("file-download-failed", serde_json::json!({
                                                         "requestId": request_id,
@@ -5441,7 +7981,7 @@ async fn handle_behaviour_event(
                                             println!("❌ No wallet credentials for paid download");
                                             let mut downloads = active_downloads.lock().await;
                                             if let Some(dl) = downloads.remove(&request_id) {
-                                                let _ = std::fs::remove_file(&dl.output_path);
+                                                discard_partial_download(&dl);
                                             }
                                             let _ = events.emit("file-download-failed", serde_json::json!({
                                                 "requestId": request_id,
@@ -5449,18 +7989,48 @@ async fn handle_behaviour_event(
                                                 "error": "Wallet not connected. Connect your wallet to download paid files."
                                             }));
                                         }
-                                    } else {
-                                        // Free file: request the first chunk immediately
-                                        let request = ChunkRequest::Chunk {
-                                            request_id: request_id.clone(),
-                                            file_hash: file_hash.clone(),
-                                            chunk_index: 0,
-                                        };
-                                        let req_id = swarm
-                                            .behaviour_mut()
-                                            .file_request
-                                            .send_request(&peer, request);
-                                        outbound_request_map.insert(req_id, request_id);
+                                    } else {
+                                        // Free file: fill the request window immediately and
+                                        // ask the other providers to help serve it.
+                                        let mut downloads = active_downloads.lock().await;
+                                        if let Some(dl) = downloads.get_mut(&request_id) {
+                                            if chunked_download_complete(dl) {
+                                                // Resumed with every chunk already on disk.
+                                                let Some(dl) = downloads.remove(&request_id) else {
+                                                    return;
+                                                };
+                                                drop(downloads);
+                                                notify_drop_completed(
+                                                    swarm,
+                                                    file_transfer_service,
+                                                    &dl.request_id,
+                                                )
+                                                .await;
+                                                spawn_full_file_verification(
+                                                    events,
+                                                    dl.request_id,
+                                                    dl.file_hash,
+                                                    dl.file_name,
+                                                    dl.file_size,
+                                                    dl.output_path,
+                                                );
+                                                return;
+                                            }
+                                            pump_chunk_download(
+                                                swarm,
+                                                outbound_request_map,
+                                                dl,
+                                                Instant::now(),
+                                            );
+                                            if dl.allow_parallel_sources {
+                                                discover_additional_chunk_sources(
+                                                    cmd_tx,
+                                                    request_id.clone(),
+                                                    file_hash.clone(),
+                                                    vec![peer, *swarm.local_peer_id()],
+                                                );
+                                            }
+                                        }
                                     }
                                 }
                                 ChunkResponse::Chunk {
@@ -5468,391 +8038,248 @@ async fn handle_behaviour_event(
                                     file_hash,
                                     chunk_index,
                                     chunk_data,
-                                    chunk_hash,
+                                    proof,
                                     error,
                                 } => {
-                                    if let Some(err) = error {
-                                        println!("❌ Chunk {} error: {}", chunk_index, err);
-                                        // Check if we should retry
-                                        let mut downloads = active_downloads.lock().await;
-                                        let mut failover: Option<(
-                                            PeerId,
-                                            String,
-                                            String,
-                                            u32,
-                                            String,
-                                        )> = None;
-                                        if let Some(dl) = downloads.get_mut(&request_id) {
-                                            dl.retry_counts[chunk_index as usize] += 1;
-                                            let attempt = dl.retry_counts[chunk_index as usize];
-                                            if attempt <= MAX_CHUNK_RETRIES {
-                                                println!(
-                                                    "🔄 Retrying chunk {} (attempt {}/{})",
-                                                    chunk_index, attempt, MAX_CHUNK_RETRIES
-                                                );
-                                                let peer_id = dl.peer_id.clone();
-                                                let fh = dl.file_hash.clone();
-                                                let rid = dl.request_id.clone();
-                                                drop(downloads);
-                                                wait_for_chunk_retry_delay(attempt, chunk_index)
-                                                    .await;
-                                                let request = ChunkRequest::Chunk {
-                                                    request_id: rid.clone(),
-                                                    file_hash: fh,
-                                                    chunk_index,
-                                                };
-                                                let req_id = swarm
-                                                    .behaviour_mut()
-                                                    .file_request
-                                                    .send_request(&peer_id, request);
-                                                outbound_request_map.insert(req_id, rid);
-                                                return;
-                                            } else {
-                                                if let Some(next_peer) = take_next_backup_peer(dl) {
-                                                    let previous_peer = dl.peer_id.clone();
-                                                    dl.peer_id = next_peer.clone();
-                                                    dl.current_chunk_index = chunk_index;
-                                                    dl.retry_counts[chunk_index as usize] = 0;
-                                                    failover = Some((
-                                                        next_peer,
-                                                        dl.file_hash.clone(),
-                                                        dl.request_id.clone(),
-                                                        chunk_index,
-                                                        previous_peer.to_string(),
-                                                    ));
-                                                } else {
-                                                    // Max retries exceeded — abort
-                                                    let Some(dl) =
-                                                        remove_active_download_for_cleanup(
-                                                            &mut downloads,
-                                                            &request_id,
-                                                            "chunk error retry exhaustion",
-                                                        )
-                                                    else {
-                                                        drop(downloads);
-                                                        return;
-                                                    };
-                                                    let _ = std::fs::remove_file(&dl.output_path);
-                                                    drop(downloads);
-                                                    let _ = events.emit("file-download-failed", serde_json::json!({
-                                                        "requestId": request_id,
-                                                        "fileHash": file_hash,
-                                                        "error": format!("Chunk {} failed after {} retries: {}", chunk_index, MAX_CHUNK_RETRIES, err)
-                                                    }));
-                                                    return;
-                                                }
-                                            }
-                                        }
-                                        drop(downloads);
-                                        if let Some((next_peer, fh, rid, idx, previous_peer)) =
-                                            failover
-                                        {
-                                            println!(
-                                                "🔁 Failing over request {} from {} to {} at chunk {}",
-                                                rid, previous_peer, next_peer, idx
-                                            );
-                                            let _ = events.emit(
-                                                "download-peer-failover",
-                                                serde_json::json!({
-                                                    "requestId": rid,
-                                                    "fileHash": fh,
-                                                    "fromPeer": previous_peer,
-                                                    "toPeer": next_peer.to_string(),
-                                                    "chunkIndex": idx,
-                                                }),
-                                            );
-                                            let request = ChunkRequest::Chunk {
-                                                request_id: rid.clone(),
-                                                file_hash: fh,
-                                                chunk_index: idx,
-                                            };
-                                            let req_id = swarm
-                                                .behaviour_mut()
-                                                .file_request
-                                                .send_request(&next_peer, request);
-                                            outbound_request_map.insert(req_id, rid);
-                                        }
+                                    if let Some(data) = &chunk_data {
+                                        crate::bandwidth::global()
+                                            .record_download(data.len() as u64);
+                                        crate::metrics::global().record_protocol_bytes(
+                                            FILE_REQUEST_PROTOCOL,
+                                            crate::metrics::Direction::In,
+                                            data.len() as u64,
+                                        );
+                                    }
+                                    let mut downloads = active_downloads.lock().await;
+                                    let Some(dl) = downloads.get_mut(&request_id) else {
+                                        return;
+                                    };
+                                    // Only accept a chunk on the outbound request that asked
+                                    // for it. Without this, a hostile seeder can push chunks
+                                    // nobody requested from it and waste bandwidth until the
+                                    // inclusion proof catches the corrupt result.
+                                    let requested = dl.outbound_chunks.remove(&outbound_req_id);
+                                    if requested.map(|(idx, _)| idx) != Some(chunk_index)
+                                        || chunk_index >= dl.total_chunks
+                                    {
+                                        println!(
+                                            "❌ Unsolicited chunk {} from peer {} for request {}; dropping",
+                                            chunk_index, peer, request_id
+                                        );
+                                        return;
+                                    }
+                                    if dl.received_chunks[chunk_index as usize] {
+                                        // A reassigned straggler was answered twice; the
+                                        // first verified copy already won.
                                         return;
                                     }
 
-                                    let chunk_data = match chunk_data {
-                                        Some(data) => data,
-                                        None => {
-                                            println!(
-                                                "❌ Chunk {} has no data and no error",
-                                                chunk_index
-                                            );
-                                            return;
+                                    let (chunk_data, leaf) = match verify_chunk_payload(
+                                        dl,
+                                        chunk_index,
+                                        chunk_data,
+                                        &proof,
+                                        error,
+                                    ) {
+                                        Ok(verified) => {
+                                            crate::metrics::global().chunk_received();
+                                            verified
                                         }
-                                    };
-
-                                    // Verify chunk hash against manifest
-                                    let mut downloads = active_downloads.lock().await;
-                                    if let Some(dl) = downloads.get_mut(&request_id) {
-                                        // Reject out-of-sequence chunks. Without this, a hostile
-                                        // seeder can answer chunk N=last first, skipping forward
-                                        // and writing an out-of-order file. The full-file hash
-                                        // catches the corrupt result, but only after wasting
-                                        // bandwidth on the partial download. Bound the damage.
-                                        if (chunk_index as usize) >= dl.chunk_hashes.len()
-                                            || chunk_index != dl.current_chunk_index
+                                        Err(reason)
+                                            if reason == crate::bandwidth::SEEDER_BUSY_ERROR =>
                                         {
                                             println!(
-                                                "❌ Chunk {} arrived out-of-sequence (expected {}); dropping",
-                                                chunk_index, dl.current_chunk_index
+                                                "⏳ Peer {} is busy; retrying chunk {} later",
+                                                peer, chunk_index
+                                            );
+                                            record_chunk_busy(
+                                                dl,
+                                                chunk_index,
+                                                peer,
+                                                Instant::now(),
+                                            );
+                                            pump_chunk_download(
+                                                swarm,
+                                                outbound_request_map,
+                                                dl,
+                                                Instant::now(),
                                             );
                                             return;
                                         }
-                                        let expected_hash = &dl.chunk_hashes[chunk_index as usize];
-                                        let mut hasher = Sha256::new();
-                                        hasher.update(&chunk_data);
-                                        let computed_hash = hex::encode(hasher.finalize());
-
-                                        if computed_hash != *expected_hash
-                                            || computed_hash != chunk_hash
-                                        {
+                                        Err(reason) => {
                                             println!(
-                                                "❌ Chunk {} hash mismatch! Expected: {}, Got: {}",
-                                                chunk_index, expected_hash, computed_hash
+                                                "❌ Chunk {} from peer {} failed: {}",
+                                                chunk_index, peer, reason
                                             );
-                                            dl.retry_counts[chunk_index as usize] += 1;
-                                            let attempt = dl.retry_counts[chunk_index as usize];
-                                            if attempt <= MAX_CHUNK_RETRIES {
-                                                println!(
-                                                    "🔄 Retrying chunk {} due to hash mismatch (attempt {}/{})",
-                                                    chunk_index,
-                                                    attempt,
-                                                    MAX_CHUNK_RETRIES
-                                                );
-                                                let peer_id = dl.peer_id.clone();
-                                                let fh = dl.file_hash.clone();
-                                                let rid = dl.request_id.clone();
-                                                drop(downloads);
-                                                wait_for_chunk_retry_delay(attempt, chunk_index)
-                                                    .await;
-                                                let request = ChunkRequest::Chunk {
-                                                    request_id: rid.clone(),
-                                                    file_hash: fh,
-                                                    chunk_index,
-                                                };
-                                                let req_id = swarm
-                                                    .behaviour_mut()
-                                                    .file_request
-                                                    .send_request(&peer_id, request);
-                                                outbound_request_map.insert(req_id, rid);
-                                            } else {
-                                                if let Some(next_peer) = take_next_backup_peer(dl) {
-                                                    let previous_peer = dl.peer_id.clone();
-                                                    dl.peer_id = next_peer.clone();
-                                                    dl.current_chunk_index = chunk_index;
-                                                    dl.retry_counts[chunk_index as usize] = 0;
-                                                    let fh = dl.file_hash.clone();
-                                                    let rid = dl.request_id.clone();
-                                                    drop(downloads);
+                                            match record_chunk_failure(
+                                                dl,
+                                                chunk_index,
+                                                peer,
+                                                Instant::now(),
+                                            ) {
+                                                ChunkFailureOutcome::Retry { attempt } => {
                                                     println!(
-                                                        "🔁 Failing over request {} from {} to {} at chunk {} (hash mismatch)",
-                                                        rid, previous_peer, next_peer, chunk_index
-                                                    );
-                                                    let _ = events.emit(
-                                                        "download-peer-failover",
-                                                        serde_json::json!({
-                                                            "requestId": rid,
-                                                            "fileHash": fh,
-                                                            "fromPeer": previous_peer.to_string(),
-                                                            "toPeer": next_peer.to_string(),
-                                                            "chunkIndex": chunk_index,
-                                                        }),
+                                                        "🔄 Retrying chunk {} (attempt {}/{})",
+                                                        chunk_index, attempt, MAX_CHUNK_RETRIES
                                                     );
-                                                    let request = ChunkRequest::Chunk {
-                                                        request_id: rid.clone(),
-                                                        file_hash: fh,
+                                                }
+                                                ChunkFailureOutcome::Failover { from, to } => {
+                                                    record_failed_source(&from);
+                                                    emit_chunk_source_failover(
+                                                        events,
+                                                        dl,
+                                                        from,
+                                                        to,
                                                         chunk_index,
-                                                    };
-                                                    let req_id = swarm
-                                                        .behaviour_mut()
-                                                        .file_request
-                                                        .send_request(&next_peer, request);
-                                                    outbound_request_map.insert(req_id, rid);
-                                                } else {
+                                                        &reason,
+                                                    );
+                                                }
+                                                ChunkFailureOutcome::Exhausted => {
+                                                    record_failed_source(&peer);
                                                     let Some(dl) =
                                                         remove_active_download_for_cleanup(
                                                             &mut downloads,
                                                             &request_id,
-                                                            "chunk hash retry exhaustion",
+                                                            "chunk retry exhaustion",
                                                         )
                                                     else {
-                                                        drop(downloads);
                                                         return;
                                                     };
-                                                    let _ = std::fs::remove_file(&dl.output_path);
+                                                    discard_partial_download(&dl);
                                                     drop(downloads);
                                                     let _ = events.emit("file-download-failed", serde_json::json!({
                                                         "requestId": request_id,
                                                         "fileHash": file_hash,
-                                                        "error": format!("Chunk {} failed integrity check after {} retries", chunk_index, MAX_CHUNK_RETRIES)
+                                                        "error": format!("Chunk {} failed after {} retries: {}", chunk_index, MAX_CHUNK_RETRIES, reason)
                                                     }));
+                                                    return;
                                                 }
                                             }
-                                            return;
-                                        }
-
-                                        // Write chunk to file (append)
-                                        use std::io::Write;
-                                        let write_result = std::fs::OpenOptions::new()
-                                            .append(true)
-                                            .open(&dl.output_path)
-                                            .and_then(|mut f| f.write_all(&chunk_data));
-
-                                        if let Err(e) = write_result {
-                                            println!(
-                                                "❌ Failed to write chunk {}: {}",
-                                                chunk_index, e
-                                            );
-                                            let Some(dl) = remove_active_download_for_cleanup(
-                                                &mut downloads,
-                                                &request_id,
-                                                "chunk write failure",
-                                            ) else {
-                                                drop(downloads);
-                                                return;
-                                            };
-                                            let _ = std::fs::remove_file(&dl.output_path);
-                                            drop(downloads);
-                                            let _ = events.emit(
-                                                "file-download-failed",
-                                                serde_json::json!({
-                                                    "requestId": request_id,
-                                                    "fileHash": file_hash,
-                                                    "error": format!("Failed to write chunk: {}", e)
-                                                }),
+                                            pump_chunk_download(
+                                                swarm,
+                                                outbound_request_map,
+                                                dl,
+                                                Instant::now(),
                                             );
                                             return;
                                         }
+                                    };
 
-                                        dl.received_chunks[chunk_index as usize] = true;
-                                        dl.bytes_written += chunk_data.len() as u64;
-                                        dl.current_chunk_index = chunk_index + 1;
+                                    // Chunks arrive out of order from several sources, so
+                                    // each one is written at its own offset.
+                                    use std::io::Write;
+                                    let offset = chunk_index as u64 * CHUNK_SIZE as u64;
+                                    let write_result = std::fs::OpenOptions::new()
+                                        .write(true)
+                                        .open(&dl.output_path)
+                                        .and_then(|mut f| {
+                                            f.seek(SeekFrom::Start(offset))?;
+                                            f.write_all(&chunk_data)
+                                        });
 
-                                        // Emit progress
-                                        let progress =
-                                            (dl.bytes_written as f64 / dl.file_size as f64) * 100.0;
-                                        let elapsed = dl.start_time.elapsed().as_secs_f64();
-                                        let speed_bps = if elapsed > 0.0 {
-                                            (dl.bytes_written as f64 / elapsed) as u64
-                                        } else {
-                                            0
+                                    if let Err(e) = write_result {
+                                        println!("❌ Failed to write chunk {}: {}", chunk_index, e);
+                                        let Some(dl) = remove_active_download_for_cleanup(
+                                            &mut downloads,
+                                            &request_id,
+                                            "chunk write failure",
+                                        ) else {
+                                            return;
                                         };
+                                        discard_partial_download(&dl);
+                                        drop(downloads);
                                         let _ = events.emit(
-                                            "download-progress",
+                                            "file-download-failed",
                                             serde_json::json!({
-                                                "requestId": dl.request_id,
-                                                "fileHash": dl.file_hash,
-                                                "fileName": dl.file_name,
-                                                "bytesWritten": dl.bytes_written,
-                                                "totalBytes": dl.file_size,
-                                                "speedBps": speed_bps,
-                                                "progress": progress
+                                                "requestId": request_id,
+                                                "fileHash": file_hash,
+                                                "error": format!("Failed to write chunk: {}", e)
                                             }),
                                         );
+                                        return;
+                                    }
+                                    // Journal the verified leaf so a restart can re-check
+                                    // this chunk on disk without the full manifest.
+                                    crate::pending_downloads::record_verified_chunk(
+                                        &request_id,
+                                        chunk_index,
+                                        &leaf,
+                                    );
 
-                                        // Check if all chunks received
-                                        if dl.current_chunk_index >= dl.total_chunks {
-                                            // All chunks received — verify full file hash
-                                            let output_path = dl.output_path.clone();
-                                            let expected_file_hash = dl.file_hash.clone();
-                                            let request_id_clone = dl.request_id.clone();
-                                            let file_name_clone = dl.file_name.clone();
-                                            let file_size = dl.file_size;
-                                            let _ = downloads.remove(&request_id);
-                                            drop(downloads);
-
-                                            // Verify full file SHA-256
-                                            let events_clone = events.clone();
-                                            tokio::spawn(async move {
-                                                match tokio::task::spawn_blocking(move || {
-                                                    let mut file =
-                                                        std::fs::File::open(&output_path)?;
-                                                    let mut hasher = Sha256::new();
-                                                    let mut buf = vec![0u8; 256 * 1024];
-                                                    loop {
-                                                        let n = file.read(&mut buf)?;
-                                                        if n == 0 {
-                                                            break;
-                                                        }
-                                                        hasher.update(&buf[..n]);
-                                                    }
-                                                    Ok::<(String, PathBuf), std::io::Error>((
-                                                        hex::encode(hasher.finalize()),
-                                                        output_path,
-                                                    ))
-                                                })
-                                                .await
-                                                {
-                                                    Ok(Ok((computed_hash, output_path))) => {
-                                                        if computed_hash == expected_file_hash {
-                                                            println!(
-                                                                "✅ File verified and saved: {:?}",
-                                                                output_path
-                                                            );
-                                                            events_clone.emit("file-download-complete", serde_json::json!({
-                                                                "requestId": request_id_clone,
-                                                                "fileHash": expected_file_hash,
-                                                                "fileName": file_name_clone,
-                                                                "filePath": output_path.to_string_lossy(),
-                                                                "fileSize": file_size,
-                                                                "status": "completed"
-                                                            }));
-                                                        } else {
-                                                            println!("❌ Full file hash mismatch! Expected: {}, Got: {}", expected_file_hash, computed_hash);
-                                                            let _ =
-                                                                std::fs::remove_file(&output_path);
-                                                            events_clone.emit("file-download-failed", serde_json::json!({
-                                                                "requestId": request_id_clone,
-                                                                "fileHash": expected_file_hash,
-                                                                "error": "File integrity verification failed — hash mismatch after all chunks received"
-                                                            }));
-                                                        }
-                                                    }
-                                                    Ok(Err(e)) => {
-                                                        println!("❌ Failed to verify file: {}", e);
-                                                        events_clone.emit("file-download-failed", serde_json::json!({
-                                                            "requestId": request_id_clone,
-                                                            "fileHash": expected_file_hash,
-                                                            "error": format!("Failed to verify file: {}", e)
-                                                        }));
-                                                    }
-                                                    Err(e) => {
-                                                        println!(
-                                                            "❌ Verification task panicked: {}",
-                                                            e
-                                                        );
-                                                        events_clone.emit("file-download-failed", serde_json::json!({
-                                                            "requestId": request_id_clone,
-                                                            "fileHash": expected_file_hash,
-                                                            "error": format!("Verification task failed: {}", e)
-                                                        }));
-                                                    }
-                                                }
-                                            });
-                                        } else {
-                                            // Request next chunk
-                                            let peer_id = dl.peer_id;
-                                            let fh = dl.file_hash.clone();
-                                            let rid = dl.request_id.clone();
-                                            let next_index = dl.current_chunk_index;
-                                            drop(downloads);
-
-                                            let request = ChunkRequest::Chunk {
-                                                request_id: rid.clone(),
-                                                file_hash: fh,
-                                                chunk_index: next_index,
-                                            };
-                                            let req_id = swarm
-                                                .behaviour_mut()
-                                                .file_request
-                                                .send_request(&peer_id, request);
-                                            outbound_request_map.insert(req_id, rid);
+                                    mark_chunk_received(
+                                        dl,
+                                        chunk_index,
+                                        peer,
+                                        chunk_data.len(),
+                                        Instant::now(),
+                                    );
+
+                                    // Emit progress
+                                    let progress =
+                                        (dl.bytes_written as f64 / dl.file_size as f64) * 100.0;
+                                    let elapsed = dl.start_time.elapsed().as_secs_f64();
+                                    let speed_bps = if elapsed > 0.0 {
+                                        (dl.bytes_written as f64 / elapsed) as u64
+                                    } else {
+                                        0
+                                    };
+                                    events.emit(
+                                        "download-progress",
+                                        serde_json::json!({
+                                            "requestId": dl.request_id,
+                                            "fileHash": dl.file_hash,
+                                            "fileName": dl.file_name,
+                                            "bytesWritten": dl.bytes_written,
+                                            "totalBytes": dl.file_size,
+                                            "speedBps": speed_bps,
+                                            "progress": progress,
+                                            "activeSources": dl.sources.len()
+                                        }),
+                                    );
+
+                                    // Check if all chunks received
+                                    if chunked_download_complete(dl) {
+                                        // All chunks received — verify full file hash
+                                        let output_path = dl.output_path.clone();
+                                        let expected_file_hash = dl.file_hash.clone();
+                                        let request_id_clone = dl.request_id.clone();
+                                        let file_name_clone = dl.file_name.clone();
+                                        let file_size = dl.file_size;
+                                        record_source_sessions(dl);
+                                        let vouchers = downloads
+                                            .remove(&request_id)
+                                            .and_then(|dl| dl.vouchers);
+                                        drop(downloads);
+                                        notify_drop_completed(
+                                            swarm,
+                                            file_transfer_service,
+                                            &request_id_clone,
+                                        )
+                                        .await;
+                                        if let Some(vouchers) = vouchers {
+                                            spawn_voucher_settlement(
+                                                cmd_tx,
+                                                vouchers.terms.payer,
+                                                vouchers.private_key,
+                                                false,
+                                            );
                                         }
+
+                                        spawn_full_file_verification(
+                                            events,
+                                            request_id_clone,
+                                            expected_file_hash,
+                                            file_name_clone,
+                                            file_size,
+                                            output_path,
+                                        );
+                                    } else {
+                                        pump_chunk_download(
+                                            swarm,
+                                            outbound_request_map,
+                                            dl,
+                                            Instant::now(),
+                                        );
                                     }
                                 }
                                 ChunkResponse::PaymentAck {
@@ -5871,7 +8298,7 @@ async fn handle_behaviour_event(
                                         );
                                         let mut downloads = active_downloads.lock().await;
                                         if let Some(dl) = downloads.remove(&request_id) {
-                                            let _ = std::fs::remove_file(&dl.output_path);
+                                            discard_partial_download(&dl);
                                         }
                                         let _ = events.emit(
                                             "file-download-failed",
@@ -5889,25 +8316,148 @@ async fn handle_behaviour_event(
                                         file_hash
                                     );
 
-                                    // Mark payment confirmed and request first chunk
+                                    // Mark payment confirmed and fill the request window
+                                    // from the paid seeder.
                                     let mut downloads = active_downloads.lock().await;
                                     if let Some(dl) = downloads.get_mut(&request_id) {
                                         dl.payment_confirmed = true;
-                                        let peer_id = dl.peer_id;
-                                        let fh = dl.file_hash.clone();
-                                        let rid = dl.request_id.clone();
-                                        drop(downloads);
-
-                                        let request = ChunkRequest::Chunk {
-                                            request_id: rid.clone(),
-                                            file_hash: fh,
-                                            chunk_index: 0,
-                                        };
-                                        let req_id = swarm
-                                            .behaviour_mut()
-                                            .file_request
-                                            .send_request(&peer_id, request);
-                                        outbound_request_map.insert(req_id, rid);
+                                        pump_chunk_download(
+                                            swarm,
+                                            outbound_request_map,
+                                            dl,
+                                            Instant::now(),
+                                        );
+                                    }
+                                }
+                                ChunkResponse::VoucherAck {
+                                    request_id,
+                                    file_hash,
+                                    accepted,
+                                    chunks_covered,
+                                    error,
+                                } => {
+                                    let mut downloads = active_downloads.lock().await;
+                                    if !accepted {
+                                        let err_msg = error.unwrap_or_else(|| {
+                                            "Payment voucher rejected by seeder".to_string()
+                                        });
+                                        if err_msg
+                                            == crate::micropayments::VOUCHER_CREDIT_LIMIT_ERROR
+                                        {
+                                            if let Some(vouchers) = downloads
+                                                .get_mut(&request_id)
+                                                .and_then(|dl| dl.vouchers.as_mut())
+                                            {
+                                                // Settle what we owe; the scheduler
+                                                // re-sends the voucher once the seeder
+                                                // credits it.
+                                                println!(
+                                                    "💸 Seeder wants vouchers settled before more credit for {}",
+                                                    file_hash
+                                                );
+                                                if vouchers.pending == Some(outbound_req_id) {
+                                                    vouchers.pending = None;
+                                                }
+                                                vouchers.awaiting_settlement = Some(Instant::now());
+                                                spawn_voucher_settlement(
+                                                    cmd_tx,
+                                                    vouchers.terms.payer.clone(),
+                                                    vouchers.private_key.clone(),
+                                                    true,
+                                                );
+                                                return;
+                                            }
+                                        }
+                                        println!(
+                                            "❌ Payment voucher rejected for {}: {}",
+                                            file_hash, err_msg
+                                        );
+                                        if let Some(dl) = downloads.remove(&request_id) {
+                                            discard_partial_download(&dl);
+                                        }
+                                        events.emit(
+                                            "file-download-failed",
+                                            serde_json::json!({
+                                                "requestId": request_id,
+                                                "fileHash": file_hash,
+                                                "error": err_msg
+                                            }),
+                                        );
+                                        return;
+                                    }
+                                    let Some(dl) = downloads.get_mut(&request_id) else {
+                                        return;
+                                    };
+                                    let total_chunks = dl.total_chunks;
+                                    let Some(vouchers) = dl.vouchers.as_mut() else {
+                                        return;
+                                    };
+                                    if vouchers.pending == Some(outbound_req_id) {
+                                        vouchers.pending = None;
+                                    }
+                                    vouchers.acked =
+                                        vouchers.acked.max(chunks_covered.min(total_chunks));
+                                    if !dl.payment_confirmed {
+                                        println!(
+                                            "✅ Payment voucher accepted for {}, starting chunk download",
+                                            file_hash
+                                        );
+                                        dl.payment_confirmed = true;
+                                    }
+                                    pump_chunk_download(
+                                        swarm,
+                                        outbound_request_map,
+                                        dl,
+                                        Instant::now(),
+                                    );
+                                }
+                                ChunkResponse::SettlementAck {
+                                    tx_hash,
+                                    accepted,
+                                    error,
+                                } => {
+                                    if accepted {
+                                        {
+                                            let mut book = crate::micropayments::issued().lock();
+                                            if book.mark_notified(&tx_hash) {
+                                                if let Err(e) = book.persist() {
+                                                    println!(
+                                                        "⚠️ Failed to save voucher settlement {}: {}",
+                                                        tx_hash, e
+                                                    );
+                                                }
+                                            }
+                                        }
+                                        println!(
+                                            "✅ Seeder credited voucher settlement {}",
+                                            tx_hash
+                                        );
+                                        // Resume downloads that were waiting on
+                                        // this seeder to credit a settlement.
+                                        let mut downloads = active_downloads.lock().await;
+                                        for dl in downloads.values_mut() {
+                                            let Some(vouchers) = dl.vouchers.as_mut() else {
+                                                continue;
+                                            };
+                                            if vouchers.payee_peer != peer
+                                                || vouchers.awaiting_settlement.take().is_none()
+                                            {
+                                                continue;
+                                            }
+                                            pump_chunk_download(
+                                                swarm,
+                                                outbound_request_map,
+                                                dl,
+                                                Instant::now(),
+                                            );
+                                        }
+                                    } else {
+                                        // Left unnotified, so the next settle retries it.
+                                        println!(
+                                            "⚠️ Seeder did not credit voucher settlement {}: {}",
+                                            tx_hash,
+                                            error.unwrap_or_default()
+                                        );
                                     }
                                 }
                             }
@@ -5921,6 +8471,17 @@ async fn handle_behaviour_event(
                     ..
                 } => {
                     let our_request_id = outbound_request_map.remove(&outbound_req_id);
+                    if let Some(tx_hash) = our_request_id
+                        .as_deref()
+                        .and_then(|id| id.strip_prefix(SETTLEMENT_NOTICE_PREFIX))
+                    {
+                        // Stays unnotified; the next settle sends it again.
+                        println!(
+                            "⚠️ Could not deliver voucher settlement {} to {}: {:?}",
+                            tx_hash, peer, error
+                        );
+                        return;
+                    }
                     let peer_short = &peer.to_string()[..std::cmp::min(8, peer.to_string().len())];
                     let mut emit_failure = true;
                     let mut request_id_for_error: Option<String> = None;
@@ -5930,105 +8491,96 @@ async fn handle_behaviour_event(
                         request_id_for_error = Some(request_id.clone());
                         let mut downloads = active_downloads.lock().await;
                         if let Some(dl) = downloads.get_mut(&request_id) {
-                            if dl.peer_id == peer {
-                                let chunk_index = dl
-                                    .current_chunk_index
-                                    .min(dl.total_chunks.saturating_sub(1));
-                                if (chunk_index as usize) >= dl.retry_counts.len() {
-                                    println!(
-                                        "❌ Invalid retry index {} for request {} (retry len {})",
-                                        chunk_index,
-                                        request_id,
-                                        dl.retry_counts.len()
-                                    );
-                                    if let Some(dl) = remove_active_download_for_cleanup(
-                                        &mut downloads,
-                                        &request_id,
-                                        "invalid outbound retry index",
-                                    ) {
-                                        file_hash_for_error = Some(dl.file_hash.clone());
-                                        let _ = std::fs::remove_file(&dl.output_path);
-                                    } else {
-                                        emit_failure = false;
-                                    }
-                                } else {
-                                    dl.retry_counts[chunk_index as usize] += 1;
-                                    let attempt = dl.retry_counts[chunk_index as usize];
-
-                                    if attempt <= MAX_CHUNK_RETRIES {
+                            let tracked_chunk = dl.outbound_chunks.remove(&outbound_req_id);
+                            let live_chunk = tracked_chunk
+                                .map(|(chunk_index, _)| chunk_index)
+                                .filter(|chunk_index| {
+                                    dl.in_flight
+                                        .get(chunk_index)
+                                        .is_some_and(|chunk| chunk.peer_id == peer)
+                                });
+                            if let Some(chunk_index) = live_chunk {
+                                match record_chunk_failure(dl, chunk_index, peer, Instant::now()) {
+                                    ChunkFailureOutcome::Retry { attempt } => {
                                         println!(
                                             "🔄 Retrying chunk {} after outbound failure (attempt {}/{})",
                                             chunk_index, attempt, MAX_CHUNK_RETRIES
                                         );
-                                        let peer_id = dl.peer_id.clone();
-                                        let fh = dl.file_hash.clone();
-                                        let rid = dl.request_id.clone();
-                                        drop(downloads);
-                                        wait_for_chunk_retry_delay(attempt, chunk_index).await;
-                                        let request = ChunkRequest::Chunk {
-                                            request_id: rid.clone(),
-                                            file_hash: fh,
+                                        pump_chunk_download(
+                                            swarm,
+                                            outbound_request_map,
+                                            dl,
+                                            Instant::now(),
+                                        );
+                                        return;
+                                    }
+                                    ChunkFailureOutcome::Failover { from, to } => {
+                                        record_failed_source(&from);
+                                        emit_chunk_source_failover(
+                                            events,
+                                            dl,
+                                            from,
+                                            to,
                                             chunk_index,
-                                        };
-                                        let req_id = swarm
-                                            .behaviour_mut()
-                                            .file_request
-                                            .send_request(&peer_id, request);
-                                        outbound_request_map.insert(req_id, rid);
+                                            "outbound failure",
+                                        );
+                                        pump_chunk_download(
+                                            swarm,
+                                            outbound_request_map,
+                                            dl,
+                                            Instant::now(),
+                                        );
                                         return;
-                                    } else {
-                                        if let Some(next_peer) = take_next_backup_peer(dl) {
-                                            let previous_peer = dl.peer_id.clone();
-                                            dl.peer_id = next_peer.clone();
-                                            dl.current_chunk_index = chunk_index;
-                                            dl.retry_counts[chunk_index as usize] = 0;
-                                            let fh = dl.file_hash.clone();
-                                            let rid = dl.request_id.clone();
-                                            drop(downloads);
-                                            println!(
-                                                "🔁 Failing over request {} from {} to {} at chunk {} (outbound failure)",
-                                                rid, previous_peer, next_peer, chunk_index
-                                            );
-                                            let _ = events.emit(
-                                                "download-peer-failover",
-                                                serde_json::json!({
-                                                    "requestId": rid,
-                                                    "fileHash": fh,
-                                                    "fromPeer": previous_peer.to_string(),
-                                                    "toPeer": next_peer.to_string(),
-                                                    "chunkIndex": chunk_index,
-                                                }),
-                                            );
-                                            let request = ChunkRequest::Chunk {
-                                                request_id: rid.clone(),
-                                                file_hash: fh,
-                                                chunk_index,
-                                            };
-                                            let req_id = swarm
-                                                .behaviour_mut()
-                                                .file_request
-                                                .send_request(&next_peer, request);
-                                            outbound_request_map.insert(req_id, rid);
-                                            return;
+                                    }
+                                    ChunkFailureOutcome::Exhausted => {
+                                        record_failed_source(&peer);
+                                        if let Some(dl) = remove_active_download_for_cleanup(
+                                            &mut downloads,
+                                            &request_id,
+                                            "outbound failure retry exhaustion",
+                                        ) {
+                                            file_hash_for_error = Some(dl.file_hash.clone());
+                                            discard_partial_download(&dl);
                                         } else {
-                                            // Max retries exceeded
-                                            if let Some(dl) = remove_active_download_for_cleanup(
-                                                &mut downloads,
-                                                &request_id,
-                                                "outbound failure retry exhaustion",
-                                            ) {
-                                                file_hash_for_error = Some(dl.file_hash.clone());
-                                                let _ = std::fs::remove_file(&dl.output_path);
-                                            } else {
-                                                emit_failure = false;
-                                            }
+                                            emit_failure = false;
                                         }
                                     }
                                 }
+                            } else if dl.peer_id == peer && !dl.payment_confirmed {
+                                // The payment proof never reached the paid seeder, so no
+                                // chunk will ever be authorized for this request.
+                                if let Some(dl) = remove_active_download_for_cleanup(
+                                    &mut downloads,
+                                    &request_id,
+                                    "payment proof outbound failure",
+                                ) {
+                                    file_hash_for_error = Some(dl.file_hash.clone());
+                                    discard_partial_download(&dl);
+                                } else {
+                                    emit_failure = false;
+                                }
+                            } else if let Some(vouchers) = dl
+                                .vouchers
+                                .as_mut()
+                                .filter(|v| v.pending == Some(outbound_req_id))
+                            {
+                                // A top-up voucher got lost; the next scheduler tick
+                                // signs and sends it again.
+                                vouchers.pending = None;
+                                emit_failure = false;
                             } else {
-                                // Another seeder attempt failed after a different seeder
-                                // already became the active source for this request.
+                                // A stale chunk request (already reassigned or received),
+                                // or a FileInfo attempt against an extra provider after
+                                // another seeder already started this download.
                                 emit_failure = false;
+                                if tracked_chunk.is_none()
+                                    && decrement_file_request_attempt(
+                                        pending_file_attempts,
+                                        &request_id,
+                                    ) == 0
+                                {
+                                    pending_file_hashes.remove(&request_id);
+                                }
                             }
                         } else {
                             // No active chunked download yet => this was a FileInfo seeder attempt.
@@ -6158,6 +8710,7 @@ async fn handle_behaviour_event(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use sha2::{Digest, Sha256};
 
     fn test_active_download(request_id: &str) -> ActiveChunkedDownload {
         test_active_download_with_peer(request_id, PeerId::random())
@@ -6170,16 +8723,22 @@ mod tests {
             file_name: "file.bin".to_string(),
             file_size: 1,
             total_chunks: 1,
-            chunk_hashes: vec!["chunk-hash".to_string()],
+            merkle_root: "merkle-root".to_string(),
             received_chunks: vec![false],
             output_path: std::env::temp_dir().join(format!("{request_id}.download")),
             bytes_written: 0,
             peer_id,
             backup_peers: vec![],
+            sources: HashMap::from([(peer_id, ChunkSource::default())]),
+            allow_parallel_sources: false,
+            in_flight: HashMap::new(),
+            outbound_chunks: HashMap::new(),
+            retry_after: HashMap::new(),
+            first_missing_chunk: 0,
             retry_counts: vec![0],
-            current_chunk_index: 0,
             start_time: std::time::Instant::now(),
             payment_confirmed: false,
+            vouchers: None,
         }
     }
 
@@ -6388,6 +8947,73 @@ mod tests {
         assert!(err.contains("70000"));
     }
 
+    #[test]
+    fn p2p_listen_addrs_cover_tcp_and_quic_on_both_families() {
+        let addrs: Vec<String> = p2p_listen_addrs(4100)
+            .iter()
+            .map(|a| a.to_string())
+            .collect();
+
+        assert_eq!(
+            addrs,
+            vec![
+                "/ip4/0.0.0.0/tcp/4100",
+                "/ip4/0.0.0.0/udp/4100/quic-v1",
+                "/ip6/::/tcp/4100",
+                "/ip6/::/udp/4100/quic-v1",
+            ]
+        );
+    }
+
+    #[test]
+    fn prefer_quic_addrs_orders_quic_then_tcp_then_relay() {
+        let peer = "12D3KooWAHWpUyBsFvgC6fb9jjmtDtKMM1qUChiNjtBDrTTEAY5C";
+        let mut addrs: Vec<Multiaddr> = [
+            format!(
+                "/ip4/203.0.113.5/tcp/4001/p2p/{}/p2p-circuit/p2p/{}",
+                peer, peer
+            ),
+            format!("/ip4/198.51.100.7/tcp/4001/p2p/{}", peer),
+            format!("/ip4/198.51.100.7/udp/4001/quic-v1/p2p/{}", peer),
+            format!("/ip6/2001:db8::7/tcp/4001/p2p/{}", peer),
+            format!("/ip6/2001:db8::7/udp/4001/quic-v1/p2p/{}", peer),
+        ]
+        .iter()
+        .map(|s| s.parse().unwrap())
+        .collect();
+
+        prefer_quic_addrs(&mut addrs);
+
+        let ranks: Vec<u8> = addrs.iter().map(transport_dial_rank).collect();
+        assert_eq!(ranks, vec![0, 0, 1, 1, 2]);
+        // Stable within a rank: IPv4 stays ahead of IPv6.
+        assert!(addrs[0].to_string().starts_with("/ip4/198.51.100.7/udp"));
+        assert!(addrs[2].to_string().starts_with("/ip4/198.51.100.7/tcp"));
+    }
+
+    #[test]
+    fn split_quic_dial_holds_back_tcp_and_relays() {
+        let peer = "12D3KooWAHWpUyBsFvgC6fb9jjmtDtKMM1qUChiNjtBDrTTEAY5C";
+        let relay = format!(
+            "/ip4/203.0.113.5/tcp/4001/p2p/{}/p2p-circuit/p2p/{}",
+            peer, peer
+        );
+        let tcp = format!("/ip4/198.51.100.7/tcp/4001/p2p/{}", peer);
+        let quic = format!("/ip4/198.51.100.7/udp/4001/quic-v1/p2p/{}", peer);
+        let parse = |addrs: &[&String]| -> Vec<Multiaddr> {
+            addrs.iter().map(|s| s.parse().unwrap()).collect()
+        };
+
+        let (first, rest) = split_quic_dial(parse(&[&relay, &tcp, &quic]));
+        assert_eq!(first, parse(&[&quic]));
+        assert_eq!(rest, parse(&[&tcp, &relay]));
+
+        // Without QUIC everything is dialed at once.
+        let (first, rest) = split_quic_dial(parse(&[&relay, &tcp]));
+        assert_eq!(first, parse(&[&tcp, &relay]));
+        assert!(rest.is_empty());
+    }
+
     #[test]
     fn add_backup_peer_accepts_first_distinct_peer() {
         let active_peer = PeerId::random();
@@ -6400,25 +9026,597 @@ mod tests {
     }
 
     #[test]
-    fn add_backup_peer_rejects_active_peer() {
-        let active_peer = PeerId::random();
-        let mut download = test_active_download_with_peer("req-1", active_peer);
-
-        assert!(!add_backup_peer(&mut download, active_peer));
-
-        assert!(download.backup_peers.is_empty());
+    fn add_backup_peer_rejects_active_peer() {
+        let active_peer = PeerId::random();
+        let mut download = test_active_download_with_peer("req-1", active_peer);
+
+        assert!(!add_backup_peer(&mut download, active_peer));
+
+        assert!(download.backup_peers.is_empty());
+    }
+
+    #[test]
+    fn rank_backup_peers_promotes_best_performer_first() {
+        let active_peer = PeerId::random();
+        let flaky = PeerId::random();
+        let unknown = PeerId::random();
+        let fast = PeerId::random();
+        let mut download = test_active_download_with_peer("req-1", active_peer);
+        for peer in [fast, unknown, flaky] {
+            assert!(add_backup_peer(&mut download, peer));
+        }
+        let history = |peer: &PeerId| {
+            let perf = |bps: f64, completed: u32, failed: u32| {
+                Some(crate::seeder_selection::PeerPerformance {
+                    throughput_bps: Some(bps),
+                    latency_ms: Some(50.0),
+                    completed,
+                    failed,
+                    updated_at: 1,
+                })
+            };
+            if *peer == fast {
+                perf(5_000_000.0, 8, 0)
+            } else if *peer == flaky {
+                perf(200_000.0, 1, 6)
+            } else {
+                None
+            }
+        };
+
+        rank_backup_peers(&mut download, history);
+
+        assert_eq!(take_next_backup_peer(&mut download), Some(fast));
+        assert_eq!(take_next_backup_peer(&mut download), Some(unknown));
+        assert_eq!(take_next_backup_peer(&mut download), Some(flaky));
+    }
+
+    #[test]
+    fn add_backup_peer_rejects_duplicate_backup_peer() {
+        let active_peer = PeerId::random();
+        let backup_peer = PeerId::random();
+        let mut download = test_active_download_with_peer("req-1", active_peer);
+
+        assert!(add_backup_peer(&mut download, backup_peer));
+        assert!(!add_backup_peer(&mut download, backup_peer));
+
+        assert_eq!(download.backup_peers, vec![backup_peer]);
+    }
+
+    fn test_swarm_download(total_chunks: u32, peers: &[PeerId]) -> ActiveChunkedDownload {
+        let mut download = test_active_download_with_peer("swarm-req", peers[0]);
+        download.total_chunks = total_chunks;
+        download.file_size = total_chunks as u64 * CHUNK_SIZE as u64;
+        download.received_chunks = vec![false; total_chunks as usize];
+        download.retry_counts = vec![0; total_chunks as usize];
+        download.allow_parallel_sources = true;
+        download.payment_confirmed = true;
+        for peer in &peers[1..] {
+            add_chunk_source(&mut download, *peer);
+        }
+        download
+    }
+
+    #[test]
+    fn add_chunk_source_promotes_backup_peer() {
+        let active_peer = PeerId::random();
+        let extra_peer = PeerId::random();
+        let mut download = test_active_download_with_peer("req-1", active_peer);
+        assert!(add_backup_peer(&mut download, extra_peer));
+
+        assert!(add_chunk_source(&mut download, extra_peer));
+        assert!(!add_chunk_source(&mut download, extra_peer));
+
+        assert!(download.backup_peers.is_empty());
+        assert_eq!(download.sources.len(), 2);
+        assert!(!add_backup_peer(&mut download, extra_peer));
+    }
+
+    #[test]
+    fn schedule_chunk_requests_spreads_window_across_sources() {
+        let peers = [PeerId::random(), PeerId::random(), PeerId::random()];
+        let mut download = test_swarm_download(64, &peers);
+
+        let assignments = schedule_chunk_requests(&mut download, Instant::now());
+
+        assert_eq!(
+            assignments.len(),
+            peers.len() * CHUNK_MAX_IN_FLIGHT_PER_PEER
+        );
+        let indices: Vec<u32> = assignments.iter().map(|(idx, _)| *idx).collect();
+        assert_eq!(indices, (0..assignments.len() as u32).collect::<Vec<_>>());
+        for peer in &peers {
+            assert_eq!(
+                chunk_requests_in_flight(&download, peer),
+                CHUNK_MAX_IN_FLIGHT_PER_PEER
+            );
+        }
+        assert!(schedule_chunk_requests(&mut download, Instant::now()).is_empty());
+    }
+
+    #[test]
+    fn schedule_chunk_requests_caps_total_window() {
+        let peers: Vec<PeerId> = (0..8).map(|_| PeerId::random()).collect();
+        let mut download = test_swarm_download(64, &peers);
+
+        let assignments = schedule_chunk_requests(&mut download, Instant::now());
+
+        assert_eq!(assignments.len(), CHUNK_DOWNLOAD_WINDOW);
+        assert_eq!(download.in_flight.len(), CHUNK_DOWNLOAD_WINDOW);
+    }
+
+    #[test]
+    fn schedule_chunk_requests_waits_for_payment() {
+        let peer = PeerId::random();
+        let mut download = test_swarm_download(4, &[peer]);
+        download.payment_confirmed = false;
+
+        assert!(schedule_chunk_requests(&mut download, Instant::now()).is_empty());
+        assert!(download.in_flight.is_empty());
+    }
+
+    #[test]
+    fn schedule_chunk_requests_skips_received_and_backing_off_chunks() {
+        let peer = PeerId::random();
+        let mut download = test_swarm_download(4, &[peer]);
+        let now = Instant::now();
+        download.received_chunks[0] = true;
+        download.first_missing_chunk = 1;
+        download.retry_after.insert(1, now + Duration::from_secs(1));
+
+        let assignments = schedule_chunk_requests(&mut download, now);
+        assert_eq!(assignments, vec![(2, peer), (3, peer)]);
+
+        let later = now + Duration::from_secs(2);
+        download.in_flight.clear();
+        let assignments = schedule_chunk_requests(&mut download, later);
+        assert_eq!(assignments.first(), Some(&(1, peer)));
+        assert!(!download.retry_after.contains_key(&1));
+    }
+
+    fn with_test_vouchers(download: &mut ActiveChunkedDownload, acked: u32) {
+        download.vouchers = Some(VoucherProgress {
+            terms: crate::micropayments::VoucherTerms {
+                session_id: download.request_id.clone(),
+                payer: "0xpayer".to_string(),
+                payee: "0xpayee".to_string(),
+                file_hash: download.file_hash.clone(),
+                price_wei: "1600".to_string(),
+                total_chunks: download.total_chunks,
+            },
+            private_key: String::new(),
+            payee_peer: download.peer_id,
+            interval: 4,
+            acked,
+            pending: None,
+            awaiting_settlement: None,
+            charged: HashSet::new(),
+        });
+    }
+
+    #[test]
+    fn schedule_chunk_requests_stays_within_voucher_coverage() {
+        let peers = [PeerId::random(), PeerId::random(), PeerId::random()];
+        let mut download = test_swarm_download(16, &peers);
+        with_test_vouchers(&mut download, 6);
+
+        let assignments = schedule_chunk_requests(&mut download, Instant::now());
+        let mut indices: Vec<u32> = assignments.iter().map(|(index, _)| *index).collect();
+        indices.sort_unstable();
+        assert_eq!(indices, (0..6).collect::<Vec<_>>());
+        assert!(schedule_chunk_requests(&mut download, Instant::now()).is_empty());
+
+        // Retrying an already-paid chunk does not use up more coverage.
+        download.in_flight.remove(&2);
+        let retry = schedule_chunk_requests(&mut download, Instant::now());
+        assert_eq!(retry.len(), 1);
+        assert_eq!(retry[0].0, 2);
+
+        download.vouchers.as_mut().unwrap().acked = 8;
+        let topped_up: Vec<u32> = schedule_chunk_requests(&mut download, Instant::now())
+            .iter()
+            .map(|(index, _)| *index)
+            .collect();
+        assert_eq!(topped_up, vec![6, 7]);
+        assert_eq!(download.vouchers.as_ref().unwrap().charged.len(), 8);
+    }
+
+    #[test]
+    fn voucher_top_up_due_when_half_an_interval_is_left() {
+        let peer = PeerId::random();
+        let mut download = test_swarm_download(16, &[peer]);
+        assert_eq!(voucher_top_up_due(&download), None);
+
+        with_test_vouchers(&mut download, 0);
+        assert_eq!(voucher_top_up_due(&download), Some(4));
+
+        let vouchers = download.vouchers.as_mut().unwrap();
+        vouchers.acked = 4;
+        vouchers.charged.insert(0);
+        assert_eq!(voucher_top_up_due(&download), None);
+
+        download.vouchers.as_mut().unwrap().charged.insert(1);
+        assert_eq!(voucher_top_up_due(&download), Some(8));
+
+        let vouchers = download.vouchers.as_mut().unwrap();
+        vouchers.acked = 14;
+        vouchers.charged = (0..13).collect();
+        assert_eq!(voucher_top_up_due(&download), Some(16));
+
+        download.vouchers.as_mut().unwrap().acked = 16;
+        assert_eq!(voucher_top_up_due(&download), None);
+    }
+
+    #[test]
+    fn voucher_top_up_waits_on_a_settlement_after_a_credit_refusal() {
+        let peer = PeerId::random();
+        let mut download = test_swarm_download(16, &[peer]);
+        with_test_vouchers(&mut download, 4);
+        download.vouchers.as_mut().unwrap().charged = (0..4).collect();
+        assert_eq!(voucher_top_up_due(&download), Some(8));
+
+        download.vouchers.as_mut().unwrap().awaiting_settlement = Some(Instant::now());
+        assert_eq!(voucher_top_up_due(&download), None);
+
+        // Without an ack for the settlement, the voucher is retried later.
+        download.vouchers.as_mut().unwrap().awaiting_settlement =
+            Instant::now().checked_sub(Duration::from_secs(VOUCHER_CREDIT_RETRY_SECS));
+        assert_eq!(voucher_top_up_due(&download), Some(8));
+    }
+
+    #[test]
+    fn pick_chunk_source_prefers_faster_source() {
+        let slow = PeerId::random();
+        let fast = PeerId::random();
+        let mut download = test_swarm_download(8, &[slow, fast]);
+        download.sources.get_mut(&slow).unwrap().throughput_bps = Some(10_000.0);
+        download.sources.get_mut(&fast).unwrap().throughput_bps = Some(1_000_000.0);
+
+        assert_eq!(pick_chunk_source(&download, None), Some(fast));
+        assert_eq!(pick_chunk_source(&download, Some(fast)), Some(slow));
+    }
+
+    #[test]
+    fn pick_chunk_source_probes_unmeasured_sources() {
+        let measured = PeerId::random();
+        let fresh = PeerId::random();
+        let mut download = test_swarm_download(8, &[measured, fresh]);
+        download.sources.get_mut(&measured).unwrap().throughput_bps = Some(500_000.0);
+        download.in_flight.insert(
+            0,
+            InFlightChunk {
+                peer_id: measured,
+                sent_at: Instant::now(),
+            },
+        );
+
+        assert_eq!(pick_chunk_source(&download, None), Some(fresh));
+    }
+
+    #[test]
+    fn record_chunk_throughput_smooths_samples() {
+        let mut source = ChunkSource::default();
+        record_chunk_throughput(&mut source, 1_000, Duration::from_secs(1));
+        assert_eq!(source.throughput_bps, Some(1_000.0));
+
+        record_chunk_throughput(&mut source, 2_000, Duration::from_secs(1));
+        let bps = source.throughput_bps.unwrap();
+        assert!((bps - 1_300.0).abs() < 1e-6, "got {bps}");
+    }
+
+    #[test]
+    fn reassign_straggler_chunks_moves_slow_request_to_other_source() {
+        let slow = PeerId::random();
+        let fast = PeerId::random();
+        let mut download = test_swarm_download(8, &[slow, fast]);
+        let sent_at = Instant::now();
+        download.in_flight.insert(
+            0,
+            InFlightChunk {
+                peer_id: slow,
+                sent_at,
+            },
+        );
+
+        let early = sent_at + Duration::from_millis(CHUNK_STRAGGLER_MIN_MS / 2);
+        let unlimited = crate::bandwidth::BandwidthManager::new(Default::default());
+        assert!(reassign_straggler_chunks(&mut download, &unlimited, early).is_empty());
+
+        let late = sent_at + Duration::from_millis(CHUNK_STRAGGLER_MIN_MS + 1);
+
+        // The fast source's per-peer allowance is already spent.
+        let limited = crate::bandwidth::BandwidthManager::new(crate::bandwidth::BandwidthLimits {
+            per_peer_download_bps: CHUNK_SIZE as u64,
+            ..Default::default()
+        });
+        assert!(limited.try_reserve_download(fast, CHUNK_SIZE as u64));
+        assert!(reassign_straggler_chunks(&mut download, &limited, late).is_empty());
+        assert_eq!(download.in_flight[&0].peer_id, slow);
+        assert!(download.sources[&slow].throughput_bps.is_none());
+
+        let reassigned = reassign_straggler_chunks(&mut download, &unlimited, late);
+
+        assert_eq!(reassigned, vec![(0, fast)]);
+        assert_eq!(download.in_flight[&0].peer_id, fast);
+        assert!(download.sources[&slow].throughput_bps.is_some());
+    }
+
+    #[test]
+    fn reassign_straggler_chunks_needs_a_second_source() {
+        let only = PeerId::random();
+        let mut download = test_swarm_download(8, &[only]);
+        let sent_at = Instant::now();
+        download.in_flight.insert(
+            0,
+            InFlightChunk {
+                peer_id: only,
+                sent_at,
+            },
+        );
+
+        let late = sent_at + Duration::from_secs(60);
+        let unlimited = crate::bandwidth::BandwidthManager::new(Default::default());
+        assert!(reassign_straggler_chunks(&mut download, &unlimited, late).is_empty());
+        assert_eq!(download.in_flight[&0].peer_id, only);
+    }
+
+    #[test]
+    fn chunk_straggler_timeout_scales_with_throughput() {
+        let min = Duration::from_millis(CHUNK_STRAGGLER_MIN_MS);
+        assert_eq!(chunk_straggler_timeout(None), min);
+
+        let fast = ChunkSource {
+            throughput_bps: Some(100.0 * CHUNK_SIZE as f64),
+            failures: 0,
+        };
+        assert_eq!(chunk_straggler_timeout(Some(&fast)), min);
+
+        let slow = ChunkSource {
+            throughput_bps: Some(CHUNK_SIZE as f64 / 10.0),
+            failures: 0,
+        };
+        assert!(chunk_straggler_timeout(Some(&slow)) > Duration::from_secs(29));
+    }
+
+    #[test]
+    fn mark_chunk_received_tracks_out_of_order_chunks() {
+        let peer = PeerId::random();
+        let mut download = test_swarm_download(3, &[peer]);
+        let now = Instant::now();
+
+        mark_chunk_received(&mut download, 2, peer, 10, now);
+        assert_eq!(download.first_missing_chunk, 0);
+        assert!(!chunked_download_complete(&download));
+
+        mark_chunk_received(&mut download, 0, peer, 10, now);
+        assert_eq!(download.first_missing_chunk, 1);
+
+        mark_chunk_received(&mut download, 1, peer, 10, now);
+        assert_eq!(download.first_missing_chunk, 3);
+        assert_eq!(download.bytes_written, 30);
+        assert!(chunked_download_complete(&download));
+    }
+
+    #[test]
+    fn mark_chunk_received_clears_source_failures() {
+        let peer = PeerId::random();
+        let mut download = test_swarm_download(2, &[peer]);
+        let now = Instant::now();
+        download.in_flight.insert(
+            0,
+            InFlightChunk {
+                peer_id: peer,
+                sent_at: now,
+            },
+        );
+        download.sources.get_mut(&peer).unwrap().failures = 2;
+
+        mark_chunk_received(
+            &mut download,
+            0,
+            peer,
+            1024,
+            now + Duration::from_millis(10),
+        );
+
+        assert!(download.in_flight.is_empty());
+        assert_eq!(download.sources[&peer].failures, 0);
+        assert!(download.sources[&peer].throughput_bps.is_some());
+    }
+
+    #[test]
+    fn record_chunk_failure_retries_with_backoff() {
+        let peer = PeerId::random();
+        let mut download = test_swarm_download(2, &[peer]);
+        let now = Instant::now();
+        download.in_flight.insert(
+            1,
+            InFlightChunk {
+                peer_id: peer,
+                sent_at: now,
+            },
+        );
+
+        let outcome = record_chunk_failure(&mut download, 1, peer, now);
+
+        assert_eq!(outcome, ChunkFailureOutcome::Retry { attempt: 1 });
+        assert!(download.in_flight.is_empty());
+        assert_eq!(download.retry_after[&1], now + chunk_retry_delay(1, 1));
+    }
+
+    #[test]
+    fn record_chunk_busy_backs_off_without_dropping_the_seeder() {
+        let peer = PeerId::random();
+        let mut download = test_swarm_download(8, &[peer]);
+        let now = Instant::now();
+        // Every queued request of the only seeder is turned away, many
+        // times over; the seeder stays and nothing counts as a retry.
+        for round in 0..(MAX_CHUNK_RETRIES as u32 + 2) {
+            for idx in 0..CHUNK_MAX_IN_FLIGHT_PER_PEER as u32 {
+                download.in_flight.insert(
+                    idx,
+                    InFlightChunk {
+                        peer_id: peer,
+                        sent_at: now,
+                    },
+                );
+                record_chunk_busy(&mut download, idx, peer, now);
+                assert!(!download.in_flight.contains_key(&idx), "round {round}");
+            }
+        }
+
+        assert!(download.sources.contains_key(&peer));
+        assert_eq!(download.sources[&peer].failures, 0);
+        assert!(download.retry_counts.iter().all(|count| *count == 0));
+        let delay = download.retry_after[&0] - now;
+        assert!(delay >= std::time::Duration::from_millis(CHUNK_BUSY_RETRY_MS));
+        let backed_off = |idx: &u32| *idx < CHUNK_MAX_IN_FLIGHT_PER_PEER as u32;
+        let early = schedule_chunk_requests(&mut download, now);
+        assert!(
+            !early.iter().any(|(idx, _)| backed_off(idx)),
+            "busy chunks wait out their backoff"
+        );
+        for (idx, _) in early {
+            download.in_flight.remove(&idx);
+        }
+        let later = now + std::time::Duration::from_secs(5);
+        let retried = schedule_chunk_requests(&mut download, later);
+        assert!(retried.iter().any(|(idx, _)| backed_off(idx)));
+    }
+
+    #[test]
+    fn record_chunk_failure_drops_source_and_keeps_others() {
+        let bad = PeerId::random();
+        let good = PeerId::random();
+        let mut download = test_swarm_download(8, &[bad, good]);
+        let now = Instant::now();
+        for idx in 0..2 {
+            download.in_flight.insert(
+                idx,
+                InFlightChunk {
+                    peer_id: bad,
+                    sent_at: now,
+                },
+            );
+        }
+
+        for _ in 0..MAX_CHUNK_RETRIES {
+            assert!(matches!(
+                record_chunk_failure(&mut download, 0, bad, now),
+                ChunkFailureOutcome::Retry { .. }
+            ));
+        }
+        let outcome = record_chunk_failure(&mut download, 0, bad, now);
+
+        assert_eq!(
+            outcome,
+            ChunkFailureOutcome::Failover {
+                from: bad,
+                to: good
+            }
+        );
+        assert!(!download.sources.contains_key(&bad));
+        assert!(download.in_flight.is_empty());
+        assert_eq!(download.retry_counts[0], 0);
+    }
+
+    #[test]
+    fn record_chunk_failure_promotes_backup_when_last_source_fails() {
+        let active = PeerId::random();
+        let backup = PeerId::random();
+        let mut download = test_swarm_download(2, &[active]);
+        add_backup_peer(&mut download, backup);
+        let now = Instant::now();
+
+        for _ in 0..MAX_CHUNK_RETRIES {
+            record_chunk_failure(&mut download, 0, active, now);
+        }
+        let outcome = record_chunk_failure(&mut download, 0, active, now);
+
+        assert_eq!(
+            outcome,
+            ChunkFailureOutcome::Failover {
+                from: active,
+                to: backup
+            }
+        );
+        assert_eq!(download.peer_id, backup);
+        assert!(download.sources.contains_key(&backup));
+    }
+
+    #[test]
+    fn record_chunk_failure_exhausts_without_sources_or_backups() {
+        let active = PeerId::random();
+        let mut download = test_swarm_download(2, &[active]);
+        let now = Instant::now();
+
+        for _ in 0..MAX_CHUNK_RETRIES {
+            record_chunk_failure(&mut download, 0, active, now);
+        }
+
+        assert_eq!(
+            record_chunk_failure(&mut download, 0, active, now),
+            ChunkFailureOutcome::Exhausted
+        );
+    }
+
+    #[test]
+    fn verify_chunk_payload_checks_inclusion_proof() {
+        let data: Vec<u8> = (0..CHUNK_SIZE * 2 + 17).map(|i| (i % 251) as u8).collect();
+        let tree = crate::merkle::MerkleTree::from_bytes(&data);
+        let mut download = test_active_download("req-1");
+        download.file_size = data.len() as u64;
+        download.total_chunks = tree.chunk_count();
+        download.merkle_root = tree.root();
+
+        let chunk = data[CHUNK_SIZE..CHUNK_SIZE * 2].to_vec();
+        let proof = tree.proof(1).unwrap();
+        assert_eq!(
+            verify_chunk_payload(&download, 1, Some(chunk.clone()), &proof, None),
+            Ok((chunk.clone(), crate::merkle::leaf_hash(&chunk)))
+        );
+        // Right bytes, wrong slot; and a proof for another chunk.
+        assert!(verify_chunk_payload(&download, 0, Some(chunk.clone()), &proof, None).is_err());
+        let other_proof = tree.proof(2).unwrap();
+        assert!(
+            verify_chunk_payload(&download, 1, Some(chunk.clone()), &other_proof, None).is_err()
+        );
+        let mut tampered = chunk.clone();
+        tampered[10] ^= 0xff;
+        assert!(verify_chunk_payload(&download, 1, Some(tampered), &proof, None).is_err());
+        assert!(verify_chunk_payload(&download, 1, None, &proof, None).is_err());
+        assert_eq!(
+            verify_chunk_payload(&download, 1, Some(chunk), &proof, Some("busy".to_string())),
+            Err("busy".to_string())
+        );
     }
 
     #[test]
-    fn add_backup_peer_rejects_duplicate_backup_peer() {
-        let active_peer = PeerId::random();
-        let backup_peer = PeerId::random();
-        let mut download = test_active_download_with_peer("req-1", active_peer);
+    fn file_info_matches_download_requires_identical_manifest() {
+        let download = test_active_download("req-1");
 
-        assert!(add_backup_peer(&mut download, backup_peer));
-        assert!(!add_backup_peer(&mut download, backup_peer));
+        assert!(file_info_matches_download(&download, 1, 1, "merkle-root"));
+        assert!(file_info_matches_download(&download, 1, 1, "MERKLE-ROOT"));
+        assert!(!file_info_matches_download(&download, 2, 1, "merkle-root"));
+        assert!(!file_info_matches_download(&download, 1, 1, "forged"));
+    }
 
-        assert_eq!(download.backup_peers, vec![backup_peer]);
+    #[test]
+    fn file_info_manifest_must_agree_with_file_size() {
+        assert!(file_info_manifest_consistent(0, 0, "root"));
+        assert!(file_info_manifest_consistent(1, 1, "root"));
+        assert!(file_info_manifest_consistent(
+            CHUNK_SIZE as u64 + 1,
+            2,
+            "root"
+        ));
+        assert!(!file_info_manifest_consistent(
+            CHUNK_SIZE as u64 + 1,
+            1,
+            "root"
+        ));
+        assert!(!file_info_manifest_consistent(CHUNK_SIZE as u64, 2, "root"));
+        assert!(!file_info_manifest_consistent(1, 1, ""));
     }
 
     #[tokio::test]
@@ -6490,132 +9688,68 @@ mod tests {
         assert_eq!(chunk_retry_delay(2, 7), chunk_retry_delay(2, 7));
     }
 
-    #[tokio::test(start_paused = true)]
-    async fn test_chunk_retry_wait_uses_tokio_time() {
-        let delay = chunk_retry_delay(2, 7);
-        let started_at = tokio::time::Instant::now();
-        let wait = tokio::spawn(async move {
-            wait_for_chunk_retry_delay(2, 7).await;
-            tokio::time::Instant::now()
-        });
-
-        tokio::task::yield_now().await;
-        assert!(!wait.is_finished());
-
-        tokio::time::advance(delay - std::time::Duration::from_millis(1)).await;
-        tokio::task::yield_now().await;
-        assert!(!wait.is_finished());
-
-        tokio::time::advance(std::time::Duration::from_millis(1)).await;
-        let finished_at = wait.await.expect("retry sleep task should finish");
-        assert_eq!(finished_at.duration_since(started_at), delay);
+    fn sealed_record(
+        key: &str,
+        value: &str,
+        keypair: &libp2p::identity::Keypair,
+    ) -> dht_record::SignedRecord {
+        dht_record::SignedRecord::seal(key, value.to_string(), keypair, dht_record::now_ms())
+            .unwrap()
     }
 
     #[test]
-    fn test_select_best_get_record_value_merges_seeder_multiaddrs() {
-        let stale = serde_json::json!({
-            "hash": "abc123",
-            "fileName": "demo.bin",
-            "fileSize": 1024,
-            "protocol": "WebRTC",
-            "createdAt": 100,
-            "peerId": "peer-a",
-            "priceWei": "0",
-            "walletAddress": "",
-            "seeders": [{
-                "peerId": "peer-a",
-                "priceWei": "0",
-                "walletAddress": "",
-                "multiaddrs": ["/ip4/10.0.0.1/tcp/4001/p2p/peer-a"]
-            }]
-        });
-        let fresh = serde_json::json!({
-            "hash": "abc123",
-            "fileName": "demo.bin",
-            "fileSize": 1024,
-            "protocol": "WebRTC",
-            "createdAt": 200,
-            "peerId": "peer-a",
-            "priceWei": "5",
-            "walletAddress": "0xabc",
-            "seeders": [
-                {
-                    "peerId": "peer-a",
-                    "priceWei": "5",
-                    "walletAddress": "0xabc",
-                    "multiaddrs": ["/ip4/130.245.173.73/tcp/4001/p2p-circuit/p2p/peer-a"]
-                },
-                {
-                    "peerId": "peer-b",
-                    "priceWei": "0",
-                    "walletAddress": "",
-                    "multiaddrs": ["/ip4/130.245.173.73/tcp/4001/p2p-circuit/p2p/peer-b"]
-                }
-            ]
-        });
-
-        let selected = select_best_get_record_value(vec![stale.to_string(), fresh.to_string()])
-            .expect("expected merged value");
-        let parsed: serde_json::Value = serde_json::from_str(&selected).unwrap();
-        let seeders = parsed.get("seeders").and_then(|v| v.as_array()).unwrap();
-        assert_eq!(seeders.len(), 2);
-
-        let peer_a = seeders
-            .iter()
-            .find(|s| s.get("peerId").and_then(|v| v.as_str()) == Some("peer-a"))
-            .expect("peer-a seeder missing");
-        let peer_a_addrs = peer_a.get("multiaddrs").and_then(|v| v.as_array()).unwrap();
-        assert!(peer_a_addrs
-            .iter()
-            .any(|a| a.as_str() == Some("/ip4/10.0.0.1/tcp/4001/p2p/peer-a")));
-        assert!(peer_a_addrs
-            .iter()
-            .any(|a| a.as_str() == Some("/ip4/130.245.173.73/tcp/4001/p2p-circuit/p2p/peer-a")));
-
-        assert_eq!(
-            parsed.get("createdAt").and_then(|v| v.as_u64()),
-            Some(200),
-            "newer metadata should be selected as base"
-        );
+    fn test_select_best_get_record_value_prefers_highest_seq() {
+        let keypair = libp2p::identity::Keypair::generate_ed25519();
+        let older = sealed_record("notes", "a much longer but older value", &keypair);
+        let newer = sealed_record("notes", "new", &keypair);
+        let selected = select_best_get_record_value(vec![newer, older]).expect("expected best");
+        assert_eq!(selected, "new");
+        assert_eq!(select_best_get_record_value(Vec::new()), None);
     }
 
     #[test]
-    fn test_select_best_get_record_value_promotes_legacy_peer_id() {
-        let legacy = serde_json::json!({
-            "hash": "legacy-hash",
-            "fileName": "legacy.dat",
-            "fileSize": 42,
-            "protocol": "WebRTC",
-            "createdAt": 10,
-            "peerId": "legacy-peer",
-            "priceWei": "9",
-            "walletAddress": "0xlegacy"
-        });
+    fn test_select_best_get_record_value_merges_host_registry_copies() {
+        let host_a = libp2p::identity::Keypair::generate_ed25519();
+        let host_b = libp2p::identity::Keypair::generate_ed25519();
+        let registry_for = |keypair: &libp2p::identity::Keypair, updated_at: u64| {
+            let entries = serde_json::json!([{
+                "peerId": PeerId::from(keypair.public()).to_string(),
+                "walletAddress": "0xabc",
+                "updatedAt": updated_at,
+            }]);
+            dht_record::sign_host_registry(&entries.to_string(), keypair).unwrap()
+        };
+        let copy_a = sealed_record(HOST_REGISTRY_KEY, &registry_for(&host_a, 10), &host_a);
+        let copy_b = sealed_record(HOST_REGISTRY_KEY, &registry_for(&host_b, 20), &host_b);
 
-        let selected =
-            select_best_get_record_value(vec![legacy.to_string()]).expect("expected value");
-        let parsed: serde_json::Value = serde_json::from_str(&selected).unwrap();
-        let seeders = parsed.get("seeders").and_then(|v| v.as_array()).unwrap();
-        assert_eq!(seeders.len(), 1);
-        let only = &seeders[0];
-        assert_eq!(
-            only.get("peerId").and_then(|v| v.as_str()),
-            Some("legacy-peer")
-        );
-        assert_eq!(only.get("priceWei").and_then(|v| v.as_str()), Some("9"));
-        assert_eq!(
-            only.get("walletAddress").and_then(|v| v.as_str()),
-            Some("0xlegacy")
-        );
+        let selected = select_best_get_record_value(vec![copy_a, copy_b]).expect("expected merge");
+        let merged: Vec<HostRegistryMergeEntry> = serde_json::from_str(&selected).unwrap();
+        assert_eq!(merged.len(), 2);
+        assert_eq!(merged[0].peer_id, PeerId::from(host_b.public()).to_string());
+        // Signatures survive the merge so the result can be republished.
+        assert!(merged.iter().all(|e| !e.signature.is_empty()));
     }
 
     #[test]
-    fn test_select_best_get_record_value_falls_back_to_longest_for_non_file_records() {
-        let short = serde_json::json!({"foo": "bar"}).to_string();
-        let long = serde_json::json!({"foo": "this is a much longer generic record"}).to_string();
-        let selected =
-            select_best_get_record_value(vec![short.clone(), long.clone()]).expect("expected best");
-        assert_eq!(selected, long);
+    fn test_seal_dht_value_refuses_records_peers_would_reject() {
+        let keypair = libp2p::identity::Keypair::generate_ed25519();
+        let other = PeerId::from(libp2p::identity::Keypair::generate_ed25519().public());
+        let foreign_key = format!("chiral_pubkey_{}", other);
+        assert!(seal_dht_value(&foreign_key, "ab".repeat(32), &keypair).is_err());
+
+        let own_key = format!("chiral_pubkey_{}", PeerId::from(keypair.public()));
+        let bytes = seal_dht_value(&own_key, "ab".repeat(32), &keypair).unwrap();
+        let opened =
+            dht_record::SignedRecord::open(&own_key, &bytes, dht_record::now_ms()).unwrap();
+        assert_eq!(opened.value, "ab".repeat(32));
+
+        // Our own registry entry is signed on the way out.
+        let registry = serde_json::json!([{
+            "peerId": PeerId::from(keypair.public()).to_string(),
+            "walletAddress": "0xabc",
+            "updatedAt": 5,
+        }]);
+        assert!(seal_dht_value(HOST_REGISTRY_KEY, registry.to_string(), &keypair).is_ok());
     }
 
     // Bootstrap-discovery tests target the testnet preset directly because
@@ -6764,17 +9898,19 @@ mod tests {
         let request = FileTransferRequest {
             transfer_id: "tx-001".to_string(),
             file_name: "test.txt".to_string(),
-            file_data: vec![1, 2, 3, 4, 5],
+            file_size: 5,
+            file_hash: "ab".repeat(32),
             price_wei: "0".to_string(),
             sender_wallet: String::new(),
-            file_hash: String::new(),
-            file_size: 5,
+            closed: None,
         };
         let json = serde_json::to_string(&request).unwrap();
+        assert!(!json.contains("file_data"));
         let deserialized: FileTransferRequest = serde_json::from_str(&json).unwrap();
         assert_eq!(request.transfer_id, deserialized.transfer_id);
         assert_eq!(request.file_name, deserialized.file_name);
-        assert_eq!(request.file_data, deserialized.file_data);
+        assert_eq!(request.file_hash, deserialized.file_hash);
+        assert_eq!(request.file_size, deserialized.file_size);
     }
 
     #[test]
@@ -6782,11 +9918,11 @@ mod tests {
         let request = FileTransferRequest {
             transfer_id: "tx-paid-001".to_string(),
             file_name: "paid_file.zip".to_string(),
-            file_data: vec![],
+            file_size: 1048576,
+            file_hash: "deadbeef".to_string(),
             price_wei: "1000000000000000000".to_string(),
             sender_wallet: "0xabc123".to_string(),
-            file_hash: "deadbeef".to_string(),
-            file_size: 1048576,
+            closed: None,
         };
         let json = serde_json::to_string(&request).unwrap();
         let deserialized: FileTransferRequest = serde_json::from_str(&json).unwrap();
@@ -6794,7 +9930,45 @@ mod tests {
         assert_eq!(deserialized.sender_wallet, "0xabc123");
         assert_eq!(deserialized.file_hash, "deadbeef");
         assert_eq!(deserialized.file_size, 1048576);
-        assert!(deserialized.file_data.is_empty());
+    }
+
+    #[test]
+    fn file_transfer_offer_stays_small_for_huge_files() {
+        // The offer size must not depend on the file size: a 2 GB file is
+        // described, not carried.
+        let request = FileTransferRequest {
+            transfer_id: "tx-big".to_string(),
+            file_name: "video.mp4".to_string(),
+            file_size: 2 * 1024 * 1024 * 1024,
+            file_hash: "ab".repeat(32),
+            price_wei: "0".to_string(),
+            sender_wallet: String::new(),
+            closed: None,
+        };
+        let encoded = cbor4ii::serde::to_vec(Vec::new(), &request).unwrap();
+        assert!(encoded.len() < 256);
+    }
+
+    #[test]
+    fn ephemeral_signing_identity_signs_verifiable_file_info() {
+        let (address, private_key) = ephemeral_signing_identity().unwrap();
+        let payload = file_info_sign_payload(
+            "hash",
+            "file.txt",
+            5,
+            CHUNK_SIZE as u32,
+            1,
+            "hash",
+            "0",
+            &address,
+            None,
+            0,
+        );
+        let signature = crate::wallet::sign_message(&private_key, &payload).unwrap();
+        assert!(crate::wallet::verify_signature(
+            &payload, &signature, &address
+        ));
+        assert_ne!(ephemeral_signing_identity().unwrap().0, address);
     }
 
     #[test]
@@ -6907,15 +10081,11 @@ mod tests {
             file_size: 1048576,
             chunk_size: 262144,
             total_chunks: 4,
-            chunk_hashes: vec![
-                "hash0".to_string(),
-                "hash1".to_string(),
-                "hash2".to_string(),
-                "hash3".to_string(),
-            ],
+            merkle_root: "root".to_string(),
             price_wei: "1000000000000000".to_string(),
             wallet_address: "0x1234567890abcdef".to_string(),
             folder_hash: None,
+            voucher_interval: 16,
             signature: String::new(),
             error: None,
         };
@@ -6923,17 +10093,19 @@ mod tests {
         let deserialized: ChunkResponse = serde_json::from_str(&json).unwrap();
         if let ChunkResponse::FileInfo {
             total_chunks,
-            chunk_hashes,
+            merkle_root,
             price_wei,
             wallet_address,
+            voucher_interval,
             error,
             ..
         } = deserialized
         {
             assert_eq!(total_chunks, 4);
-            assert_eq!(chunk_hashes.len(), 4);
+            assert_eq!(merkle_root, "root");
             assert_eq!(price_wei, "1000000000000000");
             assert_eq!(wallet_address, "0x1234567890abcdef");
+            assert_eq!(voucher_interval, 16);
             assert!(error.is_none());
         } else {
             panic!("Expected FileInfo variant");
@@ -6947,7 +10119,7 @@ mod tests {
             file_hash: "abc123".to_string(),
             chunk_index: 0,
             chunk_data: Some(vec![10, 20, 30]),
-            chunk_hash: "deadbeef".to_string(),
+            proof: vec!["deadbeef".to_string(), "cafe".to_string()],
             error: None,
         };
         let json = serde_json::to_string(&resp).unwrap();
@@ -6955,14 +10127,14 @@ mod tests {
         if let ChunkResponse::Chunk {
             chunk_index,
             chunk_data,
-            chunk_hash,
+            proof,
             error,
             ..
         } = deserialized
         {
             assert_eq!(chunk_index, 0);
             assert_eq!(chunk_data.unwrap(), vec![10, 20, 30]);
-            assert_eq!(chunk_hash, "deadbeef");
+            assert_eq!(proof, vec!["deadbeef", "cafe"]);
             assert!(error.is_none());
         } else {
             panic!("Expected Chunk variant");
@@ -6978,10 +10150,11 @@ mod tests {
             file_size: 0,
             chunk_size: 262144,
             total_chunks: 0,
-            chunk_hashes: vec![],
+            merkle_root: String::new(),
             price_wei: "0".to_string(),
             wallet_address: String::new(),
             folder_hash: None,
+            voucher_interval: 0,
             signature: String::new(),
             error: Some("File not found".to_string()),
         };
@@ -7008,10 +10181,11 @@ mod tests {
             100,
             256_000,
             1,
-            &["h1".to_string()],
+            "root",
             "1000",
             "0xATTACKER",
             None,
+            0,
         );
         let b = file_info_sign_payload(
             "abc",
@@ -7019,10 +10193,11 @@ mod tests {
             100,
             256_000,
             1,
-            &["h1".to_string()],
+            "root",
             "1000",
             "0xVICTIM:0xATTACKER",
             None,
+            0,
         );
         assert_ne!(a, b);
     }
@@ -7030,26 +10205,10 @@ mod tests {
     #[test]
     fn file_info_sign_payload_is_deterministic() {
         let p1 = file_info_sign_payload(
-            "abc",
-            "doc.pdf",
-            1024,
-            256_000,
-            1,
-            &["h1".to_string()],
-            "100",
-            "0x1234",
-            None,
+            "abc", "doc.pdf", 1024, 256_000, 1, "root", "100", "0x1234", None, 0,
         );
         let p2 = file_info_sign_payload(
-            "abc",
-            "doc.pdf",
-            1024,
-            256_000,
-            1,
-            &["h1".to_string()],
-            "100",
-            "0x1234",
-            None,
+            "abc", "doc.pdf", 1024, 256_000, 1, "root", "100", "0x1234", None, 0,
         );
         assert_eq!(p1, p2);
     }
@@ -7066,7 +10225,7 @@ mod tests {
         let wallet = test_wallet_for_private_key(private_key);
 
         for price_wei in ["0", "1000000000000000000"] {
-            let chunk_hashes = vec!["chunk-a".to_string(), "chunk-b".to_string()];
+            let tree = crate::merkle::MerkleTree::from_bytes(&[7u8; CHUNK_SIZE + 1]);
             let access = ResolvedFileAccess {
                 price_wei: price_wei.parse::<u128>().unwrap(),
                 wallet_address: wallet.clone(),
@@ -7076,8 +10235,7 @@ mod tests {
                 format!("req-{}", price_wei),
                 "file-hash".to_string(),
                 "document.pdf".to_string(),
-                1024,
-                chunk_hashes.clone(),
+                &tree,
                 access,
                 private_key,
             );
@@ -7087,10 +10245,11 @@ mod tests {
                 file_name,
                 file_size,
                 total_chunks,
-                chunk_hashes: response_hashes,
+                merkle_root,
                 price_wei: response_price,
                 wallet_address,
                 folder_hash,
+                voucher_interval,
                 signature,
                 error,
                 ..
@@ -7098,12 +10257,19 @@ mod tests {
             {
                 assert_eq!(file_hash, "file-hash");
                 assert_eq!(file_name, "document.pdf");
-                assert_eq!(file_size, 1024);
+                assert_eq!(file_size, CHUNK_SIZE as u64 + 1);
                 assert_eq!(total_chunks, 2);
-                assert_eq!(response_hashes, chunk_hashes);
+                assert_eq!(merkle_root, tree.root());
                 assert_eq!(response_price, price_wei);
                 assert_eq!(wallet_address, wallet);
                 assert!(folder_hash.is_none());
+                // Only paid files are offered pay-as-you-go vouchers.
+                let expected_interval = if price_wei == "0" {
+                    0
+                } else {
+                    crate::micropayments::VOUCHER_CHUNK_INTERVAL
+                };
+                assert_eq!(voucher_interval, expected_interval);
                 assert!(!signature.is_empty());
                 assert!(error.is_none());
 
@@ -7113,10 +10279,11 @@ mod tests {
                     file_size,
                     CHUNK_SIZE as u32,
                     total_chunks,
-                    &response_hashes,
+                    &merkle_root,
                     &response_price,
                     &wallet_address,
                     folder_hash.as_deref(),
+                    voucher_interval,
                 );
                 assert!(crate::wallet::verify_signature(
                     &payload,
@@ -7140,8 +10307,7 @@ mod tests {
             "req-invalid".to_string(),
             "file-hash".to_string(),
             "document.pdf".to_string(),
-            1024,
-            vec!["chunk-a".to_string()],
+            &crate::merkle::MerkleTree::from_bytes(b"data"),
             access,
             "not-a-private-key",
         );
@@ -7149,7 +10315,7 @@ mod tests {
         if let ChunkResponse::FileInfo {
             file_size,
             total_chunks,
-            chunk_hashes,
+            merkle_root,
             price_wei,
             wallet_address,
             folder_hash,
@@ -7160,7 +10326,7 @@ mod tests {
         {
             assert_eq!(file_size, 0);
             assert_eq!(total_chunks, 0);
-            assert!(chunk_hashes.is_empty());
+            assert!(merkle_root.is_empty());
             assert_eq!(price_wei, "0");
             assert!(wallet_address.is_empty());
             assert_eq!(folder_hash.as_deref(), Some("folder-hash"));
@@ -7177,15 +10343,7 @@ mod tests {
     #[test]
     fn file_info_sign_payload_binds_folder_hash_when_present() {
         let direct = file_info_sign_payload(
-            "abc",
-            "doc.pdf",
-            1024,
-            256_000,
-            1,
-            &["h1".to_string()],
-            "100",
-            "0x1234",
-            None,
+            "abc", "doc.pdf", 1024, 256_000, 1, "root", "100", "0x1234", None, 0,
         );
         let folder_a = file_info_sign_payload(
             "abc",
@@ -7193,10 +10351,11 @@ mod tests {
             1024,
             256_000,
             1,
-            &["h1".to_string()],
+            "root",
             "100",
             "0x1234",
             Some("folder-a"),
+            0,
         );
         let folder_b = file_info_sign_payload(
             "abc",
@@ -7204,15 +10363,140 @@ mod tests {
             1024,
             256_000,
             1,
-            &["h1".to_string()],
+            "root",
             "100",
             "0x1234",
             Some("folder-b"),
+            0,
         );
         assert_ne!(direct, folder_a);
         assert_ne!(folder_a, folder_b);
     }
 
+    fn drop_offer_info(offers: &[(&str, PeerId)], offered_at: Instant) -> SharedFileInfo {
+        SharedFileInfo {
+            file_path: "/outbox/offer.bin".to_string(),
+            file_name: "offer.bin".to_string(),
+            file_size: 1024,
+            merkle_tree: Some(Arc::new(crate::merkle::MerkleTree::from_bytes(b"offer"))),
+            price_wei: 0,
+            wallet_address: String::new(),
+            private_key: String::new(),
+            folder_access: HashMap::new(),
+            drop_offers: offers
+                .iter()
+                .map(|(transfer_id, peer_id)| {
+                    (
+                        transfer_id.to_string(),
+                        DropOffer {
+                            peer_id: *peer_id,
+                            offered_at,
+                        },
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn drop_offers_are_served_only_to_the_offered_peer() {
+        let receiver = PeerId::random();
+        let stranger = PeerId::random();
+        let info = drop_offer_info(&[("tx-1", receiver)], Instant::now());
+
+        assert!(resolve_file_access(&info, receiver, None).is_ok());
+        assert!(chunk_request_access_error(None, receiver, "offer", &info).is_none());
+        assert_eq!(
+            resolve_file_access(&info, stranger, None).unwrap_err(),
+            "File not found"
+        );
+        assert_eq!(
+            chunk_request_access_error(None, stranger, "offer", &info).as_deref(),
+            Some("File not found")
+        );
+
+        let mut public = info.clone();
+        public.drop_offers.clear();
+        assert!(resolve_file_access(&public, stranger, None).is_ok());
+    }
+
+    #[test]
+    fn closing_the_last_drop_offer_unshares_the_file() {
+        let alice = PeerId::random();
+        let bob = PeerId::random();
+        let mut shared = HashMap::from([(
+            "offer".to_string(),
+            drop_offer_info(&[("tx-a", alice), ("tx-b", bob)], Instant::now()),
+        )]);
+
+        // Only the receiver an offer was made to can close it.
+        assert!(close_drop_offer(&mut shared, "tx-a", bob).is_none());
+        assert_eq!(shared["offer"].drop_offers.len(), 2);
+
+        assert!(close_drop_offer(&mut shared, "tx-a", alice).is_none());
+        assert!(!drop_offer_allows(&shared["offer"], alice));
+        assert!(drop_offer_allows(&shared["offer"], bob));
+
+        let unshared = close_drop_offer(&mut shared, "tx-b", bob).unwrap();
+        assert_eq!(unshared.file_path, "/outbox/offer.bin");
+        assert!(shared.is_empty());
+        assert!(close_drop_offer(&mut shared, "tx-b", bob).is_none());
+    }
+
+    #[test]
+    fn drop_offers_expire_with_the_outbox() {
+        let receiver = PeerId::random();
+        let offered_at = Instant::now();
+        let mut public = drop_offer_info(&[], offered_at);
+        public.file_path = "/shared/public.bin".to_string();
+        let mut shared = HashMap::from([
+            (
+                "offer".to_string(),
+                drop_offer_info(&[("tx-1", receiver)], offered_at),
+            ),
+            ("public".to_string(), public),
+        ]);
+
+        assert!(expire_drop_offers(&mut shared, offered_at).is_empty());
+        let later = offered_at + crate::file_transfer::OUTBOX_RETENTION + Duration::from_secs(1);
+        let expired = expire_drop_offers(&mut shared, later);
+        assert_eq!(expired.len(), 1);
+        assert_eq!(expired[0].file_path, "/outbox/offer.bin");
+        assert!(!shared.contains_key("offer"));
+        assert!(shared.contains_key("public"));
+    }
+
+    #[tokio::test]
+    async fn drop_offer_sweep_unshares_expired_offers_without_a_new_offer() {
+        let offered_at = Instant::now();
+        let shared: SharedFilesMap = Arc::new(Mutex::new(HashMap::from([(
+            "offer".to_string(),
+            drop_offer_info(&[("tx-1", PeerId::random())], offered_at),
+        )])));
+
+        assert_eq!(sweep_drop_offers(&shared, offered_at).await, 0);
+        assert!(shared.lock().await.contains_key("offer"));
+        let later = offered_at + crate::file_transfer::OUTBOX_RETENTION + Duration::from_secs(1);
+        assert_eq!(sweep_drop_offers(&shared, later).await, 1);
+        assert!(shared.lock().await.is_empty());
+    }
+
+    #[test]
+    fn file_transfer_close_notice_round_trips() {
+        let notice = FileTransferRequest::close_notice(
+            "tx-1".to_string(),
+            "ab".repeat(32),
+            crate::file_transfer::TransferStatus::Declined,
+        );
+        let encoded = cbor4ii::serde::to_vec(Vec::new(), &notice).unwrap();
+        let decoded: FileTransferRequest = cbor4ii::serde::from_slice(&encoded).unwrap();
+        assert_eq!(
+            decoded.closed,
+            Some(crate::file_transfer::TransferStatus::Declined)
+        );
+        assert_eq!(decoded.transfer_id, "tx-1");
+    }
+
     #[test]
     fn paid_folder_child_rejects_direct_file_info_access() {
         let mut folder_access = HashMap::new();
@@ -7228,20 +10512,21 @@ mod tests {
             file_path: "/path/to/file.txt".to_string(),
             file_name: "file.txt".to_string(),
             file_size: 1024,
-            chunk_hashes: Some(vec!["hash0".to_string()]),
+            merkle_tree: Some(Arc::new(crate::merkle::MerkleTree::from_bytes(b"hash0"))),
             price_wei: 0,
             wallet_address: "0xdirect".to_string(),
             private_key: String::new(),
             folder_access,
+            drop_offers: HashMap::new(),
         };
 
-        let direct = resolve_file_access(&info, None);
+        let direct = resolve_file_access(&info, PeerId::random(), None);
         assert!(
             direct.is_err(),
             "paid folder children must not expose free direct FileInfo"
         );
 
-        let folder = resolve_file_access(&info, Some("folder-abc")).unwrap();
+        let folder = resolve_file_access(&info, PeerId::random(), Some("folder-abc")).unwrap();
         assert_eq!(folder.price_wei, 10_000);
         assert_eq!(folder.wallet_address, "0xfolder");
         assert_eq!(folder.folder_hash.as_deref(), Some("folder-abc"));
@@ -7288,6 +10573,203 @@ mod tests {
         );
     }
 
+    #[test]
+    fn voucher_settlement_claims_are_separate_from_download_payments() {
+        let tx = "0xabc";
+        let payer = "0xaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa";
+        let peer = PeerId::random();
+        let settlement = PaymentScope::Settlement {
+            payer: payer.to_string(),
+        };
+        let file = PaymentScope::DirectFile {
+            file_hash: "file-a".to_string(),
+        };
+        let key = payment_scope_key(tx, &settlement, payer, &peer);
+        assert!(key.starts_with("settlement:0xabc:"));
+        assert!(ledger_entry_uses_tx(&key, tx));
+
+        let mut spent = HashSet::new();
+        assert_eq!(
+            classify_payment_claim(&spent, tx, &settlement, payer, &peer),
+            PaymentClaimStatus::Available
+        );
+        spent.insert(key);
+        assert_eq!(
+            classify_payment_claim(&spent, tx, &settlement, payer, &peer),
+            PaymentClaimStatus::ClaimedOtherScope
+        );
+        assert_eq!(
+            classify_payment_claim(&spent, tx, &file, payer, &peer),
+            PaymentClaimStatus::ClaimedOtherScope,
+            "a settlement tx must not also unlock a download"
+        );
+
+        let mut spent = HashSet::new();
+        spent.insert(payment_scope_key(tx, &file, payer, &peer));
+        assert_eq!(
+            classify_payment_claim(&spent, tx, &settlement, payer, &peer),
+            PaymentClaimStatus::ClaimedOtherScope,
+            "a download payment must not also settle vouchers"
+        );
+    }
+
+    #[test]
+    fn charge_voucher_allowance_counts_distinct_chunks() {
+        let mut access = AuthorizedChunkAccess {
+            peer_id: PeerId::random(),
+            scope: PaymentScope::DirectFile {
+                file_hash: "file-a".to_string(),
+            },
+            voucher_allowance: Some(VoucherAllowance {
+                covered: 2,
+                served: HashSet::new(),
+            }),
+        };
+        assert_eq!(charge_voucher_allowance(Some(&mut access), 5), Ok(true));
+        assert_eq!(charge_voucher_allowance(Some(&mut access), 5), Ok(false));
+        assert_eq!(charge_voucher_allowance(Some(&mut access), 6), Ok(true));
+        assert_eq!(
+            charge_voucher_allowance(Some(&mut access), 7),
+            Err(VOUCHER_EXHAUSTED_ERROR.to_string())
+        );
+        assert_eq!(charge_voucher_allowance(Some(&mut access), 6), Ok(false));
+
+        access.voucher_allowance = None;
+        assert_eq!(charge_voucher_allowance(Some(&mut access), 7), Ok(false));
+        assert_eq!(charge_voucher_allowance(None, 7), Ok(false));
+    }
+
+    #[test]
+    fn refunded_voucher_chunk_frees_the_allowance() {
+        let mut access = AuthorizedChunkAccess {
+            peer_id: PeerId::random(),
+            scope: PaymentScope::DirectFile {
+                file_hash: "file-a".to_string(),
+            },
+            voucher_allowance: Some(VoucherAllowance {
+                covered: 1,
+                served: HashSet::new(),
+            }),
+        };
+        assert_eq!(charge_voucher_allowance(Some(&mut access), 5), Ok(true));
+        refund_voucher_allowance(Some(&mut access), 5);
+        assert_eq!(charge_voucher_allowance(Some(&mut access), 6), Ok(true));
+        assert_eq!(
+            charge_voucher_allowance(Some(&mut access), 5),
+            Err(VOUCHER_EXHAUSTED_ERROR.to_string())
+        );
+    }
+
+    #[test]
+    fn accept_payment_voucher_checks_the_file_terms() {
+        let private_key = "0x0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef";
+        let payer = test_wallet_for_private_key(private_key);
+        let dir = tempfile::tempdir().unwrap();
+        let mut book =
+            crate::micropayments::ReceivedVoucherBook::load(dir.path().join("received.json"));
+        let info = SharedFileInfo {
+            file_path: "/tmp/file".to_string(),
+            file_name: "file.bin".to_string(),
+            file_size: 3 * CHUNK_SIZE as u64,
+            merkle_tree: None,
+            price_wei: 3_000,
+            wallet_address: "0x2222222222222222222222222222222222222222".to_string(),
+            private_key: String::new(),
+            folder_access: HashMap::new(),
+            drop_offers: HashMap::new(),
+        };
+        let terms = crate::micropayments::VoucherTerms {
+            session_id: "req-1".to_string(),
+            payer,
+            payee: info.wallet_address.clone(),
+            file_hash: "file-a".to_string(),
+            price_wei: "3000".to_string(),
+            total_chunks: 3,
+        };
+        let voucher = |terms: &crate::micropayments::VoucherTerms| {
+            crate::micropayments::PaymentVoucher::sign(terms, 1, 100, private_key).unwrap()
+        };
+
+        let mut cheaper = terms.clone();
+        cheaper.price_wei = "30".to_string();
+        assert!(accept_payment_voucher(
+            &mut book,
+            voucher(&cheaper),
+            PeerId::random(),
+            "req-1",
+            "file-a",
+            &info,
+            100
+        )
+        .is_err());
+        let mut elsewhere = terms.clone();
+        elsewhere.payee = "0x3333333333333333333333333333333333333333".to_string();
+        assert!(accept_payment_voucher(
+            &mut book,
+            voucher(&elsewhere),
+            PeerId::random(),
+            "req-1",
+            "file-a",
+            &info,
+            100
+        )
+        .is_err());
+        assert!(
+            accept_payment_voucher(
+                &mut book,
+                voucher(&terms),
+                PeerId::random(),
+                "req-2",
+                "file-a",
+                &info,
+                100
+            )
+            .is_err(),
+            "a voucher belongs to one download request"
+        );
+        assert_eq!(
+            accept_payment_voucher(
+                &mut book,
+                voucher(&terms),
+                PeerId::random(),
+                "req-1",
+                "file-a",
+                &info,
+                100
+            ),
+            Ok(1)
+        );
+
+        let mut free = info.clone();
+        free.price_wei = 0;
+        assert!(accept_payment_voucher(
+            &mut book,
+            voucher(&terms),
+            PeerId::random(),
+            "req-1",
+            "file-a",
+            &free,
+            100
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn file_info_sign_payload_binds_voucher_interval() {
+        let upfront = file_info_sign_payload(
+            "abc", "doc.pdf", 1024, 256_000, 1, "root", "100", "0x1234", None, 0,
+        );
+        let vouchers = file_info_sign_payload(
+            "abc", "doc.pdf", 1024, 256_000, 1, "root", "100", "0x1234", None, 16,
+        );
+        let other_interval = file_info_sign_payload(
+            "abc", "doc.pdf", 1024, 256_000, 1, "root", "100", "0x1234", None, 1,
+        );
+        assert_ne!(upfront, vouchers);
+        assert_ne!(vouchers, other_interval);
+        assert!(vouchers.starts_with(&upfront));
+    }
+
     #[tokio::test]
     async fn persist_spent_tx_set_to_path_persists_claims() {
         let dir = tempfile::tempdir().unwrap();
@@ -7342,6 +10824,74 @@ mod tests {
         assert_eq!(store.lock().await.len(), 1);
     }
 
+    #[test]
+    fn direct_payment_is_resumable_only_by_the_payer_and_peer_that_claimed_it() {
+        let payer = "0xaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa";
+        let peer = PeerId::random();
+        let scope = PaymentScope::DirectFile {
+            file_hash: "File-A".to_string(),
+        };
+        let mut spent = HashSet::new();
+        spent.insert(payment_scope_key("0xABC", &scope, payer, &peer));
+
+        assert_eq!(
+            classify_payment_claim(&spent, "0xabc", &scope, payer, &peer),
+            PaymentClaimStatus::ClaimedDirectFile
+        );
+        assert!(direct_payment_claimed_by(
+            &spent,
+            "0xabc",
+            &scope,
+            &payer.to_uppercase().replace("0X", "0x"),
+            &peer
+        ));
+        // Another downloader replaying the public tx is still refused.
+        assert_eq!(
+            classify_payment_claim(&spent, "0xabc", &scope, "0xbbbb", &PeerId::random()),
+            PaymentClaimStatus::ClaimedDirectFile
+        );
+        assert!(!direct_payment_claimed_by(
+            &spent,
+            "0xabc",
+            &scope,
+            payer,
+            &PeerId::random()
+        ));
+        assert!(!direct_payment_claimed_by(
+            &spent, "0xabc", &scope, "0xbbbb", &peer
+        ));
+        // A different file under the same tx is a different scope.
+        let other = PaymentScope::DirectFile {
+            file_hash: "file-b".to_string(),
+        };
+        assert_eq!(
+            classify_payment_claim(&spent, "0xabc", &other, payer, &peer),
+            PaymentClaimStatus::ClaimedOtherScope
+        );
+        assert!(!direct_payment_claimed_by(
+            &spent, "0xabc", &other, payer, &peer
+        ));
+    }
+
+    #[test]
+    fn legacy_direct_payment_entries_stay_single_use() {
+        let payer = "0xaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa";
+        let peer = PeerId::random();
+        let scope = PaymentScope::DirectFile {
+            file_hash: "file-a".to_string(),
+        };
+        for legacy in ["file:0xabc:file-a", "0xabc:file-a"] {
+            let spent = HashSet::from([legacy.to_string()]);
+            assert_eq!(
+                classify_payment_claim(&spent, "0xabc", &scope, payer, &peer),
+                PaymentClaimStatus::ClaimedDirectFile
+            );
+            assert!(!direct_payment_claimed_by(
+                &spent, "0xabc", &scope, payer, &peer
+            ));
+        }
+    }
+
     #[tokio::test]
     async fn claim_payment_tx_fails_closed_when_persistence_fails() {
         let dir = tempfile::tempdir().unwrap();
@@ -7385,11 +10935,12 @@ mod tests {
             file_path: "/path/to/file.txt".to_string(),
             file_name: "file.txt".to_string(),
             file_size: 1024,
-            chunk_hashes: Some(vec!["hash0".to_string()]),
+            merkle_tree: Some(Arc::new(crate::merkle::MerkleTree::from_bytes(b"hash0"))),
             price_wei: 0,
             wallet_address: "0xdirect".to_string(),
             private_key: String::new(),
             folder_access,
+            drop_offers: HashMap::new(),
         };
 
         assert!(
@@ -7402,6 +10953,7 @@ mod tests {
             scope: PaymentScope::Folder {
                 folder_hash: "folder-abc".to_string(),
             },
+            voucher_allowance: None,
         };
         assert!(chunk_request_access_error(Some(&folder_auth), peer, "file-hash", &info).is_none());
         assert!(
@@ -7415,6 +10967,7 @@ mod tests {
             scope: PaymentScope::DirectFile {
                 file_hash: "other-file".to_string(),
             },
+            voucher_allowance: None,
         };
         assert!(
             chunk_request_access_error(Some(&wrong_file_auth), peer, "file-hash", &info).is_some()
@@ -7427,11 +10980,12 @@ mod tests {
             file_path: "/path/to/file.txt".to_string(),
             file_name: "file.txt".to_string(),
             file_size: 1024,
-            chunk_hashes: Some(vec!["hash0".to_string()]),
+            merkle_tree: Some(Arc::new(crate::merkle::MerkleTree::from_bytes(b"hash0"))),
             price_wei: 0,
             wallet_address: "0xdirect".to_string(),
             private_key: String::new(),
             folder_access: HashMap::new(),
+            drop_offers: HashMap::new(),
         };
 
         assert!(chunk_request_access_error(None, PeerId::random(), "file-hash", &info).is_none());
@@ -7458,11 +11012,13 @@ mod tests {
         let stats = NetworkStats {
             connected_peers: 5,
             total_peers: 10,
+            bandwidth: Default::default(),
         };
         let json = serde_json::to_string(&stats).unwrap();
         // camelCase serialization
         assert!(json.contains("connectedPeers"));
         assert!(json.contains("totalPeers"));
+        assert!(json.contains("\"bandwidth\":{\"uploadBps\""));
     }
 
     #[test]
@@ -7471,15 +11027,16 @@ mod tests {
             file_path: "/path/to/file.txt".to_string(),
             file_name: "file.txt".to_string(),
             file_size: 1024,
-            chunk_hashes: None,
+            merkle_tree: None,
             price_wei: 0,
             wallet_address: String::new(),
             private_key: String::new(),
             folder_access: HashMap::new(),
+            drop_offers: HashMap::new(),
         };
         assert_eq!(info.file_name, "file.txt");
         assert_eq!(info.file_size, 1024);
-        assert!(info.chunk_hashes.is_none());
+        assert!(info.merkle_tree.is_none());
         assert_eq!(info.price_wei, 0);
     }
 
@@ -7489,11 +11046,12 @@ mod tests {
             file_path: "/path/to/file.txt".to_string(),
             file_name: "file.txt".to_string(),
             file_size: 1024,
-            chunk_hashes: None,
+            merkle_tree: None,
             price_wei: 5_000_000_000_000_000,
             wallet_address: "0xabc123".to_string(),
             private_key: String::new(),
             folder_access: HashMap::new(),
+            drop_offers: HashMap::new(),
         };
         assert_eq!(info.price_wei, 5_000_000_000_000_000);
         assert_eq!(info.wallet_address, "0xabc123");
@@ -7571,20 +11129,33 @@ mod tests {
     }
 
     #[test]
-    fn test_compute_chunk_hashes() {
-        // Create a temp file with known content
-        let dir = std::env::temp_dir();
-        let path = dir.join("chiral_test_chunk_hashes.bin");
+    fn test_compute_merkle_tree() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("chunks.bin");
         let data = vec![0xABu8; CHUNK_SIZE * 2 + 100]; // 2.x chunks
         std::fs::write(&path, &data).unwrap();
+        let path = path.to_str().unwrap();
 
-        let hashes = compute_chunk_hashes(path.to_str().unwrap()).unwrap();
-        assert_eq!(hashes.len(), 3); // ceil((2*CHUNK_SIZE + 100) / CHUNK_SIZE) = 3
-                                     // Each hash should be a 64-char hex string (SHA-256)
-        for h in &hashes {
-            assert_eq!(h.len(), 64);
-        }
+        let root = crate::merkle::MerkleTree::from_bytes(&data).root();
+        let tree = compute_merkle_tree(path, &root).unwrap();
+        assert_eq!(tree.chunk_count(), 3);
+        assert_eq!(tree.root(), root);
+
+        // Files published under their plain SHA-256 keep being served.
+        let legacy = hex::encode(Sha256::digest(&data));
+        assert!(compute_merkle_tree(path, &legacy).is_ok());
+
+        assert!(compute_merkle_tree(path, &"0".repeat(64)).is_err());
+        assert!(compute_merkle_tree("/nonexistent/chunks.bin", &root).is_err());
+    }
 
-        std::fs::remove_file(&path).unwrap();
+    #[test]
+    fn file_info_sign_payload_binds_merkle_root() {
+        let sign = |root: &str| {
+            file_info_sign_payload(
+                "abc", "doc.pdf", 1024, 256_000, 1, root, "100", "0x1234", None, 0,
+            )
+        };
+        assert_ne!(sign("root-a"), sign("root-b"));
     }
 }
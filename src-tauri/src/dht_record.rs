@@ -0,0 +1,1042 @@
+//! Signed envelopes for every value stored in the Kademlia DHT.
+//!
+//! A raw `put_record` used to accept any bytes under any key, so anyone
+//! could overwrite `chiral_host_registry`, an agreement, or a peer's
+//! encryption key. Every value is now wrapped in a [`SignedRecord`] that
+//! names its kind, its publisher (a libp2p peer ID), a sequence number and
+//! an expiry, signed by the publisher's identity key. Nodes check the
+//! envelope and a per-kind validator before storing an inbound `PutRecord`
+//! and before trusting a `GetRecord` result, so forged or stale values are
+//! dropped at the store instead of being reconciled later.
+//!
+//! Ownership rules per kind:
+//! - Peer-owned keys (`chiral_seeder_*`, `chiral_host_*`, `chiral_pubkey_*`,
+//!   `chiral_kwpost_*`, `chiral_verdictpost_*`) end in a peer ID and only
+//!   that peer may publish them.
+//! - Shared keys (the host registry, reputation issuer keys) accept any
+//!   publisher whose content validates; the highest sequence number wins.
+//!   A newer host registry must still carry every other host's entry from
+//!   the stored copy.
+//! - File metadata and folder manifests stay with the wallet that signed
+//!   the stored copy, and an agreement may only be replaced by one of its
+//!   stored parties, naming the same host and client.
+//! - Anything else belongs to its first publisher until the record expires.
+
+use libp2p::identity::{Keypair, PublicKey};
+use libp2p::kad::{self, store::RecordStore};
+use libp2p::PeerId;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Envelope format version.
+pub const RECORD_VERSION: u8 = 1;
+/// How long a freshly sealed record stays valid.
+pub const RECORD_LIFETIME_SECS: u64 = 6 * 60 * 60;
+/// Own records with less than this left are re-sealed by the refresh task.
+pub const RECORD_REFRESH_MARGIN_SECS: u64 = RECORD_LIFETIME_SECS / 2;
+/// Records claiming to live longer than this are rejected.
+const MAX_RECORD_LIFETIME_SECS: u64 = 2 * RECORD_LIFETIME_SECS;
+/// How far ahead of our clock a sequence number may be.
+const MAX_CLOCK_SKEW_MS: u64 = 10 * 60 * 1000;
+
+/// How far past the clock the sequence counter may run before resetting.
+const SEQ_BUMP_WINDOW_MS: u64 = 1000;
+
+/// Highest sequence number handed out, so two puts in the same millisecond
+/// still order correctly.
+static LAST_SEQ: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RecordKind {
+    FileMetadata,
+    Seeder,
+    Folder,
+    HostRegistry,
+    HostAd,
+    Agreement,
+    EncryptionKey,
+    ReputationIssuer,
+    KeywordPosting,
+    ReputationVerdicts,
+    Generic,
+}
+
+/// Key prefixes in match order; the exact registry key has to be checked
+/// before the `chiral_host_` prefix it shares.
+const KIND_PREFIXES: &[(&str, RecordKind)] = &[
+    ("chiral_file_", RecordKind::FileMetadata),
+    ("chiral_seeder_", RecordKind::Seeder),
+    ("chiral_folder_", RecordKind::Folder),
+    ("chiral_host_", RecordKind::HostAd),
+    ("chiral_agreement_", RecordKind::Agreement),
+    ("chiral_pubkey_", RecordKind::EncryptionKey),
+    (
+        crate::reputation::ISSUER_KEY_DHT_PREFIX,
+        RecordKind::ReputationIssuer,
+    ),
+    ("chiral_kwpost_", RecordKind::KeywordPosting),
+    (
+        crate::reputation::VERDICT_BUNDLE_PREFIX,
+        RecordKind::ReputationVerdicts,
+    ),
+];
+
+const HOST_REGISTRY_KEY: &str = "chiral_host_registry";
+
+impl RecordKind {
+    pub fn for_key(key: &str) -> Self {
+        if key == HOST_REGISTRY_KEY {
+            return Self::HostRegistry;
+        }
+        KIND_PREFIXES
+            .iter()
+            .find(|(prefix, _)| key.starts_with(prefix))
+            .map(|(_, kind)| *kind)
+            .unwrap_or(Self::Generic)
+    }
+
+    /// The key with this kind's prefix removed.
+    fn key_suffix(self, key: &str) -> &str {
+        KIND_PREFIXES
+            .iter()
+            .find(|(_, kind)| *kind == self)
+            .and_then(|(prefix, _)| key.strip_prefix(prefix))
+            .unwrap_or(key)
+    }
+}
+
+/// A DHT value wrapped with its publisher's signature.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SignedRecord {
+    pub v: u8,
+    pub kind: RecordKind,
+    /// Peer ID whose identity key signed this record.
+    pub publisher: String,
+    /// Unix time in milliseconds at sealing, bumped to stay monotonic.
+    pub seq: u64,
+    /// Unix time in seconds after which the record is dropped.
+    pub expires_at: u64,
+    pub value: String,
+    #[serde(default)]
+    pub signature: String,
+}
+
+impl SignedRecord {
+    /// Wrap `value` for `key` and sign it with the node identity.
+    pub fn seal(key: &str, value: String, keypair: &Keypair, now_ms: u64) -> Result<Self, String> {
+        let mut record = Self {
+            v: RECORD_VERSION,
+            kind: RecordKind::for_key(key),
+            publisher: PeerId::from(keypair.public()).to_string(),
+            seq: next_seq(now_ms),
+            expires_at: now_ms / 1000 + RECORD_LIFETIME_SECS,
+            value,
+            signature: String::new(),
+        };
+        let signature = keypair
+            .sign(&record.sign_payload(key))
+            .map_err(|e| format!("failed to sign DHT record: {}", e))?;
+        record.signature = hex::encode(signature);
+        Ok(record)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        serde_json::to_vec(self).unwrap_or_default()
+    }
+
+    /// Parse and fully validate a stored value for `key`: envelope, signature,
+    /// lifetime, and the kind-specific contents.
+    pub fn open(key: &str, bytes: &[u8], now_ms: u64) -> Result<Self, String> {
+        let record: Self = serde_json::from_slice(bytes)
+            .map_err(|_| "value is not a signed DHT record".to_string())?;
+        if record.v != RECORD_VERSION {
+            return Err(format!("unsupported record version {}", record.v));
+        }
+        if record.kind != RecordKind::for_key(key) {
+            return Err(format!("record kind {:?} does not match key", record.kind));
+        }
+        let now_secs = now_ms / 1000;
+        if record.expires_at <= now_secs {
+            return Err("record has expired".to_string());
+        }
+        if record.expires_at > now_secs + MAX_RECORD_LIFETIME_SECS {
+            return Err("record expiry is too far in the future".to_string());
+        }
+        if record.seq > now_ms + MAX_CLOCK_SKEW_MS {
+            return Err("record sequence is ahead of the local clock".to_string());
+        }
+        let publisher =
+            PeerId::from_str(&record.publisher).map_err(|_| "invalid publisher".to_string())?;
+        let signature =
+            hex::decode(&record.signature).map_err(|_| "invalid signature".to_string())?;
+        let public_key = public_key_from_peer_id(&publisher)
+            .ok_or_else(|| "publisher key is not embedded in its peer ID".to_string())?;
+        if !public_key.verify(&record.sign_payload(key), &signature) {
+            return Err("signature does not match publisher".to_string());
+        }
+        validate_value(record.kind, key, &record.publisher, &record.value)?;
+        Ok(record)
+    }
+
+    /// True once less than the refresh margin of its lifetime is left.
+    pub fn needs_refresh(&self, now_ms: u64) -> bool {
+        self.expires_at <= now_ms / 1000 + RECORD_REFRESH_MARGIN_SECS
+    }
+
+    /// Length-prefixed so no field can bleed into its neighbour; the key is
+    /// included so a record can't be replayed under another key.
+    fn sign_payload(&self, key: &str) -> Vec<u8> {
+        let kind = serde_json::to_string(&self.kind).unwrap_or_default();
+        let mut out = Vec::with_capacity(96 + key.len() + self.value.len());
+        out.extend_from_slice(b"chiral-dht-record");
+        out.push(self.v);
+        for part in [
+            key.as_bytes(),
+            kind.as_bytes(),
+            self.publisher.as_bytes(),
+            self.value.as_bytes(),
+        ] {
+            out.extend_from_slice(&(part.len() as u32).to_le_bytes());
+            out.extend_from_slice(part);
+        }
+        out.extend_from_slice(&self.seq.to_le_bytes());
+        out.extend_from_slice(&self.expires_at.to_le_bytes());
+        out
+    }
+}
+
+fn next_seq(now_ms: u64) -> u64 {
+    let prev = LAST_SEQ
+        .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |last| {
+            Some(bump_seq(last, now_ms))
+        })
+        .unwrap_or(0);
+    bump_seq(prev, now_ms)
+}
+
+/// Follow the clock, stepping past `last` only while it is within a second
+/// of `now_ms`; a `last` far from the clock (a jump either way) is dropped
+/// rather than dragging every later sequence number with it.
+fn bump_seq(last: u64, now_ms: u64) -> u64 {
+    if last >= now_ms && last - now_ms < SEQ_BUMP_WINDOW_MS {
+        last + 1
+    } else {
+        now_ms
+    }
+}
+
+pub fn now_ms() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Ed25519 peer IDs inline their public key; hashed (RSA) IDs don't and
+/// can't sign records.
+pub fn public_key_from_peer_id(peer_id: &PeerId) -> Option<PublicKey> {
+    let multihash: &libp2p::multihash::Multihash<64> = peer_id.as_ref();
+    if multihash.code() != 0 {
+        return None;
+    }
+    PublicKey::try_decode_protobuf(multihash.digest()).ok()
+}
+
+/// Decide whether an inbound record may replace what we already store for
+/// `key`. Re-sent identical bytes (replication) are always fine; otherwise
+/// the incoming record must be newer, and first-writer keys stay with
+/// their publisher (or signing wallet, or agreement parties) until the
+/// stored copy expires.
+pub fn accept_inbound(
+    key: &str,
+    incoming: &[u8],
+    existing: Option<&[u8]>,
+    now_ms: u64,
+) -> Result<SignedRecord, String> {
+    let record = SignedRecord::open(key, incoming, now_ms)?;
+    let Some(existing_bytes) = existing else {
+        return Ok(record);
+    };
+    if existing_bytes == incoming {
+        return Ok(record);
+    }
+    // A stored copy that no longer validates (expired, or written before
+    // the envelope existed) never blocks a valid replacement.
+    let Ok(current) = SignedRecord::open(key, existing_bytes, now_ms) else {
+        return Ok(record);
+    };
+    match record.kind {
+        RecordKind::Generic if record.publisher != current.publisher => {
+            return Err(format!("key is owned by {}", current.publisher));
+        }
+        RecordKind::FileMetadata | RecordKind::Folder => check_signer_unchanged(&current, &record)?,
+        RecordKind::Agreement => check_agreement_update(&current, &record)?,
+        _ => {}
+    }
+    if record.seq <= current.seq {
+        return Err(format!(
+            "stale record (seq {} <= stored {})",
+            record.seq, current.seq
+        ));
+    }
+    if record.kind == RecordKind::HostRegistry {
+        check_host_registry_update(&current, &record)?;
+    }
+    Ok(record)
+}
+
+/// A newer host registry has to keep every entry of the stored copy, at
+/// the same or a later `updatedAt`. Only a host itself may drop or roll
+/// back its own entry, so nobody can wipe the registry by publishing `[]`
+/// or a subset at a higher sequence number. Entries leave with the stored
+/// copy once its envelope expires.
+fn check_host_registry_update(
+    current: &SignedRecord,
+    incoming: &SignedRecord,
+) -> Result<(), String> {
+    let parse = |value: &str| -> Result<Vec<serde_json::Value>, String> {
+        serde_json::from_str(value).map_err(|e| format!("malformed host registry: {}", e))
+    };
+    let incoming_entries = parse(&incoming.value)?;
+    for entry in parse(&current.value)? {
+        let (peer_id, _, updated_at) = registry_entry_fields(&entry);
+        if peer_id == incoming.publisher {
+            continue;
+        }
+        let kept = incoming_entries.iter().any(|candidate| {
+            let (candidate_peer, _, candidate_updated_at) = registry_entry_fields(candidate);
+            candidate_peer == peer_id && candidate_updated_at >= updated_at
+        });
+        if !kept {
+            return Err(format!(
+                "host registry update drops the entry for '{}'",
+                peer_id
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Anyone can sign fresh metadata for a popular hash with their own wallet,
+/// so a newer file metadata or folder manifest must come from the wallet
+/// that signed the stored one.
+fn check_signer_unchanged(current: &SignedRecord, incoming: &SignedRecord) -> Result<(), String> {
+    let field = match current.kind {
+        RecordKind::Folder => "ownerWallet",
+        _ => "walletAddress",
+    };
+    let signer = |value: &str| {
+        serde_json::from_str::<serde_json::Value>(value)
+            .ok()
+            .and_then(|v| v.get(field)?.as_str().map(str::to_ascii_lowercase))
+    };
+    let owner = signer(&current.value).unwrap_or_default();
+    if signer(&incoming.value).as_deref() != Some(owner.as_str()) {
+        return Err(format!("key is owned by wallet {}", owner));
+    }
+    Ok(())
+}
+
+/// Only a party named in the stored agreement may replace it, and the
+/// replacement has to name the same host and client.
+fn check_agreement_update(current: &SignedRecord, incoming: &SignedRecord) -> Result<(), String> {
+    let parties = |value: &str| {
+        let agreement: serde_json::Value = serde_json::from_str(value).unwrap_or_default();
+        let party = |field: &str| {
+            agreement
+                .get(field)
+                .and_then(|v| v.as_str())
+                .unwrap_or("")
+                .to_string()
+        };
+        (party("hostPeerId"), party("clientPeerId"))
+    };
+    let (host, client) = parties(&current.value);
+    if incoming.publisher != host && incoming.publisher != client {
+        return Err("only a party to the stored agreement may replace it".to_string());
+    }
+    if parties(&incoming.value) != (host, client) {
+        return Err("agreement update changes its parties".to_string());
+    }
+    Ok(())
+}
+
+/// Store an inbound `PutRecord` only if [`accept_inbound`] allows it. Used
+/// with `StoreInserts::FilterBoth`, where Kademlia leaves storing to us.
+pub fn store_inbound<S: RecordStore>(
+    store: &mut S,
+    record: kad::Record,
+    now_ms: u64,
+) -> Result<(), String> {
+    let key = String::from_utf8_lossy(record.key.as_ref()).to_string();
+    let existing = store.get(&record.key).map(|r| r.value.clone());
+    accept_inbound(&key, &record.value, existing.as_deref(), now_ms)?;
+    store
+        .put(record)
+        .map_err(|e| format!("record store rejected {}: {:?}", key, e))
+}
+
+fn validate_value(kind: RecordKind, key: &str, publisher: &str, value: &str) -> Result<(), String> {
+    let suffix = kind.key_suffix(key);
+    match kind {
+        RecordKind::FileMetadata => {
+            let metadata: crate::FileMetadata = serde_json::from_str(value)
+                .map_err(|e| format!("malformed file metadata: {}", e))?;
+            if metadata.hash != suffix {
+                return Err("file metadata hash does not match key".to_string());
+            }
+            if !metadata.verify_publisher() {
+                return Err("file metadata signature is invalid".to_string());
+            }
+        }
+        RecordKind::Seeder => {
+            let (file_hash, peer_id) = split_peer_suffix(suffix)?;
+            require_owner(peer_id, publisher)?;
+            let seeder: crate::SeederInfo = serde_json::from_str(value)
+                .map_err(|e| format!("malformed seeder entry: {}", e))?;
+            if seeder.peer_id != peer_id || !seeder.verify(file_hash) {
+                return Err("seeder entry signature is invalid".to_string());
+            }
+        }
+        RecordKind::Folder => {
+            let manifest: crate::FolderManifest = serde_json::from_str(value)
+                .map_err(|e| format!("malformed folder manifest: {}", e))?;
+            if manifest.hash != suffix || !manifest.verify() {
+                return Err("folder manifest signature is invalid".to_string());
+            }
+        }
+        RecordKind::HostRegistry => validate_host_registry(value)?,
+        RecordKind::HostAd => {
+            require_owner(suffix, publisher)?;
+            let ad: serde_json::Value = serde_json::from_str(value)
+                .map_err(|e| format!("malformed host advertisement: {}", e))?;
+            if ad.get("peerId").and_then(|v| v.as_str()) != Some(suffix) {
+                return Err("host advertisement peerId does not match key".to_string());
+            }
+        }
+        RecordKind::Agreement => {
+            let agreement: serde_json::Value =
+                serde_json::from_str(value).map_err(|e| format!("malformed agreement: {}", e))?;
+            if agreement.get("agreementId").and_then(|v| v.as_str()) != Some(suffix) {
+                return Err("agreement id does not match key".to_string());
+            }
+            let is_party = ["hostPeerId", "clientPeerId"]
+                .iter()
+                .any(|field| agreement.get(field).and_then(|v| v.as_str()) == Some(publisher));
+            if !is_party {
+                return Err("agreement publisher is not a party to it".to_string());
+            }
+        }
+        RecordKind::EncryptionKey => {
+            require_owner(suffix, publisher)?;
+            if hex::decode(value.trim()).map(|k| k.len()) != Ok(32) {
+                return Err("encryption key is not 32 hex-encoded bytes".to_string());
+            }
+        }
+        RecordKind::ReputationIssuer => {
+            let record: crate::reputation::ReputationIssuerKeyRecord = serde_json::from_str(value)
+                .map_err(|e| format!("malformed issuer key record: {}", e))?;
+            crate::reputation::validate_issuer_key_record_for_wallet(&record, suffix)?;
+        }
+        RecordKind::KeywordPosting => {
+            let (term, peer_id) = split_peer_suffix(suffix)?;
+            require_owner(peer_id, publisher)?;
+            let posting: crate::search_index::KeywordPosting = serde_json::from_str(value)
+                .map_err(|e| format!("malformed keyword posting: {}", e))?;
+            if posting.term != term || posting.peer_id != peer_id || !posting.verify() {
+                return Err("keyword posting signature is invalid".to_string());
+            }
+        }
+        RecordKind::ReputationVerdicts => {
+            let (seeder_wallet, peer_id) = split_peer_suffix(suffix)?;
+            require_owner(peer_id, publisher)?;
+            let bundle: crate::reputation::VerdictBundle = serde_json::from_str(value)
+                .map_err(|e| format!("malformed verdict bundle: {}", e))?;
+            bundle.validate_for_key(seeder_wallet, peer_id)?;
+        }
+        RecordKind::Generic => {}
+    }
+    Ok(())
+}
+
+/// Split `<rest>_<peerId>`; peer IDs never contain `_`.
+fn split_peer_suffix(suffix: &str) -> Result<(&str, &str), String> {
+    suffix
+        .rsplit_once('_')
+        .filter(|(rest, peer)| !rest.is_empty() && !peer.is_empty())
+        .ok_or_else(|| "key does not end in a peer ID".to_string())
+}
+
+fn require_owner(key_peer: &str, publisher: &str) -> Result<(), String> {
+    if key_peer != publisher {
+        return Err(format!("only {} may publish this key", key_peer));
+    }
+    Ok(())
+}
+
+/// Bytes each host signs for its own registry entry.
+fn host_registry_entry_payload(peer_id: &str, wallet_address: &str, updated_at: u64) -> Vec<u8> {
+    let mut out = Vec::with_capacity(96);
+    out.extend_from_slice(b"host-registry");
+    out.push(0);
+    for part in [peer_id.as_bytes(), wallet_address.as_bytes()] {
+        out.extend_from_slice(&(part.len() as u32).to_le_bytes());
+        out.extend_from_slice(part);
+    }
+    out.extend_from_slice(&updated_at.to_le_bytes());
+    out
+}
+
+fn registry_entry_fields(entry: &serde_json::Value) -> (&str, &str, u64) {
+    (
+        entry.get("peerId").and_then(|v| v.as_str()).unwrap_or(""),
+        entry
+            .get("walletAddress")
+            .and_then(|v| v.as_str())
+            .unwrap_or(""),
+        entry.get("updatedAt").and_then(|v| v.as_u64()).unwrap_or(0),
+    )
+}
+
+/// Sign this node's own entries in a host registry value. Entries from
+/// other hosts keep the signatures they were published with.
+pub fn sign_host_registry(value: &str, keypair: &Keypair) -> Result<String, String> {
+    let mut entries: Vec<serde_json::Value> = serde_json::from_str(value)
+        .map_err(|e| format!("Malformed chiral_host_registry JSON: {}", e))?;
+    let local_peer = PeerId::from(keypair.public()).to_string();
+    for entry in &mut entries {
+        let (peer_id, wallet_address, updated_at) = registry_entry_fields(entry);
+        if peer_id != local_peer {
+            continue;
+        }
+        let payload = host_registry_entry_payload(peer_id, wallet_address, updated_at);
+        let signature = keypair
+            .sign(&payload)
+            .map_err(|e| format!("failed to sign host registry entry: {}", e))?;
+        entry["signature"] = serde_json::Value::String(hex::encode(signature));
+    }
+    serde_json::to_string(&entries).map_err(|e| format!("Failed to serialize registry: {}", e))
+}
+
+/// Every entry must carry a valid signature from the host it describes, so
+/// a registry publisher can't list hosts that never advertised.
+fn validate_host_registry(value: &str) -> Result<(), String> {
+    let entries: Vec<serde_json::Value> =
+        serde_json::from_str(value).map_err(|e| format!("malformed host registry: {}", e))?;
+    for entry in &entries {
+        let (peer_id, wallet_address, updated_at) = registry_entry_fields(entry);
+        if !host_registry_entry_valid(entry, peer_id, wallet_address, updated_at) {
+            return Err(format!(
+                "host registry entry for '{}' is not signed by it",
+                peer_id
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn host_registry_entry_valid(
+    entry: &serde_json::Value,
+    peer_id: &str,
+    wallet_address: &str,
+    updated_at: u64,
+) -> bool {
+    let Some(public_key) = PeerId::from_str(peer_id)
+        .ok()
+        .and_then(|p| public_key_from_peer_id(&p))
+    else {
+        return false;
+    };
+    let Some(signature) = entry
+        .get("signature")
+        .and_then(|v| v.as_str())
+        .and_then(|s| hex::decode(s).ok())
+    else {
+        return false;
+    };
+    let payload = host_registry_entry_payload(peer_id, wallet_address, updated_at);
+    public_key.verify(&payload, &signature)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_PRIVATE_KEY: &str =
+        "0x0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef";
+    const OTHER_PRIVATE_KEY: &str =
+        "0x1111111111111111111111111111111111111111111111111111111111111111";
+    const NOW_MS: u64 = 1_800_000_000_000;
+
+    fn peer(keypair: &Keypair) -> String {
+        PeerId::from(keypair.public()).to_string()
+    }
+
+    fn sealed(key: &str, value: &str, keypair: &Keypair, now_ms: u64) -> Vec<u8> {
+        SignedRecord::seal(key, value.to_string(), keypair, now_ms)
+            .unwrap()
+            .to_bytes()
+    }
+
+    fn signed_file_metadata(hash: &str) -> String {
+        signed_file_metadata_by(hash, TEST_PRIVATE_KEY)
+    }
+
+    fn signed_file_metadata_by(hash: &str, private_key: &str) -> String {
+        let mut metadata = crate::FileMetadata {
+            hash: hash.to_string(),
+            file_name: "report.pdf".to_string(),
+            file_size: 4096,
+            protocol: "WebRTC".to_string(),
+            created_at: 1_700_000_000,
+            wallet_address: crate::wallet::address_from_private_key(private_key).unwrap(),
+            publisher_signature: String::new(),
+        };
+        metadata.sign(private_key).unwrap();
+        serde_json::to_string(&metadata).unwrap()
+    }
+
+    #[test]
+    fn kind_follows_key_prefix() {
+        assert_eq!(
+            RecordKind::for_key("chiral_host_registry"),
+            RecordKind::HostRegistry
+        );
+        assert_eq!(
+            RecordKind::for_key("chiral_host_12D3Koo"),
+            RecordKind::HostAd
+        );
+        assert_eq!(
+            RecordKind::for_key("chiral_seeder_abc_12D3Koo"),
+            RecordKind::Seeder
+        );
+        assert_eq!(
+            RecordKind::for_key("chiral_reputation_issuer_v1_0xabc"),
+            RecordKind::ReputationIssuer
+        );
+        assert_eq!(
+            RecordKind::for_key("chiral_kwpost_report_12D3Koo"),
+            RecordKind::KeywordPosting
+        );
+        assert_eq!(
+            RecordKind::for_key("chiral_verdictpost_0xabc_12D3Koo"),
+            RecordKind::ReputationVerdicts
+        );
+        assert_eq!(RecordKind::for_key("something_else"), RecordKind::Generic);
+    }
+
+    #[test]
+    fn seal_and_open_round_trip() {
+        let keypair = Keypair::generate_ed25519();
+        let bytes = sealed("notes", "hello", &keypair, NOW_MS);
+        let record = SignedRecord::open("notes", &bytes, NOW_MS).unwrap();
+        assert_eq!(record.value, "hello");
+        assert_eq!(record.publisher, peer(&keypair));
+        assert_eq!(record.kind, RecordKind::Generic);
+        assert_eq!(record.expires_at, NOW_MS / 1000 + RECORD_LIFETIME_SECS);
+    }
+
+    #[test]
+    fn open_rejects_tampering_replay_and_legacy_values() {
+        let keypair = Keypair::generate_ed25519();
+        let mut record = SignedRecord::seal("notes", "hello".into(), &keypair, NOW_MS).unwrap();
+        assert!(SignedRecord::open("other_key", &record.to_bytes(), NOW_MS).is_err());
+
+        record.value = "forged".to_string();
+        assert!(SignedRecord::open("notes", &record.to_bytes(), NOW_MS)
+            .unwrap_err()
+            .contains("signature"));
+
+        assert!(SignedRecord::open("notes", b"hello", NOW_MS).is_err());
+        assert!(SignedRecord::open("notes", b"{\"foo\":1}", NOW_MS).is_err());
+    }
+
+    #[test]
+    fn open_enforces_lifetime_and_clock_skew() {
+        let keypair = Keypair::generate_ed25519();
+        let bytes = sealed("notes", "hello", &keypair, NOW_MS);
+        let after_expiry = NOW_MS + (RECORD_LIFETIME_SECS + 1) * 1000;
+        assert!(SignedRecord::open("notes", &bytes, after_expiry)
+            .unwrap_err()
+            .contains("expired"));
+
+        let from_future = sealed("notes", "hello", &keypair, NOW_MS + 3_600_000);
+        assert!(SignedRecord::open("notes", &from_future, NOW_MS)
+            .unwrap_err()
+            .contains("ahead"));
+
+        let record = SignedRecord::open("notes", &bytes, NOW_MS).unwrap();
+        assert!(!record.needs_refresh(NOW_MS));
+        assert!(record.needs_refresh(NOW_MS + (RECORD_REFRESH_MARGIN_SECS + 1) * 1000));
+    }
+
+    #[test]
+    fn sequence_numbers_step_past_same_millisecond_puts() {
+        assert_eq!(bump_seq(0, NOW_MS), NOW_MS);
+        assert_eq!(bump_seq(NOW_MS, NOW_MS), NOW_MS + 1);
+        assert_eq!(bump_seq(NOW_MS + 5, NOW_MS), NOW_MS + 6);
+        assert_eq!(bump_seq(NOW_MS - 1, NOW_MS), NOW_MS);
+        // A counter left far ahead by a clock jump doesn't stick.
+        assert_eq!(bump_seq(NOW_MS + 3_600_000, NOW_MS), NOW_MS);
+    }
+
+    #[test]
+    fn peer_owned_keys_only_accept_their_peer() {
+        let owner = Keypair::generate_ed25519();
+        let other = Keypair::generate_ed25519();
+        let key = format!("chiral_pubkey_{}", peer(&owner));
+        let value = "ab".repeat(32);
+
+        assert!(SignedRecord::open(&key, &sealed(&key, &value, &owner, NOW_MS), NOW_MS).is_ok());
+        assert!(
+            SignedRecord::open(&key, &sealed(&key, &value, &other, NOW_MS), NOW_MS)
+                .unwrap_err()
+                .contains("only")
+        );
+        assert!(SignedRecord::open(&key, &sealed(&key, "abcd", &owner, NOW_MS), NOW_MS).is_err());
+
+        let ad_key = format!("chiral_host_{}", peer(&owner));
+        let ad = serde_json::json!({ "peerId": peer(&owner), "walletAddress": "0xabc" });
+        assert!(SignedRecord::open(
+            &ad_key,
+            &sealed(&ad_key, &ad.to_string(), &owner, NOW_MS),
+            NOW_MS
+        )
+        .is_ok());
+        assert!(SignedRecord::open(
+            &ad_key,
+            &sealed(&ad_key, &ad.to_string(), &other, NOW_MS),
+            NOW_MS
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn verdict_bundles_belong_to_their_peer_and_seeder() {
+        let owner = Keypair::generate_ed25519();
+        let other = Keypair::generate_ed25519();
+        let seeder = "0x1111111111111111111111111111111111111111";
+        let key = format!("chiral_verdictpost_{}_{}", seeder, peer(&owner));
+        let bundle = |seeder_wallet: &str| {
+            serde_json::json!({
+                "seederWallet": seeder_wallet,
+                "peerId": peer(&owner),
+                "verdicts": [],
+                "updatedAt": 1_700_000_000,
+            })
+            .to_string()
+        };
+
+        let value = bundle(seeder);
+        assert!(SignedRecord::open(&key, &sealed(&key, &value, &owner, NOW_MS), NOW_MS).is_ok());
+        assert!(
+            SignedRecord::open(&key, &sealed(&key, &value, &other, NOW_MS), NOW_MS)
+                .unwrap_err()
+                .contains("only")
+        );
+        let wrong_seeder = bundle("0x2222222222222222222222222222222222222222");
+        assert!(
+            SignedRecord::open(&key, &sealed(&key, &wrong_seeder, &owner, NOW_MS), NOW_MS)
+                .unwrap_err()
+                .contains("does not match")
+        );
+    }
+
+    #[test]
+    fn file_metadata_must_be_wallet_signed_for_its_hash() {
+        let keypair = Keypair::generate_ed25519();
+        let value = signed_file_metadata("abc123");
+        assert!(SignedRecord::open(
+            "chiral_file_abc123",
+            &sealed("chiral_file_abc123", &value, &keypair, NOW_MS),
+            NOW_MS
+        )
+        .is_ok());
+        assert!(SignedRecord::open(
+            "chiral_file_def456",
+            &sealed("chiral_file_def456", &value, &keypair, NOW_MS),
+            NOW_MS
+        )
+        .unwrap_err()
+        .contains("hash"));
+
+        let mut unsigned: serde_json::Value = serde_json::from_str(&value).unwrap();
+        unsigned["fileName"] = serde_json::json!("malware.exe");
+        assert!(SignedRecord::open(
+            "chiral_file_abc123",
+            &sealed(
+                "chiral_file_abc123",
+                &unsigned.to_string(),
+                &keypair,
+                NOW_MS
+            ),
+            NOW_MS
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn signed_metadata_stays_with_its_wallet() {
+        let node = Keypair::generate_ed25519();
+        let other_node = Keypair::generate_ed25519();
+        let key = "chiral_file_abc123";
+        let stored = sealed(key, &signed_file_metadata("abc123"), &node, NOW_MS);
+        let later = NOW_MS + 1000;
+
+        // A foreign wallet re-signing the hash is refused, from any node.
+        let foreign = signed_file_metadata_by("abc123", OTHER_PRIVATE_KEY);
+        for publisher in [&other_node, &node] {
+            let incoming = sealed(key, &foreign, publisher, later);
+            assert!(accept_inbound(key, &incoming, Some(&stored), later)
+                .unwrap_err()
+                .contains("owned by wallet"));
+        }
+        // The owning wallet may republish, even from another node.
+        let incoming = sealed(key, &signed_file_metadata("abc123"), &other_node, later);
+        assert!(accept_inbound(key, &incoming, Some(&stored), later).is_ok());
+
+        let manifest = |private_key: &str| {
+            let mut manifest = crate::FolderManifest {
+                hash: "folder1".to_string(),
+                name: "Lectures".to_string(),
+                owner_wallet: crate::wallet::address_from_private_key(private_key).unwrap(),
+                created_at: 1_700_000_000,
+                files: Vec::new(),
+                price_wei: String::new(),
+                wallet_address: String::new(),
+                publisher_signature: String::new(),
+            };
+            manifest.sign(private_key).unwrap();
+            serde_json::to_string(&manifest).unwrap()
+        };
+        let key = "chiral_folder_folder1";
+        let stored = sealed(key, &manifest(TEST_PRIVATE_KEY), &node, NOW_MS);
+        let incoming = sealed(key, &manifest(OTHER_PRIVATE_KEY), &other_node, later);
+        assert!(accept_inbound(key, &incoming, Some(&stored), later)
+            .unwrap_err()
+            .contains("owned by wallet"));
+    }
+
+    #[test]
+    fn agreements_are_published_by_a_party() {
+        let host = Keypair::generate_ed25519();
+        let stranger = Keypair::generate_ed25519();
+        let agreement = serde_json::json!({
+            "agreementId": "a1",
+            "hostPeerId": peer(&host),
+            "clientPeerId": "12D3KooWClient",
+        })
+        .to_string();
+        let key = "chiral_agreement_a1";
+        assert!(SignedRecord::open(key, &sealed(key, &agreement, &host, NOW_MS), NOW_MS).is_ok());
+        assert!(
+            SignedRecord::open(key, &sealed(key, &agreement, &stranger, NOW_MS), NOW_MS)
+                .unwrap_err()
+                .contains("party")
+        );
+        let other_key = "chiral_agreement_a2";
+        assert!(SignedRecord::open(
+            other_key,
+            &sealed(other_key, &agreement, &host, NOW_MS),
+            NOW_MS
+        )
+        .unwrap_err()
+        .contains("id does not match"));
+    }
+
+    #[test]
+    fn agreements_cannot_be_taken_over_by_a_third_party() {
+        let host = Keypair::generate_ed25519();
+        let client = Keypair::generate_ed25519();
+        let stranger = Keypair::generate_ed25519();
+        let agreement = |host_peer: &str, client_peer: &str, status: &str| {
+            serde_json::json!({
+                "agreementId": "a1",
+                "hostPeerId": host_peer,
+                "clientPeerId": client_peer,
+                "status": status,
+            })
+            .to_string()
+        };
+        let key = "chiral_agreement_a1";
+        let stored = sealed(
+            key,
+            &agreement(&peer(&host), &peer(&client), "proposed"),
+            &host,
+            NOW_MS,
+        );
+        let later = NOW_MS + 1000;
+        let accept = |value: &str, publisher: &Keypair| {
+            let incoming = sealed(key, value, publisher, later);
+            accept_inbound(key, &incoming, Some(&stored), later)
+        };
+
+        // A stranger naming itself as host, at a later seq, is refused.
+        assert!(accept(
+            &agreement(&peer(&stranger), &peer(&client), "active"),
+            &stranger
+        )
+        .unwrap_err()
+        .contains("only a party"));
+        // A real party can't swap the other one out either.
+        assert!(accept(
+            &agreement(&peer(&stranger), &peer(&client), "active"),
+            &client
+        )
+        .unwrap_err()
+        .contains("changes its parties"));
+        // Either party may update the status.
+        let accepted = agreement(&peer(&host), &peer(&client), "active");
+        assert!(accept(&accepted, &client).is_ok());
+        assert!(accept(&accepted, &host).is_ok());
+    }
+
+    #[test]
+    fn host_registry_entries_must_be_signed_by_their_host() {
+        let host_a = Keypair::generate_ed25519();
+        let host_b = Keypair::generate_ed25519();
+        let registry = serde_json::json!([
+            { "peerId": peer(&host_a), "walletAddress": "0xa", "updatedAt": 10 },
+        ])
+        .to_string();
+        let signed_a = sign_host_registry(&registry, &host_a).unwrap();
+
+        // Host B re-publishes A's signed entry alongside its own.
+        let mut entries: Vec<serde_json::Value> = serde_json::from_str(&signed_a).unwrap();
+        entries.push(
+            serde_json::json!({ "peerId": peer(&host_b), "walletAddress": "0xb", "updatedAt": 20 }),
+        );
+        let signed_both =
+            sign_host_registry(&serde_json::to_string(&entries).unwrap(), &host_b).unwrap();
+        let bytes = sealed(HOST_REGISTRY_KEY, &signed_both, &host_b, NOW_MS);
+        assert!(SignedRecord::open(HOST_REGISTRY_KEY, &bytes, NOW_MS).is_ok());
+
+        // Host B can't rewrite A's wallet or invent an unsigned host.
+        let mut forged: Vec<serde_json::Value> = serde_json::from_str(&signed_both).unwrap();
+        forged[0]["walletAddress"] = serde_json::json!("0xattacker");
+        let bytes = sealed(
+            HOST_REGISTRY_KEY,
+            &serde_json::to_string(&forged).unwrap(),
+            &host_b,
+            NOW_MS,
+        );
+        assert!(SignedRecord::open(HOST_REGISTRY_KEY, &bytes, NOW_MS).is_err());
+
+        let invented = serde_json::json!([{ "peerId": peer(&host_a), "walletAddress": "0xa", "updatedAt": 10 }]);
+        let bytes = sealed(HOST_REGISTRY_KEY, &invented.to_string(), &host_b, NOW_MS);
+        assert!(SignedRecord::open(HOST_REGISTRY_KEY, &bytes, NOW_MS).is_err());
+    }
+
+    fn signed_registry(hosts: &[(&Keypair, u64)]) -> String {
+        let mut entries = Vec::new();
+        for (host, updated_at) in hosts {
+            let entry = serde_json::json!([
+                { "peerId": peer(host), "walletAddress": "0xa", "updatedAt": updated_at },
+            ])
+            .to_string();
+            let signed: Vec<serde_json::Value> =
+                serde_json::from_str(&sign_host_registry(&entry, host).unwrap()).unwrap();
+            entries.extend(signed);
+        }
+        serde_json::to_string(&entries).unwrap()
+    }
+
+    #[test]
+    fn host_registry_updates_cannot_drop_other_hosts() {
+        let host_a = Keypair::generate_ed25519();
+        let host_b = Keypair::generate_ed25519();
+        let attacker = Keypair::generate_ed25519();
+        let stored = sealed(
+            HOST_REGISTRY_KEY,
+            &signed_registry(&[(&host_a, 10), (&host_b, 20)]),
+            &host_b,
+            NOW_MS,
+        );
+        let later = NOW_MS + 1000;
+        let accept = |value: &str, publisher: &Keypair| {
+            let incoming = sealed(HOST_REGISTRY_KEY, value, publisher, later);
+            accept_inbound(HOST_REGISTRY_KEY, &incoming, Some(&stored), later)
+        };
+
+        // An empty or partial registry at a higher seq is refused.
+        assert!(accept("[]", &attacker)
+            .unwrap_err()
+            .contains("drops the entry"));
+        assert!(accept(&signed_registry(&[(&host_b, 20)]), &attacker).is_err());
+        assert!(accept(&signed_registry(&[(&host_a, 10)]), &host_a).is_err());
+        // Nor may anyone roll a host back to an older entry.
+        assert!(accept(&signed_registry(&[(&host_a, 5), (&host_b, 20)]), &attacker).is_err());
+
+        // A superset, or a host refreshing its own entry, is fine.
+        assert!(accept(
+            &signed_registry(&[(&host_a, 10), (&host_b, 20), (&attacker, 30)]),
+            &attacker
+        )
+        .is_ok());
+        assert!(accept(&signed_registry(&[(&host_a, 40), (&host_b, 20)]), &host_a).is_ok());
+        // A host may leave the registry.
+        assert!(accept(&signed_registry(&[(&host_b, 20)]), &host_a).is_ok());
+
+        // Once the stored copy expires, so do its entries.
+        let expired = NOW_MS + (RECORD_LIFETIME_SECS + 1) * 1000;
+        let empty = sealed(HOST_REGISTRY_KEY, "[]", &attacker, expired);
+        assert!(accept_inbound(HOST_REGISTRY_KEY, &empty, Some(&stored), expired).is_ok());
+    }
+
+    #[test]
+    fn accept_inbound_drops_stale_and_foreign_overwrites() {
+        let owner = Keypair::generate_ed25519();
+        let other = Keypair::generate_ed25519();
+        let first = sealed("notes", "v1", &owner, NOW_MS);
+        let second = sealed("notes", "v2", &owner, NOW_MS + 1000);
+
+        assert!(accept_inbound("notes", &first, None, NOW_MS + 1000).is_ok());
+        assert!(accept_inbound("notes", &second, Some(&first), NOW_MS + 1000).is_ok());
+        assert!(
+            accept_inbound("notes", &first, Some(&second), NOW_MS + 1000)
+                .unwrap_err()
+                .contains("stale")
+        );
+        // Replication of the bytes we already hold is not an update.
+        assert!(accept_inbound("notes", &second, Some(&second), NOW_MS + 1000).is_ok());
+
+        let foreign = sealed("notes", "mine now", &other, NOW_MS + 2000);
+        assert!(
+            accept_inbound("notes", &foreign, Some(&second), NOW_MS + 2000)
+                .unwrap_err()
+                .contains("owned")
+        );
+        // Once the owner's copy expires the key is free again.
+        let later = NOW_MS + (RECORD_LIFETIME_SECS + 1) * 1000;
+        let foreign = sealed("notes", "mine now", &other, later);
+        assert!(accept_inbound("notes", &foreign, Some(&second), later).is_ok());
+        // Legacy unsigned values never block a signed one.
+        assert!(accept_inbound("notes", &foreign, Some(b"legacy"), later).is_ok());
+    }
+
+    #[test]
+    fn store_inbound_keeps_invalid_records_out_of_the_store() {
+        let keypair = Keypair::generate_ed25519();
+        let local = PeerId::from(keypair.public());
+        let mut store = kad::store::MemoryStore::new(local);
+        let key = kad::RecordKey::new(&"notes");
+
+        let forged = kad::Record::new(key.clone(), b"plain value".to_vec());
+        assert!(store_inbound(&mut store, forged, NOW_MS).is_err());
+        assert!(store.get(&key).is_none());
+
+        let valid = kad::Record::new(key.clone(), sealed("notes", "hello", &keypair, NOW_MS));
+        store_inbound(&mut store, valid.clone(), NOW_MS).unwrap();
+        assert_eq!(store.get(&key).unwrap().value, valid.value);
+    }
+
+    #[test]
+    fn ed25519_peer_ids_expose_their_public_key() {
+        let keypair = Keypair::generate_ed25519();
+        let peer_id = PeerId::from(keypair.public());
+        assert_eq!(public_key_from_peer_id(&peer_id), Some(keypair.public()));
+    }
+}
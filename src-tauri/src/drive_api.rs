@@ -15,6 +15,7 @@ use crate::drive_storage::{
     self, collect_descendants, generate_id, generate_share_token, now_secs, DriveItem,
     DriveManifest, ShareLink,
 };
+use crate::http_range;
 
 // ---------------------------------------------------------------------------
 // State
@@ -316,8 +317,12 @@ async fn verify_share_access(share: &ShareLink, access: Option<&str>) -> Result<
             )
             .await
             {
-                Ok(()) => Ok(()),
+                Ok(()) => {
+                    crate::metrics::global().payment_verification("drive_share", true);
+                    Ok(())
+                }
                 Err(e) => {
+                    crate::metrics::global().payment_verification("drive_share", false);
                     release_share_tx(tx_hash).await;
                     Err(e)
                 }
@@ -579,6 +584,8 @@ struct UpdateItemRequest {
     parent_id: Option<String>,
     starred: Option<bool>,
     price_chi: Option<String>,
+    description: Option<String>,
+    tags: Option<Vec<String>>,
 }
 
 #[derive(Deserialize)]
@@ -674,6 +681,8 @@ async fn create_folder(
         storage_path: None,
         owner,
         is_public: true,
+        description: None,
+        tags: Vec::new(),
         merkle_root: None,
         protocol: None,
         price_chi: None,
@@ -791,6 +800,8 @@ async fn upload_file(
         storage_path: Some(storage_name),
         owner,
         is_public: true,
+        description: None,
+        tags: Vec::new(),
         merkle_root: None,
         protocol: None,
         price_chi: None,
@@ -852,6 +863,12 @@ async fn update_item(
     if let Some(p) = req.price_chi {
         item.price_chi = if p.is_empty() { None } else { Some(p) };
     }
+    if let Some(description) = req.description {
+        item.description = crate::search_index::normalize_description(Some(&description));
+    }
+    if let Some(tags) = req.tags {
+        item.tags = crate::search_index::normalize_tags(&tags);
+    }
     item.modified_at = now;
 
     let updated = item.clone();
@@ -1036,18 +1053,6 @@ async fn download_file(
     let Some(files_dir) = drive_storage::drive_files_dir() else {
         return (StatusCode::INTERNAL_SERVER_ERROR, "Storage error").into_response();
     };
-    let path = files_dir.join(sp);
-    let metadata = match tokio::fs::metadata(&path).await {
-        Ok(m) => m,
-        Err(_) => return (StatusCode::NOT_FOUND, "File not found on disk").into_response(),
-    };
-    if metadata.len() > 500 * 1024 * 1024 {
-        return (StatusCode::PAYLOAD_TOO_LARGE, "File too large").into_response();
-    }
-    let data = match tokio::fs::read(&path).await {
-        Ok(d) => d,
-        Err(_) => return (StatusCode::NOT_FOUND, "File not found on disk").into_response(),
-    };
     let content_type = item
         .mime_type
         .clone()
@@ -1058,17 +1063,18 @@ async fn download_file(
     } else {
         format!("attachment; filename=\"{}\"", item.name)
     };
+    let file = http_range::FileResponse {
+        path: files_dir.join(sp),
+        content_type,
+        content_hash: item.merkle_root.clone(),
+        cache_control: "private, no-cache",
+        content_disposition: Some(disposition),
+    };
+    drop(m);
 
-    (
-        StatusCode::OK,
-        [
-            ("Content-Type", content_type),
-            ("Content-Length", data.len().to_string()),
-            ("Content-Disposition", disposition),
-        ],
-        data,
-    )
-        .into_response()
+    http_range::serve_file(&headers, file)
+        .await
+        .unwrap_or_else(|_| (StatusCode::NOT_FOUND, "File not found on disk").into_response())
 }
 
 // ---------------------------------------------------------------------------
@@ -1271,6 +1277,7 @@ async fn public_browse(
 /// GET /drive/:token/download/:item_id/:filename  — download or preview shared file data
 async fn public_download(
     Extension(state): Extension<Arc<DriveState>>,
+    headers: HeaderMap,
     Path((token, subpath)): Path<(String, String)>,
     Query(q): Query<PublicBrowseQuery>,
 ) -> Response {
@@ -1323,7 +1330,10 @@ async fn public_download(
         .unwrap_or_else(|| "application/octet-stream".to_string());
 
     let as_attachment = q.view.unwrap_or(0) == 0 && q.dl.unwrap_or(1) != 0;
-    if as_attachment {
+    // A resumed or revalidated download is the same download again.
+    let repeat_request = http_range::is_resumed_request(&headers)
+        || headers.contains_key(axum::http::header::IF_NONE_MATCH);
+    if as_attachment && !repeat_request {
         let mut m = state.manifest.write().await;
         if let Some(link) = m.shares.iter_mut().find(|s| s.id == token) {
             link.download_count += 1;
@@ -1335,40 +1345,35 @@ async fn public_download(
     let Some(files_dir) = drive_storage::drive_files_dir() else {
         return (StatusCode::INTERNAL_SERVER_ERROR, "Storage error").into_response();
     };
-    let file_path = files_dir.join(&sp);
-
-    let data = match tokio::fs::read(&file_path).await {
-        Ok(d) => d,
-        Err(_) => return (StatusCode::NOT_FOUND, "File not found on disk").into_response(),
-    };
-
     let disposition = if as_attachment {
         format!("attachment; filename=\"{}\"", file_name)
     } else {
         format!("inline; filename=\"{}\"", file_name)
     };
+    let file = http_range::FileResponse {
+        path: files_dir.join(&sp),
+        content_type,
+        content_hash: target_item.merkle_root.clone(),
+        // Revalidate every time: access to a share can be revoked.
+        cache_control: "private, no-cache",
+        content_disposition: Some(disposition),
+    };
 
-    (
-        StatusCode::OK,
-        [
-            ("Content-Type", content_type),
-            ("Content-Length", data.len().to_string()),
-            ("Content-Disposition", disposition),
-        ],
-        data,
-    )
-        .into_response()
+    http_range::serve_file(&headers, file)
+        .await
+        .unwrap_or_else(|_| (StatusCode::NOT_FOUND, "File not found on disk").into_response())
 }
 
 /// GET /drive/:token/*path  — browse subfolder of shared folder
 async fn public_browse_path(
     Extension(state): Extension<Arc<DriveState>>,
+    headers: HeaderMap,
     Path((token, subpath)): Path<(String, String)>,
     Query(q): Query<PublicBrowseQuery>,
 ) -> Response {
     // Handle "download" and "download/..." as special file-data routes
     if subpath == "download" || subpath.starts_with("download/") {
-        return public_download(Extension(state), Path((token, subpath)), Query(q)).await;
+        return public_download(Extension(state), headers, Path((token, subpath)), Query(q)).await;
     }
 
     let (share, root_item, item, children) = {
@@ -28,6 +28,12 @@ pub struct DriveItem {
     /// When false, all share links for this item are blocked.
     #[serde(default = "default_true")]
     pub is_public: bool,
+    /// Free-text description, indexed for keyword search when published.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// User tags, indexed for keyword search as `tag:<tag>`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
 
     // ── Seeding metadata (optional, only set for files published to DHT) ──
     /// SHA-256 Merkle root from DHT publishing.
@@ -345,6 +351,8 @@ mod tests {
             storage_path: Some("files/test".into()),
             owner: "test-owner".into(),
             is_public: true,
+            description: None,
+            tags: Vec::new(),
             merkle_root: None,
             protocol: None,
             price_chi: None,
@@ -550,6 +558,8 @@ mod tests {
                 storage_path: None,
                 owner: "test-owner".into(),
                 is_public: true,
+                description: None,
+                tags: Vec::new(),
                 merkle_root: None,
                 protocol: None,
                 price_chi: None,
@@ -570,6 +580,8 @@ mod tests {
                 storage_path: None,
                 owner: "test-owner".into(),
                 is_public: true,
+                description: None,
+                tags: Vec::new(),
                 merkle_root: None,
                 protocol: None,
                 price_chi: None,
@@ -590,6 +602,8 @@ mod tests {
                 storage_path: None,
                 owner: "test-owner".into(),
                 is_public: true,
+                description: None,
+                tags: Vec::new(),
                 merkle_root: None,
                 protocol: None,
                 price_chi: None,
@@ -610,6 +624,8 @@ mod tests {
                 storage_path: None,
                 owner: "test-owner".into(),
                 is_public: true,
+                description: None,
+                tags: Vec::new(),
                 merkle_root: None,
                 protocol: None,
                 price_chi: None,
@@ -6,18 +6,52 @@
 //! - HKDF-SHA256 for key derivation
 //!
 //! This implements the ECIES (Elliptic Curve Integrated Encryption Scheme) pattern.
+//!
+//! Two formats are supported:
+//! - `EncryptedFileBundle`: the whole buffer under one nonce, hex-encoded.
+//!   Kept for small payloads and older peers.
+//! - The streaming format: the file is split into segments that are sealed
+//!   one at a time (the STREAM construction), so files of any size can be
+//!   encrypted from a reader into a writer in constant memory. It is stored
+//!   as raw binary.
+//! - Envelopes: the streaming format under a random content key, with that
+//!   key wrapped once per recipient. Recipients can be added or removed by
+//!   rewriting the key slots alone; the payload is never re-encrypted.
 
 use aes_gcm::{
-    aead::{Aead, AeadCore, KeyInit, OsRng},
+    aead::{Aead, AeadCore, KeyInit, OsRng, Payload},
     Aes256Gcm, Key, Nonce,
 };
 use hkdf::Hkdf;
 use serde::{Deserialize, Serialize};
 use sha2::Sha256;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
 use x25519_dalek::{EphemeralSecret, PublicKey, StaticSecret};
 
 /// Domain separator for key derivation
 const HKDF_INFO: &[u8] = b"chiral-network-v2-e2ee";
+/// Domain separator for the streaming format's key
+const STREAM_HKDF_INFO: &[u8] = b"chiral-network-v2-e2ee-stream";
+/// Leading bytes of every streamed ciphertext (format version 1)
+pub const STREAM_MAGIC: &[u8; 8] = b"CHRLSTR1";
+/// Plaintext bytes per segment
+pub const STREAM_SEGMENT_SIZE: usize = 64 * 1024;
+/// AES-GCM authentication tag appended to every segment
+const STREAM_TAG_SIZE: usize = 16;
+/// Random per-file part of each segment nonce; the remaining 5 bytes are a
+/// 32-bit segment counter and the final-segment flag.
+const STREAM_NONCE_PREFIX_SIZE: usize = 7;
+/// magic || ephemeral public key || segment size (u32 LE) || nonce prefix
+const STREAM_HEADER_SIZE: usize = 8 + 32 + 4 + STREAM_NONCE_PREFIX_SIZE;
+/// Leading bytes of a multi-recipient envelope (format version 1)
+pub const ENVELOPE_MAGIC: &[u8; 8] = b"CHRLENV1";
+/// Domain separator for the per-recipient key-wrapping keys
+const ENVELOPE_HKDF_INFO: &[u8] = b"chiral-network-v2-e2ee-envelope";
+/// Upper bound on key slots, so a corrupt count can't make us allocate much
+pub const MAX_ENVELOPE_RECIPIENTS: usize = 256;
+/// A 32-byte content key sealed with AES-GCM
+const WRAPPED_KEY_SIZE: usize = 32 + STREAM_TAG_SIZE;
 
 /// Encrypted file bundle containing all data needed for decryption
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -182,6 +216,660 @@ pub fn encrypt_for_recipient_hex(
     encrypt_for_recipient(plaintext, &pk_bytes)
 }
 
+fn derive_stream_key(
+    shared_secret: &[u8; 32],
+    ephemeral_public: &PublicKey,
+) -> Result<Aes256Gcm, String> {
+    let hk = Hkdf::<Sha256>::new(Some(ephemeral_public.as_bytes()), shared_secret);
+    let mut encryption_key = [0u8; 32];
+    hk.expand(STREAM_HKDF_INFO, &mut encryption_key)
+        .map_err(|e| format!("HKDF expansion failed: {}", e))?;
+    Ok(Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(
+        &encryption_key,
+    )))
+}
+
+/// Nonce for one segment: prefix || counter (u32 BE) || last flag.
+/// The flag makes dropping trailing segments detectable, the counter makes
+/// reordering detectable.
+fn stream_nonce(prefix: &[u8; STREAM_NONCE_PREFIX_SIZE], counter: u32, last: bool) -> [u8; 12] {
+    let mut nonce = [0u8; 12];
+    nonce[..STREAM_NONCE_PREFIX_SIZE].copy_from_slice(prefix);
+    nonce[STREAM_NONCE_PREFIX_SIZE..11].copy_from_slice(&counter.to_be_bytes());
+    nonce[11] = last as u8;
+    nonce
+}
+
+/// Read until `buf` is full or the reader is exhausted.
+fn read_full<R: Read>(reader: &mut R, buf: &mut [u8]) -> Result<usize, String> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(format!("Read failed: {}", e)),
+        }
+    }
+    Ok(filled)
+}
+
+/// Seal `reader` into `writer` as STREAM segments of `segment_size`
+/// plaintext bytes, authenticating `aad` with each. The last segment is
+/// found with one segment of lookahead, so an empty input still produces a
+/// single (empty) final segment.
+fn seal_segments<R: Read, W: Write>(
+    reader: &mut R,
+    writer: &mut W,
+    cipher: &Aes256Gcm,
+    prefix: &[u8; STREAM_NONCE_PREFIX_SIZE],
+    segment_size: usize,
+    aad: &[u8],
+) -> Result<u64, String> {
+    let mut written = 0u64;
+    let mut current = vec![0u8; segment_size];
+    let mut next = vec![0u8; segment_size];
+    let mut current_len = read_full(reader, &mut current)?;
+    let mut counter: u32 = 0;
+    loop {
+        let next_len = if current_len == segment_size {
+            read_full(reader, &mut next)?
+        } else {
+            0
+        };
+        let last = next_len == 0;
+        let nonce = stream_nonce(prefix, counter, last);
+        let sealed = cipher
+            .encrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: &current[..current_len],
+                    aad,
+                },
+            )
+            .map_err(|e| format!("Encryption failed: {}", e))?;
+        writer
+            .write_all(&sealed)
+            .map_err(|e| format!("Write failed: {}", e))?;
+        written += sealed.len() as u64;
+        if last {
+            break;
+        }
+        counter = counter
+            .checked_add(1)
+            .ok_or_else(|| "File too large for the streaming format".to_string())?;
+        std::mem::swap(&mut current, &mut next);
+        current_len = next_len;
+    }
+    writer.flush().map_err(|e| format!("Write failed: {}", e))?;
+    Ok(written)
+}
+
+/// Inverse of `seal_segments`. Segments are written as soon as they
+/// authenticate; returns the number of plaintext bytes.
+fn open_segments<R: Read, W: Write>(
+    reader: &mut R,
+    writer: &mut W,
+    cipher: &Aes256Gcm,
+    prefix: &[u8; STREAM_NONCE_PREFIX_SIZE],
+    segment_size: usize,
+    aad: &[u8],
+) -> Result<u64, String> {
+    let sealed_size = segment_size + STREAM_TAG_SIZE;
+    let mut current = vec![0u8; sealed_size];
+    let mut next = vec![0u8; sealed_size];
+    let mut current_len = read_full(reader, &mut current)?;
+    let mut counter: u32 = 0;
+    let mut plaintext_len = 0u64;
+    loop {
+        if current_len < STREAM_TAG_SIZE {
+            return Err("Encrypted file is truncated".to_string());
+        }
+        let next_len = if current_len == sealed_size {
+            read_full(reader, &mut next)?
+        } else {
+            0
+        };
+        let last = next_len == 0;
+        let nonce = stream_nonce(prefix, counter, last);
+        let plaintext = cipher
+            .decrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: &current[..current_len],
+                    aad,
+                },
+            )
+            .map_err(|_| {
+                format!(
+                    "Decryption failed at segment {} (wrong key, or the file was modified or truncated)",
+                    counter
+                )
+            })?;
+        writer
+            .write_all(&plaintext)
+            .map_err(|e| format!("Write failed: {}", e))?;
+        plaintext_len += plaintext.len() as u64;
+        if last {
+            break;
+        }
+        counter = counter
+            .checked_add(1)
+            .ok_or_else(|| "Encrypted file has too many segments".to_string())?;
+        std::mem::swap(&mut current, &mut next);
+        current_len = next_len;
+    }
+    writer.flush().map_err(|e| format!("Write failed: {}", e))?;
+    Ok(plaintext_len)
+}
+
+fn parse_segment_size(bytes: &[u8]) -> Result<usize, String> {
+    let segment_size = u32::from_le_bytes(
+        bytes
+            .try_into()
+            .map_err(|_| "Invalid segment size".to_string())?,
+    ) as usize;
+    if segment_size == 0 || segment_size > 16 * STREAM_SEGMENT_SIZE {
+        return Err(format!("Unsupported segment size {}", segment_size));
+    }
+    Ok(segment_size)
+}
+
+fn random_nonce_prefix() -> [u8; STREAM_NONCE_PREFIX_SIZE] {
+    let mut prefix = [0u8; STREAM_NONCE_PREFIX_SIZE];
+    aes_gcm::aead::rand_core::RngCore::fill_bytes(&mut OsRng, &mut prefix);
+    prefix
+}
+
+/// Encrypt everything `reader` yields for a recipient, writing the streaming
+/// format to `writer`. Memory use is bounded by two segments regardless of
+/// input size. Returns the number of bytes written.
+pub fn encrypt_stream_for_recipient<R: Read, W: Write>(
+    mut reader: R,
+    mut writer: W,
+    recipient_public_key: &[u8; 32],
+) -> Result<u64, String> {
+    let recipient_pk = PublicKey::from(*recipient_public_key);
+    let ephemeral_secret = EphemeralSecret::random_from_rng(OsRng);
+    let ephemeral_public = PublicKey::from(&ephemeral_secret);
+    let shared_secret = ephemeral_secret.diffie_hellman(&recipient_pk);
+    let cipher = derive_stream_key(shared_secret.as_bytes(), &ephemeral_public)?;
+    let prefix = random_nonce_prefix();
+
+    // The header is authenticated as associated data on every segment, so a
+    // tampered segment size or key is caught on the first segment.
+    let mut header = Vec::with_capacity(STREAM_HEADER_SIZE);
+    header.extend_from_slice(STREAM_MAGIC);
+    header.extend_from_slice(ephemeral_public.as_bytes());
+    header.extend_from_slice(&(STREAM_SEGMENT_SIZE as u32).to_le_bytes());
+    header.extend_from_slice(&prefix);
+    writer
+        .write_all(&header)
+        .map_err(|e| format!("Write failed: {}", e))?;
+
+    let sealed = seal_segments(
+        &mut reader,
+        &mut writer,
+        &cipher,
+        &prefix,
+        STREAM_SEGMENT_SIZE,
+        &header,
+    )?;
+    Ok(header.len() as u64 + sealed)
+}
+
+/// Decrypt a streamed ciphertext from `reader` into `writer`.
+///
+/// Segments are written as soon as they authenticate, so on error `writer`
+/// may hold a verified prefix of the plaintext and must be discarded; the
+/// file helpers below do that. Returns the number of plaintext bytes.
+pub fn decrypt_stream_with_keypair<R: Read, W: Write>(
+    mut reader: R,
+    mut writer: W,
+    keypair: &EncryptionKeypair,
+) -> Result<u64, String> {
+    let mut header = [0u8; STREAM_HEADER_SIZE];
+    if read_full(&mut reader, &mut header)? != STREAM_HEADER_SIZE
+        || &header[..STREAM_MAGIC.len()] != STREAM_MAGIC
+    {
+        return Err("Not a streamed encrypted file".to_string());
+    }
+    let ephemeral_pk_bytes: [u8; 32] = header[8..40]
+        .try_into()
+        .map_err(|_| "Ephemeral public key must be 32 bytes")?;
+    let segment_size = parse_segment_size(&header[40..44])?;
+    let mut prefix = [0u8; STREAM_NONCE_PREFIX_SIZE];
+    prefix.copy_from_slice(&header[44..]);
+
+    let ephemeral_pk = PublicKey::from(ephemeral_pk_bytes);
+    let shared_secret = keypair.secret.diffie_hellman(&ephemeral_pk);
+    let cipher = derive_stream_key(shared_secret.as_bytes(), &ephemeral_pk)?;
+
+    open_segments(
+        &mut reader,
+        &mut writer,
+        &cipher,
+        &prefix,
+        segment_size,
+        &header,
+    )
+}
+
+/// One recipient's copy of an envelope's content key.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EnvelopeSlot {
+    pub recipient_public_key: [u8; 32],
+    ephemeral_public_key: [u8; 32],
+    wrapped_key: [u8; WRAPPED_KEY_SIZE],
+}
+
+fn slot_cipher(
+    shared_secret: &[u8; 32],
+    ephemeral_public: &[u8; 32],
+    recipient_public: &[u8; 32],
+) -> Result<Aes256Gcm, String> {
+    let hk = Hkdf::<Sha256>::new(Some(ephemeral_public), shared_secret);
+    let mut wrapping_key = [0u8; 32];
+    hk.expand_multi_info(&[ENVELOPE_HKDF_INFO, recipient_public], &mut wrapping_key)
+        .map_err(|e| format!("HKDF expansion failed: {}", e))?;
+    Ok(Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&wrapping_key)))
+}
+
+impl EnvelopeSlot {
+    /// Wrap `content_key` for one recipient. Every slot has its own
+    /// ephemeral key, so each wrapping key is used exactly once and a fixed
+    /// nonce is safe.
+    fn wrap(content_key: &[u8; 32], recipient_public_key: &[u8; 32]) -> Result<Self, String> {
+        let ephemeral_secret = EphemeralSecret::random_from_rng(OsRng);
+        let ephemeral_public = PublicKey::from(&ephemeral_secret);
+        let shared_secret =
+            ephemeral_secret.diffie_hellman(&PublicKey::from(*recipient_public_key));
+        let cipher = slot_cipher(
+            shared_secret.as_bytes(),
+            ephemeral_public.as_bytes(),
+            recipient_public_key,
+        )?;
+        let sealed = cipher
+            .encrypt(
+                Nonce::from_slice(&[0u8; 12]),
+                Payload {
+                    msg: content_key,
+                    aad: ENVELOPE_MAGIC,
+                },
+            )
+            .map_err(|e| format!("Key wrapping failed: {}", e))?;
+        Ok(Self {
+            recipient_public_key: *recipient_public_key,
+            ephemeral_public_key: *ephemeral_public.as_bytes(),
+            wrapped_key: sealed
+                .try_into()
+                .map_err(|_| "Wrapped key has unexpected length".to_string())?,
+        })
+    }
+
+    fn unwrap(&self, keypair: &EncryptionKeypair) -> Result<[u8; 32], String> {
+        let shared_secret = keypair
+            .secret
+            .diffie_hellman(&PublicKey::from(self.ephemeral_public_key));
+        let cipher = slot_cipher(
+            shared_secret.as_bytes(),
+            &self.ephemeral_public_key,
+            &self.recipient_public_key,
+        )?;
+        cipher
+            .decrypt(
+                Nonce::from_slice(&[0u8; 12]),
+                Payload {
+                    msg: &self.wrapped_key,
+                    aad: ENVELOPE_MAGIC,
+                },
+            )
+            .map_err(|_| "Failed to unwrap content key".to_string())?
+            .try_into()
+            .map_err(|_| "Content key has unexpected length".to_string())
+    }
+}
+
+/// Everything before an envelope's payload segments.
+///
+/// Only the payload parameters are authenticated with the segments; the key
+/// slots are deliberately left out so they can be rewritten on their own.
+/// A recipient can therefore not tell from the file who else holds a slot.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EnvelopeHeader {
+    slots: Vec<EnvelopeSlot>,
+    segment_size: u32,
+    nonce_prefix: [u8; STREAM_NONCE_PREFIX_SIZE],
+}
+
+impl EnvelopeHeader {
+    /// Public keys that currently hold a key slot, in slot order.
+    pub fn recipients(&self) -> Vec<[u8; 32]> {
+        self.slots.iter().map(|s| s.recipient_public_key).collect()
+    }
+
+    fn payload_aad(&self) -> Vec<u8> {
+        let mut aad = Vec::with_capacity(8 + 4 + STREAM_NONCE_PREFIX_SIZE);
+        aad.extend_from_slice(ENVELOPE_MAGIC);
+        aad.extend_from_slice(&self.segment_size.to_le_bytes());
+        aad.extend_from_slice(&self.nonce_prefix);
+        aad
+    }
+
+    /// Find our slot by public key and unwrap the content key from it.
+    fn content_key(&self, keypair: &EncryptionKeypair) -> Result<[u8; 32], String> {
+        let ours = keypair.public_key_bytes();
+        self.slots
+            .iter()
+            .find(|s| s.recipient_public_key == ours)
+            .ok_or_else(|| "Not a recipient of this file".to_string())?
+            .unwrap(keypair)
+    }
+
+    /// Add a slot for `recipient`. Returns false if it already has one.
+    fn add_recipient(
+        &mut self,
+        content_key: &[u8; 32],
+        recipient: &[u8; 32],
+    ) -> Result<bool, String> {
+        if self
+            .slots
+            .iter()
+            .any(|s| &s.recipient_public_key == recipient)
+        {
+            return Ok(false);
+        }
+        if self.slots.len() >= MAX_ENVELOPE_RECIPIENTS {
+            return Err(format!(
+                "An envelope holds at most {} recipients",
+                MAX_ENVELOPE_RECIPIENTS
+            ));
+        }
+        self.slots.push(EnvelopeSlot::wrap(content_key, recipient)?);
+        Ok(true)
+    }
+
+    /// Drop `recipient`'s slot. Returns false if it had none.
+    ///
+    /// This only stops future copies of the file from opening for them; it
+    /// cannot revoke a copy (or content key) they already have.
+    fn remove_recipient(&mut self, recipient: &[u8; 32]) -> bool {
+        let before = self.slots.len();
+        self.slots.retain(|s| &s.recipient_public_key != recipient);
+        self.slots.len() != before
+    }
+
+    /// Returns the number of bytes written.
+    fn write_to<W: Write>(&self, writer: &mut W) -> Result<u64, String> {
+        let mut out = Vec::with_capacity(
+            8 + 2 + self.slots.len() * (64 + WRAPPED_KEY_SIZE) + 4 + STREAM_NONCE_PREFIX_SIZE,
+        );
+        out.extend_from_slice(ENVELOPE_MAGIC);
+        out.extend_from_slice(&(self.slots.len() as u16).to_le_bytes());
+        for slot in &self.slots {
+            out.extend_from_slice(&slot.recipient_public_key);
+            out.extend_from_slice(&slot.ephemeral_public_key);
+            out.extend_from_slice(&slot.wrapped_key);
+        }
+        out.extend_from_slice(&self.segment_size.to_le_bytes());
+        out.extend_from_slice(&self.nonce_prefix);
+        writer
+            .write_all(&out)
+            .map_err(|e| format!("Write failed: {}", e))?;
+        Ok(out.len() as u64)
+    }
+
+    /// Read a header, leaving `reader` at the first payload segment.
+    pub fn read_from<R: Read>(reader: &mut R) -> Result<Self, String> {
+        let mut fixed = [0u8; 10];
+        if read_full(reader, &mut fixed)? != fixed.len() || &fixed[..8] != ENVELOPE_MAGIC {
+            return Err("Not an encrypted envelope".to_string());
+        }
+        let count = u16::from_le_bytes([fixed[8], fixed[9]]) as usize;
+        if count == 0 || count > MAX_ENVELOPE_RECIPIENTS {
+            return Err(format!("Invalid envelope recipient count {}", count));
+        }
+        let mut slots = Vec::with_capacity(count);
+        for _ in 0..count {
+            let mut raw = [0u8; 64 + WRAPPED_KEY_SIZE];
+            if read_full(reader, &mut raw)? != raw.len() {
+                return Err("Envelope header is truncated".to_string());
+            }
+            let mut slot = EnvelopeSlot {
+                recipient_public_key: [0u8; 32],
+                ephemeral_public_key: [0u8; 32],
+                wrapped_key: [0u8; WRAPPED_KEY_SIZE],
+            };
+            slot.recipient_public_key.copy_from_slice(&raw[..32]);
+            slot.ephemeral_public_key.copy_from_slice(&raw[32..64]);
+            slot.wrapped_key.copy_from_slice(&raw[64..]);
+            slots.push(slot);
+        }
+        let mut params = [0u8; 4 + STREAM_NONCE_PREFIX_SIZE];
+        if read_full(reader, &mut params)? != params.len() {
+            return Err("Envelope header is truncated".to_string());
+        }
+        let segment_size = parse_segment_size(&params[..4])? as u32;
+        let mut nonce_prefix = [0u8; STREAM_NONCE_PREFIX_SIZE];
+        nonce_prefix.copy_from_slice(&params[4..]);
+        Ok(Self {
+            slots,
+            segment_size,
+            nonce_prefix,
+        })
+    }
+}
+
+/// Encrypt `reader` once for several recipients. A random content key
+/// seals the payload and is wrapped for each (deduplicated) public key.
+/// Returns the number of bytes written.
+pub fn encrypt_envelope<R: Read, W: Write>(
+    mut reader: R,
+    mut writer: W,
+    recipients: &[[u8; 32]],
+) -> Result<u64, String> {
+    if recipients.is_empty() {
+        return Err("At least one recipient is required".to_string());
+    }
+    let content_key: [u8; 32] = Aes256Gcm::generate_key(&mut OsRng).into();
+    let mut header = EnvelopeHeader {
+        slots: Vec::with_capacity(recipients.len()),
+        segment_size: STREAM_SEGMENT_SIZE as u32,
+        nonce_prefix: random_nonce_prefix(),
+    };
+    for recipient in recipients {
+        header.add_recipient(&content_key, recipient)?;
+    }
+
+    let header_len = header.write_to(&mut writer)?;
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&content_key));
+    let sealed = seal_segments(
+        &mut reader,
+        &mut writer,
+        &cipher,
+        &header.nonce_prefix,
+        STREAM_SEGMENT_SIZE,
+        &header.payload_aad(),
+    )?;
+    Ok(header_len + sealed)
+}
+
+/// Decrypt an envelope using whichever key slot belongs to `keypair`.
+/// Returns the number of plaintext bytes.
+pub fn decrypt_envelope<R: Read, W: Write>(
+    mut reader: R,
+    mut writer: W,
+    keypair: &EncryptionKeypair,
+) -> Result<u64, String> {
+    let header = EnvelopeHeader::read_from(&mut reader)?;
+    let content_key = header.content_key(keypair)?;
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&content_key));
+    open_segments(
+        &mut reader,
+        &mut writer,
+        &cipher,
+        &header.nonce_prefix,
+        header.segment_size as usize,
+        &header.payload_aad(),
+    )
+}
+
+/// Change an envelope's recipients, copying the payload through untouched.
+///
+/// `keypair` must hold a slot: its content key is what gets wrapped for
+/// the added recipients. Removals are applied before additions, and the
+/// envelope must keep at least one recipient. Returns the new header.
+pub fn rewrap_envelope<R: Read, W: Write>(
+    mut reader: R,
+    mut writer: W,
+    keypair: &EncryptionKeypair,
+    add: &[[u8; 32]],
+    remove: &[[u8; 32]],
+) -> Result<EnvelopeHeader, String> {
+    let mut header = EnvelopeHeader::read_from(&mut reader)?;
+    let content_key = header.content_key(keypair)?;
+    for recipient in remove {
+        header.remove_recipient(recipient);
+    }
+    for recipient in add {
+        header.add_recipient(&content_key, recipient)?;
+    }
+    if header.slots.is_empty() {
+        return Err("An envelope must keep at least one recipient".to_string());
+    }
+    header.write_to(&mut writer)?;
+    std::io::copy(&mut reader, &mut writer).map_err(|e| format!("Copy failed: {}", e))?;
+    writer.flush().map_err(|e| format!("Write failed: {}", e))?;
+    Ok(header)
+}
+
+fn file_has_magic(path: &Path, expected: &[u8; 8]) -> bool {
+    let mut magic = [0u8; 8];
+    std::fs::File::open(path)
+        .map_err(|e| e.to_string())
+        .and_then(|mut f| read_full(&mut f, &mut magic))
+        .is_ok_and(|n| n == magic.len() && &magic == expected)
+}
+
+/// Whether a file starts with the streaming format's magic bytes.
+pub fn is_stream_encrypted_file(path: &Path) -> bool {
+    file_has_magic(path, STREAM_MAGIC)
+}
+
+/// Whether a file starts with the envelope format's magic bytes.
+pub fn is_envelope_file(path: &Path) -> bool {
+    file_has_magic(path, ENVELOPE_MAGIC)
+}
+
+/// Write `dest` through a sibling temp file so a failed run never leaves a
+/// partial (or, when decrypting, partially unauthenticated) output behind.
+fn stream_file_to_file(
+    src: &Path,
+    dest: &Path,
+    transform: impl FnOnce(std::fs::File, &mut BufWriter<std::fs::File>) -> Result<u64, String>,
+) -> Result<u64, String> {
+    let input =
+        std::fs::File::open(src).map_err(|e| format!("Failed to open {}: {}", src.display(), e))?;
+    let mut tmp_name = dest.as_os_str().to_owned();
+    tmp_name.push(".part");
+    let tmp = PathBuf::from(tmp_name);
+    let output = std::fs::File::create(&tmp)
+        .map_err(|e| format!("Failed to create {}: {}", tmp.display(), e))?;
+    let mut writer = BufWriter::new(output);
+    let result = transform(input, &mut writer).and_then(|n| {
+        writer
+            .into_inner()
+            .map_err(|e| format!("Write failed: {}", e))?
+            .sync_all()
+            .map_err(|e| format!("Write failed: {}", e))?;
+        std::fs::rename(&tmp, dest)
+            .map_err(|e| format!("Failed to finish {}: {}", dest.display(), e))?;
+        Ok(n)
+    });
+    if result.is_err() {
+        let _ = std::fs::remove_file(&tmp);
+    }
+    result
+}
+
+/// Encrypt the file at `src` for a recipient into `dest` in the streaming
+/// format. Returns the size of `dest`.
+pub fn encrypt_file_stream_for_recipient(
+    src: &Path,
+    dest: &Path,
+    recipient_public_key: &[u8; 32],
+) -> Result<u64, String> {
+    stream_file_to_file(src, dest, |input, writer| {
+        encrypt_stream_for_recipient(BufReader::new(input), writer, recipient_public_key)
+    })
+}
+
+/// Decrypt a streamed file at `src` into `dest`. `dest` only appears once
+/// every segment has authenticated. Returns the plaintext size.
+pub fn decrypt_file_stream_with_keypair(
+    src: &Path,
+    dest: &Path,
+    keypair: &EncryptionKeypair,
+) -> Result<u64, String> {
+    stream_file_to_file(src, dest, |input, writer| {
+        decrypt_stream_with_keypair(BufReader::new(input), writer, keypair)
+    })
+}
+
+/// Encrypt the file at `src` into an envelope at `dest` for `recipients`.
+/// Returns the size of `dest`.
+pub fn encrypt_file_envelope(
+    src: &Path,
+    dest: &Path,
+    recipients: &[[u8; 32]],
+) -> Result<u64, String> {
+    stream_file_to_file(src, dest, |input, writer| {
+        encrypt_envelope(BufReader::new(input), writer, recipients)
+    })
+}
+
+/// Decrypt the envelope at `src` into `dest`. Returns the plaintext size.
+pub fn decrypt_file_envelope(
+    src: &Path,
+    dest: &Path,
+    keypair: &EncryptionKeypair,
+) -> Result<u64, String> {
+    stream_file_to_file(src, dest, |input, writer| {
+        decrypt_envelope(BufReader::new(input), writer, keypair)
+    })
+}
+
+/// Rewrite the key slots of the envelope at `path` in place (through a temp
+/// file). Returns the recipients afterwards.
+pub fn rewrap_file_envelope(
+    path: &Path,
+    keypair: &EncryptionKeypair,
+    add: &[[u8; 32]],
+    remove: &[[u8; 32]],
+) -> Result<Vec<[u8; 32]>, String> {
+    let mut recipients = Vec::new();
+    stream_file_to_file(path, path, |input, writer| {
+        let header = rewrap_envelope(BufReader::new(input), writer, keypair, add, remove)?;
+        recipients = header.recipients();
+        Ok(0)
+    })?;
+    Ok(recipients)
+}
+
+/// Recipients of the envelope at `path`, read from its header only.
+pub fn read_file_envelope_recipients(path: &Path) -> Result<Vec<[u8; 32]>, String> {
+    let file = std::fs::File::open(path)
+        .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    Ok(EnvelopeHeader::read_from(&mut BufReader::new(file))?.recipients())
+}
+
+/// Parse a hex X25519 public key.
+pub fn parse_public_key_hex(public_key_hex: &str) -> Result<[u8; 32], String> {
+    hex::decode(public_key_hex.trim())
+        .map_err(|e| format!("Invalid public key hex: {}", e))?
+        .try_into()
+        .map_err(|_| "Public key must be 32 bytes".to_string())
+}
 
 #[cfg(test)]
 mod tests {
@@ -356,4 +1044,395 @@ mod tests {
         let decrypted = decrypt_with_keypair(&deserialized, &recipient).unwrap();
         assert_eq!(decrypted, b"serialize me");
     }
+
+    fn stream_roundtrip(plaintext: &[u8]) -> Vec<u8> {
+        let recipient = EncryptionKeypair::generate();
+        let mut sealed = Vec::new();
+        let written =
+            encrypt_stream_for_recipient(plaintext, &mut sealed, &recipient.public_key_bytes())
+                .unwrap();
+        assert_eq!(written, sealed.len() as u64);
+        let mut opened = Vec::new();
+        let n = decrypt_stream_with_keypair(sealed.as_slice(), &mut opened, &recipient).unwrap();
+        assert_eq!(n, plaintext.len() as u64);
+        assert_eq!(opened, plaintext);
+        sealed
+    }
+
+    fn patterned(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i % 251) as u8).collect()
+    }
+
+    #[test]
+    fn test_stream_roundtrip_small() {
+        let sealed = stream_roundtrip(b"hello stream");
+        assert_eq!(&sealed[..8], STREAM_MAGIC);
+        assert_eq!(sealed.len(), STREAM_HEADER_SIZE + 12 + STREAM_TAG_SIZE);
+    }
+
+    #[test]
+    fn test_stream_empty_input_has_one_final_segment() {
+        let sealed = stream_roundtrip(b"");
+        assert_eq!(sealed.len(), STREAM_HEADER_SIZE + STREAM_TAG_SIZE);
+    }
+
+    #[test]
+    fn test_stream_multi_segment_is_binary_sized() {
+        let plaintext = patterned(STREAM_SEGMENT_SIZE * 3 + 1234);
+        let sealed = stream_roundtrip(&plaintext);
+        // Binary output: only the header and one tag per segment are added.
+        assert_eq!(
+            sealed.len(),
+            STREAM_HEADER_SIZE + plaintext.len() + 4 * STREAM_TAG_SIZE
+        );
+    }
+
+    #[test]
+    fn test_stream_exact_segment_multiple() {
+        let plaintext = patterned(STREAM_SEGMENT_SIZE * 2);
+        let sealed = stream_roundtrip(&plaintext);
+        // No empty trailing segment when the input ends on a boundary.
+        assert_eq!(
+            sealed.len(),
+            STREAM_HEADER_SIZE + plaintext.len() + 2 * STREAM_TAG_SIZE
+        );
+    }
+
+    fn seal(plaintext: &[u8], recipient: &EncryptionKeypair) -> Vec<u8> {
+        let mut sealed = Vec::new();
+        encrypt_stream_for_recipient(plaintext, &mut sealed, &recipient.public_key_bytes())
+            .unwrap();
+        sealed
+    }
+
+    #[test]
+    fn test_stream_truncation_at_segment_boundary_fails() {
+        let recipient = EncryptionKeypair::generate();
+        let sealed = seal(&patterned(STREAM_SEGMENT_SIZE * 2 + 10), &recipient);
+        // Drop the whole final segment: the remaining ones are intact but the
+        // last of them was not sealed as final.
+        let cut = STREAM_HEADER_SIZE + 2 * (STREAM_SEGMENT_SIZE + STREAM_TAG_SIZE);
+        let mut out = Vec::new();
+        assert!(decrypt_stream_with_keypair(&sealed[..cut], &mut out, &recipient).is_err());
+    }
+
+    #[test]
+    fn test_stream_truncation_mid_segment_fails() {
+        let recipient = EncryptionKeypair::generate();
+        let sealed = seal(&patterned(1000), &recipient);
+        let mut out = Vec::new();
+        let err = decrypt_stream_with_keypair(&sealed[..sealed.len() - 1], &mut out, &recipient)
+            .unwrap_err();
+        assert!(err.contains("segment 0"));
+        let mut out = Vec::new();
+        assert!(decrypt_stream_with_keypair(
+            &sealed[..STREAM_HEADER_SIZE + 3],
+            &mut out,
+            &recipient
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_stream_reordered_segments_fail() {
+        let recipient = EncryptionKeypair::generate();
+        let sealed = seal(&patterned(STREAM_SEGMENT_SIZE * 3), &recipient);
+        let seg = STREAM_SEGMENT_SIZE + STREAM_TAG_SIZE;
+        let mut swapped = sealed[..STREAM_HEADER_SIZE].to_vec();
+        swapped.extend_from_slice(&sealed[STREAM_HEADER_SIZE + seg..STREAM_HEADER_SIZE + 2 * seg]);
+        swapped.extend_from_slice(&sealed[STREAM_HEADER_SIZE..STREAM_HEADER_SIZE + seg]);
+        swapped.extend_from_slice(&sealed[STREAM_HEADER_SIZE + 2 * seg..]);
+        let mut out = Vec::new();
+        assert!(decrypt_stream_with_keypair(swapped.as_slice(), &mut out, &recipient).is_err());
+    }
+
+    #[test]
+    fn test_stream_tampered_byte_fails() {
+        let recipient = EncryptionKeypair::generate();
+        let mut sealed = seal(&patterned(5000), &recipient);
+        let last = sealed.len() - 20;
+        sealed[last] ^= 0x01;
+        let mut out = Vec::new();
+        assert!(decrypt_stream_with_keypair(sealed.as_slice(), &mut out, &recipient).is_err());
+    }
+
+    #[test]
+    fn test_stream_tampered_header_fails() {
+        let recipient = EncryptionKeypair::generate();
+        let mut sealed = seal(b"header is authenticated", &recipient);
+        sealed[STREAM_HEADER_SIZE - 1] ^= 0x01;
+        let mut out = Vec::new();
+        assert!(decrypt_stream_with_keypair(sealed.as_slice(), &mut out, &recipient).is_err());
+
+        let mut bad_magic = seal(b"x", &recipient);
+        bad_magic[0] = b'X';
+        let err =
+            decrypt_stream_with_keypair(bad_magic.as_slice(), &mut out, &recipient).unwrap_err();
+        assert!(err.contains("Not a streamed"));
+    }
+
+    #[test]
+    fn test_stream_wrong_key_fails() {
+        let recipient = EncryptionKeypair::generate();
+        let other = EncryptionKeypair::generate();
+        let sealed = seal(b"for recipient only", &recipient);
+        let mut out = Vec::new();
+        assert!(decrypt_stream_with_keypair(sealed.as_slice(), &mut out, &other).is_err());
+        assert!(out.is_empty());
+    }
+
+    #[test]
+    fn test_stream_file_helpers_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("plain.bin");
+        let enc = dir.path().join("plain.bin.encrypted");
+        let dec = dir.path().join("plain.out");
+        let plaintext = patterned(STREAM_SEGMENT_SIZE + 77);
+        std::fs::write(&src, &plaintext).unwrap();
+        let recipient = EncryptionKeypair::generate();
+
+        let size =
+            encrypt_file_stream_for_recipient(&src, &enc, &recipient.public_key_bytes()).unwrap();
+        assert_eq!(size, std::fs::metadata(&enc).unwrap().len());
+        assert!(is_stream_encrypted_file(&enc));
+        assert!(!is_stream_encrypted_file(&src));
+
+        let n = decrypt_file_stream_with_keypair(&enc, &dec, &recipient).unwrap();
+        assert_eq!(n, plaintext.len() as u64);
+        assert_eq!(std::fs::read(&dec).unwrap(), plaintext);
+    }
+
+    #[test]
+    fn test_stream_file_decrypt_failure_leaves_no_output() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("plain.bin");
+        let enc = dir.path().join("plain.bin.encrypted");
+        let dec = dir.path().join("plain.out");
+        std::fs::write(&src, patterned(STREAM_SEGMENT_SIZE * 2 + 5)).unwrap();
+        let recipient = EncryptionKeypair::generate();
+        encrypt_file_stream_for_recipient(&src, &enc, &recipient.public_key_bytes()).unwrap();
+
+        let mut sealed = std::fs::read(&enc).unwrap();
+        let len = sealed.len();
+        sealed[len - 1] ^= 0xff;
+        std::fs::write(&enc, sealed).unwrap();
+
+        assert!(decrypt_file_stream_with_keypair(&enc, &dec, &recipient).is_err());
+        assert!(!dec.exists());
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn test_parse_public_key_hex() {
+        let kp = EncryptionKeypair::generate();
+        let hex_key = hex::encode(kp.public_key_bytes());
+        assert_eq!(
+            parse_public_key_hex(&hex_key).unwrap(),
+            kp.public_key_bytes()
+        );
+        assert!(parse_public_key_hex("zz").is_err());
+        assert!(parse_public_key_hex("abcd").is_err());
+    }
+
+    fn envelope_for(plaintext: &[u8], recipients: &[&EncryptionKeypair]) -> Vec<u8> {
+        let keys: Vec<[u8; 32]> = recipients.iter().map(|k| k.public_key_bytes()).collect();
+        let mut sealed = Vec::new();
+        let written = encrypt_envelope(plaintext, &mut sealed, &keys).unwrap();
+        assert_eq!(written, sealed.len() as u64);
+        sealed
+    }
+
+    fn open_envelope(sealed: &[u8], keypair: &EncryptionKeypair) -> Result<Vec<u8>, String> {
+        let mut out = Vec::new();
+        decrypt_envelope(sealed, &mut out, keypair)?;
+        Ok(out)
+    }
+
+    #[test]
+    fn test_envelope_every_recipient_can_decrypt() {
+        let (a, b, c) = (
+            EncryptionKeypair::generate(),
+            EncryptionKeypair::generate(),
+            EncryptionKeypair::generate(),
+        );
+        let plaintext = patterned(STREAM_SEGMENT_SIZE * 2 + 99);
+        let sealed = envelope_for(&plaintext, &[&a, &b, &c]);
+        assert_eq!(&sealed[..8], ENVELOPE_MAGIC);
+        for keypair in [&a, &b, &c] {
+            assert_eq!(open_envelope(&sealed, keypair).unwrap(), plaintext);
+        }
+    }
+
+    #[test]
+    fn test_envelope_non_recipient_is_rejected() {
+        let a = EncryptionKeypair::generate();
+        let outsider = EncryptionKeypair::generate();
+        let sealed = envelope_for(b"team only", &[&a]);
+        let err = open_envelope(&sealed, &outsider).unwrap_err();
+        assert!(err.contains("Not a recipient"));
+    }
+
+    #[test]
+    fn test_envelope_deduplicates_and_requires_recipients() {
+        let a = EncryptionKeypair::generate();
+        let sealed = envelope_for(b"x", &[&a, &a]);
+        let header = EnvelopeHeader::read_from(&mut sealed.as_slice()).unwrap();
+        assert_eq!(header.recipients(), vec![a.public_key_bytes()]);
+
+        let mut out = Vec::new();
+        assert!(encrypt_envelope(&b"x"[..], &mut out, &[]).is_err());
+    }
+
+    #[test]
+    fn test_envelope_rejects_too_many_recipients() {
+        let keys: Vec<[u8; 32]> = (0..=MAX_ENVELOPE_RECIPIENTS)
+            .map(|_| EncryptionKeypair::generate().public_key_bytes())
+            .collect();
+        let mut out = Vec::new();
+        assert!(encrypt_envelope(&b"x"[..], &mut out, &keys).is_err());
+    }
+
+    #[test]
+    fn test_envelope_header_roundtrip() {
+        let (a, b) = (EncryptionKeypair::generate(), EncryptionKeypair::generate());
+        let sealed = envelope_for(b"header", &[&a, &b]);
+        let mut reader = sealed.as_slice();
+        let header = EnvelopeHeader::read_from(&mut reader).unwrap();
+        let mut rewritten = Vec::new();
+        let len = header.write_to(&mut rewritten).unwrap();
+        assert_eq!(len as usize, sealed.len() - reader.len());
+        assert_eq!(rewritten, sealed[..len as usize]);
+        assert_eq!(
+            header.recipients(),
+            vec![a.public_key_bytes(), b.public_key_bytes()]
+        );
+    }
+
+    #[test]
+    fn test_envelope_add_recipient_keeps_payload() {
+        let (owner, newcomer) = (EncryptionKeypair::generate(), EncryptionKeypair::generate());
+        let plaintext = patterned(5000);
+        let sealed = envelope_for(&plaintext, &[&owner]);
+        assert!(open_envelope(&sealed, &newcomer).is_err());
+
+        let mut rewrapped = Vec::new();
+        let header = rewrap_envelope(
+            sealed.as_slice(),
+            &mut rewrapped,
+            &owner,
+            &[newcomer.public_key_bytes()],
+            &[],
+        )
+        .unwrap();
+        assert_eq!(header.recipients().len(), 2);
+        assert_eq!(open_envelope(&rewrapped, &newcomer).unwrap(), plaintext);
+        assert_eq!(open_envelope(&rewrapped, &owner).unwrap(), plaintext);
+
+        // The payload segments are byte-for-byte the same.
+        let payload_len = sealed.len() - (8 + 2 + (64 + WRAPPED_KEY_SIZE) + 4 + 7);
+        assert_eq!(
+            sealed[sealed.len() - payload_len..],
+            rewrapped[rewrapped.len() - payload_len..]
+        );
+    }
+
+    #[test]
+    fn test_envelope_remove_recipient() {
+        let (owner, leaver) = (EncryptionKeypair::generate(), EncryptionKeypair::generate());
+        let sealed = envelope_for(b"members only", &[&owner, &leaver]);
+
+        let mut rewrapped = Vec::new();
+        let header = rewrap_envelope(
+            sealed.as_slice(),
+            &mut rewrapped,
+            &owner,
+            &[],
+            &[leaver.public_key_bytes()],
+        )
+        .unwrap();
+        assert_eq!(header.recipients(), vec![owner.public_key_bytes()]);
+        assert!(open_envelope(&rewrapped, &leaver).is_err());
+        assert_eq!(open_envelope(&rewrapped, &owner).unwrap(), b"members only");
+    }
+
+    #[test]
+    fn test_envelope_rewrap_requires_a_slot_and_a_survivor() {
+        let (owner, outsider) = (EncryptionKeypair::generate(), EncryptionKeypair::generate());
+        let sealed = envelope_for(b"x", &[&owner]);
+
+        let mut out = Vec::new();
+        let err = rewrap_envelope(
+            sealed.as_slice(),
+            &mut out,
+            &outsider,
+            &[outsider.public_key_bytes()],
+            &[],
+        )
+        .unwrap_err();
+        assert!(err.contains("Not a recipient"));
+
+        let mut out = Vec::new();
+        assert!(rewrap_envelope(
+            sealed.as_slice(),
+            &mut out,
+            &owner,
+            &[],
+            &[owner.public_key_bytes()]
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_envelope_tampering_fails() {
+        let a = EncryptionKeypair::generate();
+        let sealed = envelope_for(&patterned(3000), &[&a]);
+
+        let mut payload = sealed.clone();
+        let last = payload.len() - 1;
+        payload[last] ^= 0x01;
+        assert!(open_envelope(&payload, &a).is_err());
+
+        // Flip a byte of the wrapped content key.
+        let mut slot = sealed.clone();
+        slot[8 + 2 + 64] ^= 0x01;
+        assert!(open_envelope(&slot, &a).is_err());
+
+        // Flip a byte of the nonce prefix, which is authenticated.
+        let mut params = sealed.clone();
+        params[8 + 2 + 64 + WRAPPED_KEY_SIZE + 4] ^= 0x01;
+        assert!(open_envelope(&params, &a).is_err());
+
+        assert!(open_envelope(&sealed[..sealed.len() - 5], &a).is_err());
+        assert!(open_envelope(&sealed[..20], &a).is_err());
+    }
+
+    #[test]
+    fn test_envelope_file_helpers() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("report.pdf");
+        let enc = dir.path().join("report.pdf.encrypted");
+        let dec = dir.path().join("report.out.pdf");
+        let plaintext = patterned(STREAM_SEGMENT_SIZE + 1);
+        std::fs::write(&src, &plaintext).unwrap();
+        let (owner, peer) = (EncryptionKeypair::generate(), EncryptionKeypair::generate());
+
+        encrypt_file_envelope(&src, &enc, &[owner.public_key_bytes()]).unwrap();
+        assert!(is_envelope_file(&enc));
+        assert!(!is_stream_encrypted_file(&enc));
+        assert!(decrypt_file_envelope(&enc, &dec, &peer).is_err());
+        assert!(!dec.exists());
+
+        let recipients =
+            rewrap_file_envelope(&enc, &owner, &[peer.public_key_bytes()], &[]).unwrap();
+        assert_eq!(
+            recipients,
+            vec![owner.public_key_bytes(), peer.public_key_bytes()]
+        );
+        assert_eq!(read_file_envelope_recipients(&enc).unwrap(), recipients);
+
+        decrypt_file_envelope(&enc, &dec, &peer).unwrap();
+        assert_eq!(std::fs::read(&dec).unwrap(), plaintext);
+        // Nothing but the three files is left behind.
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 3);
+    }
 }
@@ -1,11 +1,40 @@
 use serde::Serialize;
 use tauri::Emitter;
+use tokio::sync::broadcast;
+
+/// Events buffered per subscriber before a slow reader starts missing them.
+pub const EVENT_BUFFER: usize = 1024;
+
+/// An event as delivered to daemon subscribers.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DaemonEvent {
+    pub event: String,
+    pub payload: serde_json::Value,
+    /// Unix time in milliseconds when the event was emitted.
+    pub timestamp: u64,
+}
+
+impl DaemonEvent {
+    pub fn new(event: &str, payload: serde_json::Value) -> Self {
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+        Self {
+            event: event.to_string(),
+            payload,
+            timestamp,
+        }
+    }
+}
 
 /// Event sink used by networking services.
-/// In GUI mode it forwards to Tauri, in headless mode it's a no-op.
+/// In GUI mode it forwards to Tauri; under the daemon it fans out to
+/// `/api/headless/events` subscribers; otherwise it's a no-op.
 #[derive(Clone)]
 pub enum EventSink {
     Tauri(tauri::AppHandle),
+    Broadcast(broadcast::Sender<DaemonEvent>),
     Noop,
 }
 
@@ -14,16 +43,161 @@ impl EventSink {
         Self::Tauri(app)
     }
 
+    pub fn broadcast() -> Self {
+        Self::Broadcast(broadcast::channel(EVENT_BUFFER).0)
+    }
+
     pub fn noop() -> Self {
         Self::Noop
     }
 
+    /// A receiver for events emitted from now on, or `None` if this sink
+    /// doesn't broadcast.
+    pub fn subscribe(&self) -> Option<broadcast::Receiver<DaemonEvent>> {
+        match self {
+            Self::Broadcast(tx) => Some(tx.subscribe()),
+            _ => None,
+        }
+    }
+
     pub fn emit<T>(&self, event: &str, payload: T)
     where
         T: Serialize + Clone,
     {
-        if let Self::Tauri(app) = self {
-            let _ = app.emit(event, payload);
+        match self {
+            Self::Tauri(app) => {
+                let _ = app.emit(event, payload);
+            }
+            Self::Broadcast(tx) => {
+                // Skip the serialization when nobody is listening.
+                if tx.receiver_count() == 0 {
+                    return;
+                }
+                let payload = serde_json::to_value(payload).unwrap_or(serde_json::Value::Null);
+                let _ = tx.send(DaemonEvent::new(event, payload));
+            }
+            Self::Noop => {}
+        }
+    }
+}
+
+/// Which event types a subscriber wants. Built from a comma-separated list
+/// where `download-*` matches every type starting with `download-`; an
+/// empty list matches everything.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EventFilter {
+    patterns: Vec<String>,
+}
+
+impl EventFilter {
+    pub fn parse(types: Option<&str>) -> Self {
+        let patterns = types
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|t| !t.is_empty())
+            .map(str::to_string)
+            .collect();
+        Self { patterns }
+    }
+
+    pub fn matches(&self, event: &str) -> bool {
+        self.patterns.is_empty()
+            || self.patterns.iter().any(|p| match p.strip_suffix('*') {
+                Some(prefix) => event.starts_with(prefix),
+                None => event == p,
+            })
+    }
+}
+
+/// The next event `filter` accepts, or `None` once the sink is gone. A
+/// subscriber that fell behind gets an `events-lagged` event saying how
+/// many it missed, whatever its filter.
+pub async fn next_matching(
+    rx: &mut broadcast::Receiver<DaemonEvent>,
+    filter: &EventFilter,
+) -> Option<DaemonEvent> {
+    loop {
+        match rx.recv().await {
+            Ok(event) if filter.matches(&event.event) => return Some(event),
+            Ok(_) => continue,
+            Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                return Some(DaemonEvent::new(
+                    "events-lagged",
+                    serde_json::json!({ "skipped": skipped }),
+                ))
+            }
+            Err(broadcast::error::RecvError::Closed) => return None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filter_matches_exact_and_prefix_patterns() {
+        let all = EventFilter::parse(None);
+        assert!(all.matches("download-progress"));
+        assert_eq!(EventFilter::parse(Some(" , ")), all);
+
+        let filter = EventFilter::parse(Some("download-*, file-transfer-request"));
+        assert!(filter.matches("download-progress"));
+        assert!(filter.matches("download-resumed"));
+        assert!(filter.matches("file-transfer-request"));
+        assert!(!filter.matches("file-transfer-complete"));
+        assert!(!filter.matches("peer-discovered"));
+    }
+
+    #[tokio::test]
+    async fn broadcast_sink_delivers_to_every_subscriber() {
+        let sink = EventSink::broadcast();
+        // Nothing is buffered for subscribers that join later.
+        sink.emit("peer-discovered", "early");
+        let mut a = sink.subscribe().unwrap();
+        let mut b = sink.subscribe().unwrap();
+
+        sink.emit(
+            "download-progress",
+            serde_json::json!({ "requestId": "r1", "progress": 50.0 }),
+        );
+        for rx in [&mut a, &mut b] {
+            let event = rx.recv().await.unwrap();
+            assert_eq!(event.event, "download-progress");
+            assert_eq!(event.payload["requestId"], "r1");
+            assert!(event.timestamp > 0);
+        }
+        assert!(a.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn next_matching_skips_filtered_events_and_reports_lag() {
+        let (tx, mut rx) = broadcast::channel(2);
+        let sink = EventSink::Broadcast(tx);
+        let filter = EventFilter::parse(Some("file-*"));
+
+        sink.emit("peer-discovered", 1);
+        sink.emit("file-sent", 2);
+        let event = next_matching(&mut rx, &filter).await.unwrap();
+        assert_eq!(event.event, "file-sent");
+
+        for i in 0..5 {
+            sink.emit("file-sent", i);
         }
+        let lagged = next_matching(&mut rx, &filter).await.unwrap();
+        assert_eq!(lagged.event, "events-lagged");
+        assert_eq!(lagged.payload["skipped"], 3);
+        assert_eq!(next_matching(&mut rx, &filter).await.unwrap().payload, 3);
+
+        drop(sink);
+        assert_eq!(next_matching(&mut rx, &filter).await.unwrap().payload, 4);
+        assert!(next_matching(&mut rx, &filter).await.is_none());
+    }
+
+    #[test]
+    fn non_broadcast_sinks_have_no_subscribers() {
+        assert!(EventSink::noop().subscribe().is_none());
+        EventSink::noop().emit("anything", 1);
     }
 }
@@ -1,20 +1,120 @@
+use rand::RngCore;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 use tokio::sync::Mutex;
 
+use crate::dht::FileTransferRequest;
 use crate::event_sink::EventSink;
 
+/// A ChiralDrop offer. Only metadata is kept here; the content stays on the
+/// sender's disk until the receiver accepts and pulls it in chunks.
 #[derive(Clone, Serialize, Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct PendingTransfer {
     pub transfer_id: String,
     pub peer_id: String,
     pub file_name: String,
-    pub file_data: Vec<u8>,
+    pub file_hash: String,
+    pub file_size: u64,
+    /// Price in wei; "0" for free offers.
+    #[serde(default)]
+    pub price_wei: String,
+    #[serde(default)]
+    pub sender_wallet: String,
+    /// Chunked download pulling the content, once the offer is accepted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub download_request_id: Option<String>,
     pub status: TransferStatus,
 }
 
+impl PendingTransfer {
+    fn from_offer(peer_id: String, offer: FileTransferRequest) -> Self {
+        let price_wei = if offer.price_wei.trim().is_empty() {
+            "0".to_string()
+        } else {
+            offer.price_wei
+        };
+        Self {
+            transfer_id: offer.transfer_id,
+            peer_id,
+            file_name: offer.file_name,
+            file_hash: offer.file_hash,
+            file_size: offer.file_size,
+            price_wei,
+            sender_wallet: offer.sender_wallet,
+            download_request_id: None,
+            status: TransferStatus::Pending,
+        }
+    }
+
+    pub fn is_paid(&self) -> bool {
+        self.price_wei.parse::<u128>().is_ok_and(|price| price > 0)
+    }
+}
+
+/// How long staged outgoing files, and the offers serving them, are kept
+/// for receivers to pull.
+pub const OUTBOX_RETENTION: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+pub fn outbox_dir() -> PathBuf {
+    crate::network::data_dir().join("chiraldrop-outbox")
+}
+
+/// Write content that only exists in memory (e.g. an encrypted bundle) to
+/// the outbox, so it can be offered and served in chunks like any file on
+/// disk. Staged files older than a week are cleared on the way.
+pub fn stage_outgoing_bytes(bytes: &[u8]) -> Result<PathBuf, String> {
+    stage_outgoing_bytes_in(&outbox_dir(), bytes, SystemTime::now())
+}
+
+/// A fresh outbox path for callers that stream their output (e.g. the
+/// streaming encryptor) instead of building it in memory.
+pub fn reserve_outgoing_path() -> Result<PathBuf, String> {
+    reserve_outgoing_path_in(&outbox_dir(), SystemTime::now())
+}
+
+/// Delete `path` if it is a file staged in the outbox. Files offered from
+/// elsewhere on disk are left alone.
+pub fn discard_staged_file(path: &Path) {
+    discard_staged_file_in(&outbox_dir(), path);
+}
+
+fn discard_staged_file_in(dir: &Path, path: &Path) {
+    if path.parent() == Some(dir) {
+        let _ = std::fs::remove_file(path);
+    }
+}
+
+fn stage_outgoing_bytes_in(dir: &Path, bytes: &[u8], now: SystemTime) -> Result<PathBuf, String> {
+    let path = reserve_outgoing_path_in(dir, now)?;
+    std::fs::write(&path, bytes).map_err(|e| format!("Failed to stage outgoing file: {}", e))?;
+    Ok(path)
+}
+
+fn reserve_outgoing_path_in(dir: &Path, now: SystemTime) -> Result<PathBuf, String> {
+    std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create outbox: {}", e))?;
+    if let Ok(entries) = std::fs::read_dir(dir) {
+        for entry in entries.flatten() {
+            let expired = entry
+                .metadata()
+                .and_then(|m| m.modified())
+                .ok()
+                .and_then(|modified| now.duration_since(modified).ok())
+                .is_some_and(|age| age > OUTBOX_RETENTION);
+            if expired {
+                let _ = std::fs::remove_file(entry.path());
+            }
+        }
+    }
+
+    let mut name = [0u8; 16];
+    rand::rngs::OsRng.fill_bytes(&mut name);
+    Ok(dir.join(format!("{}.bin", hex::encode(name))))
+}
+
 #[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub enum TransferStatus {
@@ -24,6 +124,7 @@ pub enum TransferStatus {
     InProgress,
     Completed,
     Failed,
+    Cancelled,
 }
 
 pub struct FileTransferService {
@@ -39,164 +140,166 @@ impl FileTransferService {
         }
     }
 
-    pub async fn send_file(
+    /// Remember an offer we sent, so it shows up as outgoing.
+    pub async fn record_outgoing_offer(
         &self,
         events: EventSink,
-        peer_id: String,
-        file_name: String,
-        file_data: Vec<u8>,
-        transfer_id: String,
-    ) -> Result<(), String> {
-        let file_size = file_data.len();
-
-        // Store as pending outgoing
-        let transfer = PendingTransfer {
-            transfer_id: transfer_id.clone(),
-            peer_id: peer_id.clone(),
-            file_name: file_name.clone(),
-            file_data,
-            status: TransferStatus::Pending,
-        };
+        to_peer_id: String,
+        offer: FileTransferRequest,
+    ) {
+        let transfer = PendingTransfer::from_offer(to_peer_id.clone(), offer);
 
-        {
-            let mut outgoing = self.pending_outgoing.lock().await;
-            outgoing.insert(transfer_id.clone(), transfer);
-        }
-
-        // Emit file-sent event
         events.emit(
             "file-sent",
             serde_json::json!({
-                "transferId": transfer_id,
-                "peerId": peer_id,
-                "fileName": file_name,
-                "fileSize": file_size
+                "transferId": transfer.transfer_id,
+                "peerId": to_peer_id,
+                "fileName": transfer.file_name,
+                "fileHash": transfer.file_hash,
+                "fileSize": transfer.file_size
             }),
         );
 
-        // In a real implementation, this would use libp2p request-response protocol
-        // to send the file to the peer. For now, we emit the event and mark as completed.
-        // The actual P2P transfer would be handled by extending the DHT behavior.
-
-        // Update status to completed (simulated for now)
-        {
-            let mut outgoing = self.pending_outgoing.lock().await;
-            if let Some(t) = outgoing.get_mut(&transfer_id) {
-                t.status = TransferStatus::Completed;
-            }
-        }
-
-        events.emit(
-            "file-transfer-complete",
-            serde_json::json!({
-                "transferId": transfer_id,
-                "status": "completed"
-            }),
-        );
-
-        Ok(())
+        let mut outgoing = self.pending_outgoing.lock().await;
+        outgoing.insert(transfer.transfer_id.clone(), transfer);
     }
 
-    pub async fn receive_file_request(
+    /// Store an incoming offer and ask the user to accept or decline it.
+    pub async fn receive_offer(
         &self,
         events: EventSink,
         from_peer_id: String,
-        file_name: String,
-        file_data: Vec<u8>,
-        transfer_id: String,
-    ) -> Result<(), String> {
-        let file_size = file_data.len();
-
-        let transfer = PendingTransfer {
-            transfer_id: transfer_id.clone(),
-            peer_id: from_peer_id.clone(),
-            file_name: file_name.clone(),
-            file_data,
-            status: TransferStatus::Pending,
-        };
+        offer: FileTransferRequest,
+    ) {
+        let transfer = PendingTransfer::from_offer(from_peer_id.clone(), offer);
 
-        {
-            let mut incoming = self.pending_incoming.lock().await;
-            incoming.insert(transfer_id.clone(), transfer);
+        // Paid offers go through the payment prompt; free ones through the
+        // plain accept/decline prompt.
+        if transfer.is_paid() {
+            events.emit(
+                "chiraldrop-paid-request",
+                serde_json::json!({
+                    "transferId": transfer.transfer_id,
+                    "fromPeerId": from_peer_id,
+                    "fileName": transfer.file_name,
+                    "fileHash": transfer.file_hash,
+                    "fileSize": transfer.file_size,
+                    "priceWei": transfer.price_wei,
+                    "senderWallet": transfer.sender_wallet
+                }),
+            );
+        } else {
+            events.emit(
+                "file-transfer-request",
+                serde_json::json!({
+                    "transferId": transfer.transfer_id,
+                    "fromPeerId": from_peer_id,
+                    "fileName": transfer.file_name,
+                    "fileHash": transfer.file_hash,
+                    "fileSize": transfer.file_size
+                }),
+            );
         }
 
-        // Emit event to frontend for user to accept/decline
-        events.emit(
-            "file-transfer-request",
-            serde_json::json!({
-                "transferId": transfer_id,
-                "fromPeerId": from_peer_id,
-                "fileName": file_name,
-                "fileSize": file_size
-            }),
-        );
-
-        Ok(())
+        let mut incoming = self.pending_incoming.lock().await;
+        incoming.insert(transfer.transfer_id.clone(), transfer);
     }
 
-    pub async fn accept_transfer(
-        &self,
-        events: EventSink,
-        transfer_id: String,
-        custom_download_dir: Option<String>,
-    ) -> Result<String, String> {
+    /// Mark a pending offer as accepted and return it, so the caller can
+    /// start pulling its content.
+    pub async fn accept_transfer(&self, transfer_id: &str) -> Result<PendingTransfer, String> {
         let mut incoming = self.pending_incoming.lock().await;
 
-        if let Some(transfer) = incoming.get_mut(&transfer_id) {
-            transfer.status = TransferStatus::Accepted;
-
-            // Get Downloads folder path
-            let downloads_dir = if let Some(ref dir) = custom_download_dir {
-                let p = std::path::PathBuf::from(dir);
-                if p.exists() && p.is_dir() {
-                    p
-                } else {
-                    dirs::download_dir()
-                        .ok_or_else(|| "Could not find Downloads folder".to_string())?
-                }
-            } else {
-                dirs::download_dir().ok_or_else(|| "Could not find Downloads folder".to_string())?
-            };
-
-            // Create file path
-            let file_path = downloads_dir.join(&transfer.file_name);
+        match incoming.get_mut(transfer_id) {
+            Some(transfer) if transfer.status == TransferStatus::Pending => {
+                transfer.status = TransferStatus::Accepted;
+                Ok(transfer.clone())
+            }
+            Some(_) => Err("Transfer is no longer pending".to_string()),
+            None => Err("Transfer not found".to_string()),
+        }
+    }
 
-            // Save file to disk
-            std::fs::write(&file_path, &transfer.file_data)
-                .map_err(|e| format!("Failed to save file: {}", e))?;
+    /// Record the download pulling an accepted offer's content.
+    pub async fn start_transfer_download(
+        &self,
+        transfer_id: &str,
+        request_id: String,
+    ) -> Result<(), String> {
+        let mut incoming = self.pending_incoming.lock().await;
 
-            transfer.status = TransferStatus::Completed;
+        if let Some(transfer) = incoming.get_mut(transfer_id) {
+            transfer.download_request_id = Some(request_id);
+            transfer.status = TransferStatus::InProgress;
+            Ok(())
+        } else {
+            Err("Transfer not found".to_string())
+        }
+    }
 
-            let file_path_str = file_path.to_string_lossy().to_string();
+    /// Put an accepted offer back to pending after its download could not
+    /// be started.
+    pub async fn reset_transfer(&self, transfer_id: &str) {
+        let mut incoming = self.pending_incoming.lock().await;
+        if let Some(transfer) = incoming.get_mut(transfer_id) {
+            transfer.download_request_id = None;
+            transfer.status = TransferStatus::Pending;
+        }
+    }
 
-            // Emit file-received event with file path
-            events.emit(
-                "file-received",
-                serde_json::json!({
-                    "transferId": transfer_id,
-                    "fileName": transfer.file_name,
-                    "fromPeerId": transfer.peer_id,
-                    "filePath": file_path_str
-                }),
-            );
+    /// Mark an incoming transfer cancelled and return it. Its
+    /// `download_request_id` is the download that has to be stopped, if one
+    /// was started.
+    pub async fn cancel_transfer(&self, transfer_id: &str) -> Result<PendingTransfer, String> {
+        let mut incoming = self.pending_incoming.lock().await;
 
-            Ok(file_path_str)
+        if let Some(transfer) = incoming.get_mut(transfer_id) {
+            transfer.status = TransferStatus::Cancelled;
+            Ok(transfer.clone())
         } else {
             Err("Transfer not found".to_string())
         }
     }
 
-    pub async fn decline_transfer(&self, transfer_id: String) -> Result<(), String> {
+    /// Mark an incoming offer declined and return it, so the sender can be
+    /// told to stop serving it.
+    pub async fn decline_transfer(&self, transfer_id: String) -> Result<PendingTransfer, String> {
         let mut incoming = self.pending_incoming.lock().await;
 
         if let Some(transfer) = incoming.get_mut(&transfer_id) {
             transfer.status = TransferStatus::Declined;
-            Ok(())
+            Ok(transfer.clone())
         } else {
             Err("Transfer not found".to_string())
         }
     }
 
+    /// Mark the transfer pulled by download `request_id` completed once all
+    /// its chunks arrived. Returns it, or `None` if the download was not a
+    /// ChiralDrop transfer.
+    pub async fn complete_transfer_download(&self, request_id: &str) -> Option<PendingTransfer> {
+        let mut incoming = self.pending_incoming.lock().await;
+        let transfer = incoming.values_mut().find(|t| {
+            t.status == TransferStatus::InProgress
+                && t.download_request_id.as_deref() == Some(request_id)
+        })?;
+        transfer.status = TransferStatus::Completed;
+        Some(transfer.clone())
+    }
+
+    /// Record that the receiver of an offer we sent closed it.
+    pub async fn close_outgoing_offer(&self, transfer_id: &str, status: TransferStatus) {
+        let mut outgoing = self.pending_outgoing.lock().await;
+        if let Some(transfer) = outgoing.get_mut(transfer_id) {
+            transfer.status = status;
+        }
+    }
+
+    /// Whether `transfer_id` is an offer we received (as opposed to sent).
+    pub async fn is_incoming(&self, transfer_id: &str) -> bool {
+        self.pending_incoming.lock().await.contains_key(transfer_id)
+    }
+
     pub async fn get_pending_incoming(&self) -> Vec<PendingTransfer> {
         let incoming = self.pending_incoming.lock().await;
         incoming
@@ -246,6 +349,7 @@ mod tests {
             TransferStatus::InProgress,
             TransferStatus::Completed,
             TransferStatus::Failed,
+            TransferStatus::Cancelled,
         ];
         for status in statuses {
             let json = serde_json::to_string(&status).unwrap();
@@ -254,22 +358,181 @@ mod tests {
         }
     }
 
+    fn offer(transfer_id: &str, price_wei: &str) -> FileTransferRequest {
+        FileTransferRequest {
+            transfer_id: transfer_id.to_string(),
+            file_name: "video.mp4".to_string(),
+            file_size: 2 * 1024 * 1024 * 1024,
+            file_hash: "ab".repeat(32),
+            price_wei: price_wei.to_string(),
+            sender_wallet: "0xseller".to_string(),
+            closed: None,
+        }
+    }
+
     #[test]
     fn test_pending_transfer_serialization() {
-        let transfer = PendingTransfer {
-            transfer_id: "tx-001".to_string(),
-            peer_id: "peer-abc".to_string(),
-            file_name: "test.txt".to_string(),
-            file_data: vec![1, 2, 3],
-            status: TransferStatus::Pending,
-        };
+        let transfer = PendingTransfer::from_offer("peer-abc".to_string(), offer("tx-001", "0"));
         let json = serde_json::to_string(&transfer).unwrap();
         assert!(json.contains("transferId"));
         assert!(json.contains("peerId"));
         assert!(json.contains("fileName"));
+        assert!(json.contains("fileHash"));
+        assert!(!json.contains("fileData"));
+        assert!(!json.contains("downloadRequestId"));
         let deserialized: PendingTransfer = serde_json::from_str(&json).unwrap();
         assert_eq!(deserialized.transfer_id, "tx-001");
-        assert_eq!(deserialized.file_data, vec![1, 2, 3]);
+        assert_eq!(deserialized.file_size, 2 * 1024 * 1024 * 1024);
+    }
+
+    #[test]
+    fn test_offer_price_defaults_to_free() {
+        let free = PendingTransfer::from_offer("peer".to_string(), offer("a", ""));
+        assert_eq!(free.price_wei, "0");
+        assert!(!free.is_paid());
+        let paid = PendingTransfer::from_offer("peer".to_string(), offer("b", "1000"));
+        assert!(paid.is_paid());
+    }
+
+    #[tokio::test]
+    async fn test_receive_offer_is_listed_as_pending() {
+        let service = FileTransferService::new();
+        service
+            .receive_offer(
+                EventSink::noop(),
+                "peer-abc".to_string(),
+                offer("tx-1", "0"),
+            )
+            .await;
+        let incoming = service.get_pending_incoming().await;
+        assert_eq!(incoming.len(), 1);
+        assert_eq!(incoming[0].peer_id, "peer-abc");
+        assert_eq!(incoming[0].status, TransferStatus::Pending);
+    }
+
+    #[tokio::test]
+    async fn test_accept_then_cancel_returns_download_request() {
+        let service = FileTransferService::new();
+        service
+            .receive_offer(EventSink::noop(), "peer".to_string(), offer("tx-1", "0"))
+            .await;
+
+        let accepted = service.accept_transfer("tx-1").await.unwrap();
+        assert_eq!(accepted.status, TransferStatus::Accepted);
+        assert_eq!(
+            service.accept_transfer("tx-1").await.unwrap_err(),
+            "Transfer is no longer pending"
+        );
+
+        service
+            .start_transfer_download("tx-1", "chiraldrop-1".to_string())
+            .await
+            .unwrap();
+        assert!(service.get_pending_incoming().await.is_empty());
+        assert_eq!(
+            service
+                .cancel_transfer("tx-1")
+                .await
+                .unwrap()
+                .download_request_id,
+            Some("chiraldrop-1".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_complete_transfer_download_marks_only_its_transfer() {
+        let service = FileTransferService::new();
+        for id in ["tx-1", "tx-2"] {
+            service
+                .receive_offer(EventSink::noop(), "peer".to_string(), offer(id, "0"))
+                .await;
+            service.accept_transfer(id).await.unwrap();
+            service
+                .start_transfer_download(id, format!("chiraldrop-{}", id))
+                .await
+                .unwrap();
+        }
+
+        assert!(service
+            .complete_transfer_download("some-other-download")
+            .await
+            .is_none());
+        let done = service
+            .complete_transfer_download("chiraldrop-tx-2")
+            .await
+            .unwrap();
+        assert_eq!(done.transfer_id, "tx-2");
+        assert_eq!(done.status, TransferStatus::Completed);
+        assert!(service
+            .complete_transfer_download("chiraldrop-tx-2")
+            .await
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_reset_transfer_allows_accepting_again() {
+        let service = FileTransferService::new();
+        service
+            .receive_offer(EventSink::noop(), "peer".to_string(), offer("tx-1", "0"))
+            .await;
+        service.accept_transfer("tx-1").await.unwrap();
+        service.reset_transfer("tx-1").await;
+        assert!(service.accept_transfer("tx-1").await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_record_outgoing_offer() {
+        let service = FileTransferService::new();
+        service
+            .record_outgoing_offer(EventSink::noop(), "peer".to_string(), offer("tx-1", "5"))
+            .await;
+        let outgoing = service.get_pending_outgoing().await;
+        assert_eq!(outgoing.len(), 1);
+        assert_eq!(outgoing[0].price_wei, "5");
+    }
+
+    #[test]
+    fn test_stage_outgoing_bytes_writes_a_fresh_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = stage_outgoing_bytes_in(dir.path(), b"bundle", SystemTime::now()).unwrap();
+        let second = stage_outgoing_bytes_in(dir.path(), b"bundle", SystemTime::now()).unwrap();
+        assert_ne!(first, second);
+        assert_eq!(std::fs::read(&first).unwrap(), b"bundle");
+    }
+
+    #[test]
+    fn test_stage_outgoing_bytes_prunes_expired_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let old = stage_outgoing_bytes_in(dir.path(), b"old", SystemTime::now()).unwrap();
+        let later = SystemTime::now() + OUTBOX_RETENTION + Duration::from_secs(60);
+        let fresh = stage_outgoing_bytes_in(dir.path(), b"new", later).unwrap();
+        assert!(!old.exists());
+        assert!(fresh.exists());
+    }
+
+    #[test]
+    fn test_reserve_outgoing_path_is_unused_and_prunes() {
+        let dir = tempfile::tempdir().unwrap();
+        let old = stage_outgoing_bytes_in(dir.path(), b"old", SystemTime::now()).unwrap();
+        let later = SystemTime::now() + OUTBOX_RETENTION + Duration::from_secs(60);
+        let reserved = reserve_outgoing_path_in(dir.path(), later).unwrap();
+        assert!(!old.exists());
+        assert!(!reserved.exists());
+        assert_eq!(reserved.parent(), Some(dir.path()));
+    }
+
+    #[test]
+    fn test_discard_staged_file_only_touches_the_outbox() {
+        let outbox = tempfile::tempdir().unwrap();
+        let elsewhere = tempfile::tempdir().unwrap();
+        let staged = stage_outgoing_bytes_in(outbox.path(), b"bundle", SystemTime::now()).unwrap();
+        let user_file = elsewhere.path().join("photo.jpg");
+        std::fs::write(&user_file, b"photo").unwrap();
+
+        discard_staged_file_in(outbox.path(), &user_file);
+        discard_staged_file_in(outbox.path(), &staged);
+        assert!(user_file.exists());
+        assert!(!staged.exists());
     }
 
     #[tokio::test]
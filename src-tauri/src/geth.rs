@@ -6,6 +6,7 @@
 //! genesis-version migration. Those were the three sources of "blocks
 //! regress on restart" bugs in the previous implementation and are gone.
 
+use crate::chain_index::{IndexQuery, Page};
 use crate::network;
 use crate::rpc_client;
 use flate2::read::GzDecoder;
@@ -867,16 +868,37 @@ impl GethProcess {
         .map(|_| ())
     }
 
-    /// Blocks mined by our coinbase in the last `max_blocks` heights,
-    /// newest first. One eth_getBlockByNumber per height. Capped at 500
-    /// so this doesn't become a thundering herd on long-lived chains.
-    pub async fn get_mined_blocks(&self, max_blocks: u64) -> Result<Vec<MinedBlock>, String> {
-        if !self.is_running() {
-            return Ok(Vec::new());
-        }
-        let coinbase = self.miner_address.as_deref().unwrap_or("").to_lowercase();
+    /// Blocks mined by `coinbase` (our own coinbase when `None`), newest
+    /// first. Served from the local chain index once it has caught up;
+    /// until then the last 500 heights are scanned, one
+    /// eth_getBlockByNumber each, and only while geth is running.
+    pub async fn get_mined_blocks(
+        &self,
+        coinbase: Option<&str>,
+        query: &IndexQuery,
+    ) -> Result<Page<MinedBlock>, String> {
+        let coinbase = coinbase
+            .or(self.miner_address.as_deref())
+            .unwrap_or("")
+            .trim()
+            .to_lowercase();
+        let empty = Page {
+            items: Vec::new(),
+            total: 0,
+        };
         if coinbase.is_empty() {
-            return Ok(Vec::new());
+            return Ok(empty);
+        }
+        {
+            let index = crate::chain_index::global().lock();
+            if index.caught_up() {
+                return Ok(index
+                    .mined_blocks(&coinbase, query)
+                    .map(|block| mined_block(block.number, block.timestamp, block.difficulty)));
+            }
+        }
+        if !self.is_running() {
+            return Ok(empty);
         }
         let endpoint = self.effective_rpc_endpoint();
         let head = match rpc_client::call(&endpoint, "eth_blockNumber", serde_json::json!([])).await
@@ -885,12 +907,12 @@ impl GethProcess {
             Err(_) => 0,
         };
         if head == 0 {
-            return Ok(Vec::new());
+            return Ok(empty);
         }
 
-        let scan = max_blocks.min(500);
+        let wanted = query.offset + query.page_size();
         let mut out = Vec::new();
-        for offset in 0..scan {
+        for offset in 0..500 {
             let Some(n) = head.checked_sub(offset) else {
                 break;
             };
@@ -913,18 +935,20 @@ impl GethProcess {
             {
                 continue;
             }
-            out.push(MinedBlock {
-                block_number: n,
-                timestamp: field_hex_u64(&block, "timestamp", "eth_getBlockByNumber")?,
-                reward_wei: "5000000000000000000".into(),
-                reward_chi: 5.0,
-                difficulty: field_hex_u64(&block, "difficulty", "eth_getBlockByNumber")?,
-            });
-            if out.len() as u64 >= max_blocks {
+            let timestamp = field_hex_u64(&block, "timestamp", "eth_getBlockByNumber")?;
+            if !query.matches(timestamp) {
+                continue;
+            }
+            out.push(mined_block(
+                n,
+                timestamp,
+                field_hex_u64(&block, "difficulty", "eth_getBlockByNumber")?,
+            ));
+            if out.len() >= wanted {
                 break;
             }
         }
-        Ok(out)
+        Ok(query.page(out, |block| block.timestamp))
     }
 
     // GPU mining lives in the `geth_gpu` module now. The Tauri command
@@ -941,6 +965,16 @@ impl Default for GethProcess {
 // Helpers
 // ============================================================================
 
+fn mined_block(block_number: u64, timestamp: u64, difficulty: u64) -> MinedBlock {
+    MinedBlock {
+        block_number,
+        timestamp,
+        reward_wei: "5000000000000000000".into(),
+        reward_chi: 5.0,
+        difficulty,
+    }
+}
+
 fn value_hex_str<'a>(value: &'a serde_json::Value, context: &str) -> Result<&'a str, String> {
     value
         .as_str()
@@ -1,6 +1,6 @@
 use axum::{
     extract::{Path, State},
-    http::StatusCode,
+    http::{HeaderMap, StatusCode},
     response::{IntoResponse, Response},
     routing::{delete, get, post},
     Json, Router,
@@ -16,6 +16,7 @@ use tower_http::cors::{Any, CorsLayer};
 use crate::chain_rpc_api;
 use crate::drive_api::{self, DriveState};
 use crate::hosting::{self, HostedSite, SiteFile};
+use crate::http_range;
 use crate::rating_api;
 use crate::rating_storage::RatingState;
 use crate::relay_share_proxy::{self, RelayShareRegistry};
@@ -213,21 +214,23 @@ async fn redirect_to_index(Path(site_id): Path<String>) -> Response {
 async fn serve_site_root(
     Path(site_id): Path<String>,
     State(state): State<Arc<HostingServerState>>,
+    headers: HeaderMap,
 ) -> Response {
-    serve_site_path_inner(&site_id, "index.html", &state).await
+    serve_site_path_inner(&site_id, "index.html", &state, &headers).await
 }
 
 /// GET /sites/{site_id}/*path  — serve any file within the site
 async fn serve_site_file(
     Path((site_id, file_path)): Path<(String, String)>,
     State(state): State<Arc<HostingServerState>>,
+    headers: HeaderMap,
 ) -> Response {
     let path = if file_path.is_empty() || file_path == "/" {
         "index.html"
     } else {
         &file_path
     };
-    serve_site_path_inner(&site_id, path, &state).await
+    serve_site_path_inner(&site_id, path, &state, &headers).await
 }
 
 /// Core file-serving logic with directory traversal protection.
@@ -235,6 +238,7 @@ async fn serve_site_path_inner(
     site_id: &str,
     requested_path: &str,
     state: &HostingServerState,
+    headers: &HeaderMap,
 ) -> Response {
     // Look up the site
     let sites = state.sites.read().await;
@@ -279,28 +283,20 @@ async fn serve_site_path_inner(
         return (StatusCode::FORBIDDEN, "Path traversal not allowed").into_response();
     }
 
-    // Read the file
-    let data = match tokio::fs::read(&canonical).await {
-        Ok(d) => d,
-        Err(_) => {
-            return (StatusCode::NOT_FOUND, "File not found").into_response();
-        }
-    };
-
     // Determine MIME type from extension
     let ext = canonical.extension().and_then(|e| e.to_str()).unwrap_or("");
-    let content_type = hosting::mime_from_extension(ext);
+    let file = http_range::FileResponse {
+        content_type: hosting::mime_from_extension(ext).to_string(),
+        path: canonical,
+        content_hash: None,
+        cache_control: "public, max-age=3600",
+        content_disposition: None,
+    };
 
-    (
-        StatusCode::OK,
-        [
-            ("Content-Type", content_type.to_string()),
-            ("Content-Length", data.len().to_string()),
-            ("Cache-Control", "public, max-age=3600".to_string()),
-        ],
-        data,
-    )
-        .into_response()
+    // Stream the file, honouring Range and conditional headers
+    http_range::serve_file(headers, file)
+        .await
+        .unwrap_or_else(|_| (StatusCode::NOT_FOUND, "File not found").into_response())
 }
 
 // ---------------------------------------------------------------------------
@@ -0,0 +1,742 @@
+//! HTTP over libp2p for relay shares and sites.
+//!
+//! Every node holds a circuit-relay reservation on its relay, so the relay
+//! can reach a sharer over that connection even when the sharer's local
+//! gateway (port 9419) can't be dialled from the internet. The relay sends
+//! `/drive/<token>/...` and `/sites/<id>/...` requests over
+//! `/chiral/http/1.0.0`; the sharer replays them against its local server
+//! and returns the response.
+//!
+//! Request-response carries one message each way, so response bodies are
+//! pulled in chunks: the head carries the first chunk and, if more follows,
+//! a stream id the relay passes to `Next` until the owner reports `done`.
+//! Pulling keeps the relay in control of the pace — nothing is read from
+//! the local server until the visitor has taken the previous chunk.
+
+use axum::body::Bytes;
+use futures_util::{Stream, StreamExt};
+use libp2p::{request_response, PeerId, StreamProtocol};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, oneshot, Mutex};
+
+use crate::metrics;
+use crate::relay_share_proxy;
+
+pub const HTTP_P2P_PROTOCOL: &str = "/chiral/http/1.0.0";
+
+/// Largest body slice carried by one response message.
+pub const HTTP_P2P_CHUNK_BYTES: usize = 256 * 1024;
+
+/// Request bodies travel inline in `Start`; anything larger is left to the
+/// tunnel or the direct proxy. Keeps the CBOR request under its 1 MiB cap.
+pub const MAX_HTTP_P2P_REQUEST_BODY_BYTES: usize = 256 * 1024;
+
+/// How long the relay waits for any single message round trip.
+pub const HTTP_P2P_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Response streams a sharer keeps open at once, across all relays.
+const MAX_OPEN_STREAMS: usize = 64;
+
+/// Open streams the relay hasn't pulled from for this long are dropped.
+const STREAM_IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HttpP2pRequest {
+    /// Replay a visitor request against the sharer's local server.
+    Start {
+        resource_type: String,
+        resource_id: String,
+        method: String,
+        /// Path and query, still percent-encoded, e.g. `/drive/tok/a.txt?v=2`.
+        path: String,
+        headers: Vec<(String, String)>,
+        body: Vec<u8>,
+    },
+    /// Pull the next chunk of an open response body.
+    Next { stream_id: u64 },
+    /// The visitor went away; drop the open response body.
+    Cancel { stream_id: u64 },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HttpP2pResponse {
+    /// Status, end-to-end headers and the first body chunk. `stream_id` is
+    /// set when the body continues.
+    Head {
+        status: u16,
+        headers: Vec<(String, String)>,
+        body: Vec<u8>,
+        stream_id: Option<u64>,
+    },
+    Chunk {
+        data: Vec<u8>,
+        done: bool,
+    },
+    Cancelled,
+    Error {
+        message: String,
+    },
+}
+
+impl HttpP2pRequest {
+    fn body_len(&self) -> usize {
+        match self {
+            HttpP2pRequest::Start { body, .. } => body.len(),
+            HttpP2pRequest::Next { .. } | HttpP2pRequest::Cancel { .. } => 0,
+        }
+    }
+}
+
+impl HttpP2pResponse {
+    fn body_len(&self) -> usize {
+        match self {
+            HttpP2pResponse::Head { body, .. } => body.len(),
+            HttpP2pResponse::Chunk { data, .. } => data.len(),
+            HttpP2pResponse::Cancelled | HttpP2pResponse::Error { .. } => 0,
+        }
+    }
+}
+
+/// Count HTTP body bytes carried over the protocol for `/metrics`.
+fn record_body_bytes(direction: metrics::Direction, len: usize) {
+    metrics::global().record_protocol_bytes(HTTP_P2P_PROTOCOL, direction, len as u64);
+}
+
+pub type HttpP2pBehaviour = request_response::cbor::Behaviour<HttpP2pRequest, HttpP2pResponse>;
+
+/// Build the behaviour; sharers answer (`Inbound`), relays ask (`Outbound`).
+pub fn behaviour(support: request_response::ProtocolSupport) -> HttpP2pBehaviour {
+    request_response::cbor::Behaviour::new(
+        [(StreamProtocol::new(HTTP_P2P_PROTOCOL), support)],
+        request_response::Config::default().with_request_timeout(HTTP_P2P_REQUEST_TIMEOUT),
+    )
+}
+
+// ---------------------------------------------------------------------------
+// Sharer side
+// ---------------------------------------------------------------------------
+
+type ServedBody = Pin<Box<dyn Stream<Item = Result<Bytes, String>> + Send>>;
+
+struct ServedStream {
+    peer: PeerId,
+    body: ServedBody,
+    leftover: Bytes,
+    last_used: Instant,
+}
+
+/// Answers relay requests for the shares and sites this node published.
+/// Only resources registered with `publish` are served, and only under
+/// their own `/drive/<token>` or `/sites/<id>` prefix.
+#[derive(Clone, Default)]
+pub struct HttpP2pServer {
+    /// "share:<token>" / "site:<id>" -> local origin, e.g. http://127.0.0.1:9419
+    resources: Arc<Mutex<HashMap<String, String>>>,
+    streams: Arc<Mutex<HashMap<u64, ServedStream>>>,
+    next_stream_id: Arc<AtomicU64>,
+}
+
+fn resource_key(resource_type: &str, resource_id: &str) -> String {
+    format!("{}:{}", resource_type, resource_id)
+}
+
+impl HttpP2pServer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub async fn publish(&self, resource_type: &str, resource_id: &str, local_origin: &str) {
+        self.resources.lock().await.insert(
+            resource_key(resource_type, resource_id),
+            local_origin.to_string(),
+        );
+    }
+
+    pub async fn unpublish(&self, resource_type: &str, resource_id: &str) {
+        self.resources
+            .lock()
+            .await
+            .remove(&resource_key(resource_type, resource_id));
+    }
+
+    pub async fn handle(&self, peer: PeerId, request: HttpP2pRequest) -> HttpP2pResponse {
+        record_body_bytes(metrics::Direction::In, request.body_len());
+        let result = match request {
+            HttpP2pRequest::Start {
+                resource_type,
+                resource_id,
+                method,
+                path,
+                headers,
+                body,
+            } => {
+                let origin = self
+                    .resources
+                    .lock()
+                    .await
+                    .get(&resource_key(&resource_type, &resource_id))
+                    .cloned();
+                match origin {
+                    Some(origin) => {
+                        let target = relay_share_proxy::scoped_origin_url(
+                            &origin,
+                            &resource_type,
+                            &resource_id,
+                            &path,
+                        );
+                        match target {
+                            Ok(url) => self.start(peer, url, &method, &headers, body).await,
+                            Err(e) => Err(e),
+                        }
+                    }
+                    None => Err(format!(
+                        "{}:{} is not published on this node",
+                        resource_type, resource_id
+                    )),
+                }
+            }
+            HttpP2pRequest::Next { stream_id } => self.next(peer, stream_id).await,
+            HttpP2pRequest::Cancel { stream_id } => {
+                let mut streams = self.streams.lock().await;
+                if streams.get(&stream_id).is_some_and(|s| s.peer == peer) {
+                    streams.remove(&stream_id);
+                }
+                Ok(HttpP2pResponse::Cancelled)
+            }
+        };
+        let response = result.unwrap_or_else(|message| HttpP2pResponse::Error { message });
+        record_body_bytes(metrics::Direction::Out, response.body_len());
+        response
+    }
+
+    async fn start(
+        &self,
+        peer: PeerId,
+        url: reqwest::Url,
+        method: &str,
+        headers: &[(String, String)],
+        body: Vec<u8>,
+    ) -> Result<HttpP2pResponse, String> {
+        if body.len() > MAX_HTTP_P2P_REQUEST_BODY_BYTES {
+            return Err("request body exceeds the libp2p limit".to_string());
+        }
+        let method = reqwest::Method::from_bytes(method.as_bytes())
+            .map_err(|e| format!("invalid method '{}': {}", method, e))?;
+        let client = reqwest::Client::builder()
+            // The path is already scoped to the resource; a redirect from the
+            // local server must reach the visitor, not be followed here.
+            .redirect(reqwest::redirect::Policy::none())
+            .connect_timeout(Duration::from_secs(10))
+            .build()
+            .map_err(|e| format!("local HTTP client could not be initialized: {}", e))?;
+        let mut request = client.request(method, url);
+        for (name, value) in headers {
+            if relay_share_proxy::is_forwardable_request_header(name) {
+                request = request.header(name.as_str(), value.as_str());
+            }
+        }
+        if !body.is_empty() {
+            request = request.body(body);
+        }
+        let response = request
+            .send()
+            .await
+            .map_err(|e| format!("local server request failed: {}", e))?;
+
+        let status = response.status().as_u16();
+        let headers = response_headers(response.headers());
+        let mut stream = ServedStream {
+            peer,
+            body: Box::pin(
+                response
+                    .bytes_stream()
+                    .map(|r| r.map_err(|e| e.to_string())),
+            ),
+            leftover: Bytes::new(),
+            last_used: Instant::now(),
+        };
+        let (body, done) = read_chunk(&mut stream, HTTP_P2P_CHUNK_BYTES).await?;
+        let stream_id = if done {
+            None
+        } else {
+            Some(self.open_stream(stream).await?)
+        };
+        Ok(HttpP2pResponse::Head {
+            status,
+            headers,
+            body,
+            stream_id,
+        })
+    }
+
+    async fn open_stream(&self, stream: ServedStream) -> Result<u64, String> {
+        let mut streams = self.streams.lock().await;
+        let now = Instant::now();
+        streams.retain(|_, s| now.duration_since(s.last_used) < STREAM_IDLE_TIMEOUT);
+        if streams.len() >= MAX_OPEN_STREAMS {
+            return Err("too many open response streams".to_string());
+        }
+        let id = self.next_stream_id.fetch_add(1, Ordering::Relaxed);
+        streams.insert(id, stream);
+        Ok(id)
+    }
+
+    async fn next(&self, peer: PeerId, stream_id: u64) -> Result<HttpP2pResponse, String> {
+        // Take the stream out while reading so the lock isn't held across
+        // the local server's response time.
+        let mut stream = {
+            let mut streams = self.streams.lock().await;
+            match streams.remove(&stream_id) {
+                Some(stream) if stream.peer == peer => stream,
+                Some(stream) => {
+                    streams.insert(stream_id, stream);
+                    return Err(format!("unknown response stream {}", stream_id));
+                }
+                None => return Err(format!("unknown response stream {}", stream_id)),
+            }
+        };
+        let (data, done) = read_chunk(&mut stream, HTTP_P2P_CHUNK_BYTES).await?;
+        if !done {
+            stream.last_used = Instant::now();
+            self.streams.lock().await.insert(stream_id, stream);
+        }
+        Ok(HttpP2pResponse::Chunk { data, done })
+    }
+
+    #[cfg(test)]
+    async fn open_stream_count(&self) -> usize {
+        self.streams.lock().await.len()
+    }
+}
+
+/// Read up to `limit` bytes; `true` once the body is exhausted.
+async fn read_chunk(stream: &mut ServedStream, limit: usize) -> Result<(Vec<u8>, bool), String> {
+    let mut out = Vec::new();
+    loop {
+        if !stream.leftover.is_empty() {
+            let take = stream.leftover.len().min(limit - out.len());
+            out.extend_from_slice(&stream.leftover.split_to(take));
+        }
+        if out.len() == limit {
+            return Ok((out, false));
+        }
+        match stream.body.next().await {
+            Some(Ok(bytes)) => stream.leftover = bytes,
+            Some(Err(e)) => return Err(format!("local server body failed: {}", e)),
+            None => return Ok((out, true)),
+        }
+    }
+}
+
+fn response_headers(headers: &reqwest::header::HeaderMap) -> Vec<(String, String)> {
+    headers
+        .iter()
+        .filter(|(name, _)| relay_share_proxy::is_forwardable_response_header(name.as_str()))
+        .filter_map(|(name, value)| {
+            value
+                .to_str()
+                .ok()
+                .map(|value| (name.as_str().to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+// ---------------------------------------------------------------------------
+// Relay side
+// ---------------------------------------------------------------------------
+
+/// One outbound request, handed from the HTTP gateway to the swarm task.
+pub struct HttpP2pCall {
+    pub peer: PeerId,
+    pub request: HttpP2pRequest,
+    pub reply: oneshot::Sender<Result<HttpP2pResponse, String>>,
+}
+
+/// Cheap handle the relay's HTTP handlers use to reach owners over the swarm.
+#[derive(Clone)]
+pub struct HttpP2pClient {
+    calls: mpsc::Sender<HttpP2pCall>,
+}
+
+impl HttpP2pClient {
+    /// The receiver must be drained by the task that owns the swarm, via
+    /// `HttpP2pPending::send`.
+    pub fn new(capacity: usize) -> (Self, mpsc::Receiver<HttpP2pCall>) {
+        let (calls, rx) = mpsc::channel(capacity);
+        (Self { calls }, rx)
+    }
+
+    pub async fn call(
+        &self,
+        peer: PeerId,
+        request: HttpP2pRequest,
+    ) -> Result<HttpP2pResponse, String> {
+        let (reply, rx) = oneshot::channel();
+        self.calls
+            .send(HttpP2pCall {
+                peer,
+                request,
+                reply,
+            })
+            .await
+            .map_err(|_| "libp2p swarm is not running".to_string())?;
+        rx.await
+            .map_err(|_| "libp2p swarm dropped the request".to_string())?
+    }
+
+    /// Best-effort `Cancel` that doesn't wait, for use from `Drop`.
+    pub fn cancel(&self, peer: PeerId, stream_id: u64) {
+        let (reply, _) = oneshot::channel();
+        let _ = self.calls.try_send(HttpP2pCall {
+            peer,
+            request: HttpP2pRequest::Cancel { stream_id },
+            reply,
+        });
+    }
+}
+
+/// Outbound requests awaiting a response, owned by the swarm task.
+#[derive(Default)]
+pub struct HttpP2pPending {
+    replies: HashMap<
+        request_response::OutboundRequestId,
+        oneshot::Sender<Result<HttpP2pResponse, String>>,
+    >,
+}
+
+impl HttpP2pPending {
+    pub fn send(&mut self, behaviour: &mut HttpP2pBehaviour, call: HttpP2pCall) {
+        record_body_bytes(metrics::Direction::Out, call.request.body_len());
+        let id = behaviour.send_request(&call.peer, call.request);
+        self.replies.insert(id, call.reply);
+    }
+
+    pub fn on_event(&mut self, event: request_response::Event<HttpP2pRequest, HttpP2pResponse>) {
+        match event {
+            request_response::Event::Message {
+                message:
+                    request_response::Message::Response {
+                        request_id,
+                        response,
+                    },
+                ..
+            } => {
+                record_body_bytes(metrics::Direction::In, response.body_len());
+                if let Some(reply) = self.replies.remove(&request_id) {
+                    let _ = reply.send(Ok(response));
+                }
+            }
+            request_response::Event::OutboundFailure {
+                peer,
+                request_id,
+                error,
+            } => {
+                if let Some(reply) = self.replies.remove(&request_id) {
+                    let _ = reply.send(Err(format!(
+                        "libp2p HTTP request to {} failed: {}",
+                        peer, error
+                    )));
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    fn served(chunks: Vec<Result<&'static [u8], &'static str>>) -> ServedStream {
+        let items = chunks
+            .into_iter()
+            .map(|c| c.map(Bytes::from_static).map_err(str::to_string))
+            .collect::<Vec<_>>();
+        ServedStream {
+            peer: PeerId::random(),
+            body: Box::pin(futures_util::stream::iter(items)),
+            leftover: Bytes::new(),
+            last_used: Instant::now(),
+        }
+    }
+
+    /// Local server that answers each connection with `body` after
+    /// recording the raw request.
+    async fn local_origin(body: Vec<u8>) -> (String, mpsc::UnboundedReceiver<String>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (tx, rx) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut buf = vec![0u8; 8192];
+                let n = socket.read(&mut buf).await.unwrap_or(0);
+                let _ = tx.send(String::from_utf8_lossy(&buf[..n]).to_string());
+                let head = format!(
+                    "HTTP/1.1 201 Created\r\nContent-Length: {}\r\nX-Origin: local\r\nConnection: close\r\n\r\n",
+                    body.len()
+                );
+                let _ = socket.write_all(head.as_bytes()).await;
+                let _ = socket.write_all(&body).await;
+            }
+        });
+        (format!("http://{}", addr), rx)
+    }
+
+    fn start(resource_type: &str, resource_id: &str, method: &str, path: &str) -> HttpP2pRequest {
+        HttpP2pRequest::Start {
+            resource_type: resource_type.to_string(),
+            resource_id: resource_id.to_string(),
+            method: method.to_string(),
+            path: path.to_string(),
+            headers: vec![
+                ("x-visitor".to_string(), "1".to_string()),
+                ("connection".to_string(), "keep-alive".to_string()),
+            ],
+            body: Vec::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn read_chunk_splits_and_joins_source_chunks() {
+        let mut stream = served(vec![Ok(b"abc"), Ok(b"defgh"), Ok(b"i")]);
+
+        assert_eq!(
+            read_chunk(&mut stream, 4).await.unwrap(),
+            (b"abcd".to_vec(), false)
+        );
+        assert_eq!(
+            read_chunk(&mut stream, 4).await.unwrap(),
+            (b"efgh".to_vec(), false)
+        );
+        assert_eq!(
+            read_chunk(&mut stream, 4).await.unwrap(),
+            (b"i".to_vec(), true)
+        );
+    }
+
+    #[tokio::test]
+    async fn read_chunk_reports_body_errors() {
+        let mut stream = served(vec![Ok(b"ab"), Err("reset")]);
+        let err = read_chunk(&mut stream, 8).await.unwrap_err();
+        assert!(err.contains("reset"));
+    }
+
+    #[tokio::test]
+    async fn server_rejects_unpublished_resources() {
+        let server = HttpP2pServer::new();
+        let response = server
+            .handle(PeerId::random(), start("share", "tok", "GET", "/drive/tok"))
+            .await;
+        assert!(matches!(
+            response,
+            HttpP2pResponse::Error { message } if message.contains("not published")
+        ));
+    }
+
+    #[tokio::test]
+    async fn server_keeps_requests_inside_the_published_resource() {
+        let server = HttpP2pServer::new();
+        server.publish("share", "tok", "http://127.0.0.1:9").await;
+
+        for path in [
+            "/drive/other/file",
+            "/api/drive/items",
+            "/drive/tok/../../api",
+        ] {
+            let response = server
+                .handle(PeerId::random(), start("share", "tok", "GET", path))
+                .await;
+            assert!(
+                matches!(response, HttpP2pResponse::Error { .. }),
+                "{path} should be refused"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn server_replays_request_and_returns_small_body_in_head() {
+        let (origin, mut requests) = local_origin(b"hello".to_vec()).await;
+        let server = HttpP2pServer::new();
+        server.publish("site", "s1", &origin).await;
+
+        let mut request = start("site", "s1", "POST", "/sites/s1/form?x=1");
+        if let HttpP2pRequest::Start { body, .. } = &mut request {
+            *body = b"a=b".to_vec();
+        }
+        let response = server.handle(PeerId::random(), request).await;
+
+        let HttpP2pResponse::Head {
+            status,
+            headers,
+            body,
+            stream_id,
+        } = response
+        else {
+            panic!("expected head, got {:?}", response);
+        };
+        assert_eq!(status, 201);
+        assert_eq!(body, b"hello");
+        assert_eq!(stream_id, None);
+        assert!(headers.contains(&("x-origin".to_string(), "local".to_string())));
+        assert!(!headers.iter().any(|(k, _)| k == "connection"));
+
+        let raw = requests.recv().await.unwrap();
+        assert!(raw.starts_with("POST /sites/s1/form?x=1 HTTP/1.1"), "{raw}");
+        assert!(raw.contains("x-visitor: 1"));
+        assert!(!raw.to_ascii_lowercase().contains("keep-alive"));
+        assert!(raw.ends_with("a=b"));
+        assert_eq!(server.open_stream_count().await, 0);
+    }
+
+    #[tokio::test]
+    async fn server_streams_large_bodies_to_the_opening_peer_only() {
+        let payload = (0..HTTP_P2P_CHUNK_BYTES * 2 + 10)
+            .map(|i| (i % 251) as u8)
+            .collect::<Vec<_>>();
+        let (origin, _requests) = local_origin(payload.clone()).await;
+        let server = HttpP2pServer::new();
+        server.publish("share", "tok", &origin).await;
+        let relay = PeerId::random();
+
+        let HttpP2pResponse::Head {
+            body, stream_id, ..
+        } = server
+            .handle(relay, start("share", "tok", "GET", "/drive/tok/big.bin"))
+            .await
+        else {
+            panic!("expected head");
+        };
+        let stream_id = stream_id.expect("large body should stay open");
+        let mut received = body;
+
+        let stranger = server
+            .handle(PeerId::random(), HttpP2pRequest::Next { stream_id })
+            .await;
+        assert!(matches!(stranger, HttpP2pResponse::Error { .. }));
+
+        loop {
+            match server
+                .handle(relay, HttpP2pRequest::Next { stream_id })
+                .await
+            {
+                HttpP2pResponse::Chunk { data, done } => {
+                    assert!(data.len() <= HTTP_P2P_CHUNK_BYTES);
+                    received.extend_from_slice(&data);
+                    if done {
+                        break;
+                    }
+                }
+                other => panic!("unexpected {:?}", other),
+            }
+        }
+        assert_eq!(received, payload);
+        assert_eq!(server.open_stream_count().await, 0);
+    }
+
+    #[tokio::test]
+    async fn cancel_drops_open_stream() {
+        let server = HttpP2pServer::new();
+        let relay = PeerId::random();
+        let id = server
+            .open_stream(ServedStream {
+                peer: relay,
+                ..served(vec![Ok(b"x")])
+            })
+            .await
+            .unwrap();
+
+        let response = server
+            .handle(PeerId::random(), HttpP2pRequest::Cancel { stream_id: id })
+            .await;
+        assert_eq!(response, HttpP2pResponse::Cancelled);
+        assert_eq!(server.open_stream_count().await, 1);
+
+        server
+            .handle(relay, HttpP2pRequest::Cancel { stream_id: id })
+            .await;
+        assert_eq!(server.open_stream_count().await, 0);
+    }
+
+    #[tokio::test]
+    async fn open_streams_are_capped_and_idle_ones_expire() {
+        let server = HttpP2pServer::new();
+        for _ in 0..MAX_OPEN_STREAMS {
+            server.open_stream(served(vec![])).await.unwrap();
+        }
+        assert!(server.open_stream(served(vec![])).await.is_err());
+
+        for stream in server.streams.lock().await.values_mut() {
+            stream.last_used = Instant::now() - STREAM_IDLE_TIMEOUT;
+        }
+        server.open_stream(served(vec![])).await.unwrap();
+        assert_eq!(server.open_stream_count().await, 1);
+    }
+
+    #[tokio::test]
+    async fn client_call_fails_when_swarm_is_gone() {
+        let (client, rx) = HttpP2pClient::new(1);
+        drop(rx);
+        let err = client
+            .call(PeerId::random(), HttpP2pRequest::Next { stream_id: 1 })
+            .await
+            .unwrap_err();
+        assert!(err.contains("not running"));
+    }
+
+    #[tokio::test]
+    async fn client_call_returns_swarm_reply() {
+        let (client, mut rx) = HttpP2pClient::new(1);
+        tokio::spawn(async move {
+            let call = rx.recv().await.unwrap();
+            assert_eq!(call.request, HttpP2pRequest::Next { stream_id: 7 });
+            let _ = call.reply.send(Ok(HttpP2pResponse::Cancelled));
+        });
+        let response = client
+            .call(PeerId::random(), HttpP2pRequest::Next { stream_id: 7 })
+            .await
+            .unwrap();
+        assert_eq!(response, HttpP2pResponse::Cancelled);
+    }
+
+    #[test]
+    fn messages_roundtrip_through_cbor() {
+        let messages = vec![
+            start("share", "tok", "GET", "/drive/tok"),
+            HttpP2pRequest::Cancel { stream_id: 3 },
+        ];
+        for message in messages {
+            let bytes = cbor4ii::serde::to_vec(Vec::new(), &message).unwrap();
+            let back: HttpP2pRequest = cbor4ii::serde::from_slice(&bytes).unwrap();
+            assert_eq!(back, message);
+        }
+        let head = HttpP2pResponse::Head {
+            status: 200,
+            headers: vec![("content-type".to_string(), "text/plain".to_string())],
+            body: b"hi".to_vec(),
+            stream_id: Some(9),
+        };
+        let bytes = cbor4ii::serde::to_vec(Vec::new(), &head).unwrap();
+        let back: HttpP2pResponse = cbor4ii::serde::from_slice(&bytes).unwrap();
+        assert_eq!(back, head);
+    }
+
+    #[test]
+    fn full_request_body_fits_the_cbor_request_limit() {
+        let request = HttpP2pRequest::Start {
+            resource_type: "share".to_string(),
+            resource_id: "tok".to_string(),
+            method: "PUT".to_string(),
+            path: "/drive/tok/upload".to_string(),
+            headers: Vec::new(),
+            body: vec![0xff; MAX_HTTP_P2P_REQUEST_BODY_BYTES],
+        };
+        let bytes = cbor4ii::serde::to_vec(Vec::new(), &request).unwrap();
+        assert!(bytes.len() < 1024 * 1024);
+    }
+}
@@ -0,0 +1,485 @@
+//! Serving files over HTTP with range and conditional request support.
+//!
+//! Used by Drive downloads, Drive share links, CDN sites, and locally
+//! hosted sites. Bodies are streamed from disk, so a response never holds
+//! more than one read buffer of the file in memory.
+//!
+//! - `ETag` is the quoted content hash. Drive items use their Merkle root;
+//!   site files are hashed with SHA-256 on first request and cached until
+//!   their size or modification time changes.
+//! - `If-None-Match` that matches the ETag gets `304 Not Modified`.
+//! - A single `Range: bytes=...` gets `206 Partial Content` (or `416` when
+//!   it starts past the end). Multi-range requests get the whole file,
+//!   which RFC 9110 allows.
+//! - `If-Range` only honours a range when it carries our current strong
+//!   ETag, so a resumed download never splices two versions of a file.
+
+use axum::{
+    body::Body,
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+/// Bytes read from disk per body chunk.
+const READ_BUFFER_SIZE: usize = 64 * 1024;
+/// Cached site-file hashes are dropped wholesale past this many entries.
+const HASH_CACHE_LIMIT: usize = 4096;
+
+/// What to serve and how to label it.
+pub struct FileResponse {
+    pub path: PathBuf,
+    pub content_type: String,
+    /// Hex content hash for the ETag. Hashed from disk when absent.
+    pub content_hash: Option<String>,
+    pub cache_control: &'static str,
+    pub content_disposition: Option<String>,
+}
+
+/// The outcome of parsing a `Range` header against a file length.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RangeRequest {
+    /// No usable range: serve the whole file.
+    Full,
+    /// Inclusive byte range within the file.
+    Partial { start: u64, end: u64 },
+    /// The range starts past the end of the file.
+    Unsatisfiable,
+}
+
+/// Parse a `Range` header for a file of `len` bytes. Malformed headers,
+/// other units, and multi-range requests fall back to `Full`.
+pub fn parse_range(value: &str, len: u64) -> RangeRequest {
+    let Some(spec) = value.trim().strip_prefix("bytes=") else {
+        return RangeRequest::Full;
+    };
+    if spec.contains(',') {
+        return RangeRequest::Full;
+    }
+    let Some((first, last)) = spec.trim().split_once('-') else {
+        return RangeRequest::Full;
+    };
+    let (first, last) = (first.trim(), last.trim());
+
+    if first.is_empty() {
+        // Suffix range: the last N bytes.
+        let Ok(suffix) = last.parse::<u64>() else {
+            return RangeRequest::Full;
+        };
+        if suffix == 0 || len == 0 {
+            return RangeRequest::Unsatisfiable;
+        }
+        return RangeRequest::Partial {
+            start: len.saturating_sub(suffix),
+            end: len - 1,
+        };
+    }
+
+    let Ok(start) = first.parse::<u64>() else {
+        return RangeRequest::Full;
+    };
+    let end = if last.is_empty() {
+        len.saturating_sub(1)
+    } else {
+        match last.parse::<u64>() {
+            Ok(end) if end >= start => end.min(len.saturating_sub(1)),
+            _ => return RangeRequest::Full,
+        }
+    };
+    if start >= len {
+        return RangeRequest::Unsatisfiable;
+    }
+    RangeRequest::Partial { start, end }
+}
+
+fn opaque_tag(tag: &str) -> &str {
+    tag.trim().trim_start_matches("W/")
+}
+
+/// `If-None-Match` uses weak comparison: `W/` prefixes are ignored.
+pub fn if_none_match_matches(value: &str, etag: &str) -> bool {
+    value
+        .split(',')
+        .map(str::trim)
+        .any(|tag| tag == "*" || opaque_tag(tag) == opaque_tag(etag))
+}
+
+/// `If-Range` uses strong comparison, and a date never matches because we
+/// don't send `Last-Modified`.
+pub fn if_range_matches(value: &str, etag: &str) -> bool {
+    let value = value.trim();
+    !value.starts_with("W/") && value == etag
+}
+
+/// Whether the request resumes a transfer part-way through, as opposed to
+/// starting one. Used to avoid counting a resumed download twice.
+pub fn is_resumed_request(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::RANGE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().strip_prefix("bytes="))
+        .is_some_and(|spec| !spec.trim_start().starts_with("0-"))
+}
+
+fn file_stamp(metadata: &std::fs::Metadata) -> (u64, Option<SystemTime>) {
+    (metadata.len(), metadata.modified().ok())
+}
+
+type HashCacheEntry = ((u64, Option<SystemTime>), String);
+
+static HASH_CACHE: Lazy<Mutex<HashMap<PathBuf, HashCacheEntry>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+fn sha256_file(path: &Path) -> std::io::Result<String> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher)?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// SHA-256 of a file, cached by path until its size or mtime changes.
+async fn cached_file_hash(path: &Path, metadata: &std::fs::Metadata) -> std::io::Result<String> {
+    let stamp = file_stamp(metadata);
+    if let Some((cached_stamp, hash)) = HASH_CACHE.lock().get(path) {
+        if *cached_stamp == stamp {
+            return Ok(hash.clone());
+        }
+    }
+    let owned = path.to_path_buf();
+    let hash = tokio::task::spawn_blocking(move || sha256_file(&owned))
+        .await
+        .map_err(std::io::Error::other)??;
+    let mut cache = HASH_CACHE.lock();
+    if cache.len() >= HASH_CACHE_LIMIT {
+        cache.clear();
+    }
+    cache.insert(path.to_path_buf(), (stamp, hash.clone()));
+    Ok(hash)
+}
+
+/// Stream `len` bytes of `file`, which is already positioned at the start.
+fn stream_body(file: tokio::fs::File, len: u64) -> Body {
+    let stream = futures::stream::unfold((file, len), |(mut file, remaining)| async move {
+        if remaining == 0 {
+            return None;
+        }
+        let mut buf = vec![0u8; (remaining as usize).min(READ_BUFFER_SIZE)];
+        match file.read(&mut buf).await {
+            Ok(0) => Some((
+                Err(std::io::Error::new(
+                    std::io::ErrorKind::UnexpectedEof,
+                    "file shrank while being served",
+                )),
+                (file, 0),
+            )),
+            Ok(n) => {
+                buf.truncate(n);
+                Some((Ok(buf), (file, remaining - n as u64)))
+            }
+            Err(e) => Some((Err(e), (file, 0))),
+        }
+    });
+    Body::from_stream(stream)
+}
+
+fn set_header(headers: &mut HeaderMap, name: header::HeaderName, value: &str) {
+    if let Ok(value) = HeaderValue::from_str(value) {
+        headers.insert(name, value);
+    }
+}
+
+/// Serve `file` honouring `Range`, `If-Range` and `If-None-Match`.
+/// Errors only when the file cannot be opened or read; callers map that to
+/// their own 404.
+pub async fn serve_file(
+    request_headers: &HeaderMap,
+    file: FileResponse,
+) -> std::io::Result<Response> {
+    let mut handle = tokio::fs::File::open(&file.path).await?;
+    let metadata = handle.metadata().await?;
+    let len = metadata.len();
+    let hash = match file.content_hash.filter(|h| !h.trim().is_empty()) {
+        Some(hash) => hash,
+        None => cached_file_hash(&file.path, &metadata).await?,
+    };
+    let etag = format!("\"{}\"", hash);
+
+    let request_header = |name: header::HeaderName| {
+        request_headers
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string)
+    };
+
+    let mut headers = HeaderMap::new();
+    set_header(&mut headers, header::ETAG, &etag);
+    set_header(&mut headers, header::CACHE_CONTROL, file.cache_control);
+    set_header(&mut headers, header::ACCEPT_RANGES, "bytes");
+
+    if request_header(header::IF_NONE_MATCH).is_some_and(|v| if_none_match_matches(&v, &etag)) {
+        return Ok((StatusCode::NOT_MODIFIED, headers).into_response());
+    }
+
+    let range_allowed =
+        request_header(header::IF_RANGE).is_none_or(|v| if_range_matches(&v, &etag));
+    let range = match request_header(header::RANGE) {
+        Some(value) if range_allowed => parse_range(&value, len),
+        _ => RangeRequest::Full,
+    };
+    if range == RangeRequest::Unsatisfiable {
+        set_header(
+            &mut headers,
+            header::CONTENT_RANGE,
+            &format!("bytes */{}", len),
+        );
+        return Ok((StatusCode::RANGE_NOT_SATISFIABLE, headers).into_response());
+    }
+
+    set_header(&mut headers, header::CONTENT_TYPE, &file.content_type);
+    if let Some(disposition) = &file.content_disposition {
+        set_header(&mut headers, header::CONTENT_DISPOSITION, disposition);
+    }
+    let (status, start, body_len) = match range {
+        RangeRequest::Partial { start, end } => {
+            set_header(
+                &mut headers,
+                header::CONTENT_RANGE,
+                &format!("bytes {}-{}/{}", start, end, len),
+            );
+            (StatusCode::PARTIAL_CONTENT, start, end - start + 1)
+        }
+        _ => (StatusCode::OK, 0, len),
+    };
+    if start > 0 {
+        handle.seek(SeekFrom::Start(start)).await?;
+    }
+    set_header(&mut headers, header::CONTENT_LENGTH, &body_len.to_string());
+    Ok((status, headers, stream_body(handle, body_len)).into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_range_forms() {
+        assert_eq!(
+            parse_range("bytes=0-99", 1000),
+            RangeRequest::Partial { start: 0, end: 99 }
+        );
+        assert_eq!(
+            parse_range("bytes=500-", 1000),
+            RangeRequest::Partial {
+                start: 500,
+                end: 999
+            }
+        );
+        assert_eq!(
+            parse_range("bytes=-100", 1000),
+            RangeRequest::Partial {
+                start: 900,
+                end: 999
+            }
+        );
+        // End past EOF is clamped; a suffix longer than the file is the
+        // whole file.
+        assert_eq!(
+            parse_range("bytes=990-5000", 1000),
+            RangeRequest::Partial {
+                start: 990,
+                end: 999
+            }
+        );
+        assert_eq!(
+            parse_range("bytes=-5000", 1000),
+            RangeRequest::Partial { start: 0, end: 999 }
+        );
+    }
+
+    #[test]
+    fn parse_range_unsatisfiable() {
+        assert_eq!(
+            parse_range("bytes=1000-", 1000),
+            RangeRequest::Unsatisfiable
+        );
+        assert_eq!(parse_range("bytes=-0", 1000), RangeRequest::Unsatisfiable);
+        assert_eq!(parse_range("bytes=0-", 0), RangeRequest::Unsatisfiable);
+    }
+
+    #[test]
+    fn parse_range_falls_back_to_full() {
+        for value in [
+            "items=0-1",
+            "bytes=0-1,5-6",
+            "bytes=abc-",
+            "bytes=5-2",
+            "bytes=",
+            "bytes=1",
+        ] {
+            assert_eq!(parse_range(value, 1000), RangeRequest::Full, "{}", value);
+        }
+    }
+
+    #[test]
+    fn if_none_match_comparison_is_weak() {
+        let etag = "\"abc\"";
+        assert!(if_none_match_matches("\"abc\"", etag));
+        assert!(if_none_match_matches("W/\"abc\"", etag));
+        assert!(if_none_match_matches("\"x\", \"abc\"", etag));
+        assert!(if_none_match_matches("*", etag));
+        assert!(!if_none_match_matches("\"abd\"", etag));
+    }
+
+    #[test]
+    fn if_range_comparison_is_strong() {
+        let etag = "\"abc\"";
+        assert!(if_range_matches("\"abc\"", etag));
+        assert!(!if_range_matches("W/\"abc\"", etag));
+        assert!(!if_range_matches("Wed, 21 Oct 2015 07:28:00 GMT", etag));
+    }
+
+    #[test]
+    fn resumed_request_detection() {
+        let mut headers = HeaderMap::new();
+        assert!(!is_resumed_request(&headers));
+        headers.insert(header::RANGE, HeaderValue::from_static("bytes=0-"));
+        assert!(!is_resumed_request(&headers));
+        headers.insert(header::RANGE, HeaderValue::from_static("bytes=4096-"));
+        assert!(is_resumed_request(&headers));
+    }
+
+    async fn serve(dir: &Path, headers: HeaderMap, hash: Option<&str>) -> Response {
+        serve_file(
+            &headers,
+            FileResponse {
+                path: dir.join("video.mp4"),
+                content_type: "video/mp4".into(),
+                content_hash: hash.map(str::to_string),
+                cache_control: "public, max-age=3600",
+                content_disposition: None,
+            },
+        )
+        .await
+        .unwrap()
+    }
+
+    async fn body_bytes(response: Response) -> Vec<u8> {
+        axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap()
+            .to_vec()
+    }
+
+    fn test_file() -> (tempfile::TempDir, Vec<u8>) {
+        let dir = tempfile::tempdir().unwrap();
+        let data: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+        std::fs::write(dir.path().join("video.mp4"), &data).unwrap();
+        (dir, data)
+    }
+
+    #[tokio::test]
+    async fn serves_whole_file_with_etag() {
+        let (dir, data) = test_file();
+        let response = serve(dir.path(), HeaderMap::new(), Some("deadbeef")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let headers = response.headers().clone();
+        assert_eq!(headers[header::ETAG], "\"deadbeef\"");
+        assert_eq!(headers[header::ACCEPT_RANGES], "bytes");
+        assert_eq!(headers[header::CONTENT_LENGTH], data.len().to_string());
+        assert_eq!(headers[header::CACHE_CONTROL], "public, max-age=3600");
+        assert_eq!(body_bytes(response).await, data);
+    }
+
+    #[tokio::test]
+    async fn serves_partial_content() {
+        let (dir, data) = test_file();
+        let mut headers = HeaderMap::new();
+        headers.insert(header::RANGE, HeaderValue::from_static("bytes=100000-"));
+        let response = serve(dir.path(), headers, Some("deadbeef")).await;
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(
+            response.headers()[header::CONTENT_RANGE],
+            "bytes 100000-199999/200000"
+        );
+        assert_eq!(response.headers()[header::CONTENT_LENGTH], "100000");
+        assert_eq!(body_bytes(response).await, data[100000..]);
+    }
+
+    #[tokio::test]
+    async fn range_past_end_is_416() {
+        let (dir, _) = test_file();
+        let mut headers = HeaderMap::new();
+        headers.insert(header::RANGE, HeaderValue::from_static("bytes=300000-"));
+        let response = serve(dir.path(), headers, Some("deadbeef")).await;
+        assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(response.headers()[header::CONTENT_RANGE], "bytes */200000");
+    }
+
+    #[tokio::test]
+    async fn stale_if_range_serves_whole_file() {
+        let (dir, data) = test_file();
+        let mut headers = HeaderMap::new();
+        headers.insert(header::RANGE, HeaderValue::from_static("bytes=10-19"));
+        headers.insert(header::IF_RANGE, HeaderValue::from_static("\"old\""));
+        let response = serve(dir.path(), headers.clone(), Some("deadbeef")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(body_bytes(response).await.len(), data.len());
+
+        headers.insert(header::IF_RANGE, HeaderValue::from_static("\"deadbeef\""));
+        let response = serve(dir.path(), headers, Some("deadbeef")).await;
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(body_bytes(response).await, data[10..20]);
+    }
+
+    #[tokio::test]
+    async fn matching_if_none_match_is_304() {
+        let (dir, _) = test_file();
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::IF_NONE_MATCH,
+            HeaderValue::from_static("\"deadbeef\""),
+        );
+        let response = serve(dir.path(), headers, Some("deadbeef")).await;
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(response.headers()[header::ETAG], "\"deadbeef\"");
+        assert!(body_bytes(response).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn hashes_file_when_no_hash_is_known() {
+        let (dir, data) = test_file();
+        let response = serve(dir.path(), HeaderMap::new(), None).await;
+        let expected = format!("\"{}\"", hex::encode(Sha256::digest(&data)));
+        assert_eq!(response.headers()[header::ETAG], expected.as_str());
+
+        // A changed file gets a new ETag even though the path is cached.
+        std::fs::write(dir.path().join("video.mp4"), b"replaced").unwrap();
+        let response = serve(dir.path(), HeaderMap::new(), None).await;
+        let expected = format!("\"{}\"", hex::encode(Sha256::digest(b"replaced")));
+        assert_eq!(response.headers()[header::ETAG], expected.as_str());
+    }
+
+    #[tokio::test]
+    async fn missing_file_is_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let result = serve_file(
+            &HeaderMap::new(),
+            FileResponse {
+                path: dir.path().join("nope"),
+                content_type: "text/plain".into(),
+                content_hash: None,
+                cache_control: "no-cache",
+                content_disposition: None,
+            },
+        )
+        .await;
+        assert!(result.is_err());
+    }
+}
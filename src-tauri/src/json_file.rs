@@ -0,0 +1,163 @@
+//! Small JSON files saved whole: the voucher books, the seeder
+//! performance book, the chain index and pending-download records. Key
+//! files share the temp naming through `keystore::write_private_file`.
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Numbers temp files so concurrent writes of one path never share one.
+static TEMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Write `json` to `path`, creating its directory. Write-then-rename, so a
+/// crash mid-write leaves the previous file.
+pub fn write_json_atomic(path: &Path, json: &str) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| format!("create {}: {}", dir.display(), e))?;
+    }
+    let tmp = temp_path(path);
+    std::fs::write(&tmp, json).map_err(|e| format!("write {}: {}", tmp.display(), e))?;
+    std::fs::rename(&tmp, path).map_err(|e| {
+        let _ = std::fs::remove_file(&tmp);
+        format!("rename {} to {}: {}", tmp.display(), path.display(), e)
+    })
+}
+
+/// A temp file next to `path` that no other write in any process uses.
+pub(crate) fn temp_path(path: &Path) -> PathBuf {
+    let file_name = path
+        .file_name()
+        .and_then(|s| s.to_str())
+        .unwrap_or("data.json");
+    path.with_file_name(format!(
+        "{}.{}.{}.tmp",
+        file_name,
+        std::process::id(),
+        TEMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ))
+}
+
+/// `write_json_atomic` for a value, as compact JSON.
+pub fn save_json_atomic<T: Serialize>(path: &Path, value: &T) -> Result<(), String> {
+    let json = serde_json::to_string(value).map_err(|e| e.to_string())?;
+    write_json_atomic(path, &json)
+}
+
+/// Read the JSON at `path`; a missing or unreadable file gives the default.
+pub fn load_json_or_default<T: DeserializeOwned + Default>(path: &Path) -> T {
+    std::fs::read_to_string(path)
+        .ok()
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
+
+/// Read the JSON at `path`. A missing file gives the default; a file that
+/// does not parse is renamed aside first, so saving the default never
+/// overwrites it. `tag` prefixes the log lines.
+pub fn load_json_or_quarantine<T: DeserializeOwned + Default>(path: &Path, tag: &str) -> T {
+    let data = match std::fs::read(path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return T::default(),
+        Err(e) => {
+            eprintln!(
+                "[{}] Failed to read {}: {}; starting empty",
+                tag,
+                path.display(),
+                e
+            );
+            return T::default();
+        }
+    };
+    match serde_json::from_slice(&data) {
+        Ok(value) => value,
+        Err(e) => {
+            match quarantine(path) {
+                Ok(aside) => eprintln!(
+                    "[{}] Malformed {} quarantined at {}: {}",
+                    tag,
+                    path.display(),
+                    aside.display(),
+                    e
+                ),
+                Err(quarantine_err) => eprintln!(
+                    "[{}] Malformed {} could not be quarantined: {}; starting empty",
+                    tag,
+                    path.display(),
+                    quarantine_err
+                ),
+            }
+            T::default()
+        }
+    }
+}
+
+fn quarantine(path: &Path) -> Result<PathBuf, String> {
+    let aside = quarantine_path(path);
+    std::fs::rename(path, &aside)
+        .map_err(|e| format!("rename {} to {}: {}", path.display(), aside.display(), e))?;
+    Ok(aside)
+}
+
+fn quarantine_path(path: &Path) -> PathBuf {
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let file_name = path
+        .file_name()
+        .and_then(|s| s.to_str())
+        .unwrap_or("data.json");
+    for attempt in 0..1000 {
+        let suffix = if attempt == 0 {
+            format!("malformed-{timestamp}")
+        } else {
+            format!("malformed-{timestamp}-{attempt}")
+        };
+        let candidate = path.with_file_name(format!("{file_name}.{suffix}"));
+        if !candidate.exists() {
+            return candidate;
+        }
+    }
+    path.with_file_name(format!("{file_name}.malformed-{timestamp}-overflow"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_helpers_write_atomically_and_default_on_bad_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nested").join("book.json");
+        assert!(load_json_or_default::<Vec<u32>>(&path).is_empty());
+
+        save_json_atomic(&path, &vec![1u32, 2]).unwrap();
+        assert_eq!(load_json_or_default::<Vec<u32>>(&path), vec![1, 2]);
+        let names: Vec<_> = std::fs::read_dir(path.parent().unwrap())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(names, vec!["book.json"], "no temp file left behind");
+
+        std::fs::write(&path, "{not json").unwrap();
+        assert!(load_json_or_default::<Vec<u32>>(&path).is_empty());
+    }
+
+    #[test]
+    fn malformed_files_are_quarantined_not_overwritten() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("book.json");
+        assert!(load_json_or_quarantine::<Vec<u32>>(&path, "Test").is_empty());
+
+        std::fs::write(&path, "{not json").unwrap();
+        assert!(load_json_or_quarantine::<Vec<u32>>(&path, "Test").is_empty());
+        assert!(!path.exists());
+        let aside: Vec<_> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        assert_eq!(aside.len(), 1);
+        assert_eq!(std::fs::read_to_string(&aside[0]).unwrap(), "{not json");
+    }
+}
@@ -0,0 +1,823 @@
+//! Disk-backed Kademlia record store.
+//!
+//! `MemoryStore` loses every record and provider entry a node holds when it
+//! restarts, so a restarted bootstrap or relay node serves nothing until
+//! publishers' republish timers heal the network. [`PersistentStore`] keeps
+//! the same in-memory indexes (it wraps a `MemoryStore`) and mirrors every
+//! change to an append-only journal under the network data dir. The journal
+//! is replayed on start, with expired entries dropped, and rewritten as a
+//! compact snapshot once it grows well past the live data. Kademlia calls
+//! the store from its poll loop, so all journal I/O is handed to a writer
+//! thread that batches appends and writes snapshots.
+//!
+//! Expiry times are monotonic `Instant`s in memory and Unix milliseconds on
+//! disk. Nodes choose the backend with `CHIRAL_DHT_STORE=memory|disk`.
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use libp2p::kad::store::{self, MemoryStore, MemoryStoreConfig, RecordStore};
+use libp2p::kad::{ProviderRecord, Record, RecordKey};
+use libp2p::PeerId;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::mpsc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Selects the record store backend; read by `create_swarm` and the relay.
+pub const DHT_STORE_ENV: &str = "CHIRAL_DHT_STORE";
+/// Directory under the network data dir holding the journal.
+pub const DHT_STORE_DIR: &str = "dht_store";
+const JOURNAL_FILE: &str = "records.log";
+/// Journal entries allowed beyond twice the live entries before compacting.
+const COMPACT_SLACK_OPS: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StoreMode {
+    Memory,
+    Disk,
+}
+
+impl FromStr for StoreMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "" | "memory" => Ok(Self::Memory),
+            "disk" => Ok(Self::Disk),
+            other => Err(format!(
+                "{} must be 'memory' or 'disk', got '{}'",
+                DHT_STORE_ENV, other
+            )),
+        }
+    }
+}
+
+/// The backend chosen by `CHIRAL_DHT_STORE`; memory when unset.
+pub fn store_mode_from_env() -> Result<StoreMode, String> {
+    match std::env::var(DHT_STORE_ENV) {
+        Ok(raw) => raw.parse(),
+        Err(std::env::VarError::NotPresent) => Ok(StoreMode::Memory),
+        Err(std::env::VarError::NotUnicode(_)) => {
+            Err(format!("{} must be valid UTF-8", DHT_STORE_ENV))
+        }
+    }
+}
+
+/// Quotas for a [`PersistentStore`]. The per-entry limits are passed to
+/// the wrapped `MemoryStore`; `max_total_value_bytes` caps the sum of all
+/// record values, which also bounds the compacted journal.
+#[derive(Debug, Clone)]
+pub struct PersistentStoreConfig {
+    pub max_records: usize,
+    pub max_value_bytes: usize,
+    pub max_providers_per_key: usize,
+    pub max_provided_keys: usize,
+    pub max_total_value_bytes: usize,
+}
+
+impl Default for PersistentStoreConfig {
+    fn default() -> Self {
+        let memory = MemoryStoreConfig::default();
+        Self {
+            max_records: memory.max_records,
+            max_value_bytes: memory.max_value_bytes,
+            max_providers_per_key: memory.max_providers_per_key,
+            max_provided_keys: memory.max_provided_keys,
+            max_total_value_bytes: 32 * 1024 * 1024,
+        }
+    }
+}
+
+impl PersistentStoreConfig {
+    fn memory(&self) -> MemoryStoreConfig {
+        MemoryStoreConfig {
+            max_records: self.max_records,
+            max_value_bytes: self.max_value_bytes,
+            max_providers_per_key: self.max_providers_per_key,
+            max_provided_keys: self.max_provided_keys,
+        }
+    }
+}
+
+/// One journal line.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "camelCase")]
+enum JournalOp {
+    #[serde(rename_all = "camelCase")]
+    Put {
+        key: String,
+        value: String,
+        publisher: Option<String>,
+        expires_at: Option<u64>,
+    },
+    Remove {
+        key: String,
+    },
+    #[serde(rename_all = "camelCase")]
+    AddProvider {
+        key: String,
+        provider: String,
+        addresses: Vec<String>,
+        expires_at: Option<u64>,
+    },
+    RemoveProvider {
+        key: String,
+        provider: String,
+    },
+}
+
+impl JournalOp {
+    fn put(record: &Record) -> Self {
+        Self::Put {
+            key: BASE64.encode(record.key.as_ref()),
+            value: BASE64.encode(&record.value),
+            publisher: record.publisher.map(|p| p.to_string()),
+            expires_at: record.expires.map(instant_to_unix_ms),
+        }
+    }
+
+    fn add_provider(record: &ProviderRecord) -> Self {
+        Self::AddProvider {
+            key: BASE64.encode(record.key.as_ref()),
+            provider: record.provider.to_string(),
+            addresses: record.addresses.iter().map(|a| a.to_string()).collect(),
+            expires_at: record.expires.map(instant_to_unix_ms),
+        }
+    }
+}
+
+fn unix_now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+fn instant_to_unix_ms(at: Instant) -> u64 {
+    let now = Instant::now();
+    let unix_now = unix_now_ms();
+    if at >= now {
+        unix_now.saturating_add((at - now).as_millis() as u64)
+    } else {
+        unix_now.saturating_sub((now - at).as_millis() as u64)
+    }
+}
+
+/// `None` once the time has passed — the entry is expired.
+fn unix_ms_to_instant(unix_ms: u64, unix_now: u64) -> Option<Instant> {
+    (unix_ms > unix_now).then(|| Instant::now() + Duration::from_millis(unix_ms - unix_now))
+}
+
+/// Work for the journal writer thread, applied in order.
+enum JournalWrite {
+    Append(JournalOp),
+    /// Replace the journal with these live entries.
+    Snapshot {
+        records: Vec<Record>,
+        providers: Vec<ProviderRecord>,
+    },
+    /// Acknowledged once everything queued before it is on disk.
+    Flush(mpsc::Sender<()>),
+}
+
+/// Sending half of the journal writer thread.
+struct JournalWriter {
+    tx: Option<mpsc::Sender<JournalWrite>>,
+    thread: Option<std::thread::JoinHandle<()>>,
+}
+
+impl JournalWriter {
+    fn spawn(path: PathBuf) -> Result<Self, String> {
+        let (tx, rx) = mpsc::channel();
+        let thread = std::thread::Builder::new()
+            .name("dht-store-journal".to_string())
+            .spawn(move || run_journal_writer(&path, rx))
+            .map_err(|e| format!("Failed to start DHT store writer: {}", e))?;
+        Ok(Self {
+            tx: Some(tx),
+            thread: Some(thread),
+        })
+    }
+
+    fn send(&self, write: JournalWrite) {
+        if let Some(tx) = self.tx.as_ref() {
+            let _ = tx.send(write);
+        }
+    }
+}
+
+impl Drop for JournalWriter {
+    /// Let the writer drain what is queued before the store goes away.
+    fn drop(&mut self) {
+        self.tx.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Apply queued writes until the store is dropped. Appends that queue up
+/// while the thread is busy go out in one write.
+fn run_journal_writer(path: &Path, rx: mpsc::Receiver<JournalWrite>) {
+    while let Ok(first) = rx.recv() {
+        let mut pending = String::new();
+        let mut acks = Vec::new();
+        for write in std::iter::once(first).chain(rx.try_iter()) {
+            match write {
+                JournalWrite::Append(op) => match serde_json::to_string(&op) {
+                    Ok(line) => {
+                        pending.push_str(&line);
+                        pending.push('\n');
+                    }
+                    Err(e) => println!("[DHT-STORE] Failed to encode journal entry: {}", e),
+                },
+                JournalWrite::Snapshot { records, providers } => {
+                    // Appends queued before the snapshot are part of it.
+                    pending.clear();
+                    let ops = records
+                        .iter()
+                        .map(JournalOp::put)
+                        .chain(providers.iter().map(JournalOp::add_provider));
+                    if let Err(e) = write_snapshot(path, ops) {
+                        println!("[DHT-STORE] Compaction failed: {}", e);
+                    }
+                }
+                JournalWrite::Flush(ack) => acks.push(ack),
+            }
+        }
+        if !pending.is_empty() {
+            let appended = std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .and_then(|mut file| file.write_all(pending.as_bytes()));
+            if let Err(e) = appended {
+                println!("[DHT-STORE] Failed to append to {:?}: {}", path, e);
+            }
+        }
+        for ack in acks {
+            let _ = ack.send(());
+        }
+    }
+}
+
+/// Write `ops` to a temporary file and move it over the journal.
+fn write_snapshot(path: &Path, ops: impl Iterator<Item = JournalOp>) -> Result<(), String> {
+    let tmp = path.with_extension("log.tmp");
+    let mut contents = String::new();
+    for op in ops {
+        let line = serde_json::to_string(&op).map_err(|e| e.to_string())?;
+        contents.push_str(&line);
+        contents.push('\n');
+    }
+    std::fs::File::create(&tmp)
+        .and_then(|mut file| {
+            file.write_all(contents.as_bytes())?;
+            file.sync_all()
+        })
+        .map_err(|e| format!("Failed to write DHT store snapshot {:?}: {}", tmp, e))?;
+    std::fs::rename(&tmp, path)
+        .map_err(|e| format!("Failed to replace DHT store {:?}: {}", path, e))
+}
+
+/// A `MemoryStore` mirrored to an append-only journal. Built with
+/// [`PersistentStore::in_memory`] it behaves exactly like `MemoryStore`, so
+/// the swarm has one store type whichever backend is selected.
+pub struct PersistentStore {
+    inner: MemoryStore,
+    config: PersistentStoreConfig,
+    writer: Option<JournalWriter>,
+    /// Keys with at least one provider; `MemoryStore` can't list them.
+    provider_keys: HashSet<RecordKey>,
+    value_bytes: usize,
+    /// Live records and provider records, in the same units as
+    /// `journal_ops` so the compaction check is cheap and honest.
+    live_records: usize,
+    live_providers: usize,
+    journal_ops: usize,
+}
+
+impl PersistentStore {
+    pub fn in_memory(local_id: PeerId, config: PersistentStoreConfig) -> Self {
+        Self {
+            inner: MemoryStore::with_config(local_id, config.memory()),
+            config,
+            writer: None,
+            provider_keys: HashSet::new(),
+            value_bytes: 0,
+            live_records: 0,
+            live_providers: 0,
+            journal_ops: 0,
+        }
+    }
+
+    /// Load the journal in `dir` (creating it if needed) and compact it.
+    pub fn open(
+        local_id: PeerId,
+        dir: &Path,
+        config: PersistentStoreConfig,
+    ) -> Result<Self, String> {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create DHT store dir {:?}: {}", dir, e))?;
+        let mut store = Self::in_memory(local_id, config);
+        let path = dir.join(JOURNAL_FILE);
+        if path.exists() {
+            let file = std::fs::File::open(&path)
+                .map_err(|e| format!("Failed to open DHT store {:?}: {}", path, e))?;
+            let unix_now = unix_now_ms();
+            let mut skipped = 0usize;
+            for line in BufReader::new(file).lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+                // A crash mid-append leaves at most one torn trailing line.
+                match serde_json::from_str::<JournalOp>(&line) {
+                    Ok(op) => store.apply(op, unix_now),
+                    Err(_) => skipped += 1,
+                }
+            }
+            if skipped > 0 {
+                println!("[DHT-STORE] Skipped {} unreadable journal lines", skipped);
+            }
+        }
+        // Nothing serves from the store yet, so the opening snapshot is
+        // written here and a failure can still fall back to memory.
+        let (records, providers) = store.live_entries();
+        store.journal_ops = records.len() + providers.len();
+        write_snapshot(
+            &path,
+            records
+                .iter()
+                .map(JournalOp::put)
+                .chain(providers.iter().map(JournalOp::add_provider)),
+        )?;
+        store.writer = Some(JournalWriter::spawn(path)?);
+        println!(
+            "[DHT-STORE] Loaded {} records and {} provider keys from {:?}",
+            store.live_records,
+            store.provider_keys.len(),
+            dir
+        );
+        Ok(store)
+    }
+
+    /// Replay one journal entry without journaling it again. Entries that
+    /// are expired, malformed, or over quota are dropped.
+    fn apply(&mut self, op: JournalOp, unix_now: u64) {
+        let decode = |key: &str| BASE64.decode(key).ok().map(RecordKey::from);
+        match op {
+            JournalOp::Put {
+                key,
+                value,
+                publisher,
+                expires_at,
+            } => {
+                let (Some(key), Ok(value)) = (decode(&key), BASE64.decode(value)) else {
+                    return;
+                };
+                let expires = match expires_at {
+                    Some(at) => match unix_ms_to_instant(at, unix_now) {
+                        Some(instant) => Some(instant),
+                        None => {
+                            self.remove_record(&key);
+                            return;
+                        }
+                    },
+                    None => None,
+                };
+                let record = Record {
+                    key,
+                    value,
+                    publisher: publisher.and_then(|p| PeerId::from_str(&p).ok()),
+                    expires,
+                };
+                let _ = self.put_record(record);
+            }
+            JournalOp::Remove { key } => {
+                if let Some(key) = decode(&key) {
+                    self.remove_record(&key);
+                }
+            }
+            JournalOp::AddProvider {
+                key,
+                provider,
+                addresses,
+                expires_at,
+            } => {
+                let (Some(key), Ok(provider)) = (decode(&key), PeerId::from_str(&provider)) else {
+                    return;
+                };
+                let expires = match expires_at {
+                    Some(at) => match unix_ms_to_instant(at, unix_now) {
+                        Some(instant) => Some(instant),
+                        None => {
+                            self.drop_provider(&key, &provider);
+                            return;
+                        }
+                    },
+                    None => None,
+                };
+                let _ = self.insert_provider(ProviderRecord {
+                    key,
+                    provider,
+                    expires,
+                    addresses: addresses.iter().filter_map(|a| a.parse().ok()).collect(),
+                });
+            }
+            JournalOp::RemoveProvider { key, provider } => {
+                if let (Some(key), Ok(provider)) = (decode(&key), PeerId::from_str(&provider)) {
+                    self.drop_provider(&key, &provider);
+                }
+            }
+        }
+    }
+
+    fn put_record(&mut self, record: Record) -> store::Result<()> {
+        let replaced = self.inner.get(&record.key).map(|r| r.value.len());
+        let total = self.value_bytes - replaced.unwrap_or(0) + record.value.len();
+        if total > self.config.max_total_value_bytes {
+            return Err(store::Error::MaxRecords);
+        }
+        self.inner.put(record)?;
+        self.value_bytes = total;
+        if replaced.is_none() {
+            self.live_records += 1;
+        }
+        Ok(())
+    }
+
+    fn remove_record(&mut self, key: &RecordKey) -> bool {
+        let Some(len) = self.inner.get(key).map(|r| r.value.len()) else {
+            return false;
+        };
+        self.inner.remove(key);
+        self.value_bytes -= len;
+        self.live_records -= 1;
+        true
+    }
+
+    fn insert_provider(&mut self, record: ProviderRecord) -> store::Result<()> {
+        let key = record.key.clone();
+        let before = self.inner.providers(&key).len();
+        self.inner.add_provider(record)?;
+        // Re-adding a provider updates it in place, and a full key may
+        // evict a farther provider instead of growing.
+        self.live_providers += self.inner.providers(&key).len() - before;
+        self.provider_keys.insert(key);
+        Ok(())
+    }
+
+    /// Returns whether `provider` was listed for `key`.
+    fn drop_provider(&mut self, key: &RecordKey, provider: &PeerId) -> bool {
+        let providers = self.inner.providers(key);
+        if !providers.iter().any(|p| p.provider == *provider) {
+            return false;
+        }
+        self.inner.remove_provider(key, provider);
+        self.live_providers -= 1;
+        if providers.len() == 1 {
+            self.provider_keys.remove(key);
+        }
+        true
+    }
+
+    /// Queue one entry for the journal, compacting once the journal is
+    /// mostly dead weight. Disk errors are logged by the writer; the
+    /// in-memory store stays authoritative.
+    fn journal(&mut self, op: JournalOp) {
+        let Some(writer) = self.writer.as_ref() else {
+            return;
+        };
+        writer.send(JournalWrite::Append(op));
+        self.journal_ops += 1;
+        let live = self.live_records + self.live_providers;
+        if self.journal_ops > 2 * live + COMPACT_SLACK_OPS {
+            self.compact();
+        }
+    }
+
+    /// Drop expired entries and have the writer replace the journal with a
+    /// snapshot of what is left.
+    pub fn compact(&mut self) {
+        if self.writer.is_none() {
+            return;
+        }
+        let (records, providers) = self.live_entries();
+        self.journal_ops = records.len() + providers.len();
+        if let Some(writer) = self.writer.as_ref() {
+            writer.send(JournalWrite::Snapshot { records, providers });
+        }
+    }
+
+    /// Block until every queued journal write is on disk.
+    pub fn flush(&self) {
+        let Some(writer) = self.writer.as_ref() else {
+            return;
+        };
+        let (ack_tx, ack_rx) = mpsc::channel();
+        writer.send(JournalWrite::Flush(ack_tx));
+        let _ = ack_rx.recv();
+    }
+
+    /// Remove expired entries and return copies of the rest.
+    fn live_entries(&mut self) -> (Vec<Record>, Vec<ProviderRecord>) {
+        let now = Instant::now();
+        let expired: Vec<RecordKey> = self
+            .inner
+            .records()
+            .filter(|r| r.is_expired(now))
+            .map(|r| r.key.clone())
+            .collect();
+        for key in &expired {
+            self.remove_record(key);
+        }
+        let records: Vec<Record> = self.inner.records().map(|r| r.into_owned()).collect();
+        let mut providers = Vec::new();
+        let provider_keys: Vec<RecordKey> = self.provider_keys.iter().cloned().collect();
+        for key in provider_keys {
+            for provider in self.inner.providers(&key) {
+                if provider.is_expired(now) {
+                    self.drop_provider(&key, &provider.provider);
+                } else {
+                    providers.push(provider);
+                }
+            }
+        }
+        (records, providers)
+    }
+
+    /// Bytes of record values currently held.
+    pub fn value_bytes(&self) -> usize {
+        self.value_bytes
+    }
+}
+
+impl RecordStore for PersistentStore {
+    type RecordsIter<'a> = <MemoryStore as RecordStore>::RecordsIter<'a>;
+    type ProvidedIter<'a> = <MemoryStore as RecordStore>::ProvidedIter<'a>;
+
+    fn get(&self, k: &RecordKey) -> Option<Cow<'_, Record>> {
+        self.inner.get(k)
+    }
+
+    fn put(&mut self, r: Record) -> store::Result<()> {
+        let op = JournalOp::put(&r);
+        self.put_record(r)?;
+        self.journal(op);
+        Ok(())
+    }
+
+    fn remove(&mut self, k: &RecordKey) {
+        if self.remove_record(k) {
+            self.journal(JournalOp::Remove {
+                key: BASE64.encode(k.as_ref()),
+            });
+        }
+    }
+
+    fn records(&self) -> Self::RecordsIter<'_> {
+        self.inner.records()
+    }
+
+    fn add_provider(&mut self, record: ProviderRecord) -> store::Result<()> {
+        let op = JournalOp::add_provider(&record);
+        self.insert_provider(record)?;
+        self.journal(op);
+        Ok(())
+    }
+
+    fn providers(&self, key: &RecordKey) -> Vec<ProviderRecord> {
+        self.inner.providers(key)
+    }
+
+    fn provided(&self) -> Self::ProvidedIter<'_> {
+        self.inner.provided()
+    }
+
+    fn remove_provider(&mut self, k: &RecordKey, p: &PeerId) {
+        if self.drop_provider(k, p) {
+            self.journal(JournalOp::RemoveProvider {
+                key: BASE64.encode(k.as_ref()),
+                provider: p.to_string(),
+            });
+        }
+    }
+}
+
+/// Build the store selected by `mode`, falling back to memory (with a log
+/// line) when the journal can't be opened.
+pub fn build_store(mode: StoreMode, local_id: PeerId, dir: &Path) -> PersistentStore {
+    let config = PersistentStoreConfig::default();
+    match mode {
+        StoreMode::Memory => PersistentStore::in_memory(local_id, config),
+        StoreMode::Disk => {
+            PersistentStore::open(local_id, dir, config.clone()).unwrap_or_else(|e| {
+                println!("[DHT-STORE] {}; falling back to the in-memory store", e);
+                PersistentStore::in_memory(local_id, config)
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libp2p::Multiaddr;
+
+    fn record(key: &str, value: &[u8], ttl: Duration) -> Record {
+        Record {
+            key: RecordKey::new(&key),
+            value: value.to_vec(),
+            publisher: None,
+            expires: Some(Instant::now() + ttl),
+        }
+    }
+
+    fn provider(key: &str, peer: PeerId, ttl: Duration) -> ProviderRecord {
+        ProviderRecord {
+            key: RecordKey::new(&key),
+            provider: peer,
+            expires: Some(Instant::now() + ttl),
+            addresses: vec!["/ip4/10.0.0.1/tcp/4001".parse::<Multiaddr>().unwrap()],
+        }
+    }
+
+    #[test]
+    fn store_mode_parses_names() {
+        assert_eq!("disk".parse::<StoreMode>(), Ok(StoreMode::Disk));
+        assert_eq!(" Memory ".parse::<StoreMode>(), Ok(StoreMode::Memory));
+        assert_eq!("".parse::<StoreMode>(), Ok(StoreMode::Memory));
+        assert!("sqlite"
+            .parse::<StoreMode>()
+            .unwrap_err()
+            .contains("sqlite"));
+    }
+
+    #[test]
+    fn records_and_providers_survive_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let local = PeerId::random();
+        let remote = PeerId::random();
+        let hour = Duration::from_secs(3600);
+        {
+            let mut store = PersistentStore::open(local, dir.path(), Default::default()).unwrap();
+            store.put(record("a", b"one", hour)).unwrap();
+            store.put(record("b", b"two", hour)).unwrap();
+            store.put(record("a", b"uno", hour)).unwrap();
+            store.remove(&RecordKey::new(&"b"));
+            store.add_provider(provider("file", remote, hour)).unwrap();
+            store.add_provider(provider("file", local, hour)).unwrap();
+            store.remove_provider(&RecordKey::new(&"file"), &local);
+        }
+
+        let store = PersistentStore::open(local, dir.path(), Default::default()).unwrap();
+        assert_eq!(store.get(&RecordKey::new(&"a")).unwrap().value, b"uno");
+        assert!(store.get(&RecordKey::new(&"b")).is_none());
+        let providers = store.providers(&RecordKey::new(&"file"));
+        assert_eq!(providers.len(), 1);
+        assert_eq!(providers[0].provider, remote);
+        assert_eq!(providers[0].addresses.len(), 1);
+        assert_eq!(store.value_bytes(), 3);
+    }
+
+    #[test]
+    fn expired_entries_are_dropped_on_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let local = PeerId::random();
+        {
+            let mut store = PersistentStore::open(local, dir.path(), Default::default()).unwrap();
+            store
+                .put(record("short", b"x", Duration::from_millis(1)))
+                .unwrap();
+            store
+                .put(record("long", b"y", Duration::from_secs(3600)))
+                .unwrap();
+            store
+                .add_provider(provider("p", PeerId::random(), Duration::from_millis(1)))
+                .unwrap();
+        }
+        std::thread::sleep(Duration::from_millis(20));
+
+        let store = PersistentStore::open(local, dir.path(), Default::default()).unwrap();
+        assert!(store.get(&RecordKey::new(&"short")).is_none());
+        assert!(store.get(&RecordKey::new(&"long")).is_some());
+        assert!(store.providers(&RecordKey::new(&"p")).is_empty());
+    }
+
+    #[test]
+    fn total_value_quota_rejects_oversized_puts() {
+        let config = PersistentStoreConfig {
+            max_total_value_bytes: 8,
+            ..Default::default()
+        };
+        let mut store = PersistentStore::in_memory(PeerId::random(), config);
+        let hour = Duration::from_secs(3600);
+        store.put(record("a", b"12345", hour)).unwrap();
+        assert!(store.put(record("b", b"12345", hour)).is_err());
+        // Replacing a value only counts the difference.
+        store.put(record("a", b"12345678", hour)).unwrap();
+        assert_eq!(store.value_bytes(), 8);
+        store.remove(&RecordKey::new(&"a"));
+        store.put(record("b", b"12345", hour)).unwrap();
+    }
+
+    #[test]
+    fn compaction_rewrites_the_journal_to_live_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let local = PeerId::random();
+        let hour = Duration::from_secs(3600);
+        let mut store = PersistentStore::open(local, dir.path(), Default::default()).unwrap();
+        for i in 0..50 {
+            store
+                .put(record("churn", format!("v{}", i).as_bytes(), hour))
+                .unwrap();
+        }
+        let journal = dir.path().join(JOURNAL_FILE);
+        store.flush();
+        assert_eq!(
+            std::fs::read_to_string(&journal).unwrap().lines().count(),
+            50
+        );
+
+        store.compact();
+        store.flush();
+        let contents = std::fs::read_to_string(&journal).unwrap();
+        assert_eq!(contents.lines().count(), 1);
+        assert!(contents.contains(&BASE64.encode(b"v49")));
+    }
+
+    #[test]
+    fn compaction_stays_amortised_with_many_providers_per_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let local = PeerId::random();
+        let hour = Duration::from_secs(3600);
+        let mut store = PersistentStore::open(local, dir.path(), Default::default()).unwrap();
+        let per_key = store.config.max_providers_per_key;
+        let keys = (COMPACT_SLACK_OPS / per_key) + 10;
+        for k in 0..keys {
+            for _ in 0..per_key {
+                store
+                    .add_provider(provider(&format!("file{}", k), PeerId::random(), hour))
+                    .unwrap();
+            }
+        }
+        assert_eq!(store.live_providers, keys * per_key);
+        store.compact();
+        for i in 0..10 {
+            store
+                .put(record("churn", format!("v{}", i).as_bytes(), hour))
+                .unwrap();
+        }
+        store.flush();
+
+        // The puts were appended to the snapshot, not each followed by one.
+        let journal = std::fs::read_to_string(dir.path().join(JOURNAL_FILE)).unwrap();
+        assert_eq!(journal.lines().count(), keys * per_key + 10);
+    }
+
+    #[test]
+    fn removing_an_unknown_provider_is_not_journaled() {
+        let dir = tempfile::tempdir().unwrap();
+        let local = PeerId::random();
+        let mut store = PersistentStore::open(local, dir.path(), Default::default()).unwrap();
+        let key = RecordKey::new(&"file");
+        store
+            .add_provider(provider("file", local, Duration::from_secs(3600)))
+            .unwrap();
+        for _ in 0..10 {
+            store.remove_provider(&key, &PeerId::random());
+        }
+        store.remove_provider(&key, &local);
+        store.remove_provider(&key, &local);
+        store.flush();
+
+        let journal = std::fs::read_to_string(dir.path().join(JOURNAL_FILE)).unwrap();
+        assert_eq!(journal.lines().count(), 2, "one add and one remove");
+        assert!(store.providers(&key).is_empty());
+    }
+
+    #[test]
+    fn torn_trailing_line_is_ignored() {
+        let dir = tempfile::tempdir().unwrap();
+        let local = PeerId::random();
+        {
+            let mut store = PersistentStore::open(local, dir.path(), Default::default()).unwrap();
+            store
+                .put(record("a", b"one", Duration::from_secs(3600)))
+                .unwrap();
+        }
+        let journal = dir.path().join(JOURNAL_FILE);
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&journal)
+            .unwrap();
+        write!(file, "{{\"op\":\"put\",\"key\":").unwrap();
+        drop(file);
+
+        let store = PersistentStore::open(local, dir.path(), Default::default()).unwrap();
+        assert_eq!(store.get(&RecordKey::new(&"a")).unwrap().value, b"one");
+    }
+}
@@ -0,0 +1,597 @@
+//! Encrypted wallet keystores (Web3 Secret Storage, version 3).
+//!
+//! The same JSON format geth and most Ethereum wallets write: the private
+//! key is encrypted with AES-128-CTR under a key stretched from the
+//! passphrase with scrypt (or PBKDF2-HMAC-SHA256 when importing), and a
+//! keccak MAC over the second half of the derived key and the ciphertext
+//! detects a wrong passphrase before anything is decrypted.
+//!
+//! Headless nodes used to keep the key as plaintext hex on disk. Those
+//! files are still read, and [`migrate_plaintext_key_file`] rewrites one in
+//! place as a keystore.
+
+use aes::Aes128;
+use ctr::cipher::{KeyIvInit, StreamCipher};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use tiny_keccak::{Hasher, Keccak};
+
+type Aes128Ctr = ctr::Ctr128BE<Aes128>;
+
+/// Environment variable the CLI and daemon read the passphrase from when
+/// no passphrase file is given.
+pub const PASSPHRASE_ENV: &str = "CHIRAL_WALLET_PASSPHRASE";
+
+const CIPHER: &str = "aes-128-ctr";
+const DKLEN: u32 = 32;
+
+/// geth's "standard" scrypt cost (N = 2^18, r = 8, p = 1).
+const SCRYPT_LOG_N: u8 = 18;
+const SCRYPT_R: u32 = 8;
+const SCRYPT_P: u32 = 1;
+
+/// Upper bounds on KDF cost accepted from a keystore file, so a crafted
+/// file cannot make an unlock allocate gigabytes or spin for minutes.
+/// scrypt needs `128 * r * n` bytes; the cap is what geth's standard
+/// parameters use.
+const MAX_SCRYPT_LOG_N: u8 = 20;
+const MAX_SCRYPT_R: u32 = 32;
+const MAX_SCRYPT_P: u32 = 8;
+const MAX_SCRYPT_MEMORY: u64 = 256 * 1024 * 1024;
+const MAX_PBKDF2_ROUNDS: u32 = 10_000_000;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Keystore {
+    pub version: u32,
+    pub id: String,
+    /// Address without the `0x` prefix, as geth writes it.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub address: String,
+    #[serde(alias = "Crypto")]
+    pub crypto: KeystoreCrypto,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeystoreCrypto {
+    pub cipher: String,
+    pub cipherparams: CipherParams,
+    pub ciphertext: String,
+    pub kdf: String,
+    pub kdfparams: KdfParams,
+    pub mac: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CipherParams {
+    pub iv: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum KdfParams {
+    Scrypt {
+        dklen: u32,
+        n: u32,
+        r: u32,
+        p: u32,
+        salt: String,
+    },
+    Pbkdf2 {
+        dklen: u32,
+        c: u32,
+        prf: String,
+        salt: String,
+    },
+}
+
+impl Keystore {
+    /// The `0x`-prefixed address recorded in the keystore, if any.
+    pub fn address(&self) -> Option<String> {
+        if self.address.is_empty() {
+            None
+        } else {
+            Some(format!(
+                "0x{}",
+                self.address.trim_start_matches("0x").to_lowercase()
+            ))
+        }
+    }
+}
+
+fn keccak256(data: &[&[u8]]) -> [u8; 32] {
+    let mut keccak = Keccak::v256();
+    for part in data {
+        keccak.update(part);
+    }
+    let mut out = [0u8; 32];
+    keccak.finalize(&mut out);
+    out
+}
+
+fn random_bytes<const N: usize>() -> [u8; N] {
+    let mut bytes = [0u8; N];
+    rand::rngs::OsRng.fill_bytes(&mut bytes);
+    bytes
+}
+
+fn derive_key(params: &KdfParams, passphrase: &str) -> Result<[u8; 32], String> {
+    let mut key = [0u8; 32];
+    match params {
+        KdfParams::Scrypt {
+            dklen,
+            n,
+            r,
+            p,
+            salt,
+        } => {
+            if *dklen != DKLEN {
+                return Err(format!("unsupported keystore dklen {}", dklen));
+            }
+            if !n.is_power_of_two() || *n < 2 {
+                return Err(format!("scrypt n must be a power of two, got {}", n));
+            }
+            let log_n = n.trailing_zeros() as u8;
+            if log_n > MAX_SCRYPT_LOG_N
+                || *r > MAX_SCRYPT_R
+                || *p > MAX_SCRYPT_P
+                || 128 * u64::from(*r) * u64::from(*n) > MAX_SCRYPT_MEMORY
+            {
+                return Err("keystore scrypt parameters are too expensive".to_string());
+            }
+            let salt = hex::decode(salt).map_err(|e| format!("invalid keystore salt: {}", e))?;
+            let params = scrypt::Params::new(log_n, *r, *p, key.len())
+                .map_err(|e| format!("invalid scrypt parameters: {}", e))?;
+            scrypt::scrypt(passphrase.as_bytes(), &salt, &params, &mut key)
+                .map_err(|e| format!("scrypt failed: {}", e))?;
+        }
+        KdfParams::Pbkdf2 {
+            dklen,
+            c,
+            prf,
+            salt,
+        } => {
+            if *dklen != DKLEN {
+                return Err(format!("unsupported keystore dklen {}", dklen));
+            }
+            if prf != "hmac-sha256" {
+                return Err(format!("unsupported keystore prf {}", prf));
+            }
+            if *c == 0 || *c > MAX_PBKDF2_ROUNDS {
+                return Err(format!("keystore pbkdf2 round count {} out of range", c));
+            }
+            let salt = hex::decode(salt).map_err(|e| format!("invalid keystore salt: {}", e))?;
+            pbkdf2::pbkdf2_hmac::<sha2::Sha256>(passphrase.as_bytes(), &salt, *c, &mut key);
+        }
+    }
+    Ok(key)
+}
+
+/// Encrypt a hex private key under `passphrase` with the standard scrypt
+/// cost. Slow on purpose (about a second); call it off the async runtime.
+pub fn encrypt_private_key(private_key: &str, passphrase: &str) -> Result<Keystore, String> {
+    let params = KdfParams::Scrypt {
+        dklen: DKLEN,
+        n: 1 << SCRYPT_LOG_N,
+        r: SCRYPT_R,
+        p: SCRYPT_P,
+        salt: hex::encode(random_bytes::<32>()),
+    };
+    encrypt_private_key_with(private_key, passphrase, params)
+}
+
+/// [`encrypt_private_key`] with explicit KDF parameters (the salt
+/// included). Lower costs are only meant for tests.
+pub fn encrypt_private_key_with(
+    private_key: &str,
+    passphrase: &str,
+    params: KdfParams,
+) -> Result<Keystore, String> {
+    if passphrase.is_empty() {
+        return Err("Wallet passphrase must not be empty".to_string());
+    }
+    let address = crate::wallet::address_from_private_key(private_key)?;
+    let mut ciphertext = hex::decode(private_key.trim().trim_start_matches("0x"))
+        .map_err(|e| format!("Invalid key: {}", e))?;
+
+    let derived = derive_key(&params, passphrase)?;
+    let iv = random_bytes::<16>();
+    Aes128Ctr::new(derived[..16].into(), &iv.into()).apply_keystream(&mut ciphertext);
+    let mac = keccak256(&[&derived[16..32], &ciphertext]);
+
+    Ok(Keystore {
+        version: 3,
+        id: uuid::Uuid::new_v4().to_string(),
+        address: address.trim_start_matches("0x").to_string(),
+        crypto: KeystoreCrypto {
+            cipher: CIPHER.to_string(),
+            cipherparams: CipherParams {
+                iv: hex::encode(iv),
+            },
+            ciphertext: hex::encode(ciphertext),
+            kdf: match params {
+                KdfParams::Scrypt { .. } => "scrypt",
+                KdfParams::Pbkdf2 { .. } => "pbkdf2",
+            }
+            .to_string(),
+            kdfparams: params,
+            mac: hex::encode(mac),
+        },
+    })
+}
+
+/// Decrypt a keystore, returning the `0x`-prefixed private key. A wrong
+/// passphrase fails the MAC check and is reported as such.
+pub fn decrypt_private_key(keystore: &Keystore, passphrase: &str) -> Result<String, String> {
+    if keystore.version != 3 {
+        return Err(format!("unsupported keystore version {}", keystore.version));
+    }
+    let crypto = &keystore.crypto;
+    if crypto.cipher != CIPHER {
+        return Err(format!("unsupported keystore cipher {}", crypto.cipher));
+    }
+    let kdf_matches = matches!(
+        (&crypto.kdfparams, crypto.kdf.as_str()),
+        (KdfParams::Scrypt { .. }, "scrypt") | (KdfParams::Pbkdf2 { .. }, "pbkdf2")
+    );
+    if !kdf_matches {
+        return Err(format!(
+            "keystore kdf {} does not match its parameters",
+            crypto.kdf
+        ));
+    }
+    let iv: [u8; 16] = hex::decode(&crypto.cipherparams.iv)
+        .ok()
+        .and_then(|iv| iv.try_into().ok())
+        .ok_or("invalid keystore iv")?;
+    let mut plaintext = hex::decode(&crypto.ciphertext)
+        .map_err(|e| format!("invalid keystore ciphertext: {}", e))?;
+    let expected_mac =
+        hex::decode(&crypto.mac).map_err(|e| format!("invalid keystore mac: {}", e))?;
+
+    let derived = derive_key(&crypto.kdfparams, passphrase)?;
+    let mac = keccak256(&[&derived[16..32], &plaintext]);
+    // Not secret-dependent in a way an attacker can time remotely, but
+    // compare without early exit anyway.
+    let mac_ok = expected_mac.len() == mac.len()
+        && expected_mac
+            .iter()
+            .zip(mac.iter())
+            .fold(0u8, |acc, (a, b)| acc | (a ^ b))
+            == 0;
+    if !mac_ok {
+        return Err("Wrong wallet passphrase".to_string());
+    }
+
+    Aes128Ctr::new(derived[..16].into(), &iv.into()).apply_keystream(&mut plaintext);
+    if plaintext.len() != 32 {
+        return Err(format!(
+            "keystore decrypted to {} bytes, expected 32",
+            plaintext.len()
+        ));
+    }
+    let private_key = format!("0x{}", hex::encode(&plaintext));
+    if let Some(address) = keystore.address() {
+        if crate::wallet::address_from_private_key(&private_key)? != address {
+            return Err("keystore address does not match its key".to_string());
+        }
+    }
+    Ok(private_key)
+}
+
+/// Contents of a wallet key file: a legacy plaintext hex key or a keystore.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WalletKeyFile {
+    /// `0x`-prefixed private key stored in clear.
+    Plaintext(String),
+    Encrypted(Box<Keystore>),
+}
+
+/// Parse a wallet key file. JSON is read as a keystore; anything else must
+/// be a 32-byte hex key (with or without `0x`).
+pub fn parse_wallet_key_file(raw: &str) -> Result<WalletKeyFile, String> {
+    let trimmed = raw.trim();
+    if trimmed.starts_with('{') {
+        return serde_json::from_str(trimmed)
+            .map(|keystore| WalletKeyFile::Encrypted(Box::new(keystore)))
+            .map_err(|e| format!("wallet-key file is not a valid keystore: {}", e));
+    }
+    let bytes = hex::decode(trimmed.trim_start_matches("0x"))
+        .map_err(|e| format!("wallet-key file is not hex: {}", e))?;
+    if bytes.len() != 32 {
+        return Err(format!(
+            "wallet-key file decoded to {} bytes, expected 32",
+            bytes.len()
+        ));
+    }
+    Ok(WalletKeyFile::Plaintext(format!(
+        "0x{}",
+        hex::encode(bytes)
+    )))
+}
+
+pub fn read_wallet_key_file(path: &Path) -> Result<WalletKeyFile, String> {
+    let raw =
+        std::fs::read_to_string(path).map_err(|e| format!("read {}: {}", path.display(), e))?;
+    parse_wallet_key_file(&raw)
+}
+
+/// Write `keystore` to `path` atomically, readable by the owner only.
+pub fn write_keystore_file(path: &Path, keystore: &Keystore) -> Result<(), String> {
+    let json =
+        serde_json::to_string_pretty(keystore).map_err(|e| format!("serialize keystore: {}", e))?;
+    write_private_file(path, json.as_bytes())
+}
+
+/// Write `contents` to `path` atomically, readable by the owner only. Used
+/// for any file that may hold key material.
+pub fn write_private_file(path: &Path, contents: &[u8]) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("create {}: {}", parent.display(), e))?;
+    }
+    let tmp = crate::json_file::temp_path(path);
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    use std::io::Write;
+    let written = options
+        .open(&tmp)
+        .and_then(|mut f| f.write_all(contents).and_then(|_| f.sync_all()))
+        .map_err(|e| format!("write {}: {}", tmp.display(), e))
+        .and_then(|_| {
+            std::fs::rename(&tmp, path)
+                .map_err(|e| format!("rename {} to {}: {}", tmp.display(), path.display(), e))
+        });
+    if written.is_err() {
+        let _ = std::fs::remove_file(&tmp);
+    }
+    written
+}
+
+/// Replace a plaintext key file with a keystore encrypted under
+/// `passphrase`. A file that is already a keystore is left alone.
+pub fn migrate_plaintext_key_file(path: &Path, passphrase: &str) -> Result<Keystore, String> {
+    match read_wallet_key_file(path)? {
+        WalletKeyFile::Encrypted(keystore) => Ok(*keystore),
+        WalletKeyFile::Plaintext(private_key) => {
+            let keystore = encrypt_private_key(&private_key, passphrase)?;
+            write_keystore_file(path, &keystore)?;
+            Ok(keystore)
+        }
+    }
+}
+
+/// Read the wallet passphrase from `file` (first line, trailing newline
+/// stripped) or, without a file, from [`PASSPHRASE_ENV`]. `None` when
+/// neither is set.
+pub fn read_passphrase(file: Option<&Path>) -> Result<Option<String>, String> {
+    if let Some(path) = file {
+        let raw = std::fs::read_to_string(path)
+            .map_err(|e| format!("read passphrase file {}: {}", path.display(), e))?;
+        let line = raw.lines().next().unwrap_or_default().to_string();
+        if line.is_empty() {
+            return Err(format!("passphrase file {} is empty", path.display()));
+        }
+        return Ok(Some(line));
+    }
+    Ok(std::env::var(PASSPHRASE_ENV).ok().filter(|p| !p.is_empty()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &str = "0x4c0883a69102937d6231471b5dbb6204fe512961708279cea2c89f1f7a0f2c4f";
+
+    fn light_scrypt() -> KdfParams {
+        KdfParams::Scrypt {
+            dklen: 32,
+            n: 1 << 10,
+            r: 8,
+            p: 1,
+            salt: hex::encode([7u8; 32]),
+        }
+    }
+
+    #[test]
+    fn round_trip_with_scrypt_and_pbkdf2() {
+        let pbkdf2 = KdfParams::Pbkdf2 {
+            dklen: 32,
+            c: 1000,
+            prf: "hmac-sha256".to_string(),
+            salt: hex::encode([9u8; 16]),
+        };
+        for params in [light_scrypt(), pbkdf2] {
+            let keystore = encrypt_private_key_with(KEY, "hunter2", params).unwrap();
+            assert_eq!(keystore.version, 3);
+            assert_eq!(
+                keystore.address(),
+                Some(crate::wallet::address_from_private_key(KEY).unwrap())
+            );
+            assert!(!keystore.crypto.ciphertext.contains(&KEY[2..]));
+            assert_eq!(decrypt_private_key(&keystore, "hunter2").unwrap(), KEY);
+        }
+    }
+
+    #[test]
+    fn wrong_passphrase_and_tampering_are_rejected() {
+        let keystore = encrypt_private_key_with(KEY, "hunter2", light_scrypt()).unwrap();
+        assert_eq!(
+            decrypt_private_key(&keystore, "hunter3"),
+            Err("Wrong wallet passphrase".to_string())
+        );
+
+        let mut tampered = keystore.clone();
+        let mut ct = hex::decode(&tampered.crypto.ciphertext).unwrap();
+        ct[0] ^= 1;
+        tampered.crypto.ciphertext = hex::encode(ct);
+        assert!(decrypt_private_key(&tampered, "hunter2").is_err());
+
+        let mut wrong_kdf = keystore;
+        wrong_kdf.crypto.kdf = "pbkdf2".to_string();
+        assert!(decrypt_private_key(&wrong_kdf, "hunter2").is_err());
+
+        assert!(encrypt_private_key_with(KEY, "", light_scrypt()).is_err());
+    }
+
+    #[test]
+    fn decrypts_the_web3_secret_storage_pbkdf2_test_vector() {
+        // Test vector from the Web3 Secret Storage definition.
+        let json = r#"{
+            "crypto": {
+                "cipher": "aes-128-ctr",
+                "cipherparams": {"iv": "6087dab2f9fdbbfaddc31a909735c1e6"},
+                "ciphertext": "5318b4d5bcd28de64ee5559e671353e16f075ecae9f99c7a79a38af5f869aa46",
+                "kdf": "pbkdf2",
+                "kdfparams": {
+                    "c": 262144,
+                    "dklen": 32,
+                    "prf": "hmac-sha256",
+                    "salt": "ae3cd4e7013836a3df6bd7241b12db061dbe2c6785853cce422d148a624ce0bd"
+                },
+                "mac": "517ead924a9d0dc3124507e3393d175ce3ff7c1e96529c6c555ce9e51205e9b2"
+            },
+            "id": "3198bc9c-6672-5ab3-d995-4942343ae5b6",
+            "version": 3
+        }"#;
+        let keystore: Keystore = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            decrypt_private_key(&keystore, "testpassword").unwrap(),
+            "0x7a28b5ba57c53603b0b07b56bba752f7784bf506fa95edc395f5cf6c7514fe9d"
+        );
+    }
+
+    #[test]
+    fn expensive_or_malformed_kdf_parameters_are_refused() {
+        let mut keystore = encrypt_private_key_with(KEY, "pw", light_scrypt()).unwrap();
+        keystore.crypto.kdfparams = KdfParams::Scrypt {
+            dklen: 32,
+            n: 1 << 24,
+            r: 8,
+            p: 1,
+            salt: hex::encode([7u8; 32]),
+        };
+        assert!(decrypt_private_key(&keystore, "pw")
+            .unwrap_err()
+            .contains("too expensive"));
+        // 8 GiB, 1 GiB, then r and p past their own caps.
+        for (n, r, p) in [
+            (1 << 20, 64, 1),
+            (1 << 20, 8, 1),
+            (1 << 10, 64, 1),
+            (1 << 10, 8, 64),
+        ] {
+            keystore.crypto.kdfparams = KdfParams::Scrypt {
+                dklen: 32,
+                n,
+                r,
+                p,
+                salt: hex::encode([7u8; 32]),
+            };
+            assert!(decrypt_private_key(&keystore, "pw")
+                .unwrap_err()
+                .contains("too expensive"));
+        }
+        keystore.crypto.kdfparams = KdfParams::Scrypt {
+            dklen: 32,
+            n: 1000,
+            r: 8,
+            p: 1,
+            salt: hex::encode([7u8; 32]),
+        };
+        assert!(decrypt_private_key(&keystore, "pw").is_err());
+    }
+
+    #[test]
+    fn key_files_parse_as_plaintext_or_keystore() {
+        assert_eq!(
+            parse_wallet_key_file(&format!("  {}\n", &KEY[2..])).unwrap(),
+            WalletKeyFile::Plaintext(KEY.to_string())
+        );
+        assert!(parse_wallet_key_file("0x1234").is_err());
+        assert!(parse_wallet_key_file("not hex").is_err());
+
+        let keystore = encrypt_private_key_with(KEY, "pw", light_scrypt()).unwrap();
+        let json = serde_json::to_string(&keystore).unwrap();
+        assert_eq!(
+            parse_wallet_key_file(&json).unwrap(),
+            WalletKeyFile::Encrypted(Box::new(keystore))
+        );
+        assert!(parse_wallet_key_file("{\"version\": 3}").is_err());
+    }
+
+    #[test]
+    fn keystore_file_is_written_owner_only_and_reads_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("wallet.key");
+        std::fs::write(&path, &KEY[2..]).unwrap();
+
+        let keystore = encrypt_private_key_with(KEY, "pw", light_scrypt()).unwrap();
+        write_keystore_file(&path, &keystore).unwrap();
+        assert_eq!(
+            read_wallet_key_file(&path).unwrap(),
+            WalletKeyFile::Encrypted(Box::new(keystore.clone()))
+        );
+        assert!(!std::fs::read_to_string(&path).unwrap().contains(&KEY[2..]));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        // Already encrypted: migration is a no-op.
+        assert_eq!(
+            migrate_plaintext_key_file(&path, "other").unwrap(),
+            keystore
+        );
+    }
+
+    #[test]
+    fn concurrent_private_writes_never_share_a_temp_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("wallet.json");
+        // A leftover from the old fixed temp name does not get in the way.
+        std::fs::create_dir(path.with_extension("tmp")).unwrap();
+
+        let writers: Vec<_> = (0..8)
+            .map(|i| {
+                let path = path.clone();
+                std::thread::spawn(move || write_private_file(&path, format!("{i}").as_bytes()))
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap().unwrap();
+        }
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert!(contents.parse::<u32>().unwrap() < 8);
+        let mut names: Vec<_> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        names.sort();
+        assert_eq!(
+            names,
+            vec!["wallet.json", "wallet.tmp"],
+            "no temp file left behind"
+        );
+    }
+
+    #[test]
+    fn passphrase_file_uses_its_first_line() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("pass");
+        std::fs::write(&path, "correct horse\nignored\n").unwrap();
+        assert_eq!(
+            read_passphrase(Some(&path)).unwrap().as_deref(),
+            Some("correct horse")
+        );
+        std::fs::write(&path, "\n").unwrap();
+        assert!(read_passphrase(Some(&path)).is_err());
+        assert!(read_passphrase(Some(&dir.path().join("missing"))).is_err());
+    }
+}
@@ -1,7 +1,11 @@
 pub mod auth;
+pub mod bandwidth;
 pub mod cdn_server;
+pub mod chain_index;
 pub mod chain_rpc_api;
+pub mod daemon_auth;
 pub mod dht;
+pub mod dht_record;
 pub mod drive_api;
 pub mod drive_storage;
 mod encryption;
@@ -11,12 +15,24 @@ pub mod geth;
 pub mod geth_gpu;
 pub mod hosting;
 pub mod hosting_server;
+pub mod http_p2p;
+pub mod http_range;
+mod json_file;
+pub mod kad_store;
+pub mod keystore;
+pub mod merkle;
+pub mod metrics;
+pub mod micropayments;
+pub mod nat;
 pub mod network;
+mod pending_downloads;
 pub mod rating_api;
 pub mod rating_storage;
 pub mod relay_share_proxy;
 pub mod reputation;
 pub mod rpc_client;
+pub mod search_index;
+pub mod seeder_selection;
 mod speed_tiers;
 pub mod version;
 pub mod wallet;
@@ -57,6 +73,8 @@ pub struct AppState {
     /// Active WebSocket tunnel tasks keyed by resource key (e.g. "site:abc123").
     /// Dropping the AbortHandle cancels the tunnel task.
     pub tunnel_handles: Arc<Mutex<HashMap<String, tokio::task::AbortHandle>>>,
+    /// Relay-published shares and sites that relays may fetch over libp2p.
+    pub http_p2p: http_p2p::HttpP2pServer,
     // The effective `VersionPolicy` lives in `version::EFFECTIVE_POLICY`
     // (a global RwLock) since Phase 5; that gives every caller —
     // including the sync libp2p Identify event handler — a non-blocking
@@ -182,11 +200,14 @@ async fn start_dht_internal(
         return Err("DHT already running".to_string());
     }
 
-    let dht = Arc::new(DhtService::new(
-        state.file_transfer.clone(),
-        state.download_directory.clone(),
-        state.download_credentials.clone(),
-    ));
+    let dht = Arc::new(
+        DhtService::new(
+            state.file_transfer.clone(),
+            state.download_directory.clone(),
+            state.download_credentials.clone(),
+        )
+        .with_http_p2p_server(state.http_p2p.clone()),
+    );
     let app_for_bootstrap_reseed = app.clone();
     let dht_for_bootstrap_reseed = dht.clone();
     let result = dht.start(app).await?;
@@ -227,33 +248,8 @@ async fn start_dht(
     start_dht_internal(app, state.inner(), false).await
 }
 
-fn compute_sha256_file(path: &std::path::Path) -> Result<String, String> {
-    use sha2::{Digest, Sha256};
-    use std::io::Read;
-
-    let mut file = std::fs::File::open(path).map_err(|e| {
-        format!(
-            "Failed to open file for hashing '{}': {}",
-            path.display(),
-            e
-        )
-    })?;
-    let mut hasher = Sha256::new();
-    let mut buf = [0u8; 1024 * 1024];
-    loop {
-        let n = file.read(&mut buf).map_err(|e| {
-            format!(
-                "Failed to read file for hashing '{}': {}",
-                path.display(),
-                e
-            )
-        })?;
-        if n == 0 {
-            break;
-        }
-        hasher.update(&buf[..n]);
-    }
-    Ok(hex::encode(hasher.finalize()))
+fn compute_merkle_root_file(path: &std::path::Path) -> Result<String, String> {
+    crate::merkle::MerkleTree::from_file(path).map(|tree| tree.root())
 }
 
 fn signed_reseed_credentials<'a>(
@@ -319,6 +315,7 @@ async fn auto_reseed_drive_files(
                     item.protocol.clone(),
                     item.price_chi.clone(),
                     folder_access,
+                    (item.tags.clone(), item.description.clone()),
                 ))
             })
             .collect::<Vec<_>>()
@@ -360,6 +357,7 @@ async fn auto_reseed_drive_files(
         _protocol,
         price_chi,
         folder_access,
+        (tags, description),
     ) in candidates
     {
         attempted_ids.insert(item_id.clone());
@@ -400,7 +398,8 @@ async fn auto_reseed_drive_files(
                 // otherwise block libp2p / wallet RPC / UI events for the
                 // duration of the read.
                 let path_for_hash = full_path.clone();
-                match tokio::task::spawn_blocking(move || compute_sha256_file(&path_for_hash)).await
+                match tokio::task::spawn_blocking(move || compute_merkle_root_file(&path_for_hash))
+                    .await
                 {
                     Ok(Ok(hash)) => {
                         hash_updates.push((item_id.clone(), hash.clone()));
@@ -548,6 +547,19 @@ async fn auto_reseed_drive_files(
             );
             continue;
         }
+        publish_search_keywords(
+            &dht,
+            search_index::KeywordEntry::new(
+                &file_hash,
+                &file_name,
+                file_size,
+                &tags,
+                description.as_deref(),
+            ),
+            owner_wallet,
+            Some(pk),
+        )
+        .await;
         published_dht_records_count.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
     }
     let reseeded_dht_metadata =
@@ -744,10 +756,22 @@ async fn get_network_stats(state: tauri::State<'_, AppState>) -> Result<dht::Net
         Ok(dht::NetworkStats {
             connected_peers: 0,
             total_peers: 0,
+            bandwidth: bandwidth::global().stats(),
         })
     }
 }
 
+#[tauri::command]
+async fn get_bandwidth_limits() -> Result<bandwidth::BandwidthLimits, String> {
+    Ok(bandwidth::global().limits())
+}
+
+#[tauri::command]
+async fn set_bandwidth_limits(limits: bandwidth::BandwidthLimits) -> Result<(), String> {
+    bandwidth::global().set_limits(limits);
+    Ok(())
+}
+
 #[tauri::command]
 async fn get_dht_health(state: tauri::State<'_, AppState>) -> Result<dht::DhtHealthInfo, String> {
     let dht_guard = state.dht.lock().await;
@@ -764,6 +788,9 @@ async fn get_dht_health(state: tauri::State<'_, AppState>) -> Result<dht::DhtHea
             bootstrap_nodes: vec![],
             shared_files: 0,
             protocols: vec![],
+            nat_status: nat::Reachability::Unknown,
+            public_address: None,
+            port_mappings: vec![],
         })
     }
 }
@@ -812,11 +839,7 @@ async fn ping_peer(
     }
 }
 
-fn chiraldrop_send_metadata(
-    price_wei: Option<String>,
-    sender_wallet: Option<String>,
-    file_hash: Option<String>,
-) -> Result<(String, String, String), String> {
+fn chiraldrop_price_wei(price_wei: Option<String>) -> Result<String, String> {
     let normalized_price = match price_wei {
         None => "0".to_string(),
         Some(raw) if raw.is_empty() => "0".to_string(),
@@ -831,6 +854,15 @@ fn chiraldrop_send_metadata(
                 .to_string()
         }
     };
+    Ok(normalized_price)
+}
+
+fn chiraldrop_send_metadata(
+    price_wei: Option<String>,
+    sender_wallet: Option<String>,
+    file_hash: Option<String>,
+) -> Result<(String, String, String), String> {
+    let normalized_price = chiraldrop_price_wei(price_wei)?;
     let price = normalized_price
         .parse::<u128>()
         .map_err(|_| "priceWei must be empty, 0, or a valid u128 wei amount".to_string())?;
@@ -849,103 +881,341 @@ fn chiraldrop_send_metadata(
     Ok((normalized_price, sender_wallet, file_hash))
 }
 
+/// Offer a file that this node already shares (e.g. published from Drive) to
+/// a peer. The receiver pulls it over the chunked protocol after accepting.
 #[tauri::command]
 async fn send_file(
+    app: tauri::AppHandle,
     state: tauri::State<'_, AppState>,
     peer_id: String,
     file_name: String,
-    file_data: Vec<u8>,
     transfer_id: String,
     price_wei: Option<String>,
     sender_wallet: Option<String>,
     file_hash: Option<String>,
-    file_size: Option<u64>,
 ) -> Result<(), String> {
     let (price_wei, sender_wallet, file_hash) =
         chiraldrop_send_metadata(price_wei, sender_wallet, file_hash)?;
-    let actual_size = file_size.unwrap_or(file_data.len() as u64);
-    let dht_guard = state.dht.lock().await;
+    if file_hash.is_empty() {
+        return Err("fileHash is required for ChiralDrop sends".to_string());
+    }
+    let dht = {
+        let dht_guard = state.dht.lock().await;
+        dht_guard
+            .as_ref()
+            .cloned()
+            .ok_or_else(|| "DHT not running".to_string())?
+    };
 
-    if let Some(dht) = dht_guard.as_ref() {
-        dht.send_file(
-            peer_id,
-            transfer_id,
-            file_name,
-            file_data,
-            price_wei,
-            sender_wallet,
-            file_hash,
-            actual_size,
-        )
-        .await
-    } else {
-        Err("DHT not running".to_string())
+    let shared = {
+        let shared_files = dht.get_shared_files();
+        let map = shared_files.lock().await;
+        map.get(&file_hash).cloned()
+    }
+    .ok_or_else(|| format!("{} is not being shared by this node", file_hash))?;
+    // The receiver is charged whatever the seeder's signed FileInfo says, so
+    // the offer has to advertise that same price.
+    if shared.price_wei.to_string() != price_wei {
+        return Err(format!(
+            "{} is shared at {} wei, not {} wei",
+            file_name, shared.price_wei, price_wei
+        ));
     }
+
+    let offer = dht::FileTransferRequest {
+        transfer_id,
+        file_name,
+        file_size: shared.file_size,
+        file_hash,
+        price_wei,
+        sender_wallet,
+        closed: None,
+    };
+    dht.send_file_offer(peer_id.clone(), offer.clone()).await?;
+    state
+        .file_transfer
+        .lock()
+        .await
+        .record_outgoing_offer(crate::event_sink::EventSink::tauri(app), peer_id, offer)
+        .await;
+    Ok(())
 }
 
-/// Send a file to a peer by reading from a file path on disk (used by ChiralDrop)
+/// Offer a file on disk to a peer (used by ChiralDrop). Only the offer is
+/// sent; the file is served in chunks once the receiver accepts.
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 async fn send_file_by_path(
+    app: tauri::AppHandle,
     state: tauri::State<'_, AppState>,
     peer_id: String,
     file_path: String,
     transfer_id: String,
     price_wei: Option<String>,
-    sender_wallet: Option<String>,
-    file_hash: Option<String>,
+    wallet_address: Option<String>,
+    private_key: Option<String>,
 ) -> Result<(), String> {
-    let (price_wei, sender_wallet, file_hash) =
-        chiraldrop_send_metadata(price_wei, sender_wallet, file_hash)?;
-    let path = std::path::Path::new(&file_path);
+    let price_wei: u128 = chiraldrop_price_wei(price_wei)?
+        .parse()
+        .map_err(|_| "priceWei must be empty, 0, or a valid u128 wei amount".to_string())?;
+    let path = std::path::PathBuf::from(&file_path);
     let file_name = path
         .file_name()
         .map(|n| n.to_string_lossy().to_string())
         .unwrap_or_else(|| file_path.clone());
-    let file_data = std::fs::read(&file_path).map_err(|e| format!("Failed to read file: {}", e))?;
-    let file_size = file_data.len() as u64;
+    offer_chiraldrop_file(
+        app,
+        state.inner(),
+        peer_id,
+        transfer_id,
+        path,
+        file_name,
+        price_wei,
+        wallet_address.unwrap_or_default().trim().to_string(),
+        private_key.unwrap_or_default().trim().to_string(),
+    )
+    .await
+}
 
-    let dht_guard = state.dht.lock().await;
-    if let Some(dht) = dht_guard.as_ref() {
-        dht.send_file(
-            peer_id,
+#[allow(clippy::too_many_arguments)]
+async fn offer_chiraldrop_file(
+    app: tauri::AppHandle,
+    state: &AppState,
+    peer_id: String,
+    transfer_id: String,
+    file_path: std::path::PathBuf,
+    file_name: String,
+    price_wei: u128,
+    wallet_address: String,
+    private_key: String,
+) -> Result<(), String> {
+    let dht = {
+        let dht_guard = state.dht.lock().await;
+        dht_guard
+            .as_ref()
+            .cloned()
+            .ok_or_else(|| "DHT not running".to_string())?
+    };
+    let offer = dht
+        .offer_file(
+            peer_id.clone(),
             transfer_id,
+            file_path,
             file_name,
-            file_data,
             price_wei,
-            sender_wallet,
-            file_hash,
-            file_size,
+            wallet_address,
+            private_key,
         )
+        .await?;
+    state
+        .file_transfer
+        .lock()
         .await
-    } else {
-        Err("DHT not running".to_string())
-    }
+        .record_outgoing_offer(crate::event_sink::EventSink::tauri(app), peer_id, offer)
+        .await;
+    Ok(())
 }
 
+/// Accept a ChiralDrop offer and start pulling it from the sender over the
+/// chunked protocol. Paid offers need the wallet that pays the sender.
+/// Returns the download request id, which carries progress events.
 #[tauri::command]
 async fn accept_file_transfer(
     app: tauri::AppHandle,
     state: tauri::State<'_, AppState>,
     transfer_id: String,
+    wallet_address: Option<String>,
+    private_key: Option<String>,
 ) -> Result<String, String> {
-    let custom_dir = state.download_directory.lock().await.clone();
-    let file_transfer = state.file_transfer.lock().await;
-    file_transfer
-        .accept_transfer(
-            crate::event_sink::EventSink::tauri(app),
-            transfer_id,
-            custom_dir,
+    let dht = {
+        let dht_guard = state.dht.lock().await;
+        dht_guard
+            .as_ref()
+            .cloned()
+            .ok_or_else(|| "DHT not running".to_string())?
+    };
+    let file_transfer = state.file_transfer.clone();
+    let transfer = file_transfer
+        .lock()
+        .await
+        .accept_transfer(&transfer_id)
+        .await?;
+
+    let started = start_chiraldrop_download(
+        &app,
+        state.inner(),
+        &dht,
+        &transfer,
+        wallet_address,
+        private_key,
+    )
+    .await;
+    match started {
+        Ok(request_id) => {
+            file_transfer
+                .lock()
+                .await
+                .start_transfer_download(&transfer_id, request_id.clone())
+                .await?;
+            println!(
+                "📥 Accepted ChiralDrop {} from {}, downloading as {}",
+                transfer_id, transfer.peer_id, request_id
+            );
+            Ok(request_id)
+        }
+        Err(e) => {
+            // Leave the offer in the inbox so it can be accepted again.
+            file_transfer
+                .lock()
+                .await
+                .reset_transfer(&transfer_id)
+                .await;
+            Err(e)
+        }
+    }
+}
+
+async fn start_chiraldrop_download(
+    app: &tauri::AppHandle,
+    state: &AppState,
+    dht: &dht::DhtService,
+    transfer: &file_transfer::PendingTransfer,
+    wallet_address: Option<String>,
+    private_key: Option<String>,
+) -> Result<String, String> {
+    let request_id = current_download_request_id("chiraldrop", &transfer.file_hash)?;
+    if transfer.is_paid() {
+        ensure_version_supported(state).await?;
+        let wallet_address = wallet_address.unwrap_or_default().trim().to_string();
+        let private_key = private_key.unwrap_or_default().trim().to_string();
+        if wallet_address.is_empty() || private_key.is_empty() {
+            return Err("Wallet must be unlocked to accept a paid transfer".to_string());
+        }
+        state.download_credentials.lock().await.insert(
+            request_id.clone(),
+            dht::DownloadCredentials {
+                wallet_address,
+                private_key,
+                folder_hash: None,
+                folder_payment_tx: None,
+                pay_with_vouchers: false,
+            },
+        );
+    }
+
+    let _ = app.emit(
+        "download-started",
+        serde_json::json!({
+            "requestId": request_id,
+            "fileHash": transfer.file_hash,
+            "fileName": transfer.file_name,
+            "seeders": 1
+        }),
+    );
+    if let Err(e) = dht
+        .request_file(
+            transfer.peer_id.clone(),
+            transfer.file_hash.clone(),
+            request_id.clone(),
+            Vec::new(),
+            None,
         )
         .await
+    {
+        state.download_credentials.lock().await.remove(&request_id);
+        let _ = app.emit(
+            "file-download-failed",
+            serde_json::json!({
+                "requestId": request_id,
+                "fileHash": transfer.file_hash,
+                "error": e
+            }),
+        );
+        return Err(e);
+    }
+    Ok(request_id)
 }
 
+/// Decline a ChiralDrop offer and tell the sender to stop serving it.
 #[tauri::command]
 async fn decline_file_transfer(
     state: tauri::State<'_, AppState>,
     transfer_id: String,
 ) -> Result<(), String> {
-    let file_transfer = state.file_transfer.lock().await;
-    file_transfer.decline_transfer(transfer_id).await
+    let transfer = state
+        .file_transfer
+        .lock()
+        .await
+        .decline_transfer(transfer_id)
+        .await?;
+    notify_chiraldrop_sender(
+        state.inner(),
+        &transfer,
+        file_transfer::TransferStatus::Declined,
+    )
+    .await;
+    Ok(())
+}
+
+/// Best-effort notice to the sender of `transfer` that we closed it. The
+/// sender expires stale offers on its own if this never arrives.
+async fn notify_chiraldrop_sender(
+    state: &AppState,
+    transfer: &file_transfer::PendingTransfer,
+    status: file_transfer::TransferStatus,
+) {
+    let dht = state.dht.lock().await.as_ref().cloned();
+    if let Some(dht) = dht {
+        if let Err(e) = dht.close_file_offer(transfer, status).await {
+            eprintln!(
+                "Failed to tell {} that ChiralDrop {} closed: {}",
+                transfer.peer_id, transfer.transfer_id, e
+            );
+        }
+    }
+}
+
+/// Stop an accepted ChiralDrop transfer and delete what was downloaded.
+#[tauri::command]
+async fn cancel_file_transfer(
+    state: tauri::State<'_, AppState>,
+    transfer_id: String,
+) -> Result<(), String> {
+    let transfer = state
+        .file_transfer
+        .lock()
+        .await
+        .cancel_transfer(&transfer_id)
+        .await?;
+    notify_chiraldrop_sender(
+        state.inner(),
+        &transfer,
+        file_transfer::TransferStatus::Cancelled,
+    )
+    .await;
+    if let Some(request_id) = transfer.download_request_id {
+        let dht = state.dht.lock().await.as_ref().cloned();
+        if let Some(dht) = dht {
+            dht.cancel_download(request_id).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Cancel an in-progress download and delete its partial file.
+#[tauri::command]
+async fn cancel_download(
+    state: tauri::State<'_, AppState>,
+    request_id: String,
+) -> Result<bool, String> {
+    let dht = state
+        .dht
+        .lock()
+        .await
+        .as_ref()
+        .cloned()
+        .ok_or_else(|| "DHT not running".to_string())?;
+    dht.cancel_download(request_id).await
 }
 
 #[tauri::command]
@@ -989,6 +1259,10 @@ struct HostRegistryEntry {
     wallet_address: String,
     #[serde(rename = "updatedAt")]
     updated_at: u64,
+    /// The host's own signature over this entry (see `dht_record`), kept
+    /// so entries from other hosts survive a read-modify-write.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    signature: String,
 }
 
 pub fn validate_host_ad_wallet_address(wallet_address: Option<&str>) -> Result<String, String> {
@@ -1043,6 +1317,8 @@ fn host_registry_after_publish(
         peer_id,
         wallet_address,
         updated_at,
+        // Signed by the DHT layer when the registry is published.
+        signature: String::new(),
     });
     registry
 }
@@ -1556,9 +1832,30 @@ pub async fn remove_seeder_entry(dht: &dht::DhtService, file_hash: &str) -> Resu
         let key = seeder_entry_key(file_hash, &peer_id);
         let _ = dht.remove_dht_record(key).await;
     }
+    if let Err(e) = search_index::unpublish_keywords(dht, file_hash).await {
+        println!("[SEARCH] Keyword unpublish failed for {}: {}", file_hash, e);
+    }
     dht.stop_providing_file(file_hash.to_string()).await
 }
 
+/// Advertise a published file under its search keywords. Failures are only
+/// logged: the file is already reachable by hash, keyword search is a bonus.
+pub async fn publish_search_keywords(
+    dht: &dht::DhtService,
+    entry: search_index::KeywordEntry,
+    wallet_address: &str,
+    private_key: Option<&str>,
+) {
+    let hash = entry.hash.clone();
+    let Some(private_key) = private_key.filter(|k| !k.trim().is_empty()) else {
+        return;
+    };
+    match search_index::publish_keywords(dht, entry, wallet_address, private_key).await {
+        Ok(terms) => println!("[SEARCH] Indexed {} under {} keywords", hash, terms),
+        Err(e) => println!("[SEARCH] Keyword publish failed for {}: {}", hash, e),
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "camelCase")]
 struct SearchWarning {
@@ -1855,12 +2152,8 @@ async fn publish_file(
     let file_data = std::fs::read(&file_path).map_err(|e| e.to_string())?;
     let file_size = file_data.len() as u64;
 
-    // Compute SHA-256 hash
-    use sha2::{Digest, Sha256};
-    let mut hasher = Sha256::new();
-    hasher.update(&file_data);
-    let hash = hasher.finalize();
-    let merkle_root = hex::encode(hash);
+    // Content hash commits to the Merkle root over the file's chunks
+    let merkle_root = crate::merkle::MerkleTree::from_bytes(&file_data).root();
 
     println!("Publishing file: {} with hash: {}", file_name, merkle_root);
 
@@ -1944,6 +2237,13 @@ async fn publish_file(
         if let Err(e) = publish_seeder_entry(dht, &merkle_root, &our_seeder).await {
             println!("Provider publish failed for {}: {}", merkle_root, e);
         }
+        publish_search_keywords(
+            dht,
+            search_index::KeywordEntry::new(&merkle_root, &file_name, file_size, &[], None),
+            &wallet_addr,
+            private_key.as_deref(),
+        )
+        .await;
 
         println!("File published: {}", merkle_root);
     } else {
@@ -1965,12 +2265,8 @@ async fn publish_file_data(
 ) -> Result<PublishResult, String> {
     let file_size = file_data.len() as u64;
 
-    // Compute SHA-256 hash
-    use sha2::{Digest, Sha256};
-    let mut hasher = Sha256::new();
-    hasher.update(&file_data);
-    let hash = hasher.finalize();
-    let merkle_root = hex::encode(hash);
+    // Content hash commits to the Merkle root over the file's chunks
+    let merkle_root = crate::merkle::MerkleTree::from_bytes(&file_data).root();
 
     println!(
         "Publishing file from data: {} with hash: {}",
@@ -2316,6 +2612,25 @@ async fn try_repair_local_drive_seed(
     build_local_search_result(dht, file_hash).await
 }
 
+/// Find published files by name, extension, MIME type or tag. Every query
+/// term must match; results are ranked by seeders and publisher reputation.
+#[tauri::command]
+async fn search_files_by_keyword(
+    state: tauri::State<'_, AppState>,
+    query: String,
+    limit: Option<usize>,
+) -> Result<Vec<search_index::KeywordSearchResult>, String> {
+    let dht = state
+        .dht
+        .lock()
+        .await
+        .as_ref()
+        .cloned()
+        .ok_or("DHT not running")?;
+    let reputation = search_index::local_reputation().await?;
+    search_index::search_keywords(&dht, &query, search_index::clamp_limit(limit), &reputation).await
+}
+
 #[tauri::command]
 async fn search_file(
     state: tauri::State<'_, AppState>,
@@ -2600,17 +2915,174 @@ async fn search_file(
 struct DownloadStartResult {
     request_id: String,
     status: String,
+    /// Why the seeder was chosen; absent for local-cache hits.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    selection: Option<seeder_selection::SeederSelection>,
 }
 
-fn download_request_timestamp_millis_at(now: std::time::SystemTime) -> Result<u128, String> {
-    now.duration_since(std::time::UNIX_EPOCH)
-        .map(|duration| duration.as_millis())
-        .map_err(|err| {
-            format!(
-                "Cannot start download because the system clock is before UNIX_EPOCH: {}",
-                err
-            )
-        })
+fn normalize_min_seeder_reputation(value: Option<f64>) -> Result<f64, String> {
+    match value {
+        None => Ok(0.0),
+        Some(v) if v.is_finite() => Ok(v.clamp(0.0, 100.0)),
+        Some(_) => Err("Minimum seeder reputation must be a number".to_string()),
+    }
+}
+
+/// Pair seeder records with their wallet's Elo and the peer's measured
+/// performance. `peer_ids` keeps that order and adds bare candidates for
+/// peers without a record.
+fn seeder_candidates(
+    peer_ids: &[String],
+    records: &[SeederInfo],
+    elos: &std::collections::HashMap<String, f64>,
+    history: impl Fn(&str) -> Option<seeder_selection::PeerPerformance>,
+) -> Vec<seeder_selection::SeederCandidate> {
+    peer_ids
+        .iter()
+        .map(|peer_id| {
+            let record = records.iter().find(|r| &r.peer_id == peer_id);
+            let wallet_address = record.map(|r| r.wallet_address.clone()).unwrap_or_default();
+            seeder_selection::SeederCandidate {
+                peer_id: peer_id.clone(),
+                elo: elos.get(&wallet_address.to_lowercase()).copied(),
+                wallet_address,
+                price_wei: record.map(|r| r.price_wei.clone()).unwrap_or_default(),
+                multiaddrs: record.map(|r| r.multiaddrs.clone()).unwrap_or_default(),
+                performance: history(peer_id),
+            }
+        })
+        .collect()
+}
+
+/// Elo of each seeder wallet that has any reputation record, from the
+/// verdicts cached locally (optionally refreshed from the DHT first).
+async fn seeder_wallet_elos(
+    state: &AppState,
+    records: &[SeederInfo],
+    sync: bool,
+) -> std::collections::HashMap<String, f64> {
+    let wallets: Vec<String> = records
+        .iter()
+        .map(|r| r.wallet_address.trim().to_lowercase())
+        .filter(|w| reputation::is_valid_wallet(w))
+        .collect::<std::collections::BTreeSet<_>>()
+        .into_iter()
+        .collect();
+    let mut elos = std::collections::HashMap::new();
+    if wallets.is_empty() {
+        return elos;
+    }
+    let ratings = local_rating_state(state);
+    if sync {
+        ratings.sync_verdicts_for(&wallets).await;
+    }
+    let Ok(now) = rating_storage::now_secs() else {
+        return elos;
+    };
+    let manifest = ratings.manifest.read().await;
+    for wallet in wallets {
+        let snapshot =
+            rating_storage::compute_reputation_for_wallet(&manifest.events, &wallet, now);
+        if snapshot.transaction_count > 0 {
+            elos.insert(wallet, snapshot.elo);
+        }
+    }
+    elos
+}
+
+fn peer_performance(peer_id: &str) -> Option<seeder_selection::PeerPerformance> {
+    seeder_selection::performance().lock().get(peer_id)
+}
+
+/// Pick the seeder to dispatch to. A single `requested` seeder is the
+/// user's own choice and is kept if it meets `min_reputation`; with
+/// several, the best-ranked one that costs no more than `max_price_wei`
+/// wins. The explanation ranks every known seeder, so it shows whether
+/// the pick was the top-ranked one.
+fn choose_seeder(
+    requested: &[String],
+    candidates: &[seeder_selection::SeederCandidate],
+    min_reputation: f64,
+    max_price_wei: Option<u128>,
+) -> Result<(String, Option<seeder_selection::SeederSelection>), String> {
+    let (ranked, excluded) = seeder_selection::rank_seeders(candidates, min_reputation);
+    let affordable =
+        |s: &seeder_selection::RankedSeeder| match (max_price_wei, s.price_wei.parse::<u128>()) {
+            (Some(max), Ok(price)) => price <= max,
+            _ => true,
+        };
+    let chosen = if requested.len() == 1 {
+        let peer_id = &requested[0];
+        if !ranked.iter().any(|s| &s.peer_id == peer_id) {
+            let elo = candidates
+                .iter()
+                .find(|c| &c.peer_id == peer_id)
+                .and_then(|c| c.elo)
+                .unwrap_or(rating_storage::BASE_ELO);
+            return Err(format!(
+                "Seeder {} has reputation {:.1}, below your minimum of {:.0}",
+                peer_id, elo, min_reputation
+            ));
+        }
+        peer_id.clone()
+    } else {
+        ranked
+            .iter()
+            .find(|s| requested.contains(&s.peer_id) && affordable(s))
+            .map(|s| s.peer_id.clone())
+            .ok_or_else(|| {
+                format!(
+                    "No seeder for this file meets your minimum reputation of {:.0}",
+                    min_reputation
+                )
+            })?
+    };
+    let index = ranked.iter().position(|s| s.peer_id == chosen).unwrap_or(0);
+    let selection = seeder_selection::explain_selection(&ranked, index, excluded, min_reputation);
+    Ok((chosen, selection))
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RankedSeedersResult {
+    seeders: Vec<seeder_selection::RankedSeeder>,
+    excluded_below_min: usize,
+}
+
+/// Rank a file's seeders by reputation, past performance, price and
+/// proximity, best first, leaving out those below `min_reputation`.
+#[tauri::command]
+async fn rank_download_seeders(
+    state: tauri::State<'_, AppState>,
+    file_hash: String,
+    min_reputation: Option<f64>,
+) -> Result<RankedSeedersResult, String> {
+    let min_reputation = normalize_min_seeder_reputation(min_reputation)?;
+    let dht = {
+        let dht_guard = state.dht.lock().await;
+        dht_guard.as_ref().cloned()
+    }
+    .ok_or_else(|| "DHT not running".to_string())?;
+    let records = fetch_seeders(&dht, &file_hash).await?;
+    let elos = seeder_wallet_elos(state.inner(), &records, true).await;
+    let peer_ids: Vec<String> = records.iter().map(|r| r.peer_id.clone()).collect();
+    let candidates = seeder_candidates(&peer_ids, &records, &elos, peer_performance);
+    let (seeders, excluded_below_min) = seeder_selection::rank_seeders(&candidates, min_reputation);
+    Ok(RankedSeedersResult {
+        seeders,
+        excluded_below_min,
+    })
+}
+
+fn download_request_timestamp_millis_at(now: std::time::SystemTime) -> Result<u128, String> {
+    now.duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_millis())
+        .map_err(|err| {
+            format!(
+                "Cannot start download because the system clock is before UNIX_EPOCH: {}",
+                err
+            )
+        })
 }
 
 fn download_request_id_at(
@@ -2641,23 +3113,19 @@ async fn start_download(
     _seeder_wallet_address: Option<String>,
     folder_hash: Option<String>,
     folder_payment_tx: Option<String>,
+    min_seeder_reputation: Option<f64>,
+    pay_with_vouchers: Option<bool>,
 ) -> Result<DownloadStartResult, String> {
     // Phase 2 version gate: refuse paid downloads from out-of-date
     // clients (the frontend modal already prevents this for honest UIs;
     // this handles direct invoke bypasses).
     ensure_version_supported(state.inner()).await?;
 
-    // Stage 3: single chosen seeder — the frontend passes the user's selection
-    // as the first (and ideally only) entry. We no longer dispatch to multiple
-    // seeders in parallel: the user chose who to pay, so only that seeder gets
-    // the request and the payment.
-    if seeders.len() > 1 {
-        println!(
-            "⚠️ start_download received {} seeders; Stage 3 dispatches to the first only. \
-             Update the UI to pass a single chosen seeder.",
-            seeders.len()
-        );
-    }
+    // Stage 3: single chosen seeder. The frontend passes the user's selection
+    // as the only entry; callers that pass several get the best-ranked one
+    // they can afford. Either way only that seeder gets the request and the
+    // payment.
+    let min_seeder_reputation = normalize_min_seeder_reputation(min_seeder_reputation)?;
     let _ = file_size; // file_size is used by the frontend for UX; backend no longer charges by size.
     println!(
         "⚡ Starting download: {} (hash: {}) — chosen seeder: {}",
@@ -2746,6 +3214,7 @@ async fn start_download(
             return Ok(DownloadStartResult {
                 request_id,
                 status: "downloading".to_string(),
+                selection: None,
             });
         }
     }
@@ -2847,6 +3316,7 @@ async fn start_download(
             return Ok(DownloadStartResult {
                 request_id,
                 status: "downloading".to_string(),
+                selection: None,
             });
         }
     }
@@ -2867,15 +3337,65 @@ async fn start_download(
     }
 
     if let Some(dht) = dht.as_ref() {
-        // Generate a unique request ID
-        let request_id = current_download_request_id("download", &file_hash)?;
-
-        // Store download credentials if wallet is available (needed for file payment in event loop)
         let seeder_price: u128 = seeder_price_wei
             .as_deref()
             .unwrap_or("0")
             .parse()
             .unwrap_or(0);
+
+        // Look up the per-seeder records so we can rank the candidates and
+        // dial the chosen one directly. Bound the lookup so download startup
+        // stays snappy even when DHT lookups are slow.
+        let seeder_records = match tokio::time::timeout(
+            tokio::time::Duration::from_millis(1200),
+            fetch_seeders(dht, &file_hash),
+        )
+        .await
+        {
+            Ok(Ok(list)) => list,
+            _ => Vec::new(),
+        };
+        let mut ranked_peer_ids: Vec<String> =
+            seeder_records.iter().map(|r| r.peer_id.clone()).collect();
+        for peer_id in &candidate_seeders {
+            if !ranked_peer_ids.contains(peer_id) {
+                ranked_peer_ids.push(peer_id.clone());
+            }
+        }
+        let elos = seeder_wallet_elos(state.inner(), &seeder_records, false).await;
+        let candidates =
+            seeder_candidates(&ranked_peer_ids, &seeder_records, &elos, peer_performance);
+        // Stage 3: no silent fall-through to a seeder the user did not
+        // authorise. If the chosen seeder fails, the user is informed and can
+        // re-select (a new pick = a new payment). Free files are the
+        // exception: once the chosen seeder's FileInfo arrives, the DHT event
+        // loop recruits the other providers as parallel chunk sources, since
+        // nobody has to be paid.
+        let (chosen_seeder, selection) = choose_seeder(
+            &candidate_seeders,
+            &candidates,
+            min_seeder_reputation,
+            seeder_price_wei.as_ref().map(|_| seeder_price),
+        )?;
+        if let Some(selection) = &selection {
+            println!(
+                "🧭 Picked seeder {} (score {:.2}, elo {:.1}, by {:?}{})",
+                chosen_seeder,
+                selection.score,
+                selection.elo,
+                selection.picked_by,
+                if selection.manual {
+                    ", user choice"
+                } else {
+                    ""
+                }
+            );
+        }
+
+        // Generate a unique request ID
+        let request_id = current_download_request_id("download", &file_hash)?;
+
+        // Store download credentials if wallet is available (needed for file payment in event loop)
         if seeder_price > 0 || wallet_address.is_some() || folder_payment_tx_for_request.is_some() {
             if let (Some(ref addr), Some(ref key)) = (&wallet_address, &private_key) {
                 let mut creds = state.download_credentials.lock().await;
@@ -2886,6 +3406,7 @@ async fn start_download(
                         private_key: key.clone(),
                         folder_hash: folder_hash_for_request.clone(),
                         folder_payment_tx: folder_payment_tx_for_request.clone(),
+                        pay_with_vouchers: pay_with_vouchers.unwrap_or(false),
                     },
                 );
             } else if folder_payment_tx_for_request.is_some() {
@@ -2907,34 +3428,10 @@ async fn start_download(
             }),
         );
 
-        // Look up seeder multiaddresses from per-seeder records so we can
-        // dial peers directly. Bound the lookup so download startup stays
-        // snappy even when DHT lookups are slow.
-        let seeder_addrs: std::collections::HashMap<String, Vec<String>> = {
-            match tokio::time::timeout(
-                tokio::time::Duration::from_millis(1200),
-                fetch_seeders(dht, &file_hash),
-            )
-            .await
-            {
-                Ok(Ok(list)) => list
-                    .into_iter()
-                    .map(|s| (s.peer_id, s.multiaddrs))
-                    .collect(),
-                _ => std::collections::HashMap::new(),
-            }
-        };
-
-        // Stage 3: dispatch to the chosen seeder only. If this one fails, the
-        // user is informed explicitly and can re-select (a new pick = a new
-        // payment). No silent fall-through to a seeder the user did not
-        // authorise.
-        let chosen_seeder = candidate_seeders.first().cloned().ok_or_else(|| {
-            "No chosen seeder provided — the Download UI must select one first.".to_string()
-        })?;
-        let addrs = seeder_addrs
-            .get(&chosen_seeder)
-            .cloned()
+        let addrs = seeder_records
+            .iter()
+            .find(|r| r.peer_id == chosen_seeder)
+            .map(|r| r.multiaddrs.clone())
             .unwrap_or_default();
         println!(
             "Dispatching file request to chosen seeder: {} for file {}",
@@ -2957,6 +3454,7 @@ async fn start_download(
             Ok(DownloadStartResult {
                 request_id,
                 status: "requesting".to_string(),
+                selection,
             })
         } else {
             let error_msg = if last_error.is_empty() {
@@ -2979,6 +3477,50 @@ async fn start_download(
     }
 }
 
+/// Settle outstanding payment vouchers on chain. Without `force`, only
+/// debts past the size or age threshold are paid.
+#[tauri::command]
+async fn settle_payment_vouchers(
+    state: tauri::State<'_, AppState>,
+    wallet_address: String,
+    private_key: String,
+    force: Option<bool>,
+) -> Result<Vec<micropayments::IssuedSettlement>, String> {
+    let force = force.unwrap_or(false);
+    let dht = state.dht.lock().await.as_ref().cloned();
+    match dht {
+        Some(dht) => {
+            dht.settle_payment_vouchers(&wallet_address, &private_key, force)
+                .await
+        }
+        // Seeders are told about the settlement next time the node runs.
+        None => micropayments::settle(&wallet_address, &private_key, force).await,
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PaymentVoucherSummary {
+    /// Unsettled vouchers this wallet signed, per seeder
+    owed: Vec<micropayments::VoucherBalance>,
+    /// Unsettled vouchers this node holds from downloaders
+    receivable: Vec<micropayments::VoucherBalance>,
+}
+
+#[tauri::command]
+async fn get_payment_voucher_summary(
+    wallet_address: Option<String>,
+) -> Result<PaymentVoucherSummary, String> {
+    let owed = match wallet_address.as_deref().map(str::trim) {
+        Some(wallet) if !wallet.is_empty() => micropayments::issued().lock().balances(wallet),
+        _ => Vec::new(),
+    };
+    Ok(PaymentVoucherSummary {
+        owed,
+        receivable: micropayments::received().lock().balances(),
+    })
+}
+
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct DownloadCostResult {
@@ -3619,10 +4161,20 @@ async fn request_faucet(address: String) -> Result<wallet::SendTransactionResult
 async fn get_transaction_history(
     state: tauri::State<'_, AppState>,
     address: String,
+    offset: Option<usize>,
+    limit: Option<usize>,
+    since: Option<u64>,
+    until: Option<u64>,
 ) -> Result<wallet::TransactionHistoryResult, String> {
     let endpoint = geth::wallet_rpc_endpoint();
     let metadata = { state.tx_metadata.lock().await.clone() };
-    wallet::get_transaction_history(&endpoint, &address, &metadata).await
+    let query = chain_index::IndexQuery {
+        offset: offset.unwrap_or(0),
+        limit,
+        since,
+        until,
+    };
+    wallet::get_transaction_history(&endpoint, &address, &metadata, &query).await
 }
 
 #[tauri::command]
@@ -3945,6 +4497,26 @@ async fn get_mining_status(state: tauri::State<'_, AppState>) -> Result<MiningSt
     geth.get_mining_status().await
 }
 
+/// Blocks mined by `address`, or by the configured coinbase when omitted.
+#[tauri::command]
+async fn get_mined_blocks(
+    state: tauri::State<'_, AppState>,
+    address: Option<String>,
+    offset: Option<usize>,
+    limit: Option<usize>,
+    since: Option<u64>,
+    until: Option<u64>,
+) -> Result<chain_index::Page<geth::MinedBlock>, String> {
+    let query = chain_index::IndexQuery {
+        offset: offset.unwrap_or(0),
+        limit,
+        since,
+        until,
+    };
+    let geth = state.geth.lock().await;
+    geth.get_mined_blocks(address.as_deref(), &query).await
+}
+
 /// Diagnose the "mining page shows N CHI but wallet page shows 0" class
 /// of bug. Queries the same wallet address on both the local Geth node
 /// (what the mining page reports) and the canonical RPC (what the
@@ -4375,44 +4947,93 @@ async fn decrypt_file_data(
     Ok(tauri::ipc::Response::new(bytes))
 }
 
-/// Send an encrypted file to a peer
+/// Encrypt a file on disk for a peer and offer it over ChiralDrop.
+/// The file is encrypted segment by segment into the outbox, so it never
+/// has to fit in memory.
 #[tauri::command]
 async fn send_encrypted_file(
+    app: tauri::AppHandle,
     state: tauri::State<'_, AppState>,
     peer_id: String,
-    file_name: String,
-    file_data: Vec<u8>,
+    file_path: String,
     recipient_public_key: String,
     transfer_id: String,
 ) -> Result<(), String> {
-    // Encrypt the file for the recipient
-    let encrypted_bundle =
-        encryption::encrypt_for_recipient_hex(&file_data, &recipient_public_key)?;
+    let recipient_pk = encryption::parse_public_key_hex(&recipient_public_key)?;
+    let src = std::path::PathBuf::from(&file_path);
+    let file_name = src
+        .file_name()
+        .and_then(|n| n.to_str())
+        .ok_or("Invalid file path")?
+        .to_string();
 
-    // Serialize the encrypted bundle to JSON for transmission
-    let encrypted_json = serde_json::to_vec(&encrypted_bundle)
-        .map_err(|e| format!("Failed to serialize encrypted bundle: {}", e))?;
+    let staged_path = file_transfer::reserve_outgoing_path()?;
+    let dest = staged_path.clone();
+    tokio::task::spawn_blocking(move || {
+        encryption::encrypt_file_stream_for_recipient(&src, &dest, &recipient_pk)
+    })
+    .await
+    .map_err(|e| format!("Encryption task panicked: {}", e))??;
 
-    // Send via DHT
-    let dht_guard = state.dht.lock().await;
-    if let Some(dht) = dht_guard.as_ref() {
-        // Prefix file name with .encrypted to indicate it's encrypted
-        let encrypted_file_name = format!("{}.encrypted", file_name);
-        let size = encrypted_json.len() as u64;
-        dht.send_file(
-            peer_id,
-            transfer_id,
-            encrypted_file_name,
-            encrypted_json,
-            String::new(),
-            String::new(),
-            String::new(),
-            size,
-        )
-        .await
-    } else {
-        Err("DHT not running".to_string())
+    // Suffix the name so the receiver knows to decrypt it
+    let encrypted_file_name = format!("{}.encrypted", file_name);
+    offer_chiraldrop_file(
+        app,
+        state.inner(),
+        peer_id,
+        transfer_id,
+        staged_path,
+        encrypted_file_name,
+        0,
+        String::new(),
+        String::new(),
+    )
+    .await
+}
+
+/// Decrypt a received `.encrypted` file on disk with our keypair.
+/// Streamed files and envelopes are decrypted in constant memory; legacy
+/// JSON bundles are still accepted. Returns the path of the plaintext file, which
+/// defaults to the input path without its `.encrypted` suffix.
+#[tauri::command]
+async fn decrypt_encrypted_file(
+    state: tauri::State<'_, AppState>,
+    input_path: String,
+    output_path: Option<String>,
+) -> Result<String, String> {
+    let keypair = current_encryption_keypair(state.inner()).await?;
+    let src = std::path::PathBuf::from(&input_path);
+    let dest = match output_path.filter(|p| !p.trim().is_empty()) {
+        Some(p) => std::path::PathBuf::from(p),
+        None => match input_path.strip_suffix(".encrypted") {
+            Some(stripped) if !stripped.is_empty() => std::path::PathBuf::from(stripped),
+            _ => std::path::PathBuf::from(format!("{}.decrypted", input_path)),
+        },
+    };
+    if dest == src {
+        return Err("Output path must differ from the input path".into());
     }
+
+    let dest_for_task = dest.clone();
+    tokio::task::spawn_blocking(move || {
+        if encryption::is_stream_encrypted_file(&src) {
+            return encryption::decrypt_file_stream_with_keypair(&src, &dest_for_task, &keypair);
+        }
+        if encryption::is_envelope_file(&src) {
+            return encryption::decrypt_file_envelope(&src, &dest_for_task, &keypair);
+        }
+        let json = std::fs::read(&src).map_err(|e| format!("Failed to read file: {}", e))?;
+        let bundle: encryption::EncryptedFileBundle = serde_json::from_slice(&json)
+            .map_err(|_| "File is not in a recognised encrypted format".to_string())?;
+        let bytes = encryption::decrypt_with_keypair(&bundle, &keypair)?;
+        std::fs::write(&dest_for_task, &bytes)
+            .map_err(|e| format!("Failed to write file: {}", e))?;
+        Ok(bytes.len() as u64)
+    })
+    .await
+    .map_err(|e| format!("Decryption task panicked: {}", e))??;
+
+    Ok(dest.to_string_lossy().into_owned())
 }
 
 /// Publish a peer's encryption public key to the DHT (for discovery)
@@ -4441,6 +5062,13 @@ async fn publish_encryption_key(state: tauri::State<'_, AppState>) -> Result<(),
 async fn lookup_encryption_key(
     state: tauri::State<'_, AppState>,
     peer_id: String,
+) -> Result<Option<String>, String> {
+    lookup_peer_encryption_key(state.inner(), &peer_id).await
+}
+
+async fn lookup_peer_encryption_key(
+    state: &AppState,
+    peer_id: &str,
 ) -> Result<Option<String>, String> {
     let dht_guard = state.dht.lock().await;
     if let Some(dht) = dht_guard.as_ref() {
@@ -4451,6 +5079,132 @@ async fn lookup_encryption_key(
     }
 }
 
+async fn current_encryption_keypair(state: &AppState) -> Result<EncryptionKeypair, String> {
+    state
+        .encryption_keypair
+        .lock()
+        .await
+        .clone()
+        .ok_or_else(|| "Encryption keypair not initialized".to_string())
+}
+
+/// Resolve envelope recipients given as peer IDs (through the keys they
+/// published to the DHT) and/or as hex public keys.
+async fn resolve_encryption_recipients(
+    state: &AppState,
+    peer_ids: &[String],
+    public_keys: &[String],
+) -> Result<Vec<[u8; 32]>, String> {
+    let mut recipients = Vec::with_capacity(peer_ids.len() + public_keys.len());
+    for peer_id in peer_ids {
+        let key_hex = lookup_peer_encryption_key(state, peer_id)
+            .await?
+            .ok_or_else(|| format!("Peer {} has not published an encryption key", peer_id))?;
+        recipients.push(encryption::parse_public_key_hex(&key_hex)?);
+    }
+    for key_hex in public_keys {
+        recipients.push(encryption::parse_public_key_hex(key_hex)?);
+    }
+    Ok(recipients)
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct EncryptedEnvelopeInfo {
+    path: String,
+    recipients: Vec<String>,
+}
+
+/// Encrypt a file once for several recipients (plus ourselves, so we can
+/// manage the recipient list later). Returns the envelope's path, which
+/// defaults to the input path with a `.encrypted` suffix.
+#[tauri::command]
+async fn encrypt_file_for_peers(
+    state: tauri::State<'_, AppState>,
+    input_path: String,
+    output_path: Option<String>,
+    recipient_peer_ids: Vec<String>,
+    recipient_public_keys: Option<Vec<String>>,
+) -> Result<EncryptedEnvelopeInfo, String> {
+    let own_key = current_encryption_keypair(state.inner())
+        .await?
+        .public_key_bytes();
+    let mut recipients = vec![own_key];
+    recipients.extend(
+        resolve_encryption_recipients(
+            state.inner(),
+            &recipient_peer_ids,
+            &recipient_public_keys.unwrap_or_default(),
+        )
+        .await?,
+    );
+
+    let src = std::path::PathBuf::from(&input_path);
+    let dest = std::path::PathBuf::from(
+        output_path
+            .filter(|p| !p.trim().is_empty())
+            .unwrap_or_else(|| format!("{}.encrypted", input_path)),
+    );
+    if dest == src {
+        return Err("Output path must differ from the input path".into());
+    }
+    let dest_for_task = dest.clone();
+    let recipients_for_task = recipients.clone();
+    tokio::task::spawn_blocking(move || {
+        encryption::encrypt_file_envelope(&src, &dest_for_task, &recipients_for_task)
+    })
+    .await
+    .map_err(|e| format!("Encryption task panicked: {}", e))??;
+
+    let mut seen = HashSet::new();
+    Ok(EncryptedEnvelopeInfo {
+        path: dest.to_string_lossy().into_owned(),
+        recipients: recipients
+            .into_iter()
+            .filter(|k| seen.insert(*k))
+            .map(hex::encode)
+            .collect(),
+    })
+}
+
+/// Add or remove recipients of an envelope without re-encrypting it. We
+/// must hold a slot ourselves. Returns the recipients' public keys.
+#[tauri::command]
+async fn update_envelope_recipients(
+    state: tauri::State<'_, AppState>,
+    path: String,
+    add_peer_ids: Option<Vec<String>>,
+    add_public_keys: Option<Vec<String>>,
+    remove_public_keys: Option<Vec<String>>,
+) -> Result<Vec<String>, String> {
+    let keypair = current_encryption_keypair(state.inner()).await?;
+    let add = resolve_encryption_recipients(
+        state.inner(),
+        &add_peer_ids.unwrap_or_default(),
+        &add_public_keys.unwrap_or_default(),
+    )
+    .await?;
+    let remove = remove_public_keys
+        .unwrap_or_default()
+        .iter()
+        .map(|k| encryption::parse_public_key_hex(k))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let recipients = tokio::task::spawn_blocking(move || {
+        encryption::rewrap_file_envelope(std::path::Path::new(&path), &keypair, &add, &remove)
+    })
+    .await
+    .map_err(|e| format!("Rewrap task panicked: {}", e))??;
+    Ok(recipients.into_iter().map(hex::encode).collect())
+}
+
+/// Public keys holding a slot in an envelope.
+#[tauri::command]
+async fn get_envelope_recipients(path: String) -> Result<Vec<String>, String> {
+    let recipients = encryption::read_file_envelope_recipients(std::path::Path::new(&path))?;
+    Ok(recipients.into_iter().map(hex::encode).collect())
+}
+
 // ---------------------------------------------------------------------------
 // Hosting commands
 // ---------------------------------------------------------------------------
@@ -4717,48 +5471,14 @@ async fn get_hosting_server_status(
     })
 }
 
-#[derive(Debug, PartialEq, Eq)]
-struct RelayTunnelResponse {
-    status: u16,
-    headers: HashMap<String, String>,
-    body_bytes: Vec<u8>,
-}
-
-fn relay_tunnel_response_from_local_body(
-    status: u16,
-    headers: HashMap<String, String>,
-    body_result: Result<Vec<u8>, String>,
-) -> RelayTunnelResponse {
-    match body_result {
-        Ok(body_bytes) => RelayTunnelResponse {
-            status,
-            headers,
-            body_bytes,
-        },
-        Err(err) => {
-            let mut headers = HashMap::new();
-            headers.insert(
-                "content-type".to_string(),
-                "text/plain; charset=utf-8".to_string(),
-            );
-            RelayTunnelResponse {
-                status: 502,
-                headers,
-                body_bytes: format!("Local response body read failed: {}", err).into_bytes(),
-            }
-        }
-    }
-}
-
 // ---------------------------------------------------------------------------
 // WebSocket tunnel to relay (NAT traversal for hosting/drive proxy)
 // ---------------------------------------------------------------------------
 
-#[derive(Debug, serde::Deserialize, PartialEq, Eq)]
-struct DesktopTunnelRequest {
-    id: String,
-    path: String,
-}
+/// Request body chunks the owner buffers per tunnelled request; enough for the
+/// relay's whole request-size limit, so a well-behaved relay never overflows.
+const DESKTOP_TUNNEL_BODY_CAPACITY: usize =
+    relay_share_proxy::MAX_TUNNEL_REQUEST_BODY_BYTES / relay_share_proxy::TUNNEL_CHUNK_BYTES + 1;
 
 #[derive(Debug, PartialEq, Eq)]
 struct DesktopTunnelRequestFrameError {
@@ -4766,6 +5486,23 @@ struct DesktopTunnelRequestFrameError {
     message: String,
 }
 
+/// The shared resource a tunnel serves and the local server it reads from.
+#[derive(Debug, Clone)]
+struct DesktopTunnelTarget {
+    resource_type: String,
+    resource_id: String,
+    local_origin: String,
+}
+
+/// One in-flight tunnelled request on the owner side.
+struct DesktopTunnelStream {
+    /// Feeds the local request body; dropped on request-end.
+    body: Option<tokio::sync::mpsc::Sender<Result<axum::body::Bytes, std::io::Error>>>,
+    /// Response chunks the relay is currently willing to accept.
+    credits: Arc<tokio::sync::Semaphore>,
+    task: tokio::task::JoinHandle<()>,
+}
+
 fn recover_desktop_tunnel_request_id(text: &str) -> Option<String> {
     serde_json::from_str::<serde_json::Value>(text)
         .ok()
@@ -4779,45 +5516,394 @@ fn recover_desktop_tunnel_request_id(text: &str) -> Option<String> {
         })
 }
 
-fn parse_desktop_tunnel_request_frame(
+fn parse_desktop_tunnel_frame(
     text: &str,
-) -> Result<DesktopTunnelRequest, DesktopTunnelRequestFrameError> {
-    let req = serde_json::from_str::<DesktopTunnelRequest>(text).map_err(|err| {
-        DesktopTunnelRequestFrameError {
-            id: recover_desktop_tunnel_request_id(text),
-            message: format!("failed to parse tunnel request frame: {}", err),
-        }
-    })?;
+) -> Result<relay_share_proxy::TunnelRelayFrame, DesktopTunnelRequestFrameError> {
+    let frame =
+        serde_json::from_str::<relay_share_proxy::TunnelRelayFrame>(text).map_err(|err| {
+            DesktopTunnelRequestFrameError {
+                id: recover_desktop_tunnel_request_id(text),
+                message: format!("failed to parse tunnel request frame: {}", err),
+            }
+        })?;
 
-    if req.id.trim().is_empty() {
+    if frame.id().trim().is_empty() {
         return Err(DesktopTunnelRequestFrameError {
             id: None,
             message: "tunnel request frame missing id".to_string(),
         });
     }
-    if req.path.trim().is_empty() {
+    if frame.id().len() > u8::MAX as usize {
         return Err(DesktopTunnelRequestFrameError {
-            id: Some(req.id.clone()),
-            message: "tunnel request frame missing path".to_string(),
+            id: Some(frame.id().to_string()),
+            message: "tunnel request id longer than 255 bytes".to_string(),
         });
     }
+    if let relay_share_proxy::TunnelRelayFrame::Request {
+        id, method, path, ..
+    } = &frame
+    {
+        if path.trim().is_empty() {
+            return Err(DesktopTunnelRequestFrameError {
+                id: Some(id.clone()),
+                message: "tunnel request frame missing path".to_string(),
+            });
+        }
+        if reqwest::Method::from_bytes(method.as_bytes()).is_err() {
+            return Err(DesktopTunnelRequestFrameError {
+                id: Some(id.clone()),
+                message: format!("tunnel request frame has invalid method '{}'", method),
+            });
+        }
+    }
 
-    Ok(req)
+    Ok(frame)
 }
 
 fn desktop_tunnel_error_response_text(id: &str, error: &str) -> String {
-    use base64::Engine;
-    serde_json::json!({
-        "id": id,
-        "status": 502,
-        "headers": {
-            "content-type": "text/plain; charset=utf-8",
-        },
-        "body": base64::engine::general_purpose::STANDARD.encode(
-            format!("Malformed tunnel request frame from relay: {}", error).as_bytes()
-        ),
+    serde_json::to_string(&relay_share_proxy::TunnelOwnerFrame::Error {
+        id: id.to_string(),
+        message: format!("Malformed tunnel request frame from relay: {}", error),
     })
-    .to_string()
+    .unwrap_or_default()
+}
+
+/// Build the local request for a tunnelled one. The path must resolve inside
+/// the shared resource on the local origin, and only end-to-end headers are
+/// forwarded.
+fn desktop_tunnel_local_request(
+    client: &reqwest::Client,
+    target: &DesktopTunnelTarget,
+    method: &str,
+    path: &str,
+    headers: &[(String, String)],
+    body: Option<relay_share_proxy::ChannelBody>,
+) -> Result<reqwest::RequestBuilder, String> {
+    let url = relay_share_proxy::scoped_origin_url(
+        &target.local_origin,
+        &target.resource_type,
+        &target.resource_id,
+        path,
+    )?;
+    let method = reqwest::Method::from_bytes(method.as_bytes())
+        .map_err(|err| format!("invalid method '{}': {}", method, err))?;
+    let mut request = client.request(method, url);
+    for (name, value) in headers {
+        if relay_share_proxy::is_forwardable_request_header(name) {
+            request = request.header(name.as_str(), value.as_str());
+        }
+    }
+    if let Some(body) = body {
+        request = request.body(reqwest::Body::wrap_stream(body));
+    }
+    Ok(request)
+}
+
+fn desktop_tunnel_response_headers(headers: &reqwest::header::HeaderMap) -> Vec<(String, String)> {
+    headers
+        .iter()
+        .filter(|(name, _)| relay_share_proxy::is_forwardable_response_header(name.as_str()))
+        .filter_map(|(name, value)| {
+            value
+                .to_str()
+                .ok()
+                .map(|value| (name.as_str().to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+/// Hand a request body chunk from the relay to its local request. Errors
+/// carry the request id when the relay should be told the request failed.
+fn route_desktop_tunnel_chunk(
+    streams: &mut HashMap<String, DesktopTunnelStream>,
+    frame: &[u8],
+) -> Result<(), DesktopTunnelRequestFrameError> {
+    let (id, payload) = relay_share_proxy::decode_tunnel_chunk(frame)
+        .map_err(|message| DesktopTunnelRequestFrameError { id: None, message })?;
+    let Some(stream) = streams.get_mut(id) else {
+        return Err(DesktopTunnelRequestFrameError {
+            id: None,
+            message: format!("request body chunk for unknown id={}", id),
+        });
+    };
+    let Some(body) = stream.body.as_ref() else {
+        return Err(DesktopTunnelRequestFrameError {
+            id: Some(id.to_string()),
+            message: "request body chunk after request-end".to_string(),
+        });
+    };
+    match body.try_send(Ok(axum::body::Bytes::copy_from_slice(payload))) {
+        Ok(()) => Ok(()),
+        // The local server answered without reading the whole body.
+        Err(tokio::sync::mpsc::error::TrySendError::Closed(_)) => Ok(()),
+        Err(tokio::sync::mpsc::error::TrySendError::Full(_)) => {
+            if let Some(stream) = streams.remove(id) {
+                stream.task.abort();
+            }
+            Err(DesktopTunnelRequestFrameError {
+                id: Some(id.to_string()),
+                message: "request body exceeds the tunnel limit".to_string(),
+            })
+        }
+    }
+}
+
+async fn send_desktop_tunnel_frame(
+    out_tx: &tokio::sync::mpsc::Sender<tokio_tungstenite::tungstenite::Message>,
+    frame: &relay_share_proxy::TunnelOwnerFrame,
+) -> Result<(), String> {
+    let text = serde_json::to_string(frame).map_err(|err| err.to_string())?;
+    out_tx
+        .send(tokio_tungstenite::tungstenite::Message::Text(text))
+        .await
+        .map_err(|err| err.to_string())
+}
+
+/// Run one tunnelled request against the local server and stream the response
+/// back, never sending more chunks than the relay has granted credit for.
+async fn stream_desktop_tunnel_response(
+    request: reqwest::RequestBuilder,
+    id: &str,
+    out_tx: &tokio::sync::mpsc::Sender<tokio_tungstenite::tungstenite::Message>,
+    credits: &tokio::sync::Semaphore,
+) -> Result<(), String> {
+    use futures_util::StreamExt;
+    use relay_share_proxy::TunnelOwnerFrame;
+
+    let response = match request.send().await {
+        Ok(response) => response,
+        Err(err) => {
+            let frame = TunnelOwnerFrame::Error {
+                id: id.to_string(),
+                message: format!("Local server error: {}", err),
+            };
+            return send_desktop_tunnel_frame(out_tx, &frame).await;
+        }
+    };
+    let head = TunnelOwnerFrame::Response {
+        id: id.to_string(),
+        status: response.status().as_u16(),
+        headers: desktop_tunnel_response_headers(response.headers()),
+    };
+    send_desktop_tunnel_frame(out_tx, &head).await?;
+
+    let mut body = response.bytes_stream();
+    while let Some(chunk) = body.next().await {
+        let chunk = match chunk {
+            Ok(chunk) => chunk,
+            Err(err) => {
+                let frame = TunnelOwnerFrame::Error {
+                    id: id.to_string(),
+                    message: format!("Local response body read failed: {}", err),
+                };
+                return send_desktop_tunnel_frame(out_tx, &frame).await;
+            }
+        };
+        for piece in chunk.chunks(relay_share_proxy::TUNNEL_CHUNK_BYTES) {
+            credits
+                .acquire()
+                .await
+                .map_err(|err| err.to_string())?
+                .forget();
+            let frame = relay_share_proxy::encode_tunnel_chunk(id, piece)?;
+            out_tx
+                .send(tokio_tungstenite::tungstenite::Message::Binary(frame))
+                .await
+                .map_err(|err| err.to_string())?;
+        }
+    }
+    send_desktop_tunnel_frame(
+        out_tx,
+        &TunnelOwnerFrame::ResponseEnd { id: id.to_string() },
+    )
+    .await
+}
+
+/// Apply one control frame from the relay. Returns Err only when the tunnel
+/// writer is gone and the session should end.
+async fn handle_desktop_tunnel_frame(
+    frame: relay_share_proxy::TunnelRelayFrame,
+    streams: &mut HashMap<String, DesktopTunnelStream>,
+    target: &DesktopTunnelTarget,
+    client: &reqwest::Client,
+    out_tx: &tokio::sync::mpsc::Sender<tokio_tungstenite::tungstenite::Message>,
+) -> Result<(), String> {
+    use relay_share_proxy::TunnelRelayFrame;
+
+    match frame {
+        TunnelRelayFrame::Request {
+            id,
+            method,
+            path,
+            headers,
+            has_body,
+        } => {
+            streams.retain(|_, stream| !stream.task.is_finished());
+            let (body_tx, body) = if has_body {
+                let (tx, rx) = tokio::sync::mpsc::channel(DESKTOP_TUNNEL_BODY_CAPACITY);
+                (Some(tx), Some(relay_share_proxy::ChannelBody(rx)))
+            } else {
+                (None, None)
+            };
+            let request = match desktop_tunnel_local_request(
+                client, target, &method, &path, &headers, body,
+            ) {
+                Ok(request) => request,
+                Err(err) => {
+                    println!(
+                        "[TUNNEL] Rejected request for {}:{} id={}: {}",
+                        target.resource_type, target.resource_id, id, err
+                    );
+                    let frame = relay_share_proxy::TunnelOwnerFrame::Error { id, message: err };
+                    return send_desktop_tunnel_frame(out_tx, &frame).await;
+                }
+            };
+
+            let credits = Arc::new(tokio::sync::Semaphore::new(
+                relay_share_proxy::TUNNEL_WINDOW_CHUNKS as usize,
+            ));
+            let task = tokio::spawn({
+                let id = id.clone();
+                let out_tx = out_tx.clone();
+                let credits = Arc::clone(&credits);
+                let target = target.clone();
+                async move {
+                    let send_result =
+                        stream_desktop_tunnel_response(request, &id, &out_tx, &credits).await;
+                    if let Err(message) = relay_tunnel_response_send_result(
+                        &target.resource_type,
+                        &target.resource_id,
+                        &id,
+                        send_result,
+                    ) {
+                        println!("{}", message);
+                    }
+                }
+            });
+            streams.insert(
+                id,
+                DesktopTunnelStream {
+                    body: body_tx,
+                    credits,
+                    task,
+                },
+            );
+        }
+        TunnelRelayFrame::RequestEnd { id } => {
+            if let Some(stream) = streams.get_mut(&id) {
+                stream.body = None;
+            }
+        }
+        TunnelRelayFrame::Window { id, credits } => {
+            if let Some(stream) = streams.get(&id) {
+                let credits = credits.min(relay_share_proxy::TUNNEL_WINDOW_CHUNKS);
+                stream.credits.add_permits(credits as usize);
+            }
+        }
+        TunnelRelayFrame::Cancel { id } => {
+            if let Some(stream) = streams.remove(&id) {
+                stream.task.abort();
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Serve relay requests over one connected tunnel until either side closes.
+async fn run_desktop_tunnel_session(
+    ws_stream: tokio_tungstenite::WebSocketStream<
+        tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>,
+    >,
+    target: &DesktopTunnelTarget,
+    client: &reqwest::Client,
+) {
+    use futures_util::{SinkExt, StreamExt};
+    use tokio_tungstenite::tungstenite::Message;
+
+    let (mut ws_tx, mut ws_rx) = ws_stream.split();
+    // Per-request tasks share the socket through this writer.
+    let (out_tx, mut out_rx) = tokio::sync::mpsc::channel::<Message>(64);
+    let mut writer = tokio::spawn({
+        let label = format!("{}:{}", target.resource_type, target.resource_id);
+        async move {
+            while let Some(msg) = out_rx.recv().await {
+                if let Err(err) = ws_tx.send(msg).await {
+                    println!("[TUNNEL] Failed to write to relay for {}: {}", label, err);
+                    break;
+                }
+            }
+        }
+    });
+    let mut streams: HashMap<String, DesktopTunnelStream> = HashMap::new();
+
+    loop {
+        let msg = tokio::select! {
+            msg = ws_rx.next() => msg,
+            _ = &mut writer => break,
+        };
+        let Some(Ok(msg)) = msg else {
+            break;
+        };
+        let frame_error = match msg {
+            Message::Text(text) => match parse_desktop_tunnel_frame(&text) {
+                Ok(frame) => {
+                    if handle_desktop_tunnel_frame(frame, &mut streams, target, client, &out_tx)
+                        .await
+                        .is_err()
+                    {
+                        break;
+                    }
+                    None
+                }
+                Err(err) => Some(err),
+            },
+            Message::Binary(data) => route_desktop_tunnel_chunk(&mut streams, &data).err(),
+            Message::Ping(data) => {
+                let send_result = out_tx
+                    .send(Message::Pong(data))
+                    .await
+                    .map_err(|err| err.to_string());
+                if let Err(message) = relay_tunnel_pong_send_result(
+                    &target.resource_type,
+                    &target.resource_id,
+                    send_result,
+                ) {
+                    println!("{}", message);
+                    break;
+                }
+                None
+            }
+            Message::Close(_) => break,
+            _ => None,
+        };
+
+        let Some(err) = frame_error else {
+            continue;
+        };
+        match err.id {
+            Some(id) => {
+                println!(
+                    "[TUNNEL] Malformed request frame for {}:{} id={}: {}",
+                    target.resource_type, target.resource_id, id, err.message
+                );
+                let msg = Message::Text(desktop_tunnel_error_response_text(&id, &err.message));
+                if out_tx.send(msg).await.is_err() {
+                    break;
+                }
+            }
+            None => {
+                println!(
+                    "[TUNNEL] Malformed request frame for {}:{} without recoverable id: {}",
+                    target.resource_type, target.resource_id, err.message
+                );
+            }
+        }
+    }
+
+    for (_, stream) in streams.drain() {
+        stream.task.abort();
+    }
+    writer.abort();
 }
 
 /// Spawn a background task that maintains a WebSocket tunnel to the relay.
@@ -4829,168 +5915,53 @@ fn spawn_relay_tunnel(
     resource_id: String,
     local_origin: String,
 ) -> tokio::task::AbortHandle {
-    use futures_util::StreamExt;
     // Fix 0.0.0.0 — it's not a valid destination address for clients
     let local_origin = local_origin
         .replace("://0.0.0.0:", "://127.0.0.1:")
         .replace("://0.0.0.0/", "://127.0.0.1/");
+    let target = DesktopTunnelTarget {
+        resource_type,
+        resource_id,
+        local_origin,
+    };
     let handle = tokio::spawn(async move {
+        // Redirects are passed back to the visitor rather than followed here,
+        // and there is no overall timeout so large downloads can stream.
+        let client = reqwest::Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .connect_timeout(std::time::Duration::from_secs(10))
+            .build()
+            .unwrap_or_default();
         loop {
             let ws_url = format!(
                 "{}/api/tunnel/ws?type={}&id={}",
                 relay_base
                     .replace("http://", "ws://")
                     .replace("https://", "wss://"),
-                resource_type,
-                resource_id
+                target.resource_type,
+                target.resource_id
             );
             println!(
                 "[TUNNEL] Connecting to {} for {}:{}",
-                ws_url, resource_type, resource_id
+                ws_url, target.resource_type, target.resource_id
             );
 
             match tokio_tungstenite::connect_async(&ws_url).await {
                 Ok((ws_stream, _)) => {
-                    println!("[TUNNEL] Connected for {}:{}", resource_type, resource_id);
-                    let (mut ws_tx, mut ws_rx) = futures_util::StreamExt::split(ws_stream);
-
-                    let client = reqwest::Client::builder()
-                        .timeout(std::time::Duration::from_secs(30))
-                        .build()
-                        .unwrap_or_default();
-                    let local = local_origin.clone();
-
-                    while let Some(Ok(msg)) = ws_rx.next().await {
-                        match msg {
-                            tokio_tungstenite::tungstenite::Message::Text(text) => {
-                                let req = match parse_desktop_tunnel_request_frame(&text) {
-                                    Ok(r) => r,
-                                    Err(err) => {
-                                        match err.id {
-                                            Some(id) => {
-                                                println!(
-                                                    "[TUNNEL] Malformed request frame for {}:{} id={}: {}",
-                                                    resource_type, resource_id, id, err.message
-                                                );
-                                                let msg =
-                                                    tokio_tungstenite::tungstenite::Message::Text(
-                                                        desktop_tunnel_error_response_text(
-                                                            &id,
-                                                            &err.message,
-                                                        ),
-                                                    );
-                                                if futures_util::SinkExt::send(&mut ws_tx, msg)
-                                                    .await
-                                                    .is_err()
-                                                {
-                                                    break;
-                                                }
-                                            }
-                                            None => {
-                                                println!(
-                                                    "[TUNNEL] Malformed request frame for {}:{} without recoverable id: {}",
-                                                    resource_type, resource_id, err.message
-                                                );
-                                            }
-                                        }
-                                        continue;
-                                    }
-                                };
-
-                                // Fetch from local server
-                                let target = format!("{}{}", local, req.path);
-                                let (status, headers, body_bytes): (
-                                    u16,
-                                    HashMap<String, String>,
-                                    Vec<u8>,
-                                ) = match client.get(&target).send().await {
-                                    Ok(resp) => {
-                                        let st = resp.status().as_u16();
-                                        let mut hdr = HashMap::<String, String>::new();
-                                        for (k, v) in resp.headers() {
-                                            if let Ok(vs) = v.to_str() {
-                                                hdr.insert(k.to_string(), vs.to_string());
-                                            }
-                                        }
-                                        let body_result = resp
-                                            .bytes()
-                                            .await
-                                            .map(|bytes| bytes.to_vec())
-                                            .map_err(|err| err.to_string());
-                                        if let Err(err) = body_result.as_ref() {
-                                            println!(
-                                                "[TUNNEL] Failed to read local response body for {}:{} id={}: {}",
-                                                resource_type, resource_id, req.id, err
-                                            );
-                                        }
-                                        let tunnel_response = relay_tunnel_response_from_local_body(
-                                            st,
-                                            hdr,
-                                            body_result,
-                                        );
-                                        (
-                                            tunnel_response.status,
-                                            tunnel_response.headers,
-                                            tunnel_response.body_bytes,
-                                        )
-                                    }
-                                    Err(_) => (502, HashMap::new(), b"Local server error".to_vec()),
-                                };
-
-                                // Send response back through the tunnel
-                                use base64::Engine;
-                                let request_id = req.id.clone();
-                                let resp_json = serde_json::json!({
-                                    "id": &request_id,
-                                    "status": status,
-                                    "headers": headers,
-                                    "body": base64::engine::general_purpose::STANDARD.encode(&body_bytes),
-                                });
-                                let msg = tokio_tungstenite::tungstenite::Message::Text(
-                                    resp_json.to_string(),
-                                );
-                                let send_result = futures_util::SinkExt::send(&mut ws_tx, msg)
-                                    .await
-                                    .map_err(|err| err.to_string());
-                                if let Err(message) = relay_tunnel_response_send_result(
-                                    &resource_type,
-                                    &resource_id,
-                                    &request_id,
-                                    send_result,
-                                ) {
-                                    println!("{}", message);
-                                    break;
-                                }
-                            }
-                            tokio_tungstenite::tungstenite::Message::Ping(data) => {
-                                let send_result = futures_util::SinkExt::send(
-                                    &mut ws_tx,
-                                    tokio_tungstenite::tungstenite::Message::Pong(data),
-                                )
-                                .await
-                                .map_err(|err| err.to_string());
-                                if let Err(message) = relay_tunnel_pong_send_result(
-                                    &resource_type,
-                                    &resource_id,
-                                    send_result,
-                                ) {
-                                    println!("{}", message);
-                                    break;
-                                }
-                            }
-                            tokio_tungstenite::tungstenite::Message::Close(_) => break,
-                            _ => {}
-                        }
-                    }
+                    println!(
+                        "[TUNNEL] Connected for {}:{}",
+                        target.resource_type, target.resource_id
+                    );
+                    run_desktop_tunnel_session(ws_stream, &target, &client).await;
                     println!(
                         "[TUNNEL] Disconnected from relay for {}:{}",
-                        resource_type, resource_id
+                        target.resource_type, target.resource_id
                     );
                 }
                 Err(e) => {
                     println!(
                         "[TUNNEL] Failed to connect for {}:{}: {}",
-                        resource_type, resource_id, e
+                        target.resource_type, target.resource_id, e
                     );
                 }
             }
@@ -5029,6 +6000,13 @@ fn relay_tunnel_pong_send_result(
     })
 }
 
+/// This node's libp2p peer ID for relay registration, once the DHT is up.
+/// Lets the relay reach us over the swarm when our origin isn't reachable.
+async fn relay_register_peer_id(state: &AppState) -> Option<String> {
+    let dht = state.dht.lock().await.clone()?;
+    dht.get_peer_id().await
+}
+
 #[tauri::command]
 async fn publish_site_to_relay(
     state: tauri::State<'_, AppState>,
@@ -5061,7 +6039,14 @@ async fn publish_site_to_relay(
     let url = format!("{}/api/sites/relay-register", relay_base);
 
     let owner_lower = owner_wallet.to_lowercase();
-    let payload = relay_share_proxy::register_payload("site", &site_id, &owner_lower, &origin);
+    let peer_id = relay_register_peer_id(&state).await;
+    let payload = relay_share_proxy::register_payload_with_peer(
+        "site",
+        &site_id,
+        &owner_lower,
+        &origin,
+        peer_id.as_deref(),
+    );
     let signature = wallet::sign_message(&private_key, &payload)
         .map_err(|e| format!("Failed to sign register payload: {}", e))?;
 
@@ -5075,6 +6060,7 @@ async fn publish_site_to_relay(
             "origin_url": origin,
             "owner_wallet": owner_lower,
             "signature": signature,
+            "peer_id": peer_id,
         }))
         .timeout(std::time::Duration::from_secs(30))
         .send()
@@ -5095,6 +6081,10 @@ async fn publish_site_to_relay(
         .map(|a| format!("http://127.0.0.1:{}", a.port()))
         .ok_or("Local server not running")?;
     let tunnel_key = format!("site:{}", site_id);
+    state
+        .http_p2p
+        .publish("site", &site_id, &local_for_tunnel)
+        .await;
     let abort_handle = spawn_relay_tunnel(
         relay_base.to_string(),
         "site".to_string(),
@@ -5181,6 +6171,7 @@ async fn unpublish_site_from_relay(
     if let Some(handle) = state.tunnel_handles.lock().await.remove(&tunnel_key) {
         handle.abort();
     }
+    state.http_p2p.unpublish("site", &site_id).await;
 
     // Clear relay URL from local metadata
     if let Some(s) = all_sites.iter_mut().find(|s| s.id == site_id) {
@@ -5784,6 +6775,8 @@ async fn drive_create_folder(
         storage_path: None,
         owner,
         is_public: true,
+        description: None,
+        tags: Vec::new(),
         merkle_root: None,
         protocol: None,
         price_chi: None,
@@ -5831,23 +6824,37 @@ async fn drive_upload_file(
     file_path: String,
     parent_id: Option<String>,
     merkle_root: Option<String>,
+    encrypt: Option<bool>,
 ) -> Result<DsItem, String> {
     if owner.is_empty() {
         return Err("owner required".into());
     }
     let src = std::path::PathBuf::from(&file_path);
-    let file_name = src
+    let mut file_name = src
         .file_name()
         .and_then(|n| n.to_str())
         .ok_or("Invalid file path")?
         .to_string();
 
+    // Encrypted uploads are sealed to our own key; only we can read them back.
+    let encrypt_to = if encrypt.unwrap_or(false) {
+        let keypair_guard = state.encryption_keypair.lock().await;
+        let keypair = keypair_guard
+            .as_ref()
+            .ok_or("Encryption keypair not initialized")?;
+        file_name = format!("{}.encrypted", file_name);
+        Some(keypair.public_key_bytes())
+    } else {
+        None
+    };
+
     // Stat first so we can reject oversize uploads without copying anything.
+    // Encrypted uploads are written segment by segment and are not capped.
     let file_size = tokio::fs::metadata(&src)
         .await
         .map_err(|e| format!("Failed to stat file: {}", e))?
         .len();
-    if file_size > 500 * 1024 * 1024 {
+    if encrypt_to.is_none() && file_size > 500 * 1024 * 1024 {
         return Err("File exceeds 500 MB limit".into());
     }
 
@@ -5870,38 +6877,37 @@ async fn drive_upload_file(
     // separate from the async reactor.
     let dest_for_copy = dest.clone();
     let src_for_copy = src.clone();
-    let copied_bytes = tokio::fs::copy(&src_for_copy, &dest_for_copy)
+    let copied_bytes = if let Some(public_key) = encrypt_to {
+        tokio::task::spawn_blocking(move || {
+            encryption::encrypt_file_stream_for_recipient(
+                &src_for_copy,
+                &dest_for_copy,
+                &public_key,
+            )
+        })
         .await
-        .map_err(|e| format!("Failed to copy file: {}", e))?;
+        .map_err(|e| format!("Encryption task panicked: {}", e))??
+    } else {
+        tokio::fs::copy(&src_for_copy, &dest_for_copy)
+            .await
+            .map_err(|e| format!("Failed to copy file: {}", e))?
+    };
 
     // Reuse caller-supplied merkle_root if present (e.g. import flows
     // that already know the hash). Otherwise hash the just-written
-    // destination on the blocking pool so the runtime stays free.
-    let computed_merkle_root = if let Some(h) = merkle_root.clone().filter(|h| !h.trim().is_empty())
-    {
+    // destination on the blocking pool so the runtime stays free. A
+    // caller's root describes the plaintext, so it never applies to an
+    // encrypted upload.
+    let caller_merkle_root = merkle_root
+        .clone()
+        .filter(|h| !h.trim().is_empty() && encrypt_to.is_none());
+    let computed_merkle_root = if let Some(h) = caller_merkle_root {
         h
     } else {
         let dest_for_hash = dest.clone();
-        tokio::task::spawn_blocking(move || -> Result<String, String> {
-            use sha2::{Digest, Sha256};
-            use std::io::Read;
-            let mut file = std::fs::File::open(&dest_for_hash)
-                .map_err(|e| format!("Failed to open uploaded file for hashing: {}", e))?;
-            let mut hasher = Sha256::new();
-            let mut buf = [0u8; 1024 * 1024];
-            loop {
-                let n = file
-                    .read(&mut buf)
-                    .map_err(|e| format!("Failed to read uploaded file while hashing: {}", e))?;
-                if n == 0 {
-                    break;
-                }
-                hasher.update(&buf[..n]);
-            }
-            Ok(hex::encode(hasher.finalize()))
-        })
-        .await
-        .map_err(|e| format!("Hash task panicked: {}", e))??
+        tokio::task::spawn_blocking(move || compute_merkle_root_file(&dest_for_hash))
+            .await
+            .map_err(|e| format!("Hash task panicked: {}", e))??
     };
 
     let item = DsItem {
@@ -5917,6 +6923,8 @@ async fn drive_upload_file(
         storage_path: Some(storage_name),
         owner,
         is_public: true,
+        description: None,
+        tags: Vec::new(),
         merkle_root: Some(computed_merkle_root),
         protocol: None,
         price_chi: None,
@@ -5965,6 +6973,7 @@ async fn drive_update_item(
         .find(|i| i.id == item_id && i.owner == owner)
         .ok_or("Item not found")?;
     let now = ds::now_secs()?;
+    let renamed = name.is_some();
     if let Some(n) = name {
         if n.is_empty() || n.len() > 255 {
             return Err("Invalid name".into());
@@ -5984,9 +6993,66 @@ async fn drive_update_item(
     let updated = item.clone();
     drop(m);
     state.drive_state.persist().await;
+    if renamed {
+        refresh_drive_item_keywords(&state, &updated).await;
+    }
+    Ok(updated)
+}
+
+/// Update the description and tags used for keyword search. A file that is
+/// currently seeding is re-indexed straight away.
+#[tauri::command]
+async fn drive_set_item_metadata(
+    state: tauri::State<'_, AppState>,
+    owner: String,
+    item_id: String,
+    description: Option<String>,
+    tags: Option<Vec<String>>,
+) -> Result<DsItem, String> {
+    if owner.is_empty() {
+        return Err("owner required".into());
+    }
+    let mut m = state.drive_state.manifest.write().await;
+    let item = m
+        .items
+        .iter_mut()
+        .find(|i| i.id == item_id && i.owner == owner)
+        .ok_or("Item not found")?;
+    if let Some(description) = description {
+        item.description = search_index::normalize_description(Some(&description));
+    }
+    if let Some(tags) = tags {
+        item.tags = search_index::normalize_tags(&tags);
+    }
+    item.modified_at = ds::now_secs()?;
+    let updated = item.clone();
+    drop(m);
+    state.drive_state.persist().await;
+
+    refresh_drive_item_keywords(&state, &updated).await;
     Ok(updated)
 }
 
+/// Re-index a seeding Drive file after its name or search metadata changed.
+async fn refresh_drive_item_keywords(state: &AppState, item: &DsItem) {
+    let Some(hash) = item.merkle_root.as_deref().filter(|_| item.seeding) else {
+        return;
+    };
+    let Some(dht) = state.dht.lock().await.as_ref().cloned() else {
+        return;
+    };
+    let entry = search_index::KeywordEntry::new(
+        hash,
+        &item.name,
+        item.size.unwrap_or(0),
+        &item.tags,
+        item.description.as_deref(),
+    );
+    if let Err(e) = search_index::refresh_keywords(&dht, entry).await {
+        println!("[SEARCH] Keyword refresh failed for {}: {}", hash, e);
+    }
+}
+
 #[tauri::command]
 async fn drive_delete_item(
     state: tauri::State<'_, AppState>,
@@ -6253,7 +7319,7 @@ async fn publish_drive_file_inner(
     }
 
     // Look up the Drive item from the manifest.
-    let (file_name, storage_path, file_size_hint, existing_merkle_root, folder_access) = {
+    let (file_name, storage_path, file_size_hint, existing_merkle_root, folder_access, search_meta) = {
         let m = state.drive_state.manifest.read().await;
         let item = m
             .items
@@ -6270,6 +7336,7 @@ async fn publish_drive_file_inner(
             item.size,
             item.merkle_root.clone(),
             paid_folder_policies_for_drive_item(&m, item),
+            (item.tags.clone(), item.description.clone()),
         )
     };
 
@@ -6301,26 +7368,9 @@ async fn publish_drive_file_inner(
         root
     } else {
         let path_for_hash = full_path.clone();
-        tokio::task::spawn_blocking(move || -> Result<String, String> {
-            use sha2::{Digest, Sha256};
-            use std::io::Read;
-            let mut file = std::fs::File::open(&path_for_hash)
-                .map_err(|e| format!("Failed to open file: {}", e))?;
-            let mut hasher = Sha256::new();
-            let mut buf = [0u8; 1024 * 1024];
-            loop {
-                let n = file
-                    .read(&mut buf)
-                    .map_err(|e| format!("Failed to read file while hashing: {}", e))?;
-                if n == 0 {
-                    break;
-                }
-                hasher.update(&buf[..n]);
-            }
-            Ok(hex::encode(hasher.finalize()))
-        })
-        .await
-        .map_err(|e| format!("Hash task panicked: {}", e))??
+        tokio::task::spawn_blocking(move || compute_merkle_root_file(&path_for_hash))
+            .await
+            .map_err(|e| format!("Hash task panicked: {}", e))??
     };
 
     let proto = protocol.unwrap_or_else(|| "WebRTC".to_string());
@@ -6416,6 +7466,20 @@ async fn publish_drive_file_inner(
     if let Err(e) = publish_seeder_entry(&dht, &file_hash, &our_seeder).await {
         println!("Provider publish failed for {}: {}", file_hash, e);
     }
+    let (tags, description) = search_meta;
+    publish_search_keywords(
+        &dht,
+        search_index::KeywordEntry::new(
+            &file_hash,
+            &file_name,
+            actual_size,
+            &tags,
+            description.as_deref(),
+        ),
+        &wallet_addr,
+        private_key.as_deref(),
+    )
+    .await;
 
     // Update the Drive manifest with seeding metadata
     let updated_item = {
@@ -7699,12 +8763,12 @@ async fn publish_drive_share(
     if owner_wallet.is_empty() || private_key.is_empty() {
         return Err("Wallet must be unlocked to publish a share to the relay".into());
     }
-    let origin = state
+    let addr = state
         .hosting_server_addr
         .lock()
         .await
-        .map(|a| format!("http://{}", a))
         .ok_or("Local server not running")?;
+    let origin = format!("http://{}", addr);
 
     let relay_base = relay_url.trim_end_matches('/');
     let url = format!("{}/api/drive/relay-register", relay_base);
@@ -7713,7 +8777,14 @@ async fn publish_drive_share(
     // the relay can verify we own `owner_wallet` and bind this
     // signature to this exact registration (FM-A04/A05).
     let owner_lower = owner_wallet.to_lowercase();
-    let payload = relay_share_proxy::register_payload("share", &share_token, &owner_lower, &origin);
+    let peer_id = relay_register_peer_id(&state).await;
+    let payload = relay_share_proxy::register_payload_with_peer(
+        "share",
+        &share_token,
+        &owner_lower,
+        &origin,
+        peer_id.as_deref(),
+    );
     let signature = wallet::sign_message(&private_key, &payload)
         .map_err(|e| format!("Failed to sign register payload: {}", e))?;
 
@@ -7725,6 +8796,7 @@ async fn publish_drive_share(
             "origin_url": origin,
             "owner_wallet": owner_lower,
             "signature": signature,
+            "peer_id": peer_id,
         }))
         .timeout(std::time::Duration::from_secs(30))
         .send()
@@ -7736,7 +8808,16 @@ async fn publish_drive_share(
         return Err(format!("Relay error: {}", text));
     }
 
-    // Start WebSocket tunnel to relay for NAT traversal
+    // Serve the relay over libp2p, and start the WebSocket tunnel, for NAT
+    // traversal
+    state
+        .http_p2p
+        .publish(
+            "share",
+            &share_token,
+            &format!("http://127.0.0.1:{}", addr.port()),
+        )
+        .await;
     let tunnel_key = format!("share:{}", share_token);
     let abort_handle = spawn_relay_tunnel(
         relay_base.to_string(),
@@ -7800,6 +8881,7 @@ async fn unpublish_drive_share(
     if let Some(handle) = state.tunnel_handles.lock().await.remove(&tunnel_key) {
         handle.abort();
     }
+    state.http_p2p.unpublish("share", &share_token).await;
 
     println!("[DRIVE] Unpublished share token={} from relay", share_token);
     Ok(())
@@ -7891,6 +8973,8 @@ struct ReputationVerdictProof {
     owner_signature: String,
     updated_at: u64,
     verdict_signature: String,
+    /// Signed into the verdict; submit it alongside the signature.
+    issued_at: u64,
 }
 
 #[tauri::command]
@@ -8011,6 +9095,7 @@ fn compute_reputation_verdict_proof(
         .filter(|value| !value.is_empty())
         .map(str::to_string);
 
+    let now = rating_storage::now_secs()?;
     let signing_key = derive_reputation_issuer_key(&private_key)?;
     let verifying_key = hex::encode(signing_key.verifying_key().to_bytes());
     let owner_payload = reputation::issuer_key_binding_payload(&issuer_wallet, &verifying_key);
@@ -8024,6 +9109,7 @@ fn compute_reputation_verdict_proof(
         amount_wei,
         outcome,
         tx_hash,
+        issued_at: now,
     };
     let verdict_signature = signing_key.sign(&reputation::verdict_signing_payload(&verdict));
 
@@ -8031,8 +9117,86 @@ fn compute_reputation_verdict_proof(
         issuer_wallet,
         verifying_key,
         owner_signature,
-        updated_at: rating_storage::now_secs()?,
+        updated_at: now,
         verdict_signature: hex::encode(verdict_signature.to_bytes()),
+        issued_at: now,
+    })
+}
+
+/// Local reputation cache; reads and writes go through the DHT when it runs.
+fn local_rating_state(state: &AppState) -> rating_storage::RatingState {
+    rating_storage::RatingState::new_with_issuer_dht(
+        network::data_dir(),
+        Some(Arc::clone(&state.dht)),
+    )
+}
+
+/// Publish the issuer key, cache the verdict and gossip this node's
+/// verdict bundle for the seeder. Returns how many verdicts were published.
+#[tauri::command]
+async fn publish_reputation_verdict(
+    state: tauri::State<'_, AppState>,
+    verdict: reputation::SignedVerdict,
+    issuer_key: reputation::ReputationIssuerKeyRecord,
+) -> Result<usize, String> {
+    let now = rating_storage::now_secs()?;
+    reputation::verify_signed_verdict(&issuer_key, &verdict, now)?;
+    let ratings = local_rating_state(state.inner());
+    ratings.publish_issuer_key(issuer_key).await?;
+    let event = rating_storage::ReputationEvent::from_verdict(&verdict, now);
+    if rating_storage::merge_verdict_events(&mut ratings.manifest.write().await.events, vec![event])
+        > 0
+    {
+        ratings.persist().await;
+    }
+    ratings.refresh_chain_facts().await;
+    ratings
+        .publish_verdicts(&verdict.verdict.seeder_wallet)
+        .await
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct WalletReputation {
+    wallet: String,
+    #[serde(flatten)]
+    snapshot: rating_storage::ReputationSnapshot,
+    events: Vec<rating_storage::ReputationEvent>,
+}
+
+/// Recompute a wallet's Elo from verified verdicts gossiped in the DHT and
+/// the ones cached locally, without asking a rating server.
+#[tauri::command]
+async fn get_wallet_reputation(
+    state: tauri::State<'_, AppState>,
+    wallet: String,
+) -> Result<WalletReputation, String> {
+    let wallet = wallet.trim().to_lowercase();
+    if !reputation::is_valid_wallet(&wallet) {
+        return Err("wallet must be a 0x-prefixed 20-byte hex address".to_string());
+    }
+    let ratings = local_rating_state(state.inner());
+    ratings
+        .sync_verdicts_for(std::slice::from_ref(&wallet))
+        .await;
+    let now = rating_storage::now_secs()?;
+    let manifest = ratings.manifest.read().await;
+    let snapshot = rating_storage::compute_reputation_for_wallet(&manifest.events, &wallet, now);
+    let mut events: Vec<rating_storage::ReputationEvent> = manifest
+        .events
+        .iter()
+        .filter(|e| {
+            e.seeder_wallet.eq_ignore_ascii_case(&wallet)
+                || e.downloader_wallet.eq_ignore_ascii_case(&wallet)
+        })
+        .filter(|e| now.saturating_sub(e.created_at) <= rating_storage::LOOKBACK_SECS)
+        .cloned()
+        .collect();
+    events.sort_by_key(|e| std::cmp::Reverse(e.created_at));
+    Ok(WalletReputation {
+        wallet,
+        snapshot,
+        events,
     })
 }
 
@@ -8278,6 +9442,7 @@ pub fn run() {
             hosting_server_shutdown: Arc::clone(&hosting_shutdown_for_exit),
             drive_state: Arc::new(drive_api::DriveState::new()),
             tunnel_handles: Arc::clone(&tunnel_handles_for_exit),
+            http_p2p: http_p2p::HttpP2pServer::new(),
         })
         .setup(|app| {
             use tauri::Manager;
@@ -8292,6 +9457,9 @@ pub fn run() {
             // the libp2p Identify rejection in dht.rs, and the frontend
             // UpdateGate.
             tauri::async_runtime::spawn(fetch_and_log_remote_version_policy());
+            // Follow the canonical chain so wallet history and mined blocks
+            // are served from the local index instead of RPC scans.
+            tauri::async_runtime::spawn(chain_index::run(geth::wallet_rpc_endpoint));
             // Auto-start local server with Drive routes on port 9419
             let state = app.state::<AppState>();
             let hosting: Arc<hosting_server::HostingServerState> =
@@ -8303,6 +9471,7 @@ pub fn run() {
                 Arc::clone(&state.hosting_server_shutdown);
             let tunnel_handles: Arc<Mutex<HashMap<String, tokio::task::AbortHandle>>> =
                 Arc::clone(&state.tunnel_handles);
+            let http_p2p_server = state.http_p2p.clone();
             let app_for_boot = app_handle.clone();
             tauri::async_runtime::spawn(async move {
                 hosting.load_from_disk().await;
@@ -8339,6 +9508,9 @@ pub fn run() {
                         for site in &sites {
                             if site.relay_url.is_some() {
                                 println!("[TUNNEL] Re-establishing tunnel for site {}", site.id);
+                                http_p2p_server
+                                    .publish("site", &site.id, &local_origin)
+                                    .await;
                                 let abort = spawn_relay_tunnel(
                                     relay_base.to_string(),
                                     "site".to_string(),
@@ -8364,6 +9536,8 @@ pub fn run() {
             stop_dht,
             get_dht_peers,
             get_network_stats,
+            get_bandwidth_limits,
+            set_bandwidth_limits,
             get_peer_id,
             get_dht_health,
             get_bootstrap_peer_ids,
@@ -8372,6 +9546,8 @@ pub fn run() {
             send_file_by_path,
             accept_file_transfer,
             decline_file_transfer,
+            cancel_file_transfer,
+            cancel_download,
             store_dht_value,
             get_dht_value,
             // File commands
@@ -8384,7 +9560,11 @@ pub fn run() {
             publish_file,
             publish_file_data,
             search_file,
+            search_files_by_keyword,
             start_download,
+            rank_download_seeders,
+            settle_payment_vouchers,
+            get_payment_voucher_summary,
             calculate_download_cost,
             register_shared_file,
             parse_torrent_file,
@@ -8415,6 +9595,7 @@ pub fn run() {
             start_mining,
             stop_mining,
             get_mining_status,
+            get_mined_blocks,
             get_gpu_mining_capabilities,
             list_gpu_devices,
             start_gpu_mining,
@@ -8428,6 +9609,8 @@ pub fn run() {
             get_version_status,
             compute_owner_proof,
             compute_reputation_verdict_proof,
+            publish_reputation_verdict,
+            get_wallet_reputation,
             compute_relay_register_signature,
             get_mining_balance_diagnostic,
             // Bootstrap health commands
@@ -8439,8 +9622,12 @@ pub fn run() {
             encrypt_file_for_recipient,
             decrypt_file_data,
             send_encrypted_file,
+            decrypt_encrypted_file,
             publish_encryption_key,
             lookup_encryption_key,
+            encrypt_file_for_peers,
+            update_envelope_recipients,
+            get_envelope_recipients,
             // Hosting commands
             create_hosted_site,
             list_hosted_sites,
@@ -8462,6 +9649,7 @@ pub fn run() {
             drive_read_file_bytes,
             drive_upload_file,
             drive_update_item,
+            drive_set_item_metadata,
             drive_delete_item,
             drive_create_share,
             drive_revoke_share,
@@ -9006,21 +10194,54 @@ mod multi_seeder_tests {
 
     #[test]
     fn desktop_tunnel_request_frame_accepts_valid_request() {
-        let req = parse_desktop_tunnel_request_frame(r#"{"id":"req-1","path":"/index.html"}"#)
-            .expect("valid tunnel request frame should parse");
+        let frame = parse_desktop_tunnel_frame(
+            r#"{"type":"request","id":"req-1","method":"POST","path":"/drive/tok/upload","headers":[["cookie","a=1"],["cookie","b=2"]],"hasBody":true}"#,
+        )
+        .expect("valid tunnel request frame should parse");
+
+        assert_eq!(
+            frame,
+            relay_share_proxy::TunnelRelayFrame::Request {
+                id: "req-1".to_string(),
+                method: "POST".to_string(),
+                path: "/drive/tok/upload".to_string(),
+                headers: vec![
+                    ("cookie".to_string(), "a=1".to_string()),
+                    ("cookie".to_string(), "b=2".to_string()),
+                ],
+                has_body: true,
+            }
+        );
+    }
+
+    #[test]
+    fn desktop_tunnel_frame_accepts_control_frames() {
+        use relay_share_proxy::TunnelRelayFrame;
 
         assert_eq!(
-            req,
-            DesktopTunnelRequest {
+            parse_desktop_tunnel_frame(r#"{"type":"request-end","id":"req-1"}"#).unwrap(),
+            TunnelRelayFrame::RequestEnd {
+                id: "req-1".to_string()
+            }
+        );
+        assert_eq!(
+            parse_desktop_tunnel_frame(r#"{"type":"window","id":"req-1","credits":3}"#).unwrap(),
+            TunnelRelayFrame::Window {
                 id: "req-1".to_string(),
-                path: "/index.html".to_string(),
+                credits: 3
+            }
+        );
+        assert_eq!(
+            parse_desktop_tunnel_frame(r#"{"type":"cancel","id":"req-1"}"#).unwrap(),
+            TunnelRelayFrame::Cancel {
+                id: "req-1".to_string()
             }
         );
     }
 
     #[test]
     fn desktop_tunnel_request_frame_reports_malformed_json_without_recoverable_id() {
-        let err = parse_desktop_tunnel_request_frame(r#"{"id":"req-1","path":"#)
+        let err = parse_desktop_tunnel_frame(r#"{"type":"request","id":"req-1","path":"#)
             .expect_err("malformed JSON should be rejected");
 
         assert_eq!(err.id, None);
@@ -9029,47 +10250,396 @@ mod multi_seeder_tests {
 
     #[test]
     fn desktop_tunnel_request_frame_reports_missing_required_fields() {
-        let missing_path = parse_desktop_tunnel_request_frame(r#"{"id":"req-2"}"#)
-            .expect_err("missing path should be rejected");
+        let missing_path =
+            parse_desktop_tunnel_frame(r#"{"type":"request","id":"req-2","method":"GET"}"#)
+                .expect_err("missing path should be rejected");
         assert_eq!(missing_path.id.as_deref(), Some("req-2"));
         assert!(missing_path.message.contains("path"));
 
-        let missing_id = parse_desktop_tunnel_request_frame(r#"{"path":"/index.html"}"#)
-            .expect_err("missing id should be rejected");
+        let missing_id =
+            parse_desktop_tunnel_frame(r#"{"type":"request","method":"GET","path":"/"}"#)
+                .expect_err("missing id should be rejected");
         assert_eq!(missing_id.id, None);
         assert!(missing_id.message.contains("id"));
+
+        let missing_type = parse_desktop_tunnel_frame(r#"{"id":"req-3","path":"/index.html"}"#)
+            .expect_err("untagged legacy frames should be rejected");
+        assert_eq!(missing_type.id.as_deref(), Some("req-3"));
+        assert!(missing_type.message.contains("type"));
     }
 
     #[test]
     fn desktop_tunnel_request_frame_rejects_irrecoverable_request_ids() {
-        let blank_id = parse_desktop_tunnel_request_frame(r#"{"id":"  ","path":"/"}"#)
-            .expect_err("blank id should be rejected");
+        let blank_id =
+            parse_desktop_tunnel_frame(r#"{"type":"request","id":"  ","method":"GET","path":"/"}"#)
+                .expect_err("blank id should be rejected");
 
         assert_eq!(blank_id.id, None);
         assert!(blank_id.message.contains("id"));
     }
 
     #[test]
-    fn desktop_tunnel_error_response_encodes_recovered_request_id() {
-        use base64::Engine;
+    fn desktop_tunnel_request_frame_rejects_invalid_methods() {
+        let err = parse_desktop_tunnel_frame(
+            r#"{"type":"request","id":"req-4","method":"GE T","path":"/"}"#,
+        )
+        .expect_err("invalid method should be rejected");
+
+        assert_eq!(err.id.as_deref(), Some("req-4"));
+        assert!(err.message.contains("method"));
+    }
 
+    #[test]
+    fn desktop_tunnel_error_response_encodes_recovered_request_id() {
         let frame = desktop_tunnel_error_response_text("req-3", "missing path");
         let parsed: serde_json::Value = serde_json::from_str(&frame).unwrap();
 
+        assert_eq!(parsed["type"], "error");
         assert_eq!(parsed["id"], "req-3");
-        assert_eq!(parsed["status"], 502);
+        let message = parsed["message"].as_str().unwrap();
+        assert!(message.contains("Malformed tunnel request frame from relay"));
+        assert!(message.contains("missing path"));
+    }
+
+    fn desktop_tunnel_test_target() -> DesktopTunnelTarget {
+        DesktopTunnelTarget {
+            resource_type: "share".to_string(),
+            resource_id: "tok".to_string(),
+            local_origin: "http://127.0.0.1:9419".to_string(),
+        }
+    }
+
+    #[test]
+    fn desktop_tunnel_local_request_forwards_method_and_end_to_end_headers() {
+        let client = reqwest::Client::new();
+        let headers = vec![
+            ("cookie".to_string(), "session=abc".to_string()),
+            ("range".to_string(), "bytes=0-9".to_string()),
+            ("host".to_string(), "relay.example".to_string()),
+            ("connection".to_string(), "close".to_string()),
+        ];
+
+        let request = desktop_tunnel_local_request(
+            &client,
+            &desktop_tunnel_test_target(),
+            "PUT",
+            "/drive/tok/notes.txt?v=2",
+            &headers,
+            None,
+        )
+        .expect("in-scope request should build")
+        .build()
+        .unwrap();
+
+        assert_eq!(request.method(), reqwest::Method::PUT);
         assert_eq!(
-            parsed["headers"]["content-type"],
-            "text/plain; charset=utf-8"
+            request.url().as_str(),
+            "http://127.0.0.1:9419/drive/tok/notes.txt?v=2"
+        );
+        assert_eq!(request.headers()["cookie"], "session=abc");
+        assert_eq!(request.headers()["range"], "bytes=0-9");
+        assert!(request.headers().get("host").is_none());
+        assert!(request.headers().get("connection").is_none());
+    }
+
+    #[test]
+    fn desktop_tunnel_local_request_stays_inside_the_shared_resource() {
+        let client = reqwest::Client::new();
+        let target = desktop_tunnel_test_target();
+
+        for path in [
+            "/drive/tok/../../api/drive/items",
+            "/drive/other/file.txt",
+            "/sites/tok/",
+            "@evil.example/drive/tok",
+            "//evil.example/drive/tok",
+        ] {
+            let err = desktop_tunnel_local_request(&client, &target, "GET", path, &[], None)
+                .expect_err("out-of-scope paths must be rejected");
+            assert!(!err.is_empty(), "{path}");
+        }
+    }
+
+    #[test]
+    fn desktop_tunnel_response_headers_drop_hop_by_hop_and_keep_repeats() {
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert("content-type", "text/html".parse().unwrap());
+        headers.insert("transfer-encoding", "chunked".parse().unwrap());
+        headers.append("set-cookie", "a=1".parse().unwrap());
+        headers.append("set-cookie", "b=2".parse().unwrap());
+
+        let forwarded = desktop_tunnel_response_headers(&headers);
+
+        assert!(forwarded.contains(&("content-type".to_string(), "text/html".to_string())));
+        assert!(forwarded.contains(&("set-cookie".to_string(), "a=1".to_string())));
+        assert!(forwarded.contains(&("set-cookie".to_string(), "b=2".to_string())));
+        assert!(!forwarded
+            .iter()
+            .any(|(name, _)| name == "transfer-encoding"));
+    }
+
+    fn desktop_tunnel_test_stream(
+        capacity: usize,
+    ) -> (
+        DesktopTunnelStream,
+        tokio::sync::mpsc::Receiver<Result<axum::body::Bytes, std::io::Error>>,
+    ) {
+        let (tx, rx) = tokio::sync::mpsc::channel(capacity);
+        let stream = DesktopTunnelStream {
+            body: Some(tx),
+            credits: Arc::new(tokio::sync::Semaphore::new(1)),
+            task: tokio::spawn(std::future::pending()),
+        };
+        (stream, rx)
+    }
+
+    #[tokio::test]
+    async fn desktop_tunnel_chunk_feeds_local_request_body() {
+        let (stream, mut rx) = desktop_tunnel_test_stream(4);
+        let mut streams = HashMap::new();
+        streams.insert("req-1".to_string(), stream);
+
+        let frame = relay_share_proxy::encode_tunnel_chunk("req-1", b"form=data").unwrap();
+        route_desktop_tunnel_chunk(&mut streams, &frame).unwrap();
+
+        assert_eq!(rx.try_recv().unwrap().unwrap().as_ref(), b"form=data");
+
+        let unknown = relay_share_proxy::encode_tunnel_chunk("req-9", b"x").unwrap();
+        let err = route_desktop_tunnel_chunk(&mut streams, &unknown).unwrap_err();
+        assert_eq!(err.id, None);
+
+        streams.get_mut("req-1").unwrap().body = None;
+        let err = route_desktop_tunnel_chunk(&mut streams, &frame).unwrap_err();
+        assert_eq!(err.id.as_deref(), Some("req-1"));
+        assert!(err.message.contains("after request-end"));
+    }
+
+    #[tokio::test]
+    async fn desktop_tunnel_chunk_overflow_aborts_the_request() {
+        let (stream, _rx) = desktop_tunnel_test_stream(1);
+        let mut streams = HashMap::new();
+        streams.insert("req-1".to_string(), stream);
+        let frame = relay_share_proxy::encode_tunnel_chunk("req-1", b"x").unwrap();
+
+        route_desktop_tunnel_chunk(&mut streams, &frame).unwrap();
+        let err = route_desktop_tunnel_chunk(&mut streams, &frame).unwrap_err();
+
+        assert_eq!(err.id.as_deref(), Some("req-1"));
+        assert!(err.message.contains("exceeds"));
+        assert!(streams.is_empty());
+    }
+
+    #[tokio::test]
+    async fn desktop_tunnel_chunk_after_local_response_is_ignored() {
+        let (stream, rx) = desktop_tunnel_test_stream(4);
+        drop(rx);
+        let mut streams = HashMap::new();
+        streams.insert("req-1".to_string(), stream);
+        let frame = relay_share_proxy::encode_tunnel_chunk("req-1", b"late").unwrap();
+
+        assert!(route_desktop_tunnel_chunk(&mut streams, &frame).is_ok());
+    }
+
+    /// A visitor request through the real relay router reaches the owner's
+    /// local server via `spawn_relay_tunnel` with its method, headers and
+    /// body, and a response larger than the window streams back intact.
+    #[tokio::test]
+    async fn relay_tunnel_round_trips_methods_headers_and_streamed_bodies() {
+        use axum::routing::{any, get};
+
+        let big: Vec<u8> = (0..3 * 1024 * 1024).map(|i| (i % 251) as u8).collect();
+        let local_app = axum::Router::new()
+            .route(
+                "/drive/tok/echo",
+                any(
+                    |method: axum::http::Method,
+                     headers: axum::http::HeaderMap,
+                     body: axum::body::Bytes| async move {
+                        let cookie = headers
+                            .get("cookie")
+                            .and_then(|v| v.to_str().ok())
+                            .unwrap_or_default()
+                            .to_string();
+                        (
+                            [("x-method", method.to_string()), ("x-cookie", cookie)],
+                            body,
+                        )
+                    },
+                ),
+            )
+            .route(
+                "/drive/tok/big",
+                get({
+                    let big = big.clone();
+                    move || async move { big }
+                }),
+            );
+        let local = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let local_addr = local.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(local, local_app).await.unwrap() });
+
+        let dir = tempfile::tempdir().unwrap();
+        let registry = Arc::new(relay_share_proxy::RelayShareRegistry::new(
+            dir.path().to_path_buf(),
+        ));
+        registry
+            .register(relay_share_proxy::ShareRegistration {
+                token: "tok".to_string(),
+                // Unreachable, so only the tunnel can answer.
+                origin_url: "http://127.0.0.1:9".to_string(),
+                owner_wallet: "0xWALLET".to_string(),
+                registered_at: 0,
+                peer_id: None,
+            })
+            .await;
+        let relay_app = relay_share_proxy::relay_share_routes(
+            registry,
+            Arc::new(relay_share_proxy::TunnelRegistry::new()),
+        );
+        let relay = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let relay_addr = relay.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(
+                relay,
+                relay_app.into_make_service_with_connect_info::<std::net::SocketAddr>(),
+            )
+            .await
+            .unwrap()
+        });
+
+        let tunnel = spawn_relay_tunnel(
+            format!("http://{}", relay_addr),
+            "share".to_string(),
+            "tok".to_string(),
+            format!("http://{}", local_addr),
         );
+        let client = reqwest::Client::new();
+        let echo_url = format!("http://{}/drive/tok/echo", relay_addr);
+        let mut connected = false;
+        for _ in 0..50 {
+            let resp = client.get(&echo_url).send().await.unwrap();
+            if resp.status().is_success() {
+                connected = true;
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+        assert!(connected, "tunnel never came up");
+
+        let upload: Vec<u8> = (0..200 * 1024).map(|i| (i % 7) as u8).collect();
+        let resp = client
+            .post(&echo_url)
+            .header("cookie", "session=abc")
+            .body(upload.clone())
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), reqwest::StatusCode::OK);
+        assert_eq!(resp.headers()["x-method"], "POST");
+        assert_eq!(resp.headers()["x-cookie"], "session=abc");
+        assert_eq!(resp.bytes().await.unwrap().as_ref(), upload.as_slice());
+
+        let resp = client
+            .get(format!("http://{}/drive/tok/big", relay_addr))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), reqwest::StatusCode::OK);
+        assert_eq!(resp.bytes().await.unwrap().as_ref(), big.as_slice());
+
+        tunnel.abort();
+    }
 
-        let body = parsed["body"].as_str().unwrap();
-        let decoded = base64::engine::general_purpose::STANDARD
-            .decode(body)
+    /// Run `stream_desktop_tunnel_response` against `url` and collect every
+    /// message it queued for the relay.
+    async fn desktop_tunnel_response_messages(
+        url: String,
+    ) -> Vec<tokio_tungstenite::tungstenite::Message> {
+        let (out_tx, mut out_rx) = tokio::sync::mpsc::channel(64);
+        let credits = tokio::sync::Semaphore::new(64);
+        let request = reqwest::Client::new().get(url);
+        stream_desktop_tunnel_response(request, "req-1", &out_tx, &credits)
+            .await
             .unwrap();
-        let text = String::from_utf8(decoded).unwrap();
-        assert!(text.contains("Malformed tunnel request frame from relay"));
-        assert!(text.contains("missing path"));
+        drop(out_tx);
+        let mut messages = Vec::new();
+        while let Some(message) = out_rx.recv().await {
+            messages.push(message);
+        }
+        messages
+    }
+
+    fn desktop_tunnel_owner_frame(
+        message: &tokio_tungstenite::tungstenite::Message,
+    ) -> relay_share_proxy::TunnelOwnerFrame {
+        match message {
+            tokio_tungstenite::tungstenite::Message::Text(text) => {
+                serde_json::from_str(text).unwrap()
+            }
+            other => panic!("expected a control frame, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn desktop_tunnel_response_reports_a_body_that_fails_mid_stream() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // Promise 100 bytes, send 5, then hang up.
+        let local = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let local_addr = local.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = local.accept().await.unwrap();
+            let mut buf = [0u8; 1024];
+            let _ = socket.read(&mut buf).await;
+            socket
+                .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 100\r\n\r\nhello")
+                .await
+                .unwrap();
+        });
+
+        let messages = desktop_tunnel_response_messages(format!("http://{}/", local_addr)).await;
+
+        assert!(matches!(
+            desktop_tunnel_owner_frame(&messages[0]),
+            relay_share_proxy::TunnelOwnerFrame::Response { status: 200, .. }
+        ));
+        match desktop_tunnel_owner_frame(messages.last().unwrap()) {
+            relay_share_proxy::TunnelOwnerFrame::Error { id, message } => {
+                assert_eq!(id, "req-1");
+                assert!(message.contains("body read failed"), "{}", message);
+            }
+            other => panic!("expected an error frame, got {:?}", other),
+        }
+        assert!(!messages
+            .iter()
+            .filter(|message| message.is_text())
+            .any(|message| matches!(
+                desktop_tunnel_owner_frame(message),
+                relay_share_proxy::TunnelOwnerFrame::ResponseEnd { .. }
+            )));
+    }
+
+    #[tokio::test]
+    async fn desktop_tunnel_response_with_empty_body_sends_only_head_and_end() {
+        let local_app = axum::Router::new().route("/empty", axum::routing::get(|| async { "" }));
+        let local = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let local_addr = local.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(local, local_app).await.unwrap() });
+
+        let messages =
+            desktop_tunnel_response_messages(format!("http://{}/empty", local_addr)).await;
+
+        assert_eq!(messages.len(), 2, "{:?}", messages);
+        assert!(matches!(
+            desktop_tunnel_owner_frame(&messages[0]),
+            relay_share_proxy::TunnelOwnerFrame::Response { ref id, status: 200, .. } if id == "req-1"
+        ));
+        assert_eq!(
+            desktop_tunnel_owner_frame(&messages[1]),
+            relay_share_proxy::TunnelOwnerFrame::ResponseEnd {
+                id: "req-1".to_string()
+            }
+        );
     }
 
     #[test]
@@ -9122,57 +10692,6 @@ mod multi_seeder_tests {
         );
     }
 
-    #[test]
-    fn relay_tunnel_response_preserves_valid_local_body() {
-        let mut headers = HashMap::new();
-        headers.insert("content-type".to_string(), "text/html".to_string());
-
-        let response =
-            relay_tunnel_response_from_local_body(200, headers.clone(), Ok(b"hello".to_vec()));
-
-        assert_eq!(response.status, 200);
-        assert_eq!(response.headers, headers);
-        assert_eq!(response.body_bytes, b"hello".to_vec());
-    }
-
-    #[test]
-    fn relay_tunnel_response_preserves_empty_local_body() {
-        let mut headers = HashMap::new();
-        headers.insert("x-empty".to_string(), "true".to_string());
-
-        let response = relay_tunnel_response_from_local_body(204, headers.clone(), Ok(Vec::new()));
-
-        assert_eq!(response.status, 204);
-        assert_eq!(response.headers, headers);
-        assert!(response.body_bytes.is_empty());
-    }
-
-    #[test]
-    fn relay_tunnel_response_body_read_error_returns_controlled_502() {
-        let mut headers = HashMap::new();
-        headers.insert(
-            "x-upstream".to_string(),
-            "preserved only on success".to_string(),
-        );
-
-        let response = relay_tunnel_response_from_local_body(
-            200,
-            headers,
-            Err("connection reset".to_string()),
-        );
-
-        assert_eq!(response.status, 502);
-        assert_eq!(
-            response.headers.get("content-type").map(String::as_str),
-            Some("text/plain; charset=utf-8")
-        );
-        assert!(!response.headers.contains_key("x-upstream"));
-
-        let body = String::from_utf8(response.body_bytes).unwrap();
-        assert!(body.contains("Local response body read failed"));
-        assert!(body.contains("connection reset"));
-    }
-
     fn host_registry_entry(
         peer_id: &str,
         wallet_address: &str,
@@ -9182,6 +10701,7 @@ mod multi_seeder_tests {
             peer_id: peer_id.to_string(),
             wallet_address: wallet_address.to_string(),
             updated_at,
+            signature: String::new(),
         }
     }
 
@@ -9303,6 +10823,123 @@ mod multi_seeder_tests {
         assert!(err.contains("websocket closed"));
     }
 
+    fn seeder_record(peer_id: &str, wallet: &str, price_wei: &str) -> SeederInfo {
+        SeederInfo {
+            peer_id: peer_id.to_string(),
+            price_wei: price_wei.to_string(),
+            wallet_address: wallet.to_string(),
+            multiaddrs: vec![],
+            signature: String::new(),
+        }
+    }
+
+    fn ranked_candidates(
+        records: &[SeederInfo],
+        elos: &[(&str, f64)],
+    ) -> Vec<seeder_selection::SeederCandidate> {
+        let elos: HashMap<String, f64> = elos.iter().map(|(w, e)| (w.to_string(), *e)).collect();
+        let peer_ids: Vec<String> = records.iter().map(|r| r.peer_id.clone()).collect();
+        seeder_candidates(&peer_ids, records, &elos, |_| None)
+    }
+
+    #[test]
+    fn seeder_candidates_attach_elo_by_lowercase_wallet() {
+        let records = vec![seeder_record("peer-a", "0xAAA", "5")];
+        let elos: HashMap<String, f64> = [("0xaaa".to_string(), 72.0)].into();
+        let candidates = seeder_candidates(
+            &["peer-a".to_string(), "peer-b".to_string()],
+            &records,
+            &elos,
+            |_| None,
+        );
+        assert_eq!(candidates.len(), 2);
+        assert_eq!(candidates[0].elo, Some(72.0));
+        assert_eq!(candidates[0].price_wei, "5");
+        assert_eq!(candidates[1].peer_id, "peer-b");
+        assert_eq!(candidates[1].elo, None);
+        assert!(candidates[1].wallet_address.is_empty());
+    }
+
+    #[test]
+    fn choose_seeder_keeps_explicit_choice_and_flags_override() {
+        let records = vec![
+            seeder_record("peer-a", "0xa", "0"),
+            seeder_record("peer-b", "0xb", "0"),
+        ];
+        let candidates = ranked_candidates(&records, &[("0xa", 90.0), ("0xb", 55.0)]);
+        let (chosen, selection) =
+            choose_seeder(&["peer-b".to_string()], &candidates, 0.0, None).unwrap();
+        assert_eq!(chosen, "peer-b");
+        let selection = selection.unwrap();
+        assert!(selection.manual);
+        assert_eq!(selection.candidates, 2);
+    }
+
+    #[test]
+    fn choose_seeder_rejects_explicit_choice_below_minimum() {
+        let records = vec![seeder_record("peer-a", "0xa", "0")];
+        let candidates = ranked_candidates(&records, &[("0xa", 30.0)]);
+        let err = choose_seeder(&["peer-a".to_string()], &candidates, 60.0, None).unwrap_err();
+        assert!(err.contains("below your minimum of 60"));
+    }
+
+    #[test]
+    fn choose_seeder_picks_best_affordable_among_several() {
+        let records = vec![
+            seeder_record("peer-a", "0xa", "110"),
+            seeder_record("peer-b", "0xb", "100"),
+            seeder_record("peer-c", "0xc", "500"),
+        ];
+        let candidates =
+            ranked_candidates(&records, &[("0xa", 95.0), ("0xb", 70.0), ("0xc", 40.0)]);
+        let requested: Vec<String> = records.iter().map(|r| r.peer_id.clone()).collect();
+
+        // peer-a ranks first but costs more than the authorised price.
+        let (chosen, selection) = choose_seeder(&requested, &candidates, 0.0, Some(100)).unwrap();
+        assert_eq!(chosen, "peer-b");
+        assert!(selection.unwrap().manual);
+
+        let (chosen, selection) = choose_seeder(&requested, &candidates, 0.0, None).unwrap();
+        assert_eq!(chosen, "peer-a");
+        let selection = selection.unwrap();
+        assert!(!selection.manual);
+        assert_eq!(
+            selection.picked_by.first().map(String::as_str),
+            Some("reputation")
+        );
+    }
+
+    #[test]
+    fn choose_seeder_fails_when_nobody_meets_minimum() {
+        let records = vec![
+            seeder_record("peer-a", "0xa", "0"),
+            seeder_record("peer-b", "0xb", "0"),
+        ];
+        let candidates = ranked_candidates(&records, &[("0xa", 40.0)]);
+        let requested: Vec<String> = records.iter().map(|r| r.peer_id.clone()).collect();
+        let err = choose_seeder(&requested, &candidates, 80.0, None).unwrap_err();
+        assert!(err.contains("minimum reputation of 80"));
+    }
+
+    #[test]
+    fn min_seeder_reputation_is_clamped_and_validated() {
+        assert_eq!(normalize_min_seeder_reputation(None), Ok(0.0));
+        assert_eq!(normalize_min_seeder_reputation(Some(150.0)), Ok(100.0));
+        assert_eq!(normalize_min_seeder_reputation(Some(-3.0)), Ok(0.0));
+        assert!(normalize_min_seeder_reputation(Some(f64::NAN)).is_err());
+    }
+
+    #[test]
+    fn download_start_result_omits_missing_selection() {
+        let json = serde_json::to_value(DownloadStartResult {
+            request_id: "r".to_string(),
+            status: "downloading".to_string(),
+            selection: None,
+        })
+        .unwrap();
+        assert!(json.get("selection").is_none());
+    }
+
     #[test]
     fn seeder_info_roundtrip() {
         let seeder = SeederInfo {
@@ -9999,6 +11636,8 @@ mod multi_seeder_tests {
             storage_path: Some(format!("{}.bin", id)),
             owner: owner.to_string(),
             is_public: true,
+            description: None,
+            tags: Vec::new(),
             merkle_root: None,
             protocol: None,
             price_chi: None,
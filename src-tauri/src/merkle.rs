@@ -0,0 +1,360 @@
+//! Merkle commitments over fixed-size file chunks.
+//!
+//! A file's content hash is the commitment
+//! `SHA-256("chiral-merkle-v1" || file_size_le || tree_root)`, where the
+//! tree is built over `CHUNK_SIZE` leaves. Leaves and interior nodes are
+//! domain-separated (`0x00` / `0x01` prefixes) and an odd node at the end of
+//! a level is promoted unchanged. Binding the file size fixes the tree
+//! shape, so an inclusion proof pins a chunk to exactly one offset.
+//!
+//! Files published before Merkle commitments are addressed by the plain
+//! SHA-256 of their bytes; [`MerkleTree::matches_hash`] accepts either so
+//! those hashes keep resolving.
+
+use sha2::{Digest, Sha256};
+use std::io::Read;
+use std::path::Path;
+
+/// Leaf size. Matches the chunk size of the `/chiral/file-request` protocol.
+pub const CHUNK_SIZE: usize = 256 * 1024;
+
+const COMMITMENT_TAG: &[u8] = b"chiral-merkle-v1";
+
+type Hash = [u8; 32];
+
+/// Number of chunks a file of `file_size` bytes is split into.
+pub fn chunk_count(file_size: u64) -> u32 {
+    file_size.div_ceil(CHUNK_SIZE as u64) as u32
+}
+
+/// Length of chunk `index`, or `None` when the index is past the end.
+pub fn chunk_len(file_size: u64, index: u32) -> Option<usize> {
+    let offset = index as u64 * CHUNK_SIZE as u64;
+    if offset >= file_size {
+        return None;
+    }
+    Some(std::cmp::min(CHUNK_SIZE as u64, file_size - offset) as usize)
+}
+
+pub fn leaf_hash(chunk: &[u8]) -> Hash {
+    let mut hasher = Sha256::new();
+    hasher.update([0u8]);
+    hasher.update(chunk);
+    hasher.finalize().into()
+}
+
+fn node_hash(left: &Hash, right: &Hash) -> Hash {
+    let mut hasher = Sha256::new();
+    hasher.update([1u8]);
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().into()
+}
+
+fn commitment(file_size: u64, tree_root: &Hash) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(COMMITMENT_TAG);
+    hasher.update(file_size.to_le_bytes());
+    hasher.update(tree_root);
+    hex::encode(hasher.finalize())
+}
+
+#[derive(Debug, Clone)]
+pub struct MerkleTree {
+    file_size: u64,
+    /// `levels[0]` holds the leaves; the last level holds the single root.
+    levels: Vec<Vec<Hash>>,
+    /// Plain SHA-256 of the whole file, when the tree was built from its
+    /// bytes. Lets seeders keep serving files published under that hash.
+    content_sha256: Option<String>,
+}
+
+impl MerkleTree {
+    /// Build the tree from precomputed leaf hashes. An empty file has a
+    /// single leaf over the empty chunk.
+    pub fn from_leaves(file_size: u64, mut leaves: Vec<Hash>) -> Self {
+        if leaves.is_empty() {
+            leaves.push(leaf_hash(&[]));
+        }
+        let mut levels = vec![leaves];
+        while levels.last().map(|l| l.len()).unwrap_or(0) > 1 {
+            let next = levels
+                .last()
+                .unwrap()
+                .chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => node_hash(left, right),
+                    [single] => *single,
+                    _ => unreachable!(),
+                })
+                .collect();
+            levels.push(next);
+        }
+        Self {
+            file_size,
+            levels,
+            content_sha256: None,
+        }
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Self {
+        let leaves = data.chunks(CHUNK_SIZE).map(leaf_hash).collect();
+        let mut tree = Self::from_leaves(data.len() as u64, leaves);
+        tree.content_sha256 = Some(hex::encode(Sha256::digest(data)));
+        tree
+    }
+
+    /// Stream `reader` chunk by chunk, computing the tree and the plain
+    /// SHA-256 in a single pass.
+    pub fn from_reader(mut reader: impl Read) -> std::io::Result<Self> {
+        let mut leaves = Vec::new();
+        let mut content = Sha256::new();
+        let mut file_size = 0u64;
+        let mut buf = vec![0u8; CHUNK_SIZE];
+        loop {
+            let mut filled = 0;
+            while filled < CHUNK_SIZE {
+                let n = reader.read(&mut buf[filled..])?;
+                if n == 0 {
+                    break;
+                }
+                filled += n;
+            }
+            if filled == 0 {
+                break;
+            }
+            leaves.push(leaf_hash(&buf[..filled]));
+            content.update(&buf[..filled]);
+            file_size += filled as u64;
+            if filled < CHUNK_SIZE {
+                break;
+            }
+        }
+        let mut tree = Self::from_leaves(file_size, leaves);
+        tree.content_sha256 = Some(hex::encode(content.finalize()));
+        Ok(tree)
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        let file = std::fs::File::open(path)
+            .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+        Self::from_reader(std::io::BufReader::new(file))
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))
+    }
+
+    pub fn file_size(&self) -> u64 {
+        self.file_size
+    }
+
+    pub fn chunk_count(&self) -> u32 {
+        chunk_count(self.file_size)
+    }
+
+    /// The content hash committing to this tree and the file size.
+    pub fn root(&self) -> String {
+        commitment(self.file_size, &self.levels.last().unwrap()[0])
+    }
+
+    pub fn content_sha256(&self) -> Option<&str> {
+        self.content_sha256.as_deref()
+    }
+
+    /// Whether `file_hash` addresses these bytes, either as the Merkle
+    /// commitment or as a legacy whole-file SHA-256.
+    pub fn matches_hash(&self, file_hash: &str) -> bool {
+        self.root().eq_ignore_ascii_case(file_hash)
+            || self
+                .content_sha256
+                .as_deref()
+                .is_some_and(|sha| sha.eq_ignore_ascii_case(file_hash))
+    }
+
+    /// Sibling hashes from leaf `index` up to the root, hex-encoded.
+    pub fn proof(&self, index: u32) -> Option<Vec<String>> {
+        if index >= self.chunk_count() {
+            return None;
+        }
+        let mut idx = index as usize;
+        let mut proof = Vec::new();
+        for level in &self.levels[..self.levels.len() - 1] {
+            let sibling = idx ^ 1;
+            if sibling < level.len() {
+                proof.push(hex::encode(level[sibling]));
+            }
+            idx /= 2;
+        }
+        Some(proof)
+    }
+}
+
+/// Check that `data` is chunk `index` of the file committed to by `root`.
+/// Returns the chunk's leaf hash.
+pub fn verify_chunk(
+    root: &str,
+    file_size: u64,
+    index: u32,
+    data: &[u8],
+    proof: &[String],
+) -> Result<Hash, String> {
+    let expected_len = chunk_len(file_size, index)
+        .ok_or_else(|| format!("chunk {} is past the end of the file", index))?;
+    if data.len() != expected_len {
+        return Err(format!(
+            "chunk {} is {} bytes, expected {}",
+            index,
+            data.len(),
+            expected_len
+        ));
+    }
+
+    let leaf = leaf_hash(data);
+    let mut acc = leaf;
+    let mut idx = index as usize;
+    let mut width = chunk_count(file_size) as usize;
+    let mut siblings = proof.iter();
+    while width > 1 {
+        let sibling = idx ^ 1;
+        if sibling < width {
+            let hex_sibling = siblings
+                .next()
+                .ok_or_else(|| format!("inclusion proof for chunk {} is too short", index))?;
+            let sibling_hash: Hash = hex::decode(hex_sibling)
+                .ok()
+                .and_then(|bytes| bytes.try_into().ok())
+                .ok_or_else(|| format!("inclusion proof for chunk {} is malformed", index))?;
+            acc = if idx.is_multiple_of(2) {
+                node_hash(&acc, &sibling_hash)
+            } else {
+                node_hash(&sibling_hash, &acc)
+            };
+        }
+        idx /= 2;
+        width = width.div_ceil(2);
+    }
+    if siblings.next().is_some() {
+        return Err(format!("inclusion proof for chunk {} is too long", index));
+    }
+    if !commitment(file_size, &acc).eq_ignore_ascii_case(root) {
+        return Err(format!("chunk {} does not match the Merkle root", index));
+    }
+    Ok(leaf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i * 31 % 251) as u8).collect()
+    }
+
+    #[test]
+    fn every_chunk_verifies_against_the_root() {
+        for chunks in [1usize, 2, 3, 5, 8] {
+            let data = sample(CHUNK_SIZE * chunks - 7);
+            let tree = MerkleTree::from_bytes(&data);
+            let root = tree.root();
+            assert_eq!(tree.chunk_count() as usize, chunks);
+            for (index, chunk) in data.chunks(CHUNK_SIZE).enumerate() {
+                let proof = tree.proof(index as u32).unwrap();
+                let leaf = verify_chunk(&root, data.len() as u64, index as u32, chunk, &proof)
+                    .unwrap_or_else(|e| panic!("{} chunks, index {}: {}", chunks, index, e));
+                assert_eq!(leaf, leaf_hash(chunk));
+            }
+            assert!(tree.proof(chunks as u32).is_none());
+        }
+    }
+
+    #[test]
+    fn tampered_chunks_and_proofs_are_rejected() {
+        let data = sample(CHUNK_SIZE * 3);
+        let tree = MerkleTree::from_bytes(&data);
+        let root = tree.root();
+        let size = data.len() as u64;
+        let chunk = &data[CHUNK_SIZE..2 * CHUNK_SIZE];
+        let proof = tree.proof(1).unwrap();
+
+        let mut flipped = chunk.to_vec();
+        flipped[0] ^= 1;
+        assert!(verify_chunk(&root, size, 1, &flipped, &proof).is_err());
+        // A real chunk presented at another index.
+        assert!(verify_chunk(&root, size, 0, chunk, &proof).is_err());
+        assert!(verify_chunk(&root, size, 1, chunk, &proof[1..]).is_err());
+        let mut long = proof.clone();
+        long.push(proof[0].clone());
+        assert!(verify_chunk(&root, size, 1, chunk, &long).is_err());
+        let mut bad_hex = proof.clone();
+        bad_hex[0] = "zz".to_string();
+        assert!(verify_chunk(&root, size, 1, chunk, &bad_hex).is_err());
+        assert!(verify_chunk(&root, size, 3, chunk, &proof).is_err());
+    }
+
+    #[test]
+    fn root_commits_to_file_size() {
+        // Three chunks claimed as a two-chunk file: the last real leaf
+        // would sit at index 1 if the size were not part of the root.
+        let data = sample(CHUNK_SIZE * 2 + 10);
+        let tree = MerkleTree::from_bytes(&data);
+        let lie = (CHUNK_SIZE * 2) as u64;
+        let last = &data[CHUNK_SIZE * 2..];
+        let proof = vec![hex::encode(node_hash(
+            &leaf_hash(&data[..CHUNK_SIZE]),
+            &leaf_hash(&data[CHUNK_SIZE..CHUNK_SIZE * 2]),
+        ))];
+        assert!(verify_chunk(&tree.root(), lie, 1, last, &proof).is_err());
+        assert_ne!(
+            MerkleTree::from_bytes(&data[..CHUNK_SIZE]).root(),
+            MerkleTree::from_leaves(CHUNK_SIZE as u64 + 1, vec![leaf_hash(&data[..CHUNK_SIZE])])
+                .root()
+        );
+    }
+
+    #[test]
+    fn reader_and_bytes_agree_and_keep_legacy_sha256() {
+        let data = sample(CHUNK_SIZE * 2 + 123);
+        let from_bytes = MerkleTree::from_bytes(&data);
+        // A reader that returns short reads must not shift chunk boundaries.
+        struct Trickle<'a>(&'a [u8]);
+        impl Read for Trickle<'_> {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                let n = buf.len().min(self.0.len()).min(1000);
+                buf[..n].copy_from_slice(&self.0[..n]);
+                self.0 = &self.0[n..];
+                Ok(n)
+            }
+        }
+        let from_reader = MerkleTree::from_reader(Trickle(&data)).unwrap();
+        assert_eq!(from_bytes.root(), from_reader.root());
+
+        let sha = hex::encode(Sha256::digest(&data));
+        assert_eq!(from_reader.content_sha256(), Some(sha.as_str()));
+        assert!(from_reader.matches_hash(&sha.to_uppercase()));
+        assert!(from_reader.matches_hash(&from_reader.root()));
+        assert!(!from_reader.matches_hash("deadbeef"));
+    }
+
+    #[test]
+    fn empty_file_has_a_stable_root_and_no_chunks() {
+        let tree = MerkleTree::from_bytes(&[]);
+        assert_eq!(tree.chunk_count(), 0);
+        assert!(tree.proof(0).is_none());
+        assert_eq!(
+            tree.root(),
+            MerkleTree::from_reader(std::io::empty()).unwrap().root()
+        );
+        assert_eq!(chunk_len(0, 0), None);
+    }
+
+    #[test]
+    fn from_file_matches_from_bytes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("data.bin");
+        let data = sample(CHUNK_SIZE + 1);
+        std::fs::write(&path, &data).unwrap();
+        assert_eq!(
+            MerkleTree::from_file(&path).unwrap().root(),
+            MerkleTree::from_bytes(&data).root()
+        );
+        assert!(MerkleTree::from_file(&dir.path().join("missing")).is_err());
+    }
+}
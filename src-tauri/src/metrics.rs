@@ -0,0 +1,581 @@
+//! Prometheus metrics for operators of relays, daemons and CDN nodes.
+//!
+//! Counters and gauges live in one process-wide registry ([`global`]) so
+//! the swarm loops, the CDN and the payment checks can record into it
+//! without threading a handle through every call. Gauges that are cheap to
+//! read elsewhere (geth status, CDN usage) are refreshed by the binary just
+//! before it renders a scrape.
+//!
+//! The libp2p transport's byte counters are registered per swarm. A DHT
+//! restart builds a new swarm, so its registry replaces the previous one
+//! instead of registering the same series twice.
+
+use axum::{
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use libp2p::kad;
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use prometheus_client::encoding::text::{encode_eof, encode_registry};
+use prometheus_client::encoding::EncodeLabelSet;
+use prometheus_client::metrics::counter::Counter;
+use prometheus_client::metrics::family::Family;
+use prometheus_client::metrics::gauge::Gauge;
+use prometheus_client::metrics::histogram::{exponential_buckets, Histogram};
+use prometheus_client::registry::Unit;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+pub use prometheus_client::registry::Registry;
+
+/// Content type of the OpenMetrics text exposition.
+pub const METRICS_CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum Direction {
+    In,
+    Out,
+}
+
+impl Direction {
+    fn as_str(self) -> &'static str {
+        match self {
+            Direction::In => "in",
+            Direction::Out => "out",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct QueryLabels {
+    query: &'static str,
+    outcome: &'static str,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct ProtocolLabels {
+    protocol: &'static str,
+    direction: &'static str,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct PaymentLabels {
+    kind: &'static str,
+    outcome: &'static str,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct KindLabels {
+    kind: &'static str,
+}
+
+fn query_histogram() -> Histogram {
+    // 50 ms .. ~51 s; Kademlia queries time out at 60 s.
+    Histogram::new(exponential_buckets(0.05, 2.0, 11))
+}
+
+pub struct NodeMetrics {
+    registry: Registry,
+    transport: Mutex<Option<Registry>>,
+    connected_peers: Gauge,
+    routing_table_peers: Gauge,
+    kad_queries: Family<QueryLabels, Histogram, fn() -> Histogram>,
+    chunks_served: Counter,
+    chunks_received: Counter,
+    protocol_bytes: Family<ProtocolLabels, Counter>,
+    relay_circuits_active: Gauge,
+    payment_verifications: Family<PaymentLabels, Counter>,
+    cdn_storage_bytes: Gauge,
+    cdn_active_files: Gauge,
+    cdn_active_sites: Gauge,
+    cdn_expirations: Family<KindLabels, Counter>,
+    geth_running: Gauge,
+    geth_syncing: Gauge,
+    geth_current_block: Gauge,
+    geth_highest_block: Gauge,
+    geth_peers: Gauge,
+    geth_mining: Gauge,
+    geth_hashrate: Gauge,
+}
+
+impl Default for NodeMetrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl NodeMetrics {
+    pub fn new() -> Self {
+        let mut registry = Registry::with_prefix("chiral");
+        let connected_peers = Gauge::default();
+        registry.register(
+            "connected_peers",
+            "Peers with at least one open libp2p connection",
+            connected_peers.clone(),
+        );
+        let routing_table_peers = Gauge::default();
+        registry.register(
+            "kad_routing_table_peers",
+            "Peers in the Kademlia routing table",
+            routing_table_peers.clone(),
+        );
+        let kad_queries = Family::new_with_constructor(query_histogram as fn() -> Histogram);
+        registry.register_with_unit(
+            "kad_query_duration",
+            "Duration of finished Kademlia queries",
+            Unit::Seconds,
+            kad_queries.clone(),
+        );
+        let chunks_served = Counter::default();
+        registry.register(
+            "chunks_served",
+            "File chunks read from disk and sent to downloaders",
+            chunks_served.clone(),
+        );
+        let chunks_received = Counter::default();
+        registry.register(
+            "chunks_received",
+            "Downloaded file chunks that passed their inclusion proof",
+            chunks_received.clone(),
+        );
+        let protocol_bytes = Family::default();
+        registry.register_with_unit(
+            "protocol",
+            "Payload bytes sent and received per application protocol",
+            Unit::Bytes,
+            protocol_bytes.clone(),
+        );
+        let relay_circuits_active = Gauge::default();
+        registry.register(
+            "relay_circuits_active",
+            "Circuits currently relayed by this node",
+            relay_circuits_active.clone(),
+        );
+        let payment_verifications = Family::default();
+        registry.register(
+            "payment_verifications",
+            "On-chain payment checks by kind and outcome",
+            payment_verifications.clone(),
+        );
+        let cdn_storage_bytes = Gauge::default();
+        registry.register_with_unit(
+            "cdn_storage",
+            "Bytes held for unexpired CDN files and sites",
+            Unit::Bytes,
+            cdn_storage_bytes.clone(),
+        );
+        let cdn_active_files = Gauge::default();
+        registry.register(
+            "cdn_active_files",
+            "Unexpired files hosted by the CDN",
+            cdn_active_files.clone(),
+        );
+        let cdn_active_sites = Gauge::default();
+        registry.register(
+            "cdn_active_sites",
+            "Unexpired sites hosted by the CDN",
+            cdn_active_sites.clone(),
+        );
+        let cdn_expirations = Family::default();
+        registry.register(
+            "cdn_expirations",
+            "CDN files and sites removed when their hosting term ended",
+            cdn_expirations.clone(),
+        );
+        let geth_running = Gauge::default();
+        registry.register(
+            "geth_running",
+            "1 while the local geth process is running",
+            geth_running.clone(),
+        );
+        let geth_syncing = Gauge::default();
+        registry.register(
+            "geth_syncing",
+            "1 while geth is syncing the chain",
+            geth_syncing.clone(),
+        );
+        let geth_current_block = Gauge::default();
+        registry.register(
+            "geth_current_block",
+            "Latest block imported by geth",
+            geth_current_block.clone(),
+        );
+        let geth_highest_block = Gauge::default();
+        registry.register(
+            "geth_highest_block",
+            "Highest block geth knows of",
+            geth_highest_block.clone(),
+        );
+        let geth_peers = Gauge::default();
+        registry.register("geth_peers", "Geth's devp2p peers", geth_peers.clone());
+        let geth_mining = Gauge::default();
+        registry.register("geth_mining", "1 while geth is mining", geth_mining.clone());
+        let geth_hashrate = Gauge::default();
+        registry.register(
+            "geth_hashrate",
+            "Geth's reported mining hashrate in hashes per second",
+            geth_hashrate.clone(),
+        );
+
+        Self {
+            registry,
+            transport: Mutex::new(None),
+            connected_peers,
+            routing_table_peers,
+            kad_queries,
+            chunks_served,
+            chunks_received,
+            protocol_bytes,
+            relay_circuits_active,
+            payment_verifications,
+            cdn_storage_bytes,
+            cdn_active_files,
+            cdn_active_sites,
+            cdn_expirations,
+            geth_running,
+            geth_syncing,
+            geth_current_block,
+            geth_highest_block,
+            geth_peers,
+            geth_mining,
+            geth_hashrate,
+        }
+    }
+
+    /// Install the registry holding a freshly built swarm's transport
+    /// byte counters, dropping the previous swarm's.
+    pub fn set_transport_registry(&self, registry: Registry) {
+        *self.transport.lock() = Some(registry);
+    }
+
+    pub fn set_swarm_gauges(&self, connected_peers: usize, routing_table_peers: usize) {
+        self.connected_peers.set(connected_peers as i64);
+        self.routing_table_peers.set(routing_table_peers as i64);
+    }
+
+    /// Record a finished Kademlia query. `duration` is `None` when libp2p
+    /// didn't time it, which only happens for queries that never started.
+    pub fn observe_kad_query(&self, result: &kad::QueryResult, duration: Option<Duration>) {
+        let Some(duration) = duration else {
+            return;
+        };
+        let (query, ok) = kad_query_kind(result);
+        self.kad_queries
+            .get_or_create(&QueryLabels {
+                query,
+                outcome: if ok { "ok" } else { "error" },
+            })
+            .observe(duration.as_secs_f64());
+    }
+
+    pub fn chunk_served(&self, bytes: u64, protocol: &'static str) {
+        self.chunks_served.inc();
+        self.record_protocol_bytes(protocol, Direction::Out, bytes);
+    }
+
+    pub fn chunk_received(&self) {
+        self.chunks_received.inc();
+    }
+
+    pub fn record_protocol_bytes(&self, protocol: &'static str, direction: Direction, bytes: u64) {
+        if bytes == 0 {
+            return;
+        }
+        self.protocol_bytes
+            .get_or_create(&ProtocolLabels {
+                protocol,
+                direction: direction.as_str(),
+            })
+            .inc_by(bytes);
+    }
+
+    pub fn relay_circuit_opened(&self) {
+        self.relay_circuits_active.inc();
+    }
+
+    pub fn relay_circuit_closed(&self) {
+        self.relay_circuits_active.dec();
+    }
+
+    /// Count one payment check. `kind` names what was being paid for,
+    /// e.g. `chunk_download` or `cdn_upload`.
+    pub fn payment_verification(&self, kind: &'static str, accepted: bool) {
+        self.payment_verifications
+            .get_or_create(&PaymentLabels {
+                kind,
+                outcome: if accepted { "accepted" } else { "rejected" },
+            })
+            .inc();
+    }
+
+    pub fn set_cdn_usage(&self, storage_bytes: u64, active_files: usize, active_sites: usize) {
+        self.cdn_storage_bytes.set(storage_bytes as i64);
+        self.cdn_active_files.set(active_files as i64);
+        self.cdn_active_sites.set(active_sites as i64);
+    }
+
+    /// `kind` is `file` or `site`.
+    pub fn cdn_expired(&self, kind: &'static str, count: usize) {
+        self.cdn_expirations
+            .get_or_create(&KindLabels { kind })
+            .inc_by(count as u64);
+    }
+
+    pub fn set_geth_status(&self, status: &crate::geth::GethStatus) {
+        self.geth_running.set(status.running as i64);
+        self.geth_syncing.set(status.syncing as i64);
+        self.geth_current_block.set(status.current_block as i64);
+        self.geth_highest_block.set(status.highest_block as i64);
+        self.geth_peers.set(status.peer_count as i64);
+    }
+
+    pub fn set_mining_status(&self, status: &crate::geth::MiningStatus) {
+        self.geth_mining.set(status.mining as i64);
+        self.geth_hashrate.set(status.hash_rate as i64);
+    }
+
+    /// OpenMetrics text for everything registered so far.
+    pub fn render(&self) -> Result<String, String> {
+        let mut out = String::new();
+        encode_registry(&mut out, &self.registry)
+            .map_err(|e| format!("Failed to encode metrics: {}", e))?;
+        if let Some(transport) = self.transport.lock().as_ref() {
+            encode_registry(&mut out, transport)
+                .map_err(|e| format!("Failed to encode transport metrics: {}", e))?;
+        }
+        encode_eof(&mut out).map_err(|e| format!("Failed to encode metrics: {}", e))?;
+        Ok(out)
+    }
+}
+
+fn kad_query_kind(result: &kad::QueryResult) -> (&'static str, bool) {
+    match result {
+        kad::QueryResult::Bootstrap(r) => ("bootstrap", r.is_ok()),
+        kad::QueryResult::GetClosestPeers(r) => ("get_closest_peers", r.is_ok()),
+        kad::QueryResult::GetProviders(r) => ("get_providers", r.is_ok()),
+        kad::QueryResult::StartProviding(r) => ("start_providing", r.is_ok()),
+        kad::QueryResult::RepublishProvider(r) => ("republish_provider", r.is_ok()),
+        kad::QueryResult::GetRecord(r) => ("get_record", r.is_ok()),
+        kad::QueryResult::PutRecord(r) => ("put_record", r.is_ok()),
+        kad::QueryResult::RepublishRecord(r) => ("republish_record", r.is_ok()),
+    }
+}
+
+static GLOBAL: Lazy<NodeMetrics> = Lazy::new(NodeMetrics::new);
+
+/// The process-wide metrics every subsystem records into.
+pub fn global() -> &'static NodeMetrics {
+    &GLOBAL
+}
+
+/// Response for a `GET /metrics` scrape of [`global`].
+pub fn metrics_response() -> Response {
+    match global().render() {
+        Ok(body) => ([(header::CONTENT_TYPE, METRICS_CONTENT_TYPE)], body).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
+    }
+}
+
+/// `GET /metrics` for binaries that have nothing to refresh before a scrape.
+pub fn metrics_routes() -> Router {
+    Router::new().route("/metrics", get(|| async { metrics_response() }))
+}
+
+/// Serve [`metrics_routes`] on its own listener. Returns the bound address.
+pub async fn start_metrics_server(addr: SocketAddr) -> Result<SocketAddr, String> {
+    let listener = tokio::net::TcpListener::bind(addr)
+        .await
+        .map_err(|e| format!("Failed to bind metrics listener {}: {}", addr, e))?;
+    let bound_addr = listener.local_addr().map_err(|e| e.to_string())?;
+    tokio::spawn(async move {
+        if let Err(e) = axum::serve(listener, metrics_routes()).await {
+            eprintln!("[METRICS] Server error: {}", e);
+        }
+    });
+    Ok(bound_addr)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(text: &str, series: &str) -> Option<f64> {
+        text.lines()
+            .find_map(|line| line.strip_prefix(series)?.strip_prefix(' ')?.parse().ok())
+    }
+
+    #[test]
+    fn render_reports_recorded_values() {
+        let metrics = NodeMetrics::new();
+        metrics.set_swarm_gauges(7, 42);
+        metrics.chunk_served(1024, "/chiral/file-request/4.0.0");
+        metrics.chunk_served(512, "/chiral/file-request/4.0.0");
+        metrics.chunk_received();
+        metrics.record_protocol_bytes("/chiral/http/1.0.0", Direction::In, 99);
+        metrics.record_protocol_bytes("/chiral/http/1.0.0", Direction::In, 0);
+        metrics.relay_circuit_opened();
+        metrics.relay_circuit_opened();
+        metrics.relay_circuit_closed();
+        metrics.payment_verification("cdn_upload", true);
+        metrics.payment_verification("cdn_upload", false);
+        metrics.payment_verification("cdn_upload", false);
+        metrics.set_cdn_usage(4096, 3, 1);
+        metrics.cdn_expired("file", 2);
+
+        let text = metrics.render().unwrap();
+
+        assert_eq!(sample(&text, "chiral_connected_peers"), Some(7.0));
+        assert_eq!(sample(&text, "chiral_kad_routing_table_peers"), Some(42.0));
+        assert_eq!(sample(&text, "chiral_chunks_served_total"), Some(2.0));
+        assert_eq!(sample(&text, "chiral_chunks_received_total"), Some(1.0));
+        assert_eq!(
+            sample(
+                &text,
+                "chiral_protocol_bytes_total{protocol=\"/chiral/file-request/4.0.0\",direction=\"out\"}"
+            ),
+            Some(1536.0)
+        );
+        assert_eq!(
+            sample(
+                &text,
+                "chiral_protocol_bytes_total{protocol=\"/chiral/http/1.0.0\",direction=\"in\"}"
+            ),
+            Some(99.0)
+        );
+        assert_eq!(sample(&text, "chiral_relay_circuits_active"), Some(1.0));
+        assert_eq!(
+            sample(
+                &text,
+                "chiral_payment_verifications_total{kind=\"cdn_upload\",outcome=\"accepted\"}"
+            ),
+            Some(1.0)
+        );
+        assert_eq!(
+            sample(
+                &text,
+                "chiral_payment_verifications_total{kind=\"cdn_upload\",outcome=\"rejected\"}"
+            ),
+            Some(2.0)
+        );
+        assert_eq!(sample(&text, "chiral_cdn_storage_bytes"), Some(4096.0));
+        assert_eq!(sample(&text, "chiral_cdn_active_files"), Some(3.0));
+        assert_eq!(sample(&text, "chiral_cdn_active_sites"), Some(1.0));
+        assert_eq!(
+            sample(&text, "chiral_cdn_expirations_total{kind=\"file\"}"),
+            Some(2.0)
+        );
+        assert!(text.ends_with("# EOF\n"));
+    }
+
+    #[test]
+    fn kad_queries_are_timed_by_kind_and_outcome() {
+        let metrics = NodeMetrics::new();
+        let ok = kad::QueryResult::Bootstrap(Ok(kad::BootstrapOk {
+            peer: libp2p::PeerId::random(),
+            num_remaining: 0,
+        }));
+        let err = kad::QueryResult::GetClosestPeers(Err(kad::GetClosestPeersError::Timeout {
+            key: vec![1],
+            peers: Vec::new(),
+        }));
+
+        metrics.observe_kad_query(&ok, Some(Duration::from_millis(120)));
+        metrics.observe_kad_query(&err, Some(Duration::from_secs(3)));
+        metrics.observe_kad_query(&err, None);
+
+        let text = metrics.render().unwrap();
+        assert_eq!(
+            sample(
+                &text,
+                "chiral_kad_query_duration_seconds_count{query=\"bootstrap\",outcome=\"ok\"}"
+            ),
+            Some(1.0)
+        );
+        assert_eq!(
+            sample(
+                &text,
+                "chiral_kad_query_duration_seconds_sum{query=\"get_closest_peers\",outcome=\"error\"}"
+            ),
+            Some(3.0)
+        );
+        assert_eq!(
+            sample(
+                &text,
+                "chiral_kad_query_duration_seconds_bucket{le=\"0.2\",query=\"bootstrap\",outcome=\"ok\"}"
+            ),
+            Some(1.0)
+        );
+    }
+
+    #[test]
+    fn geth_gauges_follow_status() {
+        let metrics = NodeMetrics::new();
+        metrics.set_geth_status(&crate::geth::GethStatus {
+            installed: true,
+            running: true,
+            local_running: true,
+            syncing: true,
+            current_block: 120,
+            highest_block: 500,
+            peer_count: 4,
+            chain_id: 98765,
+        });
+        metrics.set_mining_status(&crate::geth::MiningStatus {
+            mining: true,
+            hash_rate: 2500,
+            miner_address: None,
+            total_mined_wei: "0".to_string(),
+            total_mined_chi: 0.0,
+        });
+
+        let text = metrics.render().unwrap();
+        assert_eq!(sample(&text, "chiral_geth_running"), Some(1.0));
+        assert_eq!(sample(&text, "chiral_geth_syncing"), Some(1.0));
+        assert_eq!(sample(&text, "chiral_geth_current_block"), Some(120.0));
+        assert_eq!(sample(&text, "chiral_geth_highest_block"), Some(500.0));
+        assert_eq!(sample(&text, "chiral_geth_peers"), Some(4.0));
+        assert_eq!(sample(&text, "chiral_geth_mining"), Some(1.0));
+        assert_eq!(sample(&text, "chiral_geth_hashrate"), Some(2500.0));
+    }
+
+    #[test]
+    fn transport_registry_is_replaced_not_duplicated() {
+        let metrics = NodeMetrics::new();
+        for value in [5, 9] {
+            let mut registry = Registry::default();
+            let counter: Counter = Counter::default();
+            counter.inc_by(value);
+            registry.register("libp2p_bandwidth", "test", counter);
+            metrics.set_transport_registry(registry);
+        }
+
+        let text = metrics.render().unwrap();
+        assert_eq!(text.matches("# TYPE libp2p_bandwidth ").count(), 1);
+        assert_eq!(sample(&text, "libp2p_bandwidth_total"), Some(9.0));
+        assert_eq!(text.matches("# EOF").count(), 1);
+    }
+
+    #[tokio::test]
+    async fn metrics_server_serves_openmetrics_text() {
+        global().relay_circuit_opened();
+        let addr = start_metrics_server(SocketAddr::from(([127, 0, 0, 1], 0)))
+            .await
+            .unwrap();
+
+        let response = reqwest::get(format!("http://{}/metrics", addr))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), 200);
+        assert_eq!(
+            response.headers()[reqwest::header::CONTENT_TYPE],
+            METRICS_CONTENT_TYPE
+        );
+        let text = response.text().await.unwrap();
+        assert!(text.contains("# TYPE chiral_relay_circuits_active gauge"));
+        global().relay_circuit_closed();
+    }
+}
@@ -0,0 +1,1338 @@
+//! Pay-as-you-go payments for paid chunked downloads.
+//!
+//! Instead of one on-chain transaction before the first chunk, the buyer
+//! signs an off-chain voucher for the next `VOUCHER_CHUNK_INTERVAL` chunks
+//! and keeps topping it up as chunks arrive. Vouchers are cumulative: each
+//! one states the total owed for the download so far, so only the newest
+//! matters and a lost voucher costs nothing. The seeder checks every
+//! voucher and serves at most as many chunks as the newest one covers.
+//!
+//! Nothing moves on chain until the buyer settles: one transfer per seeder
+//! node covering every download since the last settlement, sent once the
+//! debt reaches `SETTLEMENT_MIN_WEI` or has been open for
+//! `SETTLEMENT_MAX_AGE_SECS`. The transfer's input names the node it
+//! settles with ([`settlement_memo`]), since several nodes may share one
+//! wallet. The seeder credits a settlement only after checking the transfer
+//! and its memo on chain, and refuses new voucher downloads from a payer
+//! whose debt has been open longer than `SETTLEMENT_GRACE_SECS`.
+//!
+//! Vouchers are only IOUs, so the seeder also limits how much it lends. A
+//! payer must hold enough CHI for its debt plus the whole file before a
+//! download opens, and its unsettled debt may not grow past what it has
+//! already settled with this seeder, or one voucher interval for a payer
+//! that never has. A buyer refused for credit settles and tries again.
+//!
+//! Both sides keep a book under `<data_dir>`: the buyer's in
+//! `issued-vouchers.json`, the seeder's in `received-vouchers.json`. The
+//! seeder's book folds history older than `RETIRE_AFTER_SECS` into
+//! per-payer totals once the payer owes nothing.
+
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::PathBuf;
+
+/// Chunks each voucher top-up pays for in advance.
+pub const VOUCHER_CHUNK_INTERVAL: u32 = 16;
+/// Debt to one seeder that is settled without waiting (1 CHI).
+pub const SETTLEMENT_MIN_WEI: u128 = 1_000_000_000_000_000_000;
+/// Debt this old is settled even when it is below `SETTLEMENT_MIN_WEI`.
+pub const SETTLEMENT_MAX_AGE_SECS: u64 = 60 * 60;
+/// Seeders refuse new voucher downloads from payers with older debt.
+pub const SETTLEMENT_GRACE_SECS: u64 = 24 * 60 * 60;
+/// Seeders fold sessions and settlements this old into a running total
+/// once the payer owes nothing, and refuse vouchers signed before then.
+pub const RETIRE_AFTER_SECS: u64 = 7 * 24 * 60 * 60;
+/// Voucher rejection that the buyer answers by settling first.
+pub const VOUCHER_CREDIT_LIMIT_ERROR: &str =
+    "Voucher credit limit reached; settle outstanding vouchers first";
+
+const VOUCHER_TAG: &[u8] = b"chiral-payment-voucher-v1";
+const SETTLEMENT_MEMO_TAG: &str = "chiral-voucher-settlement-v1:";
+const ISSUED_FILE: &str = "issued-vouchers.json";
+const RECEIVED_FILE: &str = "received-vouchers.json";
+
+/// What a voucher session pays for; fixed for the whole download.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VoucherTerms {
+    /// Download request ID the vouchers are for
+    pub session_id: String,
+    pub payer: String,
+    /// Seeder wallet from the signed FileInfo
+    pub payee: String,
+    pub file_hash: String,
+    /// Full price of the file in wei (u128 as string for CBOR safety)
+    pub price_wei: String,
+    pub total_chunks: u32,
+}
+
+impl VoucherTerms {
+    pub fn price(&self) -> Result<u128, String> {
+        parse_wei(&self.price_wei, "price_wei")
+    }
+
+    fn same_as(&self, other: &VoucherTerms) -> bool {
+        self.session_id == other.session_id
+            && self.payer.eq_ignore_ascii_case(&other.payer)
+            && self.payee.eq_ignore_ascii_case(&other.payee)
+            && self.file_hash.eq_ignore_ascii_case(&other.file_hash)
+            && self.price_wei == other.price_wei
+            && self.total_chunks == other.total_chunks
+    }
+}
+
+/// A payer's signed promise to pay `cumulative_wei` for the first
+/// `chunks_covered` chunks of a download.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PaymentVoucher {
+    pub terms: VoucherTerms,
+    pub chunks_covered: u32,
+    pub cumulative_wei: String,
+    pub issued_at: u64,
+    /// ECDSA signature by `terms.payer` over `voucher_sign_payload`
+    pub signature: String,
+}
+
+fn parse_wei(value: &str, field: &str) -> Result<u128, String> {
+    value
+        .trim()
+        .parse::<u128>()
+        .map_err(|_| format!("{field} must be a u128 wei amount"))
+}
+
+/// What the first `chunks` of `total_chunks` chunks cost: the price pro
+/// rata, rounded up, and exactly `price_wei` once every chunk is covered.
+pub fn amount_for_chunks(price_wei: u128, total_chunks: u32, chunks: u32) -> u128 {
+    if total_chunks == 0 || chunks >= total_chunks {
+        return price_wei;
+    }
+    let total = total_chunks as u128;
+    let chunks = chunks as u128;
+    // Split so neither product can overflow: q * chunks <= price_wei and
+    // r * chunks < total^2.
+    let (q, r) = (price_wei / total, price_wei % total);
+    q * chunks + (r * chunks).div_ceil(total)
+}
+
+/// Check that a payer holding `balance_wei` can pay for the whole download
+/// on `terms` on top of the `outstanding` debt it already has there.
+pub fn check_payer_funds(
+    balance_wei: u128,
+    outstanding: u128,
+    terms: &VoucherTerms,
+) -> Result<(), String> {
+    let needed = outstanding.saturating_add(terms.price()?);
+    if balance_wei < needed {
+        return Err(format!(
+            "Payer balance of {} wei does not cover {} wei of vouchers",
+            balance_wei, needed
+        ));
+    }
+    Ok(())
+}
+
+/// Length-prefixed canonical bytes for the voucher signature, in the same
+/// style as `dht::file_info_sign_payload`.
+pub fn voucher_sign_payload(
+    terms: &VoucherTerms,
+    chunks_covered: u32,
+    cumulative_wei: &str,
+    issued_at: u64,
+) -> Vec<u8> {
+    let mut out = Vec::with_capacity(320);
+    out.extend_from_slice(VOUCHER_TAG);
+    for part in [
+        terms.session_id.as_bytes(),
+        terms.payer.to_lowercase().as_bytes(),
+        terms.payee.to_lowercase().as_bytes(),
+        terms.file_hash.to_lowercase().as_bytes(),
+        terms.price_wei.as_bytes(),
+        cumulative_wei.as_bytes(),
+    ] {
+        out.extend_from_slice(&(part.len() as u32).to_le_bytes());
+        out.extend_from_slice(part);
+    }
+    out.extend_from_slice(&terms.total_chunks.to_le_bytes());
+    out.extend_from_slice(&chunks_covered.to_le_bytes());
+    out.extend_from_slice(&issued_at.to_le_bytes());
+    out
+}
+
+impl PaymentVoucher {
+    /// Sign a voucher for the first `chunks_covered` chunks, owing exactly
+    /// what `amount_for_chunks` charges for them.
+    pub fn sign(
+        terms: &VoucherTerms,
+        chunks_covered: u32,
+        issued_at: u64,
+        private_key: &str,
+    ) -> Result<Self, String> {
+        let chunks_covered = chunks_covered.min(terms.total_chunks);
+        let cumulative_wei =
+            amount_for_chunks(terms.price()?, terms.total_chunks, chunks_covered).to_string();
+        let payload = voucher_sign_payload(terms, chunks_covered, &cumulative_wei, issued_at);
+        let signature = crate::wallet::sign_message(private_key, &payload)?;
+        Ok(Self {
+            terms: terms.clone(),
+            chunks_covered,
+            cumulative_wei,
+            issued_at,
+            signature,
+        })
+    }
+
+    pub fn cumulative(&self) -> Result<u128, String> {
+        parse_wei(&self.cumulative_wei, "cumulative_wei")
+    }
+
+    /// Check the signature and that the amount pays for the chunks covered.
+    pub fn verify(&self) -> Result<(), String> {
+        let price = self.terms.price()?;
+        let cumulative = self.cumulative()?;
+        if price == 0 || self.terms.total_chunks == 0 {
+            return Err("Voucher is for a free or empty file".to_string());
+        }
+        if self.chunks_covered > self.terms.total_chunks {
+            return Err("Voucher covers more chunks than the file has".to_string());
+        }
+        if cumulative > price {
+            return Err("Voucher amount exceeds the file price".to_string());
+        }
+        let owed = amount_for_chunks(price, self.terms.total_chunks, self.chunks_covered);
+        if cumulative < owed {
+            return Err(format!(
+                "Voucher pays {} wei for {} chunks; {} wei required",
+                cumulative, self.chunks_covered, owed
+            ));
+        }
+        let payload = voucher_sign_payload(
+            &self.terms,
+            self.chunks_covered,
+            &self.cumulative_wei,
+            self.issued_at,
+        );
+        if !crate::wallet::verify_signature(&payload, &self.signature, &self.terms.payer) {
+            return Err("Voucher signature does not match the payer".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Money owed between one payer and one payee, as shown to the user.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VoucherBalance {
+    pub payer: String,
+    pub payee: String,
+    pub outstanding_wei: String,
+    pub downloads: usize,
+    /// When the oldest download not yet fully settled started
+    pub unsettled_since: Option<u64>,
+}
+
+/// Input data of a settlement transfer to the seeder node `payee_peer`.
+pub fn settlement_memo(payee_peer: &str) -> Vec<u8> {
+    format!("{}{}", SETTLEMENT_MEMO_TAG, payee_peer).into_bytes()
+}
+
+/// Check that a settlement transfer's input names `local_peer`. A transfer
+/// settling with another node of the same wallet credits nothing here.
+pub fn check_settlement_memo(input: &[u8], local_peer: &str) -> Result<(), String> {
+    if input == settlement_memo(local_peer).as_slice() {
+        Ok(())
+    } else {
+        Err("Settlement tx does not name this seeder node".to_string())
+    }
+}
+
+fn session_key(payer: &str, session_id: &str) -> String {
+    format!("{}:{}", payer.trim().to_lowercase(), session_id)
+}
+
+fn pair_key(payer: &str, payee: &str) -> String {
+    format!(
+        "{}:{}",
+        payer.trim().to_lowercase(),
+        payee.trim().to_lowercase()
+    )
+}
+
+/// Earliest start among `sessions` that accrued debt after `settled_at`.
+fn unsettled_since(
+    sessions: impl Iterator<Item = (u64, u64)>,
+    settled_at: Option<u64>,
+) -> Option<u64> {
+    sessions
+        .filter(|(_, updated_at)| settled_at.is_none_or(|at| *updated_at >= at))
+        .map(|(opened_at, _)| opened_at)
+        .min()
+}
+
+// ============================================================================
+// Seeder side
+// ============================================================================
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ReceivedSession {
+    voucher: PaymentVoucher,
+    opened_at: u64,
+    updated_at: u64,
+}
+
+/// An on-chain transfer the seeder checked and credited to a payer.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReceivedSettlement {
+    pub tx_hash: String,
+    pub payer: String,
+    pub payee: String,
+    /// Voucher debt the transfer settles, platform fee included
+    pub amount_wei: String,
+    pub settled_at: u64,
+}
+
+/// What one payer owed and settled with one payee in sessions and
+/// settlements that have been pruned from the book.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RetiredBalance {
+    owed_wei: String,
+    settled_wei: String,
+    last_settled_at: Option<u64>,
+}
+
+/// Newest voucher per download and the settlements credited against them.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ReceivedVoucherBook {
+    #[serde(default)]
+    sessions: HashMap<String, ReceivedSession>,
+    #[serde(default)]
+    settlements: Vec<ReceivedSettlement>,
+    /// Totals of pruned sessions and settlements, by payer and payee
+    #[serde(default)]
+    retired: HashMap<String, RetiredBalance>,
+    #[serde(skip)]
+    path: Option<PathBuf>,
+    /// Bumped per snapshot, so a write can tell it has been overtaken
+    #[serde(skip)]
+    generation: u64,
+}
+
+impl ReceivedVoucherBook {
+    /// Load the book at `path`; a missing file starts empty and a corrupt
+    /// one is quarantined first.
+    pub fn load(path: PathBuf) -> Self {
+        let mut book: Self = crate::json_file::load_json_or_quarantine(&path, "Vouchers");
+        book.path = Some(path);
+        book
+    }
+
+    /// Take a verified voucher and return how many chunks its download is
+    /// now paid for. Older vouchers are ignored, since the newest already
+    /// covers them, and newer ones must stay within `credit_limit`.
+    pub fn accept(&mut self, voucher: PaymentVoucher, now: u64) -> Result<u32, String> {
+        voucher.verify()?;
+        self.retire_settled(now);
+        let terms = &voucher.terms;
+        let key = session_key(&terms.payer, &terms.session_id);
+        let previous = match self.sessions.get(&key) {
+            Some(session) if !session.voucher.terms.same_as(terms) => {
+                return Err("Voucher terms changed during the download".to_string());
+            }
+            Some(session) if voucher.chunks_covered <= session.voucher.chunks_covered => {
+                return Ok(session.voucher.chunks_covered);
+            }
+            Some(session) => Some(session.voucher.cumulative()?),
+            None => None,
+        };
+        if previous.is_none() && voucher.issued_at.saturating_add(RETIRE_AFTER_SECS) <= now {
+            return Err("Voucher was signed too long ago".to_string());
+        }
+        if previous.is_none() && self.overdue(&terms.payer, &terms.payee, now) {
+            return Err(format!(
+                "Payer has vouchers unsettled for over {} hours; settle them before starting new downloads",
+                SETTLEMENT_GRACE_SECS / 3600
+            ));
+        }
+        let owed = self
+            .owed(&terms.payer, &terms.payee)
+            .saturating_sub(previous.unwrap_or(0))
+            .saturating_add(voucher.cumulative()?);
+        if owed.saturating_sub(self.settled(&terms.payer, &terms.payee)) > self.credit_limit(terms)
+        {
+            return Err(VOUCHER_CREDIT_LIMIT_ERROR.to_string());
+        }
+        let covered = voucher.chunks_covered;
+        match self.sessions.get_mut(&key) {
+            Some(session) => {
+                session.voucher = voucher;
+                session.updated_at = now;
+            }
+            None => {
+                self.sessions.insert(
+                    key,
+                    ReceivedSession {
+                        voucher,
+                        opened_at: now,
+                        updated_at: now,
+                    },
+                );
+            }
+        }
+        Ok(covered)
+    }
+
+    /// Fold sessions idle for `RETIRE_AFTER_SECS` and settlements that old
+    /// into `retired`, for every payer that owes nothing. Outstanding debt
+    /// and credit limits come out the same.
+    fn retire_settled(&mut self, now: u64) {
+        let square: Vec<String> = self
+            .pairs()
+            .into_iter()
+            .filter(|(payer, payee)| self.outstanding(payer, payee) == 0)
+            .map(|(payer, payee)| pair_key(&payer, &payee))
+            .collect();
+        let old = |at: u64| at.saturating_add(RETIRE_AFTER_SECS) <= now;
+        for key in square {
+            let mut owed = 0u128;
+            self.sessions.retain(|_, s| {
+                let terms = &s.voucher.terms;
+                if pair_key(&terms.payer, &terms.payee) != key || !old(s.updated_at) {
+                    return true;
+                }
+                owed = owed.saturating_add(s.voucher.cumulative().unwrap_or(0));
+                false
+            });
+            let mut settled = 0u128;
+            let mut last_settled_at = None;
+            self.settlements.retain(|s| {
+                if pair_key(&s.payer, &s.payee) != key || !old(s.settled_at) {
+                    return true;
+                }
+                settled =
+                    settled.saturating_add(parse_wei(&s.amount_wei, "amount_wei").unwrap_or(0));
+                last_settled_at = last_settled_at.max(Some(s.settled_at));
+                false
+            });
+            if owed == 0 && settled == 0 {
+                continue;
+            }
+            let retired = self.retired.entry(key).or_default();
+            let add = |total: &str, more: u128| {
+                parse_wei(total, "retired")
+                    .unwrap_or(0)
+                    .saturating_add(more)
+                    .to_string()
+            };
+            retired.owed_wei = add(&retired.owed_wei, owed);
+            retired.settled_wei = add(&retired.settled_wei, settled);
+            retired.last_settled_at = retired.last_settled_at.max(last_settled_at);
+        }
+    }
+
+    /// Every payer and payee pair with a session or settlement, lowercased.
+    fn pairs(&self) -> BTreeSet<(String, String)> {
+        let sessions = self.sessions.values().map(|s| {
+            (
+                s.voucher.terms.payer.as_str(),
+                s.voucher.terms.payee.as_str(),
+            )
+        });
+        let settlements = self
+            .settlements
+            .iter()
+            .map(|s| (s.payer.as_str(), s.payee.as_str()));
+        sessions
+            .chain(settlements)
+            .map(|(payer, payee)| (payer.to_lowercase(), payee.to_lowercase()))
+            .collect()
+    }
+
+    fn retired_between(&self, payer: &str, payee: &str) -> Option<&RetiredBalance> {
+        self.retired.get(&pair_key(payer, payee))
+    }
+
+    /// Unsettled debt a payer may run up on `terms`' seeder wallet: as much
+    /// as it has settled there so far, and at least one voucher interval of
+    /// this file.
+    pub fn credit_limit(&self, terms: &VoucherTerms) -> u128 {
+        let interval = terms.price().map_or(0, |price| {
+            amount_for_chunks(price, terms.total_chunks, VOUCHER_CHUNK_INTERVAL)
+        });
+        interval.max(self.settled(&terms.payer, &terms.payee))
+    }
+
+    /// Whether `terms`' download already has a voucher in the book.
+    pub fn has_session(&self, terms: &VoucherTerms) -> bool {
+        self.sessions
+            .contains_key(&session_key(&terms.payer, &terms.session_id))
+    }
+
+    fn sessions_between<'a>(
+        &'a self,
+        payer: &'a str,
+        payee: &'a str,
+    ) -> impl Iterator<Item = &'a ReceivedSession> + 'a {
+        self.sessions.values().filter(move |s| {
+            s.voucher.terms.payer.eq_ignore_ascii_case(payer)
+                && s.voucher.terms.payee.eq_ignore_ascii_case(payee)
+        })
+    }
+
+    fn settlements_between<'a>(
+        &'a self,
+        payer: &'a str,
+        payee: &'a str,
+    ) -> impl Iterator<Item = &'a ReceivedSettlement> + 'a {
+        self.settlements.iter().filter(move |s| {
+            s.payer.eq_ignore_ascii_case(payer) && s.payee.eq_ignore_ascii_case(payee)
+        })
+    }
+
+    pub fn has_vouchers_from(&self, payer: &str, payee: &str) -> bool {
+        self.sessions_between(payer, payee).next().is_some()
+    }
+
+    fn owed(&self, payer: &str, payee: &str) -> u128 {
+        let retired = self
+            .retired_between(payer, payee)
+            .map_or(0, |r| parse_wei(&r.owed_wei, "owed_wei").unwrap_or(0));
+        self.sessions_between(payer, payee)
+            .map(|s| s.voucher.cumulative().unwrap_or(0))
+            .fold(retired, u128::saturating_add)
+    }
+
+    fn settled(&self, payer: &str, payee: &str) -> u128 {
+        let retired = self
+            .retired_between(payer, payee)
+            .map_or(0, |r| parse_wei(&r.settled_wei, "settled_wei").unwrap_or(0));
+        self.settlements_between(payer, payee)
+            .map(|s| parse_wei(&s.amount_wei, "amount_wei").unwrap_or(0))
+            .fold(retired, u128::saturating_add)
+    }
+
+    /// Voucher debt from `payer` to `payee` not yet covered by a settlement.
+    pub fn outstanding(&self, payer: &str, payee: &str) -> u128 {
+        self.owed(payer, payee)
+            .saturating_sub(self.settled(payer, payee))
+    }
+
+    fn last_settled_at(&self, payer: &str, payee: &str) -> Option<u64> {
+        let retired = self
+            .retired_between(payer, payee)
+            .and_then(|r| r.last_settled_at);
+        self.settlements_between(payer, payee)
+            .map(|s| s.settled_at)
+            .max()
+            .max(retired)
+    }
+
+    /// Whether `payer` has owed `payee` money for longer than the grace
+    /// period.
+    pub fn overdue(&self, payer: &str, payee: &str, now: u64) -> bool {
+        if self.outstanding(payer, payee) == 0 {
+            return false;
+        }
+        let since = unsettled_since(
+            self.sessions_between(payer, payee)
+                .map(|s| (s.opened_at, s.updated_at)),
+            self.last_settled_at(payer, payee),
+        );
+        since.is_some_and(|since| since.saturating_add(SETTLEMENT_GRACE_SECS) <= now)
+    }
+
+    pub fn has_settlement(&self, tx_hash: &str) -> bool {
+        self.settlements
+            .iter()
+            .any(|s| s.tx_hash.eq_ignore_ascii_case(tx_hash))
+    }
+
+    /// Credit a verified settlement. Returns false if it was already
+    /// credited.
+    pub fn apply_settlement(&mut self, settlement: ReceivedSettlement) -> bool {
+        if self.has_settlement(&settlement.tx_hash) {
+            return false;
+        }
+        let now = settlement.settled_at;
+        self.settlements.push(settlement);
+        self.retire_settled(now);
+        true
+    }
+
+    /// What every payer still owes, by payer and receiving wallet.
+    pub fn balances(&self) -> Vec<VoucherBalance> {
+        let mut pairs: BTreeMap<(String, String), usize> = BTreeMap::new();
+        for session in self.sessions.values() {
+            let terms = &session.voucher.terms;
+            *pairs
+                .entry((terms.payer.to_lowercase(), terms.payee.to_lowercase()))
+                .or_default() += 1;
+        }
+        pairs
+            .into_iter()
+            .map(|((payer, payee), downloads)| VoucherBalance {
+                outstanding_wei: self.outstanding(&payer, &payee).to_string(),
+                unsettled_since: unsettled_since(
+                    self.sessions_between(&payer, &payee)
+                        .map(|s| (s.opened_at, s.updated_at)),
+                    self.last_settled_at(&payer, &payee),
+                ),
+                downloads,
+                payer,
+                payee,
+            })
+            .collect()
+    }
+
+    pub fn persist(&self) -> Result<(), String> {
+        match &self.path {
+            Some(path) => crate::json_file::save_json_atomic(path, self),
+            None => Ok(()),
+        }
+    }
+
+    /// The book's JSON, where it goes and its generation, for writing
+    /// outside the lock.
+    fn snapshot(&mut self) -> Result<Option<(PathBuf, String, u64)>, String> {
+        let Some(path) = self.path.clone() else {
+            return Ok(None);
+        };
+        let json = serde_json::to_string(self).map_err(|e| e.to_string())?;
+        self.generation += 1;
+        Ok(Some((path, json, self.generation)))
+    }
+}
+
+// ============================================================================
+// Buyer side
+// ============================================================================
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct IssuedSession {
+    terms: VoucherTerms,
+    /// Seeder node that holds the vouchers and is told about settlements
+    payee_peer: String,
+    chunks_covered: u32,
+    cumulative_wei: String,
+    opened_at: u64,
+    updated_at: u64,
+}
+
+/// An on-chain transfer settling the buyer's vouchers with one seeder.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IssuedSettlement {
+    pub tx_hash: String,
+    pub payer: String,
+    pub payee: String,
+    pub payee_peer: String,
+    /// Voucher debt settled; the seeder receives this minus the platform fee
+    pub amount_wei: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fee_tx_hash: Option<String>,
+    pub settled_at: u64,
+    /// Whether the seeder acknowledged the settlement
+    #[serde(default)]
+    pub notified: bool,
+}
+
+/// Debt to one seeder node that is ready to be settled.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DueSettlement {
+    pub payee: String,
+    pub payee_peer: String,
+    pub amount_wei: u128,
+}
+
+/// Every voucher this node signed and the settlements paid for them.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct IssuedVoucherBook {
+    #[serde(default)]
+    sessions: HashMap<String, IssuedSession>,
+    #[serde(default)]
+    settlements: Vec<IssuedSettlement>,
+    #[serde(skip)]
+    path: Option<PathBuf>,
+}
+
+impl IssuedVoucherBook {
+    /// Load the book at `path`; a missing file starts empty and a corrupt
+    /// one is quarantined first.
+    pub fn load(path: PathBuf) -> Self {
+        let mut book: Self = crate::json_file::load_json_or_quarantine(&path, "Vouchers");
+        book.path = Some(path);
+        book
+    }
+
+    /// Sign a voucher for at least `chunks` chunks of the download. Coverage
+    /// never goes down, so a download resumed after a restart re-signs what
+    /// it had already promised rather than less.
+    pub fn issue(
+        &mut self,
+        terms: &VoucherTerms,
+        payee_peer: &str,
+        chunks: u32,
+        private_key: &str,
+        now: u64,
+    ) -> Result<PaymentVoucher, String> {
+        let key = session_key(&terms.payer, &terms.session_id);
+        let previous = match self.sessions.get(&key) {
+            Some(session) if !session.terms.same_as(terms) => {
+                return Err("Voucher terms changed during the download".to_string());
+            }
+            Some(session) => Some((session.chunks_covered, session.opened_at)),
+            None => None,
+        };
+        let covered = previous.map_or(chunks, |(covered, _)| covered.max(chunks));
+        let voucher = PaymentVoucher::sign(terms, covered, now, private_key)?;
+        self.sessions.insert(
+            key,
+            IssuedSession {
+                terms: terms.clone(),
+                payee_peer: payee_peer.to_string(),
+                chunks_covered: voucher.chunks_covered,
+                cumulative_wei: voucher.cumulative_wei.clone(),
+                opened_at: previous.map_or(now, |(_, opened_at)| opened_at),
+                updated_at: now,
+            },
+        );
+        Ok(voucher)
+    }
+
+    fn groups(&self, payer: &str) -> BTreeMap<(String, String), Vec<&IssuedSession>> {
+        let mut groups: BTreeMap<(String, String), Vec<&IssuedSession>> = BTreeMap::new();
+        for session in self
+            .sessions
+            .values()
+            .filter(|s| s.terms.payer.eq_ignore_ascii_case(payer))
+        {
+            groups
+                .entry((
+                    session.terms.payee.to_lowercase(),
+                    session.payee_peer.clone(),
+                ))
+                .or_default()
+                .push(session);
+        }
+        groups
+    }
+
+    fn settled(&self, payer: &str, payee: &str, payee_peer: &str) -> (u128, Option<u64>) {
+        let mut amount = 0u128;
+        let mut last = None;
+        for s in self.settlements.iter().filter(|s| {
+            s.payer.eq_ignore_ascii_case(payer)
+                && s.payee.eq_ignore_ascii_case(payee)
+                && s.payee_peer == payee_peer
+        }) {
+            amount = amount.saturating_add(parse_wei(&s.amount_wei, "amount_wei").unwrap_or(0));
+            last = last.max(Some(s.settled_at));
+        }
+        (amount, last)
+    }
+
+    fn balance(
+        &self,
+        payer: &str,
+        payee: &str,
+        payee_peer: &str,
+        sessions: &[&IssuedSession],
+    ) -> (u128, Option<u64>) {
+        let owed: u128 = sessions
+            .iter()
+            .map(|s| parse_wei(&s.cumulative_wei, "cumulative_wei").unwrap_or(0))
+            .sum();
+        let (paid, settled_at) = self.settled(payer, payee, payee_peer);
+        let since = unsettled_since(
+            sessions.iter().map(|s| (s.opened_at, s.updated_at)),
+            settled_at,
+        );
+        (owed.saturating_sub(paid), since)
+    }
+
+    /// Debts of `payer` that should be settled now: those that reached
+    /// `SETTLEMENT_MIN_WEI` or `SETTLEMENT_MAX_AGE_SECS`, or every
+    /// non-zero one when `force` is set.
+    pub fn due_settlements(&self, payer: &str, now: u64, force: bool) -> Vec<DueSettlement> {
+        self.groups(payer)
+            .into_iter()
+            .filter_map(|((payee, payee_peer), sessions)| {
+                let (amount_wei, since) = self.balance(payer, &payee, &payee_peer, &sessions);
+                let aged =
+                    since.is_some_and(|since| since.saturating_add(SETTLEMENT_MAX_AGE_SECS) <= now);
+                (amount_wei > 0 && (force || aged || amount_wei >= SETTLEMENT_MIN_WEI)).then_some(
+                    DueSettlement {
+                        payee,
+                        payee_peer,
+                        amount_wei,
+                    },
+                )
+            })
+            .collect()
+    }
+
+    pub fn record_settlement(&mut self, settlement: IssuedSettlement) {
+        self.settlements.push(settlement);
+    }
+
+    pub fn record_fee_tx(&mut self, tx_hash: &str, fee_tx_hash: String) {
+        if let Some(s) = self
+            .settlements
+            .iter_mut()
+            .find(|s| s.tx_hash.eq_ignore_ascii_case(tx_hash))
+        {
+            s.fee_tx_hash = Some(fee_tx_hash);
+        }
+    }
+
+    /// Note that the seeder acknowledged `tx_hash`. Returns false if the
+    /// settlement is unknown.
+    pub fn mark_notified(&mut self, tx_hash: &str) -> bool {
+        match self
+            .settlements
+            .iter_mut()
+            .find(|s| s.tx_hash.eq_ignore_ascii_case(tx_hash))
+        {
+            Some(s) => {
+                s.notified = true;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Settlements of `payer` the seeder has not acknowledged yet.
+    pub fn unnotified(&self, payer: &str) -> Vec<IssuedSettlement> {
+        self.settlements
+            .iter()
+            .filter(|s| !s.notified && s.payer.eq_ignore_ascii_case(payer))
+            .cloned()
+            .collect()
+    }
+
+    /// What `payer` still owes, by seeder wallet and node.
+    pub fn balances(&self, payer: &str) -> Vec<VoucherBalance> {
+        self.groups(payer)
+            .into_iter()
+            .map(|((payee, payee_peer), sessions)| {
+                let (outstanding, since) = self.balance(payer, &payee, &payee_peer, &sessions);
+                VoucherBalance {
+                    payer: payer.to_lowercase(),
+                    payee,
+                    outstanding_wei: outstanding.to_string(),
+                    downloads: sessions.len(),
+                    unsettled_since: since,
+                }
+            })
+            .collect()
+    }
+
+    pub fn persist(&self) -> Result<(), String> {
+        match &self.path {
+            Some(path) => crate::json_file::save_json_atomic(path, self),
+            None => Ok(()),
+        }
+    }
+}
+
+static ISSUED: Lazy<Mutex<IssuedVoucherBook>> = Lazy::new(|| {
+    Mutex::new(IssuedVoucherBook::load(
+        crate::network::data_dir().join(ISSUED_FILE),
+    ))
+});
+
+static RECEIVED: Lazy<Mutex<ReceivedVoucherBook>> = Lazy::new(|| {
+    Mutex::new(ReceivedVoucherBook::load(
+        crate::network::data_dir().join(RECEIVED_FILE),
+    ))
+});
+
+/// Serialises `settle` so two callers never pay the same debt twice.
+static SETTLING: Lazy<tokio::sync::Mutex<()>> = Lazy::new(|| tokio::sync::Mutex::new(()));
+
+/// Serialises `persist_received` so snapshots are written in order.
+static RECEIVED_WRITES: Lazy<tokio::sync::Mutex<()>> = Lazy::new(|| tokio::sync::Mutex::new(()));
+
+/// Generation of the newest received-book snapshot on disk. A write whose
+/// caller was cancelled can still finish late; this keeps it from landing
+/// over a newer one.
+static RECEIVED_WRITTEN: Lazy<Mutex<u64>> = Lazy::new(|| Mutex::new(0));
+
+/// The process-wide book of vouchers this node signed as a buyer.
+pub fn issued() -> &'static Mutex<IssuedVoucherBook> {
+    &ISSUED
+}
+
+/// The process-wide book of vouchers this node accepted as a seeder.
+pub fn received() -> &'static Mutex<ReceivedVoucherBook> {
+    &RECEIVED
+}
+
+/// Save the received book from a blocking task. Only serialising the book
+/// happens under its lock.
+pub async fn persist_received() -> Result<(), String> {
+    let _writing = RECEIVED_WRITES.lock().await;
+    let Some((path, json, generation)) = received().lock().snapshot()? else {
+        return Ok(());
+    };
+    tokio::task::spawn_blocking(move || {
+        let mut written = RECEIVED_WRITTEN.lock();
+        if generation <= *written {
+            return Ok(());
+        }
+        crate::json_file::write_json_atomic(&path, &json)?;
+        *written = generation;
+        Ok(())
+    })
+    .await
+    .map_err(|e| format!("Voucher book write task failed: {}", e))?
+}
+
+/// Pay every settlement of `payer` that is due (all non-zero debts when
+/// `force` is set) and record it, not yet acknowledged by the seeder. Each
+/// transfer pays the seeder's share to its wallet; the platform fee goes
+/// out after it, best-effort, as for up-front payments.
+pub async fn settle(
+    payer: &str,
+    private_key: &str,
+    force: bool,
+) -> Result<Vec<IssuedSettlement>, String> {
+    let _settling = SETTLING.lock().await;
+    let now = crate::drive_storage::now_secs().unwrap_or(0);
+    let due = issued().lock().due_settlements(payer, now, force);
+    let mut settled = Vec::new();
+    let mut errors = Vec::new();
+    for debt in due {
+        let (seller_wei, fee_wei) = crate::speed_tiers::split_payment(debt.amount_wei);
+        if seller_wei == 0 {
+            continue;
+        }
+        let payment = match crate::wallet::send_payment_with_data(
+            payer,
+            &debt.payee,
+            &crate::speed_tiers::format_wei_as_chi_exact(seller_wei),
+            private_key,
+            &settlement_memo(&debt.payee_peer),
+        )
+        .await
+        {
+            Ok(payment) => payment,
+            Err(e) => {
+                errors.push(format!("{}: {}", debt.payee, e));
+                continue;
+            }
+        };
+        println!(
+            "💸 Settled {} wei of vouchers with {} in tx {}",
+            debt.amount_wei, debt.payee, payment.tx_hash
+        );
+        let mut settlement = IssuedSettlement {
+            tx_hash: payment.tx_hash.clone(),
+            payer: payer.to_lowercase(),
+            payee: debt.payee,
+            payee_peer: debt.payee_peer,
+            amount_wei: debt.amount_wei.to_string(),
+            fee_tx_hash: None,
+            settled_at: now,
+            notified: false,
+        };
+        // Recorded before the fee goes out so a failure there can never
+        // lead to paying the seeder twice.
+        {
+            let mut book = issued().lock();
+            book.record_settlement(settlement.clone());
+            if let Err(e) = book.persist() {
+                eprintln!(
+                    "[Vouchers] Failed to save settlement {}: {}",
+                    payment.tx_hash, e
+                );
+            }
+        }
+        if fee_wei > 0 {
+            match crate::wallet::send_payment(
+                payer,
+                crate::speed_tiers::PLATFORM_WALLET,
+                &crate::speed_tiers::format_wei_as_chi_exact(fee_wei),
+                private_key,
+            )
+            .await
+            {
+                Ok(fee) => {
+                    let mut book = issued().lock();
+                    book.record_fee_tx(&payment.tx_hash, fee.tx_hash.clone());
+                    let _ = book.persist();
+                    settlement.fee_tx_hash = Some(fee.tx_hash);
+                }
+                Err(e) => eprintln!(
+                    "[Vouchers] Platform fee for settlement {} failed: {}",
+                    payment.tx_hash, e
+                ),
+            }
+        }
+        settled.push(settlement);
+    }
+    if settled.is_empty() && !errors.is_empty() {
+        return Err(format!("Settlement failed: {}", errors.join("; ")));
+    }
+    for e in errors {
+        eprintln!("[Vouchers] Settlement failed: {}", e);
+    }
+    Ok(settled)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAYER_KEY: &str = "0x4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318";
+    const PAYEE: &str = "0x00000000000000000000000000000000000000aa";
+    const ONE_CHI: u128 = 1_000_000_000_000_000_000;
+
+    fn payer() -> String {
+        crate::wallet::address_from_private_key(PAYER_KEY).unwrap()
+    }
+
+    fn terms(session_id: &str, price_wei: u128, total_chunks: u32) -> VoucherTerms {
+        VoucherTerms {
+            session_id: session_id.to_string(),
+            payer: payer(),
+            payee: PAYEE.to_string(),
+            file_hash: "abc123".to_string(),
+            price_wei: price_wei.to_string(),
+            total_chunks,
+        }
+    }
+
+    fn voucher(session_id: &str, price_wei: u128, total: u32, chunks: u32) -> PaymentVoucher {
+        PaymentVoucher::sign(&terms(session_id, price_wei, total), chunks, 100, PAYER_KEY).unwrap()
+    }
+
+    #[test]
+    fn amount_for_chunks_is_pro_rata_rounded_up_and_exact_at_the_end() {
+        assert_eq!(amount_for_chunks(100, 3, 0), 0);
+        assert_eq!(amount_for_chunks(100, 3, 1), 34);
+        assert_eq!(amount_for_chunks(100, 3, 2), 67);
+        assert_eq!(amount_for_chunks(100, 3, 3), 100);
+        assert_eq!(amount_for_chunks(100, 3, 7), 100);
+        assert_eq!(amount_for_chunks(100, 0, 0), 100);
+        // No overflow near the top of the range.
+        let max = u128::MAX;
+        assert!(amount_for_chunks(max, u32::MAX, u32::MAX - 1) < max);
+        assert_eq!(amount_for_chunks(max, u32::MAX, u32::MAX), max);
+    }
+
+    #[test]
+    fn signed_voucher_verifies_and_rejects_tampering() {
+        let v = voucher("req-1", 1000, 10, 4);
+        assert_eq!(v.cumulative_wei, "400");
+        assert!(v.verify().is_ok());
+
+        let mut cheaper = v.clone();
+        cheaper.cumulative_wei = "300".to_string();
+        assert!(cheaper.verify().unwrap_err().contains("required"));
+
+        let mut more_chunks = v.clone();
+        more_chunks.chunks_covered = 5;
+        assert!(more_chunks.verify().unwrap_err().contains("required"));
+
+        let mut other_payee = v.clone();
+        other_payee.terms.payee = "0x00000000000000000000000000000000000000bb".to_string();
+        assert!(other_payee.verify().unwrap_err().contains("signature"));
+
+        let mut too_many = v;
+        too_many.chunks_covered = 11;
+        assert!(too_many.verify().is_err());
+    }
+
+    #[test]
+    fn voucher_sign_payload_binds_every_field() {
+        let t = terms("req-1", 1000, 10);
+        let base = voucher_sign_payload(&t, 4, "400", 100);
+        let mut shifted = t.clone();
+        shifted.session_id = "req-".to_string();
+        shifted.payer = format!("1{}", t.payer);
+        assert_ne!(voucher_sign_payload(&shifted, 4, "400", 100), base);
+        assert_ne!(voucher_sign_payload(&t, 5, "400", 100), base);
+        assert_ne!(voucher_sign_payload(&t, 4, "401", 100), base);
+        assert_ne!(voucher_sign_payload(&t, 4, "400", 101), base);
+        let mut other_file = t.clone();
+        other_file.file_hash = "abc124".to_string();
+        assert_ne!(voucher_sign_payload(&other_file, 4, "400", 100), base);
+    }
+
+    #[test]
+    fn received_book_keeps_the_newest_voucher_per_download() {
+        let mut book = ReceivedVoucherBook::default();
+        assert_eq!(book.accept(voucher("a", 1000, 10, 4), 10), Ok(4));
+        assert_eq!(book.accept(voucher("a", 1000, 10, 8), 20), Ok(8));
+        // A late, older voucher doesn't lower what the download is paid for.
+        assert_eq!(book.accept(voucher("a", 1000, 10, 6), 30), Ok(8));
+        assert_eq!(book.outstanding(&payer(), PAYEE), 800);
+
+        book.apply_settlement(ReceivedSettlement {
+            tx_hash: "0x1".to_string(),
+            payer: payer(),
+            payee: PAYEE.to_string(),
+            amount_wei: "800".to_string(),
+            settled_at: 35,
+        });
+        assert_eq!(book.accept(voucher("b", 500, 5, 5), 40), Ok(5));
+        assert_eq!(book.outstanding(&payer(), PAYEE), 500);
+        assert_eq!(book.outstanding(&payer(), "0xother"), 0);
+
+        let changed = voucher("a", 2000, 10, 9);
+        assert!(book.accept(changed, 50).unwrap_err().contains("terms"));
+    }
+
+    #[test]
+    fn received_book_credits_each_settlement_once() {
+        let mut book = ReceivedVoucherBook::default();
+        book.accept(voucher("a", 1000, 10, 10), 10).unwrap();
+        let settlement = ReceivedSettlement {
+            tx_hash: "0xTX".to_string(),
+            payer: payer(),
+            payee: PAYEE.to_string(),
+            amount_wei: "600".to_string(),
+            settled_at: 20,
+        };
+        assert!(book.apply_settlement(settlement.clone()));
+        assert!(!book.apply_settlement(ReceivedSettlement {
+            tx_hash: "0xtx".to_string(),
+            ..settlement
+        }));
+        assert_eq!(book.outstanding(&payer(), PAYEE), 400);
+        assert!(book.has_settlement("0xtx"));
+    }
+
+    #[test]
+    fn received_book_refuses_new_downloads_while_debt_is_overdue() {
+        let mut book = ReceivedVoucherBook::default();
+        book.accept(voucher("a", 1000, 10, 4), 0).unwrap();
+        let late = SETTLEMENT_GRACE_SECS + 1;
+        assert!(book.overdue(&payer(), PAYEE, late));
+        assert!(book
+            .accept(voucher("b", 1000, 10, 4), late)
+            .unwrap_err()
+            .contains("settle"));
+        // The running download can still top up.
+        assert_eq!(book.accept(voucher("a", 1000, 10, 8), late), Ok(8));
+
+        book.apply_settlement(ReceivedSettlement {
+            tx_hash: "0x1".to_string(),
+            payer: payer(),
+            payee: PAYEE.to_string(),
+            amount_wei: "800".to_string(),
+            settled_at: late,
+        });
+        assert!(!book.overdue(&payer(), PAYEE, late));
+        assert_eq!(book.accept(voucher("b", 1000, 10, 4), late), Ok(4));
+    }
+
+    #[test]
+    fn received_book_limits_credit_for_payers_that_never_settle() {
+        let mut book = ReceivedVoucherBook::default();
+        // 100 wei a chunk, so one interval is 1600 wei.
+        assert_eq!(book.accept(voucher("a", 6400, 64, 16), 10), Ok(16));
+        assert_eq!(
+            book.accept(voucher("a", 6400, 64, 32), 20),
+            Err(VOUCHER_CREDIT_LIMIT_ERROR.to_string())
+        );
+        // Nor can the debt be spread over a second download.
+        assert_eq!(
+            book.accept(voucher("b", 6400, 64, 1), 20),
+            Err(VOUCHER_CREDIT_LIMIT_ERROR.to_string())
+        );
+        assert_eq!(book.outstanding(&payer(), PAYEE), 1600);
+
+        // Settling lets the refused top-up through, and the limit grows with
+        // what has been settled.
+        book.apply_settlement(ReceivedSettlement {
+            tx_hash: "0x1".to_string(),
+            payer: payer(),
+            payee: PAYEE.to_string(),
+            amount_wei: "3200".to_string(),
+            settled_at: 30,
+        });
+        assert_eq!(book.accept(voucher("a", 6400, 64, 32), 40), Ok(32));
+        assert_eq!(book.accept(voucher("a", 6400, 64, 64), 50), Ok(64));
+        assert_eq!(book.outstanding(&payer(), PAYEE), 3200);
+    }
+
+    #[test]
+    fn received_book_retires_settled_history() {
+        let mut book = ReceivedVoucherBook::default();
+        book.accept(voucher("a", 1000, 10, 10), 10).unwrap();
+        book.apply_settlement(ReceivedSettlement {
+            tx_hash: "0x1".to_string(),
+            payer: payer(),
+            payee: PAYEE.to_string(),
+            amount_wei: "1000".to_string(),
+            settled_at: 20,
+        });
+        assert_eq!((book.sessions.len(), book.settlements.len()), (1, 1));
+
+        let later = 100 + RETIRE_AFTER_SECS;
+        let next = PaymentVoucher::sign(&terms("b", 1000, 10), 4, later, PAYER_KEY).unwrap();
+        assert_eq!(book.accept(next, later), Ok(4));
+        assert_eq!(book.sessions.len(), 1);
+        assert!(book.settlements.is_empty());
+        assert_eq!(book.outstanding(&payer(), PAYEE), 400);
+        assert_eq!(book.credit_limit(&terms("c", 100, 10)), 1000);
+        assert!(!book.overdue(&payer(), PAYEE, later));
+
+        // A replayed voucher for the retired download isn't a new debt.
+        assert!(book
+            .accept(voucher("a", 1000, 10, 10), later)
+            .unwrap_err()
+            .contains("too long ago"));
+        assert_eq!(book.outstanding(&payer(), PAYEE), 400);
+    }
+
+    #[test]
+    fn payer_funds_must_cover_debt_and_the_whole_file() {
+        let t = terms("a", 1000, 10);
+        assert!(check_payer_funds(1500, 500, &t).is_ok());
+        assert!(check_payer_funds(1499, 500, &t)
+            .unwrap_err()
+            .contains("1500 wei"));
+    }
+
+    #[test]
+    fn issued_book_never_lowers_coverage() {
+        let mut book = IssuedVoucherBook::default();
+        let t = terms("a", 1000, 10);
+        let v = book.issue(&t, "peer", 8, PAYER_KEY, 10).unwrap();
+        assert_eq!((v.chunks_covered, v.cumulative_wei.as_str()), (8, "800"));
+        let again = book.issue(&t, "peer", 4, PAYER_KEY, 20).unwrap();
+        assert_eq!(again.chunks_covered, 8);
+        assert!(again.verify().is_ok());
+        let capped = book.issue(&t, "peer", 40, PAYER_KEY, 30).unwrap();
+        assert_eq!(
+            (capped.chunks_covered, capped.cumulative_wei.as_str()),
+            (10, "1000")
+        );
+
+        let mut changed = t;
+        changed.total_chunks = 11;
+        assert!(book.issue(&changed, "peer", 1, PAYER_KEY, 40).is_err());
+    }
+
+    #[test]
+    fn settlements_are_credited_only_by_the_node_they_name() {
+        // Two seeder nodes share PAYEE's wallet and each holds vouchers.
+        let mut issued = IssuedVoucherBook::default();
+        issued
+            .issue(&terms("a", ONE_CHI, 2), "peer-1", 2, PAYER_KEY, 100)
+            .unwrap();
+        issued
+            .issue(&terms("b", ONE_CHI, 2), "peer-2", 2, PAYER_KEY, 100)
+            .unwrap();
+        let due = issued.due_settlements(&payer(), 200, true);
+        assert_eq!(due.len(), 2, "each node is settled with separately");
+        assert!(due.iter().all(|d| d.payee == PAYEE));
+
+        // A transfer settling with peer-1 can't also settle peer-2's debt.
+        let memo = settlement_memo(&due[0].payee_peer);
+        assert!(check_settlement_memo(&memo, &due[0].payee_peer).is_ok());
+        assert!(check_settlement_memo(&memo, &due[1].payee_peer).is_err());
+        assert!(check_settlement_memo(&[], &due[1].payee_peer).is_err());
+    }
+
+    #[test]
+    fn issued_book_batches_due_settlements_per_seeder() {
+        let mut book = IssuedVoucherBook::default();
+        book.issue(&terms("a", ONE_CHI / 2, 2), "peer-1", 2, PAYER_KEY, 100)
+            .unwrap();
+        book.issue(&terms("b", ONE_CHI / 2, 2), "peer-1", 2, PAYER_KEY, 100)
+            .unwrap();
+        book.issue(&terms("c", 1000, 2), "peer-2", 2, PAYER_KEY, 100)
+            .unwrap();
+
+        // Two downloads from peer-1 add up to the threshold; peer-2 is small
+        // and recent.
+        let due = book.due_settlements(&payer(), 200, false);
+        assert_eq!(
+            due,
+            vec![DueSettlement {
+                payee: PAYEE.to_string(),
+                payee_peer: "peer-1".to_string(),
+                amount_wei: ONE_CHI,
+            }]
+        );
+        assert_eq!(book.due_settlements(&payer(), 200, true).len(), 2);
+        let aged = book.due_settlements(&payer(), 100 + SETTLEMENT_MAX_AGE_SECS, false);
+        assert_eq!(aged.len(), 2);
+        assert!(book.due_settlements("0xsomeoneelse", 200, true).is_empty());
+
+        book.record_settlement(IssuedSettlement {
+            tx_hash: "0xsettle".to_string(),
+            payer: payer(),
+            payee: PAYEE.to_string(),
+            payee_peer: "peer-1".to_string(),
+            amount_wei: ONE_CHI.to_string(),
+            fee_tx_hash: None,
+            settled_at: 200,
+            notified: false,
+        });
+        let due = book.due_settlements(&payer(), 200, true);
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].payee_peer, "peer-2");
+
+        assert_eq!(book.unnotified(&payer()).len(), 1);
+        assert!(book.mark_notified("0xSETTLE"));
+        assert!(book.unnotified(&payer()).is_empty());
+        assert!(!book.mark_notified("0xunknown"));
+    }
+
+    #[test]
+    fn books_persist_and_reload() {
+        let dir = tempfile::tempdir().unwrap();
+        let issued_path = dir.path().join(ISSUED_FILE);
+        let mut issued = IssuedVoucherBook::load(issued_path.clone());
+        issued
+            .issue(&terms("a", 1000, 10), "peer", 3, PAYER_KEY, 10)
+            .unwrap();
+        issued.persist().unwrap();
+        let reloaded = IssuedVoucherBook::load(issued_path);
+        assert_eq!(reloaded.balances(&payer())[0].outstanding_wei, "300");
+
+        let received_path = dir.path().join(RECEIVED_FILE);
+        let mut received = ReceivedVoucherBook::load(received_path.clone());
+        received.accept(voucher("a", 1000, 10, 3), 10).unwrap();
+        received.persist().unwrap();
+        let reloaded = ReceivedVoucherBook::load(received_path);
+        let balances = reloaded.balances();
+        assert_eq!(balances.len(), 1);
+        assert_eq!(balances[0].outstanding_wei, "300");
+        assert_eq!(balances[0].unsettled_since, Some(10));
+    }
+
+    #[test]
+    fn corrupt_received_book_is_kept_aside() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(RECEIVED_FILE);
+        std::fs::write(&path, "{\"sessions\": truncated").unwrap();
+
+        let mut received = ReceivedVoucherBook::load(path.clone());
+        assert!(received.balances().is_empty());
+        received.accept(voucher("a", 1000, 10, 3), 10).unwrap();
+        received.persist().unwrap();
+
+        let kept: Vec<_> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|p| p != &path)
+            .collect();
+        assert_eq!(kept.len(), 1);
+        assert_eq!(
+            std::fs::read_to_string(&kept[0]).unwrap(),
+            "{\"sessions\": truncated"
+        );
+        assert_eq!(ReceivedVoucherBook::load(path).balances().len(), 1);
+    }
+}
@@ -0,0 +1,698 @@
+//! NAT reachability and router port mapping.
+//!
+//! AutoNAT (part of the DHT behaviour) tells a node whether other peers can
+//! dial it back. When a node is classed as private, it asks its router to
+//! forward the libp2p ports and the local gateway port. The gateway port is
+//! the HTTP server that the relay's drive share proxy fetches from. UPnP IGD
+//! is tried first, then NAT-PMP.
+//!
+//! Seeder records only carry addresses that peers can reach: confirmed
+//! external addresses, relay circuits, and globally routable listen
+//! addresses (these last are dropped once AutoNAT says the node is private).
+
+use libp2p::multiaddr::Protocol;
+use libp2p::Multiaddr;
+use serde::Serialize;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Port of the local HTTP gateway to map; `0` disables mapping it.
+pub const GATEWAY_PORT_ENV: &str = "CHIRAL_GATEWAY_PORT";
+/// Drive/hosting gateway port used by the desktop app and the daemon.
+pub const DEFAULT_GATEWAY_PORT: u16 = 9419;
+/// Lease requested from the router; mappings are renewed at half of it.
+pub const MAPPING_LEASE_SECS: u32 = 60 * 60;
+const MAPPING_DESCRIPTION: &str = "chiral-network";
+const UPNP_SEARCH_TIMEOUT: Duration = Duration::from_secs(3);
+const NATPMP_PORT: u16 = 5351;
+const NATPMP_ATTEMPTS: u32 = 3;
+const NATPMP_INITIAL_TIMEOUT_MS: u64 = 250;
+
+/// Whether peers can dial this node directly, as judged by AutoNAT.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Reachability {
+    #[default]
+    Unknown,
+    Public,
+    Private,
+}
+
+impl Reachability {
+    pub fn from_autonat(status: &libp2p::autonat::NatStatus) -> Self {
+        match status {
+            libp2p::autonat::NatStatus::Public(_) => Self::Public,
+            libp2p::autonat::NatStatus::Private => Self::Private,
+            libp2p::autonat::NatStatus::Unknown => Self::Unknown,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MappingProtocol {
+    Tcp,
+    Udp,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MappingMethod {
+    Upnp,
+    NatPmp,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MappingPurpose {
+    Libp2p,
+    Gateway,
+}
+
+/// A local port we want forwarded by the router.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MappingRequest {
+    pub protocol: MappingProtocol,
+    pub port: u16,
+    pub purpose: MappingPurpose,
+}
+
+/// A forwarding rule the router accepted.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PortMapping {
+    pub protocol: MappingProtocol,
+    pub purpose: MappingPurpose,
+    pub method: MappingMethod,
+    pub internal_port: u16,
+    pub external_ip: IpAddr,
+    pub external_port: u16,
+}
+
+impl PortMapping {
+    /// Dialable address for a libp2p mapping (TCP, or QUIC over UDP).
+    pub fn external_multiaddr(&self) -> Option<Multiaddr> {
+        if self.purpose != MappingPurpose::Libp2p {
+            return None;
+        }
+        let base = Multiaddr::from(self.external_ip);
+        Some(match self.protocol {
+            MappingProtocol::Tcp => base.with(Protocol::Tcp(self.external_port)),
+            MappingProtocol::Udp => base
+                .with(Protocol::Udp(self.external_port))
+                .with(Protocol::QuicV1),
+        })
+    }
+}
+
+fn parse_gateway_port(raw: Option<&str>) -> Result<u16, String> {
+    let Some(raw) = raw else {
+        return Ok(DEFAULT_GATEWAY_PORT);
+    };
+    raw.trim().parse::<u16>().map_err(|_| {
+        format!(
+            "{} requires a port number from 0 to 65535, got '{}'",
+            GATEWAY_PORT_ENV, raw
+        )
+    })
+}
+
+/// Gateway port to map when private; 9419 when `CHIRAL_GATEWAY_PORT` is unset.
+pub fn gateway_port_from_env() -> Result<u16, String> {
+    match std::env::var(GATEWAY_PORT_ENV) {
+        Ok(raw) => parse_gateway_port(Some(&raw)),
+        Err(std::env::VarError::NotPresent) => parse_gateway_port(None),
+        Err(std::env::VarError::NotUnicode(_)) => {
+            Err(format!("{} must be valid UTF-8", GATEWAY_PORT_ENV))
+        }
+    }
+}
+
+/// Ports to forward: every direct IPv4 libp2p listener (TCP and QUIC) plus
+/// the gateway port. IPv6 listeners need no NAT mapping.
+pub fn mapping_requests(listeners: &[Multiaddr], gateway_port: u16) -> Vec<MappingRequest> {
+    let mut requests: Vec<MappingRequest> = Vec::new();
+    let mut push = |request: MappingRequest| {
+        let duplicate = requests
+            .iter()
+            .any(|r| r.protocol == request.protocol && r.port == request.port);
+        if !duplicate {
+            requests.push(request);
+        }
+    };
+
+    for addr in listeners {
+        let protocols: Vec<Protocol> = addr.iter().collect();
+        if !matches!(protocols.first(), Some(Protocol::Ip4(_)))
+            || protocols.iter().any(|p| matches!(p, Protocol::P2pCircuit))
+        {
+            continue;
+        }
+        let is_quic = protocols.iter().any(|p| matches!(p, Protocol::QuicV1));
+        for proto in &protocols {
+            match proto {
+                Protocol::Tcp(port) if *port != 0 => push(MappingRequest {
+                    protocol: MappingProtocol::Tcp,
+                    port: *port,
+                    purpose: MappingPurpose::Libp2p,
+                }),
+                Protocol::Udp(port) if *port != 0 && is_quic => push(MappingRequest {
+                    protocol: MappingProtocol::Udp,
+                    port: *port,
+                    purpose: MappingPurpose::Libp2p,
+                }),
+                _ => {}
+            }
+        }
+    }
+
+    if gateway_port != 0 {
+        push(MappingRequest {
+            protocol: MappingProtocol::Tcp,
+            port: gateway_port,
+            purpose: MappingPurpose::Gateway,
+        });
+    }
+    requests
+}
+
+fn is_global_v4(ip: Ipv4Addr) -> bool {
+    let octets = ip.octets();
+    // 100.64.0.0/10 is carrier-grade NAT space.
+    let shared = octets[0] == 100 && (octets[1] & 0xc0) == 64;
+    !(ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        || shared)
+}
+
+fn is_global_v6(ip: Ipv6Addr) -> bool {
+    !(ip.is_loopback() || ip.is_unspecified() || ip.is_unique_local() || ip.is_unicast_link_local())
+}
+
+/// True when the address starts with a host other peers on the internet
+/// could route to.
+pub fn is_publicly_routable(addr: &Multiaddr) -> bool {
+    match addr.iter().next() {
+        Some(Protocol::Ip4(ip)) => is_global_v4(ip),
+        Some(Protocol::Ip6(ip)) => is_global_v6(ip),
+        Some(Protocol::Dns(host)) | Some(Protocol::Dns4(host)) | Some(Protocol::Dns6(host)) => {
+            !host.eq_ignore_ascii_case("localhost")
+        }
+        _ => false,
+    }
+}
+
+/// Addresses worth publishing in seeder records for the given reachability.
+pub fn publishable_addrs(
+    reachability: Reachability,
+    listeners: &[Multiaddr],
+    external: &[Multiaddr],
+) -> Vec<Multiaddr> {
+    let is_circuit = |addr: &Multiaddr| addr.iter().any(|p| matches!(p, Protocol::P2pCircuit));
+    let mut out: Vec<Multiaddr> = Vec::new();
+    let mut push = |addr: &Multiaddr| {
+        if !out.contains(addr) {
+            out.push(addr.clone());
+        }
+    };
+
+    for addr in listeners.iter().filter(|a| is_circuit(a)) {
+        push(addr);
+    }
+    for addr in external.iter().filter(|a| is_publicly_routable(a)) {
+        push(addr);
+    }
+    if reachability != Reachability::Private {
+        for addr in listeners
+            .iter()
+            .filter(|a| !is_circuit(a) && is_publicly_routable(a))
+        {
+            push(addr);
+        }
+    }
+    out
+}
+
+/// AutoNAT status plus the router mappings currently held for this node.
+#[derive(Debug, Default)]
+pub struct NatState {
+    pub reachability: Reachability,
+    pub public_address: Option<Multiaddr>,
+    pub mappings: Vec<PortMapping>,
+    mapping_task: Option<Arc<AtomicBool>>,
+}
+
+impl NatState {
+    /// Record a new AutoNAT status. Returns a keep-running flag when a port
+    /// mapping task should be started for it.
+    ///
+    /// Going public stops the task only if it holds no mappings. Otherwise
+    /// the node is probably public because of them, and letting the leases
+    /// lapse would flip it back to private.
+    pub fn update_status(
+        &mut self,
+        status: &libp2p::autonat::NatStatus,
+    ) -> Option<Arc<AtomicBool>> {
+        self.reachability = Reachability::from_autonat(status);
+        self.public_address = match status {
+            libp2p::autonat::NatStatus::Public(addr) => Some(addr.clone()),
+            _ => None,
+        };
+        match self.reachability {
+            Reachability::Private if self.mapping_task.is_none() => {
+                let keep_running = Arc::new(AtomicBool::new(true));
+                self.mapping_task = Some(Arc::clone(&keep_running));
+                Some(keep_running)
+            }
+            Reachability::Public if self.mappings.is_empty() => {
+                self.stop_mapping();
+                None
+            }
+            _ => None,
+        }
+    }
+
+    pub fn mapping_active(&self) -> bool {
+        self.mapping_task.is_some()
+    }
+
+    pub fn stop_mapping(&mut self) {
+        if let Some(keep_running) = self.mapping_task.take() {
+            keep_running.store(false, Ordering::SeqCst);
+        }
+    }
+}
+
+/// Ask the router to forward each requested port, over UPnP first and then
+/// NAT-PMP for whatever UPnP didn't map. Failures are logged, not returned;
+/// the result holds only the mappings that were granted.
+pub async fn map_ports(requests: &[MappingRequest]) -> Vec<PortMapping> {
+    let mut mapped = match map_with_upnp(requests).await {
+        Ok(mapped) => mapped,
+        Err(e) => {
+            println!("[NAT] UPnP unavailable: {}", e);
+            Vec::new()
+        }
+    };
+
+    let remaining: Vec<&MappingRequest> = requests
+        .iter()
+        .filter(|r| {
+            !mapped
+                .iter()
+                .any(|m| m.protocol == r.protocol && m.internal_port == r.port)
+        })
+        .collect();
+    if remaining.is_empty() {
+        return mapped;
+    }
+
+    let Some(gateway) = default_gateway_v4() else {
+        println!("[NAT] NAT-PMP skipped: default gateway unknown");
+        return mapped;
+    };
+    for request in remaining {
+        match map_with_natpmp(gateway, request).await {
+            Ok(mapping) => mapped.push(mapping),
+            Err(e) => println!(
+                "[NAT] NAT-PMP mapping {:?}/{} failed: {}",
+                request.protocol, request.port, e
+            ),
+        }
+    }
+    mapped
+}
+
+async fn map_with_upnp(requests: &[MappingRequest]) -> Result<Vec<PortMapping>, String> {
+    let options = igd_next::SearchOptions {
+        timeout: Some(UPNP_SEARCH_TIMEOUT),
+        ..Default::default()
+    };
+    let gateway = igd_next::aio::tokio::search_gateway(options)
+        .await
+        .map_err(|e| format!("gateway search failed: {}", e))?;
+    let external_ip = gateway
+        .get_external_ip()
+        .await
+        .map_err(|e| format!("external IP lookup failed: {}", e))?;
+    let local_ip = local_ip_towards(gateway.addr)?;
+
+    let mut mapped = Vec::new();
+    for request in requests {
+        let protocol = match request.protocol {
+            MappingProtocol::Tcp => igd_next::PortMappingProtocol::TCP,
+            MappingProtocol::Udp => igd_next::PortMappingProtocol::UDP,
+        };
+        let local_addr = SocketAddr::new(local_ip, request.port);
+        match gateway
+            .add_port(
+                protocol,
+                request.port,
+                local_addr,
+                MAPPING_LEASE_SECS,
+                MAPPING_DESCRIPTION,
+            )
+            .await
+        {
+            Ok(()) => mapped.push(PortMapping {
+                protocol: request.protocol,
+                purpose: request.purpose,
+                method: MappingMethod::Upnp,
+                internal_port: request.port,
+                external_ip,
+                external_port: request.port,
+            }),
+            Err(e) => println!(
+                "[NAT] UPnP mapping {:?}/{} failed: {}",
+                request.protocol, request.port, e
+            ),
+        }
+    }
+    Ok(mapped)
+}
+
+/// The interface address the OS would use to reach `target`. Connecting a
+/// UDP socket sends nothing; it only selects the route.
+fn local_ip_towards(target: SocketAddr) -> Result<IpAddr, String> {
+    let socket = std::net::UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))
+        .map_err(|e| format!("failed to bind probe socket: {}", e))?;
+    socket
+        .connect(target)
+        .map_err(|e| format!("no route to gateway {}: {}", target, e))?;
+    socket
+        .local_addr()
+        .map(|addr| addr.ip())
+        .map_err(|e| format!("failed to read local address: {}", e))
+}
+
+/// IPv4 default gateway, read from the kernel routing table. Only Linux
+/// exposes it without extra dependencies; other platforms rely on UPnP.
+fn default_gateway_v4() -> Option<Ipv4Addr> {
+    if !cfg!(target_os = "linux") {
+        return None;
+    }
+    let table = std::fs::read_to_string("/proc/net/route").ok()?;
+    parse_default_gateway(&table)
+}
+
+/// Parse `/proc/net/route`: the default route has destination 0 and the
+/// RTF_GATEWAY flag, with the gateway in little-endian hex.
+fn parse_default_gateway(table: &str) -> Option<Ipv4Addr> {
+    const RTF_GATEWAY: u32 = 0x2;
+    table.lines().skip(1).find_map(|line| {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 4 || fields[1] != "00000000" {
+            return None;
+        }
+        let flags = u32::from_str_radix(fields[3], 16).ok()?;
+        if flags & RTF_GATEWAY == 0 {
+            return None;
+        }
+        let gateway = u32::from_str_radix(fields[2], 16).ok()?;
+        Some(Ipv4Addr::from(gateway.to_le_bytes()))
+    })
+}
+
+fn natpmp_opcode(protocol: MappingProtocol) -> u8 {
+    match protocol {
+        MappingProtocol::Udp => 1,
+        MappingProtocol::Tcp => 2,
+    }
+}
+
+/// RFC 6886 mapping request: version, opcode, reserved, internal port,
+/// suggested external port, lifetime.
+fn natpmp_map_request(protocol: MappingProtocol, port: u16, lifetime: u32) -> [u8; 12] {
+    let mut request = [0u8; 12];
+    request[1] = natpmp_opcode(protocol);
+    request[4..6].copy_from_slice(&port.to_be_bytes());
+    request[6..8].copy_from_slice(&port.to_be_bytes());
+    request[8..12].copy_from_slice(&lifetime.to_be_bytes());
+    request
+}
+
+fn natpmp_check_header(response: &[u8], opcode: u8, len: usize) -> Result<(), String> {
+    if response.len() < len {
+        return Err(format!("short NAT-PMP response ({} bytes)", response.len()));
+    }
+    if response[0] != 0 || response[1] != 128 + opcode {
+        return Err(format!(
+            "unexpected NAT-PMP response header {:02x}{:02x}",
+            response[0], response[1]
+        ));
+    }
+    let result = u16::from_be_bytes([response[2], response[3]]);
+    if result != 0 {
+        return Err(format!("NAT-PMP result code {}", result));
+    }
+    Ok(())
+}
+
+fn parse_natpmp_external_response(response: &[u8]) -> Result<Ipv4Addr, String> {
+    natpmp_check_header(response, 0, 12)?;
+    Ok(Ipv4Addr::new(
+        response[8],
+        response[9],
+        response[10],
+        response[11],
+    ))
+}
+
+/// Returns the external port the router assigned.
+fn parse_natpmp_map_response(response: &[u8], protocol: MappingProtocol) -> Result<u16, String> {
+    natpmp_check_header(response, natpmp_opcode(protocol), 16)?;
+    let lifetime = u32::from_be_bytes([response[12], response[13], response[14], response[15]]);
+    if lifetime == 0 {
+        return Err("NAT-PMP mapping granted with zero lifetime".to_string());
+    }
+    Ok(u16::from_be_bytes([response[10], response[11]]))
+}
+
+async fn natpmp_exchange(gateway: Ipv4Addr, request: &[u8]) -> Result<Vec<u8>, String> {
+    let socket = tokio::net::UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))
+        .await
+        .map_err(|e| format!("failed to bind NAT-PMP socket: {}", e))?;
+    socket
+        .connect((gateway, NATPMP_PORT))
+        .await
+        .map_err(|e| format!("failed to reach gateway {}: {}", gateway, e))?;
+
+    let mut buf = [0u8; 16];
+    for attempt in 0..NATPMP_ATTEMPTS {
+        socket
+            .send(request)
+            .await
+            .map_err(|e| format!("NAT-PMP send failed: {}", e))?;
+        let wait = Duration::from_millis(NATPMP_INITIAL_TIMEOUT_MS << attempt);
+        if let Ok(received) = tokio::time::timeout(wait, socket.recv(&mut buf)).await {
+            let len = received.map_err(|e| format!("NAT-PMP receive failed: {}", e))?;
+            return Ok(buf[..len].to_vec());
+        }
+    }
+    Err(format!("no NAT-PMP reply from {}", gateway))
+}
+
+async fn map_with_natpmp(
+    gateway: Ipv4Addr,
+    request: &MappingRequest,
+) -> Result<PortMapping, String> {
+    let external_ip = parse_natpmp_external_response(&natpmp_exchange(gateway, &[0, 0]).await?)?;
+    let map_request = natpmp_map_request(request.protocol, request.port, MAPPING_LEASE_SECS);
+    let external_port = parse_natpmp_map_response(
+        &natpmp_exchange(gateway, &map_request).await?,
+        request.protocol,
+    )?;
+    Ok(PortMapping {
+        protocol: request.protocol,
+        purpose: request.purpose,
+        method: MappingMethod::NatPmp,
+        internal_port: request.port,
+        external_ip: IpAddr::V4(external_ip),
+        external_port,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libp2p::autonat::NatStatus;
+
+    fn addr(s: &str) -> Multiaddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn gateway_port_defaults_and_rejects_garbage() {
+        assert_eq!(parse_gateway_port(None).unwrap(), DEFAULT_GATEWAY_PORT);
+        assert_eq!(parse_gateway_port(Some("0")).unwrap(), 0);
+        assert_eq!(parse_gateway_port(Some("8080")).unwrap(), 8080);
+        let err = parse_gateway_port(Some("http")).unwrap_err();
+        assert!(err.contains(GATEWAY_PORT_ENV));
+    }
+
+    #[test]
+    fn mapping_requests_cover_ipv4_listeners_and_gateway() {
+        let listeners = vec![
+            addr("/ip4/192.168.1.20/tcp/4100"),
+            addr("/ip4/10.0.0.5/tcp/4100"),
+            addr("/ip4/192.168.1.20/udp/4100/quic-v1"),
+            addr("/ip6/fd00::20/tcp/4100"),
+            addr("/ip4/203.0.113.1/tcp/4001/p2p/12D3KooWAHWpUyBsFvgC6fb9jjmtDtKMM1qUChiNjtBDrTTEAY5C/p2p-circuit"),
+        ];
+
+        let requests = mapping_requests(&listeners, 9419);
+
+        assert_eq!(
+            requests,
+            vec![
+                MappingRequest {
+                    protocol: MappingProtocol::Tcp,
+                    port: 4100,
+                    purpose: MappingPurpose::Libp2p,
+                },
+                MappingRequest {
+                    protocol: MappingProtocol::Udp,
+                    port: 4100,
+                    purpose: MappingPurpose::Libp2p,
+                },
+                MappingRequest {
+                    protocol: MappingProtocol::Tcp,
+                    port: 9419,
+                    purpose: MappingPurpose::Gateway,
+                },
+            ]
+        );
+        assert_eq!(mapping_requests(&listeners, 0).len(), 2);
+    }
+
+    #[test]
+    fn publishable_addrs_drop_unreachable_listeners() {
+        let circuit = addr(
+            "/ip4/203.0.113.1/tcp/4001/p2p/12D3KooWAHWpUyBsFvgC6fb9jjmtDtKMM1qUChiNjtBDrTTEAY5C/p2p-circuit",
+        );
+        let listeners = vec![
+            addr("/ip4/127.0.0.1/tcp/4100"),
+            addr("/ip4/192.168.1.20/tcp/4100"),
+            addr("/ip4/100.72.1.9/tcp/4100"),
+            addr("/ip4/198.51.100.7/tcp/4100"),
+            addr("/ip6/2001:db8::7/udp/4100/quic-v1"),
+            addr("/ip6/fe80::1/tcp/4100"),
+            circuit.clone(),
+        ];
+        let mapped = addr("/ip4/198.51.100.99/tcp/4100");
+        let external = vec![mapped.clone(), addr("/ip4/10.1.1.1/tcp/1")];
+
+        let public = publishable_addrs(Reachability::Public, &listeners, &external);
+        assert_eq!(
+            public,
+            vec![
+                circuit.clone(),
+                mapped.clone(),
+                addr("/ip4/198.51.100.7/tcp/4100"),
+                addr("/ip6/2001:db8::7/udp/4100/quic-v1"),
+            ]
+        );
+
+        let private = publishable_addrs(Reachability::Private, &listeners, &external);
+        assert_eq!(private, vec![circuit, mapped]);
+    }
+
+    #[test]
+    fn nat_state_starts_mapping_once_and_keeps_it_while_mapped() {
+        let mut state = NatState::default();
+
+        let keep_running = state
+            .update_status(&NatStatus::Private)
+            .expect("start mapping");
+        assert!(state.update_status(&NatStatus::Private).is_none());
+        assert!(state.update_status(&NatStatus::Unknown).is_none());
+        assert!(state.mapping_active());
+
+        // Public thanks to our own mapping: keep renewing it.
+        state.mappings.push(PortMapping {
+            protocol: MappingProtocol::Tcp,
+            purpose: MappingPurpose::Libp2p,
+            method: MappingMethod::Upnp,
+            internal_port: 4100,
+            external_ip: "198.51.100.99".parse().unwrap(),
+            external_port: 4100,
+        });
+        let public = addr("/ip4/198.51.100.99/tcp/4100");
+        assert!(state
+            .update_status(&NatStatus::Public(public.clone()))
+            .is_none());
+        assert_eq!(state.reachability, Reachability::Public);
+        assert_eq!(state.public_address, Some(public.clone()));
+        assert!(keep_running.load(Ordering::SeqCst));
+
+        // Public with nothing mapped: the router isn't needed.
+        state.mappings.clear();
+        state.update_status(&NatStatus::Public(public));
+        assert!(!state.mapping_active());
+        assert!(!keep_running.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn external_multiaddr_only_for_libp2p_mappings() {
+        let mut mapping = PortMapping {
+            protocol: MappingProtocol::Udp,
+            purpose: MappingPurpose::Libp2p,
+            method: MappingMethod::NatPmp,
+            internal_port: 4100,
+            external_ip: "198.51.100.99".parse().unwrap(),
+            external_port: 51000,
+        };
+        assert_eq!(
+            mapping.external_multiaddr(),
+            Some(addr("/ip4/198.51.100.99/udp/51000/quic-v1"))
+        );
+
+        mapping.purpose = MappingPurpose::Gateway;
+        assert_eq!(mapping.external_multiaddr(), None);
+    }
+
+    #[test]
+    fn parses_default_gateway_from_route_table() {
+        let table = "Iface\tDestination\tGateway \tFlags\tRefCnt\tUse\tMetric\tMask\n\
+                     eth0\t0000A8C0\t00000000\t0001\t0\t0\t100\t00FFFFFF\n\
+                     eth0\t00000000\t0101A8C0\t0003\t0\t0\t100\t00000000\n";
+        assert_eq!(
+            parse_default_gateway(table),
+            Some(Ipv4Addr::new(192, 168, 1, 1))
+        );
+        assert_eq!(parse_default_gateway(""), None);
+    }
+
+    #[test]
+    fn natpmp_messages_round_trip() {
+        assert_eq!(
+            natpmp_map_request(MappingProtocol::Tcp, 4100, 3600),
+            [0, 2, 0, 0, 0x10, 0x04, 0x10, 0x04, 0, 0, 0x0e, 0x10]
+        );
+
+        let external = [0, 128, 0, 0, 0, 0, 0, 9, 198, 51, 100, 99];
+        assert_eq!(
+            parse_natpmp_external_response(&external).unwrap(),
+            Ipv4Addr::new(198, 51, 100, 99)
+        );
+
+        let mapped = [
+            0, 129, 0, 0, 0, 0, 0, 9, 0x10, 0x04, 0xc7, 0x38, 0, 0, 0x0e, 0x10,
+        ];
+        assert_eq!(
+            parse_natpmp_map_response(&mapped, MappingProtocol::Udp).unwrap(),
+            51000
+        );
+        // Wrong opcode for the protocol.
+        assert!(parse_natpmp_map_response(&mapped, MappingProtocol::Tcp).is_err());
+
+        let refused = [0, 130, 0, 2, 0, 0, 0, 9, 0x10, 0x04, 0, 0, 0, 0, 0, 0];
+        let err = parse_natpmp_map_response(&refused, MappingProtocol::Tcp).unwrap_err();
+        assert!(err.contains("result code 2"));
+    }
+}
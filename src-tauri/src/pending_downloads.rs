@@ -0,0 +1,565 @@
+//! On-disk records of chunked downloads that have not finished yet.
+//!
+//! One JSON file per request lives under `<data_dir>/pending-downloads/`. It
+//! is written once a seeder's signed FileInfo has been accepted, updated as
+//! soon as a payment tx exists, and removed when the download completes or
+//! fails. Next to it, a `.leaves` journal keeps the Merkle leaf hash of
+//! every chunk that passed its inclusion proof (32 bytes at `index * 32`).
+//! Which chunks arrived is never taken on trust: on restart each chunk in
+//! the partial output file is re-hashed against its journaled leaf, so a
+//! torn write or an edited file is never mistaken for progress, and the
+//! finished file is still checked against the signed root.
+
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+const LEAF_LEN: usize = 32;
+
+/// Records untouched for this long are dropped on load; their seeder is
+/// almost certainly gone and the partial file is left for the user.
+pub const PENDING_DOWNLOAD_MAX_AGE_SECS: u64 = 7 * 24 * 60 * 60;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PendingPayment {
+    pub payment_tx: String,
+    pub payer_address: String,
+}
+
+/// Everything needed to pick an interrupted download back up: the signed
+/// FileInfo exactly as the seeder sent it, where the bytes are going, and
+/// the payment (if any) that already unlocked it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PendingDownload {
+    pub request_id: String,
+    pub file_hash: String,
+    /// File name as signed by the seeder (not the local output name).
+    pub file_name: String,
+    pub file_size: u64,
+    pub chunk_size: u32,
+    pub total_chunks: u32,
+    pub merkle_root: String,
+    pub price_wei: String,
+    pub wallet_address: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub folder_hash: Option<String>,
+    pub signature: String,
+    /// Voucher interval the seeder signed into the FileInfo (0 = none).
+    #[serde(default)]
+    pub voucher_interval: u32,
+    pub output_path: PathBuf,
+    /// Seeder that sent the FileInfo (and was paid, for paid files).
+    pub peer_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub payment: Option<PendingPayment>,
+    pub updated_at: u64,
+}
+
+impl PendingDownload {
+    /// Re-check the seeder's signature over the stored manifest. A record
+    /// edited on disk must not be able to redirect chunk verification or a
+    /// reused payment.
+    pub fn signature_valid(&self) -> bool {
+        let payload = crate::dht::file_info_sign_payload(
+            &self.file_hash,
+            &self.file_name,
+            self.file_size,
+            self.chunk_size,
+            self.total_chunks,
+            &self.merkle_root,
+            &self.price_wei,
+            &self.wallet_address,
+            self.folder_hash.as_deref(),
+            self.voucher_interval,
+        );
+        !self.signature.is_empty()
+            && !self.wallet_address.is_empty()
+            && crate::wallet::verify_signature(&payload, &self.signature, &self.wallet_address)
+    }
+
+    /// Whether chunks verified against this record are valid for a fresh
+    /// FileInfo describing the same bytes.
+    pub fn same_content(
+        &self,
+        file_hash: &str,
+        file_size: u64,
+        merkle_root: &str,
+        folder_hash: Option<&str>,
+    ) -> bool {
+        self.file_hash.eq_ignore_ascii_case(file_hash)
+            && self.file_size == file_size
+            && self.merkle_root.eq_ignore_ascii_case(merkle_root)
+            && self.folder_hash.as_deref() == folder_hash
+    }
+
+    /// Whether the stored payment can be presented again instead of paying
+    /// a second time: same seeder, same wallet, same price.
+    pub fn payment_reusable(&self, peer_id: &str, price_wei: &str, wallet_address: &str) -> bool {
+        self.payment.is_some()
+            && self.peer_id == peer_id
+            && self.price_wei == price_wei
+            && self.wallet_address.eq_ignore_ascii_case(wallet_address)
+    }
+}
+
+pub fn pending_downloads_dir() -> PathBuf {
+    crate::network::data_dir().join("pending-downloads")
+}
+
+fn record_path_in(dir: &Path, request_id: &str) -> PathBuf {
+    dir.join(format!("{}.json", safe_file_stem(request_id)))
+}
+
+fn leaves_path_in(dir: &Path, request_id: &str) -> PathBuf {
+    dir.join(format!("{}.leaves", safe_file_stem(request_id)))
+}
+
+fn safe_file_stem(request_id: &str) -> String {
+    request_id
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+pub fn save_pending_download(record: &PendingDownload) {
+    let dir = pending_downloads_dir();
+    if let Err(e) = save_pending_download_in(&dir, record) {
+        eprintln!(
+            "[Downloads] Failed to save resume state for {}: {}",
+            record.request_id, e
+        );
+    }
+}
+
+fn save_pending_download_in(dir: &Path, record: &PendingDownload) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(record)
+        .map_err(|e| format!("serialize pending download: {}", e))?;
+    crate::json_file::write_json_atomic(&record_path_in(dir, &record.request_id), &json)
+}
+
+/// Attach the payment tx to an existing record. Called right after the
+/// payment is broadcast, before the proof is sent, so a crash at any later
+/// point resumes with this tx instead of paying again.
+pub fn record_pending_download_payment(request_id: &str, payment_tx: &str, payer_address: &str) {
+    let dir = pending_downloads_dir();
+    if let Err(e) = record_pending_download_payment_in(&dir, request_id, payment_tx, payer_address)
+    {
+        eprintln!(
+            "[Downloads] Failed to record payment {} for {}: {}",
+            payment_tx, request_id, e
+        );
+    }
+}
+
+fn record_pending_download_payment_in(
+    dir: &Path,
+    request_id: &str,
+    payment_tx: &str,
+    payer_address: &str,
+) -> Result<(), String> {
+    let path = record_path_in(dir, request_id);
+    let data =
+        std::fs::read_to_string(&path).map_err(|e| format!("read {}: {}", path.display(), e))?;
+    let mut record: PendingDownload =
+        serde_json::from_str(&data).map_err(|e| format!("parse {}: {}", path.display(), e))?;
+    record.payment = Some(PendingPayment {
+        payment_tx: payment_tx.to_string(),
+        payer_address: payer_address.to_string(),
+    });
+    record.updated_at = crate::drive_storage::now_secs().unwrap_or(record.updated_at);
+    save_pending_download_in(dir, &record)
+}
+
+pub fn remove_pending_download(request_id: &str) {
+    remove_pending_download_in(&pending_downloads_dir(), request_id);
+}
+
+fn remove_pending_download_in(dir: &Path, request_id: &str) {
+    for path in [
+        record_path_in(dir, request_id),
+        leaves_path_in(dir, request_id),
+    ] {
+        match std::fs::remove_file(&path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => eprintln!(
+                "[Downloads] Failed to remove resume state {}: {}",
+                path.display(),
+                e
+            ),
+        }
+    }
+}
+
+/// Journal the leaf hash of a chunk that passed its inclusion proof and
+/// was written to the output file.
+pub fn record_verified_chunk(request_id: &str, index: u32, leaf: &[u8; LEAF_LEN]) {
+    let dir = pending_downloads_dir();
+    if let Err(e) = record_verified_chunk_in(&dir, request_id, index, leaf) {
+        eprintln!(
+            "[Downloads] Failed to journal chunk {} for {}: {}",
+            index, request_id, e
+        );
+    }
+}
+
+fn record_verified_chunk_in(
+    dir: &Path,
+    request_id: &str,
+    index: u32,
+    leaf: &[u8; LEAF_LEN],
+) -> Result<(), String> {
+    std::fs::create_dir_all(dir)
+        .map_err(|e| format!("create pending download directory {}: {}", dir.display(), e))?;
+    let path = leaves_path_in(dir, request_id);
+    std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(&path)
+        .and_then(|mut f| {
+            f.seek(SeekFrom::Start(index as u64 * LEAF_LEN as u64))?;
+            f.write_all(leaf)
+        })
+        .map_err(|e| format!("write {}: {}", path.display(), e))
+}
+
+/// Forget every journaled leaf, for a download that starts over.
+pub fn clear_verified_chunks(request_id: &str) {
+    let path = leaves_path_in(&pending_downloads_dir(), request_id);
+    if let Err(e) = std::fs::remove_file(&path) {
+        if e.kind() != std::io::ErrorKind::NotFound {
+            eprintln!(
+                "[Downloads] Failed to clear chunk journal {}: {}",
+                path.display(),
+                e
+            );
+        }
+    }
+}
+
+/// Journaled leaf per chunk; `None` for chunks that never verified.
+pub fn load_verified_leaves(request_id: &str, total_chunks: u32) -> Vec<Option<[u8; LEAF_LEN]>> {
+    load_verified_leaves_in(&pending_downloads_dir(), request_id, total_chunks)
+}
+
+fn load_verified_leaves_in(
+    dir: &Path,
+    request_id: &str,
+    total_chunks: u32,
+) -> Vec<Option<[u8; LEAF_LEN]>> {
+    let data = std::fs::read(leaves_path_in(dir, request_id)).unwrap_or_default();
+    (0..total_chunks as usize)
+        .map(|index| {
+            let leaf: [u8; LEAF_LEN] = data
+                .get(index * LEAF_LEN..(index + 1) * LEAF_LEN)?
+                .try_into()
+                .ok()?;
+            // Gaps in the sparse journal read back as zeros.
+            (leaf != [0u8; LEAF_LEN]).then_some(leaf)
+        })
+        .collect()
+}
+
+/// Records to resume, oldest first. Unreadable records and ones older than
+/// [`PENDING_DOWNLOAD_MAX_AGE_SECS`] are deleted.
+pub fn load_pending_downloads() -> Vec<PendingDownload> {
+    let now = crate::drive_storage::now_secs().unwrap_or(0);
+    load_pending_downloads_from_dir(&pending_downloads_dir(), now)
+}
+
+fn load_pending_downloads_from_dir(dir: &Path, now: u64) -> Vec<PendingDownload> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut records = Vec::new();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("json") {
+            continue;
+        }
+        let record = std::fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|data| {
+                serde_json::from_str::<PendingDownload>(&data).map_err(|e| e.to_string())
+            });
+        match record {
+            Ok(record)
+                if now.saturating_sub(record.updated_at) <= PENDING_DOWNLOAD_MAX_AGE_SECS =>
+            {
+                records.push(record)
+            }
+            Ok(record) => {
+                println!(
+                    "🗑️ Dropping stale resume state for {} ({})",
+                    record.request_id, record.file_name
+                );
+                let _ = std::fs::remove_file(&path);
+            }
+            Err(e) => {
+                eprintln!(
+                    "[Downloads] Discarding unreadable resume state {}: {}",
+                    path.display(),
+                    e
+                );
+                let _ = std::fs::remove_file(&path);
+            }
+        }
+    }
+    records.sort_by_key(|r| r.updated_at);
+    records
+}
+
+/// Hash every chunk already present in `path` against its journaled leaf.
+/// Returns the per-chunk bitmap and the number of verified bytes. A missing
+/// or short file simply yields fewer verified chunks.
+pub fn verify_existing_chunks(
+    path: &Path,
+    leaves: &[Option<[u8; LEAF_LEN]>],
+    chunk_size: u32,
+    file_size: u64,
+) -> (Vec<bool>, u64) {
+    let mut received = vec![false; leaves.len()];
+    let mut verified_bytes = 0u64;
+    let Ok(mut file) = std::fs::File::open(path) else {
+        return (received, verified_bytes);
+    };
+    let mut buf = vec![0u8; chunk_size as usize];
+    for (index, expected) in leaves.iter().enumerate() {
+        let offset = index as u64 * chunk_size as u64;
+        if offset >= file_size {
+            break;
+        }
+        let len = std::cmp::min(chunk_size as u64, file_size - offset) as usize;
+        if file.read_exact(&mut buf[..len]).is_err() {
+            break;
+        }
+        if expected.is_some_and(|leaf| crate::merkle::leaf_hash(&buf[..len]) == leaf) {
+            received[index] = true;
+            verified_bytes += len as u64;
+        }
+    }
+    (received, verified_bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(request_id: &str, updated_at: u64) -> PendingDownload {
+        PendingDownload {
+            request_id: request_id.to_string(),
+            file_hash: "abc".to_string(),
+            file_name: "doc.pdf".to_string(),
+            file_size: 10,
+            chunk_size: 4,
+            total_chunks: 3,
+            merkle_root: "root".to_string(),
+            price_wei: "0".to_string(),
+            wallet_address: "0x1234".to_string(),
+            folder_hash: None,
+            signature: "sig".to_string(),
+            voucher_interval: 0,
+            output_path: PathBuf::from("/tmp/doc.pdf"),
+            peer_id: "12D3KooWpeer".to_string(),
+            payment: None,
+            updated_at,
+        }
+    }
+
+    #[test]
+    fn save_load_and_remove_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut record = sample("req/1", 100);
+        save_pending_download_in(dir.path(), &record).unwrap();
+        record.payment = Some(PendingPayment {
+            payment_tx: "0xtx".to_string(),
+            payer_address: "0xpayer".to_string(),
+        });
+        save_pending_download_in(dir.path(), &record).unwrap();
+
+        let loaded = load_pending_downloads_from_dir(dir.path(), 200);
+        assert_eq!(loaded, vec![record.clone()]);
+
+        remove_pending_download_in(dir.path(), "req/1");
+        assert!(load_pending_downloads_from_dir(dir.path(), 200).is_empty());
+        // Removing twice is not an error.
+        remove_pending_download_in(dir.path(), "req/1");
+    }
+
+    #[test]
+    fn record_payment_updates_existing_record_only() {
+        let dir = tempfile::tempdir().unwrap();
+        save_pending_download_in(dir.path(), &sample("req", 5)).unwrap();
+
+        record_pending_download_payment_in(dir.path(), "req", "0xtx", "0xpayer").unwrap();
+        let loaded = load_pending_downloads_from_dir(dir.path(), 5);
+        assert_eq!(
+            loaded[0].payment,
+            Some(PendingPayment {
+                payment_tx: "0xtx".to_string(),
+                payer_address: "0xpayer".to_string(),
+            })
+        );
+        assert!(record_pending_download_payment_in(dir.path(), "other", "0xtx", "0xp").is_err());
+    }
+
+    #[test]
+    fn request_ids_cannot_escape_the_directory() {
+        let dir = Path::new("/data/pending-downloads");
+        let path = record_path_in(dir, "../../etc/passwd");
+        assert_eq!(path.parent(), Some(dir));
+    }
+
+    #[test]
+    fn load_drops_stale_and_unreadable_records() {
+        let dir = tempfile::tempdir().unwrap();
+        save_pending_download_in(dir.path(), &sample("fresh", 1_000_000)).unwrap();
+        save_pending_download_in(dir.path(), &sample("stale", 1)).unwrap();
+        std::fs::write(dir.path().join("broken.json"), "{not json").unwrap();
+
+        let loaded = load_pending_downloads_from_dir(dir.path(), 1_000_000);
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[0].request_id, "fresh");
+        assert!(!dir.path().join("stale.json").exists());
+        assert!(!dir.path().join("broken.json").exists());
+    }
+
+    #[test]
+    fn load_of_missing_directory_is_empty() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(load_pending_downloads_from_dir(&dir.path().join("nope"), 0).is_empty());
+    }
+
+    fn leaves_of(chunks: &[&[u8]]) -> Vec<Option<[u8; 32]>> {
+        chunks
+            .iter()
+            .map(|c| Some(crate::merkle::leaf_hash(c)))
+            .collect()
+    }
+
+    #[test]
+    fn verify_existing_chunks_only_counts_matching_chunks() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("partial");
+        let leaves = leaves_of(&[b"aaaa", b"bbbb", b"cc"]);
+        // Middle chunk never arrived (zero-filled), last chunk is present.
+        std::fs::write(&path, b"aaaa\0\0\0\0cc").unwrap();
+
+        let (received, bytes) = verify_existing_chunks(&path, &leaves, 4, 10);
+        assert_eq!(received, vec![true, false, true]);
+        assert_eq!(bytes, 6);
+
+        // Bytes on disk without a journaled leaf are not trusted.
+        let mut unjournaled = leaves.clone();
+        unjournaled[2] = None;
+        let (received, _) = verify_existing_chunks(&path, &unjournaled, 4, 10);
+        assert_eq!(received, vec![true, false, false]);
+    }
+
+    #[test]
+    fn verify_existing_chunks_handles_short_and_missing_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("partial");
+        let leaves = leaves_of(&[b"aaaa", b"bbbb", b"cc"]);
+        std::fs::write(&path, b"aaaab").unwrap();
+
+        let (received, bytes) = verify_existing_chunks(&path, &leaves, 4, 10);
+        assert_eq!(received, vec![true, false, false]);
+        assert_eq!(bytes, 4);
+
+        let (received, bytes) = verify_existing_chunks(&dir.path().join("gone"), &leaves, 4, 10);
+        assert_eq!(received, vec![false; 3]);
+        assert_eq!(bytes, 0);
+    }
+
+    #[test]
+    fn leaf_journal_round_trips_out_of_order_and_is_removed_with_the_record() {
+        let dir = tempfile::tempdir().unwrap();
+        save_pending_download_in(dir.path(), &sample("req", 5)).unwrap();
+        let leaf0 = crate::merkle::leaf_hash(b"zero");
+        let leaf2 = crate::merkle::leaf_hash(b"two");
+        record_verified_chunk_in(dir.path(), "req", 2, &leaf2).unwrap();
+        record_verified_chunk_in(dir.path(), "req", 0, &leaf0).unwrap();
+
+        assert_eq!(
+            load_verified_leaves_in(dir.path(), "req", 4),
+            vec![Some(leaf0), None, Some(leaf2), None]
+        );
+        // Only .json files are records.
+        assert_eq!(load_pending_downloads_from_dir(dir.path(), 5).len(), 1);
+
+        remove_pending_download_in(dir.path(), "req");
+        assert_eq!(
+            load_verified_leaves_in(dir.path(), "req", 2),
+            vec![None, None]
+        );
+    }
+
+    #[test]
+    fn payment_is_only_reusable_with_the_same_seeder_and_terms() {
+        let mut record = sample("req", 0);
+        record.price_wei = "100".to_string();
+        assert!(!record.payment_reusable("12D3KooWpeer", "100", "0x1234"));
+        record.payment = Some(PendingPayment {
+            payment_tx: "0xtx".to_string(),
+            payer_address: "0xpayer".to_string(),
+        });
+        assert!(record.payment_reusable("12D3KooWpeer", "100", "0X1234"));
+        assert!(!record.payment_reusable("12D3KooWother", "100", "0x1234"));
+        assert!(!record.payment_reusable("12D3KooWpeer", "200", "0x1234"));
+        assert!(!record.payment_reusable("12D3KooWpeer", "100", "0x9999"));
+    }
+
+    #[test]
+    fn signature_valid_rejects_edited_records() {
+        let private_key = "0x0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef";
+        let probe = b"derive-test-wallet";
+        let wallet = crate::wallet::recover_signer(
+            probe,
+            &crate::wallet::sign_message(private_key, probe).unwrap(),
+        )
+        .unwrap();
+
+        let mut record = sample("req", 0);
+        record.wallet_address = wallet;
+        let payload = crate::dht::file_info_sign_payload(
+            &record.file_hash,
+            &record.file_name,
+            record.file_size,
+            record.chunk_size,
+            record.total_chunks,
+            &record.merkle_root,
+            &record.price_wei,
+            &record.wallet_address,
+            None,
+            0,
+        );
+        record.signature = crate::wallet::sign_message(private_key, &payload).unwrap();
+        assert!(record.signature_valid());
+
+        let mut edited = record.clone();
+        edited.merkle_root = "forged".to_string();
+        assert!(!edited.signature_valid());
+        let mut edited = record;
+        edited.price_wei = "1".to_string();
+        assert!(!edited.signature_valid());
+    }
+
+    #[test]
+    fn same_content_requires_identical_manifest() {
+        let record = sample("req", 0);
+        assert!(record.same_content("ABC", 10, "ROOT", None));
+        assert!(!record.same_content("abc", 11, "root", None));
+        assert!(!record.same_content("abc", 10, "other", None));
+        assert!(!record.same_content("abc", 10, "root", Some("folder")));
+    }
+}
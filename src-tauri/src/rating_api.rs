@@ -10,8 +10,8 @@ use std::collections::HashMap;
 use std::sync::Arc;
 
 use crate::rating_storage::{
-    self, compute_reputation_for_wallet, RatingState, ReputationEvent, TransferOutcome,
-    LOOKBACK_SECS,
+    self, compute_reputation_for_wallet, RatingState, ReputationBreakdown, ReputationEvent,
+    TransferOutcome, LOOKBACK_SECS,
 };
 use crate::reputation::{self, ReputationIssuerKeyRecord, ReputationVerdictPayload};
 
@@ -32,6 +32,10 @@ struct SubmitTransferRequest {
     issuer_wallet: Option<String>,
     #[serde(default)]
     verdict_signature: Option<String>,
+    /// Signed issue time. Verdicts without one are cached here but not
+    /// gossiped to the DHT.
+    #[serde(default)]
+    issued_at: Option<u64>,
 }
 
 #[derive(Deserialize)]
@@ -58,6 +62,7 @@ struct WalletReputationResponse {
     failed_count: usize,
     transaction_count: usize,
     total_earned_wei: String,
+    breakdown: ReputationBreakdown,
     events: Vec<ReputationEvent>,
 }
 
@@ -161,11 +166,6 @@ fn parse_wei(value: &str) -> Result<u128, String> {
         .map_err(|_| "amountWei must be an integer wei string".to_string())
 }
 
-fn parse_hex_u128(hex: &str) -> Result<u128, String> {
-    let value = hex.trim_start_matches("0x");
-    u128::from_str_radix(value, 16).map_err(|e| format!("Invalid hex value: {}", e))
-}
-
 fn validate_reputation_tx_hash(tx_hash: &str) -> Result<String, String> {
     let tx_hash = tx_hash.trim();
     if tx_hash.len() != 66
@@ -177,6 +177,18 @@ fn validate_reputation_tx_hash(tx_hash: &str) -> Result<String, String> {
     Ok(tx_hash.to_ascii_lowercase())
 }
 
+/// A submitted verdict must have been signed just now; older ones arrive
+/// through DHT sync instead. Returns 0 for untimestamped verdicts.
+fn validate_issued_at(issued_at: Option<u64>, now: u64) -> Result<u64, String> {
+    let Some(issued_at) = issued_at.filter(|t| *t > 0) else {
+        return Ok(0);
+    };
+    if issued_at.abs_diff(now) > reputation::MAX_VERDICT_CLOCK_SKEW_SECS {
+        return Err("issuedAt must be within 10 minutes of the server clock".to_string());
+    }
+    Ok(issued_at)
+}
+
 fn outcome_label(outcome: TransferOutcome) -> &'static str {
     match outcome {
         TransferOutcome::Completed => "completed",
@@ -191,6 +203,7 @@ fn transfer_verdict_payload(
     transfer_id: &str,
     file_hash: &str,
     tx_hash: Option<&str>,
+    issued_at: u64,
 ) -> ReputationVerdictPayload {
     ReputationVerdictPayload {
         transfer_id: transfer_id.to_string(),
@@ -200,6 +213,7 @@ fn transfer_verdict_payload(
         amount_wei: amount_wei.to_string(),
         outcome: outcome_label(req.outcome).to_string(),
         tx_hash: tx_hash.map(str::to_string),
+        issued_at,
     }
 }
 
@@ -207,10 +221,7 @@ async fn verify_transfer_verdict(
     state: &RatingState,
     req: &SubmitTransferRequest,
     downloader_wallet: &str,
-    amount_wei: u128,
-    transfer_id: &str,
-    file_hash: &str,
-    tx_hash: Option<&str>,
+    verdict: &ReputationVerdictPayload,
 ) -> Result<(String, String), String> {
     let issuer_wallet = req
         .issuer_wallet
@@ -234,18 +245,10 @@ async fn verify_transfer_verdict(
         .fetch_issuer_key(&issuer_wallet)
         .await?
         .ok_or_else(|| format!("No reputation issuer key published for {issuer_wallet}"))?;
-    let verdict = transfer_verdict_payload(
-        req,
-        downloader_wallet,
-        amount_wei,
-        transfer_id,
-        file_hash,
-        tx_hash,
-    );
     reputation::verify_reputation_verdict_for_wallet(
         &issuer_record,
         &issuer_wallet,
-        &verdict,
+        verdict,
         verdict_signature,
     )?;
     Ok((issuer_wallet, verdict_signature.to_string()))
@@ -309,87 +312,6 @@ async fn get_issuer_key(
     }
 }
 
-/// Validate paid transfer via on-chain transaction data.
-async fn verify_payment_tx(
-    tx_hash: &str,
-    expected_from: &str,
-    expected_to: &str,
-    min_value_wei: u128,
-) -> Result<(), String> {
-    let rpc = crate::geth::rpc_endpoint();
-    let client = reqwest::Client::new();
-
-    let tx_payload = serde_json::json!({
-        "jsonrpc": "2.0",
-        "method": "eth_getTransactionByHash",
-        "params": [tx_hash],
-        "id": 1
-    });
-    let tx_resp = client
-        .post(&rpc)
-        .json(&tx_payload)
-        .send()
-        .await
-        .map_err(|e| format!("Failed to query tx: {}", e))?;
-    let tx_json: serde_json::Value = tx_resp
-        .json()
-        .await
-        .map_err(|e| format!("Failed to parse tx response: {}", e))?;
-    let tx = tx_json.get("result").ok_or("Tx result missing")?;
-    if tx.is_null() {
-        return Err("Transaction not found".to_string());
-    }
-
-    let tx_from = tx.get("from").and_then(|v| v.as_str()).unwrap_or_default();
-    let tx_to = tx.get("to").and_then(|v| v.as_str()).unwrap_or_default();
-    if !tx_from.eq_ignore_ascii_case(expected_from) {
-        return Err("Transaction sender does not match downloader wallet".to_string());
-    }
-    if !tx_to.eq_ignore_ascii_case(expected_to) {
-        return Err("Transaction recipient does not match seeder wallet".to_string());
-    }
-
-    let tx_value_hex = tx
-        .get("value")
-        .and_then(|v| v.as_str())
-        .ok_or("Transaction value missing")?;
-    let tx_value = parse_hex_u128(tx_value_hex)?;
-    if tx_value < min_value_wei {
-        return Err("Transaction value is below expected amount".to_string());
-    }
-
-    let receipt_payload = serde_json::json!({
-        "jsonrpc": "2.0",
-        "method": "eth_getTransactionReceipt",
-        "params": [tx_hash],
-        "id": 2
-    });
-    let receipt_resp = client
-        .post(&rpc)
-        .json(&receipt_payload)
-        .send()
-        .await
-        .map_err(|e| format!("Failed to query receipt: {}", e))?;
-    let receipt_json: serde_json::Value = receipt_resp
-        .json()
-        .await
-        .map_err(|e| format!("Failed to parse receipt response: {}", e))?;
-    let receipt = receipt_json.get("result").ok_or("Receipt result missing")?;
-    if receipt.is_null() {
-        return Err("Transaction not yet confirmed".to_string());
-    }
-
-    let status = receipt
-        .get("status")
-        .and_then(|v| v.as_str())
-        .unwrap_or("0x0");
-    if status != "0x1" {
-        return Err("Transaction failed on-chain".to_string());
-    }
-
-    Ok(())
-}
-
 /// POST /api/ratings/transfer — submit or update a transfer outcome event.
 async fn submit_transfer(
     Extension(state): Extension<Arc<RatingState>>,
@@ -423,6 +345,14 @@ async fn submit_transfer(
         Ok(v) => v,
         Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
     };
+    let now = match rating_storage::now_secs() {
+        Ok(value) => value,
+        Err(err) => return (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    };
+    let issued_at = match validate_issued_at(req.issued_at, now) {
+        Ok(value) => value,
+        Err(err) => return (StatusCode::BAD_REQUEST, err).into_response(),
+    };
 
     let mut tx_hash = req
         .tx_hash
@@ -445,7 +375,7 @@ async fn submit_transfer(
             Ok(v) => v,
             Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
         };
-        if let Err(err) = verify_payment_tx(
+        if let Err(err) = reputation::verify_payment_tx(
             &validated_tx_hash,
             &downloader_wallet,
             &req.seeder_wallet,
@@ -470,23 +400,32 @@ async fn submit_transfer(
             .into_response();
     }
 
-    let (issuer_wallet, verdict_signature) = match verify_transfer_verdict(
-        &state,
+    let verdict = transfer_verdict_payload(
         &req,
         &downloader_wallet,
         amount_wei,
         &transfer_id,
         &file_hash,
         tx_hash.as_deref(),
-    )
-    .await
-    {
-        Ok(v) => v,
-        Err(err) => return (StatusCode::BAD_REQUEST, err).into_response(),
-    };
-    let now = match rating_storage::now_secs() {
-        Ok(value) => value,
-        Err(err) => return (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+        issued_at,
+    );
+    let (issuer_wallet, verdict_signature) =
+        match verify_transfer_verdict(&state, &req, &downloader_wallet, &verdict).await {
+            Ok(v) => v,
+            Err(err) => return (StatusCode::BAD_REQUEST, err).into_response(),
+        };
+    let issued_at = (issued_at > 0).then_some(issued_at);
+    // Payment was verified above whenever there was one to verify.
+    let payment_verified = amount_wei > 0;
+    let downloader_first_tx_at = match reputation::first_outgoing_tx_at(&downloader_wallet).await {
+        Ok(at) => at,
+        Err(e) => {
+            println!(
+                "[REPUTATION] Wallet age lookup for {} failed: {}",
+                downloader_wallet, e
+            );
+            None
+        }
     };
     let event_id = rating_storage::generate_event_id(
         &transfer_id,
@@ -502,10 +441,16 @@ async fn submit_transfer(
         existing.tx_hash = tx_hash.clone();
         existing.issuer_wallet = Some(issuer_wallet);
         existing.verdict_signature = Some(verdict_signature);
+        existing.issued_at = issued_at;
+        existing.payment_verified = payment_verified;
+        if downloader_first_tx_at.is_some() {
+            existing.downloader_first_tx_at = downloader_first_tx_at;
+        }
         existing.updated_at = now;
         let updated = existing.clone();
         drop(m);
         state.persist().await;
+        spawn_verdict_publish(&state, &updated);
         return (StatusCode::OK, Json(updated)).into_response();
     }
 
@@ -522,6 +467,11 @@ async fn submit_transfer(
         rating_comment: None,
         issuer_wallet: Some(issuer_wallet),
         verdict_signature: Some(verdict_signature),
+        issued_at,
+        payment_verified,
+        downloader_first_tx_at,
+        chain_checked_at: Some(now),
+        chain_checks: 1,
         created_at: now,
         updated_at: now,
     };
@@ -529,9 +479,28 @@ async fn submit_transfer(
     m.events.push(event.clone());
     drop(m);
     state.persist().await;
+    spawn_verdict_publish(&state, &event);
     (StatusCode::CREATED, Json(event)).into_response()
 }
 
+/// Re-publish the seeder's verdict bundle in the background so the new
+/// verdict reaches nodes that never talk to this server.
+fn spawn_verdict_publish(state: &Arc<RatingState>, event: &ReputationEvent) {
+    if event.issued_at.is_none() {
+        return;
+    }
+    let state = Arc::clone(state);
+    let seeder_wallet = event.seeder_wallet.clone();
+    tokio::spawn(async move {
+        if let Err(e) = state.publish_verdicts(&seeder_wallet).await {
+            println!(
+                "[REPUTATION] Failed to gossip verdicts about {}: {}",
+                seeder_wallet, e
+            );
+        }
+    });
+}
+
 /// GET /api/ratings/:wallet — wallet reputation summary + events.
 async fn get_reputation(
     Extension(state): Extension<Arc<RatingState>>,
@@ -545,6 +514,7 @@ async fn get_reputation(
         Ok(value) => value,
         Err(err) => return (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
     };
+    state.sync_verdicts_for(std::slice::from_ref(&wallet)).await;
     let m = state.manifest.read().await;
     let snapshot = compute_reputation_for_wallet(&m.events, &wallet, now);
 
@@ -568,6 +538,7 @@ async fn get_reputation(
         failed_count: snapshot.failed_count,
         transaction_count: snapshot.transaction_count,
         total_earned_wei: snapshot.total_earned_wei,
+        breakdown: snapshot.breakdown,
         events,
     })
     .into_response()
@@ -586,6 +557,7 @@ async fn batch_reputation(
         Ok(value) => value,
         Err(err) => return (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
     };
+    state.sync_verdicts_for(&wallets).await;
     let m = state.manifest.read().await;
     let mut out = HashMap::new();
 
@@ -647,6 +619,7 @@ mod tests {
             tx_hash: tx_hash.map(str::to_string),
             issuer_wallet: None,
             verdict_signature: None,
+            issued_at: None,
         }
     }
 
@@ -864,4 +837,36 @@ mod tests {
         assert_eq!(response.status(), StatusCode::BAD_REQUEST);
         assert!(state.manifest.read().await.events.is_empty());
     }
+
+    #[test]
+    fn issued_at_must_be_close_to_the_server_clock() {
+        let now = 1_700_000_000;
+        assert_eq!(validate_issued_at(None, now), Ok(0));
+        assert_eq!(validate_issued_at(Some(0), now), Ok(0));
+        assert_eq!(validate_issued_at(Some(now - 30), now), Ok(now - 30));
+        assert_eq!(validate_issued_at(Some(now + 30), now), Ok(now + 30));
+        assert!(validate_issued_at(Some(now - 3_600), now).is_err());
+        assert!(validate_issued_at(Some(now + 3_600), now).is_err());
+    }
+
+    #[test]
+    fn transfer_verdict_payload_signs_the_issue_time() {
+        let req = request("0", None);
+        let payload =
+            transfer_verdict_payload(&req, WALLET_A, 0, "transfer-1", FILE_HASH, None, 42);
+        assert_eq!(payload.issued_at, 42);
+        assert_eq!(payload.outcome, "completed");
+        assert_eq!(payload.downloader_wallet, WALLET_A);
+    }
+
+    #[tokio::test]
+    async fn submit_transfer_rejects_stale_issue_time_before_persisting() {
+        let (_dir, state) = test_state();
+        let mut req = request("0", None);
+        req.issued_at = Some(1);
+        let response = submit_transfer(Extension(state.clone()), headers(), Json(req)).await;
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert!(state.manifest.read().await.events.is_empty());
+    }
 }
@@ -1,17 +1,43 @@
+use futures::StreamExt;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::sync::{Mutex, RwLock};
 
 use crate::dht::DhtService;
-use crate::reputation::{self, ReputationIssuerKeyRecord};
+use crate::reputation::{self, ReputationIssuerKeyRecord, ReputationVerdictPayload, SignedVerdict};
 
 pub const BASE_ELO: f64 = 50.0;
 pub const MIN_ELO: f64 = 0.0;
 pub const MAX_ELO: f64 = 100.0;
 pub const LOOKBACK_DAYS: u64 = 180;
 pub const LOOKBACK_SECS: u64 = LOOKBACK_DAYS * 24 * 60 * 60;
+/// A wallet's gossiped verdicts are re-fetched at most this often.
+pub const VERDICT_SYNC_INTERVAL_SECS: u64 = 60;
+/// Downloader wallets younger than this when they score a transfer count
+/// for proportionally less.
+pub const MATURE_WALLET_AGE_SECS: u64 = 30 * 24 * 60 * 60;
+/// Wallet-age weight floor, also used when the age is unknown.
+pub const MIN_WALLET_AGE_WEIGHT: f64 = 0.1;
+/// Bounds on the weight a downloader's own Elo gives its verdicts.
+pub const MIN_COUNTERPARTY_WEIGHT: f64 = 0.25;
+pub const MAX_COUNTERPARTY_WEIGHT: f64 = 1.5;
+/// Total event weight one downloader may contribute to a seeder's score;
+/// a single full-strength event weighs up to 3.0.
+pub const COUNTERPARTY_WEIGHT_CAP: f64 = 3.0;
+/// Total event weight all unpaid verdicts together may contribute to a
+/// seeder's score, less than one small paid transfer. They cost nothing
+/// to file, so fresh wallets could otherwise outvote every paid transfer.
+pub const UNPAID_WEIGHT_CAP: f64 = 1.0;
+/// Missing chain facts are looked up again after this long, doubling with
+/// each lookup that still leaves them missing, up to
+/// `CHAIN_FACTS_MAX_RETRY_SECS`.
+pub const CHAIN_FACTS_RETRY_SECS: u64 = 60;
+pub const CHAIN_FACTS_MAX_RETRY_SECS: u64 = 24 * 60 * 60;
+/// Chain lookups in flight at once while refreshing chain facts.
+const CHAIN_FACTS_CONCURRENCY: usize = 8;
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "camelCase")]
@@ -43,10 +69,226 @@ pub struct ReputationEvent {
     pub issuer_wallet: Option<String>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub verdict_signature: Option<String>,
+    /// Signed issue time of the verdict; events without one stay local.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub issued_at: Option<u64>,
+    /// `tx_hash` was checked on-chain to pay the seeder at least
+    /// `amount_wei`. Paid events without it don't count.
+    #[serde(default)]
+    pub payment_verified: bool,
+    /// Block time of the downloader wallet's first outgoing transaction.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub downloader_first_tx_at: Option<u64>,
+    /// When the chain facts above were last looked up, and how many
+    /// lookups have run; drives the re-check backoff.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chain_checked_at: Option<u64>,
+    #[serde(default)]
+    pub chain_checks: u32,
     pub created_at: u64,
     pub updated_at: u64,
 }
 
+impl ReputationEvent {
+    /// Cache entry for a verified gossiped verdict. The signed issue time
+    /// stands in for the admission time a rating server would record.
+    pub fn from_verdict(signed: &SignedVerdict, now: u64) -> Self {
+        let verdict = &signed.verdict;
+        let outcome = match verdict.outcome.trim() {
+            "failed" => TransferOutcome::Failed,
+            _ => TransferOutcome::Completed,
+        };
+        Self {
+            id: generate_event_id(
+                verdict.transfer_id.trim(),
+                verdict.seeder_wallet.trim(),
+                verdict.downloader_wallet.trim(),
+                verdict.file_hash.trim(),
+            ),
+            transfer_id: verdict.transfer_id.trim().to_string(),
+            seeder_wallet: verdict.seeder_wallet.trim().to_string(),
+            downloader_wallet: verdict.downloader_wallet.trim().to_string(),
+            file_hash: verdict.file_hash.trim().to_string(),
+            amount_wei: verdict.amount_wei.trim().to_string(),
+            outcome,
+            tx_hash: verdict.tx_hash.clone(),
+            rating_score: None,
+            rating_comment: None,
+            issuer_wallet: Some(signed.issuer_wallet.clone()),
+            verdict_signature: Some(signed.signature.clone()),
+            issued_at: Some(verdict.issued_at),
+            payment_verified: false,
+            downloader_first_tx_at: None,
+            chain_checked_at: None,
+            chain_checks: 0,
+            created_at: verdict.issued_at,
+            updated_at: now,
+        }
+    }
+
+    fn is_paid(&self) -> bool {
+        self.amount_wei.parse::<u128>().is_ok_and(|v| v > 0)
+    }
+
+    fn missing_chain_facts(&self) -> bool {
+        (self.is_paid() && !self.payment_verified && self.tx_hash.is_some())
+            || self.downloader_first_tx_at.is_none()
+    }
+
+    /// Whether chain facts are still missing and the backoff since the last
+    /// lookup has run out. Events past the lookback window no longer score,
+    /// so they are left alone.
+    fn chain_facts_due(&self, now: u64) -> bool {
+        if !self.missing_chain_facts() || now.saturating_sub(self.created_at) > LOOKBACK_SECS {
+            return false;
+        }
+        self.chain_checked_at
+            .is_none_or(|at| now.saturating_sub(at) >= chain_facts_retry_secs(self.chain_checks))
+    }
+
+    /// Record what the chain says about this event: whether its payment
+    /// reached the seeder and when the downloader wallet first transacted.
+    /// Lookup failures leave the facts unset, so the event counts for less.
+    pub async fn attach_chain_facts(&mut self) {
+        if self.is_paid() && !self.payment_verified {
+            let amount = self.amount_wei.parse::<u128>().unwrap_or(0);
+            if let Some(tx_hash) = self.tx_hash.clone() {
+                match reputation::verify_payment_tx(
+                    &tx_hash,
+                    &self.downloader_wallet,
+                    &self.seeder_wallet,
+                    amount,
+                )
+                .await
+                {
+                    Ok(()) => self.payment_verified = true,
+                    Err(e) => println!(
+                        "[REPUTATION] Payment {} for transfer {} not verified: {}",
+                        tx_hash, self.transfer_id, e
+                    ),
+                }
+            }
+        }
+        if self.downloader_first_tx_at.is_none() {
+            match reputation::first_outgoing_tx_at(&self.downloader_wallet).await {
+                Ok(at) => self.downloader_first_tx_at = at,
+                Err(e) => println!(
+                    "[REPUTATION] Wallet age lookup for {} failed: {}",
+                    self.downloader_wallet, e
+                ),
+            }
+        }
+    }
+
+    /// The gossipable form of this event, if it carries a timestamped
+    /// issuer signature.
+    pub fn signed_verdict(&self) -> Option<SignedVerdict> {
+        let issued_at = self.issued_at.filter(|t| *t > 0)?;
+        let outcome = match self.outcome {
+            TransferOutcome::Completed => "completed",
+            TransferOutcome::Failed => "failed",
+        };
+        Some(SignedVerdict {
+            verdict: ReputationVerdictPayload {
+                transfer_id: self.transfer_id.clone(),
+                seeder_wallet: self.seeder_wallet.clone(),
+                downloader_wallet: self.downloader_wallet.clone(),
+                file_hash: self.file_hash.clone(),
+                amount_wei: self.amount_wei.clone(),
+                outcome: outcome.to_string(),
+                tx_hash: self.tx_hash.clone(),
+                issued_at,
+            },
+            issuer_wallet: self.issuer_wallet.clone()?,
+            signature: self.verdict_signature.clone()?,
+        })
+    }
+}
+
+/// Wait before the next chain-facts lookup once `checks` have run.
+fn chain_facts_retry_secs(checks: u32) -> u64 {
+    let doublings = checks.saturating_sub(1).min(20);
+    CHAIN_FACTS_RETRY_SECS
+        .saturating_mul(1 << doublings)
+        .min(CHAIN_FACTS_MAX_RETRY_SECS)
+}
+
+/// Stamp the events whose chain facts are due for a lookup and return
+/// copies of them to look up.
+fn take_due_chain_checks(events: &mut [ReputationEvent], now: u64) -> Vec<ReputationEvent> {
+    events
+        .iter_mut()
+        .filter(|e| e.chain_facts_due(now))
+        .map(|e| {
+            e.chain_checked_at = Some(now);
+            e.chain_checks = e.chain_checks.saturating_add(1);
+            e.clone()
+        })
+        .collect()
+}
+
+/// Copy the facts found for `checked` onto the cached events they came
+/// from, unless a newer verdict replaced them meanwhile. Returns how many
+/// events gained a fact.
+fn apply_chain_facts(events: &mut [ReputationEvent], checked: Vec<ReputationEvent>) -> usize {
+    let mut changed = 0;
+    for found in checked {
+        let Some(event) = events
+            .iter_mut()
+            .find(|e| e.id == found.id && e.issued_at == found.issued_at)
+        else {
+            continue;
+        };
+        let mut gained = false;
+        if found.payment_verified && !event.payment_verified {
+            event.payment_verified = true;
+            gained = true;
+        }
+        if event.downloader_first_tx_at.is_none() && found.downloader_first_tx_at.is_some() {
+            event.downloader_first_tx_at = found.downloader_first_tx_at;
+            gained = true;
+        }
+        if gained {
+            changed += 1;
+        }
+    }
+    changed
+}
+
+fn is_newer_verdict(events: &[ReputationEvent], event: &ReputationEvent) -> bool {
+    events
+        .iter()
+        .find(|e| e.id == event.id)
+        .is_none_or(|e| event.issued_at.unwrap_or(0) > e.issued_at.unwrap_or(0))
+}
+
+/// Merge verified events into `events`. A new transfer is appended; a
+/// known one takes the incoming verdict only if it was issued later, and
+/// keeps its original `created_at`. Returns how many events changed.
+pub fn merge_verdict_events(
+    events: &mut Vec<ReputationEvent>,
+    incoming: Vec<ReputationEvent>,
+) -> usize {
+    let mut changed = 0;
+    for event in incoming {
+        if !is_newer_verdict(events, &event) {
+            continue;
+        }
+        match events.iter_mut().find(|e| e.id == event.id) {
+            Some(existing) => {
+                let created_at = existing.created_at;
+                *existing = ReputationEvent {
+                    created_at,
+                    ..event
+                };
+            }
+            None => events.push(event),
+        }
+        changed += 1;
+    }
+    changed
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct RatingManifest {
     pub events: Vec<ReputationEvent>,
@@ -61,6 +303,33 @@ pub struct ReputationSnapshot {
     pub failed_count: usize,
     pub transaction_count: usize,
     pub total_earned_wei: String,
+    #[serde(default)]
+    pub breakdown: ReputationBreakdown,
+}
+
+/// What went into a score, for explaining it in the UI.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReputationBreakdown {
+    /// Mean weights of the counted events, per factor.
+    pub time_weight: f64,
+    pub amount_weight: f64,
+    pub counterparty_weight: f64,
+    pub wallet_age_weight: f64,
+    /// Distinct downloader wallets among the counted events.
+    pub counterparties: usize,
+    /// Downloaders whose influence hit `COUNTERPARTY_WEIGHT_CAP`.
+    pub capped_counterparties: usize,
+    /// Paid events left out because the payment isn't verified on-chain.
+    pub unverified_payments: usize,
+    /// Paid events left out because an earlier event used the same tx.
+    pub reused_payments: usize,
+    /// Unpaid events cut down once `UNPAID_WEIGHT_CAP` was used up.
+    #[serde(default)]
+    pub capped_unpaid_events: usize,
+    /// Elo gained from completed and lost to failed transfers.
+    pub elo_gained: f64,
+    pub elo_lost: f64,
 }
 
 #[derive(Clone)]
@@ -68,6 +337,8 @@ pub struct RatingState {
     pub manifest: Arc<RwLock<RatingManifest>>,
     data_dir: PathBuf,
     issuer_dht: Option<Arc<Mutex<Option<Arc<DhtService>>>>>,
+    /// When each wallet's gossiped verdicts were last fetched.
+    verdict_syncs: Arc<Mutex<HashMap<String, u64>>>,
 }
 
 impl RatingState {
@@ -84,6 +355,7 @@ impl RatingState {
             manifest: Arc::new(RwLock::new(manifest)),
             data_dir,
             issuer_dht,
+            verdict_syncs: Arc::new(Mutex::new(HashMap::new())),
         }
     }
 
@@ -123,6 +395,103 @@ impl RatingState {
         let dht = self.issuer_dht_service().await?;
         reputation::fetch_issuer_key(&dht, issuer_wallet).await
     }
+
+    /// Gossip every cached verdict about `seeder_wallet`. Returns how many
+    /// were published.
+    pub async fn publish_verdicts(&self, seeder_wallet: &str) -> Result<usize, String> {
+        let dht = self.issuer_dht_service().await?;
+        let verdicts: Vec<SignedVerdict> = self
+            .manifest
+            .read()
+            .await
+            .events
+            .iter()
+            .filter(|e| e.seeder_wallet.eq_ignore_ascii_case(seeder_wallet))
+            .filter_map(ReputationEvent::signed_verdict)
+            .collect();
+        reputation::publish_verdicts(&dht, seeder_wallet, verdicts, now_secs()?).await
+    }
+
+    /// Pull the verdicts other nodes gossiped about `seeder_wallet` into
+    /// this cache. Skipped when there is no DHT or the wallet was synced
+    /// within `VERDICT_SYNC_INTERVAL_SECS`. Returns how many events changed.
+    pub async fn sync_verdicts(&self, seeder_wallet: &str) -> Result<usize, String> {
+        if !self.issuer_key_store_configured() {
+            return Ok(0);
+        }
+        let wallet = seeder_wallet.trim().to_lowercase();
+        let now = now_secs()?;
+        {
+            let mut syncs = self.verdict_syncs.lock().await;
+            if syncs
+                .get(&wallet)
+                .is_some_and(|at| now.saturating_sub(*at) < VERDICT_SYNC_INTERVAL_SECS)
+            {
+                return Ok(0);
+            }
+            syncs.insert(wallet.clone(), now);
+        }
+        let dht = self.issuer_dht_service().await?;
+        let fetched = reputation::fetch_verdicts(&dht, &wallet, now).await?;
+        let incoming: Vec<ReputationEvent> = {
+            let manifest = self.manifest.read().await;
+            fetched
+                .iter()
+                .map(|v| ReputationEvent::from_verdict(v, now))
+                .filter(|e| is_newer_verdict(&manifest.events, e))
+                .collect()
+        };
+        let changed = merge_verdict_events(&mut self.manifest.write().await.events, incoming);
+        if changed > 0 {
+            self.persist().await;
+        }
+        Ok(changed)
+    }
+
+    /// `sync_verdicts` for several wallets at once, then a background
+    /// refresh of missing chain facts; failures are logged and the cached
+    /// events are used as they are.
+    pub async fn sync_verdicts_for(&self, wallets: &[String]) {
+        let results = futures::future::join_all(
+            wallets
+                .iter()
+                .map(|wallet| async move { (wallet, self.sync_verdicts(wallet).await) }),
+        )
+        .await;
+        for (wallet, result) in results {
+            if let Err(e) = result {
+                println!("[REPUTATION] Verdict sync for {} failed: {}", wallet, e);
+            }
+        }
+        self.refresh_chain_facts().await;
+    }
+
+    /// Look up missing chain facts for the events that are due, off the
+    /// caller's path. A verdict seen before its payment was mined, or while
+    /// geth was down, is verified by a later refresh.
+    pub async fn refresh_chain_facts(&self) {
+        let Ok(now) = now_secs() else {
+            return;
+        };
+        let due = take_due_chain_checks(&mut self.manifest.write().await.events, now);
+        if due.is_empty() {
+            return;
+        }
+        let state = self.clone();
+        tokio::spawn(async move {
+            let checked: Vec<ReputationEvent> = futures::stream::iter(due)
+                .map(|mut event| async move {
+                    event.attach_chain_facts().await;
+                    event
+                })
+                .buffer_unordered(CHAIN_FACTS_CONCURRENCY)
+                .collect()
+                .await;
+            apply_chain_facts(&mut state.manifest.write().await.events, checked);
+            // Also saves the backoff stamps.
+            state.persist().await;
+        });
+    }
 }
 
 fn ratings_dir(data_dir: &PathBuf) -> PathBuf {
@@ -298,59 +667,199 @@ fn clamp_elo(v: f64) -> f64 {
     v.max(MIN_ELO).min(MAX_ELO)
 }
 
-/// Compute wallet Elo from transfer outcomes in the last 180 days.
-/// Uses simple bounded Elo updates with time and amount weighting plus rating signal.
-pub fn compute_reputation_for_wallet(
-    events: &[ReputationEvent],
+/// Events that may move `seeder_wallet`'s score, oldest first: inside the
+/// lookback window, not self-scored, and, if paid, backed by a verified
+/// payment that no earlier event already used.
+fn admissible_events<'a>(
+    events: &'a [ReputationEvent],
     seeder_wallet: &str,
     now: u64,
-) -> ReputationSnapshot {
+    breakdown: &mut ReputationBreakdown,
+) -> Vec<&'a ReputationEvent> {
     let mut scoped: Vec<&ReputationEvent> = events
         .iter()
         .filter(|e| e.seeder_wallet.eq_ignore_ascii_case(seeder_wallet))
+        .filter(|e| !e.downloader_wallet.eq_ignore_ascii_case(seeder_wallet))
         .filter(|e| now.saturating_sub(e.created_at) <= LOOKBACK_SECS)
         .collect();
     scoped.sort_by_key(|e| e.created_at);
 
+    let mut used_payments = HashSet::new();
+    scoped.retain(|e| {
+        if !e.is_paid() {
+            return true;
+        }
+        let tx_hash = e.tx_hash.as_deref().unwrap_or("").trim().to_lowercase();
+        if !e.payment_verified || tx_hash.is_empty() {
+            breakdown.unverified_payments += 1;
+            return false;
+        }
+        if !used_payments.insert(tx_hash) {
+            breakdown.reused_payments += 1;
+            return false;
+        }
+        true
+    });
+    scoped
+}
+
+fn time_weight(event: &ReputationEvent, now: u64) -> f64 {
+    let age_days = now.saturating_sub(event.created_at) as f64 / 86_400.0;
+    (1.0 - age_days / LOOKBACK_DAYS as f64).max(0.0)
+}
+
+fn amount_weight(event: &ReputationEvent) -> f64 {
+    let amount_chi = wei_to_chi_f64(&event.amount_wei);
+    1.0 + (amount_chi.ln_1p() / 51f64.ln()).clamp(0.0, 1.0)
+}
+
+/// How established the downloader wallet was when it scored the transfer.
+fn wallet_age_weight(event: &ReputationEvent) -> f64 {
+    let Some(first_tx_at) = event.downloader_first_tx_at else {
+        return MIN_WALLET_AGE_WEIGHT;
+    };
+    let age = event.created_at.saturating_sub(first_tx_at) as f64;
+    (age / MATURE_WALLET_AGE_SECS as f64).clamp(MIN_WALLET_AGE_WEIGHT, 1.0)
+}
+
+/// Cut an unpaid event's weight to what is left of `UNPAID_WEIGHT_CAP`.
+/// Returns the weight to apply and whether it was cut.
+fn cap_unpaid_weight(event: &ReputationEvent, weight: f64, unpaid_used: &mut f64) -> (f64, bool) {
+    if event.is_paid() {
+        return (weight, false);
+    }
+    let allowed = (UNPAID_WEIGHT_CAP - *unpaid_used).max(0.0);
+    let capped = weight > allowed;
+    let weight = weight.min(allowed);
+    *unpaid_used += weight;
+    (weight, capped)
+}
+
+fn outcome_score(outcome: TransferOutcome) -> f64 {
+    match outcome {
+        TransferOutcome::Completed => 1.0,
+        TransferOutcome::Failed => 0.0,
+    }
+}
+
+fn elo_step(elo: f64, weight: f64, outcome: TransferOutcome) -> f64 {
+    let expected = 1.0 / (1.0 + 10f64.powf((BASE_ELO - elo) / 12.0));
+    clamp_elo(elo + 4.0 * weight * (outcome_score(outcome) - expected))
+}
+
+/// A downloader's own standing, from its seeding history with only time
+/// and amount weights so the lookup stops one level deep.
+fn counterparty_elo(events: &[ReputationEvent], wallet: &str, now: u64) -> f64 {
+    let mut ignored = ReputationBreakdown::default();
+    let mut unpaid_used = 0.0;
+    admissible_events(events, wallet, now, &mut ignored)
+        .into_iter()
+        .fold(BASE_ELO, |elo, event| {
+            let weight = time_weight(event, now) * amount_weight(event);
+            let (weight, _) = cap_unpaid_weight(event, weight, &mut unpaid_used);
+            elo_step(elo, weight, event.outcome)
+        })
+}
+
+fn round_to(value: f64, places: i32) -> f64 {
+    let factor = 10f64.powi(places);
+    (value * factor).round() / factor
+}
+
+/// Compute wallet Elo from transfer outcomes in the last 180 days.
+///
+/// Each event's K factor is `4 * w_time * w_amount * w_counterparty *
+/// w_age`: recency, payment size, the downloader's own Elo relative to
+/// base, and how old the downloader wallet was. A downloader's summed
+/// weight is capped at `COUNTERPARTY_WEIGHT_CAP`, so fresh wallets paying
+/// themselves or a rival spamming failures can't move the score far.
+pub fn compute_reputation_for_wallet(
+    events: &[ReputationEvent],
+    seeder_wallet: &str,
+    now: u64,
+) -> ReputationSnapshot {
+    let mut breakdown = ReputationBreakdown::default();
+    let scoped = admissible_events(events, seeder_wallet, now, &mut breakdown);
+
     let mut elo = BASE_ELO;
     let mut completed = 0usize;
     let mut failed = 0usize;
     let mut total_earned_wei: u128 = 0;
+    let mut counterparty_elos: HashMap<String, f64> = HashMap::new();
+    let mut spent: HashMap<String, f64> = HashMap::new();
+    let mut capped: HashSet<String> = HashSet::new();
+    let mut unpaid_used = 0.0;
+    let mut weight_sums = [0.0f64; 4];
 
     for event in &scoped {
-        let age_days = now.saturating_sub(event.created_at) as f64 / 86_400.0;
-        let w_time = (1.0 - age_days / LOOKBACK_DAYS as f64).max(0.0);
+        let downloader = event.downloader_wallet.to_lowercase();
+        let w_time = time_weight(event, now);
+        let w_amount = amount_weight(event);
+        let downloader_elo = *counterparty_elos
+            .entry(downloader.clone())
+            .or_insert_with(|| counterparty_elo(events, &downloader, now));
+        let w_counterparty =
+            (downloader_elo / BASE_ELO).clamp(MIN_COUNTERPARTY_WEIGHT, MAX_COUNTERPARTY_WEIGHT);
+        let w_age = wallet_age_weight(event);
+        for (sum, w) in weight_sums
+            .iter_mut()
+            .zip([w_time, w_amount, w_counterparty, w_age])
+        {
+            *sum += w;
+        }
 
-        let amount_chi = wei_to_chi_f64(&event.amount_wei);
-        let w_amount = 1.0 + (amount_chi.ln_1p() / 51f64.ln()).clamp(0.0, 1.0);
+        let used = spent.entry(downloader.clone()).or_insert(0.0);
+        let allowed = (COUNTERPARTY_WEIGHT_CAP - *used).max(0.0);
+        let mut weight = w_time * w_amount * w_counterparty * w_age;
+        if weight > allowed {
+            weight = allowed;
+            capped.insert(downloader);
+        }
+        let (weight, unpaid_capped) = cap_unpaid_weight(event, weight, &mut unpaid_used);
+        if unpaid_capped {
+            breakdown.capped_unpaid_events += 1;
+        }
+        *used += weight;
 
-        let outcome = match event.outcome {
+        match event.outcome {
             TransferOutcome::Completed => {
                 completed += 1;
                 if let Ok(v) = event.amount_wei.parse::<u128>() {
                     total_earned_wei = total_earned_wei.saturating_add(v);
                 }
-                1.0
-            }
-            TransferOutcome::Failed => {
-                failed += 1;
-                0.0
             }
-        };
+            TransferOutcome::Failed => failed += 1,
+        }
+
+        let next = elo_step(elo, weight, event.outcome);
+        if next >= elo {
+            breakdown.elo_gained += next - elo;
+        } else {
+            breakdown.elo_lost += elo - next;
+        }
+        elo = next;
+    }
 
-        let actual = outcome;
-        let expected = 1.0 / (1.0 + 10f64.powf((BASE_ELO - elo) / 12.0));
-        let k = 4.0 * w_time * w_amount;
-        elo = clamp_elo(elo + k * (actual - expected));
+    if !scoped.is_empty() {
+        let n = scoped.len() as f64;
+        breakdown.time_weight = round_to(weight_sums[0] / n, 2);
+        breakdown.amount_weight = round_to(weight_sums[1] / n, 2);
+        breakdown.counterparty_weight = round_to(weight_sums[2] / n, 2);
+        breakdown.wallet_age_weight = round_to(weight_sums[3] / n, 2);
     }
+    breakdown.counterparties = spent.len();
+    breakdown.capped_counterparties = capped.len();
+    breakdown.elo_gained = round_to(breakdown.elo_gained, 1);
+    breakdown.elo_lost = round_to(breakdown.elo_lost, 1);
 
     ReputationSnapshot {
-        elo: (elo * 10.0).round() / 10.0,
+        elo: round_to(elo, 1),
         base_elo: BASE_ELO,
         completed_count: completed,
         failed_count: failed,
         transaction_count: scoped.len(),
         total_earned_wei: total_earned_wei.to_string(),
+        breakdown,
     }
 }
 
@@ -367,6 +876,9 @@ mod tests {
         rating_score: Option<u8>,
         created_at: u64,
     ) -> ReputationEvent {
+        // Defaults to a verified payment from a year-old wallet, so only the
+        // tests about those factors have to think about them.
+        let paid = amount_wei.parse::<u128>().is_ok_and(|v| v > 0);
         ReputationEvent {
             id: generate_event_id(transfer_id, seeder_wallet, downloader_wallet, "hash"),
             transfer_id: transfer_id.to_string(),
@@ -375,11 +887,16 @@ mod tests {
             file_hash: "hash".to_string(),
             amount_wei: amount_wei.to_string(),
             outcome,
-            tx_hash: None,
+            tx_hash: paid.then(|| format!("0xtx-{transfer_id}-{downloader_wallet}")),
             rating_score,
             rating_comment: None,
             issuer_wallet: None,
             verdict_signature: None,
+            issued_at: None,
+            payment_verified: paid,
+            downloader_first_tx_at: Some(created_at.saturating_sub(365 * 86_400)),
+            chain_checked_at: None,
+            chain_checks: 0,
             created_at,
             updated_at: created_at,
         }
@@ -818,6 +1335,328 @@ mod tests {
         assert_eq!(snap.failed_count, 1);
     }
 
+    // --- Sybil resistance ---
+
+    const ONE_CHI: &str = "1000000000000000000";
+
+    #[test]
+    fn test_unverified_payment_is_not_counted() {
+        let now = 1_700_000_000;
+        let mut event = mk_event(
+            "t-1",
+            "0xA",
+            "0xB",
+            TransferOutcome::Completed,
+            ONE_CHI,
+            None,
+            now - 86_400,
+        );
+        event.payment_verified = false;
+        let snap = compute_reputation_for_wallet(&[event], "0xA", now);
+        assert_eq!(snap.elo, BASE_ELO);
+        assert_eq!(snap.transaction_count, 0);
+        assert_eq!(snap.breakdown.unverified_payments, 1);
+    }
+
+    #[test]
+    fn test_paid_event_without_tx_hash_is_not_counted() {
+        let now = 1_700_000_000;
+        let mut event = mk_event(
+            "t-1",
+            "0xA",
+            "0xB",
+            TransferOutcome::Completed,
+            ONE_CHI,
+            None,
+            now - 86_400,
+        );
+        event.tx_hash = None;
+        let snap = compute_reputation_for_wallet(&[event], "0xA", now);
+        assert_eq!(snap.transaction_count, 0);
+        assert_eq!(snap.breakdown.unverified_payments, 1);
+    }
+
+    #[test]
+    fn test_reused_payment_counts_once() {
+        let now = 1_700_000_000;
+        let first = mk_event(
+            "t-1",
+            "0xA",
+            "0xB",
+            TransferOutcome::Completed,
+            ONE_CHI,
+            None,
+            now - 2 * 86_400,
+        );
+        let mut second = mk_event(
+            "t-2",
+            "0xA",
+            "0xC",
+            TransferOutcome::Completed,
+            ONE_CHI,
+            None,
+            now - 86_400,
+        );
+        second.tx_hash = first.tx_hash.as_ref().map(|h| h.to_uppercase());
+        let snap = compute_reputation_for_wallet(&[first, second], "0xA", now);
+        assert_eq!(snap.transaction_count, 1);
+        assert_eq!(snap.breakdown.reused_payments, 1);
+    }
+
+    #[test]
+    fn test_self_scored_events_are_ignored() {
+        let now = 1_700_000_000;
+        let events = vec![mk_event(
+            "t-1",
+            "0xA",
+            "0xa",
+            TransferOutcome::Completed,
+            ONE_CHI,
+            None,
+            now - 86_400,
+        )];
+        let snap = compute_reputation_for_wallet(&events, "0xA", now);
+        assert_eq!(snap.transaction_count, 0);
+        assert_eq!(snap.elo, BASE_ELO);
+    }
+
+    #[test]
+    fn test_single_counterparty_influence_is_capped() {
+        let now = 1_700_000_000;
+        let one_downloader: Vec<ReputationEvent> = (0..20)
+            .map(|i| {
+                mk_event(
+                    &format!("t-{i}"),
+                    "0xA",
+                    "0xB",
+                    TransferOutcome::Completed,
+                    ONE_CHI,
+                    None,
+                    now - 3_600 * (i + 1),
+                )
+            })
+            .collect();
+        let many_downloaders: Vec<ReputationEvent> = (0..20)
+            .map(|i| {
+                mk_event(
+                    &format!("t-{i}"),
+                    "0xA",
+                    &format!("0xD{i}"),
+                    TransferOutcome::Completed,
+                    ONE_CHI,
+                    None,
+                    now - 3_600 * (i + 1),
+                )
+            })
+            .collect();
+        let capped = compute_reputation_for_wallet(&one_downloader, "0xA", now);
+        let spread = compute_reputation_for_wallet(&many_downloaders, "0xA", now);
+        assert_eq!(capped.completed_count, 20);
+        assert_eq!(capped.breakdown.counterparties, 1);
+        assert_eq!(capped.breakdown.capped_counterparties, 1);
+        assert_eq!(spread.breakdown.counterparties, 20);
+        assert_eq!(spread.breakdown.capped_counterparties, 0);
+        assert!(spread.elo > capped.elo);
+    }
+
+    #[test]
+    fn test_failure_spam_from_one_wallet_is_capped() {
+        let now = 1_700_000_000;
+        let events: Vec<ReputationEvent> = (0..50)
+            .map(|i| {
+                mk_event(
+                    &format!("t-{i}"),
+                    "0xA",
+                    "0xB",
+                    TransferOutcome::Failed,
+                    "0",
+                    None,
+                    now - 600 * (i + 1),
+                )
+            })
+            .collect();
+        let snap = compute_reputation_for_wallet(&events, "0xA", now);
+        assert_eq!(snap.failed_count, 50);
+        assert!(snap.elo > 40.0, "elo {} fell too far", snap.elo);
+    }
+
+    #[test]
+    fn test_free_verdicts_from_fresh_wallets_are_capped() {
+        let now = 1_700_000_000;
+        let sybils = |n: u64| -> Vec<ReputationEvent> {
+            (0..n)
+                .map(|i| {
+                    let created_at = now - 60 * (i + 1);
+                    let mut event = mk_event(
+                        &format!("t-{i}"),
+                        "0xA",
+                        &format!("0xSYBIL{i}"),
+                        TransferOutcome::Completed,
+                        "0",
+                        None,
+                        created_at,
+                    );
+                    event.downloader_first_tx_at = Some(created_at);
+                    event
+                })
+                .collect()
+        };
+        let few = compute_reputation_for_wallet(&sybils(20), "0xA", now);
+        let many = compute_reputation_for_wallet(&sybils(500), "0xA", now);
+
+        assert_eq!(many.completed_count, 500);
+        assert_eq!(many.breakdown.counterparties, 500);
+        assert!(many.breakdown.capped_unpaid_events > 0);
+        assert_eq!(many.elo, few.elo);
+        assert!(many.elo <= BASE_ELO + 2.0, "elo {} rose too far", many.elo);
+
+        let paid = compute_reputation_for_wallet(
+            &[mk_event(
+                "t-paid",
+                "0xA",
+                "0xB",
+                TransferOutcome::Completed,
+                ONE_CHI,
+                None,
+                now - 86_400,
+            )],
+            "0xA",
+            now,
+        );
+        assert!(paid.elo > many.elo);
+    }
+
+    #[test]
+    fn test_young_wallet_counts_less_than_mature_wallet() {
+        let now = 1_700_000_000;
+        let created_at = now - 86_400;
+        let mature = mk_event(
+            "t-1",
+            "0xA",
+            "0xB",
+            TransferOutcome::Completed,
+            ONE_CHI,
+            None,
+            created_at,
+        );
+        let mut young = mature.clone();
+        young.downloader_first_tx_at = Some(created_at - 86_400);
+        let mut unknown = mature.clone();
+        unknown.downloader_first_tx_at = None;
+
+        let mature_snap = compute_reputation_for_wallet(&[mature], "0xA", now);
+        let young_snap = compute_reputation_for_wallet(&[young], "0xA", now);
+        let unknown_snap = compute_reputation_for_wallet(&[unknown], "0xA", now);
+        assert!(mature_snap.elo > young_snap.elo);
+        assert!(young_snap.elo > BASE_ELO);
+        assert!(young_snap.elo >= unknown_snap.elo);
+        assert_eq!(mature_snap.breakdown.wallet_age_weight, 1.0);
+        assert_eq!(
+            unknown_snap.breakdown.wallet_age_weight,
+            MIN_WALLET_AGE_WEIGHT
+        );
+    }
+
+    #[test]
+    fn test_low_reputation_counterparty_counts_less() {
+        let now = 1_700_000_000;
+        // 0xB has a record of failed transfers as a seeder; 0xC is unknown.
+        let mut events: Vec<ReputationEvent> = (0..10)
+            .map(|i| {
+                mk_event(
+                    &format!("f-{i}"),
+                    "0xB",
+                    &format!("0xD{i}"),
+                    TransferOutcome::Failed,
+                    "0",
+                    None,
+                    now - 86_400 * (i + 2),
+                )
+            })
+            .collect();
+        events.push(mk_event(
+            "t-b",
+            "0xA",
+            "0xB",
+            TransferOutcome::Completed,
+            ONE_CHI,
+            None,
+            now - 86_400,
+        ));
+        let from_low = compute_reputation_for_wallet(&events, "0xA", now);
+
+        let from_unknown = compute_reputation_for_wallet(
+            &[mk_event(
+                "t-c",
+                "0xA",
+                "0xC",
+                TransferOutcome::Completed,
+                ONE_CHI,
+                None,
+                now - 86_400,
+            )],
+            "0xA",
+            now,
+        );
+        assert!(from_low.breakdown.counterparty_weight < 1.0);
+        assert_eq!(from_unknown.breakdown.counterparty_weight, 1.0);
+        assert!(from_unknown.elo > from_low.elo);
+        assert!(from_low.elo > BASE_ELO);
+    }
+
+    #[test]
+    fn test_breakdown_tracks_gains_and_losses() {
+        let now = 1_700_000_000;
+        let events = vec![
+            mk_event(
+                "t-1",
+                "0xA",
+                "0xB",
+                TransferOutcome::Completed,
+                ONE_CHI,
+                None,
+                now - 2 * 86_400,
+            ),
+            mk_event(
+                "t-2",
+                "0xA",
+                "0xC",
+                TransferOutcome::Failed,
+                "0",
+                None,
+                now - 86_400,
+            ),
+        ];
+        let snap = compute_reputation_for_wallet(&events, "0xA", now);
+        assert_eq!(snap.breakdown.counterparties, 2);
+        assert!(snap.breakdown.elo_gained > 0.0);
+        assert!(snap.breakdown.elo_lost > 0.0);
+        assert!(snap.breakdown.time_weight > 0.9);
+        assert!(snap.breakdown.amount_weight > 1.0);
+        assert_eq!(snap.breakdown.unverified_payments, 0);
+    }
+
+    #[test]
+    fn test_merge_skips_stale_verdicts() {
+        let mut events = Vec::new();
+        let mut newer = mk_event(
+            "t-1",
+            "0xA",
+            "0xB",
+            TransferOutcome::Completed,
+            "0",
+            None,
+            100,
+        );
+        newer.issued_at = Some(200);
+        assert!(is_newer_verdict(&events, &newer));
+        events.push(newer.clone());
+        let mut older = newer.clone();
+        older.issued_at = Some(150);
+        assert!(!is_newer_verdict(&events, &older));
+        assert!(!is_newer_verdict(&events, &newer));
+    }
+
     // --- ReputationSnapshot serialization roundtrip ---
 
     #[test]
@@ -829,8 +1668,14 @@ mod tests {
             failed_count: 2,
             transaction_count: 12,
             total_earned_wei: "5000000000000000000".to_string(),
+            breakdown: ReputationBreakdown {
+                counterparty_weight: 1.2,
+                capped_counterparties: 1,
+                ..Default::default()
+            },
         };
         let json = serde_json::to_string(&snap).expect("serialize");
+        assert!(json.contains("\"cappedCounterparties\":1"));
         let deser: ReputationSnapshot = serde_json::from_str(&json).expect("deserialize");
         assert_eq!(deser.elo, snap.elo);
         assert_eq!(deser.base_elo, snap.base_elo);
@@ -838,6 +1683,14 @@ mod tests {
         assert_eq!(deser.failed_count, snap.failed_count);
         assert_eq!(deser.transaction_count, snap.transaction_count);
         assert_eq!(deser.total_earned_wei, snap.total_earned_wei);
+        assert_eq!(deser.breakdown, snap.breakdown);
+    }
+
+    #[test]
+    fn test_reputation_snapshot_without_breakdown_deserializes() {
+        let json = r#"{"elo":50.0,"baseElo":50.0,"completedCount":0,"failedCount":0,"transactionCount":0,"totalEarnedWei":"0"}"#;
+        let snap: ReputationSnapshot = serde_json::from_str(json).expect("deserialize");
+        assert_eq!(snap.breakdown, ReputationBreakdown::default());
     }
 
     // --- RatingManifest default ---
@@ -847,4 +1700,177 @@ mod tests {
         let manifest = RatingManifest::default();
         assert!(manifest.events.is_empty());
     }
+
+    fn gossiped_verdict(outcome: &str, issued_at: u64) -> SignedVerdict {
+        SignedVerdict {
+            verdict: ReputationVerdictPayload {
+                transfer_id: "t-gossip".to_string(),
+                seeder_wallet: "0x1111111111111111111111111111111111111111".to_string(),
+                downloader_wallet: "0x2222222222222222222222222222222222222222".to_string(),
+                file_hash: "hash".to_string(),
+                amount_wei: "1000".to_string(),
+                outcome: outcome.to_string(),
+                tx_hash: Some("0xtx".to_string()),
+                issued_at,
+            },
+            issuer_wallet: "0x2222222222222222222222222222222222222222".to_string(),
+            signature: format!("sig-{}-{}", outcome, issued_at),
+        }
+    }
+
+    #[test]
+    fn gossiped_verdict_round_trips_through_an_event() {
+        let signed = gossiped_verdict("failed", 1_700_000_000);
+        let event = ReputationEvent::from_verdict(&signed, 1_700_000_100);
+        assert_eq!(event.outcome, TransferOutcome::Failed);
+        assert_eq!(event.created_at, 1_700_000_000);
+        assert_eq!(event.updated_at, 1_700_000_100);
+        assert_eq!(
+            event.id,
+            generate_event_id(
+                "t-gossip",
+                "0x1111111111111111111111111111111111111111",
+                "0x2222222222222222222222222222222222222222",
+                "hash"
+            )
+        );
+        assert_eq!(event.signed_verdict(), Some(signed));
+    }
+
+    #[test]
+    fn untimestamped_events_are_not_gossiped() {
+        let mut event = mk_event(
+            "t-legacy",
+            "0xA",
+            "0xB",
+            TransferOutcome::Completed,
+            "0",
+            None,
+            1_700_000_000,
+        );
+        event.issuer_wallet = Some("0xB".to_string());
+        event.verdict_signature = Some("sig".to_string());
+        assert!(event.signed_verdict().is_none());
+        let json = serde_json::to_value(&event).unwrap();
+        assert!(json.get("issuedAt").is_none());
+
+        event.issued_at = Some(1_700_000_000);
+        assert!(event.signed_verdict().is_some());
+    }
+
+    #[test]
+    fn merge_keeps_the_latest_verdict_per_transfer() {
+        let mut events = Vec::new();
+        let first = ReputationEvent::from_verdict(&gossiped_verdict("completed", 1_000), 1_000);
+        assert_eq!(merge_verdict_events(&mut events, vec![first.clone()]), 1);
+        // The same verdict again, or an older one, changes nothing.
+        assert_eq!(merge_verdict_events(&mut events, vec![first.clone()]), 0);
+        let older = ReputationEvent::from_verdict(&gossiped_verdict("failed", 500), 1_000);
+        assert_eq!(merge_verdict_events(&mut events, vec![older]), 0);
+        assert_eq!(events[0].outcome, TransferOutcome::Completed);
+
+        let newer = ReputationEvent::from_verdict(&gossiped_verdict("failed", 2_000), 2_000);
+        assert_eq!(merge_verdict_events(&mut events, vec![newer]), 1);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].outcome, TransferOutcome::Failed);
+        assert_eq!(events[0].issued_at, Some(2_000));
+        assert_eq!(events[0].created_at, 1_000);
+    }
+
+    #[test]
+    fn signed_verdicts_replace_unsigned_cache_entries() {
+        let signed = ReputationEvent::from_verdict(&gossiped_verdict("completed", 1_000), 1_000);
+        let mut legacy = signed.clone();
+        legacy.issued_at = None;
+        legacy.verdict_signature = None;
+        legacy.outcome = TransferOutcome::Failed;
+        let mut events = vec![legacy];
+        assert_eq!(merge_verdict_events(&mut events, vec![signed]), 1);
+        assert_eq!(events[0].outcome, TransferOutcome::Completed);
+    }
+
+    #[test]
+    fn missing_chain_facts_are_rechecked_with_backoff() {
+        let now = 1_800_000_000;
+        let mut pending = mk_event(
+            "t1",
+            "0xseed",
+            "0xd1",
+            TransferOutcome::Completed,
+            "5",
+            None,
+            now,
+        );
+        pending.payment_verified = false;
+        let mut events = vec![
+            pending,
+            mk_event(
+                "t2",
+                "0xseed",
+                "0xd2",
+                TransferOutcome::Completed,
+                "5",
+                None,
+                now,
+            ),
+        ];
+
+        let due = take_due_chain_checks(&mut events, now);
+        assert_eq!(due.len(), 1, "only the unverified payment is looked up");
+        assert!(take_due_chain_checks(&mut events, now + 1).is_empty());
+
+        // The tx was not mined yet: nothing found, so wait and look again.
+        assert_eq!(apply_chain_facts(&mut events, due), 0);
+        let retry = now + CHAIN_FACTS_RETRY_SECS;
+        let mut due = take_due_chain_checks(&mut events, retry);
+        assert_eq!(due.len(), 1);
+        assert!(take_due_chain_checks(&mut events, retry + CHAIN_FACTS_RETRY_SECS).is_empty());
+
+        due[0].payment_verified = true;
+        assert_eq!(apply_chain_facts(&mut events, due), 1);
+        assert!(events[0].payment_verified);
+        assert!(take_due_chain_checks(&mut events, now + CHAIN_FACTS_MAX_RETRY_SECS).is_empty());
+    }
+
+    #[test]
+    fn unknown_wallet_age_is_rechecked_until_the_lookback_ends() {
+        let now = 1_800_000_000;
+        let mut event = mk_event(
+            "t1",
+            "0xseed",
+            "0xd1",
+            TransferOutcome::Completed,
+            "0",
+            None,
+            now,
+        );
+        event.downloader_first_tx_at = None;
+        let mut events = vec![event];
+        let mut at = now;
+        for _ in 0..15 {
+            assert_eq!(take_due_chain_checks(&mut events, at).len(), 1);
+            at += CHAIN_FACTS_MAX_RETRY_SECS;
+        }
+        assert_eq!(
+            chain_facts_retry_secs(events[0].chain_checks),
+            CHAIN_FACTS_MAX_RETRY_SECS
+        );
+        assert!(take_due_chain_checks(&mut events, at - 1).is_empty());
+        assert_eq!(take_due_chain_checks(&mut events, at).len(), 1);
+        assert!(take_due_chain_checks(&mut events, now + LOOKBACK_SECS + 1).is_empty());
+
+        // A newer verdict replaced the event while its lookup ran.
+        let mut found = events[0].clone();
+        found.downloader_first_tx_at = Some(now - 86_400);
+        events[0].issued_at = Some(now + 5);
+        assert_eq!(apply_chain_facts(&mut events, vec![found]), 0);
+    }
+
+    #[tokio::test]
+    async fn sync_without_a_dht_uses_the_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = RatingState::new(dir.path().to_path_buf());
+        assert_eq!(state.sync_verdicts("0xabc").await, Ok(0));
+        assert!(state.publish_verdicts("0xabc").await.is_err());
+    }
 }
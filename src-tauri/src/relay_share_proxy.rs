@@ -3,29 +3,37 @@
 //! The relay never stores file data. It keeps mappings from share tokens and
 //! site IDs to the owner's local server. When a visitor requests content:
 //!
-//! 1. If the owner has an active WebSocket tunnel, the request is forwarded
-//!    through the tunnel (works behind NAT without port forwarding).
-//! 2. Otherwise, the relay tries a direct HTTP proxy to the origin URL.
-//! 3. If both fail, an offline error page is shown.
+//! 1. If the owner has an active WebSocket tunnel, the request (any method,
+//!    with its headers and a streamed body) is forwarded through the tunnel
+//!    (works behind NAT without port forwarding).
+//! 2. Otherwise, if the owner registered its libp2p peer ID, the relay asks
+//!    for the content over the owner's existing swarm connection
+//!    (`crate::http_p2p`), so the origin port needn't be reachable either.
+//! 3. Otherwise, the relay tries a direct HTTP proxy to the origin URL.
+//! 4. If all fail, an offline error page is shown.
 
 use axum::{
-    body::Body,
+    body::{Body, Bytes},
     extract::{
         ws::{Message, WebSocket},
         ConnectInfo, Extension, Path, Query, WebSocketUpgrade,
     },
-    http::StatusCode,
+    http::{HeaderMap, Method, StatusCode, Uri},
     response::{Html, IntoResponse, Response},
-    routing::{delete, get, post},
+    routing::{any, delete, get, post},
     Json, Router,
 };
-use futures_util::{SinkExt, StreamExt};
+use futures_util::{SinkExt, Stream, StreamExt};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
 use std::path::PathBuf;
+use std::pin::Pin;
 use std::sync::Arc;
-use tokio::sync::{oneshot, RwLock};
+use std::task::{Context, Poll};
+use tokio::sync::{mpsc, oneshot, RwLock};
+
+use crate::http_p2p::{self, HttpP2pClient, HttpP2pRequest, HttpP2pResponse};
 
 // ---------------------------------------------------------------------------
 // Data model
@@ -37,6 +45,9 @@ pub struct ShareRegistration {
     pub origin_url: String,
     pub owner_wallet: String,
     pub registered_at: u64,
+    /// Owner's libp2p peer ID, when it can be reached over the swarm.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub peer_id: Option<String>,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -45,6 +56,9 @@ pub struct SiteRegistration {
     pub origin_url: String,
     pub owner_wallet: String,
     pub registered_at: u64,
+    /// Owner's libp2p peer ID, when it can be reached over the swarm.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub peer_id: Option<String>,
 }
 
 #[derive(Clone)]
@@ -52,6 +66,8 @@ pub struct RelayShareRegistry {
     pub shares: Arc<RwLock<HashMap<String, ShareRegistration>>>,
     pub sites: Arc<RwLock<HashMap<String, SiteRegistration>>>,
     persist_path: PathBuf,
+    /// Set on a relay whose swarm speaks `/chiral/http/1.0.0`.
+    p2p_http: Option<HttpP2pClient>,
 }
 
 #[derive(Serialize, Deserialize, Default)]
@@ -68,9 +84,22 @@ impl RelayShareRegistry {
             shares: Arc::new(RwLock::new(HashMap::new())),
             sites: Arc::new(RwLock::new(HashMap::new())),
             persist_path,
+            p2p_http: None,
         }
     }
 
+    /// Fetch from owners that registered a peer ID through `client`.
+    pub fn with_p2p_http(mut self, client: HttpP2pClient) -> Self {
+        self.p2p_http = Some(client);
+        self
+    }
+
+    fn p2p_route(&self, peer_id: Option<&str>) -> Option<P2pRoute> {
+        let client = self.p2p_http.clone()?;
+        let peer = peer_id?.parse().ok()?;
+        Some(P2pRoute { client, peer })
+    }
+
     pub async fn load_from_disk(&self) -> Result<(), String> {
         let data = match std::fs::read_to_string(&self.persist_path) {
             Ok(data) => data,
@@ -202,36 +231,306 @@ impl RelayShareRegistry {
     }
 }
 
+// ---------------------------------------------------------------------------
+// WebSocket tunnel protocol
+// ---------------------------------------------------------------------------
+//
+// Visitor requests are multiplexed over the owner's WebSocket. Control frames
+// are JSON text tagged by `type`; bodies travel as binary frames laid out as
+// `[id length: u8][id bytes][payload]`, so nothing is base64-inflated.
+//
+//   relay → owner   request {id, method, path, headers, hasBody}
+//                   body chunks, then request-end (only when hasBody)
+//   owner → relay   response {id, status, headers}
+//                   body chunks, then response-end — or error {id, message}
+//   relay → owner   window {id, credits} as the visitor drains the body
+//                   cancel {id} when the visitor goes away or times out
+//
+// The owner may have at most TUNNEL_WINDOW_CHUNKS response chunks in flight
+// per request and must wait for window credits before sending more.
+
+/// Largest body payload either side puts into a single binary frame.
+pub(crate) const TUNNEL_CHUNK_BYTES: usize = 64 * 1024;
+
+/// Response chunks the owner may send per request before waiting for credit.
+pub(crate) const TUNNEL_WINDOW_CHUNKS: u32 = 16;
+
+/// Visitor request bodies above this size are refused with 413.
+pub(crate) const MAX_TUNNEL_REQUEST_BODY_BYTES: usize = 32 * 1024 * 1024;
+
+/// How long to wait for the owner's response head once the request (and its
+/// body, if any) has been handed to the tunnel.
+const TUNNEL_RESPONSE_HEAD_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(15);
+
+/// Response body channel capacity: the window plus one slot a compliant owner
+/// never fills, so a terminal error can always be queued behind the data.
+const TUNNEL_BODY_CHANNEL_CAPACITY: usize = TUNNEL_WINDOW_CHUNKS as usize + 1;
+
+/// Frames sent relay → owner over the WebSocket.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub(crate) enum TunnelRelayFrame {
+    Request {
+        id: String,
+        method: String,
+        /// Path and query on the owner's local server.
+        path: String,
+        #[serde(default)]
+        headers: Vec<(String, String)>,
+        #[serde(default, rename = "hasBody")]
+        has_body: bool,
+    },
+    RequestEnd {
+        id: String,
+    },
+    Window {
+        id: String,
+        credits: u32,
+    },
+    Cancel {
+        id: String,
+    },
+}
+
+impl TunnelRelayFrame {
+    pub(crate) fn id(&self) -> &str {
+        match self {
+            Self::Request { id, .. }
+            | Self::RequestEnd { id }
+            | Self::Window { id, .. }
+            | Self::Cancel { id } => id,
+        }
+    }
+}
+
+/// Frames sent owner → relay over the WebSocket.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub(crate) enum TunnelOwnerFrame {
+    Response {
+        id: String,
+        status: u16,
+        #[serde(default)]
+        headers: Vec<(String, String)>,
+    },
+    ResponseEnd {
+        id: String,
+    },
+    Error {
+        id: String,
+        message: String,
+    },
+}
+
+/// Prefix `payload` with the id of the request it belongs to.
+pub(crate) fn encode_tunnel_chunk(id: &str, payload: &[u8]) -> Result<Vec<u8>, String> {
+    let id_len = u8::try_from(id.len())
+        .map_err(|_| format!("tunnel request id is {} bytes, max 255", id.len()))?;
+    if id_len == 0 {
+        return Err("tunnel request id is empty".to_string());
+    }
+    let mut frame = Vec::with_capacity(1 + id.len() + payload.len());
+    frame.push(id_len);
+    frame.extend_from_slice(id.as_bytes());
+    frame.extend_from_slice(payload);
+    Ok(frame)
+}
+
+/// Split a binary chunk frame into its request id and payload.
+pub(crate) fn decode_tunnel_chunk(frame: &[u8]) -> Result<(&str, &[u8]), String> {
+    let (&id_len, rest) = frame
+        .split_first()
+        .ok_or_else(|| "empty tunnel chunk frame".to_string())?;
+    let id_len = id_len as usize;
+    if id_len == 0 || rest.len() < id_len {
+        return Err(format!("tunnel chunk frame has invalid id length {id_len}"));
+    }
+    let (id, payload) = rest.split_at(id_len);
+    let id = std::str::from_utf8(id).map_err(|e| format!("tunnel chunk id is not UTF-8: {e}"))?;
+    Ok((id, payload))
+}
+
+/// Headers that describe a single connection and must not be forwarded.
+fn is_hop_by_hop_header(name: &str) -> bool {
+    matches!(
+        name.to_ascii_lowercase().as_str(),
+        "connection"
+            | "keep-alive"
+            | "proxy-authenticate"
+            | "proxy-authorization"
+            | "proxy-connection"
+            | "te"
+            | "trailer"
+            | "transfer-encoding"
+            | "upgrade"
+    )
+}
+
+/// Visitor request headers passed on to the owner. Host and length are
+/// recomputed by whichever client makes the next hop.
+pub(crate) fn is_forwardable_request_header(name: &str) -> bool {
+    !is_hop_by_hop_header(name)
+        && !name.eq_ignore_ascii_case("host")
+        && !name.eq_ignore_ascii_case("content-length")
+}
+
+/// Owner response headers passed back to the visitor.
+pub(crate) fn is_forwardable_response_header(name: &str) -> bool {
+    !is_hop_by_hop_header(name)
+}
+
+/// Resolve a proxied `path` against `origin` and make sure it stays inside the
+/// shared resource: `/drive/<token>` for shares, `/sites/<id>` for sites.
+///
+/// Both the relay (direct fallback) and the owner (tunnel) apply this, so a
+/// path like `/drive/tok/../../api/drive/items` or `@other.host/` can't reach
+/// anything else on — or beyond — the owner's server.
+pub(crate) fn scoped_origin_url(
+    origin: &str,
+    resource_type: &str,
+    resource_id: &str,
+    path: &str,
+) -> Result<reqwest::Url, String> {
+    let scope = match resource_type {
+        "share" => format!("/drive/{resource_id}"),
+        "site" => format!("/sites/{resource_id}"),
+        other => return Err(format!("unknown tunnel resource type '{other}'")),
+    };
+    if !path.starts_with('/') || path.starts_with("//") || path.contains('\\') {
+        return Err(format!("path must be origin-relative: {path}"));
+    }
+    let base = reqwest::Url::parse(origin).map_err(|e| format!("invalid origin URL: {e}"))?;
+    let url = base
+        .join(path)
+        .map_err(|e| format!("invalid path {path}: {e}"))?;
+    if url.origin() != base.origin() {
+        return Err(format!("path escapes the origin: {path}"));
+    }
+    let in_scope = url
+        .path()
+        .strip_prefix(&scope)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'));
+    if !in_scope {
+        return Err(format!("path {} is outside {}", url.path(), scope));
+    }
+    Ok(url)
+}
+
+/// Adapts a channel of body pieces into the `Sync` stream reqwest wants for
+/// streaming request bodies.
+pub(crate) struct ChannelBody(pub(crate) mpsc::Receiver<Result<Bytes, std::io::Error>>);
+
+impl Stream for ChannelBody {
+    type Item = Result<Bytes, std::io::Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.get_mut().0.poll_recv(cx)
+    }
+}
+
 // ---------------------------------------------------------------------------
 // WebSocket tunnel registry
 // ---------------------------------------------------------------------------
 
-/// A pending tunnel request: the relay sends a TunnelRequest over the WS and
-/// waits on the oneshot for the client's TunnelResponse.
+/// A pending tunnel request: the relay sends a request frame over the WS and
+/// waits on the oneshot for the owner's response head.
 type TunnelResponder = oneshot::Sender<TunnelResponse>;
 
-/// Messages sent relay → client over the WebSocket.
-#[derive(Serialize, Deserialize)]
-struct TunnelRequest {
-    id: String,
-    path: String,
+/// Feeds owner body chunks to the visitor's response body.
+type TunnelBodySender = mpsc::Sender<Result<Bytes, String>>;
+
+/// Where an in-flight tunnel request is, as seen by the WebSocket reader.
+#[derive(Debug)]
+enum PendingTunnelRequest {
+    AwaitingHead(TunnelResponder),
+    Streaming(TunnelBodySender),
 }
 
-/// Messages sent client → relay over the WebSocket.
-#[derive(Debug, Serialize, Deserialize)]
+type PendingTunnelRequests = HashMap<String, PendingTunnelRequest>;
+
+/// Response handed back to the proxy handler that opened the request.
+#[derive(Debug)]
 struct TunnelResponse {
-    id: String,
     status: u16,
-    #[serde(default)]
-    headers: HashMap<String, String>,
-    /// Base64-encoded body
-    body: String,
+    headers: Vec<(String, String)>,
+    body: TunnelResponseBody,
+}
+
+#[derive(Debug)]
+enum TunnelResponseBody {
+    /// Relay-generated body, e.g. a 502 explaining why the tunnel failed.
+    Full(Bytes),
+    /// Chunks streamed from the owner.
+    Stream(TunnelBodyStream),
+}
+
+/// Visitor-facing response body. Every chunk the visitor pulls grants the
+/// owner one window credit; dropping the body early cancels the request.
+#[derive(Debug)]
+struct TunnelBodyStream {
+    id: String,
+    chunks: mpsc::Receiver<Result<Bytes, String>>,
+    control: mpsc::UnboundedSender<TunnelRelayFrame>,
+    finished: bool,
+}
+
+impl Stream for TunnelBodyStream {
+    type Item = Result<Bytes, std::io::Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        match this.chunks.poll_recv(cx) {
+            Poll::Ready(Some(Ok(chunk))) => {
+                let _ = this.control.send(TunnelRelayFrame::Window {
+                    id: this.id.clone(),
+                    credits: 1,
+                });
+                Poll::Ready(Some(Ok(chunk)))
+            }
+            Poll::Ready(Some(Err(err))) => {
+                this.finished = true;
+                Poll::Ready(Some(Err(std::io::Error::other(err))))
+            }
+            Poll::Ready(None) => {
+                this.finished = true;
+                Poll::Ready(None)
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl Drop for TunnelBodyStream {
+    fn drop(&mut self) {
+        if !self.finished {
+            let _ = self.control.send(TunnelRelayFrame::Cancel {
+                id: self.id.clone(),
+            });
+        }
+    }
+}
+
+/// Work pushed from proxy handlers into a tunnel's WebSocket writer task.
+#[derive(Debug)]
+enum TunnelCommand {
+    /// Send a request head and start waiting for its response.
+    Open {
+        frame: TunnelRelayFrame,
+        responder: TunnelResponder,
+    },
+    /// Forward a piece of the visitor's request body.
+    Chunk { id: String, data: Bytes },
+    /// Any other relay → owner control frame.
+    Frame(TunnelRelayFrame),
 }
 
 #[derive(Debug, PartialEq, Eq)]
 enum TunnelResponseFrameOutcome {
     Delivered { id: String },
     UnknownId { id: String },
+    Cancelled { id: String },
+    OwnerError { id: String, error: String },
+    ProtocolViolation { id: String, error: String },
     MalformedDelivered { id: String, error: String },
     MalformedUnknownId { id: String, error: String },
     MalformedUnrecoverable { error: String },
@@ -243,9 +542,37 @@ struct TunnelResponseFrameError {
     message: String,
 }
 
-/// Active tunnel: a sender half of an mpsc channel to push requests into the
+/// Active tunnel: a sender half of an mpsc channel to push commands into the
 /// WebSocket writer task, which forwards them to the connected client.
-type TunnelSender = tokio::sync::mpsc::Sender<(TunnelRequest, TunnelResponder)>;
+type TunnelSender = mpsc::Sender<TunnelCommand>;
+
+/// A visitor request on its way to the owner.
+struct ProxiedRequest {
+    method: Method,
+    /// Path and query, exactly as the visitor sent them.
+    path: String,
+    headers: HeaderMap,
+    body: Body,
+}
+
+impl ProxiedRequest {
+    fn new(method: Method, uri: &Uri, headers: HeaderMap, body: Body) -> Self {
+        Self {
+            method,
+            path: request_path_and_query(uri),
+            headers,
+            body,
+        }
+    }
+}
+
+enum TunnelOutcome {
+    Response(TunnelResponse),
+    /// No usable tunnel; the request comes back untouched for direct proxying.
+    Unavailable(ProxiedRequest),
+    /// The tunnel took the request but it failed in a way that can't be retried.
+    Failed(Response),
+}
 
 /// Global registry of active tunnels keyed by resource key (e.g. "site:abc" or
 /// "share:xyz").
@@ -268,44 +595,190 @@ impl TunnelRegistry {
         self.tunnels.write().await.remove(key);
     }
 
-    /// Send a request through the tunnel and wait for the response.
-    /// Cleans up the pending map entry on timeout to prevent memory leaks.
-    async fn request(&self, key: &str, path: String) -> Option<TunnelResponse> {
+    /// Send a request through the tunnel and wait for the response head.
+    /// The request body is streamed in the background; the head timeout only
+    /// starts once the whole body has been handed over.
+    async fn request(&self, key: &str, req: ProxiedRequest) -> TunnelOutcome {
         let sender = {
             let map = self.tunnels.read().await;
             map.get(key).cloned()
         };
-        let sender = sender?;
+        let Some(sender) = sender else {
+            return TunnelOutcome::Unavailable(req);
+        };
 
         let id = uuid::Uuid::new_v4().to_string();
+        let has_body = request_has_body(&req.headers);
         let (resp_tx, resp_rx) = oneshot::channel();
-
-        let req = TunnelRequest {
+        let frame = TunnelRelayFrame::Request {
             id: id.clone(),
-            path,
+            method: req.method.to_string(),
+            path: req.path.clone(),
+            headers: tunnel_request_headers(&req.headers),
+            has_body,
         };
 
-        if sender.send((req, resp_tx)).await.is_err() {
+        let open = TunnelCommand::Open {
+            frame,
+            responder: resp_tx,
+        };
+        if sender.send(open).await.is_err() {
             // Tunnel disconnected — unregister it
             self.unregister(key).await;
-            return None;
+            return TunnelOutcome::Unavailable(req);
         }
 
-        // Wait up to 15s for the client to respond (reduced from 30s)
-        match tokio::time::timeout(std::time::Duration::from_secs(15), resp_rx).await {
-            Ok(Ok(resp)) => Some(resp),
-            _ => {
-                // Timeout or channel dropped — the pending entry is already consumed
-                // by the responder being dropped, so no leak here. But if the tunnel
-                // is consistently timing out, remove it.
-                None
+        let ProxiedRequest {
+            method,
+            path,
+            headers,
+            body,
+        } = req;
+        let pump = has_body
+            .then(|| tokio::spawn(pump_tunnel_request_body(sender.clone(), id.clone(), body)));
+        let deadline = async move {
+            if let Some(pump) = pump {
+                match pump.await {
+                    Ok(Ok(())) => {}
+                    Ok(Err(response)) => return Some(response),
+                    Err(err) => {
+                        return Some(
+                            (
+                                StatusCode::BAD_GATEWAY,
+                                format!("Tunnel request body task failed: {err}"),
+                            )
+                                .into_response(),
+                        )
+                    }
+                }
+            }
+            tokio::time::sleep(TUNNEL_RESPONSE_HEAD_TIMEOUT).await;
+            None
+        };
+
+        tokio::select! {
+            resp = resp_rx => match resp {
+                Ok(resp) => TunnelOutcome::Response(resp),
+                Err(_) => TunnelOutcome::Failed(
+                    (
+                        StatusCode::BAD_GATEWAY,
+                        "Tunnel owner disconnected before responding",
+                    )
+                        .into_response(),
+                ),
+            },
+            failure = deadline => {
+                let _ = sender.try_send(TunnelCommand::Frame(TunnelRelayFrame::Cancel { id }));
+                match failure {
+                    Some(response) => TunnelOutcome::Failed(response),
+                    // A bodiless request can still be retried over direct HTTP.
+                    None if !has_body => TunnelOutcome::Unavailable(ProxiedRequest {
+                        method,
+                        path,
+                        headers,
+                        body: Body::empty(),
+                    }),
+                    None => TunnelOutcome::Failed(
+                        (
+                            StatusCode::GATEWAY_TIMEOUT,
+                            "Tunnel owner did not respond in time",
+                        )
+                            .into_response(),
+                    ),
+                }
             }
         }
     }
 }
 
-fn parse_tunnel_response_frame(text: &str) -> Result<TunnelResponse, TunnelResponseFrameError> {
-    serde_json::from_str::<TunnelResponse>(text).map_err(|err| TunnelResponseFrameError {
+fn request_body_too_large() -> Response {
+    (
+        StatusCode::PAYLOAD_TOO_LARGE,
+        format!(
+            "Request body exceeds the relay limit of {} bytes",
+            MAX_TUNNEL_REQUEST_BODY_BYTES
+        ),
+    )
+        .into_response()
+}
+
+/// Stream the visitor's request body into the tunnel in chunk-sized pieces,
+/// stopping with 413 once it passes the relay limit.
+async fn pump_tunnel_request_body(
+    sender: TunnelSender,
+    id: String,
+    body: Body,
+) -> Result<(), Response> {
+    let closed = || {
+        (
+            StatusCode::BAD_GATEWAY,
+            "Tunnel owner disconnected while receiving the request body",
+        )
+            .into_response()
+    };
+    let mut stream = body.into_data_stream();
+    let mut total = 0usize;
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(|e| {
+            (
+                StatusCode::BAD_REQUEST,
+                format!("Failed to read request body: {e}"),
+            )
+                .into_response()
+        })?;
+        total += chunk.len();
+        if total > MAX_TUNNEL_REQUEST_BODY_BYTES {
+            return Err(request_body_too_large());
+        }
+        let mut offset = 0;
+        while offset < chunk.len() {
+            let end = (offset + TUNNEL_CHUNK_BYTES).min(chunk.len());
+            let data = chunk.slice(offset..end);
+            offset = end;
+            sender
+                .send(TunnelCommand::Chunk {
+                    id: id.clone(),
+                    data,
+                })
+                .await
+                .map_err(|_| closed())?;
+        }
+    }
+    sender
+        .send(TunnelCommand::Frame(TunnelRelayFrame::RequestEnd { id }))
+        .await
+        .map_err(|_| closed())
+}
+
+fn declared_content_length(headers: &HeaderMap) -> Option<u64> {
+    headers
+        .get(axum::http::header::CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse().ok())
+}
+
+fn request_has_body(headers: &HeaderMap) -> bool {
+    headers.contains_key(axum::http::header::TRANSFER_ENCODING)
+        || declared_content_length(headers).is_some_and(|len| len > 0)
+}
+
+/// Forwardable visitor headers as wire pairs. Repeated headers (e.g. several
+/// `Cookie` lines) stay separate; values that aren't visible ASCII are dropped.
+fn tunnel_request_headers(headers: &HeaderMap) -> Vec<(String, String)> {
+    headers
+        .iter()
+        .filter(|(name, _)| is_forwardable_request_header(name.as_str()))
+        .filter_map(|(name, value)| {
+            value
+                .to_str()
+                .ok()
+                .map(|value| (name.as_str().to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+fn parse_tunnel_response_frame(text: &str) -> Result<TunnelOwnerFrame, TunnelResponseFrameError> {
+    serde_json::from_str::<TunnelOwnerFrame>(text).map_err(|err| TunnelResponseFrameError {
         id: recover_tunnel_response_id(text),
         message: err.to_string(),
     })
@@ -322,51 +795,116 @@ fn recover_tunnel_response_id(text: &str) -> Option<String> {
         })
 }
 
-fn tunnel_frame_error_response(id: String, error: &str) -> TunnelResponse {
-    use base64::Engine;
-
-    let mut headers = HashMap::new();
-    headers.insert(
-        "content-type".to_string(),
-        "text/plain; charset=utf-8".to_string(),
-    );
+fn tunnel_error_response(message: String) -> TunnelResponse {
     TunnelResponse {
-        id,
         status: StatusCode::BAD_GATEWAY.as_u16(),
-        headers,
-        body: base64::engine::general_purpose::STANDARD.encode(format!(
-            "Malformed tunnel response frame from owner: {error}"
-        )),
+        headers: vec![(
+            "content-type".to_string(),
+            "text/plain; charset=utf-8".to_string(),
+        )],
+        body: TunnelResponseBody::Full(Bytes::from(message)),
+    }
+}
+
+/// Fail a pending request: a 502 if its head hasn't arrived yet, otherwise an
+/// error that aborts the visitor's body mid-stream instead of truncating it
+/// silently.
+fn fail_pending_tunnel_request(entry: PendingTunnelRequest, message: String) {
+    match entry {
+        PendingTunnelRequest::AwaitingHead(tx) => {
+            let _ = tx.send(tunnel_error_response(message));
+        }
+        PendingTunnelRequest::Streaming(tx) => {
+            let _ = tx.try_send(Err(message));
+        }
+    }
+}
+
+fn fail_pending_tunnel_requests(pending: &mut PendingTunnelRequests, message: &str) {
+    for (_, entry) in pending.drain() {
+        fail_pending_tunnel_request(entry, message.to_string());
     }
 }
 
 fn route_tunnel_response_frame(
-    pending: &mut HashMap<String, TunnelResponder>,
+    pending: &mut PendingTunnelRequests,
+    control: &mpsc::UnboundedSender<TunnelRelayFrame>,
     text: &str,
 ) -> TunnelResponseFrameOutcome {
-    route_parsed_tunnel_response_frame(pending, parse_tunnel_response_frame(text))
+    route_parsed_tunnel_response_frame(pending, control, parse_tunnel_response_frame(text))
 }
 
 fn route_parsed_tunnel_response_frame(
-    pending: &mut HashMap<String, TunnelResponder>,
-    parsed: Result<TunnelResponse, TunnelResponseFrameError>,
+    pending: &mut PendingTunnelRequests,
+    control: &mpsc::UnboundedSender<TunnelRelayFrame>,
+    parsed: Result<TunnelOwnerFrame, TunnelResponseFrameError>,
 ) -> TunnelResponseFrameOutcome {
     match parsed {
-        Ok(resp) => {
-            let id = resp.id.clone();
-            if let Some(tx) = pending.remove(&id) {
-                let _ = tx.send(resp);
+        Ok(TunnelOwnerFrame::Response {
+            id,
+            status,
+            headers,
+        }) => match pending.remove(&id) {
+            Some(PendingTunnelRequest::AwaitingHead(tx)) => {
+                let (chunk_tx, chunks) = mpsc::channel(TUNNEL_BODY_CHANNEL_CAPACITY);
+                let resp = TunnelResponse {
+                    status,
+                    headers,
+                    body: TunnelResponseBody::Stream(TunnelBodyStream {
+                        id: id.clone(),
+                        chunks,
+                        control: control.clone(),
+                        finished: false,
+                    }),
+                };
+                // If the handler stopped waiting, the rejected body is dropped
+                // here and its Drop impl cancels the request.
+                if tx.send(resp).is_ok() {
+                    pending.insert(id.clone(), PendingTunnelRequest::Streaming(chunk_tx));
+                    TunnelResponseFrameOutcome::Delivered { id }
+                } else {
+                    TunnelResponseFrameOutcome::Cancelled { id }
+                }
+            }
+            Some(entry) => {
+                let error = "owner sent a second response head".to_string();
+                fail_pending_tunnel_request(entry, error.clone());
+                let _ = control.send(TunnelRelayFrame::Cancel { id: id.clone() });
+                TunnelResponseFrameOutcome::ProtocolViolation { id, error }
+            }
+            None => TunnelResponseFrameOutcome::UnknownId { id },
+        },
+        Ok(TunnelOwnerFrame::ResponseEnd { id }) => match pending.remove(&id) {
+            // Dropping the sender ends the visitor's body cleanly.
+            Some(PendingTunnelRequest::Streaming(_)) => {
                 TunnelResponseFrameOutcome::Delivered { id }
-            } else {
-                TunnelResponseFrameOutcome::UnknownId { id }
             }
-        }
+            Some(entry) => {
+                let error = "owner ended a response it never started".to_string();
+                fail_pending_tunnel_request(entry, error.clone());
+                TunnelResponseFrameOutcome::ProtocolViolation { id, error }
+            }
+            None => TunnelResponseFrameOutcome::UnknownId { id },
+        },
+        Ok(TunnelOwnerFrame::Error { id, message }) => match pending.remove(&id) {
+            Some(entry) => {
+                fail_pending_tunnel_request(
+                    entry,
+                    format!("Tunnel owner failed the request: {message}"),
+                );
+                TunnelResponseFrameOutcome::OwnerError { id, error: message }
+            }
+            None => TunnelResponseFrameOutcome::UnknownId { id },
+        },
         Err(err) => {
             let error = err.message;
             match err.id {
                 Some(id) => {
-                    if let Some(tx) = pending.remove(&id) {
-                        let _ = tx.send(tunnel_frame_error_response(id.clone(), &error));
+                    if let Some(entry) = pending.remove(&id) {
+                        fail_pending_tunnel_request(
+                            entry,
+                            format!("Malformed tunnel response frame from owner: {error}"),
+                        );
                         TunnelResponseFrameOutcome::MalformedDelivered { id, error }
                     } else {
                         TunnelResponseFrameOutcome::MalformedUnknownId { id, error }
@@ -378,6 +916,43 @@ fn route_parsed_tunnel_response_frame(
     }
 }
 
+/// Route a binary body chunk from the owner to the visitor's response body.
+fn route_tunnel_chunk_frame(
+    pending: &mut PendingTunnelRequests,
+    control: &mpsc::UnboundedSender<TunnelRelayFrame>,
+    frame: &[u8],
+) -> TunnelResponseFrameOutcome {
+    let (id, payload) = match decode_tunnel_chunk(frame) {
+        Ok(parts) => parts,
+        Err(error) => return TunnelResponseFrameOutcome::MalformedUnrecoverable { error },
+    };
+    let id = id.to_string();
+    let tx = match pending.remove(&id) {
+        Some(PendingTunnelRequest::Streaming(tx)) => tx,
+        Some(entry) => {
+            let error = "owner sent body data before the response head".to_string();
+            fail_pending_tunnel_request(entry, error.clone());
+            return TunnelResponseFrameOutcome::ProtocolViolation { id, error };
+        }
+        None => return TunnelResponseFrameOutcome::UnknownId { id },
+    };
+    if tx.is_closed() {
+        let _ = control.send(TunnelRelayFrame::Cancel { id: id.clone() });
+        return TunnelResponseFrameOutcome::Cancelled { id };
+    }
+    // A compliant owner never reaches the spare slot, see
+    // TUNNEL_BODY_CHANNEL_CAPACITY.
+    if tx.capacity() <= 1 {
+        let error = "owner exceeded the response window".to_string();
+        fail_pending_tunnel_request(PendingTunnelRequest::Streaming(tx), error.clone());
+        let _ = control.send(TunnelRelayFrame::Cancel { id: id.clone() });
+        return TunnelResponseFrameOutcome::ProtocolViolation { id, error };
+    }
+    let _ = tx.try_send(Ok(Bytes::copy_from_slice(payload)));
+    pending.insert(id.clone(), PendingTunnelRequest::Streaming(tx));
+    TunnelResponseFrameOutcome::Delivered { id }
+}
+
 // ---------------------------------------------------------------------------
 // Request/response types
 // ---------------------------------------------------------------------------
@@ -393,6 +968,10 @@ struct RegisterRequest {
     /// attacker-chosen URL (FM-A05).
     #[serde(default)]
     signature: String,
+    /// Owner's libp2p peer ID. Covered by the signature when present, so a
+    /// captured registration can't be pointed at a different node.
+    #[serde(default)]
+    peer_id: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -404,6 +983,8 @@ struct SiteRegisterRequest {
     /// site_id, owner_wallet, origin_url)`. See `RegisterRequest`.
     #[serde(default)]
     signature: String,
+    #[serde(default)]
+    peer_id: Option<String>,
 }
 
 const REGISTER_TAG: &[u8] = b"chiral-relay-register-v1";
@@ -461,21 +1042,45 @@ pub fn register_payload(
     id: &str,
     owner_wallet: &str,
     origin_url: &str,
+) -> Vec<u8> {
+    register_payload_with_peer(operation, id, owner_wallet, origin_url, None)
+}
+
+/// `register_payload` plus the owner's libp2p peer ID. Without a peer ID
+/// the bytes are identical to `register_payload`, so older clients' proofs
+/// still verify.
+pub fn register_payload_with_peer(
+    operation: &str,
+    id: &str,
+    owner_wallet: &str,
+    origin_url: &str,
+    peer_id: Option<&str>,
 ) -> Vec<u8> {
     let mut out = Vec::with_capacity(64 + origin_url.len());
     out.extend_from_slice(REGISTER_TAG);
-    for part in [
+    let parts = [
         operation.as_bytes(),
         id.as_bytes(),
         owner_wallet.as_bytes(),
         origin_url.as_bytes(),
-    ] {
+    ];
+    for part in parts.into_iter().chain(peer_id.map(str::as_bytes)) {
         out.extend_from_slice(&(part.len() as u32).to_le_bytes());
         out.extend_from_slice(part);
     }
     out
 }
 
+fn validate_registration_peer_id(peer_id: Option<&str>) -> Result<(), String> {
+    match peer_id {
+        None => Ok(()),
+        Some(raw) => raw
+            .parse::<libp2p::PeerId>()
+            .map(|_| ())
+            .map_err(|e| format!("peer_id is not a valid libp2p peer ID: {e}")),
+    }
+}
+
 fn is_valid_wallet(s: &str) -> bool {
     s.len() == 42 && s.starts_with("0x") && s[2..].chars().all(|c| c.is_ascii_hexdigit())
 }
@@ -775,12 +1380,6 @@ fn is_safe_normalized_origin_url(origin_url: &str) -> Result<(), String> {
     Ok(())
 }
 
-#[derive(Deserialize)]
-struct ProxyQuery {
-    #[serde(flatten)]
-    params: HashMap<String, String>,
-}
-
 #[derive(Deserialize)]
 struct TunnelQuery {
     /// "site" or "share"
@@ -922,10 +1521,19 @@ async fn register_share(
     if let Err(e) = is_safe_origin_url(&req.origin_url) {
         return (StatusCode::BAD_REQUEST, e).into_response();
     }
+    if let Err(e) = validate_registration_peer_id(req.peer_id.as_deref()) {
+        return (StatusCode::BAD_REQUEST, e).into_response();
+    }
     if req.signature.is_empty() {
         return (StatusCode::UNAUTHORIZED, "signature required").into_response();
     }
-    let payload = register_payload("share", &req.token, &owner, &req.origin_url);
+    let payload = register_payload_with_peer(
+        "share",
+        &req.token,
+        &owner,
+        &req.origin_url,
+        req.peer_id.as_deref(),
+    );
     if !crate::wallet::verify_signature(&payload, &req.signature, &owner) {
         return (
             StatusCode::UNAUTHORIZED,
@@ -949,11 +1557,18 @@ async fn register_share(
         Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
     };
     if let Err(e) = preflight_origin_reachable(&origin).await {
-        return (StatusCode::BAD_GATEWAY, e).into_response();
+        // An owner behind NAT is still reachable over its libp2p connection.
+        if state.p2p_route(req.peer_id.as_deref()).is_none() {
+            return (StatusCode::BAD_GATEWAY, e).into_response();
+        }
+        println!(
+            "[RELAY-SHARE] Origin unreachable for token={}; serving over libp2p: {}",
+            req.token, e
+        );
     }
     println!(
-        "[RELAY-SHARE] Registering share token={} origin={} (raw={}) owner={}",
-        req.token, origin, req.origin_url, owner
+        "[RELAY-SHARE] Registering share token={} origin={} (raw={}) owner={} peer={:?}",
+        req.token, origin, req.origin_url, owner, req.peer_id
     );
     state
         .register(ShareRegistration {
@@ -961,6 +1576,7 @@ async fn register_share(
             origin_url: origin,
             owner_wallet: owner,
             registered_at: now_secs(),
+            peer_id: req.peer_id,
         })
         .await;
     (StatusCode::OK, "Registered").into_response()
@@ -1028,14 +1644,17 @@ async fn handle_tunnel_ws(socket: WebSocket, key: String, tunnel_reg: Arc<Tunnel
     let (mut ws_tx, mut ws_rx) = socket.split();
 
     // Channel for the proxy handlers to send requests into this tunnel
-    let (req_tx, mut req_rx) = tokio::sync::mpsc::channel::<(TunnelRequest, TunnelResponder)>(32);
+    let (cmd_tx, mut cmd_rx) = mpsc::channel::<TunnelCommand>(32);
+    // Window grants and cancellations raised by the reader and response bodies.
+    // Unbounded so neither ever blocks on the writer; each entry is tiny and
+    // bounded by the number of chunks in flight.
+    let (control_tx, mut control_rx) = mpsc::unbounded_channel::<TunnelRelayFrame>();
 
-    tunnel_reg.register(key.clone(), req_tx).await;
+    tunnel_reg.register(key.clone(), cmd_tx).await;
     println!("[TUNNEL] Connected: {}", key);
 
-    // Map of pending request IDs → responders
-    let pending: Arc<RwLock<HashMap<String, TunnelResponder>>> =
-        Arc::new(RwLock::new(HashMap::new()));
+    // Map of pending request IDs → where their response goes
+    let pending: Arc<RwLock<PendingTunnelRequests>> = Arc::new(RwLock::new(HashMap::new()));
     let pending_for_read = Arc::clone(&pending);
 
     // Task: read responses from the WebSocket client
@@ -1053,89 +1672,85 @@ async fn handle_tunnel_ws(socket: WebSocket, key: String, tunnel_reg: Arc<Tunnel
                     break;
                 }
             };
-            match msg {
+            let outcome = match msg {
                 Message::Text(text) => {
-                    let parsed = parse_tunnel_response_frame(&text);
-                    let outcome = {
-                        let mut map = pending_for_read.write().await;
-                        route_parsed_tunnel_response_frame(&mut map, parsed)
-                    };
-                    match outcome {
-                        TunnelResponseFrameOutcome::Delivered { .. } => {}
-                        TunnelResponseFrameOutcome::UnknownId { id } => {
-                            eprintln!(
-                                "[TUNNEL] Ignoring response for unknown request id={} key={}",
-                                id, read_key
-                            );
-                        }
-                        TunnelResponseFrameOutcome::MalformedDelivered { id, error } => {
-                            eprintln!(
-                                "[TUNNEL] Malformed response frame for key={} id={}: {}",
-                                read_key, id, error
-                            );
-                        }
-                        TunnelResponseFrameOutcome::MalformedUnknownId { id, error } => {
-                            eprintln!(
-                                "[TUNNEL] Malformed response frame for unknown request id={} key={}: {}",
-                                id, read_key, error
-                            );
-                        }
-                        TunnelResponseFrameOutcome::MalformedUnrecoverable { error } => {
-                            eprintln!(
-                                "[TUNNEL] Malformed response frame for key={} without recoverable id: {}",
-                                read_key, error
-                            );
-                        }
-                    }
+                    let mut map = pending_for_read.write().await;
+                    route_tunnel_response_frame(&mut map, &control_tx, &text)
+                }
+                Message::Binary(data) => {
+                    let mut map = pending_for_read.write().await;
+                    route_tunnel_chunk_frame(&mut map, &control_tx, &data)
                 }
                 Message::Close(_) => {
                     println!("[TUNNEL] Client closed: {}", read_key);
                     break;
                 }
-                _ => {}
+                _ => continue,
+            };
+            match outcome {
+                TunnelResponseFrameOutcome::Delivered { .. }
+                | TunnelResponseFrameOutcome::Cancelled { .. } => {}
+                TunnelResponseFrameOutcome::UnknownId { id } => {
+                    eprintln!(
+                        "[TUNNEL] Ignoring frame for unknown request id={} key={}",
+                        id, read_key
+                    );
+                }
+                TunnelResponseFrameOutcome::OwnerError { id, error } => {
+                    eprintln!(
+                        "[TUNNEL] Owner failed request for key={} id={}: {}",
+                        read_key, id, error
+                    );
+                }
+                TunnelResponseFrameOutcome::ProtocolViolation { id, error } => {
+                    eprintln!(
+                        "[TUNNEL] Protocol violation for key={} id={}: {}",
+                        read_key, id, error
+                    );
+                }
+                TunnelResponseFrameOutcome::MalformedDelivered { id, error } => {
+                    eprintln!(
+                        "[TUNNEL] Malformed response frame for key={} id={}: {}",
+                        read_key, id, error
+                    );
+                }
+                TunnelResponseFrameOutcome::MalformedUnknownId { id, error } => {
+                    eprintln!(
+                        "[TUNNEL] Malformed response frame for unknown request id={} key={}: {}",
+                        id, read_key, error
+                    );
+                }
+                TunnelResponseFrameOutcome::MalformedUnrecoverable { error } => {
+                    eprintln!(
+                        "[TUNNEL] Malformed response frame for key={} without recoverable id: {}",
+                        read_key, error
+                    );
+                }
             }
         }
     });
 
     // Task: forward requests from proxy handlers to the WebSocket client
     let write_key = key.clone();
+    let pending_for_write = Arc::clone(&pending);
     let write_task = tokio::spawn(async move {
         // Periodic pings to keep the connection alive + cleanup stale pending entries
         let mut ping_interval = tokio::time::interval(std::time::Duration::from_secs(30));
         loop {
-            tokio::select! {
-                req = req_rx.recv() => {
-                    match req {
-                        Some((tunnel_req, responder)) => {
-                            let id = tunnel_req.id.clone();
-                            pending.write().await.insert(id, responder);
-                            let json = serde_json::to_string(&tunnel_req).unwrap_or_default();
-                            let send_result = ws_tx
-                                .send(Message::Text(json.into()))
-                                .await
-                                .map_err(|err| err.to_string());
-                            let send_result = {
-                                let mut map = pending.write().await;
-                                relay_tunnel_request_send_result(
-                                    &mut map,
-                                    &write_key,
-                                    &tunnel_req.id,
-                                    send_result,
-                                )
-                            };
-                            if let Err(message) = send_result {
-                                eprintln!("{}", message);
-                                break;
-                            }
-                        }
-                        None => break,
-                    }
-                }
+            let command = tokio::select! {
+                cmd = cmd_rx.recv() => match cmd {
+                    Some(cmd) => cmd,
+                    None => break,
+                },
+                Some(frame) = control_rx.recv() => TunnelCommand::Frame(frame),
                 _ = ping_interval.tick() => {
-                    // Clean up stale pending entries (responder dropped = channel closed)
+                    // Clean up stale pending entries (visitor side dropped)
                     {
-                        let mut map = pending.write().await;
-                        map.retain(|_, tx| !tx.is_closed());
+                        let mut map = pending_for_write.write().await;
+                        map.retain(|_, entry| match entry {
+                            PendingTunnelRequest::AwaitingHead(tx) => !tx.is_closed(),
+                            PendingTunnelRequest::Streaming(tx) => !tx.is_closed(),
+                        });
                     }
                     let send_result = ws_tx
                         .send(Message::Ping(vec![].into()))
@@ -1147,7 +1762,46 @@ async fn handle_tunnel_ws(socket: WebSocket, key: String, tunnel_reg: Arc<Tunnel
                         eprintln!("{}", message);
                         break;
                     }
+                    continue;
                 }
+            };
+
+            let (id, message) = match command {
+                TunnelCommand::Open { frame, responder } => {
+                    let id = frame.id().to_string();
+                    pending_for_write
+                        .write()
+                        .await
+                        .insert(id.clone(), PendingTunnelRequest::AwaitingHead(responder));
+                    let json = serde_json::to_string(&frame).unwrap_or_default();
+                    (id, Message::Text(json))
+                }
+                TunnelCommand::Chunk { id, data } => match encode_tunnel_chunk(&id, &data) {
+                    Ok(frame) => (id, Message::Binary(frame)),
+                    Err(err) => {
+                        eprintln!(
+                            "[TUNNEL] Dropping request body chunk for key={} id={}: {}",
+                            write_key, id, err
+                        );
+                        continue;
+                    }
+                },
+                TunnelCommand::Frame(frame) => {
+                    if let TunnelRelayFrame::Cancel { id } = &frame {
+                        pending_for_write.write().await.remove(id);
+                    }
+                    let json = serde_json::to_string(&frame).unwrap_or_default();
+                    (frame.id().to_string(), Message::Text(json))
+                }
+            };
+            let send_result = ws_tx.send(message).await.map_err(|err| err.to_string());
+            let send_result = {
+                let mut map = pending_for_write.write().await;
+                relay_tunnel_request_send_result(&mut map, &write_key, &id, send_result)
+            };
+            if let Err(message) = send_result {
+                eprintln!("{}", message);
+                break;
             }
         }
     });
@@ -1159,6 +1813,10 @@ async fn handle_tunnel_ws(socket: WebSocket, key: String, tunnel_reg: Arc<Tunnel
     }
 
     tunnel_reg.unregister(&key).await;
+    fail_pending_tunnel_requests(
+        &mut *pending.write().await,
+        "Tunnel owner disconnected mid-request",
+    );
     println!("[TUNNEL] Disconnected: {}", key);
 }
 
@@ -1175,37 +1833,160 @@ fn relay_tunnel_keepalive_ping_result(
 }
 
 // ---------------------------------------------------------------------------
-// Reverse proxy helpers
+// HTTP over libp2p
 // ---------------------------------------------------------------------------
 
-/// Build the query string from the flattened params map.
-fn build_query_string(params: &HashMap<String, String>) -> String {
-    if params.is_empty() {
-        return String::new();
+/// An owner the relay can reach over its swarm connection.
+struct P2pRoute {
+    client: HttpP2pClient,
+    peer: libp2p::PeerId,
+}
+
+impl P2pRoute {
+    /// Fetch over libp2p. The request is handed back, body intact, when the
+    /// owner can't be reached this way so the direct proxy can still try.
+    async fn request(
+        self,
+        resource_type: &str,
+        resource_id: &str,
+        req: ProxiedRequest,
+    ) -> Result<Response, ProxiedRequest> {
+        if request_has_body(&req.headers) {
+            match declared_content_length(&req.headers) {
+                Some(len) if len <= http_p2p::MAX_HTTP_P2P_REQUEST_BODY_BYTES as u64 => {}
+                // Unknown length or too big for one message.
+                _ => return Err(req),
+            }
+        }
+        let ProxiedRequest {
+            method,
+            path,
+            headers,
+            body,
+        } = req;
+        let body = match axum::body::to_bytes(body, http_p2p::MAX_HTTP_P2P_REQUEST_BODY_BYTES).await
+        {
+            Ok(body) => body,
+            Err(e) => {
+                return Ok((
+                    StatusCode::BAD_REQUEST,
+                    format!("Failed to read request body: {e}"),
+                )
+                    .into_response())
+            }
+        };
+        let start = HttpP2pRequest::Start {
+            resource_type: resource_type.to_string(),
+            resource_id: resource_id.to_string(),
+            method: method.to_string(),
+            path: path.clone(),
+            headers: tunnel_request_headers(&headers),
+            body: body.to_vec(),
+        };
+        let response = match self.client.call(self.peer, start).await {
+            Ok(response) => response,
+            Err(e) => {
+                println!(
+                    "[RELAY-P2P] {}:{} unreachable over libp2p: {}",
+                    resource_type, resource_id, e
+                );
+                return Err(ProxiedRequest {
+                    method,
+                    path,
+                    headers,
+                    body: Body::from(body),
+                });
+            }
+        };
+        let resp = match response {
+            HttpP2pResponse::Head {
+                status,
+                headers,
+                body,
+                stream_id,
+            } => owner_response(status, &headers, p2p_response_body(self, body, stream_id)),
+            HttpP2pResponse::Error { message } => {
+                tunnel_response_to_axum(tunnel_error_response(message))
+            }
+            _ => tunnel_response_to_axum(tunnel_error_response(
+                "Owner sent an unexpected libp2p response".to_string(),
+            )),
+        };
+        Ok(resp)
     }
-    let qs: Vec<String> = params.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
-    format!("?{}", qs.join("&"))
 }
 
-fn tunnel_request_send_error_response(id: String, error: &str) -> TunnelResponse {
-    use base64::Engine;
+/// Pulls the rest of a libp2p response body, cancelling it on the owner if
+/// the visitor goes away first.
+struct P2pBodyPull {
+    route: P2pRoute,
+    stream_id: u64,
+    finished: bool,
+}
 
-    let mut headers = HashMap::new();
-    headers.insert(
-        "content-type".to_string(),
-        "text/plain; charset=utf-8".to_string(),
-    );
-    TunnelResponse {
-        id,
-        status: StatusCode::BAD_GATEWAY.as_u16(),
-        headers,
-        body: base64::engine::general_purpose::STANDARD
-            .encode(format!("Failed to send tunnel request to owner: {error}")),
+impl Drop for P2pBodyPull {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.route.client.cancel(self.route.peer, self.stream_id);
+        }
+    }
+}
+
+fn p2p_response_body(route: P2pRoute, first: Vec<u8>, stream_id: Option<u64>) -> Body {
+    let Some(stream_id) = stream_id else {
+        return Body::from(first);
+    };
+    let pull = P2pBodyPull {
+        route,
+        stream_id,
+        finished: false,
+    };
+    let rest = futures_util::stream::unfold(Some(pull), |pull| async move {
+        let mut pull = pull?;
+        let next = HttpP2pRequest::Next {
+            stream_id: pull.stream_id,
+        };
+        let item = match pull.route.client.call(pull.route.peer, next).await {
+            Ok(HttpP2pResponse::Chunk { data, done }) => {
+                pull.finished = done;
+                Ok(Bytes::from(data))
+            }
+            Ok(HttpP2pResponse::Error { message }) => {
+                pull.finished = true;
+                Err(std::io::Error::other(message))
+            }
+            Ok(_) => Err(std::io::Error::other(
+                "Owner sent an unexpected libp2p response",
+            )),
+            Err(e) => Err(std::io::Error::other(e)),
+        };
+        let more = item.is_ok() && !pull.finished;
+        Some((item, more.then_some(pull)))
+    });
+    let first = futures_util::stream::once(async move { Ok(Bytes::from(first)) });
+    Body::from_stream(first.chain(rest))
+}
+
+// ---------------------------------------------------------------------------
+// Reverse proxy helpers
+// ---------------------------------------------------------------------------
+
+/// Path and query of the visitor's request, still percent-encoded.
+fn request_path_and_query(uri: &Uri) -> String {
+    uri.path_and_query()
+        .map(|pq| pq.as_str().to_string())
+        .unwrap_or_else(|| uri.path().to_string())
+}
+
+fn validate_proxied_method(method: &Method) -> Result<(), String> {
+    if method == Method::CONNECT || method == Method::TRACE {
+        return Err(format!("{} requests are not proxied", method));
     }
+    Ok(())
 }
 
 fn relay_tunnel_request_send_result(
-    pending: &mut HashMap<String, TunnelResponder>,
+    pending: &mut PendingTunnelRequests,
     key: &str,
     id: &str,
     send_result: Result<(), String>,
@@ -1217,76 +1998,90 @@ fn relay_tunnel_request_send_result(
                 "[TUNNEL] Failed to send request frame for key={} id={}: {}",
                 key, id, err
             );
-            if let Some(tx) = pending.remove(id) {
-                let _ = tx.send(tunnel_request_send_error_response(id.to_string(), &message));
+            if let Some(entry) = pending.remove(id) {
+                fail_pending_tunnel_request(
+                    entry,
+                    format!("Failed to send tunnel request to owner: {message}"),
+                );
             }
             Err(message)
         }
     }
 }
 
-/// Try the WebSocket tunnel first; if unavailable fall back to direct HTTP proxy.
+/// Try the WebSocket tunnel first, then the owner's libp2p connection, then
+/// a direct HTTP proxy.
 async fn proxy_via_tunnel_or_http(
     tunnel_reg: &Arc<TunnelRegistry>,
-    tunnel_key: &str,
-    path: &str,
-    direct_url: &str,
+    p2p: Option<P2pRoute>,
+    resource_type: &str,
+    resource_id: &str,
+    origin_url: &str,
+    req: ProxiedRequest,
 ) -> Response {
-    // Try tunnel first
-    if let Some(resp) = tunnel_reg.request(tunnel_key, path.to_string()).await {
-        return tunnel_response_to_axum(resp);
+    if let Err(e) = validate_proxied_method(&req.method) {
+        return (StatusCode::METHOD_NOT_ALLOWED, e).into_response();
+    }
+    if request_has_body(&req.headers)
+        && declared_content_length(&req.headers)
+            .is_some_and(|len| len > MAX_TUNNEL_REQUEST_BODY_BYTES as u64)
+    {
+        return request_body_too_large();
     }
+    let direct_url = match scoped_origin_url(origin_url, resource_type, resource_id, &req.path) {
+        Ok(url) => url,
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
 
-    // Fall back to direct HTTP proxy (works if port is forwarded)
-    proxy_request_direct(direct_url).await
+    // Try tunnel first
+    let tunnel_key = format!("{}:{}", resource_type, resource_id);
+    match tunnel_reg.request(&tunnel_key, req).await {
+        TunnelOutcome::Response(resp) => tunnel_response_to_axum(resp),
+        TunnelOutcome::Failed(resp) => resp,
+        TunnelOutcome::Unavailable(req) => {
+            let req = match p2p {
+                Some(route) => match route.request(resource_type, resource_id, req).await {
+                    Ok(resp) => return resp,
+                    Err(req) => req,
+                },
+                None => req,
+            };
+            // Fall back to direct HTTP proxy (works if port is forwarded)
+            proxy_request_direct(direct_url.as_str(), req).await
+        }
+    }
 }
 
-/// Convert a TunnelResponse into an Axum HTTP response.
+/// Convert a TunnelResponse into an Axum HTTP response, streaming the body.
 fn tunnel_response_to_axum(resp: TunnelResponse) -> Response {
-    use base64::Engine;
-    let status = StatusCode::from_u16(resp.status).unwrap_or(StatusCode::BAD_GATEWAY);
-    let body_bytes = match base64::engine::general_purpose::STANDARD.decode(&resp.body) {
-        Ok(bytes) => bytes,
-        Err(e) => {
-            return (
-                StatusCode::BAD_GATEWAY,
-                format!("Malformed tunnel response body: {}", e),
-            )
-                .into_response()
-        }
+    let body = match resp.body {
+        TunnelResponseBody::Full(bytes) => Body::from(bytes),
+        TunnelResponseBody::Stream(stream) => Body::from_stream(stream),
     };
+    owner_response(resp.status, &resp.headers, body)
+}
+
+/// Build the visitor's response from an owner's status and headers,
+/// keeping only end-to-end headers.
+fn owner_response(status: u16, owner_headers: &[(String, String)], body: Body) -> Response {
+    let status = StatusCode::from_u16(status).unwrap_or(StatusCode::BAD_GATEWAY);
 
-    let mut headers = axum::http::HeaderMap::new();
-    for (k, v) in &resp.headers {
+    let mut headers = HeaderMap::new();
+    for (k, v) in owner_headers {
+        if !is_forwardable_response_header(k) {
+            continue;
+        }
         if let Ok(name) = axum::http::header::HeaderName::from_bytes(k.as_bytes()) {
             if let Ok(hv) = axum::http::HeaderValue::from_str(v) {
-                headers.insert(name, hv);
+                headers.append(name, hv);
             }
         }
     }
-
-    (status, headers, body_bytes).into_response()
-}
-
-fn tunnel_read_error_response(id: String, error: &str) -> TunnelResponse {
-    use base64::Engine;
-
-    let mut headers = HashMap::new();
-    headers.insert(
-        "content-type".to_string(),
-        "text/plain; charset=utf-8".to_string(),
-    );
-    TunnelResponse {
-        id,
-        status: StatusCode::BAD_GATEWAY.as_u16(),
-        headers,
-        body: base64::engine::general_purpose::STANDARD
-            .encode(format!("Tunnel owner WebSocket read failed: {error}")),
-    }
+    (status, headers, body).into_response()
 }
 
 fn relay_tunnel_read_error_result(
-    pending: &mut HashMap<String, TunnelResponder>,
+    pending: &mut PendingTunnelRequests,
     key: &str,
     read_error: String,
 ) -> String {
@@ -1294,17 +2089,42 @@ fn relay_tunnel_read_error_result(
         "[TUNNEL] Owner WebSocket read error for key={}: {}",
         key, read_error
     );
-    let pending_ids: Vec<String> = pending.keys().cloned().collect();
-    for id in pending_ids {
-        if let Some(tx) = pending.remove(&id) {
-            let _ = tx.send(tunnel_read_error_response(id, &message));
-        }
-    }
+    fail_pending_tunnel_requests(
+        pending,
+        &format!("Tunnel owner WebSocket read failed: {message}"),
+    );
     message
 }
 
-/// Forward a GET request to the target URL directly and stream the response back.
-async fn proxy_request_direct(target: &str) -> Response {
+/// Pump a visitor body into a bounded channel reqwest can stream from,
+/// cutting it off with an error once it passes the relay limit.
+fn channel_request_body(body: Body) -> ChannelBody {
+    let (tx, rx) = mpsc::channel(4);
+    tokio::spawn(async move {
+        let mut stream = body.into_data_stream();
+        let mut total = 0usize;
+        while let Some(chunk) = stream.next().await {
+            let item = chunk.map_err(std::io::Error::other).and_then(|chunk| {
+                total += chunk.len();
+                if total > MAX_TUNNEL_REQUEST_BODY_BYTES {
+                    Err(std::io::Error::other(
+                        "request body exceeds the relay limit",
+                    ))
+                } else {
+                    Ok(chunk)
+                }
+            });
+            let failed = item.is_err();
+            if tx.send(item).await.is_err() || failed {
+                break;
+            }
+        }
+    });
+    ChannelBody(rx)
+}
+
+/// Forward a request to the target URL directly and stream the response back.
+async fn proxy_request_direct(target: &str, req: ProxiedRequest) -> Response {
     if let Err(e) = is_safe_origin_url(target) {
         eprintln!("[RELAY-SHARE] Blocking direct proxy to disallowed origin: {e}");
         return (
@@ -1329,7 +2149,18 @@ async fn proxy_request_direct(target: &str) -> Response {
                 .into_response();
         }
     };
-    let upstream = match client.get(target).send().await {
+    let method = match reqwest::Method::from_bytes(req.method.as_str().as_bytes()) {
+        Ok(method) => method,
+        Err(e) => return (StatusCode::METHOD_NOT_ALLOWED, e.to_string()).into_response(),
+    };
+    let mut request = client.request(method, target);
+    for (name, value) in tunnel_request_headers(&req.headers) {
+        request = request.header(name, value);
+    }
+    if request_has_body(&req.headers) {
+        request = request.body(reqwest::Body::wrap_stream(channel_request_body(req.body)));
+    }
+    let upstream = match request.send().await {
         Ok(r) => r,
         Err(_) => {
             return (
@@ -1345,20 +2176,15 @@ async fn proxy_request_direct(target: &str) -> Response {
     let status =
         StatusCode::from_u16(upstream.status().as_u16()).unwrap_or(StatusCode::BAD_GATEWAY);
 
-    // Forward relevant headers (convert reqwest HeaderValue -> axum HeaderValue)
-    let mut headers = axum::http::HeaderMap::new();
-    for key in &[
-        "content-type",
-        "content-length",
-        "content-disposition",
-        "cache-control",
-        "etag",
-    ] {
-        if let Some(val) = upstream.headers().get(*key) {
-            if let Ok(name) = axum::http::header::HeaderName::from_bytes(key.as_bytes()) {
-                if let Ok(hv) = axum::http::HeaderValue::from_bytes(val.as_bytes()) {
-                    headers.insert(name, hv);
-                }
+    // Forward end-to-end headers (convert reqwest HeaderValue -> axum HeaderValue)
+    let mut headers = HeaderMap::new();
+    for (key, val) in upstream.headers() {
+        if !is_forwardable_response_header(key.as_str()) {
+            continue;
+        }
+        if let Ok(name) = axum::http::header::HeaderName::from_bytes(key.as_str().as_bytes()) {
+            if let Ok(hv) = axum::http::HeaderValue::from_bytes(val.as_bytes()) {
+                headers.append(name, hv);
             }
         }
     }
@@ -1374,40 +2200,49 @@ async fn proxy_request_direct(target: &str) -> Response {
 // Drive share proxy handlers
 // ---------------------------------------------------------------------------
 
-/// Proxy GET /drive/:token to the sharer's local server.
+/// Proxy /drive/:token (any method) to the sharer's local server.
 async fn proxy_share_root(
     Extension(state): Extension<Arc<RelayShareRegistry>>,
     Extension(tunnel_reg): Extension<Arc<TunnelRegistry>>,
     Path(token): Path<String>,
-    Query(q): Query<ProxyQuery>,
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+    body: Body,
 ) -> Response {
-    if let Err(e) = validate_share_token(&token) {
-        return (StatusCode::BAD_REQUEST, e).into_response();
-    }
-    let reg = match state.lookup(&token).await {
-        Some(r) => r,
-        None => {
-            return (
-                StatusCode::NOT_FOUND,
-                Html(offline_page("Share link not found")),
-            )
-                .into_response()
-        }
-    };
-
-    let qs = build_query_string(&q.params);
-    let path = format!("/drive/{}{}", token, qs);
-    let direct_url = format!("{}{}", reg.origin_url, path);
-    let tunnel_key = format!("share:{}", token);
-    proxy_via_tunnel_or_http(&tunnel_reg, &tunnel_key, &path, &direct_url).await
+    proxy_share(
+        state,
+        tunnel_reg,
+        token,
+        ProxiedRequest::new(method, &uri, headers, body),
+    )
+    .await
 }
 
-/// Proxy GET /drive/:token/*path to the sharer's local server.
+/// Proxy /drive/:token/*path (any method) to the sharer's local server.
 async fn proxy_share_path(
     Extension(state): Extension<Arc<RelayShareRegistry>>,
     Extension(tunnel_reg): Extension<Arc<TunnelRegistry>>,
-    Path((token, subpath)): Path<(String, String)>,
-    Query(q): Query<ProxyQuery>,
+    Path((token, _subpath)): Path<(String, String)>,
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+    body: Body,
+) -> Response {
+    proxy_share(
+        state,
+        tunnel_reg,
+        token,
+        ProxiedRequest::new(method, &uri, headers, body),
+    )
+    .await
+}
+
+async fn proxy_share(
+    state: Arc<RelayShareRegistry>,
+    tunnel_reg: Arc<TunnelRegistry>,
+    token: String,
+    req: ProxiedRequest,
 ) -> Response {
     if let Err(e) = validate_share_token(&token) {
         return (StatusCode::BAD_REQUEST, e).into_response();
@@ -1423,11 +2258,8 @@ async fn proxy_share_path(
         }
     };
 
-    let qs = build_query_string(&q.params);
-    let path = format!("/drive/{}/{}{}", token, subpath, qs);
-    let direct_url = format!("{}{}", reg.origin_url, path);
-    let tunnel_key = format!("share:{}", token);
-    proxy_via_tunnel_or_http(&tunnel_reg, &tunnel_key, &path, &direct_url).await
+    let p2p = state.p2p_route(reg.peer_id.as_deref());
+    proxy_via_tunnel_or_http(&tunnel_reg, p2p, "share", &token, &reg.origin_url, req).await
 }
 
 // ---------------------------------------------------------------------------
@@ -1457,10 +2289,19 @@ async fn register_site(
     if let Err(e) = is_safe_origin_url(&req.origin_url) {
         return (StatusCode::BAD_REQUEST, e).into_response();
     }
+    if let Err(e) = validate_registration_peer_id(req.peer_id.as_deref()) {
+        return (StatusCode::BAD_REQUEST, e).into_response();
+    }
     if req.signature.is_empty() {
         return (StatusCode::UNAUTHORIZED, "signature required").into_response();
     }
-    let payload = register_payload("site", &req.site_id, &owner, &req.origin_url);
+    let payload = register_payload_with_peer(
+        "site",
+        &req.site_id,
+        &owner,
+        &req.origin_url,
+        req.peer_id.as_deref(),
+    );
     if !crate::wallet::verify_signature(&payload, &req.signature, &owner) {
         return (
             StatusCode::UNAUTHORIZED,
@@ -1485,11 +2326,17 @@ async fn register_site(
         Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
     };
     if let Err(e) = preflight_origin_reachable(&origin).await {
-        return (StatusCode::BAD_GATEWAY, e).into_response();
+        if state.p2p_route(req.peer_id.as_deref()).is_none() {
+            return (StatusCode::BAD_GATEWAY, e).into_response();
+        }
+        println!(
+            "[RELAY-SITE] Origin unreachable for site={}; serving over libp2p: {}",
+            req.site_id, e
+        );
     }
     println!(
-        "[RELAY-SITE] Registering site={} origin={} (raw={}) owner={}",
-        req.site_id, origin, req.origin_url, owner
+        "[RELAY-SITE] Registering site={} origin={} (raw={}) owner={} peer={:?}",
+        req.site_id, origin, req.origin_url, owner, req.peer_id
     );
     state
         .register_site(SiteRegistration {
@@ -1497,6 +2344,7 @@ async fn register_site(
             origin_url: origin,
             owner_wallet: owner,
             registered_at: now_secs(),
+            peer_id: req.peer_id,
         })
         .await;
     (StatusCode::OK, "Registered").into_response()
@@ -1563,32 +2411,49 @@ async fn proxy_site_redirect(
         .into_response()
 }
 
-/// Proxy GET /sites/:site_id/ to the owner's local server.
+/// Proxy /sites/:site_id/ (any method) to the owner's local server.
 async fn proxy_site_root(
     Extension(state): Extension<Arc<RelayShareRegistry>>,
     Extension(tunnel_reg): Extension<Arc<TunnelRegistry>>,
     Path(site_id): Path<String>,
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+    body: Body,
 ) -> Response {
-    if let Err(e) = validate_site_id(&site_id) {
-        return (StatusCode::BAD_REQUEST, e).into_response();
-    }
-    let reg = match state.lookup_site(&site_id).await {
-        Some(r) => r,
-        None => {
-            return (StatusCode::NOT_FOUND, Html(offline_page("Site not found"))).into_response()
-        }
-    };
-    let path = format!("/sites/{}/", site_id);
-    let direct_url = format!("{}{}", reg.origin_url, path);
-    let tunnel_key = format!("site:{}", site_id);
-    proxy_via_tunnel_or_http(&tunnel_reg, &tunnel_key, &path, &direct_url).await
+    proxy_site(
+        state,
+        tunnel_reg,
+        site_id,
+        ProxiedRequest::new(method, &uri, headers, body),
+    )
+    .await
 }
 
-/// Proxy GET /sites/:site_id/*path to the owner's local server.
+/// Proxy /sites/:site_id/*path (any method) to the owner's local server.
 async fn proxy_site_path(
     Extension(state): Extension<Arc<RelayShareRegistry>>,
     Extension(tunnel_reg): Extension<Arc<TunnelRegistry>>,
-    Path((site_id, subpath)): Path<(String, String)>,
+    Path((site_id, _subpath)): Path<(String, String)>,
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+    body: Body,
+) -> Response {
+    proxy_site(
+        state,
+        tunnel_reg,
+        site_id,
+        ProxiedRequest::new(method, &uri, headers, body),
+    )
+    .await
+}
+
+async fn proxy_site(
+    state: Arc<RelayShareRegistry>,
+    tunnel_reg: Arc<TunnelRegistry>,
+    site_id: String,
+    req: ProxiedRequest,
 ) -> Response {
     if let Err(e) = validate_site_id(&site_id) {
         return (StatusCode::BAD_REQUEST, e).into_response();
@@ -1599,10 +2464,9 @@ async fn proxy_site_path(
             return (StatusCode::NOT_FOUND, Html(offline_page("Site not found"))).into_response()
         }
     };
-    let path = format!("/sites/{}/{}", site_id, subpath);
-    let direct_url = format!("{}{}", reg.origin_url, path);
-    let tunnel_key = format!("site:{}", site_id);
-    proxy_via_tunnel_or_http(&tunnel_reg, &tunnel_key, &path, &direct_url).await
+
+    let p2p = state.p2p_route(reg.peer_id.as_deref());
+    proxy_via_tunnel_or_http(&tunnel_reg, p2p, "site", &site_id, &reg.origin_url, req).await
 }
 
 // ---------------------------------------------------------------------------
@@ -1642,8 +2506,8 @@ pub fn relay_share_routes(
         .route("/api/drive/relay-register", post(register_share))
         .route("/api/drive/relay-register/:token", delete(unregister_share))
         // Drive share proxy routes
-        .route("/drive/:token", get(proxy_share_root))
-        .route("/drive/:token/*path", get(proxy_share_path))
+        .route("/drive/:token", any(proxy_share_root))
+        .route("/drive/:token/*path", any(proxy_share_path))
         // Site registration API
         .route("/api/sites/relay-register", post(register_site))
         .route(
@@ -1652,8 +2516,8 @@ pub fn relay_share_routes(
         )
         // Site proxy routes
         .route("/sites/:site_id", get(proxy_site_redirect))
-        .route("/sites/:site_id/", get(proxy_site_root))
-        .route("/sites/:site_id/*path", get(proxy_site_path))
+        .route("/sites/:site_id/", any(proxy_site_root))
+        .route("/sites/:site_id/*path", any(proxy_site_path))
         // WebSocket tunnel
         .route("/api/tunnel/ws", get(tunnel_ws_handler))
         .layer(Extension(state))
@@ -1974,6 +2838,7 @@ mod tests {
             origin_url: origin_url.to_string(),
             owner_wallet,
             signature,
+            peer_id: None,
         }
     }
 
@@ -1986,6 +2851,7 @@ mod tests {
             origin_url: origin_url.to_string(),
             owner_wallet,
             signature,
+            peer_id: None,
         }
     }
 
@@ -2068,17 +2934,25 @@ mod tests {
         assert!(body.contains("private"));
     }
 
-    #[tokio::test]
-    async fn tunnel_response_to_axum_decodes_valid_body() {
-        use base64::Engine;
+    fn full_body_text(resp: TunnelResponse) -> String {
+        match resp.body {
+            TunnelResponseBody::Full(bytes) => String::from_utf8(bytes.to_vec()).unwrap(),
+            TunnelResponseBody::Stream(_) => panic!("expected a relay-generated body"),
+        }
+    }
 
-        let mut headers = HashMap::new();
-        headers.insert("content-type".to_string(), "text/plain".to_string());
+    #[tokio::test]
+    async fn tunnel_response_to_axum_passes_full_body_and_end_to_end_headers() {
         let response = tunnel_response_to_axum(TunnelResponse {
-            id: "req-1".to_string(),
             status: StatusCode::CREATED.as_u16(),
-            headers,
-            body: base64::engine::general_purpose::STANDARD.encode("hello tunnel"),
+            headers: vec![
+                ("content-type".to_string(), "text/plain".to_string()),
+                ("set-cookie".to_string(), "a=1".to_string()),
+                ("set-cookie".to_string(), "b=2".to_string()),
+                ("connection".to_string(), "close".to_string()),
+                ("transfer-encoding".to_string(), "chunked".to_string()),
+            ],
+            body: TunnelResponseBody::Full(Bytes::from_static(b"hello tunnel")),
         });
 
         assert_eq!(response.status(), StatusCode::CREATED);
@@ -2091,6 +2965,15 @@ mod tests {
                 .unwrap(),
             "text/plain"
         );
+        let cookies: Vec<_> = response
+            .headers()
+            .get_all(axum::http::header::SET_COOKIE)
+            .iter()
+            .map(|v| v.to_str().unwrap().to_string())
+            .collect();
+        assert_eq!(cookies, vec!["a=1", "b=2"]);
+        assert!(response.headers().get("connection").is_none());
+        assert!(response.headers().get("transfer-encoding").is_none());
         let body = axum::body::to_bytes(response.into_body(), usize::MAX)
             .await
             .unwrap();
@@ -2098,29 +2981,76 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn tunnel_response_to_axum_rejects_malformed_body() {
+    async fn tunnel_response_to_axum_streams_body_and_grants_window() {
+        let (chunk_tx, chunks) = mpsc::channel(TUNNEL_BODY_CHANNEL_CAPACITY);
+        let (control, mut control_rx) = mpsc::unbounded_channel();
         let response = tunnel_response_to_axum(TunnelResponse {
-            id: "req-2".to_string(),
             status: StatusCode::OK.as_u16(),
-            headers: HashMap::new(),
-            body: "not base64***".to_string(),
+            headers: Vec::new(),
+            body: TunnelResponseBody::Stream(TunnelBodyStream {
+                id: "req-2".to_string(),
+                chunks,
+                control,
+                finished: false,
+            }),
         });
+        chunk_tx
+            .try_send(Ok(Bytes::from_static(b"hello ")))
+            .unwrap();
+        chunk_tx
+            .try_send(Ok(Bytes::from_static(b"stream")))
+            .unwrap();
+        drop(chunk_tx);
 
-        assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
         let body = axum::body::to_bytes(response.into_body(), usize::MAX)
             .await
             .unwrap();
-        let body = String::from_utf8(body.to_vec()).unwrap();
-        assert!(body.contains("Malformed tunnel response body"));
+        assert_eq!(body.as_ref(), b"hello stream");
+
+        let mut frames = Vec::new();
+        while let Ok(frame) = control_rx.try_recv() {
+            frames.push(frame);
+        }
+        let grant = TunnelRelayFrame::Window {
+            id: "req-2".to_string(),
+            credits: 1,
+        };
+        assert_eq!(frames, vec![grant.clone(), grant]);
+    }
+
+    #[tokio::test]
+    async fn tunnel_response_to_axum_aborts_body_on_stream_error() {
+        let (chunk_tx, chunks) = mpsc::channel(TUNNEL_BODY_CHANNEL_CAPACITY);
+        let (control, _control_rx) = mpsc::unbounded_channel();
+        let response = tunnel_response_to_axum(TunnelResponse {
+            status: StatusCode::OK.as_u16(),
+            headers: Vec::new(),
+            body: TunnelResponseBody::Stream(TunnelBodyStream {
+                id: "req-3".to_string(),
+                chunks,
+                control,
+                finished: false,
+            }),
+        });
+        chunk_tx
+            .try_send(Ok(Bytes::from_static(b"partial")))
+            .unwrap();
+        chunk_tx
+            .try_send(Err("owner disconnected".to_string()))
+            .unwrap();
+
+        let err = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .expect_err("a failed stream must not look like a complete body");
+        assert!(err.to_string().contains("owner disconnected"));
     }
 
     #[tokio::test]
     async fn tunnel_response_to_axum_preserves_empty_body() {
         let response = tunnel_response_to_axum(TunnelResponse {
-            id: "req-3".to_string(),
             status: StatusCode::NO_CONTENT.as_u16(),
-            headers: HashMap::new(),
-            body: String::new(),
+            headers: Vec::new(),
+            body: TunnelResponseBody::Full(Bytes::new()),
         });
 
         assert_eq!(response.status(), StatusCode::NO_CONTENT);
@@ -2130,64 +3060,234 @@ mod tests {
         assert!(body.is_empty());
     }
 
+    #[test]
+    fn dropping_unfinished_tunnel_body_cancels_request() {
+        let (_chunk_tx, chunks) = mpsc::channel(TUNNEL_BODY_CHANNEL_CAPACITY);
+        let (control, mut control_rx) = mpsc::unbounded_channel();
+        drop(TunnelBodyStream {
+            id: "req-5".to_string(),
+            chunks,
+            control,
+            finished: false,
+        });
+
+        assert_eq!(
+            control_rx.try_recv().unwrap(),
+            TunnelRelayFrame::Cancel {
+                id: "req-5".to_string()
+            }
+        );
+    }
+
     // -----------------------------------------------------------------------
-    // build_query_string
+    // Proxied request shaping
     // -----------------------------------------------------------------------
 
     #[test]
-    fn test_build_query_string_empty() {
-        let params: HashMap<String, String> = HashMap::new();
-        assert_eq!(build_query_string(&params), "");
+    fn request_path_and_query_keeps_raw_encoding() {
+        let uri: Uri = "/drive/tok/My%20File.txt?download=1&name=a%26b"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            request_path_and_query(&uri),
+            "/drive/tok/My%20File.txt?download=1&name=a%26b"
+        );
+
+        let uri: Uri = "/sites/site-1/".parse().unwrap();
+        assert_eq!(request_path_and_query(&uri), "/sites/site-1/");
     }
 
     #[test]
-    fn test_build_query_string_single_param() {
-        let mut params = HashMap::new();
-        params.insert("page".to_string(), "1".to_string());
-        let qs = build_query_string(&params);
-        assert_eq!(qs, "?page=1");
+    fn tunnel_request_headers_drop_hop_by_hop_and_keep_repeats() {
+        let mut headers = HeaderMap::new();
+        headers.insert("host", "relay.example".parse().unwrap());
+        headers.insert("content-length", "5".parse().unwrap());
+        headers.insert("connection", "keep-alive".parse().unwrap());
+        headers.insert("range", "bytes=0-99".parse().unwrap());
+        headers.insert("if-none-match", "\"abc\"".parse().unwrap());
+        headers.append("cookie", "a=1".parse().unwrap());
+        headers.append("cookie", "b=2".parse().unwrap());
+
+        let forwarded = tunnel_request_headers(&headers);
+
+        assert!(forwarded.contains(&("range".to_string(), "bytes=0-99".to_string())));
+        assert!(forwarded.contains(&("if-none-match".to_string(), "\"abc\"".to_string())));
+        assert!(forwarded.contains(&("cookie".to_string(), "a=1".to_string())));
+        assert!(forwarded.contains(&("cookie".to_string(), "b=2".to_string())));
+        assert!(!forwarded
+            .iter()
+            .any(|(name, _)| name == "host" || name == "content-length" || name == "connection"));
     }
 
     #[test]
-    fn test_build_query_string_multiple_params() {
-        let mut params = HashMap::new();
-        params.insert("a".to_string(), "1".to_string());
-        params.insert("b".to_string(), "2".to_string());
-        let qs = build_query_string(&params);
-        // HashMap iteration order is not guaranteed, so check both possibilities
-        assert!(qs == "?a=1&b=2" || qs == "?b=2&a=1");
+    fn request_has_body_follows_length_and_chunking() {
+        let mut headers = HeaderMap::new();
+        assert!(!request_has_body(&headers));
+        headers.insert("content-length", "0".parse().unwrap());
+        assert!(!request_has_body(&headers));
+        headers.insert("content-length", "12".parse().unwrap());
+        assert!(request_has_body(&headers));
+
+        let mut chunked = HeaderMap::new();
+        chunked.insert("transfer-encoding", "chunked".parse().unwrap());
+        assert!(request_has_body(&chunked));
+    }
+
+    #[test]
+    fn connect_and_trace_are_not_proxied() {
+        assert!(validate_proxied_method(&Method::GET).is_ok());
+        assert!(validate_proxied_method(&Method::POST).is_ok());
+        assert!(validate_proxied_method(&Method::DELETE).is_ok());
+        assert!(validate_proxied_method(&Method::CONNECT).is_err());
+        assert!(validate_proxied_method(&Method::TRACE).is_err());
+    }
+
+    #[test]
+    fn scoped_origin_url_accepts_paths_inside_the_resource() {
+        let url = scoped_origin_url("http://127.0.0.1:9419", "share", "tok", "/drive/tok").unwrap();
+        assert_eq!(url.as_str(), "http://127.0.0.1:9419/drive/tok");
+
+        let url = scoped_origin_url(
+            "http://127.0.0.1:9419",
+            "share",
+            "tok",
+            "/drive/tok/a%20b.txt?download=1",
+        )
+        .unwrap();
+        assert_eq!(
+            url.as_str(),
+            "http://127.0.0.1:9419/drive/tok/a%20b.txt?download=1"
+        );
+
+        let url =
+            scoped_origin_url("http://127.0.0.1:9419", "site", "site-1", "/sites/site-1/").unwrap();
+        assert_eq!(url.path(), "/sites/site-1/");
+    }
+
+    #[test]
+    fn scoped_origin_url_rejects_escapes() {
+        let origin = "http://127.0.0.1:9419";
+        for path in [
+            "/drive/tok/../../api/drive/items",
+            "/drive/tok/%2e%2e/other",
+            "/drive/token-two/file",
+            "/drive/tokextra",
+            "/api/drive/items",
+            "//evil.example/drive/tok",
+            "@evil.example/drive/tok",
+            "http://evil.example/drive/tok",
+            "/\\evil.example/drive/tok",
+        ] {
+            assert!(
+                scoped_origin_url(origin, "share", "tok", path).is_err(),
+                "{path} should be rejected"
+            );
+        }
+        assert!(scoped_origin_url(origin, "site", "tok", "/drive/tok").is_err());
+        assert!(scoped_origin_url(origin, "blob", "tok", "/drive/tok").is_err());
     }
 
     // -----------------------------------------------------------------------
-    // WebSocket tunnel response frames
+    // WebSocket tunnel frames
     // -----------------------------------------------------------------------
 
+    #[test]
+    fn tunnel_chunk_frames_roundtrip() {
+        let frame = encode_tunnel_chunk("req-1", b"\x00binary\xff").unwrap();
+        assert_eq!(frame[0] as usize, "req-1".len());
+
+        let (id, payload) = decode_tunnel_chunk(&frame).unwrap();
+        assert_eq!(id, "req-1");
+        assert_eq!(payload, b"\x00binary\xff");
+
+        let empty = encode_tunnel_chunk("r", b"").unwrap();
+        let (id, payload) = decode_tunnel_chunk(&empty).unwrap();
+        assert_eq!(id, "r");
+        assert!(payload.is_empty());
+    }
+
+    #[test]
+    fn tunnel_chunk_frames_reject_bad_ids() {
+        assert!(encode_tunnel_chunk("", b"x").is_err());
+        assert!(encode_tunnel_chunk(&"a".repeat(256), b"x").is_err());
+        assert!(decode_tunnel_chunk(b"").is_err());
+        assert!(decode_tunnel_chunk(&[0, b'x']).is_err());
+        assert!(decode_tunnel_chunk(&[9, b'a', b'b']).is_err());
+        assert!(decode_tunnel_chunk(&[2, 0xff, 0xfe, b'x']).is_err());
+    }
+
+    #[test]
+    fn tunnel_frames_use_tagged_wire_format() {
+        let frame = TunnelRelayFrame::Request {
+            id: "req-1".to_string(),
+            method: "POST".to_string(),
+            path: "/drive/tok/upload".to_string(),
+            headers: vec![("cookie".to_string(), "a=1".to_string())],
+            has_body: true,
+        };
+        let json: serde_json::Value = serde_json::to_value(&frame).unwrap();
+        assert_eq!(json["type"], "request");
+        assert_eq!(json["hasBody"], true);
+        assert_eq!(json["headers"][0][0], "cookie");
+
+        let end = serde_json::to_value(TunnelRelayFrame::RequestEnd {
+            id: "req-1".to_string(),
+        })
+        .unwrap();
+        assert_eq!(end["type"], "request-end");
+
+        let owner: TunnelOwnerFrame =
+            serde_json::from_str(r#"{"type":"response-end","id":"req-1"}"#).unwrap();
+        assert_eq!(
+            owner,
+            TunnelOwnerFrame::ResponseEnd {
+                id: "req-1".to_string()
+            }
+        );
+    }
+
     fn pending_tunnel_response(
         id: &str,
     ) -> (
-        HashMap<String, TunnelResponder>,
+        PendingTunnelRequests,
         tokio::sync::oneshot::Receiver<TunnelResponse>,
     ) {
         let (tx, rx) = tokio::sync::oneshot::channel();
         let mut pending = HashMap::new();
-        pending.insert(id.to_string(), tx);
+        pending.insert(id.to_string(), PendingTunnelRequest::AwaitingHead(tx));
         (pending, rx)
     }
 
+    fn tunnel_control() -> (
+        mpsc::UnboundedSender<TunnelRelayFrame>,
+        mpsc::UnboundedReceiver<TunnelRelayFrame>,
+    ) {
+        mpsc::unbounded_channel()
+    }
+
+    fn response_head_frame(id: &str, status: u16) -> String {
+        serde_json::to_string(&TunnelOwnerFrame::Response {
+            id: id.to_string(),
+            status,
+            headers: vec![("content-type".to_string(), "text/plain".to_string())],
+        })
+        .unwrap()
+    }
+
+    fn streaming_body(resp: TunnelResponse) -> TunnelBodyStream {
+        match resp.body {
+            TunnelResponseBody::Stream(stream) => stream,
+            TunnelResponseBody::Full(_) => panic!("expected a streamed body"),
+        }
+    }
+
     #[test]
     fn tunnel_response_frame_routes_valid_response() {
         let (mut pending, mut rx) = pending_tunnel_response("req-1");
-        let mut headers = HashMap::new();
-        headers.insert("content-type".to_string(), "text/plain".to_string());
-        let frame = serde_json::to_string(&TunnelResponse {
-            id: "req-1".to_string(),
-            status: 204,
-            headers,
-            body: "".to_string(),
-        })
-        .unwrap();
+        let (control, _control_rx) = tunnel_control();
 
-        let outcome = route_tunnel_response_frame(&mut pending, &frame);
+        let outcome =
+            route_tunnel_response_frame(&mut pending, &control, &response_head_frame("req-1", 204));
 
         assert_eq!(
             outcome,
@@ -2195,28 +3295,29 @@ mod tests {
                 id: "req-1".to_string()
             }
         );
-        assert!(pending.is_empty());
+        assert!(matches!(
+            pending.get("req-1"),
+            Some(PendingTunnelRequest::Streaming(_))
+        ));
         let resp = rx.try_recv().unwrap();
-        assert_eq!(resp.id, "req-1");
         assert_eq!(resp.status, 204);
         assert_eq!(
-            resp.headers.get("content-type").map(String::as_str),
-            Some("text/plain")
+            resp.headers,
+            vec![("content-type".to_string(), "text/plain".to_string())]
         );
+        assert!(matches!(resp.body, TunnelResponseBody::Stream(_)));
     }
 
     #[test]
     fn tunnel_response_frame_reports_unknown_response_ids() {
         let (mut pending, mut rx) = pending_tunnel_response("req-pending");
-        let frame = serde_json::to_string(&TunnelResponse {
-            id: "req-unknown".to_string(),
-            status: 200,
-            headers: HashMap::new(),
-            body: "".to_string(),
-        })
-        .unwrap();
+        let (control, _control_rx) = tunnel_control();
 
-        let outcome = route_tunnel_response_frame(&mut pending, &frame);
+        let outcome = route_tunnel_response_frame(
+            &mut pending,
+            &control,
+            &response_head_frame("req-unknown", 200),
+        );
 
         assert_eq!(
             outcome,
@@ -2228,14 +3329,155 @@ mod tests {
         assert!(rx.try_recv().is_err());
     }
 
+    #[tokio::test]
+    async fn tunnel_chunk_frames_stream_until_response_end() {
+        let (mut pending, mut rx) = pending_tunnel_response("req-1");
+        let (control, _control_rx) = tunnel_control();
+        route_tunnel_response_frame(&mut pending, &control, &response_head_frame("req-1", 200));
+        let mut body = streaming_body(rx.try_recv().unwrap());
+
+        for piece in [&b"hello "[..], &b"world"[..]] {
+            let frame = encode_tunnel_chunk("req-1", piece).unwrap();
+            assert_eq!(
+                route_tunnel_chunk_frame(&mut pending, &control, &frame),
+                TunnelResponseFrameOutcome::Delivered {
+                    id: "req-1".to_string()
+                }
+            );
+        }
+        let end = serde_json::to_string(&TunnelOwnerFrame::ResponseEnd {
+            id: "req-1".to_string(),
+        })
+        .unwrap();
+        assert_eq!(
+            route_tunnel_response_frame(&mut pending, &control, &end),
+            TunnelResponseFrameOutcome::Delivered {
+                id: "req-1".to_string()
+            }
+        );
+        assert!(pending.is_empty());
+
+        let mut received = Vec::new();
+        while let Some(chunk) = body.next().await {
+            received.extend_from_slice(&chunk.unwrap());
+        }
+        assert_eq!(received, b"hello world");
+    }
+
+    #[tokio::test]
+    async fn tunnel_chunk_frames_beyond_the_window_fail_the_stream() {
+        let (mut pending, mut rx) = pending_tunnel_response("req-1");
+        let (control, mut control_rx) = tunnel_control();
+        route_tunnel_response_frame(&mut pending, &control, &response_head_frame("req-1", 200));
+        let mut body = streaming_body(rx.try_recv().unwrap());
+
+        let frame = encode_tunnel_chunk("req-1", b"x").unwrap();
+        for _ in 0..TUNNEL_WINDOW_CHUNKS {
+            assert!(matches!(
+                route_tunnel_chunk_frame(&mut pending, &control, &frame),
+                TunnelResponseFrameOutcome::Delivered { .. }
+            ));
+        }
+        let outcome = route_tunnel_chunk_frame(&mut pending, &control, &frame);
+
+        assert!(matches!(
+            outcome,
+            TunnelResponseFrameOutcome::ProtocolViolation { ref error, .. }
+                if error.contains("window")
+        ));
+        assert!(pending.is_empty());
+        assert_eq!(
+            control_rx.try_recv().unwrap(),
+            TunnelRelayFrame::Cancel {
+                id: "req-1".to_string()
+            }
+        );
+        for _ in 0..TUNNEL_WINDOW_CHUNKS {
+            assert!(body.next().await.unwrap().is_ok());
+        }
+        assert!(body.next().await.unwrap().is_err());
+    }
+
     #[test]
-    fn malformed_tunnel_response_frame_fails_recovered_pending_request() {
-        use base64::Engine;
+    fn tunnel_chunk_frame_before_response_head_is_a_protocol_violation() {
+        let (mut pending, mut rx) = pending_tunnel_response("req-1");
+        let (control, _control_rx) = tunnel_control();
+        let frame = encode_tunnel_chunk("req-1", b"early").unwrap();
 
+        let outcome = route_tunnel_chunk_frame(&mut pending, &control, &frame);
+
+        assert!(matches!(
+            outcome,
+            TunnelResponseFrameOutcome::ProtocolViolation { .. }
+        ));
+        let resp = rx.try_recv().unwrap();
+        assert_eq!(resp.status, StatusCode::BAD_GATEWAY.as_u16());
+        assert!(full_body_text(resp).contains("before the response head"));
+    }
+
+    #[test]
+    fn tunnel_chunk_frame_without_valid_header_is_unrecoverable() {
+        let (mut pending, _rx) = pending_tunnel_response("req-1");
+        let (control, _control_rx) = tunnel_control();
+
+        let outcome = route_tunnel_chunk_frame(&mut pending, &control, &[40, b'x']);
+
+        assert!(matches!(
+            outcome,
+            TunnelResponseFrameOutcome::MalformedUnrecoverable { .. }
+        ));
+        assert!(pending.contains_key("req-1"));
+    }
+
+    #[test]
+    fn tunnel_owner_error_frame_fails_pending_request_with_502() {
+        let (mut pending, mut rx) = pending_tunnel_response("req-1");
+        let (control, _control_rx) = tunnel_control();
+        let frame = serde_json::to_string(&TunnelOwnerFrame::Error {
+            id: "req-1".to_string(),
+            message: "path outside share".to_string(),
+        })
+        .unwrap();
+
+        let outcome = route_tunnel_response_frame(&mut pending, &control, &frame);
+
+        assert_eq!(
+            outcome,
+            TunnelResponseFrameOutcome::OwnerError {
+                id: "req-1".to_string(),
+                error: "path outside share".to_string()
+            }
+        );
+        let resp = rx.try_recv().unwrap();
+        assert_eq!(resp.status, StatusCode::BAD_GATEWAY.as_u16());
+        assert!(full_body_text(resp).contains("path outside share"));
+    }
+
+    #[tokio::test]
+    async fn tunnel_owner_error_frame_aborts_streaming_body() {
+        let (mut pending, mut rx) = pending_tunnel_response("req-1");
+        let (control, _control_rx) = tunnel_control();
+        route_tunnel_response_frame(&mut pending, &control, &response_head_frame("req-1", 200));
+        let mut body = streaming_body(rx.try_recv().unwrap());
+        let frame = serde_json::to_string(&TunnelOwnerFrame::Error {
+            id: "req-1".to_string(),
+            message: "disk read failed".to_string(),
+        })
+        .unwrap();
+
+        route_tunnel_response_frame(&mut pending, &control, &frame);
+
+        let err = body.next().await.unwrap().unwrap_err();
+        assert!(err.to_string().contains("disk read failed"));
+    }
+
+    #[test]
+    fn malformed_tunnel_response_frame_fails_recovered_pending_request() {
         let (mut pending, mut rx) = pending_tunnel_response("req-2");
-        let frame = r#"{"id":"req-2","status":"not-a-number","body":""}"#;
+        let (control, _control_rx) = tunnel_control();
+        let frame = r#"{"type":"response","id":"req-2","status":"not-a-number"}"#;
 
-        let outcome = route_tunnel_response_frame(&mut pending, frame);
+        let outcome = route_tunnel_response_frame(&mut pending, &control, frame);
 
         match outcome {
             TunnelResponseFrameOutcome::MalformedDelivered { id, error } => {
@@ -2246,24 +3488,27 @@ mod tests {
         }
         assert!(pending.is_empty());
         let resp = rx.try_recv().unwrap();
-        assert_eq!(resp.id, "req-2");
         assert_eq!(resp.status, StatusCode::BAD_GATEWAY.as_u16());
         assert_eq!(
-            resp.headers.get("content-type").map(String::as_str),
-            Some("text/plain; charset=utf-8")
+            resp.headers,
+            vec![(
+                "content-type".to_string(),
+                "text/plain; charset=utf-8".to_string()
+            )]
         );
-        let body = base64::engine::general_purpose::STANDARD
-            .decode(resp.body)
-            .unwrap();
-        let body = String::from_utf8(body).unwrap();
-        assert!(body.contains("Malformed tunnel response frame from owner"));
+        assert!(full_body_text(resp).contains("Malformed tunnel response frame from owner"));
     }
 
     #[test]
     fn malformed_tunnel_response_frame_without_recoverable_id_keeps_pending_request() {
         let (mut pending, mut rx) = pending_tunnel_response("req-3");
+        let (control, _control_rx) = tunnel_control();
 
-        let outcome = route_tunnel_response_frame(&mut pending, r#"{"id":"req-3","status":"#);
+        let outcome = route_tunnel_response_frame(
+            &mut pending,
+            &control,
+            r#"{"type":"response","id":"req-3","status":"#,
+        );
 
         match outcome {
             TunnelResponseFrameOutcome::MalformedUnrecoverable { error } => {
@@ -2275,6 +3520,167 @@ mod tests {
         assert!(rx.try_recv().is_err());
     }
 
+    /// Next tunnel frame the scripted owner receives, skipping keepalives.
+    async fn next_owner_frame<S>(owner: &mut S) -> tokio_tungstenite::tungstenite::Message
+    where
+        S: futures_util::Stream<
+                Item = Result<
+                    tokio_tungstenite::tungstenite::Message,
+                    tokio_tungstenite::tungstenite::Error,
+                >,
+            > + Unpin,
+    {
+        loop {
+            match owner.next().await.unwrap().unwrap() {
+                tokio_tungstenite::tungstenite::Message::Ping(_)
+                | tokio_tungstenite::tungstenite::Message::Pong(_) => continue,
+                msg => return msg,
+            }
+        }
+    }
+
+    /// Drive the real relay router with a scripted owner on the other end of
+    /// the tunnel WebSocket.
+    #[tokio::test]
+    async fn tunnel_forwards_method_headers_body_and_streams_response() {
+        use tokio_tungstenite::tungstenite::Message as WsMessage;
+
+        let dir = tempfile::tempdir().unwrap();
+        let registry = Arc::new(RelayShareRegistry::new(dir.path().to_path_buf()));
+        registry
+            .register(ShareRegistration {
+                token: "tok".to_string(),
+                origin_url: "http://127.0.0.1:9".to_string(),
+                owner_wallet: "0xWALLET".to_string(),
+                registered_at: now_secs(),
+                peer_id: None,
+            })
+            .await;
+        let app = relay_share_routes(registry, Arc::new(TunnelRegistry::new()));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(
+                listener,
+                app.into_make_service_with_connect_info::<SocketAddr>(),
+            )
+            .await
+            .unwrap();
+        });
+
+        let (mut owner, _) = tokio_tungstenite::connect_async(format!(
+            "ws://{addr}/api/tunnel/ws?type=share&id=tok"
+        ))
+        .await
+        .unwrap();
+        // Let the relay register the tunnel before the visitor arrives.
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+        let upload = vec![7u8; TUNNEL_CHUNK_BYTES + 10];
+        let visitor = tokio::spawn({
+            let upload = upload.clone();
+            async move {
+                reqwest::Client::new()
+                    .post(format!("http://{addr}/drive/tok/upload?x=1"))
+                    .header("cookie", "session=abc")
+                    .body(upload)
+                    .send()
+                    .await
+                    .unwrap()
+            }
+        });
+
+        let (id, path, headers) = match next_owner_frame(&mut owner).await {
+            WsMessage::Text(text) => match serde_json::from_str(&text).unwrap() {
+                TunnelRelayFrame::Request {
+                    id,
+                    method,
+                    path,
+                    headers,
+                    has_body,
+                } => {
+                    assert_eq!(method, "POST");
+                    assert!(has_body);
+                    (id, path, headers)
+                }
+                other => panic!("unexpected frame: {other:?}"),
+            },
+            other => panic!("unexpected message: {other:?}"),
+        };
+        assert_eq!(path, "/drive/tok/upload?x=1");
+        assert!(headers.contains(&("cookie".to_string(), "session=abc".to_string())));
+
+        let mut received = Vec::new();
+        loop {
+            match next_owner_frame(&mut owner).await {
+                WsMessage::Binary(frame) => {
+                    let (chunk_id, payload) = decode_tunnel_chunk(&frame).unwrap();
+                    assert_eq!(chunk_id, id);
+                    assert!(payload.len() <= TUNNEL_CHUNK_BYTES);
+                    received.extend_from_slice(payload);
+                }
+                WsMessage::Text(text) => {
+                    assert_eq!(
+                        serde_json::from_str::<TunnelRelayFrame>(&text).unwrap(),
+                        TunnelRelayFrame::RequestEnd { id: id.clone() }
+                    );
+                    break;
+                }
+                other => panic!("unexpected message: {other:?}"),
+            }
+        }
+        assert_eq!(received, upload);
+
+        let head = TunnelOwnerFrame::Response {
+            id: id.clone(),
+            status: 201,
+            headers: vec![("x-owner".to_string(), "yes".to_string())],
+        };
+        owner
+            .send(WsMessage::Text(serde_json::to_string(&head).unwrap()))
+            .await
+            .unwrap();
+        // Use the whole window, then wait for a credit before sending more.
+        for _ in 0..TUNNEL_WINDOW_CHUNKS {
+            owner
+                .send(WsMessage::Binary(encode_tunnel_chunk(&id, b"ab").unwrap()))
+                .await
+                .unwrap();
+        }
+        let response = visitor.await.unwrap();
+        assert_eq!(response.status().as_u16(), 201);
+        assert_eq!(response.headers()["x-owner"], "yes");
+
+        let body_task = tokio::spawn(async move { response.bytes().await.unwrap() });
+        loop {
+            match next_owner_frame(&mut owner).await {
+                WsMessage::Text(text) => {
+                    if let TunnelRelayFrame::Window { id: grant_id, .. } =
+                        serde_json::from_str(&text).unwrap()
+                    {
+                        assert_eq!(grant_id, id);
+                        break;
+                    }
+                }
+                other => panic!("unexpected message: {other:?}"),
+            }
+        }
+        owner
+            .send(WsMessage::Binary(encode_tunnel_chunk(&id, b"!").unwrap()))
+            .await
+            .unwrap();
+        let end = TunnelOwnerFrame::ResponseEnd { id: id.clone() };
+        owner
+            .send(WsMessage::Text(serde_json::to_string(&end).unwrap()))
+            .await
+            .unwrap();
+
+        let body = body_task.await.unwrap();
+        let mut expected = b"ab".repeat(TUNNEL_WINDOW_CHUNKS as usize);
+        expected.push(b'!');
+        assert_eq!(body.as_ref(), expected.as_slice());
+    }
+
     // -----------------------------------------------------------------------
     // now_secs
     // -----------------------------------------------------------------------
@@ -2342,8 +3748,6 @@ mod tests {
 
     #[test]
     fn relay_tunnel_request_send_result_fails_pending_on_error() {
-        use base64::Engine;
-
         let (mut pending, mut rx) = pending_tunnel_response("req-fail");
 
         let message = relay_tunnel_request_send_result(
@@ -2360,16 +3764,8 @@ mod tests {
         assert!(pending.is_empty());
 
         let resp = rx.try_recv().unwrap();
-        assert_eq!(resp.id, "req-fail");
         assert_eq!(resp.status, StatusCode::BAD_GATEWAY.as_u16());
-        assert_eq!(
-            resp.headers.get("content-type").map(String::as_str),
-            Some("text/plain; charset=utf-8")
-        );
-        let body = base64::engine::general_purpose::STANDARD
-            .decode(resp.body)
-            .unwrap();
-        let body = String::from_utf8(body).unwrap();
+        let body = full_body_text(resp);
         assert!(body.contains("Failed to send tunnel request to owner"));
         assert!(body.contains("connection reset"));
     }
@@ -2384,6 +3780,7 @@ mod tests {
             origin_url: "http://10.0.0.1:9419".to_string(),
             owner_wallet: "0xWALLET".to_string(),
             registered_at: now_secs(),
+            peer_id: None,
         };
         registry.register(reg).await;
 
@@ -2398,15 +3795,13 @@ mod tests {
     #[test]
     fn relay_tunnel_read_valid_response_routes_pending_request() {
         let (mut pending, mut rx) = pending_tunnel_response("req-read-ok");
-        let frame = serde_json::to_string(&TunnelResponse {
-            id: "req-read-ok".to_string(),
-            status: 200,
-            headers: HashMap::new(),
-            body: "".to_string(),
-        })
-        .unwrap();
+        let (control, _control_rx) = tunnel_control();
 
-        let outcome = route_tunnel_response_frame(&mut pending, &frame);
+        let outcome = route_tunnel_response_frame(
+            &mut pending,
+            &control,
+            &response_head_frame("req-read-ok", 200),
+        );
 
         assert_eq!(
             outcome,
@@ -2414,19 +3809,23 @@ mod tests {
                 id: "req-read-ok".to_string()
             }
         );
-        assert!(pending.is_empty());
         let resp = rx.try_recv().unwrap();
-        assert_eq!(resp.id, "req-read-ok");
         assert_eq!(resp.status, 200);
     }
 
     #[test]
     fn relay_tunnel_read_error_result_fails_pending_requests() {
-        use base64::Engine;
-
-        let (mut pending, mut rx_one) = pending_tunnel_response("req-read-1");
-        let (tx_two, mut rx_two) = tokio::sync::oneshot::channel();
-        pending.insert("req-read-2".to_string(), tx_two);
+        let (mut pending, rx_one) = pending_tunnel_response("req-read-1");
+        let (tx_two, rx_two) = tokio::sync::oneshot::channel();
+        pending.insert(
+            "req-read-2".to_string(),
+            PendingTunnelRequest::AwaitingHead(tx_two),
+        );
+        let (chunk_tx, mut streaming_rx) = mpsc::channel(TUNNEL_BODY_CHANNEL_CAPACITY);
+        pending.insert(
+            "req-read-3".to_string(),
+            PendingTunnelRequest::Streaming(chunk_tx),
+        );
 
         let message = relay_tunnel_read_error_result(
             &mut pending,
@@ -2438,21 +3837,15 @@ mod tests {
         assert!(message.contains("protocol error"));
         assert!(pending.is_empty());
 
-        for (mut rx, id) in [(rx_one, "req-read-1"), (rx_two, "req-read-2")] {
+        for mut rx in [rx_one, rx_two] {
             let resp = rx.try_recv().unwrap();
-            assert_eq!(resp.id, id);
             assert_eq!(resp.status, StatusCode::BAD_GATEWAY.as_u16());
-            assert_eq!(
-                resp.headers.get("content-type").map(String::as_str),
-                Some("text/plain; charset=utf-8")
-            );
-            let body = base64::engine::general_purpose::STANDARD
-                .decode(resp.body)
-                .unwrap();
-            let body = String::from_utf8(body).unwrap();
+            let body = full_body_text(resp);
             assert!(body.contains("Tunnel owner WebSocket read failed"));
             assert!(body.contains("protocol error"));
         }
+        let streamed = streaming_rx.try_recv().unwrap().unwrap_err();
+        assert!(streamed.contains("protocol error"));
     }
 
     #[tokio::test]
@@ -2472,6 +3865,7 @@ mod tests {
             origin_url: "http://10.0.0.1:9419".to_string(),
             owner_wallet: "0xWALLET".to_string(),
             registered_at: now_secs(),
+            peer_id: None,
         };
         registry.register(reg).await;
 
@@ -2491,6 +3885,7 @@ mod tests {
             origin_url: "http://10.0.0.1:9419".to_string(),
             owner_wallet: "0xSITEOWNER".to_string(),
             registered_at: now_secs(),
+            peer_id: None,
         };
         registry.register_site(reg).await;
 
@@ -2519,6 +3914,7 @@ mod tests {
             origin_url: "http://10.0.0.1:9419".to_string(),
             owner_wallet: "0xOWNER".to_string(),
             registered_at: now_secs(),
+            peer_id: None,
         };
         registry.register_site(reg).await;
 
@@ -2531,6 +3927,8 @@ mod tests {
     async fn test_registry_persist_and_load() {
         let dir = tempfile::tempdir().unwrap();
 
+        let peer_id = libp2p::PeerId::random().to_string();
+
         // Register a share and a site, then drop the registry
         {
             let registry = RelayShareRegistry::new(dir.path().to_path_buf());
@@ -2540,6 +3938,7 @@ mod tests {
                     origin_url: "http://1.2.3.4:9419".to_string(),
                     owner_wallet: "0xW".to_string(),
                     registered_at: 1000,
+                    peer_id: Some(peer_id.clone()),
                 })
                 .await;
             registry
@@ -2548,6 +3947,7 @@ mod tests {
                     origin_url: "http://1.2.3.4:9419".to_string(),
                     owner_wallet: "0xS".to_string(),
                     registered_at: 2000,
+                    peer_id: None,
                 })
                 .await;
         }
@@ -2556,13 +3956,13 @@ mod tests {
         let registry = RelayShareRegistry::new(dir.path().to_path_buf());
         registry.load_from_disk().await.unwrap();
 
-        let share = registry.lookup("persist-tok").await;
-        assert!(share.is_some());
-        assert_eq!(share.unwrap().owner_wallet, "0xW");
+        let share = registry.lookup("persist-tok").await.unwrap();
+        assert_eq!(share.owner_wallet, "0xW");
+        assert_eq!(share.peer_id, Some(peer_id));
 
-        let site = registry.lookup_site("persist-site").await;
-        assert!(site.is_some());
-        assert_eq!(site.unwrap().owner_wallet, "0xS");
+        let site = registry.lookup_site("persist-site").await.unwrap();
+        assert_eq!(site.owner_wallet, "0xS");
+        assert_eq!(site.peer_id, None);
     }
 
     #[tokio::test]
@@ -2584,6 +3984,7 @@ mod tests {
             shares: Arc::new(RwLock::new(HashMap::new())),
             sites: Arc::new(RwLock::new(HashMap::new())),
             persist_path: dir.path().to_path_buf(),
+            p2p_http: None,
         };
 
         let err = registry.persist().await.unwrap_err();
@@ -2605,4 +4006,321 @@ mod tests {
         assert!(registry.lookup("persist-tok").await.is_none());
         assert!(registry.lookup_site("persist-site").await.is_none());
     }
+    // -----------------------------------------------------------------------
+    // HTTP over libp2p
+    // -----------------------------------------------------------------------
+
+    #[test]
+    fn register_payload_with_peer_keeps_legacy_bytes_without_peer() {
+        let legacy = register_payload("share", "tok", "0xowner", "http://x/");
+        assert_eq!(
+            register_payload_with_peer("share", "tok", "0xowner", "http://x/", None),
+            legacy
+        );
+        assert_ne!(
+            register_payload_with_peer("share", "tok", "0xowner", "http://x/", Some("")),
+            legacy
+        );
+        assert_ne!(
+            register_payload_with_peer("share", "tok", "0xowner", "http://x/", Some("a")),
+            register_payload_with_peer("share", "tok", "0xowner", "http://x/", Some("b"))
+        );
+    }
+
+    #[test]
+    fn p2p_route_requires_client_and_valid_peer_id() {
+        let dir = tempfile::tempdir().unwrap();
+        let peer_id = libp2p::PeerId::random().to_string();
+        let plain = RelayShareRegistry::new(dir.path().to_path_buf());
+        assert!(plain.p2p_route(Some(&peer_id)).is_none());
+
+        let (client, _calls) = HttpP2pClient::new(1);
+        let registry = RelayShareRegistry::new(dir.path().to_path_buf()).with_p2p_http(client);
+        assert!(registry.p2p_route(None).is_none());
+        assert!(registry.p2p_route(Some("not-a-peer")).is_none());
+        let route = registry.p2p_route(Some(&peer_id)).unwrap();
+        assert_eq!(route.peer.to_string(), peer_id);
+    }
+
+    #[tokio::test]
+    async fn register_share_rejects_invalid_peer_id() {
+        let dir = tempfile::tempdir().unwrap();
+        let registry = Arc::new(RelayShareRegistry::new(dir.path().to_path_buf()));
+        let mut req = signed_register_request("p2p-token", "http://203.0.113.5:9419");
+        req.peer_id = Some("not-a-peer".to_string());
+
+        let response = register_share(
+            Extension(registry.clone()),
+            ConnectInfo(SocketAddr::from(([203, 0, 113, 5], 51111))),
+            Json(req),
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert!(registry.lookup("p2p-token").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn register_site_signature_covers_peer_id() {
+        let dir = tempfile::tempdir().unwrap();
+        let registry = Arc::new(RelayShareRegistry::new(dir.path().to_path_buf()));
+        // Signed without a peer ID; a peer ID added afterwards must not verify.
+        let mut req = signed_site_register_request("p2p-site", "http://203.0.113.5:9419");
+        req.peer_id = Some(libp2p::PeerId::random().to_string());
+
+        let response = register_site(
+            Extension(registry.clone()),
+            ConnectInfo(SocketAddr::from(([203, 0, 113, 5], 51111))),
+            Json(req),
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert!(registry.lookup_site("p2p-site").await.is_none());
+    }
+
+    /// Owner's local server: answers every request with `body` and reports
+    /// each request line and body it received.
+    async fn recording_origin(
+        body: Vec<u8>,
+    ) -> (String, mpsc::UnboundedReceiver<(String, Vec<u8>)>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (tx, rx) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut raw = Vec::new();
+                let mut buf = [0u8; 4096];
+                let head_end = loop {
+                    let n = socket.read(&mut buf).await.unwrap_or(0);
+                    if n == 0 {
+                        break None;
+                    }
+                    raw.extend_from_slice(&buf[..n]);
+                    if let Some(pos) = raw.windows(4).position(|w| w == b"\r\n\r\n") {
+                        break Some(pos + 4);
+                    }
+                };
+                let Some(head_end) = head_end else { continue };
+                let head = String::from_utf8_lossy(&raw[..head_end]).to_string();
+                let header = |wanted: &str| {
+                    head.lines()
+                        .filter_map(|line| line.split_once(':'))
+                        .find(|(name, _)| name.eq_ignore_ascii_case(wanted))
+                        .map(|(_, value)| value.trim().to_ascii_lowercase())
+                };
+                let chunked = header("transfer-encoding").as_deref() == Some("chunked");
+                let content_length = header("content-length")
+                    .map(|value| value.parse::<usize>().unwrap())
+                    .unwrap_or(0);
+                loop {
+                    let complete = if chunked {
+                        raw[head_end..].ends_with(b"0\r\n\r\n")
+                    } else {
+                        raw.len() >= head_end + content_length
+                    };
+                    if complete {
+                        break;
+                    }
+                    let n = socket.read(&mut buf).await.unwrap_or(0);
+                    if n == 0 {
+                        break;
+                    }
+                    raw.extend_from_slice(&buf[..n]);
+                }
+                let mut request_body = raw[head_end..].to_vec();
+                if chunked {
+                    let mut decoded = Vec::new();
+                    let mut rest = &request_body[..];
+                    while let Some(line_end) = rest.windows(2).position(|w| w == b"\r\n") {
+                        let size = std::str::from_utf8(&rest[..line_end]).unwrap();
+                        let size = usize::from_str_radix(size.trim(), 16).unwrap();
+                        if size == 0 {
+                            break;
+                        }
+                        decoded.extend_from_slice(&rest[line_end + 2..line_end + 2 + size]);
+                        rest = &rest[line_end + 4 + size..];
+                    }
+                    request_body = decoded;
+                }
+                let request_line = head.lines().next().unwrap_or_default().to_string();
+                let _ = tx.send((request_line, request_body));
+                let reply = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nX-Owner-Server: yes\r\nConnection: close\r\n\r\n",
+                    body.len()
+                );
+                let _ = socket.write_all(reply.as_bytes()).await;
+                let _ = socket.write_all(&body).await;
+            }
+        });
+        (format!("http://{}", addr), rx)
+    }
+
+    /// Stands in for the relay's swarm task. Calls reach `owner` as if they
+    /// had crossed the libp2p connection, or fail when `owner` is `None`.
+    fn fake_p2p_swarm(
+        owner: Option<crate::http_p2p::HttpP2pServer>,
+        mut calls: mpsc::Receiver<crate::http_p2p::HttpP2pCall>,
+    ) -> mpsc::UnboundedReceiver<HttpP2pRequest> {
+        let (seen_tx, seen_rx) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            while let Some(call) = calls.recv().await {
+                let _ = seen_tx.send(call.request.clone());
+                let reply = match &owner {
+                    Some(owner) => Ok(owner.handle(call.peer, call.request).await),
+                    None => Err("dial failure".to_string()),
+                };
+                let _ = call.reply.send(reply);
+            }
+        });
+        seen_rx
+    }
+
+    async fn serve_relay(registry: RelayShareRegistry) -> SocketAddr {
+        let app = relay_share_routes(Arc::new(registry), Arc::new(TunnelRegistry::new()));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(
+                listener,
+                app.into_make_service_with_connect_info::<SocketAddr>(),
+            )
+            .await
+            .unwrap();
+        });
+        addr
+    }
+
+    #[tokio::test]
+    async fn relay_fetches_share_over_libp2p_when_origin_is_unreachable() {
+        let payload = (0..crate::http_p2p::HTTP_P2P_CHUNK_BYTES * 2 + 17)
+            .map(|i| (i % 253) as u8)
+            .collect::<Vec<_>>();
+        let (local_origin, mut origin_requests) = recording_origin(payload.clone()).await;
+        let owner = crate::http_p2p::HttpP2pServer::new();
+        owner.publish("share", "tok", &local_origin).await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let (client, calls) = HttpP2pClient::new(8);
+        let mut seen = fake_p2p_swarm(Some(owner), calls);
+        let registry = RelayShareRegistry::new(dir.path().to_path_buf()).with_p2p_http(client);
+        registry
+            .register(ShareRegistration {
+                token: "tok".to_string(),
+                // Unreachable, so only libp2p can answer.
+                origin_url: "http://127.0.0.1:9".to_string(),
+                owner_wallet: "0xWALLET".to_string(),
+                registered_at: now_secs(),
+                peer_id: Some(libp2p::PeerId::random().to_string()),
+            })
+            .await;
+        let addr = serve_relay(registry).await;
+
+        let response = reqwest::get(format!("http://{addr}/drive/tok/big.bin?v=1"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.headers()["x-owner-server"], "yes");
+        assert_eq!(response.bytes().await.unwrap().to_vec(), payload);
+        let (request_line, _) = origin_requests.recv().await.unwrap();
+        assert_eq!(request_line, "GET /drive/tok/big.bin?v=1 HTTP/1.1");
+
+        let mut pulls = 0;
+        while let Ok(request) = seen.try_recv() {
+            if matches!(request, HttpP2pRequest::Next { .. }) {
+                pulls += 1;
+            }
+        }
+        assert_eq!(pulls, 2);
+
+        let response = reqwest::Client::new()
+            .put(format!("http://{addr}/drive/tok/notes.txt"))
+            .body("small upload")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        let (request_line, body) = origin_requests.recv().await.unwrap();
+        assert_eq!(request_line, "PUT /drive/tok/notes.txt HTTP/1.1");
+        assert_eq!(body, b"small upload");
+    }
+
+    #[tokio::test]
+    async fn relay_falls_back_to_direct_origin_when_libp2p_fails() {
+        let (origin, mut origin_requests) = recording_origin(b"direct".to_vec()).await;
+        let dir = tempfile::tempdir().unwrap();
+        let (client, calls) = HttpP2pClient::new(8);
+        let mut seen = fake_p2p_swarm(None, calls);
+        let registry = RelayShareRegistry::new(dir.path().to_path_buf()).with_p2p_http(client);
+        registry
+            .register_site(SiteRegistration {
+                site_id: "site".to_string(),
+                origin_url: origin,
+                owner_wallet: "0xWALLET".to_string(),
+                registered_at: now_secs(),
+                peer_id: Some(libp2p::PeerId::random().to_string()),
+            })
+            .await;
+        let addr = serve_relay(registry).await;
+
+        let response = reqwest::Client::new()
+            .post(format!("http://{addr}/sites/site/form"))
+            .body("a=1")
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.text().await.unwrap(), "direct");
+        assert!(matches!(
+            seen.recv().await,
+            Some(HttpP2pRequest::Start { .. })
+        ));
+        let (request_line, body) = origin_requests.recv().await.unwrap();
+        assert_eq!(request_line, "POST /sites/site/form HTTP/1.1");
+        assert_eq!(body, b"a=1");
+    }
+
+    #[tokio::test]
+    async fn owner_error_over_libp2p_is_a_bad_gateway() {
+        let dir = tempfile::tempdir().unwrap();
+        let (client, calls) = HttpP2pClient::new(8);
+        // The owner hasn't published this share, so it answers with an error.
+        let _seen = fake_p2p_swarm(Some(crate::http_p2p::HttpP2pServer::new()), calls);
+        let registry = RelayShareRegistry::new(dir.path().to_path_buf()).with_p2p_http(client);
+        registry
+            .register(ShareRegistration {
+                token: "tok".to_string(),
+                origin_url: "http://127.0.0.1:9".to_string(),
+                owner_wallet: "0xWALLET".to_string(),
+                registered_at: now_secs(),
+                peer_id: Some(libp2p::PeerId::random().to_string()),
+            })
+            .await;
+        let addr = serve_relay(registry).await;
+
+        let response = reqwest::get(format!("http://{addr}/drive/tok"))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), 502);
+        assert!(response.text().await.unwrap().contains("not published"));
+    }
+
+    #[tokio::test]
+    async fn dropping_unfinished_p2p_body_cancels_owner_stream() {
+        let (client, mut calls) = HttpP2pClient::new(4);
+        let route = P2pRoute {
+            client,
+            peer: libp2p::PeerId::random(),
+        };
+        let body = p2p_response_body(route, b"first".to_vec(), Some(42));
+        let mut stream = body.into_data_stream();
+        assert_eq!(stream.next().await.unwrap().unwrap(), "first");
+
+        drop(stream);
+
+        let call = calls.recv().await.unwrap();
+        assert_eq!(call.request, HttpP2pRequest::Cancel { stream_id: 42 });
+    }
 }
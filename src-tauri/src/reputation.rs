@@ -1,20 +1,49 @@
 //! Verifiable reputation issuer keys and transfer verdict signatures.
 //!
-//! Reputation events are admitted by the HTTP rating API, but the
-//! per-event verdict is signed by an Ed25519 issuer key. The issuer key
-//! itself is published through the DHT as a wallet-signed record so a
-//! verifier can bind `issuer_wallet` to the Ed25519 verifying key before
-//! trusting the verdict signature.
+//! Each transfer verdict is signed by the downloader's Ed25519 issuer key.
+//! The issuer key itself is published through the DHT as a wallet-signed
+//! record so a verifier can bind `issuer_wallet` to the Ed25519 verifying
+//! key before trusting the verdict signature.
+//!
+//! Verdicts are gossiped through the DHT with the same layout as keyword
+//! postings, so no single relay's ledger decides a seeder's score:
+//!
+//! - `chiral_verdicts_<seeder wallet>`: Kademlia provider key. Every node
+//!   holding verdicts about the seeder provides it.
+//! - `chiral_verdictpost_<seeder wallet>_<peerId>`: that node's
+//!   [`VerdictBundle`]. Only the peer in the key may publish it.
+//!
+//! Readers check every verdict against its issuer key and recompute the
+//! Elo locally; rating API servers only cache what they have verified.
+//! Before a verdict counts, its payment and the age of the downloader's
+//! wallet are looked up on-chain.
 
 use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
 use std::sync::Arc;
+use std::time::Duration;
 
 use crate::dht::DhtService;
 
 pub const ISSUER_KEY_DHT_PREFIX: &str = "chiral_reputation_issuer_v1_";
 const ISSUER_KEY_TAG: &[u8] = b"chiral-reputation-issuer-key-v1";
 const VERDICT_TAG: &[u8] = b"chiral-reputation-verdict-v1";
+pub const VERDICT_PROVIDER_PREFIX: &str = "chiral_verdicts_";
+pub const VERDICT_BUNDLE_PREFIX: &str = "chiral_verdictpost_";
+/// Verdicts one node publishes per seeder; the newest are kept.
+pub const MAX_VERDICTS_PER_BUNDLE: usize = 200;
+/// How far ahead of the local clock a verdict's `issued_at` may be.
+pub const MAX_VERDICT_CLOCK_SKEW_SECS: u64 = 10 * 60;
+/// Publishers whose bundles are fetched per seeder.
+const MAX_BUNDLE_PUBLISHERS: usize = 50;
+const VERDICT_LOOKUP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// First outgoing transaction time per wallet. It never changes once a
+/// wallet has sent anything, so hits are kept for the process lifetime.
+static FIRST_TX_AT: Lazy<Mutex<HashMap<String, u64>>> = Lazy::new(|| Mutex::new(HashMap::new()));
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "camelCase")]
@@ -39,6 +68,100 @@ pub struct ReputationVerdictPayload {
     pub outcome: String,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub tx_hash: Option<String>,
+    /// Unix seconds at signing. Zero on verdicts signed before verdicts
+    /// were timestamped; those are never gossiped.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub issued_at: u64,
+}
+
+fn is_zero(value: &u64) -> bool {
+    *value == 0
+}
+
+/// A verdict as gossiped: the signed payload plus who signed it.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct SignedVerdict {
+    #[serde(flatten)]
+    pub verdict: ReputationVerdictPayload,
+    pub issuer_wallet: String,
+    /// Hex Ed25519 signature over `verdict_signing_payload`.
+    pub signature: String,
+}
+
+impl SignedVerdict {
+    /// Checks that don't need the issuer key: the downloader issued it, it
+    /// is timestamped and not from the future, and its fields parse.
+    pub fn check_shape(&self, now: u64) -> Result<(), String> {
+        let verdict = &self.verdict;
+        let issuer = normalize_wallet(&self.issuer_wallet)?;
+        if !verdict
+            .downloader_wallet
+            .trim()
+            .eq_ignore_ascii_case(&issuer)
+        {
+            return Err("verdict issuer is not the downloader".to_string());
+        }
+        let seeder = normalize_seeder_wallet(&verdict.seeder_wallet)?;
+        if seeder == issuer {
+            return Err("verdict issuer scored itself".to_string());
+        }
+        if verdict.issued_at == 0 {
+            return Err("verdict has no issue time".to_string());
+        }
+        if verdict.issued_at > now + MAX_VERDICT_CLOCK_SKEW_SECS {
+            return Err("verdict issue time is ahead of the local clock".to_string());
+        }
+        if !matches!(verdict.outcome.trim(), "completed" | "failed") {
+            return Err(format!("unknown verdict outcome '{}'", verdict.outcome));
+        }
+        if verdict.amount_wei.trim().parse::<u128>().is_err() {
+            return Err("verdict amount is not an integer wei string".to_string());
+        }
+        if verdict.transfer_id.trim().is_empty() || verdict.file_hash.trim().is_empty() {
+            return Err("verdict is missing its transfer ID or file hash".to_string());
+        }
+        decode_signature(&self.signature).map(|_| ())
+    }
+}
+
+/// One node's verdicts about one seeder, stored under
+/// `chiral_verdictpost_<seeder wallet>_<peerId>`. The DHT envelope proves
+/// which peer published it; each verdict carries its own issuer signature.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct VerdictBundle {
+    pub seeder_wallet: String,
+    pub peer_id: String,
+    pub verdicts: Vec<SignedVerdict>,
+    pub updated_at: u64,
+}
+
+impl VerdictBundle {
+    /// Structural checks for the DHT record validator. Issuer signatures
+    /// need issuer key lookups, so readers check those in `fetch_verdicts`.
+    pub fn validate_for_key(&self, seeder_wallet: &str, peer_id: &str) -> Result<(), String> {
+        if self.seeder_wallet != seeder_wallet || self.peer_id != peer_id {
+            return Err("verdict bundle does not match its key".to_string());
+        }
+        if self.verdicts.len() > MAX_VERDICTS_PER_BUNDLE {
+            return Err(format!(
+                "verdict bundle lists {} verdicts, limit is {}",
+                self.verdicts.len(),
+                MAX_VERDICTS_PER_BUNDLE
+            ));
+        }
+        let foreign = self.verdicts.iter().any(|v| {
+            !v.verdict
+                .seeder_wallet
+                .trim()
+                .eq_ignore_ascii_case(seeder_wallet)
+        });
+        if foreign {
+            return Err("verdict bundle holds verdicts about another seeder".to_string());
+        }
+        Ok(())
+    }
 }
 
 fn push_part(out: &mut Vec<u8>, part: &[u8]) {
@@ -79,6 +202,31 @@ fn decode_signature(signature_hex: &str) -> Result<Signature, String> {
     Signature::from_slice(&bytes).map_err(|e| format!("invalid Ed25519 signature: {e}"))
 }
 
+fn normalize_seeder_wallet(addr: &str) -> Result<String, String> {
+    let wallet = addr.trim().to_lowercase();
+    if !is_valid_wallet(&wallet) {
+        return Err("seeder wallet must be a 0x-prefixed 20-byte hex address".to_string());
+    }
+    Ok(wallet)
+}
+
+pub fn verdict_provider_key(seeder_wallet: &str) -> Result<String, String> {
+    Ok(format!(
+        "{}{}",
+        VERDICT_PROVIDER_PREFIX,
+        normalize_seeder_wallet(seeder_wallet)?
+    ))
+}
+
+pub fn verdict_bundle_key(seeder_wallet: &str, peer_id: &str) -> Result<String, String> {
+    Ok(format!(
+        "{}{}_{}",
+        VERDICT_BUNDLE_PREFIX,
+        normalize_seeder_wallet(seeder_wallet)?,
+        peer_id
+    ))
+}
+
 pub fn issuer_key_dht_key(issuer_wallet: &str) -> Result<String, String> {
     Ok(format!(
         "{}{}",
@@ -115,6 +263,11 @@ pub fn verdict_signing_payload(verdict: &ReputationVerdictPayload) -> Vec<u8> {
     ] {
         push_part(&mut out, part);
     }
+    // Appended only when set, so signatures over untimestamped verdicts
+    // still verify.
+    if verdict.issued_at != 0 {
+        out.extend_from_slice(&verdict.issued_at.to_le_bytes());
+    }
     out
 }
 
@@ -174,6 +327,155 @@ pub fn verify_reputation_verdict_for_wallet(
         .map_err(|_| "reputation verdict signature does not match issuer key".to_string())
 }
 
+/// Full check of a gossiped verdict against its issuer's key record.
+pub fn verify_signed_verdict(
+    issuer_record: &ReputationIssuerKeyRecord,
+    signed: &SignedVerdict,
+    now: u64,
+) -> Result<(), String> {
+    signed.check_shape(now)?;
+    verify_reputation_verdict_for_wallet(
+        issuer_record,
+        &signed.issuer_wallet,
+        &signed.verdict,
+        &signed.signature,
+    )
+}
+
+/// Validate paid transfer via on-chain transaction data.
+pub async fn verify_payment_tx(
+    tx_hash: &str,
+    expected_from: &str,
+    expected_to: &str,
+    min_value_wei: u128,
+) -> Result<(), String> {
+    let rpc = crate::geth::rpc_endpoint();
+    let client = reqwest::Client::new();
+
+    let tx_payload = serde_json::json!({
+        "jsonrpc": "2.0",
+        "method": "eth_getTransactionByHash",
+        "params": [tx_hash],
+        "id": 1
+    });
+    let tx_resp = client
+        .post(&rpc)
+        .json(&tx_payload)
+        .send()
+        .await
+        .map_err(|e| format!("Failed to query tx: {}", e))?;
+    let tx_json: serde_json::Value = tx_resp
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse tx response: {}", e))?;
+    let tx = tx_json.get("result").ok_or("Tx result missing")?;
+    if tx.is_null() {
+        return Err("Transaction not found".to_string());
+    }
+
+    let tx_from = tx.get("from").and_then(|v| v.as_str()).unwrap_or_default();
+    let tx_to = tx.get("to").and_then(|v| v.as_str()).unwrap_or_default();
+    if !tx_from.eq_ignore_ascii_case(expected_from) {
+        return Err("Transaction sender does not match downloader wallet".to_string());
+    }
+    if !tx_to.eq_ignore_ascii_case(expected_to) {
+        return Err("Transaction recipient does not match seeder wallet".to_string());
+    }
+
+    let tx_value_hex = tx
+        .get("value")
+        .and_then(|v| v.as_str())
+        .ok_or("Transaction value missing")?;
+    let tx_value = crate::rpc_client::hex_to_u128(tx_value_hex)?;
+    if tx_value < min_value_wei {
+        return Err("Transaction value is below expected amount".to_string());
+    }
+
+    let receipt_payload = serde_json::json!({
+        "jsonrpc": "2.0",
+        "method": "eth_getTransactionReceipt",
+        "params": [tx_hash],
+        "id": 2
+    });
+    let receipt_resp = client
+        .post(&rpc)
+        .json(&receipt_payload)
+        .send()
+        .await
+        .map_err(|e| format!("Failed to query receipt: {}", e))?;
+    let receipt_json: serde_json::Value = receipt_resp
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse receipt response: {}", e))?;
+    let receipt = receipt_json.get("result").ok_or("Receipt result missing")?;
+    if receipt.is_null() {
+        return Err("Transaction not yet confirmed".to_string());
+    }
+
+    let status = receipt
+        .get("status")
+        .and_then(|v| v.as_str())
+        .unwrap_or("0x0");
+    if status != "0x1" {
+        return Err("Transaction failed on-chain".to_string());
+    }
+
+    Ok(())
+}
+
+/// Unix time of the block holding `wallet`'s first outgoing transaction,
+/// or `None` if it has never sent one. Binary-searches the account nonce
+/// over block heights, which needs the archive state geth is run with.
+pub async fn first_outgoing_tx_at(wallet: &str) -> Result<Option<u64>, String> {
+    let wallet = wallet.trim().to_lowercase();
+    if let Some(at) = FIRST_TX_AT.lock().get(&wallet) {
+        return Ok(Some(*at));
+    }
+    let rpc = crate::geth::rpc_endpoint();
+    let nonce_at = |block: String| {
+        let rpc = rpc.clone();
+        let wallet = wallet.clone();
+        async move {
+            let nonce = crate::rpc_client::call(
+                &rpc,
+                "eth_getTransactionCount",
+                serde_json::json!([wallet, block]),
+            )
+            .await?;
+            crate::rpc_client::hex_to_u64(nonce.as_str().unwrap_or_default())
+        }
+    };
+    if nonce_at("latest".to_string()).await? == 0 {
+        return Ok(None);
+    }
+    let head = crate::rpc_client::call(&rpc, "eth_blockNumber", serde_json::json!([])).await?;
+    let (mut lo, mut hi) = (
+        0u64,
+        crate::rpc_client::hex_to_u64(head.as_str().unwrap_or_default())?,
+    );
+    while lo < hi {
+        let mid = lo + (hi - lo) / 2;
+        if nonce_at(format!("0x{:x}", mid)).await? > 0 {
+            hi = mid;
+        } else {
+            lo = mid + 1;
+        }
+    }
+    let block = crate::rpc_client::call(
+        &rpc,
+        "eth_getBlockByNumber",
+        serde_json::json!([format!("0x{:x}", lo), false]),
+    )
+    .await?;
+    let timestamp = block
+        .get("timestamp")
+        .and_then(|v| v.as_str())
+        .ok_or("block timestamp missing")?;
+    let at = crate::rpc_client::hex_to_u64(timestamp)?;
+    FIRST_TX_AT.lock().insert(wallet, at);
+    Ok(Some(at))
+}
+
 pub async fn publish_issuer_key(
     dht: &Arc<DhtService>,
     record: ReputationIssuerKeyRecord,
@@ -199,6 +501,135 @@ pub async fn fetch_issuer_key(
     Ok(Some(record))
 }
 
+/// Publish this node's verdicts about `seeder_wallet` and provide its
+/// verdict key so readers find the bundle. Untimestamped verdicts and
+/// verdicts about other seeders are skipped; past the bundle limit the
+/// newest are kept. Returns how many verdicts were published.
+pub async fn publish_verdicts(
+    dht: &Arc<DhtService>,
+    seeder_wallet: &str,
+    mut verdicts: Vec<SignedVerdict>,
+    now: u64,
+) -> Result<usize, String> {
+    let seeder = normalize_seeder_wallet(seeder_wallet)?;
+    let peer_id = dht
+        .get_peer_id()
+        .await
+        .filter(|p| !p.is_empty())
+        .ok_or("DHT peer ID unavailable")?;
+    verdicts.retain(|v| {
+        v.verdict.seeder_wallet.trim().eq_ignore_ascii_case(&seeder) && v.check_shape(now).is_ok()
+    });
+    if verdicts.is_empty() {
+        return Ok(0);
+    }
+    verdicts.sort_by_key(|v| std::cmp::Reverse(v.verdict.issued_at));
+    verdicts.truncate(MAX_VERDICTS_PER_BUNDLE);
+    let count = verdicts.len();
+    let bundle = VerdictBundle {
+        seeder_wallet: seeder.clone(),
+        peer_id: peer_id.clone(),
+        verdicts,
+        updated_at: now,
+    };
+    let value = serde_json::to_string(&bundle)
+        .map_err(|e| format!("failed to serialize verdict bundle: {e}"))?;
+    dht.put_dht_value(verdict_bundle_key(&seeder, &peer_id)?, value)
+        .await?;
+    dht.start_providing_file(verdict_provider_key(&seeder)?)
+        .await?;
+    Ok(count)
+}
+
+/// Fetch every published bundle about `seeder_wallet` and keep the
+/// verdicts whose signature verifies against the issuer key in the DHT.
+/// Copies of the same verdict from several publishers are returned once.
+pub async fn fetch_verdicts(
+    dht: &Arc<DhtService>,
+    seeder_wallet: &str,
+    now: u64,
+) -> Result<Vec<SignedVerdict>, String> {
+    let seeder = normalize_seeder_wallet(seeder_wallet)?;
+    let providers = match tokio::time::timeout(
+        VERDICT_LOOKUP_TIMEOUT,
+        dht.get_file_providers(verdict_provider_key(&seeder)?),
+    )
+    .await
+    {
+        Ok(Ok(providers)) => providers,
+        _ => Vec::new(),
+    };
+    let seeder_ref = &seeder;
+    let lookups = providers
+        .into_iter()
+        .take(MAX_BUNDLE_PUBLISHERS)
+        .map(|peer_id| async move {
+            let key = verdict_bundle_key(seeder_ref, &peer_id).ok()?;
+            let raw = tokio::time::timeout(VERDICT_LOOKUP_TIMEOUT, dht.get_dht_value(key))
+                .await
+                .ok()?
+                .ok()??;
+            let bundle: VerdictBundle = serde_json::from_str(&raw).ok()?;
+            if let Err(e) = bundle.validate_for_key(seeder_ref, &peer_id) {
+                println!(
+                    "[REPUTATION] Dropping verdict bundle for {} from {}: {}",
+                    seeder_ref, peer_id, e
+                );
+                return None;
+            }
+            Some(bundle.verdicts)
+        });
+    let mut unique: HashMap<String, SignedVerdict> = HashMap::new();
+    for verdict in futures::future::join_all(lookups)
+        .await
+        .into_iter()
+        .flatten()
+        .flatten()
+    {
+        unique.entry(verdict.signature.clone()).or_insert(verdict);
+    }
+
+    let issuers: BTreeSet<String> = unique
+        .values()
+        .filter_map(|v| normalize_wallet(&v.issuer_wallet).ok())
+        .collect();
+    let issuer_keys: HashMap<String, ReputationIssuerKeyRecord> =
+        futures::future::join_all(issuers.into_iter().map(|wallet| async move {
+            let record =
+                tokio::time::timeout(VERDICT_LOOKUP_TIMEOUT, fetch_issuer_key(dht, &wallet))
+                    .await
+                    .ok()?
+                    .ok()??;
+            Some((wallet, record))
+        }))
+        .await
+        .into_iter()
+        .flatten()
+        .collect();
+
+    let mut dropped = 0usize;
+    let verified: Vec<SignedVerdict> = unique
+        .into_values()
+        .filter(|v| {
+            let ok = normalize_wallet(&v.issuer_wallet)
+                .ok()
+                .and_then(|wallet| issuer_keys.get(&wallet))
+                .is_some_and(|record| verify_signed_verdict(record, v, now).is_ok());
+            if !ok {
+                dropped += 1;
+            }
+            ok
+        })
+        .collect();
+    if dropped > 0 {
+        println!(
+            "[REPUTATION] Dropped {} unverifiable verdicts about {}",
+            dropped, seeder
+        );
+    }
+    Ok(verified)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -249,6 +680,31 @@ mod tests {
             amount_wei: "1000".to_string(),
             outcome: "completed".to_string(),
             tx_hash: Some("0xtx".to_string()),
+            issued_at: 0,
+        }
+    }
+
+    const NOW: u64 = 1_700_000_000;
+
+    fn signed_verdict(signing: &SigningKey) -> SignedVerdict {
+        let verdict = ReputationVerdictPayload {
+            issued_at: NOW - 60,
+            ..verdict()
+        };
+        let sig = signing.sign(&verdict_signing_payload(&verdict));
+        SignedVerdict {
+            verdict,
+            issuer_wallet: test_wallet(),
+            signature: hex::encode(sig.to_bytes()),
+        }
+    }
+
+    fn bundle(verdicts: Vec<SignedVerdict>) -> VerdictBundle {
+        VerdictBundle {
+            seeder_wallet: "0x1111111111111111111111111111111111111111".to_string(),
+            peer_id: "12D3KooWPeer".to_string(),
+            verdicts,
+            updated_at: NOW,
         }
     }
 
@@ -335,4 +791,121 @@ mod tests {
             .expect_err("missing issuer key material must reject");
         assert!(err.contains("verifying key"));
     }
+
+    #[test]
+    fn issue_time_is_signed_only_when_set() {
+        let untimed = verdict();
+        let timed = ReputationVerdictPayload {
+            issued_at: NOW,
+            ..verdict()
+        };
+        let untimed_payload = verdict_signing_payload(&untimed);
+        let timed_payload = verdict_signing_payload(&timed);
+        assert_eq!(timed_payload.len(), untimed_payload.len() + 8);
+        assert!(timed_payload.starts_with(&untimed_payload));
+
+        let serialized = serde_json::to_value(&untimed).unwrap();
+        assert!(serialized.get("issuedAt").is_none());
+    }
+
+    #[test]
+    fn signed_verdict_verifies_and_rejects_tampering() {
+        let signing = SigningKey::generate(&mut OsRng);
+        let record = signed_issuer_record(&signing);
+        let signed = signed_verdict(&signing);
+        verify_signed_verdict(&record, &signed, NOW).expect("signed verdict should verify");
+
+        let mut flipped = signed.clone();
+        flipped.verdict.outcome = "failed".to_string();
+        let err = verify_signed_verdict(&record, &flipped, NOW).expect_err("outcome is signed");
+        assert!(err.contains("verdict signature"));
+
+        let mut backdated = signed.clone();
+        backdated.verdict.issued_at -= 86_400;
+        assert!(verify_signed_verdict(&record, &backdated, NOW).is_err());
+    }
+
+    #[test]
+    fn signed_verdict_serializes_flat() {
+        let signing = SigningKey::generate(&mut OsRng);
+        let signed = signed_verdict(&signing);
+        let json = serde_json::to_value(&signed).unwrap();
+        assert_eq!(json["transferId"], "transfer-1");
+        assert_eq!(json["issuedAt"], NOW - 60);
+        assert_eq!(json["issuerWallet"], test_wallet());
+        let back: SignedVerdict = serde_json::from_value(json).unwrap();
+        assert_eq!(back, signed);
+    }
+
+    #[test]
+    fn verdict_shape_checks_run_before_signatures() {
+        let signing = SigningKey::generate(&mut OsRng);
+        let signed = signed_verdict(&signing);
+        signed.check_shape(NOW).expect("well-formed verdict");
+
+        let mut other_issuer = signed.clone();
+        other_issuer.issuer_wallet = wallet_from_private_key(OTHER_PRIVATE_KEY);
+        assert!(other_issuer
+            .check_shape(NOW)
+            .unwrap_err()
+            .contains("not the downloader"));
+
+        let mut untimed = signed.clone();
+        untimed.verdict.issued_at = 0;
+        assert!(untimed.check_shape(NOW).unwrap_err().contains("issue time"));
+
+        let mut future = signed.clone();
+        future.verdict.issued_at = NOW + MAX_VERDICT_CLOCK_SKEW_SECS + 1;
+        assert!(future.check_shape(NOW).unwrap_err().contains("ahead"));
+
+        let mut unknown = signed.clone();
+        unknown.verdict.outcome = "great".to_string();
+        assert!(unknown.check_shape(NOW).unwrap_err().contains("outcome"));
+
+        let mut self_scored = signed;
+        self_scored.verdict.seeder_wallet = test_wallet();
+        assert!(self_scored
+            .check_shape(NOW)
+            .unwrap_err()
+            .contains("scored itself"));
+    }
+
+    #[test]
+    fn verdict_bundle_must_match_its_key() {
+        let signing = SigningKey::generate(&mut OsRng);
+        let seeder = "0x1111111111111111111111111111111111111111";
+        let ok = bundle(vec![signed_verdict(&signing)]);
+        ok.validate_for_key(seeder, "12D3KooWPeer")
+            .expect("matching bundle");
+        assert!(ok.validate_for_key(seeder, "12D3KooWOther").is_err());
+
+        let mut foreign = signed_verdict(&signing);
+        foreign.verdict.seeder_wallet = "0x2222222222222222222222222222222222222222".to_string();
+        let err = bundle(vec![foreign])
+            .validate_for_key(seeder, "12D3KooWPeer")
+            .unwrap_err();
+        assert!(err.contains("another seeder"));
+
+        let oversized = bundle(vec![signed_verdict(&signing); MAX_VERDICTS_PER_BUNDLE + 1]);
+        assert!(oversized
+            .validate_for_key(seeder, "12D3KooWPeer")
+            .unwrap_err()
+            .contains("limit"));
+    }
+
+    #[test]
+    fn verdict_keys_use_the_lowercase_seeder_wallet() {
+        let wallet = "0xABCDEFabcdef0123456789012345678901234567";
+        assert_eq!(
+            verdict_provider_key(wallet).unwrap(),
+            "chiral_verdicts_0xabcdefabcdef0123456789012345678901234567"
+        );
+        assert_eq!(
+            verdict_bundle_key(wallet, "12D3KooWPeer").unwrap(),
+            "chiral_verdictpost_0xabcdefabcdef0123456789012345678901234567_12D3KooWPeer"
+        );
+        assert!(verdict_provider_key("nope")
+            .unwrap_err()
+            .contains("seeder wallet"));
+    }
 }
@@ -0,0 +1,824 @@
+//! Keyword search over the DHT.
+//!
+//! `chiral_file_<hash>` only answers "who has this exact hash". To find
+//! files by name, extension, MIME type or tag, publishers also write signed
+//! keyword postings, following the same layout as seeder records:
+//!
+//! - `chiral_kw_<term>`: Kademlia provider key. Every peer that has a
+//!   posting for `term` provides it, so a lookup returns the publishers.
+//! - `chiral_kwpost_<term>_<peerId>`: that peer's posting, listing each of
+//!   its published files that contain `term`, signed by its wallet. Each
+//!   publisher writes only its own key, so there are no write races.
+//!
+//! Terms are lowercase words from the file name, description and tags,
+//! plus `ext:<extension>`, `mime:<type>`, `mime:<top-level type>` and
+//! `tag:<tag>`. A query is split the same way and every term must match.
+//! Results are ranked by how many peers seed the file, weighted by the
+//! local reputation of the wallets that published it.
+
+use crate::dht::DhtService;
+use crate::rating_storage::{self, ReputationEvent, BASE_ELO};
+use crate::wallet;
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::time::Duration;
+
+const MIN_TERM_LEN: usize = 2;
+const MAX_TERM_LEN: usize = 64;
+/// Terms indexed per file. Long descriptions are cut off here.
+pub const MAX_TERMS_PER_FILE: usize = 32;
+/// Files listed in one posting; a publisher with more files sharing a very
+/// common word only advertises the first ones (by hash).
+pub const MAX_POSTING_ENTRIES: usize = 200;
+pub const MAX_TAGS: usize = 16;
+pub const MAX_DESCRIPTION_LEN: usize = 1024;
+/// Terms honoured per query.
+pub const MAX_QUERY_TERMS: usize = 8;
+/// Publishers whose postings are fetched per term.
+const MAX_PUBLISHERS_PER_TERM: usize = 50;
+/// Candidates whose seeder counts are looked up before ranking.
+const MAX_RANKED_CANDIDATES: usize = 50;
+const LOOKUP_TIMEOUT: Duration = Duration::from_secs(5);
+pub const DEFAULT_SEARCH_LIMIT: usize = 20;
+pub const MAX_SEARCH_LIMIT: usize = 100;
+
+/// One file as described in a keyword posting.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeywordEntry {
+    pub hash: String,
+    pub file_name: String,
+    pub file_size: u64,
+    pub mime_type: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+/// Lowercase tags, drop empty and duplicate ones, and cap their number.
+pub fn normalize_tags(tags: &[String]) -> Vec<String> {
+    let mut seen = BTreeSet::new();
+    tags.iter()
+        .map(|t| t.trim().to_lowercase())
+        .filter(|t| !t.is_empty() && t.len() <= MAX_TERM_LEN)
+        .filter(|t| seen.insert(t.clone()))
+        .take(MAX_TAGS)
+        .collect()
+}
+
+/// Trim a description, treat blank as none, and cap its length.
+pub fn normalize_description(description: Option<&str>) -> Option<String> {
+    let trimmed = description?.trim();
+    if trimmed.is_empty() {
+        return None;
+    }
+    let mut end = trimmed.len().min(MAX_DESCRIPTION_LEN);
+    while !trimmed.is_char_boundary(end) {
+        end -= 1;
+    }
+    Some(trimmed[..end].to_string())
+}
+
+impl KeywordEntry {
+    pub fn new(
+        hash: &str,
+        file_name: &str,
+        file_size: u64,
+        tags: &[String],
+        description: Option<&str>,
+    ) -> Self {
+        Self {
+            hash: hash.to_string(),
+            file_name: file_name.to_string(),
+            file_size,
+            mime_type: crate::drive_storage::mime_from_name(file_name),
+            tags: normalize_tags(tags),
+            description: normalize_description(description),
+        }
+    }
+
+    /// The terms this file is indexed under.
+    pub fn terms(&self) -> BTreeSet<String> {
+        let mut ordered = Vec::new();
+        if let Some((_, ext)) = self.file_name.rsplit_once('.') {
+            let ext = ext.to_lowercase();
+            if !ext.is_empty() && ext.chars().all(|c| c.is_ascii_alphanumeric()) {
+                ordered.push(format!("ext:{}", ext));
+            }
+        }
+        let mime = self.mime_type.to_lowercase();
+        if let Some((top, _)) = mime.split_once('/') {
+            ordered.push(format!("mime:{}", top));
+        }
+        ordered.push(format!("mime:{}", mime));
+        ordered.extend(self.tags.iter().map(|t| format!("tag:{}", t)));
+        ordered.extend(tokenize(&self.file_name));
+        for tag in &self.tags {
+            ordered.extend(tokenize(tag));
+        }
+        if let Some(description) = &self.description {
+            ordered.extend(tokenize(description));
+        }
+
+        let mut terms = BTreeSet::new();
+        for term in ordered {
+            if terms.len() == MAX_TERMS_PER_FILE {
+                break;
+            }
+            terms.insert(term);
+        }
+        terms
+    }
+}
+
+/// Split text into lowercase alphanumeric words worth indexing.
+pub fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .map(str::to_lowercase)
+        .filter(|w| (MIN_TERM_LEN..=MAX_TERM_LEN).contains(&w.chars().count()))
+        .collect()
+}
+
+/// Terms for a search query. `ext:`, `mime:` and `tag:` prefixes are kept
+/// as typed (lowercased); everything else is tokenized like file names.
+pub fn query_terms(query: &str) -> Vec<String> {
+    let mut terms = Vec::new();
+    for word in query.split_whitespace() {
+        let lower = word.to_lowercase();
+        let prefixed = ["ext:", "mime:", "tag:"]
+            .iter()
+            .any(|p| lower.strip_prefix(p).is_some_and(|rest| !rest.is_empty()));
+        if prefixed {
+            let lower = match lower.strip_prefix("ext:") {
+                Some(ext) => format!("ext:{}", ext.trim_start_matches('.')),
+                None => lower,
+            };
+            terms.push(lower);
+        } else {
+            terms.extend(tokenize(word));
+        }
+    }
+    let mut seen = BTreeSet::new();
+    terms.retain(|t| seen.insert(t.clone()));
+    terms.truncate(MAX_QUERY_TERMS);
+    terms
+}
+
+pub fn provider_key(term: &str) -> String {
+    format!("chiral_kw_{}", term)
+}
+
+pub fn posting_key(term: &str, peer_id: &str) -> String {
+    format!("chiral_kwpost_{}_{}", term, peer_id)
+}
+
+/// A publisher's signed list of files containing one term.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeywordPosting {
+    pub term: String,
+    pub peer_id: String,
+    pub wallet_address: String,
+    pub entries: Vec<KeywordEntry>,
+    pub updated_at: u64,
+    #[serde(default)]
+    pub signature: String,
+}
+
+impl KeywordPosting {
+    /// Length-prefixed so no field can bleed into its neighbour.
+    fn sign_payload(&self) -> Vec<u8> {
+        let entries = serde_json::to_vec(&self.entries).unwrap_or_default();
+        let mut out = Vec::with_capacity(64 + entries.len());
+        out.extend_from_slice(b"kwpost");
+        out.push(0);
+        for part in [
+            self.term.as_bytes(),
+            self.peer_id.as_bytes(),
+            self.wallet_address.to_lowercase().as_bytes(),
+            &entries,
+        ] {
+            out.extend_from_slice(&(part.len() as u32).to_le_bytes());
+            out.extend_from_slice(part);
+        }
+        out.extend_from_slice(&self.updated_at.to_le_bytes());
+        out
+    }
+
+    pub fn sign(&mut self, private_key: &str) -> Result<(), String> {
+        self.signature.clear();
+        let signature = wallet::sign_message(private_key, &self.sign_payload())
+            .map_err(|e| format!("failed to sign keyword posting: {}", e))?;
+        if signature.is_empty() {
+            return Err("failed to sign keyword posting: empty signature".to_string());
+        }
+        self.signature = signature;
+        Ok(())
+    }
+
+    pub fn verify(&self) -> bool {
+        !self.signature.is_empty()
+            && !self.wallet_address.is_empty()
+            && wallet::verify_signature(&self.sign_payload(), &self.signature, &self.wallet_address)
+    }
+}
+
+/// Files this node has published, so postings can be rebuilt whenever one
+/// of them is added or removed.
+#[derive(Default)]
+pub struct LocalIndex {
+    entries: HashMap<String, KeywordEntry>,
+    /// Wallet address and key of the last publish, used to re-sign
+    /// postings when a file is unpublished.
+    signer: Option<(String, String)>,
+}
+
+impl LocalIndex {
+    /// Add or replace an entry. Returns every term whose posting changed.
+    pub fn upsert(&mut self, entry: KeywordEntry) -> BTreeSet<String> {
+        let mut affected = entry.terms();
+        if let Some(old) = self.entries.insert(entry.hash.clone(), entry) {
+            affected.extend(old.terms());
+        }
+        affected
+    }
+
+    /// Drop an entry. Returns every term whose posting changed.
+    pub fn remove(&mut self, hash: &str) -> BTreeSet<String> {
+        self.entries
+            .remove(hash)
+            .map(|old| old.terms())
+            .unwrap_or_default()
+    }
+
+    /// Entries for one term's posting, ordered by hash.
+    pub fn entries_for(&self, term: &str) -> Vec<KeywordEntry> {
+        let mut entries: Vec<KeywordEntry> = self
+            .entries
+            .values()
+            .filter(|e| e.terms().contains(term))
+            .cloned()
+            .collect();
+        entries.sort_by(|a, b| a.hash.cmp(&b.hash));
+        entries.truncate(MAX_POSTING_ENTRIES);
+        entries
+    }
+}
+
+static LOCAL_INDEX: Lazy<Mutex<LocalIndex>> = Lazy::new(|| Mutex::new(LocalIndex::default()));
+
+async fn publish_term(
+    dht: &DhtService,
+    term: &str,
+    entries: Vec<KeywordEntry>,
+    peer_id: &str,
+    signer: Option<&(String, String)>,
+) -> Result<(), String> {
+    let key = posting_key(term, peer_id);
+    if entries.is_empty() {
+        let _ = dht.remove_dht_record(key).await;
+        return dht.stop_providing_file(provider_key(term)).await;
+    }
+    let Some((wallet_address, private_key)) = signer else {
+        // Nothing to sign with; the old posting stays until it expires or
+        // the next publish replaces it.
+        return Ok(());
+    };
+    let mut posting = KeywordPosting {
+        term: term.to_string(),
+        peer_id: peer_id.to_string(),
+        wallet_address: wallet_address.clone(),
+        entries,
+        updated_at: rating_storage::now_secs()?,
+        signature: String::new(),
+    };
+    posting.sign(private_key)?;
+    let json = serde_json::to_string(&posting).map_err(|e| e.to_string())?;
+    dht.put_dht_value(key, json).await?;
+    dht.start_providing_file(provider_key(term)).await
+}
+
+async fn publish_terms(
+    dht: &DhtService,
+    updates: Vec<(String, Vec<KeywordEntry>)>,
+    signer: Option<(String, String)>,
+) -> Result<usize, String> {
+    let peer_id = dht
+        .get_peer_id()
+        .await
+        .filter(|p| !p.is_empty())
+        .ok_or("DHT peer ID unavailable")?;
+    let mut failures = Vec::new();
+    for (term, entries) in &updates {
+        if let Err(e) = publish_term(dht, term, entries.clone(), &peer_id, signer.as_ref()).await {
+            failures.push(format!("{}: {}", term, e));
+        }
+    }
+    if failures.is_empty() {
+        Ok(updates.len())
+    } else {
+        Err(format!(
+            "Failed to publish keyword postings ({})",
+            failures.join("; ")
+        ))
+    }
+}
+
+/// Publish (or refresh) keyword postings for a file this node seeds.
+/// Returns how many term postings were written.
+pub async fn publish_keywords(
+    dht: &DhtService,
+    entry: KeywordEntry,
+    wallet_address: &str,
+    private_key: &str,
+) -> Result<usize, String> {
+    if wallet_address.trim().is_empty() || private_key.trim().is_empty() {
+        return Err("Wallet must be unlocked to publish keyword postings".to_string());
+    }
+    let signer = (wallet_address.to_string(), private_key.to_string());
+    let updates = {
+        let mut index = LOCAL_INDEX.lock();
+        index.signer = Some(signer.clone());
+        let affected = index.upsert(entry);
+        affected
+            .into_iter()
+            .map(|term| {
+                let entries = index.entries_for(&term);
+                (term, entries)
+            })
+            .collect::<Vec<_>>()
+    };
+    publish_terms(dht, updates, Some(signer)).await
+}
+
+/// Re-publish a file's postings after its name, description or tags
+/// changed, signing with the wallet of the last publish. Files that aren't
+/// currently indexed are left alone. Returns how many postings were written.
+pub async fn refresh_keywords(dht: &DhtService, entry: KeywordEntry) -> Result<usize, String> {
+    let (updates, signer) = {
+        let mut index = LOCAL_INDEX.lock();
+        if !index.entries.contains_key(&entry.hash) || index.signer.is_none() {
+            return Ok(0);
+        }
+        let affected = index.upsert(entry);
+        let updates = affected
+            .into_iter()
+            .map(|term| {
+                let entries = index.entries_for(&term);
+                (term, entries)
+            })
+            .collect::<Vec<_>>();
+        (updates, index.signer.clone())
+    };
+    publish_terms(dht, updates, signer).await
+}
+
+/// Stop advertising a file under its keywords. Terms still used by other
+/// local files are re-signed without it; terms left empty are withdrawn.
+pub async fn unpublish_keywords(dht: &DhtService, file_hash: &str) -> Result<(), String> {
+    let (updates, signer) = {
+        let mut index = LOCAL_INDEX.lock();
+        let affected = index.remove(file_hash);
+        let updates = affected
+            .into_iter()
+            .map(|term| {
+                let entries = index.entries_for(&term);
+                (term, entries)
+            })
+            .collect::<Vec<_>>();
+        (updates, index.signer.clone())
+    };
+    if updates.is_empty() {
+        return Ok(());
+    }
+    publish_terms(dht, updates, signer).await.map(|_| ())
+}
+
+/// A file matching every term of a query.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeywordSearchResult {
+    pub hash: String,
+    pub file_name: String,
+    pub file_size: u64,
+    pub mime_type: String,
+    pub tags: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Wallets whose signed postings list this file.
+    pub publishers: Vec<String>,
+    pub seeder_count: usize,
+    /// Mean local Elo of the publishers; unknown wallets count as neutral.
+    pub reputation: f64,
+    pub score: f64,
+}
+
+/// Keep verified postings whose entries really contain `term`, and group
+/// them by file hash.
+fn collect_term_matches(
+    term: &str,
+    postings: &[KeywordPosting],
+) -> BTreeMap<String, (KeywordEntry, BTreeSet<String>)> {
+    let mut matches: BTreeMap<String, (KeywordEntry, BTreeSet<String>)> = BTreeMap::new();
+    for posting in postings.iter().filter(|p| p.term == term) {
+        for entry in posting.entries.iter().filter(|e| e.terms().contains(term)) {
+            matches
+                .entry(entry.hash.clone())
+                .or_insert_with(|| (entry.clone(), BTreeSet::new()))
+                .1
+                .insert(posting.wallet_address.to_lowercase());
+        }
+    }
+    matches
+}
+
+/// Files present for every term, with the union of their publishers.
+pub fn intersect_postings(
+    per_term: &[(String, Vec<KeywordPosting>)],
+) -> Vec<(KeywordEntry, BTreeSet<String>)> {
+    let mut iter = per_term.iter();
+    let Some((first_term, first_postings)) = iter.next() else {
+        return Vec::new();
+    };
+    let mut acc = collect_term_matches(first_term, first_postings);
+    for (term, postings) in iter {
+        let next = collect_term_matches(term, postings);
+        acc.retain(|hash, _| next.contains_key(hash));
+        for (hash, (_, publishers)) in acc.iter_mut() {
+            if let Some((_, more)) = next.get(hash) {
+                publishers.extend(more.iter().cloned());
+            }
+        }
+    }
+    acc.into_values().collect()
+}
+
+/// Score and order candidates: seeder count weighted by publisher
+/// reputation relative to the neutral Elo, then by seeders, then by name.
+pub fn rank_results(
+    candidates: Vec<(KeywordEntry, BTreeSet<String>, usize)>,
+    reputation: &(dyn Fn(&str) -> Option<f64> + Sync),
+    limit: usize,
+) -> Vec<KeywordSearchResult> {
+    let mut results: Vec<KeywordSearchResult> = candidates
+        .into_iter()
+        .map(|(entry, publishers, seeders)| {
+            let seeder_count = seeders.max(publishers.len());
+            let reputation = if publishers.is_empty() {
+                BASE_ELO
+            } else {
+                publishers
+                    .iter()
+                    .map(|w| reputation(w).unwrap_or(BASE_ELO))
+                    .sum::<f64>()
+                    / publishers.len() as f64
+            };
+            let score = ((seeder_count as f64 * reputation / BASE_ELO) * 100.0).round() / 100.0;
+            KeywordSearchResult {
+                hash: entry.hash,
+                file_name: entry.file_name,
+                file_size: entry.file_size,
+                mime_type: entry.mime_type,
+                tags: entry.tags,
+                description: entry.description,
+                publishers: publishers.into_iter().collect(),
+                seeder_count,
+                reputation: (reputation * 10.0).round() / 10.0,
+                score,
+            }
+        })
+        .collect();
+    results.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then(b.seeder_count.cmp(&a.seeder_count))
+            .then_with(|| a.file_name.cmp(&b.file_name))
+            .then_with(|| a.hash.cmp(&b.hash))
+    });
+    results.truncate(limit);
+    results
+}
+
+/// Reputation lookup over locally known transfer outcomes. Wallets with no
+/// history return `None` so ranking treats them as neutral.
+pub fn reputation_from_events(
+    events: Vec<ReputationEvent>,
+    now: u64,
+) -> impl Fn(&str) -> Option<f64> {
+    move |wallet: &str| {
+        let snapshot = rating_storage::compute_reputation_for_wallet(&events, wallet, now);
+        (snapshot.transaction_count > 0).then_some(snapshot.elo)
+    }
+}
+
+/// Reputation lookup over the transfer outcomes this node has recorded.
+pub async fn local_reputation() -> Result<impl Fn(&str) -> Option<f64>, String> {
+    let state = rating_storage::RatingState::new(crate::network::data_dir());
+    let events = state.manifest.read().await.events.clone();
+    Ok(reputation_from_events(events, rating_storage::now_secs()?))
+}
+
+/// Result count for a search request: 20 by default, at most 100.
+pub fn clamp_limit(limit: Option<usize>) -> usize {
+    limit
+        .unwrap_or(DEFAULT_SEARCH_LIMIT)
+        .clamp(1, MAX_SEARCH_LIMIT)
+}
+
+/// Fetch and verify every publisher's posting for one term.
+async fn fetch_postings(dht: &DhtService, term: &str) -> Vec<KeywordPosting> {
+    let providers = match tokio::time::timeout(
+        LOOKUP_TIMEOUT,
+        dht.get_file_providers(provider_key(term)),
+    )
+    .await
+    {
+        Ok(Ok(providers)) => providers,
+        _ => Vec::new(),
+    };
+    let lookups = providers
+        .into_iter()
+        .take(MAX_PUBLISHERS_PER_TERM)
+        .map(|peer_id| async move {
+            let raw = tokio::time::timeout(
+                LOOKUP_TIMEOUT,
+                dht.get_dht_value(posting_key(term, &peer_id)),
+            )
+            .await
+            .ok()?
+            .ok()??;
+            let posting: KeywordPosting = serde_json::from_str(&raw).ok()?;
+            if posting.term != term || posting.peer_id != peer_id || !posting.verify() {
+                println!(
+                    "[SEARCH] Dropping keyword posting for '{}' from {}: bad signature or key",
+                    term, peer_id
+                );
+                return None;
+            }
+            Some(posting)
+        });
+    futures::future::join_all(lookups)
+        .await
+        .into_iter()
+        .flatten()
+        .collect()
+}
+
+/// Find files matching every term in `query`, best first.
+pub async fn search_keywords(
+    dht: &DhtService,
+    query: &str,
+    limit: usize,
+    reputation: &(dyn Fn(&str) -> Option<f64> + Sync),
+) -> Result<Vec<KeywordSearchResult>, String> {
+    let terms = query_terms(query);
+    if terms.is_empty() {
+        return Err("Query has no searchable terms (words need at least 2 characters)".to_string());
+    }
+    let per_term = futures::future::join_all(
+        terms
+            .iter()
+            .map(|term| async move { (term.clone(), fetch_postings(dht, term).await) }),
+    )
+    .await;
+
+    let mut matches = intersect_postings(&per_term);
+    matches.sort_by_key(|(_, publishers)| std::cmp::Reverse(publishers.len()));
+    matches.truncate(MAX_RANKED_CANDIDATES);
+
+    let candidates =
+        futures::future::join_all(matches.into_iter().map(|(entry, publishers)| async move {
+            let seeders = match tokio::time::timeout(
+                LOOKUP_TIMEOUT,
+                dht.get_file_providers(entry.hash.clone()),
+            )
+            .await
+            {
+                Ok(Ok(providers)) => providers.len(),
+                _ => 0,
+            };
+            (entry, publishers, seeders)
+        }))
+        .await;
+    Ok(rank_results(candidates, reputation, limit))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_PRIVATE_KEY: &str =
+        "0x0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef";
+
+    fn entry(hash: &str, name: &str) -> KeywordEntry {
+        KeywordEntry::new(hash, name, 1024, &[], None)
+    }
+
+    fn signed_posting(term: &str, peer: &str, entries: Vec<KeywordEntry>) -> KeywordPosting {
+        let mut posting = KeywordPosting {
+            term: term.to_string(),
+            peer_id: peer.to_string(),
+            wallet_address: wallet::address_from_private_key(TEST_PRIVATE_KEY).unwrap(),
+            entries,
+            updated_at: 1_700_000_000,
+            signature: String::new(),
+        };
+        posting.sign(TEST_PRIVATE_KEY).unwrap();
+        posting
+    }
+
+    #[test]
+    fn tokenize_lowercases_and_drops_short_words() {
+        assert_eq!(
+            tokenize("Annual_Report-2024 (Final) v2.PDF"),
+            vec!["annual", "report", "2024", "final", "v2", "pdf"]
+        );
+        assert_eq!(tokenize("a b c"), Vec::<String>::new());
+        assert_eq!(tokenize("Ünïcode Naïve"), vec!["ünïcode", "naïve"]);
+    }
+
+    #[test]
+    fn entry_terms_cover_name_extension_mime_and_tags() {
+        let entry = KeywordEntry::new(
+            "h1",
+            "Holiday Photo.JPG",
+            10,
+            &["Travel".to_string(), " travel ".to_string(), "".to_string()],
+            Some("  Beach in Lisbon  "),
+        );
+        assert_eq!(entry.tags, vec!["travel"]);
+        assert_eq!(entry.description.as_deref(), Some("Beach in Lisbon"));
+        let terms = entry.terms();
+        for term in [
+            "ext:jpg",
+            "mime:image",
+            "mime:image/jpeg",
+            "tag:travel",
+            "holiday",
+            "photo",
+            "travel",
+            "beach",
+            "lisbon",
+        ] {
+            assert!(terms.contains(term), "missing {}", term);
+        }
+    }
+
+    #[test]
+    fn entry_terms_are_capped() {
+        let description = (0..100)
+            .map(|i| format!("word{}", i))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let entry = KeywordEntry::new("h", "a.txt", 1, &[], Some(&description));
+        let terms = entry.terms();
+        assert_eq!(terms.len(), MAX_TERMS_PER_FILE);
+        // Structured terms are kept ahead of description words.
+        assert!(terms.contains("ext:txt"));
+        assert!(terms.contains("mime:text/plain"));
+    }
+
+    #[test]
+    fn description_is_capped_on_a_char_boundary() {
+        let long = "é".repeat(MAX_DESCRIPTION_LEN);
+        let capped = normalize_description(Some(&long)).unwrap();
+        assert!(capped.len() <= MAX_DESCRIPTION_LEN);
+        assert!(normalize_description(Some("   ")).is_none());
+    }
+
+    #[test]
+    fn query_terms_keep_prefixes_and_dedupe() {
+        assert_eq!(
+            query_terms("Report ext:.PDF report mime:Image tag:Work x"),
+            vec!["report", "ext:pdf", "mime:image", "tag:work"]
+        );
+        // A bare prefix is just a word.
+        assert_eq!(query_terms("ext: a"), vec!["ext"]);
+        assert_eq!(query_terms(&"ab ".repeat(20)).len(), 1);
+        let many = (0..20)
+            .map(|i| format!("w{}", i))
+            .collect::<Vec<_>>()
+            .join(" ");
+        assert_eq!(query_terms(&many).len(), MAX_QUERY_TERMS);
+    }
+
+    #[test]
+    fn posting_signature_round_trips_and_detects_tampering() {
+        let posting = signed_posting("report", "peer-a", vec![entry("h1", "report.pdf")]);
+        assert!(posting.verify());
+
+        let json = serde_json::to_string(&posting).unwrap();
+        let parsed: KeywordPosting = serde_json::from_str(&json).unwrap();
+        assert!(parsed.verify());
+
+        let mut renamed = posting.clone();
+        renamed.entries[0].file_name = "malware.exe".to_string();
+        assert!(!renamed.verify());
+
+        let mut moved = posting.clone();
+        moved.peer_id = "peer-b".to_string();
+        assert!(!moved.verify());
+
+        let mut unsigned = posting;
+        unsigned.signature.clear();
+        assert!(!unsigned.verify());
+    }
+
+    #[test]
+    fn local_index_tracks_affected_terms() {
+        let mut index = LocalIndex::default();
+        let affected = index.upsert(entry("h1", "annual report.pdf"));
+        assert!(affected.contains("report"));
+        index.upsert(entry("h2", "weekly report.txt"));
+        assert_eq!(index.entries_for("report").len(), 2);
+
+        // Renaming drops the old words from the affected set's postings.
+        let affected = index.upsert(entry("h1", "budget.pdf"));
+        assert!(affected.contains("annual"));
+        assert!(affected.contains("budget"));
+        assert!(index.entries_for("annual").is_empty());
+        assert_eq!(index.entries_for("report").len(), 1);
+
+        let affected = index.remove("h2");
+        assert!(affected.contains("weekly"));
+        assert!(index.entries_for("report").is_empty());
+        assert!(index.remove("missing").is_empty());
+    }
+
+    #[test]
+    fn intersect_requires_every_term_and_ignores_off_term_entries() {
+        let a = signed_posting(
+            "report",
+            "peer-a",
+            vec![entry("h1", "annual report.pdf"), entry("h2", "report.txt")],
+        );
+        let b = signed_posting(
+            "annual",
+            "peer-b",
+            vec![entry("h1", "annual report.pdf"), entry("h3", "cat.png")],
+        );
+        let matches = intersect_postings(&[
+            ("report".to_string(), vec![a]),
+            ("annual".to_string(), vec![b]),
+        ]);
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].0.hash, "h1");
+        assert_eq!(matches[0].1.len(), 1);
+
+        assert!(intersect_postings(&[]).is_empty());
+    }
+
+    #[test]
+    fn ranking_weights_seeders_by_reputation() {
+        let good = "0xgood".to_string();
+        let bad = "0xbad".to_string();
+        let candidates = vec![
+            (entry("h1", "a.pdf"), BTreeSet::from([bad.clone()]), 4),
+            (entry("h2", "b.pdf"), BTreeSet::from([good.clone()]), 3),
+            (
+                entry("h3", "c.pdf"),
+                BTreeSet::from(["0xnew".to_string()]),
+                0,
+            ),
+        ];
+        let reputation = |w: &str| match w {
+            "0xgood" => Some(100.0),
+            "0xbad" => Some(10.0),
+            _ => None,
+        };
+        let results = rank_results(candidates, &reputation, 10);
+        assert_eq!(
+            results.iter().map(|r| r.hash.as_str()).collect::<Vec<_>>(),
+            vec!["h2", "h3", "h1"]
+        );
+        assert_eq!(results[0].score, 6.0);
+        // A publisher always counts as a seeder even if the provider lookup
+        // came back empty.
+        assert_eq!(results[1].seeder_count, 1);
+        assert_eq!(results[1].reputation, BASE_ELO);
+
+        let limited = rank_results(
+            vec![(entry("h1", "a.pdf"), BTreeSet::new(), 1)],
+            &reputation,
+            0,
+        );
+        assert!(limited.is_empty());
+    }
+
+    #[test]
+    fn clamp_limit_applies_default_and_bounds() {
+        assert_eq!(clamp_limit(None), DEFAULT_SEARCH_LIMIT);
+        assert_eq!(clamp_limit(Some(0)), 1);
+        assert_eq!(clamp_limit(Some(5)), 5);
+        assert_eq!(clamp_limit(Some(10_000)), MAX_SEARCH_LIMIT);
+    }
+
+    #[test]
+    fn reputation_from_events_is_none_for_unknown_wallets() {
+        let lookup = reputation_from_events(Vec::new(), 1_700_000_000);
+        assert_eq!(lookup("0xabc"), None);
+    }
+}
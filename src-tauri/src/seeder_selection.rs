@@ -0,0 +1,693 @@
+//! Ranking of candidate seeders for a download.
+//!
+//! Each seeder gets a combined score in `[0, 1]` from four factors: the
+//! Elo of its wallet, the throughput, latency and reliability measured in
+//! earlier sessions, its price relative to the other candidates, and how
+//! close it is on the network. Seeders whose Elo is below the user's
+//! minimum are left out. Measurements live in a small book under
+//! `<data_dir>/peer-performance.json`, fed by the DHT event loop.
+
+use libp2p::multiaddr::Protocol;
+use libp2p::Multiaddr;
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::rating_storage::BASE_ELO;
+
+pub const REPUTATION_WEIGHT: f64 = 0.4;
+pub const PERFORMANCE_WEIGHT: f64 = 0.3;
+pub const PRICE_WEIGHT: f64 = 0.2;
+pub const PROXIMITY_WEIGHT: f64 = 0.1;
+
+/// Score for a factor nothing is known about.
+const NEUTRAL_SCORE: f64 = 0.5;
+/// Round-trip time that scores 0.5; faster peers approach 1.
+const REFERENCE_LATENCY_MS: f64 = 200.0;
+/// Smoothing for throughput and latency samples across sessions.
+const PERFORMANCE_EWMA_ALPHA: f64 = 0.3;
+/// The book keeps the most recently updated peers up to this many.
+const MAX_TRACKED_PEERS: usize = 1000;
+
+const PERFORMANCE_FILE: &str = "peer-performance.json";
+
+/// What earlier sessions measured about one peer.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PeerPerformance {
+    /// Smoothed chunk throughput in bytes/sec.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub throughput_bps: Option<f64>,
+    /// Smoothed ping round-trip time.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latency_ms: Option<f64>,
+    /// Download sessions the peer served to completion.
+    #[serde(default)]
+    pub completed: u32,
+    /// Download sessions where the peer was dropped after repeated failures.
+    #[serde(default)]
+    pub failed: u32,
+    #[serde(default)]
+    pub updated_at: u64,
+}
+
+impl PeerPerformance {
+    /// Laplace-smoothed share of sessions served to completion.
+    fn reliability(&self) -> f64 {
+        (self.completed as f64 + 1.0) / ((self.completed + self.failed) as f64 + 2.0)
+    }
+}
+
+fn ewma(previous: Option<f64>, sample: f64) -> f64 {
+    match previous {
+        Some(previous) => previous + PERFORMANCE_EWMA_ALPHA * (sample - previous),
+        None => sample,
+    }
+}
+
+/// Per-peer measurements, keyed by peer ID.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct PerformanceBook {
+    #[serde(default)]
+    peers: HashMap<String, PeerPerformance>,
+    #[serde(skip)]
+    path: Option<PathBuf>,
+    /// Bumped per snapshot, so a write can tell it has been overtaken
+    #[serde(skip)]
+    generation: u64,
+}
+
+impl PerformanceBook {
+    /// Load the book at `path`; a missing or unreadable file starts empty.
+    pub fn load(path: PathBuf) -> Self {
+        let mut book: Self = crate::json_file::load_json_or_default(&path);
+        book.path = Some(path);
+        book
+    }
+
+    pub fn get(&self, peer_id: &str) -> Option<PeerPerformance> {
+        self.peers.get(peer_id).cloned()
+    }
+
+    /// Fold in a ping round trip. Kept in memory until the next session is
+    /// recorded, since pings arrive every few seconds.
+    pub fn record_latency(&mut self, peer_id: &str, rtt: Duration, now: u64) {
+        let entry = self.peers.entry(peer_id.to_string()).or_default();
+        entry.latency_ms = Some(ewma(entry.latency_ms, rtt.as_secs_f64() * 1000.0));
+        entry.updated_at = now;
+    }
+
+    /// Record how a download session with `peer_id` went. Save once the
+    /// session's peers are all recorded.
+    pub fn record_session(
+        &mut self,
+        peer_id: &str,
+        throughput_bps: Option<f64>,
+        completed: bool,
+        now: u64,
+    ) {
+        let entry = self.peers.entry(peer_id.to_string()).or_default();
+        if let Some(sample) = throughput_bps.filter(|bps| bps.is_finite() && *bps > 0.0) {
+            entry.throughput_bps = Some(ewma(entry.throughput_bps, sample));
+        }
+        if completed {
+            entry.completed = entry.completed.saturating_add(1);
+        } else {
+            entry.failed = entry.failed.saturating_add(1);
+        }
+        entry.updated_at = now;
+        self.prune();
+    }
+
+    pub fn persist(&self) {
+        if let Err(e) = self.save() {
+            eprintln!("[Downloads] Failed to save peer performance: {}", e);
+        }
+    }
+
+    fn prune(&mut self) {
+        if self.peers.len() <= MAX_TRACKED_PEERS {
+            return;
+        }
+        let mut by_age: Vec<(String, u64)> = self
+            .peers
+            .iter()
+            .map(|(peer, perf)| (peer.clone(), perf.updated_at))
+            .collect();
+        by_age.sort_by_key(|(_, updated_at)| *updated_at);
+        let excess = self.peers.len() - MAX_TRACKED_PEERS;
+        for (peer, _) in by_age.into_iter().take(excess) {
+            self.peers.remove(&peer);
+        }
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        crate::json_file::save_json_atomic(path, self)
+    }
+
+    /// The book's JSON, where it goes and its generation, for writing
+    /// outside the lock.
+    fn snapshot(&mut self) -> Result<Option<(PathBuf, String, u64)>, String> {
+        let Some(path) = self.path.clone() else {
+            return Ok(None);
+        };
+        let json = serde_json::to_string(self).map_err(|e| e.to_string())?;
+        self.generation += 1;
+        Ok(Some((path, json, self.generation)))
+    }
+}
+
+static BOOK: Lazy<Mutex<PerformanceBook>> = Lazy::new(|| {
+    Mutex::new(PerformanceBook::load(
+        crate::network::data_dir().join(PERFORMANCE_FILE),
+    ))
+});
+
+/// Generation of the newest snapshot on disk, so a write that finishes
+/// late never lands over a newer one.
+static WRITTEN: Lazy<Mutex<u64>> = Lazy::new(|| Mutex::new(0));
+
+/// The process-wide performance book.
+pub fn performance() -> &'static Mutex<PerformanceBook> {
+    &BOOK
+}
+
+/// Save the process-wide book from a blocking task, for callers on the
+/// swarm loop. Only serialising the book happens under its lock.
+pub fn persist_in_background() {
+    let (path, json, generation) = match performance().lock().snapshot() {
+        Ok(Some(snapshot)) => snapshot,
+        Ok(None) => return,
+        Err(e) => {
+            eprintln!("[Downloads] Failed to save peer performance: {}", e);
+            return;
+        }
+    };
+    tokio::task::spawn_blocking(move || {
+        let mut written = WRITTEN.lock();
+        if generation <= *written {
+            return;
+        }
+        match crate::json_file::write_json_atomic(&path, &json) {
+            Ok(()) => *written = generation,
+            Err(e) => eprintln!("[Downloads] Failed to save peer performance: {}", e),
+        }
+    });
+}
+
+/// How directly a seeder can be reached, judged from its advertised
+/// addresses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Proximity {
+    /// Loopback or private-network address.
+    Lan,
+    /// Public address that can be dialled directly.
+    Direct,
+    /// Only reachable through a relay circuit.
+    Relayed,
+    /// No addresses advertised.
+    Unknown,
+}
+
+impl Proximity {
+    fn score(self) -> f64 {
+        match self {
+            Proximity::Lan => 1.0,
+            Proximity::Direct => 0.7,
+            Proximity::Relayed => 0.3,
+            Proximity::Unknown => NEUTRAL_SCORE,
+        }
+    }
+}
+
+fn ip_is_local(ip: std::net::IpAddr) -> bool {
+    match ip {
+        std::net::IpAddr::V4(ip) => ip.is_loopback() || ip.is_private() || ip.is_link_local(),
+        std::net::IpAddr::V6(ip) => {
+            let first = ip.segments()[0];
+            ip.is_loopback() || (first & 0xfe00) == 0xfc00 || (first & 0xffc0) == 0xfe80
+        }
+    }
+}
+
+/// Classify a seeder by the closest of its addresses.
+pub fn classify_proximity(multiaddrs: &[String]) -> Proximity {
+    let mut best = Proximity::Unknown;
+    for addr in multiaddrs
+        .iter()
+        .filter_map(|a| a.parse::<Multiaddr>().ok())
+    {
+        let mut relayed = false;
+        let mut local = false;
+        for protocol in addr.iter() {
+            match protocol {
+                Protocol::P2pCircuit => relayed = true,
+                Protocol::Ip4(ip) => local |= ip_is_local(ip.into()),
+                Protocol::Ip6(ip) => local |= ip_is_local(ip.into()),
+                _ => {}
+            }
+        }
+        let proximity = match (relayed, local) {
+            (true, _) => Proximity::Relayed,
+            (false, true) => Proximity::Lan,
+            (false, false) => Proximity::Direct,
+        };
+        if proximity.score() > best.score() || best == Proximity::Unknown {
+            best = proximity;
+        }
+    }
+    best
+}
+
+/// A seeder that could serve a download.
+#[derive(Debug, Clone, Default)]
+pub struct SeederCandidate {
+    pub peer_id: String,
+    pub wallet_address: String,
+    /// Empty when the seeder's signed record hasn't been fetched.
+    pub price_wei: String,
+    pub multiaddrs: Vec<String>,
+    /// Wallet Elo; `None` when the wallet has no record.
+    pub elo: Option<f64>,
+    pub performance: Option<PeerPerformance>,
+}
+
+/// Per-factor scores in `[0, 1]`, before weighting.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SelectionScores {
+    pub reputation: f64,
+    pub performance: f64,
+    pub price: f64,
+    pub proximity: f64,
+}
+
+impl SelectionScores {
+    fn weighted(&self) -> [(&'static str, f64); 4] {
+        [
+            ("reputation", self.reputation * REPUTATION_WEIGHT),
+            ("performance", self.performance * PERFORMANCE_WEIGHT),
+            ("price", self.price * PRICE_WEIGHT),
+            ("proximity", self.proximity * PROXIMITY_WEIGHT),
+        ]
+    }
+
+    fn total(&self) -> f64 {
+        self.weighted().iter().map(|(_, w)| w).sum()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RankedSeeder {
+    pub peer_id: String,
+    pub wallet_address: String,
+    pub price_wei: String,
+    pub elo: f64,
+    pub score: f64,
+    pub scores: SelectionScores,
+    pub proximity: Proximity,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub throughput_bps: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latency_ms: Option<f64>,
+}
+
+/// Why a download went to the seeder it did; kept in the download history.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SeederSelection {
+    pub peer_id: String,
+    pub score: f64,
+    pub elo: f64,
+    pub scores: SelectionScores,
+    /// Factors on which the seeder beat the runner-up, strongest first;
+    /// `onlyCandidate` when there was no competition.
+    pub picked_by: Vec<String>,
+    /// A higher-ranked seeder was passed over, because the user picked
+    /// this one or the better one cost more than the authorised price.
+    pub manual: bool,
+    pub candidates: usize,
+    /// Seeders left out for being below `min_reputation`.
+    pub excluded_below_min: usize,
+    pub min_reputation: f64,
+}
+
+fn parse_price(price_wei: &str) -> Option<u128> {
+    let trimmed = price_wei.trim();
+    if trimmed.is_empty() {
+        return None;
+    }
+    trimmed.parse().ok()
+}
+
+/// Performance factor of the ranking: throughput relative to
+/// `best_throughput`, ping latency and session reliability, averaged.
+pub fn performance_score(perf: Option<&PeerPerformance>, best_throughput: Option<f64>) -> f64 {
+    let Some(perf) = perf else {
+        return NEUTRAL_SCORE;
+    };
+    let throughput = match (perf.throughput_bps, best_throughput) {
+        (Some(bps), Some(best)) if best > 0.0 => (bps / best).clamp(0.0, 1.0),
+        _ => NEUTRAL_SCORE,
+    };
+    let latency = perf
+        .latency_ms
+        .map(|ms| REFERENCE_LATENCY_MS / (REFERENCE_LATENCY_MS + ms.max(0.0)))
+        .unwrap_or(NEUTRAL_SCORE);
+    (throughput + latency + perf.reliability()) / 3.0
+}
+
+/// Rank `candidates` best first, dropping those whose Elo is below
+/// `min_reputation` (wallets without a record count as the base Elo).
+/// Returns the ranking and how many candidates were dropped.
+pub fn rank_seeders(
+    candidates: &[SeederCandidate],
+    min_reputation: f64,
+) -> (Vec<RankedSeeder>, usize) {
+    let eligible: Vec<&SeederCandidate> = candidates
+        .iter()
+        .filter(|c| c.elo.unwrap_or(BASE_ELO) >= min_reputation)
+        .collect();
+    let excluded = candidates.len() - eligible.len();
+
+    let prices: Vec<u128> = eligible
+        .iter()
+        .filter_map(|c| parse_price(&c.price_wei))
+        .collect();
+    let min_price = prices.iter().min().copied();
+    let max_price = prices.iter().max().copied();
+    let best_throughput = eligible
+        .iter()
+        .filter_map(|c| c.performance.as_ref().and_then(|p| p.throughput_bps))
+        .fold(None, |best: Option<f64>, bps| {
+            Some(best.map_or(bps, |b| b.max(bps)))
+        });
+
+    let mut ranked: Vec<RankedSeeder> = eligible
+        .into_iter()
+        .map(|c| {
+            let elo = c.elo.unwrap_or(BASE_ELO);
+            let price = match (parse_price(&c.price_wei), min_price, max_price) {
+                (Some(p), Some(lo), Some(hi)) if hi > lo => {
+                    1.0 - (p - lo) as f64 / (hi - lo) as f64
+                }
+                (Some(_), _, _) => 1.0,
+                (None, _, _) => NEUTRAL_SCORE,
+            };
+            let proximity = classify_proximity(&c.multiaddrs);
+            let scores = SelectionScores {
+                reputation: (elo / 100.0).clamp(0.0, 1.0),
+                performance: performance_score(c.performance.as_ref(), best_throughput),
+                price,
+                proximity: proximity.score(),
+            };
+            RankedSeeder {
+                peer_id: c.peer_id.clone(),
+                wallet_address: c.wallet_address.clone(),
+                price_wei: c.price_wei.clone(),
+                elo,
+                score: scores.total(),
+                scores,
+                proximity,
+                throughput_bps: c.performance.as_ref().and_then(|p| p.throughput_bps),
+                latency_ms: c.performance.as_ref().and_then(|p| p.latency_ms),
+            }
+        })
+        .collect();
+    ranked.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then_with(|| a.peer_id.cmp(&b.peer_id))
+    });
+    (ranked, excluded)
+}
+
+/// Explain picking `ranked[chosen]`: the factors where it beat the best
+/// other candidate, and whether the user overrode a higher-ranked seeder.
+pub fn explain_selection(
+    ranked: &[RankedSeeder],
+    chosen: usize,
+    excluded_below_min: usize,
+    min_reputation: f64,
+) -> Option<SeederSelection> {
+    let seeder = ranked.get(chosen)?;
+    let runner_up = ranked
+        .iter()
+        .enumerate()
+        .find(|(i, _)| *i != chosen)
+        .map(|(_, s)| s);
+    let picked_by = match runner_up {
+        None => vec!["onlyCandidate".to_string()],
+        Some(other) => {
+            let mut margins: Vec<(&str, f64)> = seeder
+                .scores
+                .weighted()
+                .iter()
+                .zip(other.scores.weighted())
+                .map(|((name, mine), (_, theirs))| (*name, mine - theirs))
+                .filter(|(_, margin)| *margin > 1e-9)
+                .collect();
+            margins.sort_by(|a, b| b.1.total_cmp(&a.1));
+            margins
+                .into_iter()
+                .map(|(name, _)| name.to_string())
+                .collect()
+        }
+    };
+    Some(SeederSelection {
+        peer_id: seeder.peer_id.clone(),
+        score: seeder.score,
+        elo: seeder.elo,
+        scores: seeder.scores,
+        picked_by,
+        manual: chosen != 0,
+        candidates: ranked.len(),
+        excluded_below_min,
+        min_reputation,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(peer_id: &str, price_wei: &str, elo: Option<f64>) -> SeederCandidate {
+        SeederCandidate {
+            peer_id: peer_id.to_string(),
+            wallet_address: format!("0x{peer_id}"),
+            price_wei: price_wei.to_string(),
+            elo,
+            ..Default::default()
+        }
+    }
+
+    fn perf(throughput_bps: f64, latency_ms: f64, completed: u32, failed: u32) -> PeerPerformance {
+        PeerPerformance {
+            throughput_bps: Some(throughput_bps),
+            latency_ms: Some(latency_ms),
+            completed,
+            failed,
+            updated_at: 1,
+        }
+    }
+
+    #[test]
+    fn higher_reputation_ranks_first_when_all_else_is_equal() {
+        let (ranked, excluded) = rank_seeders(
+            &[
+                candidate("a", "100", Some(40.0)),
+                candidate("b", "100", Some(80.0)),
+            ],
+            0.0,
+        );
+        assert_eq!(excluded, 0);
+        assert_eq!(ranked[0].peer_id, "b");
+        assert!(ranked[0].score > ranked[1].score);
+    }
+
+    #[test]
+    fn cheaper_seeder_wins_between_equal_reputations() {
+        let (ranked, _) = rank_seeders(
+            &[
+                candidate("a", "300", Some(60.0)),
+                candidate("b", "100", Some(60.0)),
+            ],
+            0.0,
+        );
+        assert_eq!(ranked[0].peer_id, "b");
+        assert_eq!(ranked[0].scores.price, 1.0);
+        assert_eq!(ranked[1].scores.price, 0.0);
+    }
+
+    #[test]
+    fn unknown_price_scores_neutral() {
+        let (ranked, _) = rank_seeders(
+            &[
+                candidate("a", "", Some(50.0)),
+                candidate("b", "5", Some(50.0)),
+            ],
+            0.0,
+        );
+        let a = ranked.iter().find(|s| s.peer_id == "a").unwrap();
+        assert_eq!(a.scores.price, NEUTRAL_SCORE);
+    }
+
+    #[test]
+    fn seeders_below_minimum_reputation_are_excluded() {
+        let (ranked, excluded) = rank_seeders(
+            &[
+                candidate("a", "0", Some(30.0)),
+                candidate("b", "0", None),
+                candidate("c", "0", Some(70.0)),
+            ],
+            55.0,
+        );
+        assert_eq!(excluded, 2);
+        assert_eq!(ranked.len(), 1);
+        assert_eq!(ranked[0].peer_id, "c");
+    }
+
+    #[test]
+    fn unknown_wallet_counts_as_base_elo() {
+        let (ranked, excluded) = rank_seeders(&[candidate("a", "0", None)], BASE_ELO);
+        assert_eq!(excluded, 0);
+        assert_eq!(ranked[0].elo, BASE_ELO);
+    }
+
+    #[test]
+    fn measured_performance_can_outweigh_small_reputation_gap() {
+        let mut fast = candidate("fast", "0", Some(55.0));
+        fast.performance = Some(perf(4_000_000.0, 20.0, 10, 0));
+        let mut slow = candidate("slow", "0", Some(60.0));
+        slow.performance = Some(perf(100_000.0, 900.0, 2, 6));
+        let (ranked, _) = rank_seeders(&[slow, fast], 0.0);
+        assert_eq!(ranked[0].peer_id, "fast");
+        assert!(ranked[0].scores.performance > ranked[1].scores.performance);
+    }
+
+    #[test]
+    fn unmeasured_peer_scores_neutral_performance() {
+        let (ranked, _) = rank_seeders(&[candidate("a", "0", None)], 0.0);
+        assert_eq!(ranked[0].scores.performance, NEUTRAL_SCORE);
+    }
+
+    #[test]
+    fn proximity_prefers_lan_over_direct_over_relayed() {
+        assert_eq!(
+            classify_proximity(&["/ip4/192.168.1.5/tcp/4001".to_string()]),
+            Proximity::Lan
+        );
+        assert_eq!(
+            classify_proximity(&["/ip4/8.8.8.8/tcp/4001".to_string()]),
+            Proximity::Direct
+        );
+        assert_eq!(
+            classify_proximity(&[
+                "/ip4/8.8.8.8/tcp/4001/p2p/12D3KooWDpJ7As7BWAwRMfu1VU2WCqNjvq387JEYKDBj4kx6nXTN/p2p-circuit"
+                    .to_string()
+            ]),
+            Proximity::Relayed
+        );
+        assert_eq!(classify_proximity(&[]), Proximity::Unknown);
+        assert_eq!(
+            classify_proximity(&[
+                "/ip4/8.8.8.8/tcp/4001".to_string(),
+                "/ip6/fe80::1/tcp/4001".to_string(),
+            ]),
+            Proximity::Lan
+        );
+    }
+
+    #[test]
+    fn explain_selection_lists_winning_factors() {
+        let mut a = candidate("a", "100", Some(90.0));
+        a.multiaddrs = vec!["/ip4/10.0.0.2/tcp/4001".to_string()];
+        let b = candidate("b", "50", Some(40.0));
+        let (ranked, excluded) = rank_seeders(&[a, b], 0.0);
+        let selection = explain_selection(&ranked, 0, excluded, 0.0).unwrap();
+        assert_eq!(selection.peer_id, "a");
+        assert!(!selection.manual);
+        assert_eq!(selection.picked_by, vec!["reputation", "proximity"]);
+        assert_eq!(selection.candidates, 2);
+    }
+
+    #[test]
+    fn explain_selection_flags_manual_choice() {
+        let (ranked, _) = rank_seeders(
+            &[
+                candidate("a", "0", Some(90.0)),
+                candidate("b", "0", Some(40.0)),
+            ],
+            0.0,
+        );
+        let selection = explain_selection(&ranked, 1, 0, 0.0).unwrap();
+        assert_eq!(selection.peer_id, "b");
+        assert!(selection.manual);
+        assert!(selection.picked_by.is_empty());
+    }
+
+    #[test]
+    fn explain_selection_with_single_candidate() {
+        let (ranked, _) = rank_seeders(&[candidate("a", "0", None)], 0.0);
+        let selection = explain_selection(&ranked, 0, 0, 0.0).unwrap();
+        assert_eq!(selection.picked_by, vec!["onlyCandidate"]);
+        assert!(explain_selection(&ranked, 1, 0, 0.0).is_none());
+    }
+
+    #[test]
+    fn performance_book_smooths_and_persists() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(PERFORMANCE_FILE);
+        let mut book = PerformanceBook::load(path.clone());
+        book.record_latency("peer", Duration::from_millis(100), 10);
+        book.record_session("peer", Some(1000.0), true, 11);
+        book.record_session("peer", Some(2000.0), false, 12);
+        book.persist();
+
+        let loaded = PerformanceBook::load(path);
+        let perf = loaded.get("peer").unwrap();
+        assert_eq!(perf.completed, 1);
+        assert_eq!(perf.failed, 1);
+        assert_eq!(perf.latency_ms, Some(100.0));
+        assert!((perf.throughput_bps.unwrap() - 1300.0).abs() < 1e-6);
+        assert_eq!(perf.updated_at, 12);
+    }
+
+    #[test]
+    fn performance_book_ignores_bad_throughput_samples() {
+        let mut book = PerformanceBook::default();
+        book.record_session("peer", Some(f64::NAN), true, 1);
+        book.record_session("peer", Some(0.0), true, 2);
+        assert_eq!(book.get("peer").unwrap().throughput_bps, None);
+    }
+
+    #[test]
+    fn performance_book_drops_least_recent_peers() {
+        let mut book = PerformanceBook::default();
+        for i in 0..(MAX_TRACKED_PEERS as u64 + 5) {
+            book.record_session(&format!("peer-{i}"), None, true, i);
+        }
+        assert_eq!(book.peers.len(), MAX_TRACKED_PEERS);
+        assert!(book.get("peer-0").is_none());
+        assert!(book
+            .get(&format!("peer-{}", MAX_TRACKED_PEERS + 4))
+            .is_some());
+    }
+
+    #[test]
+    fn corrupt_performance_file_starts_empty() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(PERFORMANCE_FILE);
+        std::fs::write(&path, "not json").unwrap();
+        let book = PerformanceBook::load(path);
+        assert!(book.get("peer").is_none());
+    }
+}
@@ -62,6 +62,20 @@ pub fn format_wei_as_chi(wei: u128) -> String {
     }
 }
 
+/// Format a wei amount as a CHI string with every significant decimal, so
+/// `wallet::parse_chi_to_wei` gets back exactly `wei`. Use this, not
+/// `format_wei_as_chi`, for amounts that are sent on chain.
+pub fn format_wei_as_chi_exact(wei: u128) -> String {
+    let whole = wei / 1_000_000_000_000_000_000;
+    let frac = wei % 1_000_000_000_000_000_000;
+    if frac == 0 {
+        format!("{}", whole)
+    } else {
+        let frac_str = format!("{:018}", frac);
+        format!("{}.{}", whole, frac_str.trim_end_matches('0'))
+    }
+}
+
 /// Write file data at full speed (no rate limiting).
 /// Emits `download-progress` events during the write.
 pub async fn write_file(
@@ -200,4 +214,14 @@ mod tests {
         assert!(result.starts_with("0."));
         assert!(result.len() > 2);
     }
+
+    #[test]
+    fn test_format_wei_as_chi_exact_round_trips() {
+        for wei in [0, 1, 995, 10_000_000_000_000_000, 1_234_567_890_123_456_789] {
+            let chi = format_wei_as_chi_exact(wei);
+            assert_eq!(crate::wallet::parse_chi_to_wei(&chi), Ok(wei), "{}", chi);
+        }
+        assert_eq!(format_wei_as_chi_exact(1), "0.000000000000000001");
+        assert_eq!(format_wei_as_chi_exact(1_500_000_000_000_000_000), "1.5");
+    }
 }
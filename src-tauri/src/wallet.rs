@@ -3,6 +3,7 @@
 //! All RPC calls go through `crate::rpc_client` (connection-pooled).
 //! Endpoint resolution uses `crate::geth::effective_rpc_endpoint()`.
 
+use crate::chain_index::{IndexQuery, IndexedTx};
 use crate::rpc_client;
 use once_cell::sync::Lazy;
 use rlp::RlpStream;
@@ -91,6 +92,13 @@ pub struct SendTransactionResult {
 #[serde(rename_all = "camelCase")]
 pub struct TransactionHistoryResult {
     pub transactions: Vec<Transaction>,
+    /// Matching transactions across all pages. Without the chain index
+    /// this only counts what the recent-block scan found.
+    #[serde(default)]
+    pub total: usize,
+    /// Highest block the chain index had covered, when it served the query
+    #[serde(default)]
+    pub indexed_through: Option<u64>,
 }
 
 pub struct PaymentResult {
@@ -179,6 +187,24 @@ pub async fn send_transaction(
     to_address: &str,
     amount: &str,
     private_key: &str,
+) -> Result<SendTransactionResult, String> {
+    send_transaction_with_data(endpoints, from_address, to_address, amount, private_key, &[]).await
+}
+
+/// Intrinsic gas of a plain transfer carrying `data` as its input.
+fn transfer_gas_limit(data: &[u8]) -> u64 {
+    21000 + data.iter().map(|b| if *b == 0 { 4 } else { 16 }).sum::<u64>()
+}
+
+/// [`send_transaction`] with `data` as the transaction input, e.g. a memo
+/// the recipient checks on-chain.
+pub async fn send_transaction_with_data(
+    endpoints: &[String],
+    from_address: &str,
+    to_address: &str,
+    amount: &str,
+    private_key: &str,
+    data: &[u8],
 ) -> Result<SendTransactionResult, String> {
     if endpoints.is_empty() {
         return Err("send_transaction: no RPC endpoints configured".to_string());
@@ -225,7 +251,7 @@ pub async fn send_transaction(
         if raw == 0 { 1_000_000_000u64 } else { raw }
     };
 
-    let gas_limit: u64 = 21000;
+    let gas_limit: u64 = transfer_gas_limit(data);
     let chain_id: u64 = crate::geth::chain_id();
     let gas_cost = gas_price as u128 * gas_limit as u128;
     let total_cost = amount_wei.checked_add(gas_cost).ok_or("Amount overflow")?;
@@ -246,14 +272,14 @@ pub async fn send_transaction(
         .map_err(|e| format!("Invalid to address: {}", e))?;
 
     // Sign transaction (EIP-155)
-    let unsigned_tx = encode_unsigned_tx(nonce, gas_price as u128, gas_limit, &to_bytes, amount_wei, &[], chain_id);
+    let unsigned_tx = encode_unsigned_tx(nonce, gas_price as u128, gas_limit, &to_bytes, amount_wei, data, chain_id);
     let tx_hash_bytes = keccak256(&unsigned_tx);
     let message = Message::from_digest_slice(&tx_hash_bytes).map_err(|e| format!("Failed to create message: {}", e))?;
     let (recovery_id, signature) = secp.sign_ecdsa_recoverable(&message, &secret_key).serialize_compact();
     let v = chain_id * 2 + 35 + recovery_id.to_i32() as u64;
     let r = &signature[0..32];
     let s = &signature[32..64];
-    let signed_tx = encode_signed_tx(nonce, gas_price as u128, gas_limit, &to_bytes, amount_wei, &[], v, r, s);
+    let signed_tx = encode_signed_tx(nonce, gas_price as u128, gas_limit, &to_bytes, amount_wei, data, v, r, s);
     let signed_tx_hex = format!("0x{}", hex::encode(&signed_tx));
 
     // Broadcast
@@ -366,6 +392,13 @@ pub async fn request_faucet(address: &str) -> Result<SendTransactionResult, Stri
 /// Public API for dht.rs to send payment transactions.
 pub async fn send_payment(
     from: &str, to: &str, amount_chi: &str, private_key: &str,
+) -> Result<PaymentResult, String> {
+    send_payment_with_data(from, to, amount_chi, private_key, &[]).await
+}
+
+/// [`send_payment`] with `data` as the transaction input.
+pub async fn send_payment_with_data(
+    from: &str, to: &str, amount_chi: &str, private_key: &str, data: &[u8],
 ) -> Result<PaymentResult, String> {
     // Canonical RPC fallback list — see wallet_rpc_endpoints doc.
     // File-payment txs have to be visible to the receiver's geth, which
@@ -374,7 +407,7 @@ pub async fn send_payment(
     // unreachable (e.g. canonical relay's loopback-only bind post the
     // 2026-05 lockdown).
     let endpoints = crate::geth::wallet_rpc_endpoints();
-    let result = send_transaction(&endpoints, from, to, amount_chi, private_key).await?;
+    let result = send_transaction_with_data(&endpoints, from, to, amount_chi, private_key, data).await?;
     Ok(PaymentResult {
         tx_hash: result.hash,
         balance_before: result.balance_before,
@@ -386,11 +419,30 @@ pub async fn send_payment(
 // Transaction history
 // ============================================================================
 
+/// Transactions sent or received by `address`, newest first. Served from
+/// the local chain index once it has caught up with the chain; until then
+/// the most recent blocks are scanned over RPC.
 pub async fn get_transaction_history(
     endpoint: &str,
     address: &str,
     metadata: &HashMap<String, TransactionMeta>,
+    query: &IndexQuery,
 ) -> Result<TransactionHistoryResult, String> {
+    let indexed = {
+        let index = crate::chain_index::global().lock();
+        index
+            .caught_up()
+            .then(|| (index.transactions(address, query), index.head()))
+    };
+    if let Some((page, indexed_through)) = indexed {
+        let page = page.map(|tx| indexed_transaction(tx, address, metadata));
+        return Ok(TransactionHistoryResult {
+            transactions: page.items,
+            total: page.total,
+            indexed_through,
+        });
+    }
+
     let result = rpc_client::call(endpoint, "eth_blockNumber", serde_json::json!([])).await?;
     let latest_block = rpc_hex_u64(&result, "eth_blockNumber")?;
 
@@ -403,6 +455,7 @@ pub async fn get_transaction_history(
     const MAX_DURATION: Duration = Duration::from_secs(4);
 
     let first_block = latest_block.saturating_sub(MAX_BLOCKS - 1);
+    let wanted = query.offset + query.page_size();
     let mut cursor = latest_block;
     let started = std::time::Instant::now();
     let mut batches = 0u64;
@@ -442,6 +495,7 @@ pub async fn get_transaction_history(
                                 "eth_getBlockByNumber block",
                             )?)
                             .map_err(|e| format!("eth_getBlockByNumber block number: {e}"))?;
+                            if !query.matches(block_ts) { continue; }
 
                             for tx in txs {
                                 let from = tx.get("from").and_then(|f| f.as_str()).unwrap_or("").to_lowercase();
@@ -475,7 +529,7 @@ pub async fn get_transaction_history(
                                         file_name: fname, file_hash: fhash, speed_tier: stier,
                                         recipient_label: rlabel, balance_before: bbefore, balance_after: bafter,
                                     });
-                                    if transactions.len() >= 50 { break 'outer; }
+                                    if transactions.len() >= wanted { break 'outer; }
                                 }
                             }
                         }
@@ -489,7 +543,30 @@ pub async fn get_transaction_history(
     }
 
     transactions.sort_by(|a, b| b.block_number.cmp(&a.block_number));
-    Ok(TransactionHistoryResult { transactions })
+    let page = query.page(transactions, |tx| tx.timestamp);
+    Ok(TransactionHistoryResult {
+        transactions: page.items,
+        total: page.total,
+        indexed_through: None,
+    })
+}
+
+fn indexed_transaction(
+    tx: IndexedTx,
+    address: &str,
+    metadata: &HashMap<String, TransactionMeta>,
+) -> Transaction {
+    let (tx_type, description, file_name, file_hash, speed_tier, recipient_label, balance_before, balance_after) =
+        classify_transaction(&tx.hash, &tx.from, &tx.to, address, metadata);
+    let value_wei = tx.value_wei.parse::<u128>().unwrap_or(0);
+    Transaction {
+        hash: tx.hash, from: tx.from, to: tx.to,
+        value: rpc_client::wei_to_chi_string(value_wei), value_wei: tx.value_wei,
+        block_number: tx.block_number, timestamp: tx.timestamp, status: "confirmed".to_string(),
+        gas_used: tx.gas, tx_type, description,
+        file_name, file_hash, speed_tier,
+        recipient_label, balance_before, balance_after,
+    }
 }
 
 // ============================================================================
@@ -758,6 +835,16 @@ pub fn sign_message(private_key_hex: &str, data: &[u8]) -> Result<String, String
     Ok(hex::encode(sig_bytes))
 }
 
+/// Derive the lowercase 0x-prefixed Ethereum address of a private key.
+pub fn address_from_private_key(private_key_hex: &str) -> Result<String, String> {
+    let pk_hex = private_key_hex.trim().trim_start_matches("0x");
+    let pk_bytes = hex::decode(pk_hex).map_err(|e| format!("Invalid key: {}", e))?;
+    let secret = SecretKey::from_slice(&pk_bytes).map_err(|e| format!("Invalid key: {}", e))?;
+    let pubkey = secp256k1::PublicKey::from_secret_key(&Secp256k1::new(), &secret);
+    let addr_hash = keccak256(&pubkey.serialize_uncompressed()[1..]);
+    Ok(format!("0x{}", hex::encode(&addr_hash[12..])))
+}
+
 /// Verify a signature and recover the signer's Ethereum address.
 /// Returns the lowercase 0x-prefixed address if valid, or an error.
 pub fn recover_signer(data: &[u8], signature_hex: &str) -> Result<String, String> {
@@ -815,6 +902,157 @@ pub fn verify_signature(data: &[u8], signature_hex: &str, expected_address: &str
     }
 }
 
+// ============================================================================
+// HD wallet (BIP-39 mnemonic, BIP-32/44 derivation)
+// ============================================================================
+
+/// BIP-44 prefix for CHI accounts; account `i` lives at `{prefix}/i`.
+/// Same coin type as Ethereum, so phrases restore in the desktop app
+/// (ethers `Wallet.fromPhrase`) and in common Ethereum wallets.
+pub const CHI_DERIVATION_PATH_PREFIX: &str = "m/44'/60'/0'/0";
+
+const BIP32_HARDENED: u32 = 0x8000_0000;
+
+/// One account derived from a recovery phrase.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct DerivedAccount {
+    pub index: u32,
+    pub path: String,
+    pub address: String,
+    pub private_key: String,
+}
+
+/// Generate a new English BIP-39 recovery phrase of 12, 15, 18, 21 or 24 words.
+pub fn generate_mnemonic(word_count: usize) -> Result<String, String> {
+    if !(12..=24).contains(&word_count) || !word_count.is_multiple_of(3) {
+        return Err(format!("Unsupported mnemonic length: {} words", word_count));
+    }
+    use rand::RngCore;
+    let mut entropy = vec![0u8; word_count / 3 * 4];
+    rand::rngs::OsRng.fill_bytes(&mut entropy);
+    bip39::Mnemonic::from_entropy(&entropy)
+        .map(|m| m.to_string())
+        .map_err(|e| format!("Failed to build mnemonic: {}", e))
+}
+
+/// Check a recovery phrase against the BIP-39 wordlist and checksum.
+/// Returns the phrase normalized to single-spaced lowercase words.
+pub fn validate_mnemonic(phrase: &str) -> Result<String, String> {
+    let normalized = phrase
+        .split_whitespace()
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join(" ");
+    bip39::Mnemonic::parse_normalized(&normalized)
+        .map(|m| m.to_string())
+        .map_err(|e| format!("Invalid recovery phrase: {}", e))
+}
+
+/// BIP-39 seed for a phrase and optional extension passphrase ("25th word").
+pub fn mnemonic_to_seed(phrase: &str, passphrase: &str) -> Result<[u8; 64], String> {
+    let normalized = validate_mnemonic(phrase)?;
+    let mnemonic = bip39::Mnemonic::parse_normalized(&normalized)
+        .map_err(|e| format!("Invalid recovery phrase: {}", e))?;
+    Ok(mnemonic.to_seed(passphrase))
+}
+
+/// BIP-44 path of CHI account `index`.
+pub fn chi_account_path(index: u32) -> String {
+    format!("{}/{}", CHI_DERIVATION_PATH_PREFIX, index)
+}
+
+fn parse_derivation_path(path: &str) -> Result<Vec<u32>, String> {
+    let mut parts = path.trim().split('/');
+    if parts.next() != Some("m") {
+        return Err(format!("Derivation path must start with m/: {}", path));
+    }
+    parts
+        .map(|part| {
+            let (digits, hardened) =
+                match part.strip_suffix('\'').or_else(|| part.strip_suffix('h')) {
+                    Some(digits) => (digits, true),
+                    None => (part, false),
+                };
+            let index: u32 = digits
+                .parse()
+                .map_err(|_| format!("Invalid derivation path segment: {}", part))?;
+            if index >= BIP32_HARDENED {
+                return Err(format!("Derivation index out of range: {}", part));
+            }
+            Ok(if hardened {
+                index | BIP32_HARDENED
+            } else {
+                index
+            })
+        })
+        .collect()
+}
+
+fn hmac_sha512(key: &[u8], data: &[&[u8]]) -> [u8; 64] {
+    use hmac::{Hmac, Mac};
+    let mut mac = Hmac::<sha2::Sha512>::new_from_slice(key).expect("HMAC accepts any key length");
+    for chunk in data {
+        mac.update(chunk);
+    }
+    mac.finalize().into_bytes().into()
+}
+
+/// BIP-32 private key derivation from a seed along `path` (e.g. `m/44'/60'/0'/0/0`).
+/// Returns the 0x-prefixed hex private key.
+pub fn derive_private_key(seed: &[u8], path: &str) -> Result<String, String> {
+    let indexes = parse_derivation_path(path)?;
+    let master = hmac_sha512(b"Bitcoin seed", &[seed]);
+    let mut key = SecretKey::from_slice(&master[..32])
+        .map_err(|e| format!("Seed yields an invalid master key: {}", e))?;
+    let mut chain_code: [u8; 32] = master[32..].try_into().expect("32-byte half");
+    let secp = Secp256k1::new();
+
+    for index in indexes {
+        let child = if index & BIP32_HARDENED != 0 {
+            hmac_sha512(
+                &chain_code,
+                &[&[0u8], &key.secret_bytes(), &index.to_be_bytes()],
+            )
+        } else {
+            let public = secp256k1::PublicKey::from_secret_key(&secp, &key).serialize();
+            hmac_sha512(&chain_code, &[&public, &index.to_be_bytes()])
+        };
+        // BIP-32 says to skip to the next index in these cases; with
+        // probability ~2^-127 we report it instead.
+        let tweak = secp256k1::Scalar::from_be_bytes(child[..32].try_into().expect("32-byte half"))
+            .map_err(|_| format!("Derivation at index {} is invalid", index))?;
+        key = key
+            .add_tweak(&tweak)
+            .map_err(|_| format!("Derivation at index {} is invalid", index))?;
+        chain_code.copy_from_slice(&child[32..]);
+    }
+    Ok(format!("0x{}", hex::encode(key.secret_bytes())))
+}
+
+/// Derive CHI account `index` (`m/44'/60'/0'/0/index`) from a recovery phrase.
+pub fn derive_account(
+    phrase: &str,
+    passphrase: &str,
+    index: u32,
+) -> Result<DerivedAccount, String> {
+    let seed = mnemonic_to_seed(phrase, passphrase)?;
+    derive_account_from_seed(&seed, index)
+}
+
+/// Like [`derive_account`], for callers deriving several accounts from one seed.
+pub fn derive_account_from_seed(seed: &[u8], index: u32) -> Result<DerivedAccount, String> {
+    let path = chi_account_path(index);
+    let private_key = derive_private_key(seed, &path)?;
+    let address = address_from_private_key(&private_key)?;
+    Ok(DerivedAccount {
+        index,
+        path,
+        address,
+        private_key,
+    })
+}
+
 // ============================================================================
 // On-chain payment verification
 // ============================================================================
@@ -912,6 +1150,22 @@ pub async fn verify_tx_details(
     Ok(from_match && to_match && amount_match)
 }
 
+/// Input data of a transaction, empty for a plain transfer.
+pub async fn tx_input(tx_hash: &str) -> Result<Vec<u8>, String> {
+    let endpoints = crate::geth::wallet_rpc_endpoints();
+    let tx = rpc_client::call_with_fallbacks(
+        &endpoints,
+        "eth_getTransactionByHash",
+        serde_json::json!([tx_hash]),
+    ).await?;
+    if tx.is_null() {
+        return Err("Transaction not found".to_string());
+    }
+    let input = tx.get("input").and_then(|i| i.as_str()).unwrap_or("0x");
+    hex::decode(input.trim_start_matches("0x"))
+        .map_err(|e| format!("eth_getTransactionByHash input: {e}"))
+}
+
 /// Verify a payment transaction on-chain.
 /// Checks that the tx exists, is confirmed, sent the correct amount, and went to the correct recipient.
 pub async fn verify_payment(
@@ -973,6 +1227,12 @@ mod tests {
         paths
     }
 
+    #[test]
+    fn transfer_gas_limit_pays_for_input_data() {
+        assert_eq!(transfer_gas_limit(&[]), 21000);
+        assert_eq!(transfer_gas_limit(&[0, 1, 0xff]), 21000 + 4 + 16 + 16);
+    }
+
     #[test]
     fn load_tx_metadata_missing_file_starts_empty() {
         let dir = tempfile::tempdir().unwrap();
@@ -1157,4 +1417,84 @@ mod tests {
         let recovered = recover_signer(b"", &sig).unwrap();
         assert!(recovered.starts_with("0x"));
     }
+
+    const TEST_MNEMONIC: &str = "test test test test test test test test test test test junk";
+
+    #[test]
+    fn generate_mnemonic_round_trips_through_validation() {
+        for words in [12, 24] {
+            let phrase = generate_mnemonic(words).unwrap();
+            assert_eq!(phrase.split(' ').count(), words);
+            assert_eq!(validate_mnemonic(&phrase).unwrap(), phrase);
+        }
+        assert!(generate_mnemonic(13).is_err());
+        assert!(generate_mnemonic(27).is_err());
+    }
+
+    #[test]
+    fn validate_mnemonic_checks_wordlist_and_checksum() {
+        assert_eq!(
+            validate_mnemonic("  Test test TEST test test test\ttest test test test test junk ")
+                .unwrap(),
+            TEST_MNEMONIC
+        );
+        // Valid words, bad checksum.
+        assert!(validate_mnemonic(&["abandon"; 12].join(" ")).is_err());
+        assert!(validate_mnemonic(
+            "test test test test test test test test test test test notaword"
+        )
+        .is_err());
+        assert!(validate_mnemonic("test test test").is_err());
+    }
+
+    #[test]
+    fn mnemonic_seed_matches_bip39_vector() {
+        let phrase = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let seed = mnemonic_to_seed(phrase, "TREZOR").unwrap();
+        assert_eq!(
+            hex::encode(seed),
+            "c55257c360c07c72029aebc1b53c05ed0362ada38ead3e3e9efa3708e53495531f09a6987599d18264c1e1c92f2cf141630c7a3c4ab7c81b2f001698e7463b04"
+        );
+    }
+
+    #[test]
+    fn derive_private_key_matches_bip32_vector() {
+        let seed = hex::decode("000102030405060708090a0b0c0d0e0f").unwrap();
+        assert_eq!(
+            derive_private_key(&seed, "m").unwrap(),
+            "0xe8f32e723decf4051aefac8e2c93c9c5b214313817cdb01a1494b917c8436b35"
+        );
+        assert_eq!(
+            derive_private_key(&seed, "m/0'").unwrap(),
+            "0xedb2e14f9ee77d26dd93b4ecede8d16ed408ce149b6cd80b0715a2d911a0afea"
+        );
+        assert_eq!(
+            derive_private_key(&seed, "m/0h/1/2'/2/1000000000").unwrap(),
+            "0x471b76e389e528d6de6d816857e012c5455051cad6660850e58372a6c3e6e7c8"
+        );
+        assert!(derive_private_key(&seed, "44'/60'").is_err());
+        assert!(derive_private_key(&seed, "m/x").is_err());
+        assert!(derive_private_key(&seed, "m/2147483648").is_err());
+    }
+
+    #[test]
+    fn derive_account_matches_ethereum_wallets() {
+        let first = derive_account(TEST_MNEMONIC, "", 0).unwrap();
+        assert_eq!(first.path, "m/44'/60'/0'/0/0");
+        assert_eq!(first.address, "0xf39fd6e51aad88f6f4ce6ab8827279cfffb92266");
+        assert_eq!(
+            first.private_key,
+            "0xac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80"
+        );
+
+        let second = derive_account(TEST_MNEMONIC, "", 1).unwrap();
+        assert_eq!(second.path, "m/44'/60'/0'/0/1");
+        assert_eq!(second.address, "0x70997970c51812dc3a010c7d01b50e0d17dc79c8");
+
+        // The extension passphrase selects a different wallet.
+        assert_ne!(
+            derive_account(TEST_MNEMONIC, "extra", 0).unwrap().address,
+            first.address
+        );
+    }
 }
@@ -0,0 +1,114 @@
+/// Settles payment vouchers against a live dev chain.
+///
+/// Runs only when these are set:
+/// - `CHIRAL_DEV_CHAIN_RPC`: JSON-RPC endpoint of a node that mines on its
+///   own, started from the active network's genesis (the chain ID is
+///   checked when payments are verified).
+/// - `CHIRAL_DEV_CHAIN_FUNDED_KEY`: hex private key of an account holding at
+///   least 3 CHI on that chain.
+///
+/// The voucher books live under the data directory, so the test points
+/// `XDG_DATA_HOME` at a temporary directory first.
+use chiral_network::micropayments::{
+    self, PaymentVoucher, ReceivedSettlement, ReceivedVoucherBook, VoucherTerms,
+};
+use chiral_network::wallet;
+
+const DEV_CHAIN_RPC_ENV: &str = "CHIRAL_DEV_CHAIN_RPC";
+const DEV_CHAIN_KEY_ENV: &str = "CHIRAL_DEV_CHAIN_FUNDED_KEY";
+const PAYEE_KEY: &str = "0x8f2a55949038a9610f50fb23b5883af3b4ecb3c3bb792cbcefbd1542c692be63";
+
+fn dev_chain() -> Option<(String, String)> {
+    let rpc = std::env::var(DEV_CHAIN_RPC_ENV).ok()?;
+    let key = std::env::var(DEV_CHAIN_KEY_ENV).ok()?;
+    Some((rpc, key))
+}
+
+fn wallet_for_key(private_key: &str) -> String {
+    let probe = b"derive-test-wallet";
+    let signature = wallet::sign_message(private_key, probe).unwrap();
+    wallet::recover_signer(probe, &signature).unwrap()
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+#[tokio::test]
+async fn vouchers_settle_on_dev_chain() {
+    let Some((rpc, payer_key)) = dev_chain() else {
+        eprintln!(
+            "skipping vouchers_settle_on_dev_chain: set {DEV_CHAIN_RPC_ENV} and {DEV_CHAIN_KEY_ENV}"
+        );
+        return;
+    };
+    let data_home = tempfile::tempdir().unwrap();
+    std::env::set_var("XDG_DATA_HOME", data_home.path());
+    std::env::set_var("CHIRAL_RPC_ENDPOINT", &rpc);
+
+    let payer = wallet_for_key(&payer_key);
+    let payee = wallet_for_key(PAYEE_KEY);
+
+    // One 2 CHI download paid in two vouchers; only the second is settled.
+    let terms = VoucherTerms {
+        session_id: format!("dev-chain-{}", now_secs()),
+        payer: payer.clone(),
+        payee: payee.clone(),
+        file_hash: "dev-chain-file".to_string(),
+        price_wei: "2000000000000000000".to_string(),
+        total_chunks: 32,
+    };
+    let mut received =
+        ReceivedVoucherBook::load(data_home.path().join("seeder-received-vouchers.json"));
+    let mut last: Option<PaymentVoucher> = None;
+    for chunks in [16, 32] {
+        let voucher = micropayments::issued()
+            .lock()
+            .issue(&terms, "seeder-peer", chunks, &payer_key, now_secs())
+            .expect("issue voucher");
+        assert_eq!(received.accept(voucher.clone(), now_secs()), Ok(chunks));
+        last = Some(voucher);
+    }
+    assert_eq!(last.unwrap().cumulative_wei, terms.price_wei);
+    assert_eq!(
+        received.outstanding(&payer, &payee),
+        2_000_000_000_000_000_000
+    );
+
+    let settled = micropayments::settle(&payer, &payer_key, true)
+        .await
+        .expect("settle vouchers");
+    assert_eq!(settled.len(), 1);
+    let settlement = &settled[0];
+    assert_eq!(settlement.amount_wei, terms.price_wei);
+    assert!(wallet::wait_for_tx_mined(&settlement.tx_hash)
+        .await
+        .unwrap());
+    // The seeder's share is the price minus the platform fee (under 1%).
+    let min_seller_wei = 2_000_000_000_000_000_000u128 * 99 / 100;
+    assert!(
+        wallet::verify_payment(&settlement.tx_hash, &payer, &payee, min_seller_wei)
+            .await
+            .unwrap()
+    );
+
+    // Nothing is left to settle, and the seeder credits the tx once.
+    assert!(micropayments::settle(&payer, &payer_key, true)
+        .await
+        .unwrap()
+        .is_empty());
+    let credit = ReceivedSettlement {
+        tx_hash: settlement.tx_hash.clone(),
+        payer: payer.to_lowercase(),
+        payee: payee.to_lowercase(),
+        amount_wei: settlement.amount_wei.clone(),
+        settled_at: now_secs(),
+    };
+    assert!(received.apply_settlement(credit.clone()));
+    assert!(!received.apply_settlement(credit));
+    assert_eq!(received.outstanding(&payer, &payee), 0);
+    assert_eq!(micropayments::issued().lock().unnotified(&payer).len(), 1);
+}
@@ -26,6 +26,15 @@ export interface BootstrapNodeStatus {
   reachable: boolean;
 }
 
+export interface PortMapping {
+  protocol: 'tcp' | 'udp';
+  purpose: 'libp2p' | 'gateway';
+  method: 'upnp' | 'nat-pmp';
+  internalPort: number;
+  externalIp: string;
+  externalPort: number;
+}
+
 export interface DhtHealthInfo {
   running: boolean;
   peerId: string | null;
@@ -35,6 +44,9 @@ export interface DhtHealthInfo {
   bootstrapNodes: BootstrapNodeStatus[];
   sharedFiles: number;
   protocols: string[];
+  natStatus: 'unknown' | 'public' | 'private';
+  publicAddress: string | null;
+  portMappings: PortMapping[];
 }
 
 class DhtService {
@@ -46,6 +46,9 @@ export interface DriveItem {
   starred: boolean;
   storagePath?: string;
   isPublic?: boolean;
+  // Keyword search metadata
+  description?: string;
+  tags?: string[];
   // Seeding metadata
   merkleRoot?: string;
   protocol?: string;
@@ -97,6 +100,8 @@ function convertItem(raw: any): DriveItem {
     starred: raw.starred ?? false,
     storagePath: raw.storage_path ?? raw.storagePath ?? undefined,
     isPublic: raw.is_public ?? raw.isPublic ?? true,
+    description: raw.description ?? undefined,
+    tags: raw.tags ?? [],
     merkleRoot: raw.merkle_root ?? raw.merkleRoot ?? undefined,
     protocol: raw.protocol ?? undefined,
     priceChi: raw.price_chi ?? raw.priceChi ?? undefined,
@@ -220,14 +225,22 @@ export const driveApi = {
     });
   },
 
-  /** Upload a file — in Tauri mode, takes a file path string instead of File object */
-  async uploadFile(fileOrPath: File | string, parentId?: string | null): Promise<DriveItem> {
+  /**
+   * Upload a file — in Tauri mode, takes a file path string instead of File object.
+   * With `encrypt`, the stored copy is encrypted to our own key (Tauri only).
+   */
+  async uploadFile(
+    fileOrPath: File | string,
+    parentId?: string | null,
+    encrypt = false,
+  ): Promise<DriveItem> {
     if (isTauri() && typeof fileOrPath === 'string') {
       const invoke = await getInvoke();
       const item = await invoke('drive_upload_file', {
         owner: currentOwner,
         filePath: fileOrPath,
         parentId: parentId ?? null,
+        encrypt,
       });
       return convertItem(item);
     }
@@ -247,11 +260,18 @@ export const driveApi = {
   /** Update item properties (rename, move, star) */
   async updateItem(
     id: string,
-    updates: { name?: string; parent_id?: string | null; starred?: boolean; price_chi?: string },
+    updates: {
+      name?: string;
+      parent_id?: string | null;
+      starred?: boolean;
+      price_chi?: string;
+      description?: string;
+      tags?: string[];
+    },
   ): Promise<DriveItem> {
     if (isTauri()) {
       const invoke = await getInvoke();
-      const item = await invoke('drive_update_item', {
+      let item = await invoke('drive_update_item', {
         owner: currentOwner,
         itemId: id,
         name: updates.name ?? null,
@@ -259,6 +279,14 @@ export const driveApi = {
         starred: updates.starred ?? null,
         priceChi: updates.price_chi ?? null,
       });
+      if (updates.description !== undefined || updates.tags !== undefined) {
+        item = await invoke('drive_set_item_metadata', {
+          owner: currentOwner,
+          itemId: id,
+          description: updates.description ?? null,
+          tags: updates.tags ?? null,
+        });
+      }
       return convertItem(item);
     }
     return request<DriveItem>(`/api/drive/items/${encodeURIComponent(id)}`, {
@@ -104,18 +104,97 @@ export const encryptionService = {
   },
 
   /**
-   * Send an encrypted file to a peer
+   * Decrypt a received `.encrypted` file on disk using our keypair.
+   * Streamed files are decrypted without loading them into memory.
+   *
+   * @param inputPath - Path of the encrypted file
+   * @param outputPath - Where to write the plaintext (defaults to the input path without `.encrypted`)
+   * @returns Path of the decrypted file
+   */
+  async decryptFileAtPath(inputPath: string, outputPath?: string): Promise<string> {
+    if (!isTauri()) {
+      throw new Error('Decryption not available in web mode');
+    }
+
+    return await invoke<string>('decrypt_encrypted_file', {
+      inputPath,
+      outputPath: outputPath ?? null
+    });
+  },
+
+  /**
+   * Encrypt a file once for several peers. Each peer's key is looked up
+   * from the DHT; our own key is always included so we can manage the
+   * recipients later.
+   *
+   * @param inputPath - Path of the file to encrypt
+   * @param recipientPeerIds - Peers to share with
+   * @param outputPath - Envelope path (defaults to `<inputPath>.encrypted`)
+   * @returns The envelope path and its recipients' public keys
+   */
+  async encryptFileForPeers(
+    inputPath: string,
+    recipientPeerIds: string[],
+    outputPath?: string
+  ): Promise<{ path: string; recipients: string[] }> {
+    if (!isTauri()) {
+      throw new Error('Encryption not available in web mode');
+    }
+
+    return await invoke<{ path: string; recipients: string[] }>('encrypt_file_for_peers', {
+      inputPath,
+      outputPath: outputPath ?? null,
+      recipientPeerIds
+    });
+  },
+
+  /**
+   * Add or remove recipients of an envelope without re-encrypting it
+   *
+   * @param path - Envelope path
+   * @param addPeerIds - Peers to grant access to
+   * @param removePublicKeys - Public keys whose access is removed
+   * @returns Recipients' public keys after the change
+   */
+  async updateEnvelopeRecipients(
+    path: string,
+    addPeerIds: string[] = [],
+    removePublicKeys: string[] = []
+  ): Promise<string[]> {
+    if (!isTauri()) {
+      throw new Error('Encryption not available in web mode');
+    }
+
+    return await invoke<string[]>('update_envelope_recipients', {
+      path,
+      addPeerIds,
+      removePublicKeys
+    });
+  },
+
+  /**
+   * List the public keys that can decrypt an envelope
+   */
+  async getEnvelopeRecipients(path: string): Promise<string[]> {
+    if (!isTauri()) {
+      throw new Error('Encryption not available in web mode');
+    }
+
+    return await invoke<string[]>('get_envelope_recipients', { path });
+  },
+
+  /**
+   * Send an encrypted file to a peer. The file is encrypted from disk in
+   * segments, so it may be larger than available memory.
    *
    * @param peerId - Target peer's libp2p ID
-   * @param fileName - Name of the file
-   * @param fileData - Raw file data
+   * @param filePath - Path of the file to send
    * @param recipientPublicKey - Recipient's encryption public key
    * @param transferId - Unique transfer ID
    */
   async sendEncryptedFile(
     peerId: string,
-    fileName: string,
-    fileData: Uint8Array,
+    filePath: string,
     recipientPublicKey: string,
     transferId: string
   ): Promise<void> {
@@ -125,8 +204,7 @@ export const encryptionService = {
 
     await invoke('send_encrypted_file', {
       peerId,
-      fileName,
-      fileData: Array.from(fileData),
+      filePath,
       recipientPublicKey,
       transferId
     });
@@ -52,9 +52,25 @@ export interface ReputationResponse {
   failedCount: number;
   transactionCount: number;
   totalEarnedWei: string;
+  breakdown?: ReputationBreakdown;
   events: ReputationEvent[];
 }
 
+/** What went into a score: mean weight per factor and what was left out. */
+export interface ReputationBreakdown {
+  timeWeight: number;
+  amountWeight: number;
+  counterpartyWeight: number;
+  walletAgeWeight: number;
+  counterparties: number;
+  cappedCounterparties: number;
+  unverifiedPayments: number;
+  reusedPayments: number;
+  cappedUnpaidEvents: number;
+  eloGained: number;
+  eloLost: number;
+}
+
 export interface BatchReputationEntry {
   elo: number;
   completedCount: number;
@@ -69,6 +85,7 @@ interface ReputationVerdictProof {
   ownerSignature: string;
   updatedAt: number;
   verdictSignature: string;
+  issuedAt: number;
 }
 
 function asNumber(value: unknown, fallback = 0): number {
@@ -96,6 +113,38 @@ function normalizeEvent(event: any): ReputationEvent {
   };
 }
 
+function normalizeBreakdown(raw: any): ReputationBreakdown | undefined {
+  if (!raw || typeof raw !== 'object') return undefined;
+  return {
+    timeWeight: asNumber(raw.timeWeight),
+    amountWeight: asNumber(raw.amountWeight),
+    counterpartyWeight: asNumber(raw.counterpartyWeight),
+    walletAgeWeight: asNumber(raw.walletAgeWeight),
+    counterparties: asNumber(raw.counterparties),
+    cappedCounterparties: asNumber(raw.cappedCounterparties),
+    unverifiedPayments: asNumber(raw.unverifiedPayments),
+    reusedPayments: asNumber(raw.reusedPayments),
+    cappedUnpaidEvents: asNumber(raw.cappedUnpaidEvents),
+    eloGained: asNumber(raw.eloGained),
+    eloLost: asNumber(raw.eloLost),
+  };
+}
+
+function normalizeReputation(raw: any, wallet: string): ReputationResponse {
+  const rawEvents = Array.isArray(raw?.events) ? raw.events : [];
+  return {
+    wallet: asString(raw?.wallet, wallet),
+    elo: asNumber(raw?.elo, 50),
+    baseElo: asNumber(raw?.baseElo ?? raw?.base_elo, 50),
+    completedCount: asNumber(raw?.completedCount ?? raw?.completed_count, 0),
+    failedCount: asNumber(raw?.failedCount ?? raw?.failed_count, 0),
+    transactionCount: asNumber(raw?.transactionCount ?? raw?.transaction_count, 0),
+    totalEarnedWei: asString(raw?.totalEarnedWei ?? raw?.total_earned_wei, '0'),
+    breakdown: normalizeBreakdown(raw?.breakdown),
+    events: rawEvents.map(normalizeEvent),
+  };
+}
+
 async function request<T>(path: string, init?: RequestInit): Promise<T> {
   const method = (init?.method || 'GET').toUpperCase();
   // Authenticated routes (e.g. POST /api/ratings/transfer) require the
@@ -167,6 +216,46 @@ async function computeReputationVerdictProof(
   });
 }
 
+/** Gossip the signed verdict through this node's DHT so every peer can
+ *  recompute the seeder's Elo without trusting one rating server. */
+async function gossipVerdict(
+  proof: ReputationVerdictProof,
+  transferId: string,
+  seederWallet: string,
+  fileHash: string,
+  outcome: TransferOutcome,
+  amountWei: string,
+  txHash?: string,
+): Promise<void> {
+  try {
+    const invoke = await getInvoke();
+    await invoke('publish_reputation_verdict', {
+      verdict: {
+        transferId,
+        seederWallet,
+        downloaderWallet: proof.issuerWallet,
+        fileHash,
+        amountWei: amountWei.trim() || '0',
+        outcome,
+        txHash: txHash || undefined,
+        issuedAt: proof.issuedAt,
+        issuerWallet: proof.issuerWallet,
+        signature: proof.verdictSignature,
+      },
+      issuerKey: {
+        issuerWallet: proof.issuerWallet,
+        verifyingKey: proof.verifyingKey,
+        ownerSignature: proof.ownerSignature,
+        updatedAt: proof.updatedAt,
+      },
+    });
+  } catch (err) {
+    // The rating server still caches the verdict; DHT gossip resumes once
+    // the node is online.
+    console.warn('Failed to gossip reputation verdict:', err);
+  }
+}
+
 async function publishIssuerKey(proof: ReputationVerdictProof): Promise<void> {
   await request('/api/ratings/issuer-key', {
     method: 'POST',
@@ -197,6 +286,7 @@ export const ratingApi = {
       amountWei,
       txHash,
     );
+    await gossipVerdict(proof, transferId, seederWallet, fileHash, outcome, amountWei, txHash);
     await publishIssuerKey(proof);
     return request<ReputationEvent>('/api/ratings/transfer', {
       method: 'POST',
@@ -210,24 +300,26 @@ export const ratingApi = {
         txHash: txHash || null,
         issuerWallet: proof.issuerWallet,
         verdictSignature: proof.verdictSignature,
+        issuedAt: proof.issuedAt,
       }),
     });
   },
 
-  /** Get Elo reputation summary for a wallet. */
+  /** Get Elo reputation summary for a wallet. In the desktop app it is
+   *  recomputed locally from verdicts gossiped in the DHT; the rating
+   *  server is only asked when that fails. */
   async getReputation(wallet: string): Promise<ReputationResponse> {
+    if (isTauri()) {
+      try {
+        const invoke = await getInvoke();
+        const local = await invoke('get_wallet_reputation', { wallet });
+        return normalizeReputation(local, wallet);
+      } catch (err) {
+        console.warn('Local reputation lookup failed, asking rating server:', err);
+      }
+    }
     const raw = await request<any>(`/api/ratings/${encodeURIComponent(wallet)}`);
-    const rawEvents = Array.isArray(raw?.events) ? raw.events : [];
-    return {
-      wallet: asString(raw?.wallet, wallet),
-      elo: asNumber(raw?.elo, 50),
-      baseElo: asNumber(raw?.baseElo ?? raw?.base_elo, 50),
-      completedCount: asNumber(raw?.completedCount ?? raw?.completed_count, 0),
-      failedCount: asNumber(raw?.failedCount ?? raw?.failed_count, 0),
-      transactionCount: asNumber(raw?.transactionCount ?? raw?.transaction_count, 0),
-      totalEarnedWei: asString(raw?.totalEarnedWei ?? raw?.total_earned_wei, '0'),
-      events: rawEvents.map(normalizeEvent),
-    };
+    return normalizeReputation(raw, wallet);
   },
 
   /** Batch fetch Elo reputations for multiple wallets. */
@@ -53,6 +53,8 @@ export interface AppSettings {
   reducedMotion: boolean;
   autoStartMining: boolean;
   downloadDirectory: string; // empty string = system default Downloads folder
+  minSeederReputation: number; // 0-100; seeders below this Elo are skipped
+  payWithVouchers: boolean; // pay paid downloads per chunk with signed vouchers
   notifications: NotificationSettings;
   hostingConfig: HostingConfig;
 }
@@ -75,6 +77,8 @@ const defaultSettings: AppSettings = {
   reducedMotion: false,
   autoStartMining: false,
   downloadDirectory: '',
+  minSeederReputation: 0,
+  payWithVouchers: false,
   notifications: { ...defaultNotifications },
   hostingConfig: {
     enabled: false,
@@ -115,6 +119,10 @@ function normalizeSettings(raw: unknown): AppSettings {
       ...defaultNotifications,
       ...(parsed.notifications || {}),
     },
+    minSeederReputation: Number.isFinite(parsed.minSeederReputation as number)
+      ? Math.min(100, Math.max(0, Number(parsed.minSeederReputation)))
+      : defaultSettings.minSeederReputation,
+    payWithVouchers: !!parsed.payWithVouchers,
     hostingConfig: normalizeHostingConfig(parsed.hostingConfig),
   };
 }
@@ -18,17 +18,21 @@ export const BASE_ELO = 50;
 /// Sort seeders by the chosen mode. Returns a new array — does not mutate
 /// the input.
 ///
-/// - `best`: Elo desc, ties broken by cheaper `priceWei`. Matches what a user
-///   asking "higher reputation for the lowest cost" would expect.
+/// - `best`: backend selection score desc (reputation, past performance, price
+///   and proximity, from `rank_download_seeders`) when `getScore` knows the
+///   seeder, then Elo desc, ties broken by cheaper `priceWei`. Seeders without
+///   a score sort after the scored ones.
 /// - `elo`:  Elo desc only (price-blind).
 /// - `price`: `priceWei` asc, ties broken by higher Elo.
 export function sortSeeders<T extends SortableSeeder>(
   list: T[],
   mode: SeederSort,
   getElo: (seeder: T) => number,
+  getScore?: (seeder: T) => number | undefined,
 ): T[] {
   const decorated = list.map((s) => ({
     s,
+    score: scoreOrFloor(getScore?.(s)),
     elo: getElo(s),
     price: parsePriceWei(s.priceWei),
   }));
@@ -46,6 +50,7 @@ export function sortSeeders<T extends SortableSeeder>(
     default:
       decorated.sort(
         (a, b) =>
+          b.score - a.score ||
           b.elo - a.elo ||
           (a.price < b.price ? -1 : a.price > b.price ? 1 : 0),
       );
@@ -53,6 +58,11 @@ export function sortSeeders<T extends SortableSeeder>(
   return decorated.map((d) => d.s);
 }
 
+/// Selection scores live in [0, 1]; anything unscored sorts below them.
+function scoreOrFloor(score: number | undefined): number {
+  return typeof score === 'number' && Number.isFinite(score) ? score : -1;
+}
+
 function parsePriceWei(raw: string | undefined): bigint {
   if (!raw) return 0n;
   try {
@@ -211,6 +211,11 @@ describe('DhtService', () => {
         bootstrapNodes: [{ address: '/ip4/130.245.173.73/tcp/4001/p2p/12D3KooWRN', reachable: true }],
         sharedFiles: 10,
         protocols: ['/chiral/file-request/2.0.0'],
+        natStatus: 'private',
+        publicAddress: null,
+        portMappings: [
+          { protocol: 'tcp', purpose: 'libp2p', method: 'upnp', internalPort: 4001, externalIp: '198.51.100.7', externalPort: 4001 },
+        ],
       };
       mockInvoke.mockResolvedValueOnce(mockHealth);
 
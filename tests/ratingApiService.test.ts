@@ -19,8 +19,12 @@ function mockReputationProof() {
         ownerSignature: 'owner-signature',
         updatedAt: 1700000000,
         verdictSignature: 'verdict-signature',
+        issuedAt: 1700000000,
       };
     }
+    if (cmd === 'publish_reputation_verdict') {
+      return 1;
+    }
     if (cmd === 'compute_owner_proof') {
       return {
         timestamp: 1700000000,
@@ -140,6 +144,69 @@ describe('ratingApiService', () => {
       expect(body.txHash).toBe('0xtxhash');
       expect(body.issuerWallet).toBe('0xowner');
       expect(body.verdictSignature).toBe('verdict-signature');
+      expect(body.issuedAt).toBe(1700000000);
+    });
+
+    it('should gossip the signed verdict through the DHT', async () => {
+      const { ratingApi, setRatingOwner } = await import('$lib/services/ratingApiService');
+      enableTauri();
+      mockReputationProof();
+      setRatingOwner('0xowner', '0xprivate');
+
+      mockFetch.mockResolvedValue({
+        ok: true,
+        headers: new Headers({ 'content-type': 'application/json' }),
+        json: async () => ({ id: 'evt-1' }),
+      });
+
+      await ratingApi.recordTransferOutcome(
+        't-1', '0xseeder', 'abc123', 'completed', '1000', '0xtxhash'
+      );
+
+      expect(mockInvoke).toHaveBeenCalledWith('publish_reputation_verdict', {
+        verdict: {
+          transferId: 't-1',
+          seederWallet: '0xseeder',
+          downloaderWallet: '0xowner',
+          fileHash: 'abc123',
+          amountWei: '1000',
+          outcome: 'completed',
+          txHash: '0xtxhash',
+          issuedAt: 1700000000,
+          issuerWallet: '0xowner',
+          signature: 'verdict-signature',
+        },
+        issuerKey: {
+          issuerWallet: '0xowner',
+          verifyingKey: 'issuer-key',
+          ownerSignature: 'owner-signature',
+          updatedAt: 1700000000,
+        },
+      });
+    });
+
+    it('should still submit to the rating server when gossip fails', async () => {
+      const { ratingApi, setRatingOwner } = await import('$lib/services/ratingApiService');
+      enableTauri();
+      mockReputationProof();
+      const proofImpl = mockInvoke.getMockImplementation()!;
+      mockInvoke.mockImplementation(async (cmd: string, args?: any) => {
+        if (cmd === 'publish_reputation_verdict') {
+          throw new Error('DHT not running');
+        }
+        return proofImpl(cmd, args);
+      });
+      vi.spyOn(console, 'warn').mockImplementation(() => {});
+      setRatingOwner('0xowner', '0xprivate');
+
+      mockFetch.mockResolvedValue({
+        ok: true,
+        headers: new Headers({ 'content-type': 'application/json' }),
+        json: async () => ({ id: 'evt-1' }),
+      });
+
+      await ratingApi.recordTransferOutcome('t-1', '0xseeder', 'abc', 'failed');
+      expect(mockFetch.mock.calls[1][0]).toContain('/api/ratings/transfer');
     });
 
     it('should send null txHash when not provided', async () => {
@@ -192,6 +259,65 @@ describe('ratingApiService', () => {
   });
 
   describe('getReputation', () => {
+    it('should recompute locally from gossiped verdicts in the desktop app', async () => {
+      const { ratingApi } = await import('$lib/services/ratingApiService');
+      enableTauri();
+      mockInvoke.mockImplementation(async (cmd: string) => {
+        if (cmd === 'get_wallet_reputation') {
+          return {
+            wallet: '0xwallet',
+            elo: 61.2,
+            baseElo: 50,
+            completedCount: 3,
+            failedCount: 0,
+            transactionCount: 3,
+            totalEarnedWei: '3000',
+            breakdown: {
+              timeWeight: 0.98,
+              amountWeight: 1.2,
+              counterpartyWeight: 1.1,
+              walletAgeWeight: 0.4,
+              counterparties: 2,
+              cappedCounterparties: 1,
+              unverifiedPayments: 1,
+              reusedPayments: 0,
+              eloGained: 11.2,
+              eloLost: 0,
+            },
+            events: [],
+          };
+        }
+        throw new Error(`unexpected invoke: ${cmd}`);
+      });
+
+      const result = await ratingApi.getReputation('0xwallet');
+
+      expect(mockInvoke).toHaveBeenCalledWith('get_wallet_reputation', { wallet: '0xwallet' });
+      expect(mockFetch).not.toHaveBeenCalled();
+      expect(result.elo).toBe(61.2);
+      expect(result.completedCount).toBe(3);
+      expect(result.breakdown?.walletAgeWeight).toBe(0.4);
+      expect(result.breakdown?.cappedCounterparties).toBe(1);
+      expect(result.breakdown?.unverifiedPayments).toBe(1);
+    });
+
+    it('should fall back to the rating server when the local lookup fails', async () => {
+      const { ratingApi } = await import('$lib/services/ratingApiService');
+      enableTauri();
+      mockInvoke.mockRejectedValue(new Error('DHT not running'));
+      vi.spyOn(console, 'warn').mockImplementation(() => {});
+
+      mockFetch.mockResolvedValueOnce({
+        ok: true,
+        headers: new Headers({ 'content-type': 'application/json' }),
+        json: async () => ({ wallet: '0xwallet', elo: 55, events: [] }),
+      });
+
+      const result = await ratingApi.getReputation('0xwallet');
+      expect(mockFetch.mock.calls[0][0]).toContain('/api/ratings/0xwallet');
+      expect(result.elo).toBe(55);
+    });
+
     it('should GET reputation and normalize response', async () => {
       const { ratingApi } = await import('$lib/services/ratingApiService');
 
@@ -248,6 +374,7 @@ describe('ratingApiService', () => {
       const result = await ratingApi.getReputation('0xwallet');
       expect(result.elo).toBe(50);
       expect(result.completedCount).toBe(0);
+      expect(result.breakdown).toBeUndefined();
       expect(result.events).toHaveLength(0);
     });
 
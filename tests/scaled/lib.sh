@@ -14,6 +14,12 @@ RESULTS_FILE="${RESULTS_FILE:-/results/results.jsonl}"
 LAST_STATUS=0
 TIMER_START=0
 
+# Daemon control-API token (nodes get the same value via compose)
+AUTH_ARGS=()
+if [ -n "${CHIRAL_DAEMON_TOKEN:-}" ]; then
+    AUTH_ARGS=(-H "Authorization: Bearer ${CHIRAL_DAEMON_TOKEN}")
+fi
+
 # Ensure results directory exists
 mkdir -p "$(dirname "$RESULTS_FILE")"
 
@@ -54,7 +60,8 @@ api_get() {
     local tmp
     tmp=$(mktemp)
     local body
-    body=$(curl -s -o "$tmp" -w '%{http_code}' --max-time 10 "$url" 2>/dev/null) || true
+    body=$(curl -s -o "$tmp" -w '%{http_code}' --max-time 10 \
+        ${AUTH_ARGS[@]+"${AUTH_ARGS[@]}"} "$url" 2>/dev/null) || true
     LAST_STATUS="$body"
     cat "$tmp"
     rm -f "$tmp"
@@ -67,6 +74,7 @@ api_post() {
     tmp=$(mktemp)
     local status
     status=$(curl -s -o "$tmp" -w '%{http_code}' --max-time 30 \
+        ${AUTH_ARGS[@]+"${AUTH_ARGS[@]}"} \
         -X POST -H 'Content-Type: application/json' -d "$body" "$url" 2>/dev/null) || true
     LAST_STATUS="$status"
     cat "$tmp"
@@ -82,6 +82,30 @@ describe('sortSeeders', () => {
     expect(sortSeeders([a], 'best', mkGetElo({ A: 50 })).map((s) => s.peerId)).toEqual(['A']);
   });
 
+  it('best: selection score outranks Elo when provided', () => {
+    // A has the best Elo, but B performed better and is cheaper overall.
+    const elo = mkGetElo({ A: 90, B: 60, C: 70 });
+    const scores: Record<string, number> = { A: 0.55, B: 0.8, C: 0.6 };
+    expect(
+      sortSeeders([a, b, c], 'best', elo, (s) => scores[s.peerId]).map((s) => s.peerId),
+    ).toEqual(['B', 'C', 'A']);
+  });
+
+  it('best: unscored seeders sort after scored ones, then by Elo', () => {
+    const elo = mkGetElo({ A: 90, B: 60, C: 99 });
+    const scores: Record<string, number> = { B: 0.2 };
+    expect(
+      sortSeeders([a, b, c], 'best', elo, (s) => scores[s.peerId]).map((s) => s.peerId),
+    ).toEqual(['B', 'C', 'A']);
+  });
+
+  it('elo and price modes ignore the selection score', () => {
+    const elo = mkGetElo({ A: 70, B: 90, C: 80 });
+    const score = (s: S) => (s.peerId === 'A' ? 1 : 0);
+    expect(sortSeeders([a, b, c], 'elo', elo, score).map((s) => s.peerId)).toEqual(['B', 'C', 'A']);
+    expect(sortSeeders([a, b, c], 'price', elo, score).map((s) => s.peerId)).toEqual(['B', 'A', 'C']);
+  });
+
   it('falls back to BASE_ELO when getElo returns undefined-adjacent values', () => {
     // Two seeders, no Elo data → both get BASE_ELO, price decides.
     expect(sortSeeders([a, b], 'best', () => BASE_ELO).map((s) => s.peerId)).toEqual(['B', 'A']);